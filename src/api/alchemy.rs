@@ -1,32 +1,44 @@
-use reqwest::{header::CONTENT_TYPE, Client};
+use super::{
+    providers::{self, Provider},
+    usage,
+};
+use reqwest::header::CONTENT_TYPE;
 use serde::{de, Deserialize, Serialize};
-use serde_json;
+use serde_json::{self, json, Value};
 use std::env;
 
-impl<T: de::DeserializeOwned> AlchemyAPI<T> {
-    async fn send_request(payload: AlchemyPayload) -> Result<AlchemyAPI<T>, reqwest::Error> {
-        let response = Client::new()
+async fn post<R: de::DeserializeOwned>(payload: AlchemyPayload) -> Result<R, reqwest::Error> {
+    usage::record(Provider::Alchemy).await;
+    // json-rpc has a single path, the endpoints are the methods
+    let response = providers::send(
+        Provider::Alchemy,
+        &payload.method,
+        providers::client()
             .post(format!(
-                "https://eth-mainnet.g.alchemy.com/v2/{}",
+                "{}/{}",
+                Provider::Alchemy.base_url(),
                 env::var("ALCHEMY_API").expect("ETH_ADDRESS env var is not set")
             ))
+            .headers(Provider::Alchemy.headers())
             .header(CONTENT_TYPE, "applciation/json")
-            .body(serde_json::to_string(&payload).unwrap())
-            .send()
-            .await
-            .expect("failed response")
-            .json()
-            .await?;
+            .body(serde_json::to_string(&payload).unwrap()),
+    )
+    .await
+    .expect("failed response")
+    .json()
+    .await?;
+
+    Ok(response)
+}
 
-        Ok(response)
+impl<T: de::DeserializeOwned> AlchemyAPI<T> {
+    async fn send_request(payload: AlchemyPayload) -> Result<AlchemyAPI<T>, reqwest::Error> {
+        post(payload).await
     }
 
-    pub async fn get_eth_balance() -> Result<AlchemyAPI<String>, reqwest::Error> {
+    pub async fn get_eth_balance(address: String) -> Result<AlchemyAPI<String>, reqwest::Error> {
         let payload: AlchemyPayload = AlchemyPayload {
-            params: Some(vec![
-                String::from(env::var("ETH_ADDRESS").expect("ETH_ADDRESS env var is not set")),
-                String::from("latest"),
-            ]),
+            params: Some(vec![json!(address), json!("latest")]),
             method: String::from("eth_getBalance"),
             ..AlchemyPayload::default()
         };
@@ -42,6 +54,153 @@ impl<T: de::DeserializeOwned> AlchemyAPI<T> {
 
         AlchemyAPI::send_request(payload).await
     }
+
+    pub async fn get_block_number() -> Result<AlchemyAPI<String>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            method: String::from("eth_blockNumber"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    pub async fn get_transaction_by_hash(
+        hash: String,
+    ) -> Result<AlchemyAPI<Option<AlchemyTransaction>>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![json!(hash)]),
+            method: String::from("eth_getTransactionByHash"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    // the signed transaction as it was broadcast, Flashbots bundles carry other senders' this way
+    pub async fn get_raw_transaction_by_hash(
+        hash: String,
+    ) -> Result<AlchemyAPI<Option<String>>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![json!(hash)]),
+            method: String::from("eth_getRawTransactionByHash"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    pub async fn get_transaction_count(
+        address: String,
+        block_tag: String,
+    ) -> Result<AlchemyAPI<String>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![json!(address), json!(block_tag)]),
+            method: String::from("eth_getTransactionCount"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    pub async fn get_transaction_receipt(
+        hash: String,
+    ) -> Result<AlchemyAPI<Option<AlchemyTransactionReceipt>>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![json!(hash)]),
+            method: String::from("eth_getTransactionReceipt"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    pub async fn get_block_by_number(
+        block_number: String,
+    ) -> Result<AlchemyAPI<AlchemyBlock>, reqwest::Error> {
+        // 'true' returns full transaction objects instead of only hashes
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![json!(block_number), json!(true)]),
+            method: String::from("eth_getBlockByNumber"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    pub async fn get_code(address: String) -> Result<AlchemyAPI<String>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![json!(address), json!("latest")]),
+            method: String::from("eth_getCode"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    pub async fn get_storage_at(
+        address: String,
+        slot: String,
+    ) -> Result<AlchemyAPI<String>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![json!(address), json!(slot), json!("latest")]),
+            method: String::from("eth_getStorageAt"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    // reverts come back as an rpc error without a result, which fails to deserialize
+    pub async fn estimate_gas(
+        from: String,
+        to: String,
+        data: String,
+        value: u128,
+    ) -> Result<AlchemyAPI<String>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![json!({
+                "from": from,
+                "to": to,
+                "data": data,
+                "value": format!("{:#x}", value)
+            })]),
+            method: String::from("eth_estimateGas"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    // eth_simulateV1 runs the calls one after another in a single block on top of the latest one,
+    // the state overrides e.g. fund the sender
+    pub async fn simulate_calls(
+        state_overrides: Value,
+        calls: Vec<Value>,
+    ) -> Result<AlchemyAPI<Vec<AlchemySimulatedBlock>>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![
+                json!({
+                    "blockStateCalls": [{ "stateOverrides": state_overrides, "calls": calls }],
+                    "validation": false
+                }),
+                json!("latest"),
+            ]),
+            method: String::from("eth_simulateV1"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
+
+    pub async fn call(to: String, data: String) -> Result<AlchemyAPI<String>, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![json!({ "to": to, "data": data }), json!("latest")]),
+            method: String::from("eth_call"),
+            ..AlchemyPayload::default()
+        };
+
+        AlchemyAPI::send_request(payload).await
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -51,6 +210,47 @@ pub struct AlchemyAPI<T> {
     pub result: T,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AlchemyError {
+    pub code: i64,
+    pub message: String,
+    // the abi encoded revert data, e.g. a custom error's selector
+    pub data: Option<Value>,
+}
+
+// the raw rpc response, for calls whose error is the interesting part
+#[derive(Debug, Deserialize)]
+pub struct AlchemyCallResponse {
+    pub result: Option<String>,
+    pub error: Option<AlchemyError>,
+}
+
+impl AlchemyCallResponse {
+    // eth_call from the sender against the latest block
+    pub async fn simulate(
+        from: String,
+        to: String,
+        data: String,
+        value: u128,
+    ) -> Result<AlchemyCallResponse, reqwest::Error> {
+        let payload: AlchemyPayload = AlchemyPayload {
+            params: Some(vec![
+                json!({
+                    "from": from,
+                    "to": to,
+                    "data": data,
+                    "value": format!("{:#x}", value)
+                }),
+                json!("latest"),
+            ]),
+            method: String::from("eth_call"),
+            ..AlchemyPayload::default()
+        };
+
+        post(payload).await
+    }
+}
+
 impl AlchemyPayload {
     fn default() -> Self {
         Self {
@@ -66,6 +266,69 @@ impl AlchemyPayload {
 struct AlchemyPayload {
     id: u8,
     jsonrpc: String,
-    params: Option<Vec<String>>,
+    params: Option<Vec<Value>>,
     method: String,
 }
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AlchemyTransactionReceipt {
+    pub transaction_hash: String,
+    pub transaction_index: String,
+    pub block_number: String,
+    pub from: String,
+    pub to: Option<String>,
+    pub gas_used: String,
+    pub effective_gas_price: String,
+    pub status: String,
+    pub logs: Vec<AlchemyLog>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AlchemyLog {
+    pub address: String,
+    pub topics: Vec<String>,
+    pub data: String,
+    pub log_index: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlchemySimulatedBlock {
+    pub calls: Vec<AlchemySimulatedCall>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlchemySimulatedCall {
+    pub return_data: String,
+    // 0x1 when the call went through
+    pub status: String,
+    pub error: Option<AlchemyError>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlchemyBlock {
+    pub number: String,
+    pub hash: String,
+    pub timestamp: String,
+    pub transactions: Vec<AlchemyTransaction>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AlchemyTransaction {
+    pub hash: String,
+    pub from: String,
+    pub to: Option<String>,
+    pub nonce: String,
+    pub value: String,
+    pub gas: String,
+    pub gas_price: Option<String>,
+    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<String>,
+    pub input: String,
+    pub transaction_index: Option<String>,
+    pub block_number: Option<String>,
+}
@@ -0,0 +1,361 @@
+use super::{
+    alchemy::AlchemyAPI,
+    antisnipe,
+    cache::{CacheStats, TtlCache},
+    eth_call,
+    etherscan::{EtherscanAPI, EtherscanSourceCode},
+};
+use crate::{crypto::abi, telegram::locale::Locale, utils::uint_from_word};
+use chrono::Duration;
+use lazy_static::lazy_static;
+
+// getters of the common launch templates, the first one the contract dispatches is read
+const MAX_TX_GETTERS: [&str; 6] = [
+    "_maxTxAmount()",
+    "maxTxAmount()",
+    "maxTransactionAmount()",
+    "_maxTransactionAmount()",
+    "maxBuyAmount()",
+    "maxTx()",
+];
+const MAX_WALLET_GETTERS: [&str; 7] = [
+    "_maxWalletSize()",
+    "maxWalletSize()",
+    "maxWallet()",
+    "_maxWalletAmount()",
+    "maxWalletAmount()",
+    "_maxWalletToken()",
+    "maxHoldingAmount()",
+];
+const COOLDOWN_GETTERS: [&str; 3] = [
+    "transferDelayEnabled()",
+    "cooldownEnabled()",
+    "buyCooldownEnabled()",
+];
+const TRADING_GETTERS: [&str; 4] = [
+    "tradingOpen()",
+    "tradingEnabled()",
+    "tradingActive()",
+    "tradingOpened()",
+];
+const TRADING_SWITCHES: [&str; 4] = [
+    "openTrading()",
+    "enableTrading()",
+    "startTrading()",
+    "setTrading(bool)",
+];
+const BLACKLIST_FUNCTIONS: [&str; 8] = [
+    "isBot(address)",
+    "bots(address)",
+    "isBlacklisted(address)",
+    "blacklist(address)",
+    "addBots(address[])",
+    "setBots(address[],bool)",
+    "blacklistAddress(address,bool)",
+    "setBlacklist(address,bool)",
+];
+
+// lowercase names in verified sources, they catch the private variables that have no getter
+const MAX_TX_NAMES: [&str; 4] = [
+    "maxtxamount",
+    "maxtransactionamount",
+    "maxbuyamount",
+    "maxtx",
+];
+const MAX_WALLET_NAMES: [&str; 4] = [
+    "maxwalletsize",
+    "maxwalletamount",
+    "maxwallet",
+    "maxholdingamount",
+];
+const COOLDOWN_NAMES: [&str; 4] = [
+    "transferdelayenabled",
+    "cooldownenabled",
+    "_holderlasttransfertimestamp",
+    "cooldowntimerinterval",
+];
+const COOLDOWN_SECONDS_NAMES: [&str; 4] = [
+    "cooldowntimerinterval",
+    "_cooldowntimerinterval",
+    "cooldownseconds",
+    "_buycooldown",
+];
+const TRADING_NAMES: [&str; 4] = [
+    "tradingopen",
+    "tradingenabled",
+    "tradingactive",
+    "tradingopened",
+];
+// mappings that flag addresses whose transfers revert
+const BLACKLIST_NAMES: [&str; 6] = [
+    "bots[",
+    "_isbot[",
+    "isbot[",
+    "_isblacklisted[",
+    "isblacklisted[",
+    "blacklisted[",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    // raw token amount read from the contract's getter
+    Amount(u128),
+    // the source has one but it has no getter to read it from
+    Unreadable,
+}
+
+/*
+
+The limits a launch puts on buyers, so a snipe can be sized to pass them: the
+max tx and max wallet amounts are read from the contract's getters, found by
+their selectors in the runtime code so unverified contracts are covered too.
+Verified sources add what has no getter, like private limits, blacklists and
+cooldowns. A limit at or above the total supply was lifted and isn't shown.
+
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AntiBot {
+    pub max_tx: Option<Limit>,
+    pub max_wallet: Option<Limit>,
+    pub cooldown: bool,
+    pub cooldown_seconds: Option<u64>,
+    // the owner can flag addresses so their transfers revert
+    pub blacklist: bool,
+    pub trading_switch: bool,
+    // None when there's no switch or its state can't be read
+    pub trading_open: Option<bool>,
+}
+
+// limits of 0 aren't set yet, limits of the whole supply were lifted
+fn active(limit: Option<Limit>, total_supply: u128) -> Option<Limit> {
+    limit.filter(|limit| match limit {
+        Limit::Amount(amount) => *amount > 0 && *amount < total_supply,
+        Limit::Unreadable => true,
+    })
+}
+
+fn describe_limit(
+    name: &str,
+    limit: Limit,
+    total_supply: u128,
+    decimals: u8,
+    locale: &Locale,
+) -> String {
+    match limit {
+        Limit::Amount(amount) => format!(
+            "{} {} tokens ({:.2}% of the supply)",
+            name,
+            locale.number((amount as f64 / 10f64.powi(decimals as i32)).floor(), 0),
+            amount as f64 / total_supply as f64 * 100.0
+        ),
+        Limit::Unreadable => format!("{} (private, the amount can't be read)", name),
+    }
+}
+
+impl AntiBot {
+    // the max tx amount a buy has to stay under, None without an active readable one
+    pub fn max_tx_tokens(&self, total_supply: u128) -> Option<u128> {
+        match active(self.max_tx, total_supply) {
+            Some(Limit::Amount(amount)) => Some(amount),
+            _ => None,
+        }
+    }
+
+    pub fn describe(&self, total_supply: u128, decimals: u8, locale: &Locale) -> Vec<String> {
+        let mut mechanics = vec![];
+
+        if let Some(limit) = active(self.max_tx, total_supply) {
+            mechanics.push(describe_limit(
+                "Max tx",
+                limit,
+                total_supply,
+                decimals,
+                locale,
+            ));
+        }
+        if let Some(limit) = active(self.max_wallet, total_supply) {
+            mechanics.push(describe_limit(
+                "Max wallet",
+                limit,
+                total_supply,
+                decimals,
+                locale,
+            ));
+        }
+        if self.cooldown {
+            mechanics.push(match self.cooldown_seconds {
+                Some(seconds) => format!("Cooldown of {}s between trades", seconds),
+                None => String::from("Cooldown between trades"),
+            });
+        }
+        if self.blacklist {
+            mechanics.push(String::from(
+                "Blacklist, the owner can block wallets from selling",
+            ));
+        }
+        match self.trading_open {
+            Some(false) => mechanics.push(String::from("Trading isn't enabled yet")),
+            None if self.trading_switch => {
+                mechanics.push(String::from("Trading switch, its state can't be read"))
+            }
+            _ => (),
+        }
+
+        mechanics
+    }
+}
+
+fn has_name(source: &str, names: &[&str]) -> bool {
+    names.iter().any(|name| source.contains(name))
+}
+
+// what the verified source has, limits are Unreadable until a getter is read
+pub fn detect(source: &str) -> AntiBot {
+    let source = source.to_lowercase();
+
+    AntiBot {
+        max_tx: has_name(&source, &MAX_TX_NAMES).then_some(Limit::Unreadable),
+        max_wallet: has_name(&source, &MAX_WALLET_NAMES).then_some(Limit::Unreadable),
+        cooldown: has_name(&source, &COOLDOWN_NAMES),
+        cooldown_seconds: antisnipe::assigned_number(&source, &COOLDOWN_SECONDS_NAMES)
+            .filter(|seconds| *seconds > 0),
+        blacklist: has_name(&source, &BLACKLIST_NAMES),
+        trading_switch: has_name(&source, &TRADING_NAMES),
+        trading_open: None,
+    }
+}
+
+// the first of the signatures the lowercase runtime code dispatches
+fn find_function(code: &str, signatures: &[&'static str]) -> Option<&'static str> {
+    signatures.iter().copied().find(|signature| {
+        let selector = abi::to_hex(&abi::selector(signature));
+        code.contains(&format!("63{}", selector.trim_start_matches("0x")))
+    })
+}
+
+lazy_static! {
+    // verified sources don't change, unverified contracts are retried once this expires
+    static ref SOURCE_MECHANICS: TtlCache<Option<AntiBot>> =
+        TtlCache::new(Duration::try_hours(1).unwrap());
+}
+
+pub async fn source_cache_stats() -> CacheStats {
+    SOURCE_MECHANICS.stats().await
+}
+
+async fn source_mechanics(contract: &str) -> Option<AntiBot> {
+    if let Some(mechanics) = SOURCE_MECHANICS.get(contract).await {
+        return mechanics;
+    }
+
+    match EtherscanAPI::<Vec<EtherscanSourceCode>>::get_source_code(contract.to_owned()).await {
+        Ok(response) => {
+            let mechanics = response
+                .result
+                .first()
+                .filter(|source| !source.source_code.is_empty())
+                .map(|source| detect(&source.source_code));
+
+            SOURCE_MECHANICS.insert(contract, mechanics.clone()).await;
+            mechanics
+        }
+        Err(e) => {
+            error!("get_source_code error: {}", e.without_url());
+            None
+        }
+    }
+}
+
+async fn read_getter(contract: &str, signature: &str) -> Result<u128, reqwest::Error> {
+    Ok(uint_from_word(
+        &eth_call(contract.to_owned(), abi::function_call(signature, &[])).await?,
+    ))
+}
+
+pub async fn get_anti_bot(contract: String) -> Result<AntiBot, reqwest::Error> {
+    let contract = contract.to_lowercase();
+    let code = match AlchemyAPI::<String>::get_code(contract.clone()).await {
+        Ok(response) => response.result.trim_start_matches("0x").to_lowercase(),
+        Err(e) => return Err(e.without_url()),
+    };
+    let mut anti_bot = source_mechanics(&contract).await.unwrap_or_default();
+
+    if let Some(getter) = find_function(&code, &MAX_TX_GETTERS) {
+        anti_bot.max_tx = Some(Limit::Amount(read_getter(&contract, getter).await?));
+    }
+    if let Some(getter) = find_function(&code, &MAX_WALLET_GETTERS) {
+        anti_bot.max_wallet = Some(Limit::Amount(read_getter(&contract, getter).await?));
+    }
+    // a delay the owner turned off is no cooldown anymore
+    if let Some(getter) = find_function(&code, &COOLDOWN_GETTERS) {
+        anti_bot.cooldown = read_getter(&contract, getter).await? != 0;
+    }
+    if let Some(getter) = find_function(&code, &TRADING_GETTERS) {
+        anti_bot.trading_switch = true;
+        anti_bot.trading_open = Some(read_getter(&contract, getter).await? != 0);
+    }
+    anti_bot.trading_switch |= find_function(&code, &TRADING_SWITCHES).is_some();
+    anti_bot.blacklist |= find_function(&code, &BLACKLIST_FUNCTIONS).is_some();
+
+    Ok(anti_bot)
+}
+
+#[test]
+fn test_detect_anti_bot() {
+    let source = r#"
+        mapping (address => bool) private bots;
+        uint256 private _maxTxAmount = 20000000 * 10**_decimals;
+        uint256 public _maxWalletSize = 40000000 * 10**_decimals;
+        bool public transferDelayEnabled = true;
+        uint256 private cooldownTimerInterval = 30;
+        bool private tradingOpen;
+        function _transfer(address from, address to, uint256 amount) private {
+            require(!bots[from] && !bots[to]);
+        }
+    "#;
+    let mut anti_bot = detect(source);
+    assert_eq!(
+        anti_bot,
+        AntiBot {
+            max_tx: Some(Limit::Unreadable),
+            max_wallet: Some(Limit::Unreadable),
+            cooldown: true,
+            cooldown_seconds: Some(30),
+            blacklist: true,
+            trading_switch: true,
+            trading_open: None,
+        }
+    );
+
+    // 1B supply with 9 decimals, the max wallet was lifted
+    let total_supply = 1_000_000_000 * 10u128.pow(9);
+    anti_bot.max_tx = Some(Limit::Amount(20_000_000 * 10u128.pow(9)));
+    anti_bot.max_wallet = Some(Limit::Amount(total_supply));
+    anti_bot.trading_open = Some(false);
+    assert_eq!(
+        anti_bot.describe(total_supply, 9, &Locale::default()),
+        vec![
+            "Max tx 20,000,000 tokens (2.00% of the supply)",
+            "Cooldown of 30s between trades",
+            "Blacklist, the owner can block wallets from selling",
+            "Trading isn't enabled yet",
+        ]
+    );
+    assert_eq!(
+        anti_bot.max_tx_tokens(total_supply),
+        Some(20_000_000 * 10u128.pow(9))
+    );
+
+    let code = format!(
+        "6080{}14",
+        abi::to_hex(&abi::selector("_maxTxAmount()")).replace("0x", "63")
+    );
+    assert_eq!(
+        find_function(&code, &MAX_TX_GETTERS),
+        Some("_maxTxAmount()")
+    );
+    assert_eq!(find_function(&code, &MAX_WALLET_GETTERS), None);
+    assert!(detect("contract Plain is ERC20 {}")
+        .describe(total_supply, 9, &Locale::default())
+        .is_empty());
+}
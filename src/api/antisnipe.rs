@@ -0,0 +1,145 @@
+use core::fmt;
+
+pub const SECONDS_PER_BLOCK: u64 = 12;
+
+// variable names the common launch templates use, matched case-insensitively
+const DEAD_BLOCKS_NAMES: [&str; 6] = [
+    "deadblocks",
+    "_deadblocks",
+    "snipeblocks",
+    "_snipeblocks",
+    "antisnipeblocks",
+    "launchblocks",
+];
+const INITIAL_BUY_TAX_NAMES: [&str; 3] = ["_initialbuytax", "initialbuytax", "_initbuytax"];
+const REDUCE_BUY_TAX_AT_NAMES: [&str; 2] = ["_reducebuytaxat", "reducebuytaxat"];
+// block.number comparisons on lines mentioning these are launch block checks
+const LAUNCH_BLOCK_HINTS: [&str; 5] = [
+    "launch",
+    "tradingopen",
+    "tradingenabled",
+    "deadblock",
+    "snipe",
+];
+
+// launch mechanics that punish buys in the first blocks, found by reading the verified source
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AntiSnipe {
+    pub dead_blocks: Option<u64>,
+    pub initial_buy_tax: Option<u64>,
+    pub reduce_buy_tax_at: Option<u64>,
+    pub launch_block_checks: bool,
+}
+
+impl AntiSnipe {
+    pub fn is_detected(&self) -> bool {
+        // a 0% initial tax is how the templates look once the deployer disabled it
+        self.dead_blocks.is_some_and(|blocks| blocks > 0)
+            || self.initial_buy_tax.is_some_and(|tax| tax > 0)
+            || self.launch_block_checks
+    }
+
+    // buying before the dead blocks passed means paying the punitive tax or getting blacklisted
+    pub fn min_delay_blocks(&self) -> u64 {
+        self.dead_blocks.unwrap_or(0)
+    }
+}
+
+impl fmt::Display for AntiSnipe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut mechanics = vec![];
+
+        if let Some(blocks) = self.dead_blocks.filter(|blocks| *blocks > 0) {
+            mechanics.push(format!("{} dead blocks", blocks));
+        }
+        match (self.initial_buy_tax, self.reduce_buy_tax_at) {
+            (Some(tax), Some(buys)) if tax > 0 => {
+                mechanics.push(format!("{}% buy tax for the first {} buys", tax, buys))
+            }
+            (Some(tax), None) if tax > 0 => mechanics.push(format!("{}% initial buy tax", tax)),
+            _ => (),
+        }
+        if self.launch_block_checks && mechanics.is_empty() {
+            mechanics.push(String::from("launch block checks"));
+        }
+
+        write!(f, "{}", mechanics.join(", "))
+    }
+}
+
+// 'name = 123' or 'name=123', None if the name isn't assigned a number literal
+pub(super) fn assigned_number(source: &str, names: &[&str]) -> Option<u64> {
+    for name in names {
+        let mut rest = source;
+
+        while let Some(index) = rest.find(name) {
+            let before = rest[..index].chars().last();
+            let after = rest[index + name.len()..].trim_start();
+            rest = &rest[index + name.len()..];
+
+            if before.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+                continue;
+            }
+
+            let Some(value) = after.strip_prefix('=') else {
+                continue;
+            };
+            let digits: String = value
+                .trim_start()
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            if let Ok(number) = digits.parse::<u64>() {
+                return Some(number);
+            }
+        }
+    }
+
+    None
+}
+
+pub fn detect(source: &str) -> AntiSnipe {
+    let source = source.to_lowercase();
+
+    AntiSnipe {
+        dead_blocks: assigned_number(&source, &DEAD_BLOCKS_NAMES),
+        initial_buy_tax: assigned_number(&source, &INITIAL_BUY_TAX_NAMES),
+        reduce_buy_tax_at: assigned_number(&source, &REDUCE_BUY_TAX_AT_NAMES),
+        launch_block_checks: source.lines().any(|line| {
+            line.contains("block.number")
+                && LAUNCH_BLOCK_HINTS.iter().any(|hint| line.contains(hint))
+        }),
+    }
+}
+
+#[test]
+fn test_detect_anti_snipe() {
+    let source = r#"
+        uint256 private _initialBuyTax=25;
+        uint256 private _finalBuyTax=0;
+        uint256 private _reduceBuyTaxAt = 20;
+        uint256 public deadBlocks = 3;
+        function _transfer(address from, address to, uint256 amount) private {
+            if (block.number <= launchedAt + deadBlocks) { _isBot[to] = true; }
+        }
+    "#;
+    let anti_snipe = detect(source);
+
+    assert_eq!(
+        anti_snipe,
+        AntiSnipe {
+            dead_blocks: Some(3),
+            initial_buy_tax: Some(25),
+            reduce_buy_tax_at: Some(20),
+            launch_block_checks: true,
+        }
+    );
+    assert_eq!(
+        anti_snipe.to_string(),
+        "3 dead blocks, 25% buy tax for the first 20 buys"
+    );
+
+    // names that only end with a known one don't count
+    assert_eq!(detect("uint256 maxDeadBlocks = 5;").dead_blocks, None);
+    assert!(!detect("uint256 private _initialBuyTax = 0;").is_detected());
+}
@@ -1,28 +1,208 @@
 use crate::{
-    telegram::bot::{self, SETTINGS, WATCHED_WALLETS},
-    utils::{to_eth, to_gwei},
+    crypto::{
+        abi::{self, AbiToken},
+        launches, positions, wallet,
+    },
+    hooks::{self, DiscoveredToken},
+    logging, tasks,
+    telegram::{
+        alerts::{self, AlertCategory, WalletActivity},
+        bot::{self, SETTINGS, WATCHED_TOKENS, WATCHED_WALLETS},
+        expiry::{self, WatchKind},
+        shards,
+        templates::{self, TemplateKind},
+    },
+    utils::{
+        address_from_word, hex_to_decimal, hyperlinks_from_contract, to_checksum_address, to_eth,
+        to_gwei, uint_from_word, Chain,
+    },
 };
+use cache::TtlCache;
 use chrono::{DateTime, Datelike, Duration, Utc};
-use std::{collections::HashMap, sync::Arc};
-use teloxide::{requests::Requester, types::ChatId, Bot};
-use tokio::{sync::Mutex, time::sleep};
+use core::fmt;
+use lazy_static::lazy_static;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    env,
+    sync::Arc,
+};
+use teloxide::{
+    payloads::SendMessageSetters,
+    requests::Requester,
+    types::{ChatId, ParseMode},
+    utils::html,
+    Bot,
+};
+use tokio::{sync::Mutex, task::JoinSet, time::sleep};
 
 mod alchemy;
+mod antibot;
+mod antisnipe;
+mod approvals;
+mod buyers;
+mod cache;
 mod chainbase;
+mod clusters;
+mod convergence;
+mod deposits;
+mod errorbudget;
 mod etherscan;
+mod flashbots;
+mod funding;
+mod gasspent;
 mod honeypot;
+mod inspect;
+mod instrumentation;
+mod labels;
+mod launchpads;
+mod migrations;
 mod moralis;
-
-use alchemy::AlchemyAPI;
+mod polling;
+mod profits;
+mod providers;
+mod safe;
+mod scamcode;
+mod taxes;
+mod tokenchanges;
+mod tokenlists;
+mod tokensets;
+mod usage;
+
+pub use alchemy::AlchemyTransaction;
+use alchemy::{
+    AlchemyAPI, AlchemyCallResponse, AlchemyError, AlchemyLog, AlchemyTransactionReceipt,
+};
+pub use antibot::{get_anti_bot, AntiBot};
+pub use antisnipe::{AntiSnipe, SECONDS_PER_BLOCK};
+pub use approvals::{Approval, ApprovalKind};
+pub use buyers::{get_early_buyers, EarlyBuyers};
+pub use cache::CacheStats;
 use chainbase::ChainbaseAPI;
 pub use chainbase::ChainbaseTokenOwners;
-pub use etherscan::EtherscanTokenTransaction;
+pub use clusters::ClusterBuy;
+use clusters::ClusterTracker;
+pub use convergence::{ConvergentBuy, WalletTransaction};
 use etherscan::{
     EtherscanAPI, EtherscanContractCreatorAndTxHash, EtherscanEthPrices,
-    EtherscanInternalTransaction, EtherscanNormalTransaction,
+    EtherscanInternalTransaction, EtherscanSourceCode,
 };
+pub use etherscan::{EtherscanNormalTransaction, EtherscanTokenTransaction};
+pub use flashbots::send_bundle as send_flashbots_bundle;
+pub use funding::{trace_depth as funding_trace_depth, trace_funding, FundingOrigin, FundingPath};
+pub use gasspent::{ContractGas, GasSpent};
 pub use honeypot::HoneypotTokenInfo;
+pub use inspect::{inspect_address, AddressInspection, ContractKind};
+pub use instrumentation::{prometheus_text, EndpointStats};
+pub use labels::{
+    add_known_address, known_label, labels_of, remove_known_address, KnownAddress, LabelKind,
+};
+pub use launchpads::{feeds as launchpad_feeds, launchpad_alerts, LaunchpadFeed};
+pub use migrations::LiquidityMove;
+use migrations::PoolTracker;
 use moralis::MoralisTokenBalancesWithPrices;
+use polling::PollSchedule;
+pub use profits::{RecentBuy, WalletProfit};
+pub use providers::{client, Provider};
+pub use safe::{
+    get_next_safe_nonce, get_safe_info, get_safe_transaction, propose_safe_transaction, SafeInfo,
+    SafeMultisigTransaction, SafeProposal,
+};
+pub use scamcode::{has_templates as has_scam_templates, TemplateMatch};
+pub use taxes::{get_size_taxes, SizeSimulation, SizeTax};
+use tokenchanges::TokenReadings;
+pub use tokenchanges::{liquidity_change_percent, TokenChange};
+pub use tokenlists::{get_token_list, TokenList, TokenListEntry};
+pub use usage::ProviderUsage;
+
+lazy_static! {
+    // new pairs are warmed up into these caches, so filtering and scans don't refetch everything
+    // expired token info is kept for a while as a fallback for when honeypot.is is down
+    static ref TOKEN_INFO_CACHE: TtlCache<HoneypotTokenInfo> =
+        TtlCache::with_stale(Duration::try_minutes(1).unwrap(), Duration::try_hours(1).unwrap());
+    // tokens honeypot.is recently failed for aren't requested again until this expires
+    static ref TOKEN_INFO_FAILURES: TtlCache<()> =
+        TtlCache::new(Duration::try_seconds(30).unwrap());
+    static ref TOP_HOLDERS_CACHE: TtlCache<Vec<ChainbaseTokenOwners>> =
+        TtlCache::new(Duration::try_minutes(1).unwrap());
+    static ref NORMAL_TRANSACTIONS_CACHE: TtlCache<Vec<EtherscanNormalTransaction>> =
+        TtlCache::new(Duration::try_minutes(1).unwrap());
+    // labels barely change, so lookups (including misses) are kept for a day
+    static ref ADDRESS_LABEL_CACHE: TtlCache<Option<String>> =
+        TtlCache::new(Duration::try_days(1).unwrap());
+    // address -> the exchange it's a deposit address of, exchanges reuse them for years
+    static ref EXCHANGE_DEPOSIT_CACHE: TtlCache<Option<String>> =
+        TtlCache::new(Duration::try_days(1).unwrap());
+    // verified sources don't change, unverified contracts are retried once this expires
+    static ref ANTI_SNIPE_CACHE: TtlCache<Option<AntiSnipe>> =
+        TtlCache::new(Duration::try_hours(1).unwrap());
+    // usd prices of tokens wallets recently bought, looking at a busy wallet again doesn't refetch them all
+    static ref RECENT_BUY_PRICE_CACHE: TtlCache<f32> =
+        TtlCache::new(Duration::try_minutes(5).unwrap());
+    // lowercase contracts ETH_ADDRESS holds, checked by every wallet watched with the held filter
+    static ref HELD_TOKENS_CACHE: TtlCache<Vec<String>> =
+        TtlCache::new(Duration::try_minutes(5).unwrap());
+    // raw total supply, only changes on mints and burns
+    static ref TOTAL_SUPPLY_CACHE: TtlCache<u128> =
+        TtlCache::new(Duration::try_minutes(10).unwrap());
+    // chat -> watched wallet -> the newest transaction timestamp seen, as the last watch cycle left it
+    static ref LAST_SEEN_TRANSACTIONS: Mutex<HashMap<ChatId, HashMap<String, u64>>> =
+        Mutex::new(HashMap::<ChatId, HashMap<String, u64>>::new());
+    // lowercase contract -> snapshots of held tokens, oldest first
+    static ref CONCENTRATION_HISTORY: Mutex<HashMap<String, VecDeque<ConcentrationSnapshot>>> =
+        Mutex::new(HashMap::<String, VecDeque<ConcentrationSnapshot>>::new());
+}
+
+// request counts of the current UTC day and month, persisted across restarts
+pub async fn get_api_usage() -> Vec<ProviderUsage> {
+    usage::usage().await
+}
+
+// (cache, stats) of the response caches, for /debug
+pub async fn get_cache_stats() -> Vec<(&'static str, CacheStats)> {
+    vec![
+        ("token_info", TOKEN_INFO_CACHE.stats().await),
+        ("token_info_failures", TOKEN_INFO_FAILURES.stats().await),
+        ("top_holders", TOP_HOLDERS_CACHE.stats().await),
+        (
+            "normal_transactions",
+            NORMAL_TRANSACTIONS_CACHE.stats().await,
+        ),
+        ("address_labels", ADDRESS_LABEL_CACHE.stats().await),
+        ("exchange_deposits", EXCHANGE_DEPOSIT_CACHE.stats().await),
+        ("anti_snipe", ANTI_SNIPE_CACHE.stats().await),
+        ("anti_bot_sources", antibot::source_cache_stats().await),
+        ("total_supply", TOTAL_SUPPLY_CACHE.stats().await),
+        ("recent_buy_prices", RECENT_BUY_PRICE_CACHE.stats().await),
+        ("held_tokens", HELD_TOKENS_CACHE.stats().await),
+        ("funders", clusters::funder_cache_stats().await),
+        ("first_funding", funding::funding_cache_stats().await),
+        ("token_pools", migrations::pool_cache_stats().await),
+        ("size_taxes", taxes::size_taxes_cache_stats().await),
+    ]
+}
+
+// the providers whose error budget is spent right now
+pub async fn get_provider_outages() -> Vec<String> {
+    errorbudget::exhausted()
+        .await
+        .iter()
+        .map(|provider| provider.to_string())
+        .collect()
+}
+
+pub async fn is_provider_down(provider: Provider) -> bool {
+    errorbudget::exhausted().await.contains(&provider)
+}
+
+pub async fn get_last_seen_transactions() -> HashMap<ChatId, HashMap<String, u64>> {
+    LAST_SEEN_TRANSACTIONS.lock().await.clone()
+}
+
+// (provider, endpoint, stats) of every endpoint requested since the bot started
+pub async fn get_endpoint_stats() -> Vec<(String, String, EndpointStats)> {
+    instrumentation::stats().await
+}
 
 pub async fn get_eth_price() -> Result<f64, reqwest::Error> {
     match EtherscanAPI::<EtherscanEthPrices>::eth_price().await {
@@ -34,8 +214,22 @@ pub async fn get_eth_price() -> Result<f64, reqwest::Error> {
 pub async fn get_normal_transactions(
     address: String,
 ) -> Result<Vec<EtherscanNormalTransaction>, reqwest::Error> {
-    match EtherscanAPI::<Vec<EtherscanNormalTransaction>>::get_normal_transactions(address).await {
-        Ok(response) => Ok(response.result),
+    if let Some(transactions) = NORMAL_TRANSACTIONS_CACHE.get(&address).await {
+        return Ok(transactions);
+    }
+
+    match EtherscanAPI::<Vec<EtherscanNormalTransaction>>::get_normal_transactions(
+        address.clone(),
+        25,
+    )
+    .await
+    {
+        Ok(response) => {
+            NORMAL_TRANSACTIONS_CACHE
+                .insert(&address, response.result.clone())
+                .await;
+            Ok(response.result)
+        }
         Err(e) => Err(e.without_url()),
     }
 }
@@ -43,7 +237,32 @@ pub async fn get_normal_transactions(
 pub async fn get_token_transactions(
     address: String,
 ) -> Result<Vec<EtherscanTokenTransaction>, reqwest::Error> {
-    match EtherscanAPI::<Vec<EtherscanTokenTransaction>>::get_token_transactions(address).await {
+    get_token_transactions_page(address, 1).await
+}
+
+// newest first, TOKEN_TRANSACTIONS_PAGE_SIZE per page
+async fn get_token_transactions_page(
+    address: String,
+    page: u32,
+) -> Result<Vec<EtherscanTokenTransaction>, reqwest::Error> {
+    match EtherscanAPI::<Vec<EtherscanTokenTransaction>>::get_token_transactions(
+        address,
+        page,
+        TOKEN_TRANSACTIONS_PAGE_SIZE,
+    )
+    .await
+    {
+        Ok(response) => Ok(response.result),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+pub async fn get_contract_token_transfers(
+    contract: String,
+) -> Result<Vec<EtherscanTokenTransaction>, reqwest::Error> {
+    match EtherscanAPI::<Vec<EtherscanTokenTransaction>>::get_contract_token_transfers(contract)
+        .await
+    {
         Ok(response) => Ok(response.result),
         Err(e) => Err(e.without_url()),
     }
@@ -71,9 +290,139 @@ pub async fn get_eth_gas() -> Result<f64, reqwest::Error> {
     }
 }
 
-pub async fn get_eth_balance() -> Result<String, reqwest::Error> {
-    match AlchemyAPI::<String>::get_eth_balance().await {
-        Ok(balance) => Ok(format!("{}", to_eth(&balance.result))),
+pub async fn get_eth_gas_wei() -> Result<u128, reqwest::Error> {
+    match AlchemyAPI::<String>::get_eth_gas().await {
+        Ok(gas) => Ok(hex_to_decimal(&gas.result)),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+pub async fn get_eth_balance(address: String) -> Result<f64, reqwest::Error> {
+    match AlchemyAPI::<String>::get_eth_balance(address).await {
+        Ok(balance) => Ok(to_eth(&balance.result)),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+pub async fn get_transaction_receipt(
+    hash: String,
+) -> Result<Option<AlchemyTransactionReceipt>, reqwest::Error> {
+    match AlchemyAPI::<Option<AlchemyTransactionReceipt>>::get_transaction_receipt(hash).await {
+        Ok(receipt) => Ok(receipt.result),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+pub async fn get_transaction(hash: String) -> Result<Option<AlchemyTransaction>, reqwest::Error> {
+    match AlchemyAPI::<Option<AlchemyTransaction>>::get_transaction_by_hash(hash).await {
+        Ok(transaction) => Ok(transaction.result),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+pub async fn get_raw_transaction(hash: String) -> Result<Option<String>, reqwest::Error> {
+    match AlchemyAPI::<Option<String>>::get_raw_transaction_by_hash(hash).await {
+        Ok(transaction) => Ok(transaction.result),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+// 'pending' includes transactions waiting in the mempool, 'latest' only mined ones
+pub async fn get_transaction_count(address: String, pending: bool) -> Result<u64, reqwest::Error> {
+    let block_tag = if pending { "pending" } else { "latest" };
+
+    match AlchemyAPI::<String>::get_transaction_count(address, String::from(block_tag)).await {
+        Ok(count) => Ok(hex_to_decimal(&count.result) as u64),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+pub async fn get_block_transactions(
+    block_number: String,
+) -> Result<Vec<AlchemyTransaction>, reqwest::Error> {
+    match AlchemyAPI::<alchemy::AlchemyBlock>::get_block_by_number(block_number).await {
+        Ok(block) => Ok(block.result.transactions),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+// the block the provider is building, its transactions are the part of the mempool it sees
+pub async fn get_pending_block() -> Result<(u64, Vec<AlchemyTransaction>), reqwest::Error> {
+    match AlchemyAPI::<alchemy::AlchemyBlock>::get_block_by_number(String::from("pending")).await {
+        Ok(block) => Ok((
+            hex_to_decimal(&block.result.number) as u64,
+            block.result.transactions,
+        )),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+// returns the raw hex result of a read-only contract call
+pub async fn eth_call(to: String, data: String) -> Result<String, reqwest::Error> {
+    match AlchemyAPI::<String>::call(to, data).await {
+        Ok(response) => Ok(response.result),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+fn revert_message(error: AlchemyError) -> String {
+    match error.data {
+        Some(data) => format!("{} {}", error.message, data.as_str().unwrap_or_default()),
+        None => error.message,
+    }
+}
+
+// the revert message and data of the call, None if it goes through
+pub async fn simulate_revert(
+    from: String,
+    to: String,
+    data: String,
+    value: u128,
+) -> Result<Option<String>, reqwest::Error> {
+    match AlchemyCallResponse::simulate(from, to, data, value).await {
+        Ok(response) => Ok(response.error.map(revert_message)),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+// (to, data, value) calls run one after another, each on the state the ones before it left, e.g. a
+// sell after its approve. the revert message and data of the first failing call, None if all go through
+pub async fn simulate_revert_in_order(
+    from: String,
+    calls: Vec<(String, String, u128)>,
+) -> Result<Option<String>, reqwest::Error> {
+    let calls = calls
+        .into_iter()
+        .map(|(to, data, value)| {
+            json!({
+                "from": from,
+                "to": to,
+                "data": data,
+                "value": format!("{:#x}", value)
+            })
+        })
+        .collect();
+
+    match AlchemyAPI::<Vec<Value>>::simulate_calls(json!({}), calls).await {
+        Ok(response) => Ok(response
+            .result
+            .into_iter()
+            .flat_map(|block| block.calls)
+            .find_map(|call| call.error)
+            .map(revert_message)),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+// simulates the transaction against the latest block, errors if it would revert
+pub async fn estimate_gas(
+    from: String,
+    to: String,
+    data: String,
+    value: u128,
+) -> Result<u128, reqwest::Error> {
+    match AlchemyAPI::<String>::estimate_gas(from, to, data, value).await {
+        Ok(response) => Ok(hex_to_decimal(&response.result)),
         Err(e) => Err(e.without_url()),
     }
 }
@@ -81,160 +430,1942 @@ pub async fn get_eth_balance() -> Result<String, reqwest::Error> {
 pub async fn get_top_token_holders(
     contract: String,
 ) -> Result<Vec<ChainbaseTokenOwners>, reqwest::Error> {
-    match ChainbaseAPI::<Vec<ChainbaseTokenOwners>>::get_top_token_holders(contract).await {
-        Ok(token_owners) => Ok(token_owners.data),
+    if let Some(holders) = TOP_HOLDERS_CACHE.get(&contract).await {
+        return Ok(holders);
+    }
+
+    match ChainbaseAPI::<Vec<ChainbaseTokenOwners>>::get_top_token_holders(contract.clone()).await {
+        Ok(token_owners) => {
+            TOP_HOLDERS_CACHE
+                .insert(&contract, token_owners.data.clone())
+                .await;
+            Ok(token_owners.data)
+        }
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+pub async fn get_token_price(contract: String) -> Result<f32, reqwest::Error> {
+    match moralis::get_token_price(contract).await {
+        Ok(price) => Ok(price.usd_price),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+#[derive(Debug)]
+pub enum TokenInfoError {
+    Request(reqwest::Error),
+    // honeypot.is has no pair of the token, e.g. it launched moments ago
+    NotFound,
+    // honeypot.is failed for this token in the last few seconds and nothing is cached
+    RecentlyFailed,
+}
+
+impl std::fmt::Display for TokenInfoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenInfoError::Request(e) => write!(f, "{}", e),
+            TokenInfoError::NotFound => write!(f, "honeypot.is doesn't know this token"),
+            TokenInfoError::RecentlyFailed => {
+                write!(
+                    f,
+                    "honeypot.is failed recently for this token, try again later"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TokenInfoError {}
+
+// honeypot.is data older than this is shown as stale
+pub fn is_token_info_stale(token_info: &HoneypotTokenInfo) -> bool {
+    Utc::now() - token_info.fetched_at >= Duration::try_minutes(1).unwrap()
+}
+
+// Ethereum only until the rest of the bot knows about chains, the caches are keyed by contract
+pub async fn get_token_info(contract: String) -> Result<HoneypotTokenInfo, TokenInfoError> {
+    if let Some(token_info) = TOKEN_INFO_CACHE.get(&contract).await {
+        return Ok(token_info);
+    }
+
+    let stale = TOKEN_INFO_CACHE.get_stale(&contract).await;
+
+    if TOKEN_INFO_FAILURES.get(&contract).await.is_some() {
+        return match stale {
+            Some((_, token_info)) => Ok(token_info),
+            None => Err(TokenInfoError::RecentlyFailed),
+        };
+    }
+
+    let mut attempt = 1;
+    loop {
+        match honeypot::get_token_info(contract.clone(), Chain::Ethereum).await {
+            Ok(token_info) => {
+                TOKEN_INFO_CACHE.insert(&contract, token_info.clone()).await;
+                return Ok(token_info);
+            }
+            // asking again won't find it, it isn't an outage either
+            Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                return match stale {
+                    Some((_, token_info)) => Ok(token_info),
+                    None => Err(TokenInfoError::NotFound),
+                };
+            }
+            Err(e) if attempt < 3 => {
+                warn!(
+                    "get_token_info attempt {} failed: {}",
+                    attempt,
+                    e.without_url()
+                );
+                sleep(
+                    Duration::try_milliseconds(500 * attempt)
+                        .unwrap()
+                        .to_std()
+                        .unwrap(),
+                )
+                .await;
+                attempt += 1;
+            }
+            Err(e) => {
+                TOKEN_INFO_FAILURES.insert(&contract, ()).await;
+
+                return match stale {
+                    Some((_, token_info)) => {
+                        warn!(
+                            "get_token_info failed, using stale data: {}",
+                            e.without_url()
+                        );
+                        Ok(token_info)
+                    }
+                    None => Err(TokenInfoError::Request(e.without_url())),
+                };
+            }
+        }
+    }
+}
+
+// what can be read on-chain of a token honeypot.is doesn't know yet, None if it isn't an ERC-20.
+// the liquidity is the deepest WETH pool's, both sides counted like honeypot.is does
+async fn get_onchain_token_info(
+    contract: &str,
+) -> Result<Option<HoneypotTokenInfo>, reqwest::Error> {
+    let (name, symbol, decimals) = tokio::join!(
+        eth_call(contract.to_owned(), abi::function_call("name()", &[])),
+        eth_call(contract.to_owned(), abi::function_call("symbol()", &[])),
+        eth_call(contract.to_owned(), abi::function_call("decimals()", &[]))
+    );
+    let decimals = decimals?;
+    let Some(symbol) = abi::decode_string(&symbol?) else {
+        return Ok(None);
+    };
+    if decimals.trim_start_matches("0x").is_empty() {
+        return Ok(None);
+    }
+
+    let pools = migrations::token_pools(contract, None).await;
+    let (pools, eth_price) = tokio::join!(migrations::pool_weth(&pools), get_eth_price());
+    let deepest = pools.into_iter().max_by(|a, b| a.1.total_cmp(&b.1));
+
+    Ok(Some(HoneypotTokenInfo {
+        name: abi::decode_string(&name?).unwrap_or_else(|| symbol.clone()),
+        symbol,
+        contract_address: to_checksum_address(contract),
+        decimals: uint_from_word(&decimals).min(u8::MAX as u128) as u8,
+        pair: String::new(),
+        pair_address: deepest
+            .as_ref()
+            .map(|(pool, _)| pool.clone())
+            .unwrap_or_default(),
+        pair_token: String::from(WETH_ADDRESS),
+        pair_symbol: String::from("WETH"),
+        is_honeypot: false,
+        honeypot_reason: None,
+        buy_tax: 0.0,
+        sell_tax: 0.0,
+        liquidity: deepest
+            .map(|(_, weth)| (weth * 2.0 * eth_price.unwrap_or(0.0)) as f32)
+            .unwrap_or(0.0),
+        is_open_source: None,
+        has_proxy_calls: None,
+        flags_description: None,
+        total_holders: 0,
+        pair_created_at: None,
+        chain: Chain::Ethereum,
+        fetched_at: Utc::now(),
+        simulated: false,
+    }))
+}
+
+// /scan still shows the basics of tokens too new for honeypot.is, the result isn't simulated so
+// it must never be traded on. outages are errors, the card would pass every token off as unknown
+pub async fn get_scan_token_info(contract: String) -> Result<HoneypotTokenInfo, TokenInfoError> {
+    let e = match get_token_info(contract.clone()).await {
+        Err(TokenInfoError::NotFound) => TokenInfoError::NotFound,
+        result => return result,
+    };
+
+    match get_onchain_token_info(&contract).await {
+        Ok(Some(token_info)) => {
+            warn!("get_token_info failed, scanning on-chain data only: {}", e);
+            Ok(token_info)
+        }
+        Ok(None) => Err(e),
+        Err(onchain_e) => {
+            error!("get_onchain_token_info error: {}", onchain_e);
+            Err(e)
+        }
+    }
+}
+
+// known exchange/locker label, or the verified contract name from etherscan
+pub async fn get_address_label(address: &str) -> Option<String> {
+    if let Some((label, _)) = known_label(address) {
+        return Some(String::from(label));
+    }
+
+    if let Some(label) = ADDRESS_LABEL_CACHE.get(address).await {
+        return label;
+    }
+
+    match EtherscanAPI::<Vec<EtherscanSourceCode>>::get_source_code(address.to_owned()).await {
+        Ok(response) => {
+            let label = response
+                .result
+                .first()
+                .map(|source| source.contract_name.clone())
+                .filter(|name| !name.is_empty());
+
+            ADDRESS_LABEL_CACHE.insert(address, label.clone()).await;
+            label
+        }
+        Err(e) => {
+            error!("get_address_label error: {}", e.without_url());
+            None
+        }
+    }
+}
+
+// None for unverified contracts, their launch mechanics can't be read
+pub async fn get_anti_snipe(contract: String) -> Result<Option<AntiSnipe>, reqwest::Error> {
+    if let Some(anti_snipe) = ANTI_SNIPE_CACHE.get(&contract).await {
+        return Ok(anti_snipe);
+    }
+
+    match EtherscanAPI::<Vec<EtherscanSourceCode>>::get_source_code(contract.clone()).await {
+        Ok(response) => {
+            let anti_snipe = response
+                .result
+                .first()
+                .filter(|source| !source.source_code.is_empty())
+                .map(|source| antisnipe::detect(&source.source_code));
+
+            ANTI_SNIPE_CACHE.insert(&contract, anti_snipe.clone()).await;
+            Ok(anti_snipe)
+        }
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+// None for unverified contracts and code that matches no known scam template
+pub async fn get_template_match(contract: String) -> Result<Option<TemplateMatch>, reqwest::Error> {
+    if !scamcode::has_templates() {
+        return Ok(None);
+    }
+
+    let response = EtherscanAPI::<Vec<EtherscanSourceCode>>::get_source_code(contract)
+        .await
+        .map_err(|e| e.without_url())?;
+
+    Ok(response
+        .result
+        .first()
+        .filter(|source| !source.source_code.is_empty())
+        .and_then(|source| scamcode::best_match(&source.source_code, &source.contract_name)))
+}
+
+pub async fn get_contract_creator_and_tx_hash(
+    addresses: Vec<String>,
+) -> Result<Vec<EtherscanContractCreatorAndTxHash>, reqwest::Error> {
+    let mut results: Vec<EtherscanContractCreatorAndTxHash> = vec![];
+    let mut grouped_addresses: Vec<String> = vec![];
+
+    for i in 0..addresses.len() {
+        grouped_addresses.push(addresses[i].clone());
+
+        if i % 5 == 0 || i == addresses.len() - 1 {
+            match EtherscanAPI::<Vec<EtherscanContractCreatorAndTxHash>>::get_contract_creator_and_tx_hash(
+                grouped_addresses.clone(),
+            )
+            .await
+            {
+                Ok(creators_and_hashes) => results.extend(creators_and_hashes.result),
+                Err(e) => {
+                    return Err(e.without_url())
+                },
+            };
+
+            grouped_addresses.clear();
+        }
+    }
+
+    Ok(results)
+}
+
+pub async fn get_token_balances_with_prices() -> Result<Vec<OwnedToken>, reqwest::Error> {
+    get_wallet_token_balances(&env::var("ETH_ADDRESS").expect("ETH_ADDRESS env var is not set"))
+        .await
+}
+
+// None when the balances couldn't be read
+pub async fn get_held_tokens() -> Option<Vec<String>> {
+    if let Some(held) = HELD_TOKENS_CACHE.get("held").await {
+        return Some(held);
+    }
+
+    match get_token_balances_with_prices().await {
+        Ok(tokens) => {
+            let held: Vec<String> = tokens
+                .iter()
+                .map(|token| token.contract.to_lowercase())
+                .collect();
+            HELD_TOKENS_CACHE.insert("held", held.clone()).await;
+            Some(held)
+        }
+        Err(e) => {
+            error!("held tokens error: {}", e);
+            None
+        }
+    }
+}
+
+// the balances of any wallet, for the REST API
+pub async fn get_wallet_token_balances(address: &str) -> Result<Vec<OwnedToken>, reqwest::Error> {
+    match moralis::get_token_balances_with_prices(address).await {
+        Ok(token_balances) => Ok(to_owned_tokens(token_balances.result).await),
         Err(e) => Err(e.without_url()),
     }
 }
 
-pub async fn get_token_price(contract: String) -> Result<f32, reqwest::Error> {
-    match moralis::get_token_price(contract).await {
-        Ok(price) => Ok(price.usd_price),
-        Err(e) => Err(e.without_url()),
+async fn to_owned_tokens(token_balances: Vec<MoralisTokenBalancesWithPrices>) -> Vec<OwnedToken> {
+    let mut tokens = vec![];
+
+    for token in token_balances {
+        let balance = token.balance.parse::<f64>().unwrap_or(0.0);
+
+        if balance == 0.0 {
+            continue;
+        }
+
+        tokens.push(OwnedToken {
+            name: token.name,
+            symbol: token.symbol,
+            thumbnail: token.thumbnail,
+            contract: token.token_address,
+            balance: balance / 10.0f64.powf(token.decimals as f64),
+            decimals: token.decimals,
+            value_usd: token.usd_value,
+            usd_price_24hr_percent_change: token.usd_price_24hr_percent_change,
+            portfolio_percentage: token.portfolio_percentage,
+        });
+    }
+
+    tokens
+}
+
+#[derive(Debug, Clone)]
+pub struct WhaleTransfer {
+    pub from: String,
+    pub to: String,
+    pub amount: f64,
+    pub value_usd: f64,
+    pub hash: String,
+    pub time_stamp: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct ExchangeFlow {
+    pub inflow_usd: f64,
+    pub outflow_usd: f64,
+}
+
+// recent transfers of the token worth at least min_value_usd, newest first
+pub async fn get_whale_transfers(
+    contract: String,
+    min_value_usd: f64,
+) -> Result<Vec<WhaleTransfer>, reqwest::Error> {
+    let token_price = get_token_price(contract.clone()).await? as f64;
+    let transfers = get_contract_token_transfers(contract).await?;
+
+    Ok(transfers
+        .into_iter()
+        .map(|transfer| to_whale_transfer(transfer, token_price))
+        .filter(|transfer| transfer.value_usd >= min_value_usd)
+        .collect())
+}
+
+fn to_whale_transfer(transfer: EtherscanTokenTransaction, token_price: f64) -> WhaleTransfer {
+    let decimals = transfer.token_decimal.parse::<i32>().unwrap_or(18);
+    let amount = transfer.value.parse::<f64>().unwrap_or(0.0) / 10.0f64.powi(decimals);
+
+    WhaleTransfer {
+        from: transfer.from,
+        to: transfer.to,
+        amount,
+        value_usd: amount * token_price,
+        hash: transfer.hash,
+        time_stamp: transfer.time_stamp.parse::<i64>().unwrap_or(0),
+    }
+}
+
+// tokens moving into exchanges are usually about to be sold, moving out means accumulation
+pub fn exchange_flow(transfers: &Vec<WhaleTransfer>) -> ExchangeFlow {
+    let mut flow = ExchangeFlow::default();
+
+    for transfer in transfers {
+        let from_exchange = matches!(known_label(&transfer.from), Some((_, LabelKind::Exchange)));
+        let to_exchange = matches!(known_label(&transfer.to), Some((_, LabelKind::Exchange)));
+
+        if to_exchange && !from_exchange {
+            flow.inflow_usd += transfer.value_usd;
+        } else if from_exchange && !to_exchange {
+            flow.outflow_usd += transfer.value_usd;
+        }
+    }
+
+    flow
+}
+
+pub async fn get_total_supply(contract: String) -> Result<u128, reqwest::Error> {
+    if let Some(total_supply) = TOTAL_SUPPLY_CACHE.get(&contract).await {
+        return Ok(total_supply);
+    }
+
+    fetch_total_supply(contract).await
+}
+
+// skips the cache, mints and burns have to be seen as soon as they happen
+pub async fn fetch_total_supply(contract: String) -> Result<u128, reqwest::Error> {
+    let total_supply = uint_from_word(
+        &eth_call(contract.clone(), abi::function_call("totalSupply()", &[])).await?,
+    );
+    TOTAL_SUPPLY_CACHE.insert(&contract, total_supply).await;
+
+    Ok(total_supply)
+}
+
+pub async fn get_token_balance(contract: String, wallet: String) -> Result<u128, reqwest::Error> {
+    Ok(uint_from_word(
+        &eth_call(
+            contract,
+            abi::function_call("balanceOf(address)", &[AbiToken::Address(wallet)]),
+        )
+        .await?,
+    ))
+}
+
+// liquidity counts both sides of the pool, the market cap is fully diluted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaunchMetrics {
+    pub liquidity_eth: f64,
+    pub liquidity_usd: f64,
+    pub fdv_usd: f64,
+}
+
+fn launch_metrics(
+    weth_reserve: u128,
+    token_reserve: u128,
+    total_supply: u128,
+    eth_price: f64,
+) -> Option<LaunchMetrics> {
+    if weth_reserve == 0 || token_reserve == 0 {
+        return None;
+    }
+
+    let liquidity_eth = weth_reserve as f64 / 10.0f64.powi(18) * 2.0;
+    // the supply and the reserve have the same decimals, so they cancel out
+    let fdv_eth = liquidity_eth / 2.0 * total_supply as f64 / token_reserve as f64;
+
+    Some(LaunchMetrics {
+        liquidity_eth,
+        liquidity_usd: liquidity_eth * eth_price,
+        fdv_usd: fdv_eth * eth_price,
+    })
+}
+
+// (WETH reserve, token reserve) of a Uniswap V2 pair, None when the pair isn't a WETH pair
+async fn get_weth_reserves(pair: String) -> Result<Option<(u128, u128)>, reqwest::Error> {
+    let (reserves, token0, token1) = tokio::join!(
+        eth_call(pair.clone(), abi::function_call("getReserves()", &[])),
+        eth_call(pair.clone(), abi::function_call("token0()", &[])),
+        eth_call(pair, abi::function_call("token1()", &[]))
+    );
+
+    let reserves = reserves?;
+    let reserves = reserves.trim_start_matches("0x");
+    if reserves.len() < 128 {
+        return Ok(None);
+    }
+    let reserve0 = uint_from_word(&reserves[..64]);
+    let reserve1 = uint_from_word(&reserves[64..128]);
+
+    let weth = Some(String::from(WETH_ADDRESS));
+    if address_from_word(&token0?) == weth {
+        Ok(Some((reserve0, reserve1)))
+    } else if address_from_word(&token1?) == weth {
+        Ok(Some((reserve1, reserve0)))
+    } else {
+        Ok(None)
+    }
+}
+
+// only WETH pairs can be valued, None for anything else
+pub async fn get_launch_metrics(
+    pair: String,
+    token: String,
+) -> Result<Option<LaunchMetrics>, reqwest::Error> {
+    let (reserves, total_supply, eth_price) = tokio::join!(
+        get_weth_reserves(pair),
+        get_total_supply(token),
+        get_eth_price()
+    );
+
+    let Some((weth_reserve, token_reserve)) = reserves? else {
+        return Ok(None);
+    };
+
+    Ok(launch_metrics(
+        weth_reserve,
+        token_reserve,
+        total_supply?,
+        eth_price?,
+    ))
+}
+
+// usd value of the WETH side of a pair, both sides of a V2 pool are worth the same
+pub async fn get_pool_depth_usd(pair: String) -> Result<Option<f64>, reqwest::Error> {
+    let (reserves, eth_price) = tokio::join!(get_weth_reserves(pair), get_eth_price());
+
+    match reserves? {
+        Some((weth_reserve, _)) => Ok(Some(weth_reserve as f64 / 10.0f64.powi(18) * eth_price?)),
+        None => Ok(None),
+    }
+}
+
+// share of the total supply held by the largest wallets, percentages from 0 to 100
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HolderConcentration {
+    pub top5_percent: f64,
+    pub top10_percent: f64,
+}
+
+// the pair's liquidity and tokens sitting in lockers or burned aren't held by anyone who can
+// sell them, so they don't count as concentration
+pub fn holder_concentration(
+    holders: &[ChainbaseTokenOwners],
+    total_supply: u128,
+    pair_address: Option<&str>,
+) -> HolderConcentration {
+    if total_supply == 0 {
+        return HolderConcentration::default();
+    }
+
+    let shares: Vec<f64> = holders
+        .iter()
+        .filter(|holder| is_holder(&holder.wallet_address, pair_address))
+        .map(|holder| {
+            holder.original_amount.parse::<f64>().unwrap_or(0.0) / total_supply as f64 * 100.0
+        })
+        .collect();
+
+    HolderConcentration {
+        top5_percent: shares.iter().take(5).sum(),
+        top10_percent: shares.iter().take(10).sum(),
+    }
+}
+
+// raw amounts, burned tokens don't count as circulating
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SupplyInfo {
+    pub total: u128,
+    pub circulating: u128,
+    pub decimals: u8,
+}
+
+impl SupplyInfo {
+    fn tokens(&self, raw: u128) -> f64 {
+        raw as f64 / 10.0f64.powi(self.decimals as i32)
+    }
+
+    pub fn total_tokens(&self) -> f64 {
+        self.tokens(self.total)
+    }
+
+    pub fn circulating_tokens(&self) -> f64 {
+        self.tokens(self.circulating)
+    }
+
+    pub fn market_cap(&self, price: f64) -> f64 {
+        self.circulating_tokens() * price
+    }
+
+    pub fn fdv(&self, price: f64) -> f64 {
+        self.total_tokens() * price
+    }
+
+    pub fn percent_of_supply(&self, balance: u128) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        balance as f64 / self.total as f64 * 100.0
+    }
+}
+
+pub async fn get_supply_info(contract: String, decimals: u8) -> Result<SupplyInfo, reqwest::Error> {
+    let total = get_total_supply(contract.clone()).await?;
+
+    let mut burned: u128 = 0;
+    for address in labels::addresses_of(LabelKind::Burn) {
+        burned += get_token_balance(contract.clone(), address).await?;
+    }
+
+    Ok(SupplyInfo {
+        total,
+        circulating: total.saturating_sub(burned),
+        decimals,
+    })
+}
+
+pub async fn get_holder_concentration(
+    contract: String,
+) -> Result<HolderConcentration, reqwest::Error> {
+    let holders = get_top_token_holders(contract.clone()).await?;
+    let total_supply = get_total_supply(contract.clone()).await?;
+    let pair_address = get_token_info(contract)
+        .await
+        .ok()
+        .map(|token_info| token_info.pair_address);
+
+    Ok(holder_concentration(
+        &holders,
+        total_supply,
+        pair_address.as_deref(),
+    ))
+}
+
+#[derive(Debug, Clone)]
+pub struct ConcentrationSnapshot {
+    pub taken_at: DateTime<Utc>,
+    pub concentration: HolderConcentration,
+    // share of the supply held by the contract creator
+    pub deployer_percent: Option<f64>,
+}
+
+pub async fn concentration_history(contract: &str) -> Vec<ConcentrationSnapshot> {
+    CONCENTRATION_HISTORY
+        .lock()
+        .await
+        .get(&contract.to_lowercase())
+        .map(|history| history.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[derive(Debug)]
+pub struct OwnedToken {
+    pub name: String,
+    pub contract: String,
+    pub thumbnail: Option<String>,
+    pub symbol: String,
+    pub balance: f64,
+    pub decimals: u8,
+    pub value_usd: f64,
+    pub usd_price_24hr_percent_change: f32,
+    pub portfolio_percentage: f32,
+}
+
+// Uniswap V2 Swap(address,uint256,uint256,uint256,uint256,address) event signature
+const UNISWAP_V2_SWAP_TOPIC: &str =
+    "0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822";
+pub const WETH_ADDRESS: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+// how many transactions before and after the swap are checked for a front/backrun
+const SANDWICH_SEARCH_DEPTH: usize = 3;
+
+#[derive(Debug, Clone)]
+struct PairSwap {
+    pair: String,
+    amount0_in: u128,
+    amount1_in: u128,
+    amount0_out: u128,
+    amount1_out: u128,
+}
+
+impl PairSwap {
+    fn from_log(log: &AlchemyLog) -> Option<PairSwap> {
+        if log.topics.first()?.to_lowercase() != UNISWAP_V2_SWAP_TOPIC {
+            return None;
+        }
+
+        let data = log.data.trim_start_matches("0x");
+        if data.len() < 64 * 4 {
+            return None;
+        }
+
+        Some(PairSwap {
+            pair: log.address.to_lowercase(),
+            amount0_in: uint_from_word(&data[0..64]),
+            amount1_in: uint_from_word(&data[64..128]),
+            amount0_out: uint_from_word(&data[128..192]),
+            amount1_out: uint_from_word(&data[192..256]),
+        })
+    }
+
+    fn is_token0_in(&self) -> bool {
+        self.amount0_in > 0
+    }
+}
+
+fn find_pair_swap(receipt: &AlchemyTransactionReceipt, pair: Option<&str>) -> Option<PairSwap> {
+    receipt
+        .logs
+        .iter()
+        .filter_map(PairSwap::from_log)
+        .find(|swap| pair.map_or(true, |pair| swap.pair == pair))
+}
+
+#[derive(Debug)]
+pub struct Sandwich {
+    pub pair: String,
+    pub attacker: String,
+    pub frontrun_hash: String,
+    pub backrun_hash: String,
+    pub estimated_loss_eth: Option<f64>,
+}
+
+#[derive(Debug)]
+pub enum SandwichCheck {
+    NotFound,
+    NotASwap,
+    Clean,
+    Sandwiched(Sandwich),
+}
+
+pub async fn detect_sandwich(tx_hash: String) -> Result<SandwichCheck, reqwest::Error> {
+    let receipt = match get_transaction_receipt(tx_hash).await? {
+        Some(receipt) => receipt,
+        None => return Ok(SandwichCheck::NotFound),
+    };
+
+    let my_swap = match find_pair_swap(&receipt, None) {
+        Some(swap) => swap,
+        None => return Ok(SandwichCheck::NotASwap),
+    };
+
+    let transactions = get_block_transactions(receipt.block_number.clone()).await?;
+    let index = hex_to_decimal(&receipt.transaction_index) as usize;
+
+    let before = &transactions[index.saturating_sub(SANDWICH_SEARCH_DEPTH)..index];
+    let after = &transactions[(index + 1).min(transactions.len())
+        ..(index + 1 + SANDWICH_SEARCH_DEPTH).min(transactions.len())];
+
+    for front in before.iter().rev() {
+        for back in after {
+            // the attacker uses the same account or the same bot contract for both legs
+            let same_sender = front.from.to_lowercase() == back.from.to_lowercase();
+            let same_contract = front.to.is_some() && front.to == back.to && front.to != receipt.to;
+
+            if !same_sender && !same_contract {
+                continue;
+            }
+
+            let front_swap = match get_transaction_receipt(front.hash.clone()).await? {
+                Some(receipt) => find_pair_swap(&receipt, Some(&my_swap.pair)),
+                None => None,
+            };
+            let back_swap = match get_transaction_receipt(back.hash.clone()).await? {
+                Some(receipt) => find_pair_swap(&receipt, Some(&my_swap.pair)),
+                None => None,
+            };
+
+            if let (Some(front_swap), Some(back_swap)) = (front_swap, back_swap) {
+                if front_swap.is_token0_in() == my_swap.is_token0_in()
+                    && back_swap.is_token0_in() != my_swap.is_token0_in()
+                {
+                    return Ok(SandwichCheck::Sandwiched(Sandwich {
+                        pair: my_swap.pair.clone(),
+                        attacker: front.from.clone(),
+                        frontrun_hash: front.hash.clone(),
+                        backrun_hash: back.hash.clone(),
+                        estimated_loss_eth: estimate_sandwich_loss(&front_swap, &back_swap).await,
+                    }));
+                }
+            }
+        }
+    }
+
+    Ok(SandwichCheck::Clean)
+}
+
+// the attacker's profit is roughly what the victim lost to the worse execution price,
+// it can only be expressed in eth if the attacker paid with WETH
+async fn estimate_sandwich_loss(front_swap: &PairSwap, back_swap: &PairSwap) -> Option<f64> {
+    // token0() selector
+    let token0 = match eth_call(front_swap.pair.clone(), String::from("0x0dfe1681")).await {
+        Ok(word) => format!("0x{}", &word[word.len().saturating_sub(40)..]).to_lowercase(),
+        Err(e) => {
+            error!("eth_call error: {}", e);
+            return None;
+        }
+    };
+
+    let (paid, received) = if front_swap.is_token0_in() {
+        (front_swap.amount0_in, back_swap.amount0_out)
+    } else {
+        (front_swap.amount1_in, back_swap.amount1_out)
+    };
+
+    let paid_with_weth = (token0 == WETH_ADDRESS) == front_swap.is_token0_in();
+    if !paid_with_weth {
+        return None;
+    }
+
+    let profit = received as i128 - paid as i128;
+    Some(profit.max(0) as f64 / 10.0f64.powf(18.0))
+}
+
+pub async fn watch_wallets(bot: Bot) {
+    let mut last_transaction_timestamps = HashMap::<ChatId, HashMap<String, u64>>::new();
+    // deployments aren't token transfers, so they're tracked separately and only for wallets filtering on them
+    let mut last_deployment_timestamps = HashMap::<(ChatId, String), u64>::new();
+    let mut schedule = PollSchedule::<(ChatId, String)>::default();
+
+    loop {
+        sleep(Duration::try_minutes(1).unwrap().to_std().unwrap()).await;
+        info!("New watch wallets cycle...");
+        tasks::heartbeat("watch_wallets").await;
+        *LAST_SEEN_TRANSACTIONS.lock().await = last_transaction_timestamps.clone();
+
+        let watched_wallets_guard = WATCHED_WALLETS.lock().await;
+        let mut watched_wallets = watched_wallets_guard.clone();
+        drop(watched_wallets_guard);
+        shards::retain_owned(&mut watched_wallets).await;
+
+        if watched_wallets.is_empty() {
+            continue;
+        };
+
+        if last_transaction_timestamps.is_empty() {
+            get_last_token_transaction_timestamps(
+                &watched_wallets,
+                &mut last_transaction_timestamps,
+            )
+            .await;
+        } else {
+            let now = Utc::now();
+            let budget_factor = polling::budget_factor(&usage::usage().await, now);
+            schedule.retain(|(chat_id, wallet)| {
+                watched_wallets
+                    .get(chat_id)
+                    .is_some_and(|wallets| wallets.contains(wallet))
+            });
+
+            for (chat_id, wallets) in watched_wallets {
+                let mut cycle_transactions = vec![];
+
+                for wallet in wallets {
+                    if !schedule.is_due(&(chat_id, wallet.clone()), now) {
+                        continue;
+                    }
+                    let filter = alerts::wallet_filter(chat_id, &wallet).await;
+
+                    if filter.deployments {
+                        let last_deployment = last_deployment_timestamps
+                            .entry((chat_id, wallet.to_lowercase()))
+                            .or_insert(Utc::now().timestamp() as u64);
+
+                        for deployment in get_new_deployments(wallet.to_owned(), last_deployment)
+                            .await
+                            .iter()
+                            .rev()
+                        {
+                            let _ = bot::watched_wallet_deployment_notification(
+                                &bot, chat_id, &wallet, deployment,
+                            )
+                            .await;
+                            expiry::touch(chat_id, WatchKind::Wallet, &wallet).await;
+                        }
+                    }
+
+                    match get_new_token_transactions(
+                        wallet.to_owned(),
+                        last_transaction_timestamps
+                            .get(&chat_id)
+                            .unwrap()
+                            .get(&wallet)
+                            .unwrap_or(&0),
+                    )
+                    .await
+                    {
+                        Some((transactions, truncated)) => {
+                            update_timestamps(
+                                &mut last_transaction_timestamps,
+                                chat_id,
+                                wallet.to_owned(),
+                                transactions[0].time_stamp.parse::<u64>().unwrap_or(0),
+                            );
+
+                            if truncated {
+                                let _ = bot::truncated_transactions_notification(
+                                    &bot,
+                                    chat_id,
+                                    &wallet,
+                                    transactions.len(),
+                                )
+                                .await;
+                            }
+
+                            // the wallet's token set is seeded from its history the first time it receives a token
+                            let mut history = None;
+                            let receives_tokens = transactions
+                                .iter()
+                                .any(|t| t.to.to_lowercase() == wallet.to_lowercase());
+                            if receives_tokens && !tokensets::is_seeded(&wallet).await {
+                                let seeding = get_token_transactions(wallet.to_owned())
+                                    .await
+                                    .unwrap_or_default();
+                                // newest first, the new transfers aren't part of what it held
+                                let oldest_new = transactions
+                                    .last()
+                                    .and_then(|t| t.time_stamp.parse::<u64>().ok())
+                                    .unwrap_or(0);
+                                tokensets::seed(&wallet, &seeding, oldest_new).await;
+                                history = Some(seeding);
+                            }
+
+                            let mut first_seen = vec![];
+                            for transaction in transactions.iter().rev() {
+                                first_seen.push(tokensets::saw(&wallet, transaction).await);
+                            }
+
+                            // only filtered wallets and first buys need the extra requests to decode their activity
+                            let history = match history {
+                                Some(history) => history,
+                                None if !filter.filters_activity()
+                                    && !first_seen.contains(&true) =>
+                                {
+                                    vec![]
+                                }
+                                None => get_token_transactions(wallet.to_owned())
+                                    .await
+                                    .unwrap_or_default(),
+                            };
+
+                            let held = if filter.held_tokens {
+                                get_held_tokens().await
+                            } else {
+                                None
+                            };
+
+                            for (transaction, first_seen) in
+                                transactions.iter().rev().zip(first_seen)
+                            {
+                                if !filter
+                                    .allows_token(&transaction.contract_address, held.as_deref())
+                                {
+                                    continue;
+                                }
+
+                                let activity = if !filter.filters_activity() {
+                                    // unfiltered wallets are only labelled when they buy a new token
+                                    // or move tokens to or from an exchange
+                                    if first_seen {
+                                        Some(
+                                            classify_token_transaction(
+                                                &wallet,
+                                                transaction,
+                                                &history,
+                                                first_seen,
+                                            )
+                                            .await,
+                                        )
+                                        .filter(|a| *a == WalletActivity::NewTokenBuy)
+                                    } else {
+                                        Some(transfer_activity(&wallet, transaction).await)
+                                            .filter(|a| a.is_exchange_transfer())
+                                    }
+                                } else {
+                                    let activity = classify_token_transaction(
+                                        &wallet,
+                                        transaction,
+                                        &history,
+                                        first_seen,
+                                    )
+                                    .await;
+                                    let value_usd = match filter.min_transfer_usd {
+                                        Some(_) if activity.is_transfer() => {
+                                            Some(transfer_value_usd(transaction).await)
+                                        }
+                                        _ => None,
+                                    };
+
+                                    if !filter.allows(activity, value_usd) {
+                                        continue;
+                                    }
+                                    Some(activity)
+                                };
+
+                                cycle_transactions.push(WalletTransaction {
+                                    wallet: wallet.clone(),
+                                    transaction: transaction.clone(),
+                                    activity,
+                                });
+                            }
+                        }
+                        None => {}
+                    }
+
+                    // the newest transfer seen, new or not, sets how soon the wallet is polled again
+                    let last_activity = last_transaction_timestamps
+                        .get(&chat_id)
+                        .and_then(|timestamps| timestamps.get(&wallet))
+                        .copied()
+                        .unwrap_or(0);
+                    schedule.polled(
+                        (chat_id, wallet.clone()),
+                        last_activity as i64,
+                        now,
+                        budget_factor,
+                    );
+                }
+
+                let (convergent, singles) = convergence::group_convergent_buys(cycle_transactions);
+                for buy in &convergent {
+                    let _ = bot::convergent_buy_notification(&bot, chat_id, buy).await;
+                    for wallet_buy in &buy.buys {
+                        expiry::touch(chat_id, WatchKind::Wallet, &wallet_buy.wallet).await;
+                    }
+                }
+                for single in singles {
+                    let _ = bot::watched_wallet_notification(
+                        &bot,
+                        chat_id,
+                        &single.wallet,
+                        &single.transaction,
+                        single.activity,
+                    )
+                    .await;
+                    expiry::touch(chat_id, WatchKind::Wallet, &single.wallet).await;
+                }
+            }
+        }
+    }
+}
+
+// the transaction the wallet sent tells what it called, history is the wallet's recent token transfers.
+// first_seen is whether the token is new to the wallet's token set
+async fn classify_token_transaction(
+    wallet: &str,
+    transaction: &EtherscanTokenTransaction,
+    history: &[EtherscanTokenTransaction],
+    first_seen: bool,
+) -> WalletActivity {
+    let wallet = wallet.to_lowercase();
+    let hash = transaction.hash.to_lowercase();
+
+    let called_swap = match get_normal_transactions(wallet.clone()).await {
+        Ok(transactions) => transactions
+            .iter()
+            .find(|t| t.hash.to_lowercase() == hash)
+            .is_some_and(|t| is_swap_function(&t.function_name)),
+        Err(e) => {
+            error!("get_normal_transactions error: {}", e);
+            false
+        }
+    };
+
+    // tokens going both in and out of the wallet in one transaction is a swap, whatever was called
+    let same_transaction: Vec<&EtherscanTokenTransaction> = history
+        .iter()
+        .filter(|t| t.hash.to_lowercase() == hash)
+        .collect();
+    let sent = same_transaction
+        .iter()
+        .any(|t| t.from.to_lowercase() == wallet);
+    let received = same_transaction
+        .iter()
+        .any(|t| t.to.to_lowercase() == wallet);
+
+    if !called_swap && !(sent && received) {
+        return transfer_activity(&wallet, transaction).await;
+    }
+
+    if transaction.to.to_lowercase() == wallet && first_seen {
+        WalletActivity::NewTokenBuy
+    } else {
+        WalletActivity::Swap
+    }
+}
+
+// tokens sent to an exchange's deposit address are likely sold there, tokens from its hot wallet were withdrawn
+async fn transfer_activity(
+    wallet: &str,
+    transaction: &EtherscanTokenTransaction,
+) -> WalletActivity {
+    if transaction.from.to_lowercase() == wallet.to_lowercase() {
+        if get_exchange_deposit(&transaction.to).await.is_some() {
+            return WalletActivity::CexDeposit;
+        }
+    } else if exchange_label(&transaction.from).is_some() {
+        return WalletActivity::CexWithdrawal;
+    }
+
+    WalletActivity::Transfer
+}
+
+fn exchange_label(address: &str) -> Option<String> {
+    known_label(address)
+        .filter(|(_, kind)| *kind == LabelKind::Exchange)
+        .map(|(name, _)| name)
+}
+
+// the exchange the address belongs to, its hot wallets or a deposit address sweeping to them
+pub async fn get_exchange_deposit(address: &str) -> Option<String> {
+    if let Some(exchange) = exchange_label(address) {
+        return Some(exchange);
+    }
+
+    if let Some(exchange) = EXCHANGE_DEPOSIT_CACHE.get(address).await {
+        return exchange;
+    }
+
+    match get_token_transactions(address.to_owned()).await {
+        Ok(transfers) => {
+            let exchange = deposits::swept_to(address, &transfers, exchange_label);
+
+            EXCHANGE_DEPOSIT_CACHE
+                .insert(address, exchange.clone())
+                .await;
+            exchange
+        }
+        Err(e) => {
+            error!("get_exchange_deposit error: {}", e);
+            None
+        }
+    }
+}
+
+fn is_swap_function(function_name: &str) -> bool {
+    let name = function_name.to_lowercase();
+
+    // the Universal Router and most aggregators name their entrypoint execute or multicall
+    name.starts_with("swap") || name.starts_with("execute") || name.starts_with("multicall")
+}
+
+pub async fn transfer_value_usd(transaction: &EtherscanTokenTransaction) -> f64 {
+    let token_price = get_token_price(transaction.contract_address.clone())
+        .await
+        .unwrap_or(0.0) as f64;
+
+    to_whale_transfer(transaction.clone(), token_price).value_usd
+}
+
+// only covers the latest transfers etherscan returns, so older buys have no cost basis
+pub async fn get_wallet_profit(wallet: String, days: i64) -> Result<WalletProfit, reqwest::Error> {
+    let (token_transactions, normal_transactions, internal_transactions) = tokio::join!(
+        get_token_transactions(wallet.clone()),
+        get_normal_transactions(wallet.clone()),
+        get_internal_transactions(wallet.clone(), 100)
+    );
+    let since = (Utc::now() - Duration::try_days(days).unwrap()).timestamp() as u64;
+
+    Ok(profits::realized_profit(
+        &wallet,
+        &token_transactions?,
+        &normal_transactions?,
+        &internal_transactions?,
+        since,
+    ))
+}
+
+// only covers the latest transfers etherscan returns, a busy wallet's older buys are left out
+pub async fn get_recent_buys(wallet: String, days: i64) -> Result<Vec<RecentBuy>, reqwest::Error> {
+    let (token_transactions, normal_transactions) = tokio::join!(
+        get_token_transactions(wallet.clone()),
+        get_normal_transactions(wallet.clone())
+    );
+    let since = (Utc::now() - Duration::try_days(days).unwrap()).timestamp() as u64;
+
+    Ok(profits::recent_buys(
+        &wallet,
+        &token_transactions?,
+        &normal_transactions?,
+        since,
+    ))
+}
+
+pub async fn get_recent_buy_price(contract: String) -> Result<f32, reqwest::Error> {
+    if let Some(price) = RECENT_BUY_PRICE_CACHE.get(&contract).await {
+        return Ok(price);
+    }
+
+    let price = get_token_price(contract.clone()).await?;
+    RECENT_BUY_PRICE_CACHE.insert(&contract, price).await;
+
+    Ok(price)
+}
+
+// enough for a month of trading, older transactions of busier wallets are left out
+const GAS_HISTORY_TRANSACTIONS: u16 = 1_000;
+
+// gas the wallets paid in each period of days, e.g. &[1, 7, 30]
+pub async fn get_gas_spent(
+    wallets: Vec<String>,
+    periods: &[i64],
+) -> Result<Vec<GasSpent>, reqwest::Error> {
+    let mut transactions: Vec<EtherscanNormalTransaction> = vec![];
+    for wallet in &wallets {
+        match EtherscanAPI::<Vec<EtherscanNormalTransaction>>::get_normal_transactions(
+            wallet.clone(),
+            GAS_HISTORY_TRANSACTIONS,
+        )
+        .await
+        {
+            Ok(response) => transactions.extend(response.result),
+            Err(e) => return Err(e.without_url()),
+        }
+    }
+
+    Ok(periods
+        .iter()
+        .map(|days| {
+            let since = (Utc::now() - Duration::try_days(*days).unwrap()).timestamp() as u64;
+            gasspent::gas_spent(&wallets, &transactions, since)
+        })
+        .collect())
+}
+
+// contract creations sent by the wallet after the timestamp, which is moved to the newest one
+async fn get_new_deployments(
+    wallet: String,
+    last_time_stamp: &mut u64,
+) -> Vec<EtherscanNormalTransaction> {
+    let transactions = match get_normal_transactions(wallet).await {
+        Ok(transactions) => transactions,
+        Err(e) => {
+            error!("get_normal_transactions error: {}", e);
+            return vec![];
+        }
+    };
+
+    let deployments: Vec<EtherscanNormalTransaction> = transactions
+        .into_iter()
+        .filter(|t| t.to.is_empty() && !t.contract_address.is_empty())
+        .filter(|t| t.time_stamp.parse::<u64>().unwrap_or(0) > *last_time_stamp)
+        .collect();
+
+    if let Some(newest) = deployments.first() {
+        *last_time_stamp = newest.time_stamp.parse::<u64>().unwrap_or(*last_time_stamp);
+    }
+
+    deployments
+}
+
+// watched wallets and, for the owner chat, the bot's own trading wallets
+pub async fn watch_approvals(bot: Bot) {
+    // (chat, lowercase wallet) -> timestamp of the newest checked transaction
+    let mut last_time_stamps = HashMap::<(ChatId, String), u64>::new();
+
+    loop {
+        sleep(Duration::try_minutes(1).unwrap().to_std().unwrap()).await;
+        info!("New watch approvals cycle...");
+        tasks::heartbeat("watch_approvals").await;
+
+        // (chat, lowercase wallet) -> whether it's one of the bot's own wallets
+        let mut wallets = HashMap::<(ChatId, String), bool>::new();
+        let mut watched_wallets = WATCHED_WALLETS.lock().await.clone();
+        shards::retain_owned(&mut watched_wallets).await;
+        for (chat_id, watched) in watched_wallets {
+            for wallet in watched {
+                wallets.insert((chat_id, wallet.to_lowercase()), false);
+            }
+        }
+        // the bot's own wallets are watched once, by the primary
+        if let Some(owner_chat) = bot::owner_chat().filter(|_| shards::shard().is_primary()) {
+            for wallet in wallet::trading_wallets() {
+                wallets.insert((owner_chat, wallet), true);
+            }
+        }
+        last_time_stamps.retain(|key, _| wallets.contains_key(key));
+
+        for ((chat_id, wallet), own) in wallets {
+            let last_time_stamp = last_time_stamps
+                .entry((chat_id, wallet.clone()))
+                .or_insert(Utc::now().timestamp() as u64);
+
+            for approval in get_suspicious_approvals(wallet.clone(), last_time_stamp)
+                .await
+                .iter()
+                .rev()
+            {
+                let _ =
+                    bot::approval_warning_notification(&bot, chat_id, &wallet, approval, own).await;
+            }
+        }
+    }
+}
+
+// polls the pending block for the liquidity adds of the launches registered with /launchsnipe
+pub async fn watch_launches(bot: Bot) {
+    loop {
+        sleep(Duration::try_seconds(2).unwrap().to_std().unwrap()).await;
+        tasks::heartbeat("watch_launches").await;
+
+        let Some(owner_chat) = bot::owner_chat() else {
+            continue;
+        };
+        // registered launches are kept but not sniped until the owner opts in and sets the key
+        if !SETTINGS.get(owner_chat).await.same_block_snipes {
+            continue;
+        }
+        let Some(key) = wallet::launch_snipe_key() else {
+            continue;
+        };
+
+        let snipes = {
+            let mut snipes = launches::LAUNCH_SNIPES.lock().await;
+            snipes.retain(|snipe| !snipe.is_expired(Utc::now()));
+            snipes.clone()
+        };
+        if snipes.is_empty() {
+            continue;
+        }
+
+        let (block_number, transactions) = match get_pending_block().await {
+            Ok(block) => block,
+            Err(e) => {
+                error!("get_pending_block error: {}", e);
+                continue;
+            }
+        };
+
+        for snipe in snipes {
+            let Some(add) = transactions.iter().find_map(|transaction| {
+                launches::decode_liquidity_add(transaction, &snipe.contract)
+            }) else {
+                continue;
+            };
+
+            // one shot, a skipped buy isn't retried in a later block
+            launches::LAUNCH_SNIPES
+                .lock()
+                .await
+                .retain(|s| s.contract != snipe.contract);
+
+            let backrun = launches::backrun(&snipe, &add, block_number, &key).await;
+            if let Err(e) =
+                bot::launch_backrun_notification(&bot, owner_chat, &snipe, &add, &backrun).await
+            {
+                error!("failed to send the launch backrun notification: {}", e);
+            }
+        }
+    }
+}
+
+// approvals to unverified contracts or wallets outside the trusted routers, what drainer sites ask for
+async fn get_suspicious_approvals(wallet: String, last_time_stamp: &mut u64) -> Vec<Approval> {
+    let transactions = match get_normal_transactions(wallet.clone()).await {
+        Ok(transactions) => transactions,
+        Err(e) => {
+            error!("get_normal_transactions error: {}", e);
+            return vec![];
+        }
+    };
+
+    let new_transactions: Vec<&EtherscanNormalTransaction> = transactions
+        .iter()
+        .filter(|t| t.time_stamp.parse::<u64>().unwrap_or(0) > *last_time_stamp)
+        .collect();
+
+    if let Some(newest) = new_transactions.first() {
+        *last_time_stamp = newest.time_stamp.parse::<u64>().unwrap_or(*last_time_stamp);
+    }
+
+    let mut suspicious = vec![];
+    for approval in new_transactions
+        .into_iter()
+        .filter_map(|t| approvals::decode_approval(&wallet, t))
+    {
+        if approvals::is_trusted_spender(&approval.spender) {
+            continue;
+        }
+
+        // verified contracts have a name on etherscan, wallets and unverified contracts don't
+        if get_address_label(&approval.spender).await.is_none() {
+            suspicious.push(approval);
+        }
+    }
+
+    suspicious
+}
+
+// transfers out of a top holder wallet below this are ignored
+const TOP_HOLDER_SELL_USD: f64 = 5_000.0;
+
+#[derive(Debug, Clone)]
+pub enum TokenActivity {
+    NewLargeHolder { holder: String, value_usd: f64 },
+    TopHolderSell(WhaleTransfer),
+    CreatorMovement(WhaleTransfer),
+    // fresh wallets funded from the same source buying together, a common insider pattern
+    ClusterBuy(ClusterBuy),
+    // taxes, owner, liquidity or holder concentration moved since the last alert
+    Change(TokenChange),
+    // WETH leaving one of the token's pools, and where it went if another one received it
+    Liquidity(LiquidityMove),
+}
+
+#[derive(Debug, Default)]
+struct WatchedTokenState {
+    last_time_stamp: i64,
+    top_holders: HashSet<String>,
+    creator: Option<String>,
+    clusters: ClusterTracker,
+    readings: TokenReadings,
+    pools: PoolTracker,
+}
+
+pub async fn watch_tokens(bot: Bot) {
+    // shared between chats, every token is only fetched once per cycle
+    let mut states = HashMap::<String, WatchedTokenState>::new();
+    let mut schedule = PollSchedule::<String>::default();
+
+    loop {
+        sleep(Duration::try_minutes(1).unwrap().to_std().unwrap()).await;
+        info!("New watch tokens cycle...");
+        tasks::heartbeat("watch_tokens").await;
+
+        let mut watched_tokens = WATCHED_TOKENS.lock().await.clone();
+        shards::retain_owned(&mut watched_tokens).await;
+
+        let contracts: HashSet<String> = watched_tokens
+            .values()
+            .flatten()
+            .map(|contract| contract.to_lowercase())
+            .collect();
+        states.retain(|contract, _| contracts.contains(contract));
+        schedule.retain(|contract| contracts.contains(contract));
+        let now = Utc::now();
+        let budget_factor = polling::budget_factor(&usage::usage().await, now);
+
+        for contract in contracts {
+            if !schedule.is_due(&contract, now) {
+                continue;
+            }
+
+            let activities = match states.get_mut(&contract) {
+                Some(state) => {
+                    let activities = get_token_activity(&contract, state).await;
+                    schedule.polled(contract.clone(), state.last_time_stamp, now, budget_factor);
+                    activities
+                }
+                None => {
+                    states.insert(contract.clone(), init_watched_token(&contract).await);
+                    continue;
+                }
+            };
+
+            for (chat_id, tokens) in &watched_tokens {
+                if !tokens.iter().any(|token| token.to_lowercase() == contract) {
+                    continue;
+                }
+
+                for activity in &activities {
+                    let _ =
+                        bot::watched_token_notification(&bot, *chat_id, &contract, activity).await;
+                }
+                if !activities.is_empty() {
+                    expiry::touch(*chat_id, WatchKind::Token, &contract).await;
+                }
+            }
+        }
+    }
+}
+
+async fn init_watched_token(contract: &str) -> WatchedTokenState {
+    let (transfers, holders, creator) = tokio::join!(
+        get_contract_token_transfers(contract.to_owned()),
+        get_top_token_holders(contract.to_owned()),
+        get_contract_creator_and_tx_hash(vec![contract.to_owned()])
+    );
+
+    WatchedTokenState {
+        last_time_stamp: transfers
+            .ok()
+            .and_then(|transfers| transfers.first().map(|t| t.time_stamp.parse().unwrap_or(0)))
+            .unwrap_or(0),
+        top_holders: holders
+            .map(|holders| {
+                holders
+                    .into_iter()
+                    .map(|holder| holder.wallet_address.to_lowercase())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        creator: creator.ok().and_then(|creators| {
+            creators
+                .first()
+                .map(|creator| creator.contract_creator.to_lowercase())
+        }),
+        clusters: ClusterTracker::default(),
+        readings: TokenReadings::default(),
+        pools: PoolTracker::default(),
+    }
+}
+
+async fn get_token_activity(contract: &str, state: &mut WatchedTokenState) -> Vec<TokenActivity> {
+    let mut activities = vec![];
+
+    let (transfers, holders, token_price) = tokio::join!(
+        get_contract_token_transfers(contract.to_owned()),
+        get_top_token_holders(contract.to_owned()),
+        get_token_price(contract.to_owned())
+    );
+    let token_price = token_price.unwrap_or(0.0) as f64;
+
+    match transfers {
+        Ok(transfers) => {
+            let new_transfers: Vec<WhaleTransfer> = transfers
+                .into_iter()
+                .map(|transfer| to_whale_transfer(transfer, token_price))
+                .filter(|transfer| transfer.time_stamp > state.last_time_stamp)
+                .collect();
+
+            if let Some(latest) = new_transfers.first() {
+                state.last_time_stamp = latest.time_stamp;
+            }
+
+            let mut buyers = vec![];
+            for transfer in new_transfers.into_iter().rev() {
+                let from = transfer.from.to_lowercase();
+                let to = transfer.to.to_lowercase();
+
+                // pairs receive tokens on every sell, but they never look like fresh wallets
+                if state.creator.as_ref() != Some(&from) && known_label(&to).is_none() {
+                    buyers.push((to.clone(), transfer.time_stamp));
+                }
+
+                if state
+                    .creator
+                    .as_ref()
+                    .is_some_and(|creator| *creator == from || *creator == to)
+                {
+                    activities.push(TokenActivity::CreatorMovement(transfer));
+                } else if state.top_holders.contains(&from)
+                    && transfer.value_usd >= TOP_HOLDER_SELL_USD
+                {
+                    activities.push(TokenActivity::TopHolderSell(transfer));
+                }
+            }
+
+            for cluster in state.clusters.record_buys(buyers).await {
+                activities.push(TokenActivity::ClusterBuy(cluster));
+            }
+        }
+        Err(e) => error!("get_contract_token_transfers error: {}", e),
+    }
+
+    let readings = token_readings(contract, holders.as_deref().ok()).await;
+    let changes = state.readings.update(readings);
+
+    let pair_address = get_token_info(contract.to_owned())
+        .await
+        .ok()
+        .map(|token_info| token_info.pair_address);
+    let pools = migrations::token_pools(contract, pair_address.as_deref()).await;
+    let moves = state
+        .pools
+        .update(migrations::pool_weth(&pools).await, Utc::now().timestamp());
+
+    // the pool moves say more than honeypot.is' liquidity of whichever pool it picked
+    activities.extend(
+        changes
+            .into_iter()
+            .filter(|change| moves.is_empty() || !matches!(change, TokenChange::Liquidity { .. }))
+            .map(TokenActivity::Change),
+    );
+    activities.extend(moves.into_iter().map(TokenActivity::Liquidity));
+
+    match holders {
+        Ok(holders) => {
+            // otherwise every buy out of the pair would be a top holder selling
+            let top_holders: HashSet<String> = holders
+                .iter()
+                .map(|holder| holder.wallet_address.to_lowercase())
+                .filter(|address| is_holder(address, pair_address.as_deref()))
+                .collect();
+
+            // the first snapshot might have failed, don't report every holder as new
+            if !state.top_holders.is_empty() {
+                for holder in &holders {
+                    let address = holder.wallet_address.to_lowercase();
+
+                    // pairs, lockers, burn addresses and exchanges entering the top isn't interesting
+                    if state.top_holders.contains(&address)
+                        || !top_holders.contains(&address)
+                        || known_label(&address).is_some()
+                    {
+                        continue;
+                    }
+
+                    activities.push(TokenActivity::NewLargeHolder {
+                        holder: holder.wallet_address.clone(),
+                        value_usd: holder.usd_value.parse::<f64>().unwrap_or(0.0),
+                    });
+                }
+            }
+
+            state.top_holders = top_holders;
+        }
+        Err(e) => error!("get_top_token_holders error: {}", e),
+    }
+
+    activities
+}
+
+// the token's pair, lockers and burn addresses hold a large share of most tokens, but they
+// don't buy or sell like holders do
+fn is_holder(address: &str, pair_address: Option<&str>) -> bool {
+    !pair_address.is_some_and(|pair| pair.eq_ignore_ascii_case(address))
+        && !matches!(
+            known_label(address),
+            Some((_, LabelKind::Locker | LabelKind::Burn))
+        )
+}
+
+// the first cycle of a token only sets what the next ones are compared with
+async fn token_readings(contract: &str, holders: Option<&[ChainbaseTokenOwners]>) -> TokenReadings {
+    let (token_info, owner, total_supply) = tokio::join!(
+        get_token_info(contract.to_owned()),
+        get_token_owner(contract.to_owned()),
+        get_total_supply(contract.to_owned())
+    );
+    let token_info = token_info.ok();
+    let pair_address = token_info
+        .as_ref()
+        .map(|token_info| token_info.pair_address.clone());
+
+    TokenReadings {
+        taxes: token_info
+            .as_ref()
+            .map(|token_info| (token_info.buy_tax as f64, token_info.sell_tax as f64)),
+        // most tokens without an owner() revert, there's nothing to report for them
+        owner: owner.ok().flatten(),
+        liquidity_usd: token_info.map(|token_info| token_info.liquidity as f64),
+        top10_percent: match (holders, total_supply) {
+            (Some(holders), Ok(total_supply)) if total_supply > 0 => Some(
+                holder_concentration(holders, total_supply, pair_address.as_deref()).top10_percent,
+            ),
+            _ => None,
+        },
+    }
+}
+
+// the owner() of an Ownable token, the zero address once it's renounced
+pub async fn get_token_owner(contract: String) -> Result<Option<String>, reqwest::Error> {
+    let word = eth_call(contract, abi::function_call("owner()", &[])).await?;
+    if word.trim_start_matches("0x").len() != 64 {
+        return Ok(None);
     }
+
+    Ok(Some(
+        address_from_word(&word).unwrap_or(String::from(tokenchanges::ZERO_ADDRESS)),
+    ))
 }
 
-pub async fn get_token_info(contract: String) -> Result<HoneypotTokenInfo, reqwest::Error> {
-    match honeypot::get_token_info(contract).await {
-        Ok(token_info) => Ok(token_info),
+// bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)
+const EIP1967_IMPLEMENTATION_SLOT: &str =
+    "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+
+// None if the contract isn't an EIP-1967 proxy
+pub async fn get_proxy_implementation(contract: String) -> Result<Option<String>, reqwest::Error> {
+    match AlchemyAPI::<String>::get_storage_at(contract, String::from(EIP1967_IMPLEMENTATION_SLOT))
+        .await
+    {
+        Ok(response) => Ok(address_from_word(&response.result)),
         Err(e) => Err(e.without_url()),
     }
 }
 
-pub async fn get_contract_creator_and_tx_hash(
-    addresses: Vec<String>,
-) -> Result<Vec<EtherscanContractCreatorAndTxHash>, reqwest::Error> {
-    let mut results: Vec<EtherscanContractCreatorAndTxHash> = vec![];
-    let mut grouped_addresses: Vec<String> = vec![];
+// swapping the implementation of a proxy token can change its whole logic, a classic rug vector
+pub async fn watch_proxies(bot: Bot) {
+    // lowercase contract -> last seen implementation
+    let mut implementations = HashMap::<String, Option<String>>::new();
 
-    for i in 0..addresses.len() {
-        grouped_addresses.push(addresses[i].clone());
+    loop {
+        sleep(Duration::try_minutes(2).unwrap().to_std().unwrap()).await;
+        info!("New watch proxies cycle...");
+        tasks::heartbeat("watch_proxies").await;
+
+        let monitored = monitored_proxy_tokens().await;
+        implementations.retain(|contract, _| monitored.contains_key(contract));
+
+        for (contract, chat_ids) in monitored {
+            let previous = match implementations.get(&contract) {
+                // not a proxy, the implementation slot is only checked once
+                Some(None) => continue,
+                Some(Some(previous)) => Some(previous.clone()),
+                None => None,
+            };
 
-        if i % 5 == 0 || i == addresses.len() - 1 {
-            match EtherscanAPI::<Vec<EtherscanContractCreatorAndTxHash>>::get_contract_creator_and_tx_hash(
-                grouped_addresses.clone(),
-            )
-            .await
-            {
-                Ok(creators_and_hashes) => results.extend(creators_and_hashes.result),
+            let implementation = match get_proxy_implementation(contract.clone()).await {
+                Ok(implementation) => implementation,
                 Err(e) => {
-                    return Err(e.without_url())
-                },
+                    error!("get_proxy_implementation error: {}", e);
+                    continue;
+                }
             };
 
-            grouped_addresses.clear();
+            if let Some(previous) =
+                previous.filter(|previous| implementation.as_ref() != Some(previous))
+            {
+                for chat_id in &chat_ids {
+                    let _ = bot::proxy_upgrade_notification(
+                        &bot,
+                        *chat_id,
+                        &contract,
+                        &previous,
+                        implementation.as_deref(),
+                    )
+                    .await;
+                }
+            }
+
+            implementations.insert(contract, implementation);
         }
     }
+}
 
-    Ok(results)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SupplyChange {
+    Mint { amount: u128, percent: f64 },
+    Burn { amount: u128, percent: f64 },
 }
 
-pub async fn get_token_balances_with_prices() -> Result<Vec<OwnedToken>, reqwest::Error> {
-    match moralis::get_token_balances_with_prices().await {
-        Ok(token_balances) => Ok(to_owned_tokens(token_balances.result).await),
-        Err(e) => Err(e.without_url()),
+// percent is relative to the previous supply
+fn supply_change(previous: u128, total_supply: u128) -> Option<SupplyChange> {
+    if previous == 0 || previous == total_supply {
+        return None;
+    }
+
+    let percent = |amount: u128| amount as f64 / previous as f64 * 100.0;
+    if total_supply > previous {
+        let amount = total_supply - previous;
+        Some(SupplyChange::Mint {
+            amount,
+            percent: percent(amount),
+        })
+    } else {
+        let amount = previous - total_supply;
+        Some(SupplyChange::Burn {
+            amount,
+            percent: percent(amount),
+        })
     }
 }
 
-async fn to_owned_tokens(token_balances: Vec<MoralisTokenBalancesWithPrices>) -> Vec<OwnedToken> {
-    let mut tokens = vec![];
+// minting after launch dilutes every holder and usually comes right before a dump,
+// burns are reported too as they shrink the supply in the holders' favor
+pub async fn watch_supply(bot: Bot) {
+    // lowercase contract -> last seen raw total supply
+    let mut supplies = HashMap::<String, u128>::new();
 
-    for token in token_balances {
-        let balance = token.balance.parse::<f64>().unwrap_or(0.0);
+    loop {
+        sleep(Duration::try_minutes(2).unwrap().to_std().unwrap()).await;
+        info!("New watch supply cycle...");
+        tasks::heartbeat("watch_supply").await;
 
-        if balance == 0.0 {
-            continue;
+        let monitored = monitored_proxy_tokens().await;
+        supplies.retain(|contract, _| monitored.contains_key(contract));
+
+        for (contract, chat_ids) in monitored {
+            let total_supply = match fetch_total_supply(contract.clone()).await {
+                Ok(total_supply) => total_supply,
+                Err(e) => {
+                    error!("fetch_total_supply error: {}", e);
+                    continue;
+                }
+            };
+
+            if let Some(change) = supplies
+                .insert(contract.clone(), total_supply)
+                .and_then(|previous| supply_change(previous, total_supply))
+            {
+                for chat_id in &chat_ids {
+                    let _ = bot::supply_change_notification(
+                        &bot,
+                        *chat_id,
+                        &contract,
+                        change,
+                        total_supply,
+                    )
+                    .await;
+                }
+            }
         }
+    }
+}
 
-        tokens.push(OwnedToken {
-            name: token.name,
-            symbol: token.symbol,
-            thumbnail: token.thumbnail,
-            contract: token.token_address,
-            balance: balance / 10.0f64.powf(token.decimals as f64),
-            value_usd: token.usd_value,
-            usd_price_24hr_percent_change: token.usd_price_24hr_percent_change,
-            portfolio_percentage: token.portfolio_percentage,
-        });
+// watched tokens alert the chats watching them, held positions alert the owner
+// lowercase contract -> the chats alerted about it, the owner's positions included
+pub async fn monitored_proxy_tokens() -> HashMap<String, HashSet<ChatId>> {
+    let mut monitored = HashMap::<String, HashSet<ChatId>>::new();
+    let mut watched_tokens = WATCHED_TOKENS.lock().await.clone();
+    shards::retain_owned(&mut watched_tokens).await;
+
+    for (chat_id, tokens) in watched_tokens {
+        for token in tokens {
+            monitored
+                .entry(token.to_lowercase())
+                .or_default()
+                .insert(chat_id);
+        }
     }
 
-    tokens
-}
+    // the positions are the primary's, the other shards have none
+    if let Some(owner_chat) = bot::owner_chat().filter(|_| shards::shard().is_primary()) {
+        for contract in positions::POSITIONS.lock().await.contracts() {
+            monitored.entry(contract).or_default().insert(owner_chat);
+        }
+    }
 
-#[derive(Debug)]
-pub struct OwnedToken {
-    pub name: String,
-    pub contract: String,
-    pub thumbnail: Option<String>,
-    pub symbol: String,
-    pub balance: f64,
-    pub value_usd: f64,
-    pub usd_price_24hr_percent_change: f32,
-    pub portfolio_percentage: f32,
+    monitored
 }
 
-pub async fn watch_wallets(bot: Bot) {
-    let mut last_transaction_timestamps = HashMap::<ChatId, HashMap<String, u64>>::new();
+// a day of snapshots, accumulation is compared against the oldest one
+const CONCENTRATION_HISTORY_SIZE: usize = 48;
+// percentage points of the supply gained since the oldest snapshot
+const DEPLOYER_ACCUMULATION_ALERT: f64 = 2.0;
+const TOP_HOLDERS_ACCUMULATION_ALERT: f64 = 5.0;
 
+// one owner alert when providers start and stop failing, instead of an error per failed request
+pub async fn watch_error_budgets(bot: Bot) {
     loop {
         sleep(Duration::try_minutes(1).unwrap().to_std().unwrap()).await;
-        info!("New watch wallets cycle...");
+        tasks::heartbeat("watch_error_budgets").await;
 
-        let watched_wallets_guard = WATCHED_WALLETS.lock().await;
-        let watched_wallets = watched_wallets_guard.clone();
-        drop(watched_wallets_guard);
+        let changes = errorbudget::check(Utc::now().timestamp() / 60).await;
+        if changes.is_empty() {
+            continue;
+        }
+        for change in &changes {
+            info!("provider error budget: {}", change);
+        }
 
-        if watched_wallets.is_empty() {
+        // the warnings of the failed provider requests are held back from the log stream during an outage
+        let outage = !errorbudget::exhausted().await.is_empty();
+        let held_back = logging::hold_back_stream(outage);
+
+        let Some(owner_chat) = bot::owner_chat() else {
             continue;
         };
+        let mut message = changes.iter().fold(
+            String::from("📡 Provider error budgets"),
+            |message, change| format!("{}\n{}", message, change),
+        );
+        if outage {
+            message.push_str("\n\nDCA buys and TWAP slices skipped because of it aren't reported in chats until the outage is over");
+        }
+        if held_back > 0 {
+            message.push_str(&format!(
+                "\n\n{} provider warnings were held back from the log stream during the outage",
+                held_back
+            ));
+        }
+        let _ = bot.send_message(owner_chat, message).await;
+    }
+}
 
-        if last_transaction_timestamps.is_empty() {
-            get_last_token_transaction_timestamps(
-                &watched_wallets,
-                &mut last_transaction_timestamps,
-            )
-            .await;
-        } else {
-            for (chat_id, wallets) in watched_wallets {
-                for wallet in wallets {
-                    match get_new_token_transactions(
-                        wallet.to_owned(),
-                        last_transaction_timestamps
-                            .get(&chat_id)
-                            .unwrap()
-                            .get(&wallet)
-                            .unwrap_or(&0),
-                    )
-                    .await
-                    {
-                        Some(transactions) => {
-                            update_timestamps(
-                                &mut last_transaction_timestamps,
-                                chat_id,
-                                wallet.to_owned(),
-                                transactions[0].time_stamp.parse::<u64>().unwrap_or(0),
-                            );
+// deployers quietly buying back their own held tokens is often the setup for a dump
+pub async fn watch_concentration(bot: Bot) {
+    loop {
+        sleep(Duration::try_minutes(30).unwrap().to_std().unwrap()).await;
+        info!("New watch concentration cycle...");
+        tasks::heartbeat("watch_concentration").await;
 
-                            for transaction in transactions.iter().rev() {
-                                let _ = bot::watched_wallet_notification(
-                                    &bot,
-                                    chat_id,
-                                    &wallet,
-                                    transaction,
-                                )
-                                .await;
-                            }
-                        }
-                        None => {
-                            continue;
-                        }
-                    }
+        let contracts = positions::POSITIONS.lock().await.contracts();
+        CONCENTRATION_HISTORY
+            .lock()
+            .await
+            .retain(|contract, _| contracts.contains(contract));
+
+        for contract in contracts {
+            let snapshot = match take_concentration_snapshot(contract.clone()).await {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    error!("take_concentration_snapshot error: {}", e);
+                    continue;
                 }
+            };
+
+            let mut history = CONCENTRATION_HISTORY.lock().await;
+            let snapshots = history.entry(contract.clone()).or_default();
+
+            let accumulated = snapshots.front().and_then(|oldest| {
+                let deployer_gain = snapshot.deployer_percent.unwrap_or(0.0)
+                    - oldest.deployer_percent.unwrap_or(0.0);
+                let top_holders_gain =
+                    snapshot.concentration.top10_percent - oldest.concentration.top10_percent;
+
+                (deployer_gain >= DEPLOYER_ACCUMULATION_ALERT
+                    || top_holders_gain >= TOP_HOLDERS_ACCUMULATION_ALERT)
+                    .then(|| oldest.clone())
+            });
+
+            // after an alert the current state becomes the new baseline
+            if accumulated.is_some() {
+                snapshots.clear();
+            }
+            snapshots.push_back(snapshot.clone());
+            if snapshots.len() > CONCENTRATION_HISTORY_SIZE {
+                snapshots.pop_front();
+            }
+            drop(history);
+
+            if let (Some(oldest), Some(owner_chat)) = (accumulated, bot::owner_chat()) {
+                let _ =
+                    bot::accumulation_notification(&bot, owner_chat, &contract, &oldest, &snapshot)
+                        .await;
             }
         }
     }
 }
 
+async fn take_concentration_snapshot(
+    contract: String,
+) -> Result<ConcentrationSnapshot, reqwest::Error> {
+    let concentration = get_holder_concentration(contract.clone()).await?;
+
+    let creator = get_contract_creator_and_tx_hash(vec![contract.clone()])
+        .await?
+        .first()
+        .map(|creator| creator.contract_creator.clone());
+    let deployer_percent = match creator {
+        Some(creator) => {
+            let balance = get_token_balance(contract.clone(), creator).await?;
+            let total_supply = get_total_supply(contract).await?;
+
+            (total_supply > 0).then(|| balance as f64 / total_supply as f64 * 100.0)
+        }
+        None => None,
+    };
+
+    Ok(ConcentrationSnapshot {
+        taken_at: Utc::now(),
+        concentration,
+        deployer_percent,
+    })
+}
+
 async fn get_last_token_transaction_timestamps(
     watched_wallets: &HashMap<ChatId, Vec<String>>,
     last_transaction_timestamps: &mut HashMap<ChatId, HashMap<String, u64>>,
@@ -279,33 +2410,57 @@ fn update_timestamps(
         .or_insert(HashMap::from([(wallet, timestamp)]));
 }
 
+const TOKEN_TRANSACTIONS_PAGE_SIZE: u32 = 100;
+// a burst of activity is paged through up to this many pages, older transactions are skipped
+pub const MAX_TOKEN_TRANSACTION_PAGES: u32 = 5;
+
+// the transactions of a page newer than the timestamp, and whether the page reached the timestamp
+fn new_transactions_in_page(
+    page: Vec<EtherscanTokenTransaction>,
+    timestamp: u64,
+) -> (Vec<EtherscanTokenTransaction>, bool) {
+    let page_size = page.len();
+    let new_transactions: Vec<EtherscanTokenTransaction> = page
+        .into_iter()
+        .take_while(|t| t.time_stamp.parse::<u64>().unwrap_or(0) > timestamp)
+        .collect();
+
+    // a page that isn't full is the wallet's last one
+    let reached =
+        new_transactions.len() < page_size || page_size < TOKEN_TRANSACTIONS_PAGE_SIZE as usize;
+    (new_transactions, reached)
+}
+
+// pages until the last seen timestamp is reached, true if MAX_TOKEN_TRANSACTION_PAGES ran out first
 async fn get_new_token_transactions(
     wallet: String,
     timestamp: &u64,
-) -> Option<Vec<EtherscanTokenTransaction>> {
-    match get_token_transactions(wallet).await {
-        Ok(transactions) => {
-            let mut new_transactions = Vec::<EtherscanTokenTransaction>::new();
-
-            for i in 0..transactions.len() {
-                if &transactions[i].time_stamp.parse::<u64>().unwrap_or(0) > timestamp {
-                    new_transactions.push(transactions[i].clone());
-                } else {
-                    break;
-                }
+) -> Option<(Vec<EtherscanTokenTransaction>, bool)> {
+    let mut new_transactions = Vec::<EtherscanTokenTransaction>::new();
+
+    for page in 1..=MAX_TOKEN_TRANSACTION_PAGES {
+        let transactions = match get_token_transactions_page(wallet.clone(), page).await {
+            Ok(transactions) => transactions,
+            Err(e) => {
+                // the last seen timestamp isn't moved, so every page is fetched again next cycle
+                error!("get_token_transactions error: {}", e);
+                return None;
             }
+        };
 
-            if !new_transactions.is_empty() {
-                return Some(new_transactions);
-            }
+        let (transactions, reached) = new_transactions_in_page(transactions, *timestamp);
+        new_transactions.extend(transactions);
 
-            None
-        }
-        Err(e) => {
-            error!("get_token_transactions error: {}", e);
-            None
+        // wallets without a last seen timestamp only get their latest page
+        if reached || *timestamp == 0 {
+            if new_transactions.is_empty() {
+                return None;
+            }
+            return Some((new_transactions, false));
         }
     }
+
+    Some((new_transactions, true))
 }
 
 pub async fn new_token_alerts(bot: Bot) {
@@ -315,10 +2470,9 @@ pub async fn new_token_alerts(bot: Bot) {
     loop {
         sleep(Duration::try_minutes(1).unwrap().to_std().unwrap()).await;
         info!("New token alerts cycle...");
+        tasks::heartbeat("new_token_alerts").await;
 
-        let settings_guard = SETTINGS.lock().await;
-        let settings = settings_guard.clone();
-        drop(settings_guard);
+        let settings = SETTINGS.snapshot().await;
 
         if settings.is_empty() {
             continue;
@@ -338,33 +2492,221 @@ pub async fn new_token_alerts(bot: Bot) {
 
         filter_new_tokens(&mut monitored_tokens, &mut last_removed_token).await;
 
-        for token in &monitored_tokens {
+        for token in monitored_tokens.iter_mut() {
+            // only fetched once per token, and only if it passed the safety checks
+            let mut launch_metrics: Option<Option<LaunchMetrics>> = None;
+            let mut early_buyers: Option<Option<EarlyBuyers>> = None;
+            let mut anti_snipe: Option<Option<AntiSnipe>> = None;
+
             for chat_id in settings.keys() {
-                if !settings.get(chat_id).unwrap().snipe_new_tokens {
+                if !settings.get(chat_id).unwrap().snipe_new_tokens
+                    || !bot::has_premium(*chat_id).await
+                {
                     continue;
                 }
 
                 if token.to_buy {
                     trace!("Token to buy true for: {:?}", token);
-                    let _ = bot
-                        .send_message(
+
+                    if anti_snipe.is_none() {
+                        anti_snipe =
+                            Some(match get_anti_snipe(token.contract_address.clone()).await {
+                                Ok(anti_snipe) => anti_snipe,
+                                Err(e) => {
+                                    error!("get_anti_snipe error: {}", e);
+                                    None
+                                }
+                            });
+                    }
+
+                    // the alert waits out the chat's delay, or the contract's dead blocks if they're longer
+                    let delay_blocks = settings.get(chat_id).unwrap().launch_delay_blocks.max(
+                        anti_snipe
+                            .clone()
+                            .flatten()
+                            .map_or(0, |a| a.min_delay_blocks()),
+                    );
+                    let launch_age = Utc::now().timestamp() - token.creation_timestamp;
+                    if launch_age < (delay_blocks * SECONDS_PER_BLOCK) as i64 {
+                        continue;
+                    }
+                    let detected_anti_snipe = anti_snipe
+                        .clone()
+                        .flatten()
+                        .filter(|anti_snipe| anti_snipe.is_detected());
+
+                    let sniper_filter = settings.get(chat_id).unwrap().sniper_filter;
+                    let template = templates::get(*chat_id, TemplateKind::Sniper).await;
+                    let shows_metrics = template.as_ref().is_some_and(|template| {
+                        ["liquidity_eth", "liquidity_usd", "fdv_usd"]
+                            .iter()
+                            .any(|placeholder| templates::uses(template, placeholder))
+                    });
+
+                    if sniper_filter.needs_metrics() || shows_metrics {
+                        if launch_metrics.is_none() {
+                            launch_metrics = Some(
+                                match get_launch_metrics(
+                                    token.uniswap_pair_address.clone(),
+                                    token.contract_address.clone(),
+                                )
+                                .await
+                                {
+                                    Ok(metrics) => metrics,
+                                    Err(e) => {
+                                        error!("get_launch_metrics error: {}", e);
+                                        None
+                                    }
+                                },
+                            );
+                        }
+
+                        // launches that can't be valued are skipped, they can't be checked either
+                        match launch_metrics.flatten() {
+                            Some(metrics) if sniper_filter.allows(&metrics) => (),
+                            None if !sniper_filter.needs_metrics() => (),
+                            _ => continue,
+                        }
+                    }
+
+                    // rechecked every cycle, so the alert waits until enough wallets bought
+                    if sniper_filter.needs_buyers() {
+                        if early_buyers.is_none() {
+                            early_buyers = Some(
+                                match get_early_buyers(
+                                    token.uniswap_pair_address.clone(),
+                                    token.contract_address.clone(),
+                                    token.creator.clone(),
+                                )
+                                .await
+                                {
+                                    Ok(buyers) => Some(buyers),
+                                    Err(e) => {
+                                        error!("get_early_buyers error: {}", e);
+                                        None
+                                    }
+                                },
+                            );
+                        }
+
+                        match early_buyers.flatten() {
+                            Some(buyers) if sniper_filter.allows_buyers(&buyers) => (),
+                            _ => continue,
+                        }
+                    }
+
+                    if let Some(template) = template {
+                        let metric = |value: Option<f64>, precision: usize| match value {
+                            Some(value) => format!("{:.*}", precision, value),
+                            None => String::from("?"),
+                        };
+                        let metrics = launch_metrics.flatten();
+                        let message = templates::render(
+                            &html::escape(&template),
+                            &[
+                                ("pair", token.uniswap_pair_address.clone()),
+                                ("contract", token.contract_address.clone()),
+                                (
+                                    "liquidity_eth",
+                                    metric(metrics.as_ref().map(|m| m.liquidity_eth), 2),
+                                ),
+                                (
+                                    "liquidity_usd",
+                                    metric(metrics.as_ref().map(|m| m.liquidity_usd), 0),
+                                ),
+                                ("fdv_usd", metric(metrics.as_ref().map(|m| m.fdv_usd), 0)),
+                                (
+                                    "anti_snipe",
+                                    detected_anti_snipe
+                                        .as_ref()
+                                        .map(|a| a.to_string())
+                                        .unwrap_or_default(),
+                                ),
+                                (
+                                    "links",
+                                    hyperlinks_from_contract(
+                                        &token.contract_address,
+                                        Chain::Ethereum,
+                                        settings.get(chat_id).unwrap().chart_site,
+                                    ),
+                                ),
+                            ],
+                        );
+
+                        let _ = alerts::send_alert(
+                            &bot,
                             *chat_id,
-                            format!(
-                                "💎💎💎 New token 💎💎💎\n\n\
-                                This new token passed all the checks:\n❌ honeypot\n✅ liquidity locked\n✅ contract renounced\n\n\
-                                Disclamer:\nThese checks can't detect everything (e.g.: delayed honeypot) Be careful and make sure to check it manually before buying!\n\n\
-                                📄 Uniswap pair address: {}",
-                                token.uniswap_pair_address,
-                            ),
+                            AlertCategory::Sniper,
+                            None,
+                            message,
                         )
+                        .await
+                        .parse_mode(ParseMode::Html)
+                        .disable_web_page_preview(true)
+                        .reply_markup(bot::new_token_keyboard(&token.contract_address))
                         .await;
+                        continue;
+                    }
+
+                    // scanned once per token, every later cycle and chat reuses the scan
+                    if token.scan.is_none() {
+                        token.scan = bot::new_token_scan(&token.contract_address).await;
+                    }
+
+                    let mut message = format!(
+                        "💎💎💎 New token 💎💎💎\n\n\
+                        This new token passed all the checks:\n❌ honeypot\n✅ liquidity locked\n✅ contract renounced\n\n\
+                        Disclamer:\nThese checks can't detect everything (e.g.: delayed honeypot) Be careful and make sure to check it manually before buying!\n\n\
+                        📄 Uniswap pair address: {}",
+                        token.uniswap_pair_address,
+                    );
+                    if let Some(scan) = &token.scan {
+                        message.push_str(&format!(
+                            "\n\n{}",
+                            bot::new_token_summary(scan, &settings.get(chat_id).unwrap().locale)
+                        ));
+                    }
+                    if let Some(Some(metrics)) = launch_metrics {
+                        message.push_str(&format!(
+                            "\n💧 Liquidity: {:.2} ETH (${:.0})\n🏦 FDV: ${:.0}",
+                            metrics.liquidity_eth, metrics.liquidity_usd, metrics.fdv_usd
+                        ));
+                    }
+                    if let Some(Some(buyers)) = early_buyers {
+                        message.push_str(&format!(
+                            "\n👥 Early buyers: {} ({:.2} ETH, ${:.0})",
+                            buyers.buyers, buyers.volume_eth, buyers.volume_usd
+                        ));
+                    }
+                    if let Some(anti_snipe) = &detected_anti_snipe {
+                        message.push_str(&format!(
+                            "\n🛡 Anti-snipe: {}, check the current tax before buying",
+                            html::escape(&anti_snipe.to_string())
+                        ));
+                    }
+                    message.push_str(&format!(
+                        "\n{}",
+                        hyperlinks_from_contract(
+                            &token.contract_address,
+                            Chain::Ethereum,
+                            settings.get(chat_id).unwrap().chart_site,
+                        )
+                    ));
+
+                    let _ =
+                        alerts::send_alert(&bot, *chat_id, AlertCategory::Sniper, None, message)
+                            .await
+                            .parse_mode(ParseMode::Html)
+                            .disable_web_page_preview(true)
+                            .reply_markup(bot::new_token_keyboard(&token.contract_address))
+                            .await;
                 }
             }
         }
     }
 }
 
-async fn is_token_honeypot(contract: String) -> Option<bool> {
+pub async fn is_token_honeypot(contract: String) -> Option<bool> {
     match get_token_info(contract).await {
         Ok(info) => {
             if info.is_honeypot || (info.buy_tax > 5.0 || info.sell_tax > 5.0) {
@@ -380,21 +2722,18 @@ async fn is_token_honeypot(contract: String) -> Option<bool> {
     }
 }
 
-pub async fn is_liquidity_locked(contract: String) -> Option<bool> {
+// name of the first known locker among the top holders, None if the holders couldn't be fetched
+pub async fn get_liquidity_locker(contract: String) -> Option<Option<String>> {
     match get_top_token_holders(contract).await {
         Ok(holders) => {
-            for holder in holders {
-                // TrustSwap: Team Finance Lock
-                if holder.wallet_address == "0xE2fE530C047f2d85298b07D9333C05737f1435fB"
-                // UNCX Network Security : Token Vesting
-                || holder.wallet_address
-                == "0xDba68f07d1b7Ca219f78ae8582C213d975c25cAf"
-                {
-                    return Some(true);
-                }
-            }
-
-            Some(false)
+            Some(
+                holders
+                    .iter()
+                    .find_map(|holder| match known_label(&holder.wallet_address) {
+                        Some((name, LabelKind::Locker)) => Some(name),
+                        _ => None,
+                    }),
+            )
         }
         Err(e) => {
             error!("get_top_token_holders error: {}", e);
@@ -403,6 +2742,12 @@ pub async fn is_liquidity_locked(contract: String) -> Option<bool> {
     }
 }
 
+pub async fn is_liquidity_locked(contract: String) -> Option<bool> {
+    get_liquidity_locker(contract)
+        .await
+        .map(|locker| locker.is_some())
+}
+
 pub async fn is_liqudity_burned(contract: String) -> Option<bool> {
     match get_top_token_holders(contract).await {
         Ok(holders) => {
@@ -439,6 +2784,80 @@ pub async fn is_contract_renounced(creator_address: String) -> Option<bool> {
     }
 }
 
+// the checks new token alerts passed, None for a check that couldn't be done
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaunchVerdict {
+    pub honeypot: Option<bool>,
+    pub liquidity_locked: Option<bool>,
+    pub renounced: Option<bool>,
+}
+
+impl LaunchVerdict {
+    pub fn passed(&self) -> bool {
+        self.honeypot == Some(false)
+            && self.liquidity_locked == Some(true)
+            && self.renounced == Some(true)
+    }
+}
+
+impl fmt::Display for LaunchVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let honeypot = match self.honeypot {
+            Some(false) => "❌ honeypot",
+            Some(true) => "🚨 honeypot or taxes above 5%",
+            None => "❔ honeypot check failed",
+        };
+        let liquidity = match self.liquidity_locked {
+            Some(true) => "✅ liquidity locked",
+            Some(false) => "🚨 liquidity not locked",
+            None => "❔ liquidity check failed",
+        };
+        let renounced = match self.renounced {
+            Some(true) => "✅ contract renounced",
+            Some(false) => "🚨 contract not renounced",
+            None => "❔ renounce check failed",
+        };
+
+        write!(f, "{}\n{}\n{}", honeypot, liquidity, renounced)
+    }
+}
+
+// the alert's checks again for one token, its button only has room for the contract so
+// the pair comes from honeypot.is and the creator from etherscan
+pub async fn recheck_launch(contract: String) -> LaunchVerdict {
+    let honeypot = is_token_honeypot(contract.clone()).await;
+
+    let burned = match get_token_info(contract.clone()).await {
+        Ok(token_info) => is_liqudity_burned(token_info.pair_address).await,
+        Err(e) => {
+            error!("get_token_info error: {}", e);
+            None
+        }
+    };
+    let liquidity_locked = match (burned, is_liquidity_locked(contract.clone()).await) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    };
+
+    let renounced = match get_contract_creator_and_tx_hash(vec![contract]).await {
+        Ok(creators) => match creators.first() {
+            Some(creator) => is_contract_renounced(creator.contract_creator.clone()).await,
+            None => None,
+        },
+        Err(e) => {
+            error!("get_contract_creator_and_tx_hash error: {}", e);
+            None
+        }
+    };
+
+    LaunchVerdict {
+        honeypot,
+        liquidity_locked,
+        renounced,
+    }
+}
+
 async fn filter_new_tokens(monitored_tokens: &mut Vec<NewToken>, last_removed_token: &mut String) {
     #[derive(Default, Debug)]
     struct TokenCheck {
@@ -510,6 +2929,7 @@ async fn filter_new_tokens(monitored_tokens: &mut Vec<NewToken>, last_removed_to
                 .get(&token.uniswap_pair_address)
                 .unwrap()
                 .liquidity_locked_or_burned
+                && token.allowed_by_hooks
             {
                 token.to_buy = true;
             }
@@ -585,14 +3005,30 @@ async fn check_for_new_tokens(monitored_tokens: &mut Vec<NewToken>, contract_add
                     })
                     .collect();
 
+                let contract_address = contract_address.unwrap_or_default();
+                let creation_timestamp =
+                    filtered_transactions[i].time_stamp.parse::<i64>().unwrap();
+                let allowed_by_hooks = hooks::token_discovered(&DiscoveredToken {
+                    source: String::from("uniswap_v2"),
+                    pair: uniswap_pair_address.clone(),
+                    contract: contract_address.clone(),
+                    creator: creator.clone(),
+                    creation_timestamp,
+                });
+
                 monitored_tokens.push(NewToken {
                     uniswap_pair_address: uniswap_pair_address.to_owned(),
-                    contract_address: contract_address.unwrap_or_default(),
+                    contract_address,
                     creator: creator,
-                    creation_timestamp: filtered_transactions[i].time_stamp.parse::<i64>().unwrap(),
+                    creation_timestamp,
                     to_buy: false,
+                    allowed_by_hooks,
+                    scan: None,
                 })
             }
+
+            let new_tokens = monitored_tokens.len() - filtered_transactions.len()..;
+            warm_up_tokens(&monitored_tokens[new_tokens]).await;
         }
         Err(e) => {
             error!("get_internal_transactions error: {:?}", e);
@@ -600,6 +3036,24 @@ async fn check_for_new_tokens(monitored_tokens: &mut Vec<NewToken>, contract_add
     }
 }
 
+// fetches everything filter_new_tokens and scans need, concurrently for all tokens
+async fn warm_up_tokens(tokens: &[NewToken]) {
+    let mut warm_ups = JoinSet::new();
+
+    for token in tokens.iter().cloned() {
+        warm_ups.spawn(async move {
+            let _ = tokio::join!(
+                get_token_info(token.contract_address.clone()),
+                get_top_token_holders(token.uniswap_pair_address.clone()),
+                get_top_token_holders(token.contract_address.clone()),
+                get_normal_transactions(token.creator.clone()),
+            );
+        });
+    }
+
+    while warm_ups.join_next().await.is_some() {}
+}
+
 #[derive(Debug, Clone)]
 struct NewToken {
     uniswap_pair_address: String,
@@ -607,6 +3061,10 @@ struct NewToken {
     creator: String,
     creation_timestamp: i64,
     to_buy: bool,
+    // rejected tokens are still tracked, so they aren't discovered again, but never alerted
+    allowed_by_hooks: bool,
+    // the alert's scan, None until the token is first alerted
+    scan: Option<bot::NewTokenScan>,
 }
 
 /*
@@ -683,3 +3141,132 @@ struct CUInner {
 pub struct CU {
     inner: Arc<CUInner>,
 }
+
+#[test]
+fn test_launch_metrics() {
+    // 5 weth against half of a 1,000,000 supply with 9 decimals
+    let metrics = launch_metrics(
+        5 * 10u128.pow(18),
+        500_000 * 10u128.pow(9),
+        1_000_000 * 10u128.pow(9),
+        2_000.0,
+    )
+    .unwrap();
+
+    assert_eq!(metrics.liquidity_eth, 10.0);
+    assert_eq!(metrics.liquidity_usd, 20_000.0);
+    assert_eq!(metrics.fdv_usd, 20_000.0);
+    assert_eq!(launch_metrics(0, 1, 1, 2_000.0), None);
+}
+
+#[test]
+fn test_supply_info() {
+    let supply = SupplyInfo {
+        total: 1_000_000 * 10u128.pow(9),
+        circulating: 800_000 * 10u128.pow(9),
+        decimals: 9,
+    };
+
+    assert_eq!(supply.market_cap(0.5), 400_000.0);
+    assert_eq!(supply.fdv(0.5), 500_000.0);
+    assert_eq!(supply.percent_of_supply(10_000 * 10u128.pow(9)), 1.0);
+}
+
+#[test]
+fn test_holder_concentration() {
+    let holder = |address: &str, amount: &str| ChainbaseTokenOwners {
+        wallet_address: String::from(address),
+        original_amount: String::from(amount),
+        amount: String::new(),
+        usd_value: String::new(),
+    };
+    let pair = "0x3333333333333333333333333333333333333333";
+    let holders = vec![
+        holder("0x000000000000000000000000000000000000dead", "500"),
+        holder(pair, "200"),
+        holder("0x1111111111111111111111111111111111111111", "100"),
+        holder("0x2222222222222222222222222222222222222222", "50"),
+    ];
+
+    let concentration = holder_concentration(&holders, 1000, Some(pair));
+    assert_eq!(concentration.top5_percent, 15.0);
+    assert_eq!(concentration.top10_percent, 15.0);
+    assert_eq!(
+        holder_concentration(&holders, 1000, None).top10_percent,
+        35.0
+    );
+    assert_eq!(
+        holder_concentration(&holders, 0, Some(pair)),
+        HolderConcentration::default()
+    );
+}
+
+#[test]
+fn test_supply_change() {
+    assert_eq!(supply_change(1000, 1000), None);
+    assert_eq!(supply_change(0, 1000), None);
+    assert_eq!(
+        supply_change(1000, 1500),
+        Some(SupplyChange::Mint {
+            amount: 500,
+            percent: 50.0
+        })
+    );
+    assert_eq!(
+        supply_change(1000, 900),
+        Some(SupplyChange::Burn {
+            amount: 100,
+            percent: 10.0
+        })
+    );
+}
+
+#[test]
+fn test_new_transactions_in_page() {
+    let page = |timestamps: std::ops::RangeInclusive<u64>| -> Vec<EtherscanTokenTransaction> {
+        timestamps
+            .rev()
+            .map(|timestamp| EtherscanTokenTransaction {
+                time_stamp: timestamp.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    };
+
+    // a full page of new transactions needs the next page
+    let (new_transactions, reached) = new_transactions_in_page(page(101..=200), 50);
+    assert_eq!((new_transactions.len(), reached), (100, false));
+
+    let (new_transactions, reached) = new_transactions_in_page(page(1..=100), 50);
+    assert_eq!((new_transactions.len(), reached), (50, true));
+    assert_eq!(new_transactions[0].time_stamp, "100");
+
+    // the wallet's last page
+    let (new_transactions, reached) = new_transactions_in_page(page(61..=90), 50);
+    assert_eq!((new_transactions.len(), reached), (30, true));
+}
+
+#[test]
+fn test_launch_verdict() {
+    let verdict = LaunchVerdict {
+        honeypot: Some(false),
+        liquidity_locked: Some(true),
+        renounced: Some(true),
+    };
+    assert!(verdict.passed());
+    assert_eq!(
+        verdict.to_string(),
+        "❌ honeypot\n✅ liquidity locked\n✅ contract renounced"
+    );
+
+    let unlocked = LaunchVerdict {
+        liquidity_locked: Some(false),
+        renounced: None,
+        ..verdict
+    };
+    assert!(!unlocked.passed());
+    assert_eq!(
+        unlocked.to_string(),
+        "❌ honeypot\n🚨 liquidity not locked\n❔ renounce check failed"
+    );
+}
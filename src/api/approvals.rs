@@ -0,0 +1,216 @@
+use super::{etherscan::EtherscanNormalTransaction, labels::known_label};
+use crate::{
+    crypto::abi,
+    utils::{address_from_word, uint_from_word},
+};
+use core::fmt;
+
+const PERMIT2: &str = "0x000000000022d473030f116ddee9f6b43ac78ba3";
+
+// routers and approval managers of the common dexes and marketplaces, approving them is part of trading
+const TRUSTED_SPENDERS: [&str; 10] = [
+    // Uniswap V2 Router
+    "0x7a250d5630b4cf539739df2c5dacb4c659f2488d",
+    // Uniswap V3 SwapRouter
+    "0xe592427a0aece92de3edee1f18e0157c05861564",
+    // Uniswap SwapRouter02
+    "0x68b3465833fb72a70ecdf485e0e4c7bd8665fc45",
+    // Uniswap Universal Router
+    "0x3fc91a3afd70395cd496c647d5a6cc9d4b2b7fad",
+    "0x66a9893cc07d91d95644aedd05d03f95e1dba8af",
+    PERMIT2,
+    // 1inch v5 and v6
+    "0x1111111254eeb25477b68fb85ed929f73a960582",
+    "0x111111125421ca6dc452d289314280a0f8842a65",
+    // 0x Exchange Proxy
+    "0xdef1c0ded9bec7f1a1670819833240f027b25eff",
+    // OpenSea Seaport conduit
+    "0x1e0049783f008a0085193e00003d00cd54003c71",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApprovalKind {
+    Approve,
+    IncreaseAllowance,
+    ApprovalForAll,
+    Permit,
+    Permit2,
+}
+
+impl fmt::Display for ApprovalKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ApprovalKind::Approve => write!(f, "approve"),
+            ApprovalKind::IncreaseAllowance => write!(f, "increaseAllowance"),
+            ApprovalKind::ApprovalForAll => write!(f, "setApprovalForAll"),
+            ApprovalKind::Permit => write!(f, "permit"),
+            ApprovalKind::Permit2 => write!(f, "Permit2 approve"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Approval {
+    pub kind: ApprovalKind,
+    pub token: String,
+    pub spender: String,
+    // None for unlimited approvals and approvals of a whole NFT collection
+    pub amount: Option<u128>,
+    pub hash: String,
+}
+
+// the approvals a wallet signed itself, permits submitted by someone else aren't in its transactions
+pub fn decode_approval(wallet: &str, transaction: &EtherscanNormalTransaction) -> Option<Approval> {
+    if transaction.from.to_lowercase() != wallet.to_lowercase()
+        || transaction.is_error != "0"
+        || transaction.input.len() < 10
+    {
+        return None;
+    }
+
+    let selector = abi::from_hex(&transaction.input[..10]);
+    let words: Vec<&str> = transaction.input[10..]
+        .as_bytes()
+        .chunks(64)
+        .filter_map(|word| std::str::from_utf8(word).ok())
+        .collect();
+    let word = |i: usize| words.get(i).copied().unwrap_or_default();
+    let amount = |word: &str| match uint_from_word(word) {
+        // uint_from_word saturates, so uint256 and uint160 max, the usual unlimited approvals, end up here
+        u128::MAX => None,
+        amount => Some(amount),
+    };
+    let token = transaction.to.to_lowercase();
+
+    let (kind, token, spender, amount) = if selector == abi::selector("approve(address,uint256)") {
+        (ApprovalKind::Approve, token, word(0), amount(word(1)))
+    } else if selector == abi::selector("increaseAllowance(address,uint256)") {
+        (
+            ApprovalKind::IncreaseAllowance,
+            token,
+            word(0),
+            amount(word(1)),
+        )
+    } else if selector == abi::selector("setApprovalForAll(address,bool)") {
+        // revoking sets it to false
+        if uint_from_word(word(1)) == 0 {
+            return None;
+        }
+        (ApprovalKind::ApprovalForAll, token, word(0), None)
+    } else if selector
+        == abi::selector("permit(address,address,uint256,uint256,uint8,bytes32,bytes32)")
+    {
+        (ApprovalKind::Permit, token, word(1), amount(word(2)))
+    } else if selector == abi::selector("approve(address,address,uint160,uint48)")
+        && token == PERMIT2
+    {
+        let permit2_token = address_from_word(word(0))?;
+        (
+            ApprovalKind::Permit2,
+            permit2_token,
+            word(1),
+            amount(word(2)),
+        )
+    } else {
+        return None;
+    };
+
+    // zero allowances revoke approvals
+    if amount == Some(0) {
+        return None;
+    }
+
+    Some(Approval {
+        kind,
+        token,
+        spender: address_from_word(spender)?,
+        amount,
+        hash: transaction.hash.clone(),
+    })
+}
+
+// known routers and registry addresses are fine, everything else needs a verified contract
+pub fn is_trusted_spender(spender: &str) -> bool {
+    let spender = spender.to_lowercase();
+
+    TRUSTED_SPENDERS.contains(&spender.as_str()) || known_label(&spender).is_some()
+}
+
+#[test]
+fn test_decode_approval() {
+    let wallet = "0x11ddacb10c3891e356dce6d7c6f22dd69c93e2cd";
+    let spender = "0x00000000000000000000000000000000deadbeef";
+    let transaction = |to: &str, input: String| EtherscanNormalTransaction {
+        time_stamp: String::from("100"),
+        hash: String::from("0xabc"),
+        from: wallet.to_owned(),
+        to: to.to_owned(),
+        value: String::from("0"),
+        is_error: String::from("0"),
+        txreceipt_status: String::from("1"),
+        input,
+        ..EtherscanNormalTransaction::default()
+    };
+    let token = "0x6982508145454ce325ddbe47a25d4ec3d2311933";
+
+    let unlimited = transaction(
+        token,
+        abi::function_call(
+            "approve(address,uint256)",
+            &[
+                abi::AbiToken::Address(spender.to_owned()),
+                abi::AbiToken::Uint(u128::MAX),
+            ],
+        ),
+    );
+    assert_eq!(
+        decode_approval(wallet, &unlimited),
+        Some(Approval {
+            kind: ApprovalKind::Approve,
+            token: token.to_owned(),
+            spender: spender.to_owned(),
+            amount: None,
+            hash: String::from("0xabc"),
+        })
+    );
+
+    let revoke = transaction(
+        token,
+        abi::function_call(
+            "approve(address,uint256)",
+            &[
+                abi::AbiToken::Address(spender.to_owned()),
+                abi::AbiToken::Uint(0),
+            ],
+        ),
+    );
+    assert_eq!(decode_approval(wallet, &revoke), None);
+
+    let permit2 = transaction(
+        PERMIT2,
+        abi::function_call(
+            "approve(address,address,uint160,uint48)",
+            &[
+                abi::AbiToken::Address(token.to_owned()),
+                abi::AbiToken::Address(spender.to_owned()),
+                abi::AbiToken::Uint(1000),
+                abi::AbiToken::Uint(0),
+            ],
+        ),
+    );
+    let approval = decode_approval(wallet, &permit2).unwrap();
+    assert_eq!(
+        (approval.kind, approval.token.as_str(), approval.amount),
+        (ApprovalKind::Permit2, token, Some(1000))
+    );
+
+    // approvals sent by another wallet aren't the watched wallet's
+    assert_eq!(
+        decode_approval("0x4444444444444444444444444444444444444444", &unlimited),
+        None
+    );
+    assert!(is_trusted_spender(
+        "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D"
+    ));
+    assert!(!is_trusted_spender(spender));
+}
@@ -0,0 +1,169 @@
+use super::{
+    cache::TtlCache,
+    clusters::funded_by,
+    etherscan::{EtherscanAPI, EtherscanTokenTransaction},
+    get_eth_price,
+    labels::known_label,
+    WETH_ADDRESS,
+};
+use chrono::Duration;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+// the pair's oldest transfers, the first buyers are among them
+const FIRST_TRANSFERS: u32 = 100;
+
+lazy_static! {
+    // deployer:wallet -> whether the deployer funded the wallet, so buyers aren't traced again every cycle
+    static ref DEPLOYER_FUNDED: TtlCache<bool> = TtlCache::new(Duration::try_hours(6).unwrap());
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EarlyBuyers {
+    pub buyers: usize,
+    pub volume_eth: f64,
+    pub volume_usd: f64,
+}
+
+// buyer -> eth spent, a buy is the pair sending out tokens in the transaction that sent WETH into it.
+// the WETH is split by the tokens each recipient got, and the deployer, the token contract and
+// labelled routers and lockers aren't buyers
+fn early_buys(
+    transfers: &[EtherscanTokenTransaction],
+    pair: &str,
+    token: &str,
+    creator: &str,
+) -> HashMap<String, f64> {
+    let (pair, token, creator) = (
+        pair.to_lowercase(),
+        token.to_lowercase(),
+        creator.to_lowercase(),
+    );
+
+    let mut paid: HashMap<&str, f64> = HashMap::new();
+    let mut sent: HashMap<&str, f64> = HashMap::new();
+    for transfer in transfers {
+        let value = transfer.value.parse::<f64>().unwrap_or(0.0);
+        let contract = transfer.contract_address.to_lowercase();
+        if contract == WETH_ADDRESS && transfer.to.to_lowercase() == pair {
+            *paid.entry(&transfer.hash).or_default() += value / 10.0f64.powi(18);
+        } else if contract == token && transfer.from.to_lowercase() == pair {
+            *sent.entry(&transfer.hash).or_default() += value;
+        }
+    }
+
+    let mut buys: HashMap<String, f64> = HashMap::new();
+    for transfer in transfers {
+        let buyer = transfer.to.to_lowercase();
+        if transfer.contract_address.to_lowercase() != token
+            || transfer.from.to_lowercase() != pair
+            || [&pair, &token, &creator].contains(&&buyer)
+            || known_label(&buyer).is_some()
+        {
+            continue;
+        }
+
+        let hash = transfer.hash.as_str();
+        if let (Some(eth), Some(tokens)) = (paid.get(hash), sent.get(hash)) {
+            if *tokens > 0.0 {
+                let received = transfer.value.parse::<f64>().unwrap_or(0.0);
+                *buys.entry(buyer).or_default() += eth * received / tokens;
+            }
+        }
+    }
+
+    buys
+}
+
+// the pair's first token transfers, wallets the deployer funded are left out with the deployer
+pub async fn get_early_buyers(
+    pair: String,
+    token: String,
+    creator: String,
+) -> Result<EarlyBuyers, reqwest::Error> {
+    let (transfers, eth_price) = tokio::join!(
+        EtherscanAPI::<Vec<EtherscanTokenTransaction>>::get_first_token_transactions(
+            pair.clone(),
+            FIRST_TRANSFERS
+        ),
+        get_eth_price()
+    );
+    let transfers = transfers.map_err(|e| e.without_url())?.result;
+
+    let mut buys = early_buys(&transfers, &pair, &token, &creator);
+
+    let creator = creator.to_lowercase();
+    let mut funded = vec![];
+    let mut unchecked = vec![];
+    for buyer in buys.keys() {
+        match DEPLOYER_FUNDED.get(&format!("{}:{}", creator, buyer)).await {
+            Some(true) => funded.push(buyer.clone()),
+            Some(false) => (),
+            None => unchecked.push(buyer.clone()),
+        }
+    }
+    for (wallet, is_funded) in funded_by(&unchecked, &creator).await {
+        DEPLOYER_FUNDED
+            .insert(&format!("{}:{}", creator, wallet), is_funded)
+            .await;
+        if is_funded {
+            funded.push(wallet);
+        }
+    }
+    for wallet in funded {
+        buys.remove(&wallet);
+    }
+
+    let volume_eth: f64 = buys.values().sum();
+
+    Ok(EarlyBuyers {
+        buyers: buys.len(),
+        volume_eth,
+        volume_usd: volume_eth * eth_price?,
+    })
+}
+
+#[test]
+fn test_early_buys() {
+    let pair = "0x00000000000000000000000000000000000000aa";
+    let token = "0x00000000000000000000000000000000000000bb";
+    let creator = "0x00000000000000000000000000000000000000cc";
+    let transfer =
+        |hash: &str, contract: &str, from: &str, to: &str, value: &str| EtherscanTokenTransaction {
+            hash: hash.to_owned(),
+            from: from.to_owned(),
+            contract_address: contract.to_owned(),
+            to: to.to_owned(),
+            value: value.to_owned(),
+            token_decimal: String::from("18"),
+            ..EtherscanTokenTransaction::default()
+        };
+    let half_eth = "500000000000000000";
+
+    let transfers = [
+        // two buys of the same wallet count once
+        transfer("0x1", WETH_ADDRESS, "0xrouter", pair, half_eth),
+        transfer("0x1", token, pair, "0xa", "1000"),
+        transfer("0x2", WETH_ADDRESS, "0xrouter", pair, half_eth),
+        transfer("0x2", token, pair, "0xa", "1000"),
+        transfer("0x3", WETH_ADDRESS, "0xrouter", pair, half_eth),
+        transfer("0x3", token, pair, "0xb", "1000"),
+        // the deployer's own buy
+        transfer("0x4", WETH_ADDRESS, "0xrouter", pair, half_eth),
+        transfer("0x4", token, pair, creator, "1000"),
+        // a sell
+        transfer("0x5", token, "0xb", pair, "1000"),
+        transfer("0x5", WETH_ADDRESS, pair, "0xrouter", half_eth),
+        // a bundled buy for two wallets splits the eth by the tokens each got
+        transfer("0x6", WETH_ADDRESS, "0xrouter", pair, "1000000000000000000"),
+        transfer("0x6", token, pair, "0xc", "3000"),
+        transfer("0x6", token, pair, "0xd", "1000"),
+    ];
+
+    let buys = early_buys(&transfers, pair, token, creator);
+    assert_eq!(buys.len(), 4);
+    assert_eq!(buys["0xa"], 1.0);
+    assert_eq!(buys["0xb"], 0.5);
+    assert_eq!(buys["0xc"], 0.75);
+    assert_eq!(buys["0xd"], 0.25);
+}
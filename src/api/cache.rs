@@ -0,0 +1,121 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::sync::Mutex;
+
+// lookups since the bot started, for /debug
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+impl CacheStats {
+    pub fn hit_percent(&self) -> Option<f64> {
+        match self.hits + self.misses {
+            0 => None,
+            lookups => Some(self.hits as f64 / lookups as f64 * 100.0),
+        }
+    }
+}
+
+// in-memory cache keyed by lowercase address, entries expire after the ttl
+// but are kept until max_stale so callers can fall back on them
+#[derive(Debug)]
+pub struct TtlCache<V> {
+    entries: Mutex<HashMap<String, (DateTime<Utc>, V)>>,
+    ttl: Duration,
+    max_stale: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_stale(ttl, ttl)
+    }
+
+    pub fn with_stale(ttl: Duration, max_stale: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            max_stale: max_stale.max(ttl),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Option<V> {
+        let entries = self.entries.lock().await;
+
+        match entries.get(&key.to_lowercase()) {
+            Some((inserted_at, value)) if Utc::now() - *inserted_at < self.ttl => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(value.clone())
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    // expired entry that is still within max_stale, with the time it was inserted
+    pub async fn get_stale(&self, key: &str) -> Option<(DateTime<Utc>, V)> {
+        let entries = self.entries.lock().await;
+
+        match entries.get(&key.to_lowercase()) {
+            Some((inserted_at, value)) if Utc::now() - *inserted_at < self.max_stale => {
+                Some((*inserted_at, value.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn insert(&self, key: &str, value: V) {
+        let mut entries = self.entries.lock().await;
+
+        // drop entries that can't be used anymore so the cache doesn't grow forever
+        let max_stale = self.max_stale;
+        entries.retain(|_, (inserted_at, _)| Utc::now() - *inserted_at < max_stale);
+        entries.insert(key.to_lowercase(), (Utc::now(), value));
+    }
+
+    // stale fallbacks aren't counted, only the lookups of fresh entries
+    pub async fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().await.len(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_cache_stats() {
+    let cache = TtlCache::new(Duration::try_minutes(1).unwrap());
+    assert_eq!(cache.stats().await.hit_percent(), None);
+
+    cache.insert("0xAbC", 1).await;
+    assert_eq!(cache.get("0xabc").await, Some(1));
+    assert_eq!(cache.get("0xabc").await, Some(1));
+    assert_eq!(cache.get("0xdef").await, None);
+
+    let stats = cache.stats().await;
+    assert_eq!(
+        stats,
+        CacheStats {
+            hits: 2,
+            misses: 1,
+            entries: 1
+        }
+    );
+    assert_eq!(
+        stats.hit_percent().map(|percent| percent.round()),
+        Some(67.0)
+    );
+}
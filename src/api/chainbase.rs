@@ -1,20 +1,30 @@
-use reqwest::{header::CONTENT_TYPE, Client};
+use super::{
+    instrumentation,
+    providers::{self, Provider},
+    usage,
+};
+use reqwest::header::CONTENT_TYPE;
 use serde::{de, Deserialize};
 use std::env;
 
 impl<T: de::DeserializeOwned> ChainbaseAPI<T> {
     async fn send_request(url: String) -> Result<ChainbaseAPI<T>, reqwest::Error> {
-        let response = Client::new()
-            .get(format!("https://api.chainbase.online/v1/{}", url))
-            .header(CONTENT_TYPE, "applciation/json")
-            .header(
-                "x-api-key",
-                env::var("CHAINBASE_API").expect("CHAINBASE_API env var is not set"),
-            )
-            .send()
-            .await?
-            .json()
-            .await?;
+        usage::record(Provider::Chainbase).await;
+        let response = providers::send(
+            Provider::Chainbase,
+            &instrumentation::endpoint(&url),
+            providers::client()
+                .get(format!("{}/{}", Provider::Chainbase.base_url(), url))
+                .headers(Provider::Chainbase.headers())
+                .header(CONTENT_TYPE, "applciation/json")
+                .header(
+                    "x-api-key",
+                    env::var("CHAINBASE_API").expect("CHAINBASE_API env var is not set"),
+                ),
+        )
+        .await?
+        .json()
+        .await?;
 
         Ok(response)
     }
@@ -40,7 +50,7 @@ pub struct ChainbaseAPI<T> {
     pub data: T,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct ChainbaseTokenOwners {
     pub wallet_address: String,
     pub original_amount: String,
@@ -0,0 +1,272 @@
+use super::{
+    cache::{CacheStats, TtlCache},
+    etherscan::EtherscanNormalTransaction,
+    get_normal_transactions,
+    labels::{known_label, LabelKind},
+};
+use chrono::{Duration, Utc};
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+
+// etherscan returns the latest 25 transactions, a full page means the wallet isn't fresh
+const NORMAL_TRANSACTIONS_PAGE: usize = 25;
+const NEW_WALLET_DAYS: i64 = 7;
+// buys further apart than this aren't considered coordinated
+const CLUSTER_WINDOW_MINUTES: i64 = 60;
+const MIN_CLUSTER_WALLETS: usize = 3;
+// fresh wallets funded by fresh wallets are followed up to this many hops
+const MAX_FUNDING_HOPS: usize = 3;
+// every lookup is an etherscan request, the rest of the buyers are looked up in the next cycles
+const MAX_FUNDING_LOOKUPS: usize = 10;
+
+lazy_static! {
+    // wallet -> where its first eth came from, None for wallets that aren't fresh
+    static ref FUNDERS: TtlCache<Option<String>> = TtlCache::new(Duration::try_hours(6).unwrap());
+}
+
+pub async fn funder_cache_stats() -> CacheStats {
+    FUNDERS.stats().await
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterBuy {
+    pub funder: String,
+    pub wallets: Vec<String>,
+}
+
+// recent buyers of a watched token, and the funders already reported for it
+#[derive(Debug, Default)]
+pub struct ClusterTracker {
+    buys: Vec<(String, i64)>,
+    reported: HashSet<String>,
+}
+
+impl ClusterTracker {
+    // clusters are only reported once per token and funder
+    pub async fn record_buys(&mut self, buys: Vec<(String, i64)>) -> Vec<ClusterBuy> {
+        let cutoff = Utc::now().timestamp() - CLUSTER_WINDOW_MINUTES * 60;
+
+        self.buys.retain(|(_, time_stamp)| *time_stamp >= cutoff);
+        for (wallet, time_stamp) in buys {
+            let wallet = wallet.to_lowercase();
+            if time_stamp >= cutoff && !self.buys.iter().any(|(w, _)| *w == wallet) {
+                self.buys.push((wallet, time_stamp));
+            }
+        }
+
+        let (edges, _) = funding_edges(&self.buys).await;
+        find_clusters(&self.buys, &edges)
+            .into_iter()
+            .filter(|cluster| self.reported.insert(cluster.funder.clone()))
+            .collect()
+    }
+}
+
+// the first incoming eth of a wallet with a short history, wallets funded by exchanges are skipped
+// as exchange hot wallets fund thousands of unrelated users
+fn fresh_wallet_funder(
+    wallet: &str,
+    transactions: &[EtherscanNormalTransaction],
+    now: i64,
+) -> Option<String> {
+    if transactions.is_empty() || transactions.len() >= NORMAL_TRANSACTIONS_PAGE {
+        return None;
+    }
+
+    let wallet = wallet.to_lowercase();
+    let first_time_stamp = transactions
+        .iter()
+        .filter_map(|t| t.time_stamp.parse::<i64>().ok())
+        .min()?;
+    if now - first_time_stamp > NEW_WALLET_DAYS * 24 * 60 * 60 {
+        return None;
+    }
+
+    let (_, funder) = transactions
+        .iter()
+        .filter(|t| {
+            t.to.to_lowercase() == wallet
+                && t.from.to_lowercase() != wallet
+                && t.value.parse::<f64>().unwrap_or(0.0) > 0.0
+        })
+        .filter_map(|t| Some((t.time_stamp.parse::<i64>().ok()?, t.from.to_lowercase())))
+        .min()?;
+
+    if matches!(known_label(&funder), Some((_, LabelKind::Exchange))) {
+        return None;
+    }
+
+    Some(funder)
+}
+
+// wallet -> funder edges of the buyers and their fresh funders, and the wallets that couldn't be looked up
+async fn funding_edges(buys: &[(String, i64)]) -> (HashMap<String, String>, HashSet<String>) {
+    let mut edges = HashMap::new();
+    let mut skipped = HashSet::new();
+    let mut lookups = 0;
+    let mut pending: Vec<(String, usize)> = buys.iter().map(|(w, _)| (w.clone(), 0)).collect();
+
+    while let Some((wallet, hops)) = pending.pop() {
+        if edges.contains_key(&wallet) {
+            continue;
+        }
+
+        let funder = match FUNDERS.get(&wallet).await {
+            Some(funder) => funder,
+            None if lookups < MAX_FUNDING_LOOKUPS => {
+                lookups += 1;
+                match get_normal_transactions(wallet.clone()).await {
+                    Ok(transactions) => {
+                        let funder =
+                            fresh_wallet_funder(&wallet, &transactions, Utc::now().timestamp());
+                        FUNDERS.insert(&wallet, funder.clone()).await;
+                        funder
+                    }
+                    Err(e) => {
+                        error!("get_normal_transactions error: {}", e);
+                        skipped.insert(wallet.clone());
+                        None
+                    }
+                }
+            }
+            None => {
+                skipped.insert(wallet.clone());
+                None
+            }
+        };
+
+        if let Some(funder) = funder {
+            if hops + 1 < MAX_FUNDING_HOPS {
+                pending.push((funder.clone(), hops + 1));
+            }
+            edges.insert(wallet, funder);
+        }
+    }
+
+    (edges, skipped)
+}
+
+// follows the funding edges to the oldest funder, so wallets funded through
+// intermediate fresh wallets still end up under the same source
+fn funding_source(wallet: &str, edges: &HashMap<String, String>) -> Option<String> {
+    let mut source = edges.get(wallet)?;
+
+    for _ in 1..MAX_FUNDING_HOPS {
+        match edges.get(source) {
+            Some(funder) => source = funder,
+            None => break,
+        }
+    }
+
+    Some(source.clone())
+}
+
+// whether the wallet's fresh funding leads back to the funder within the followed hops
+fn is_funded_by(wallet: &str, funder: &str, edges: &HashMap<String, String>) -> bool {
+    let mut current = wallet;
+
+    for _ in 0..MAX_FUNDING_HOPS {
+        match edges.get(current) {
+            Some(source) if source == funder => return true,
+            Some(source) => current = source,
+            None => break,
+        }
+    }
+
+    false
+}
+
+// whether the funding path of the wallet runs into a wallet that couldn't be looked up
+fn is_undecided(wallet: &str, edges: &HashMap<String, String>, skipped: &HashSet<String>) -> bool {
+    let mut current = wallet;
+
+    for _ in 0..MAX_FUNDING_HOPS {
+        if skipped.contains(current) {
+            return true;
+        }
+        match edges.get(current) {
+            Some(source) => current = source,
+            None => break,
+        }
+    }
+
+    false
+}
+
+// wallet -> whether the funder funded it, e.g. the fresh wallets a deployer buys its own launch with.
+// wallets that couldn't be looked up yet are left out, so they're checked again later
+pub async fn funded_by(wallets: &[String], funder: &str) -> HashMap<String, bool> {
+    let funder = funder.to_lowercase();
+    let buys: Vec<(String, i64)> = wallets
+        .iter()
+        .map(|wallet| (wallet.to_lowercase(), 0))
+        .collect();
+    let (edges, skipped) = funding_edges(&buys).await;
+
+    buys.into_iter()
+        .map(|(wallet, _)| wallet)
+        .filter_map(|wallet| {
+            let funded = is_funded_by(&wallet, &funder, &edges);
+            (funded || !is_undecided(&wallet, &edges, &skipped)).then_some((wallet, funded))
+        })
+        .collect()
+}
+
+fn find_clusters(buys: &[(String, i64)], edges: &HashMap<String, String>) -> Vec<ClusterBuy> {
+    let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+
+    for (wallet, _) in buys {
+        if let Some(source) = funding_source(wallet, edges) {
+            sources.entry(source).or_default().push(wallet.clone());
+        }
+    }
+
+    let mut clusters: Vec<ClusterBuy> = sources
+        .into_iter()
+        .filter(|(_, wallets)| wallets.len() >= MIN_CLUSTER_WALLETS)
+        .map(|(funder, wallets)| ClusterBuy { funder, wallets })
+        .collect();
+    clusters.sort_by(|a, b| b.wallets.len().cmp(&a.wallets.len()));
+
+    clusters
+}
+
+#[test]
+fn test_find_clusters() {
+    let edge = |wallet: &str, funder: &str| (wallet.to_owned(), funder.to_owned());
+    let buys: Vec<(String, i64)> = ["0xa", "0xb", "0xc", "0xd", "0xe"]
+        .iter()
+        .map(|wallet| (wallet.to_string(), 0))
+        .collect();
+
+    // 0xc was funded through the fresh wallet 0xhop, 0xe has no fresh funding
+    let edges = HashMap::from([
+        edge("0xa", "0xsource"),
+        edge("0xb", "0xsource"),
+        edge("0xc", "0xhop"),
+        edge("0xhop", "0xsource"),
+        edge("0xd", "0xother"),
+    ]);
+
+    assert_eq!(
+        find_clusters(&buys, &edges),
+        vec![ClusterBuy {
+            funder: String::from("0xsource"),
+            wallets: vec![
+                String::from("0xa"),
+                String::from("0xb"),
+                String::from("0xc")
+            ],
+        }]
+    );
+    assert!(find_clusters(&buys[3..], &edges).is_empty());
+
+    assert!(is_funded_by("0xc", "0xsource", &edges));
+    assert!(!is_funded_by("0xd", "0xsource", &edges));
+
+    // 0xhop's funder couldn't be looked up, so 0xc isn't known yet
+    let skipped = HashSet::from([String::from("0xhop"), String::from("0xe")]);
+    assert!(is_undecided("0xc", &edges, &skipped));
+    assert!(is_undecided("0xe", &edges, &skipped));
+    assert!(!is_undecided("0xd", &edges, &skipped));
+}
@@ -0,0 +1,127 @@
+use super::etherscan::EtherscanTokenTransaction;
+use crate::telegram::alerts::WalletActivity;
+use std::collections::HashMap;
+
+// several watched wallets buying the same token in one cycle is sent as a single alert
+const MIN_CONVERGENT_WALLETS: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct WalletTransaction {
+    pub wallet: String,
+    pub transaction: EtherscanTokenTransaction,
+    pub activity: Option<WalletActivity>,
+}
+
+impl WalletTransaction {
+    // tokens coming into the wallet, transfers between wallets aren't buys
+    fn is_buy(&self) -> bool {
+        self.transaction.to.to_lowercase() == self.wallet.to_lowercase()
+            && !self.activity.is_some_and(|activity| activity.is_transfer())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConvergentBuy {
+    pub contract: String,
+    pub token_name: String,
+    pub token_symbol: String,
+    // one buy per wallet, the first one of the cycle
+    pub buys: Vec<WalletTransaction>,
+}
+
+// returns the convergent buys, most wallets first, and the transactions still alerted one by one
+pub fn group_convergent_buys(
+    transactions: Vec<WalletTransaction>,
+) -> (Vec<ConvergentBuy>, Vec<WalletTransaction>) {
+    let mut buyers = HashMap::<String, Vec<String>>::new();
+    for transaction in transactions.iter().filter(|t| t.is_buy()) {
+        let wallets = buyers
+            .entry(transaction.transaction.contract_address.to_lowercase())
+            .or_default();
+        let wallet = transaction.wallet.to_lowercase();
+        if !wallets.contains(&wallet) {
+            wallets.push(wallet);
+        }
+    }
+
+    let mut convergent = HashMap::<String, ConvergentBuy>::new();
+    let mut singles = vec![];
+    for transaction in transactions {
+        let contract = transaction.transaction.contract_address.to_lowercase();
+        let converged = transaction.is_buy()
+            && buyers
+                .get(&contract)
+                .is_some_and(|wallets| wallets.len() >= MIN_CONVERGENT_WALLETS);
+
+        if !converged {
+            singles.push(transaction);
+            continue;
+        }
+
+        let buy = convergent
+            .entry(contract.clone())
+            .or_insert_with(|| ConvergentBuy {
+                contract,
+                token_name: transaction.transaction.token_name.clone(),
+                token_symbol: transaction.transaction.token_symbol.clone(),
+                buys: vec![],
+            });
+        if !buy
+            .buys
+            .iter()
+            .any(|b| b.wallet.to_lowercase() == transaction.wallet.to_lowercase())
+        {
+            buy.buys.push(transaction);
+        }
+    }
+
+    let mut convergent: Vec<ConvergentBuy> = convergent.into_values().collect();
+    convergent.sort_by(|a, b| b.buys.len().cmp(&a.buys.len()));
+
+    (convergent, singles)
+}
+
+#[test]
+fn test_group_convergent_buys() {
+    let token = "0x3333333333333333333333333333333333333333";
+    let other = "0x4444444444444444444444444444444444444444";
+    let transaction = |wallet: &str, contract: &str, incoming: bool| WalletTransaction {
+        wallet: wallet.to_owned(),
+        transaction: EtherscanTokenTransaction {
+            time_stamp: String::from("100"),
+            from: if incoming { "0xpool" } else { wallet }.to_owned(),
+            contract_address: contract.to_owned(),
+            to: if incoming { wallet } else { "0xpool" }.to_owned(),
+            value: String::from("1"),
+            token_name: String::from("Pepe"),
+            token_symbol: String::from("PEPE"),
+            token_decimal: String::from("18"),
+            ..EtherscanTokenTransaction::default()
+        },
+        activity: None,
+    };
+
+    let (convergent, singles) = group_convergent_buys(vec![
+        transaction("0xa", token, true),
+        transaction("0xb", token, true),
+        // a second buy of the same wallet isn't alerted again
+        transaction("0xb", token, true),
+        transaction("0xc", token, true),
+        // sells and tokens only two wallets bought are alerted one by one
+        transaction("0xd", token, false),
+        transaction("0xa", other, true),
+        transaction("0xb", other, true),
+    ]);
+
+    assert_eq!(convergent.len(), 1);
+    assert_eq!(convergent[0].contract, token);
+    assert_eq!(
+        convergent[0]
+            .buys
+            .iter()
+            .map(|buy| buy.wallet.as_str())
+            .collect::<Vec<&str>>(),
+        vec!["0xa", "0xb", "0xc"]
+    );
+    assert_eq!(singles.len(), 3);
+}
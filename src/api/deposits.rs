@@ -0,0 +1,86 @@
+use super::etherscan::EtherscanTokenTransaction;
+use std::collections::HashMap;
+
+// share of an address' outgoing transfers that must reach exchanges for it to be a deposit address
+const MIN_SWEPT_PERCENT: usize = 80;
+
+/*
+
+Exchanges give every customer a deposit address of their own and sweep what
+arrives there to their hot wallets. Tokens sent to a labelled exchange address
+are a deposit, and so are tokens sent to an address that forwards nearly all
+it receives to labelled exchange addresses. A wallet depositing a token is
+likely selling it on the exchange, which a DEX sell alert wouldn't tell.
+
+Only the latest transfers of the recipient are looked at, so a deposit
+address that never swept yet isn't recognized.
+
+*/
+// the exchange the address sweeps to, exchange_of names the labelled exchange addresses
+pub fn swept_to(
+    address: &str,
+    transfers: &[EtherscanTokenTransaction],
+    exchange_of: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let address = address.to_lowercase();
+    let outgoing: Vec<&EtherscanTokenTransaction> = transfers
+        .iter()
+        .filter(|t| t.from.to_lowercase() == address)
+        .collect();
+    if outgoing.is_empty() {
+        return None;
+    }
+
+    let mut exchanges = HashMap::<String, usize>::new();
+    for transfer in &outgoing {
+        if let Some(exchange) = exchange_of(&transfer.to) {
+            *exchanges.entry(exchange).or_default() += 1;
+        }
+    }
+
+    let swept: usize = exchanges.values().sum();
+    if swept * 100 < outgoing.len() * MIN_SWEPT_PERCENT {
+        return None;
+    }
+    exchanges
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(exchange, _)| exchange)
+}
+
+#[test]
+fn test_swept_to() {
+    let transfer = |from: &str, to: &str| EtherscanTokenTransaction {
+        from: from.to_owned(),
+        to: to.to_owned(),
+        ..EtherscanTokenTransaction::default()
+    };
+    let exchange_of = |address: &str| match address {
+        "0xbinance14" | "0xbinance15" => Some(String::from("Binance")),
+        "0xkraken" => Some(String::from("Kraken")),
+        _ => None,
+    };
+
+    let deposit = [
+        transfer("0xalice", "0xdeposit"),
+        transfer("0xDEPOSIT", "0xbinance14"),
+        transfer("0xbob", "0xdeposit"),
+        transfer("0xdeposit", "0xbinance15"),
+    ];
+    assert_eq!(
+        swept_to("0xdeposit", &deposit, exchange_of),
+        Some(String::from("Binance"))
+    );
+
+    // a trader who once sent tokens to an exchange isn't its deposit address
+    let trader = [
+        transfer("0xtrader", "0xkraken"),
+        transfer("0xtrader", "0xpair"),
+        transfer("0xtrader", "0xfriend"),
+    ];
+    assert_eq!(swept_to("0xtrader", &trader, exchange_of), None);
+    assert_eq!(
+        swept_to("0xfresh", &[transfer("0xalice", "0xfresh")], exchange_of),
+        None
+    );
+}
@@ -0,0 +1,248 @@
+use super::providers::Provider;
+use core::fmt;
+use lazy_static::lazy_static;
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+};
+use tokio::sync::Mutex;
+
+const DEFAULT_BUDGET_PERCENT: f64 = 50.0;
+const DEFAULT_WINDOW_MINUTES: i64 = 10;
+// a handful of failed requests in a quiet window isn't an outage
+const MIN_WINDOW_REQUESTS: u64 = 20;
+
+// PROVIDER_ERROR_BUDGET_PERCENT, the share of failed requests a provider's outage starts at
+pub fn budget_percent() -> f64 {
+    env::var("PROVIDER_ERROR_BUDGET_PERCENT")
+        .ok()
+        .and_then(|percent| percent.parse::<f64>().ok())
+        .filter(|percent| *percent > 0.0 && *percent <= 100.0)
+        .unwrap_or(DEFAULT_BUDGET_PERCENT)
+}
+
+// PROVIDER_ERROR_WINDOW_MINUTES, how far back the failed requests are counted
+pub fn window_minutes() -> i64 {
+    env::var("PROVIDER_ERROR_WINDOW_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse::<i64>().ok())
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(DEFAULT_WINDOW_MINUTES)
+}
+
+// requests without a response, rate limits and server errors, a 404 is the provider answering
+pub fn is_failure(status: &str) -> bool {
+    !status.chars().all(|c| c.is_ascii_digit()) || status == "429" || status.starts_with('5')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetChange {
+    Exhausted {
+        provider: String,
+        error_percent: f64,
+        requests: u64,
+    },
+    Recovered {
+        provider: String,
+        // minutes since the outage started
+        minutes: i64,
+    },
+}
+
+impl fmt::Display for BudgetChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetChange::Exhausted {
+                provider,
+                error_percent,
+                requests,
+            } => write!(
+                f,
+                "🔴 {}: {:.0}% of {} requests failed in the last {} minutes",
+                provider,
+                error_percent,
+                requests,
+                window_minutes()
+            ),
+            BudgetChange::Recovered { provider, minutes } => {
+                write!(f, "🟢 {} recovered after {} minutes", provider, minutes)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ErrorBudgets {
+    // provider -> (minute, requests, failures) of the minutes in the window, oldest first
+    windows: HashMap<String, VecDeque<(i64, u64, u64)>>,
+    // provider -> minute its outage started
+    exhausted: HashMap<String, i64>,
+}
+
+lazy_static! {
+    static ref ERROR_BUDGETS: Mutex<ErrorBudgets> = Mutex::new(ErrorBudgets::default());
+}
+
+/*
+
+A provider that fails more than PROVIDER_ERROR_BUDGET_PERCENT of its requests
+over the last PROVIDER_ERROR_WINDOW_MINUTES has spent its error budget. The
+owner gets one alert for the outage instead of a log line per failed request,
+and chats aren't told about every DCA buy or TWAP slice skipped because of it.
+The outage is over once the failures drop below half the budget, so a provider
+hovering around it doesn't alert on every check.
+
+*/
+impl ErrorBudgets {
+    pub fn record(&mut self, provider: &str, failed: bool, minute: i64) {
+        let window = self.windows.entry(provider.to_owned()).or_default();
+
+        match window.back_mut() {
+            Some((last, requests, failures)) if *last == minute => {
+                *requests += 1;
+                *failures += failed as u64;
+            }
+            _ => window.push_back((minute, 1, failed as u64)),
+        }
+    }
+
+    // (requests, failures) of the provider in the window ending at minute
+    fn totals(&mut self, provider: &str, minute: i64, window_minutes: i64) -> (u64, u64) {
+        let Some(window) = self.windows.get_mut(provider) else {
+            return (0, 0);
+        };
+        while window
+            .front()
+            .is_some_and(|(first, _, _)| minute - first >= window_minutes)
+        {
+            window.pop_front();
+        }
+
+        window
+            .iter()
+            .fold((0, 0), |(requests, failures), (_, r, f)| {
+                (requests + r, failures + f)
+            })
+    }
+
+    pub fn check(
+        &mut self,
+        minute: i64,
+        budget_percent: f64,
+        window_minutes: i64,
+    ) -> Vec<BudgetChange> {
+        let mut providers: Vec<String> = self.windows.keys().cloned().collect();
+        providers.sort();
+        let mut changes = vec![];
+
+        for provider in providers {
+            let (requests, failures) = self.totals(&provider, minute, window_minutes);
+            let error_percent = match requests {
+                0 => 0.0,
+                _ => failures as f64 / requests as f64 * 100.0,
+            };
+
+            match self.exhausted.get(&provider) {
+                None if requests >= MIN_WINDOW_REQUESTS && error_percent >= budget_percent => {
+                    self.exhausted.insert(provider.clone(), minute);
+                    changes.push(BudgetChange::Exhausted {
+                        provider,
+                        error_percent,
+                        requests,
+                    });
+                }
+                Some(since) if error_percent < budget_percent / 2.0 => {
+                    changes.push(BudgetChange::Recovered {
+                        minutes: minute - since,
+                        provider: provider.clone(),
+                    });
+                    self.exhausted.remove(&provider);
+                }
+                _ => {}
+            }
+        }
+
+        changes
+    }
+
+    pub fn is_exhausted(&self, provider: &str) -> bool {
+        self.exhausted.contains_key(provider)
+    }
+}
+
+pub async fn record(provider: Provider, status: &str, minute: i64) {
+    ERROR_BUDGETS
+        .lock()
+        .await
+        .record(&provider.to_string(), is_failure(status), minute);
+}
+
+pub async fn check(minute: i64) -> Vec<BudgetChange> {
+    ERROR_BUDGETS
+        .lock()
+        .await
+        .check(minute, budget_percent(), window_minutes())
+}
+
+// the providers in an outage right now
+pub async fn exhausted() -> Vec<Provider> {
+    let budgets = ERROR_BUDGETS.lock().await;
+
+    Provider::all()
+        .into_iter()
+        .filter(|provider| budgets.is_exhausted(&provider.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_error_budgets() {
+    let mut budgets = ErrorBudgets::default();
+    let record = |budgets: &mut ErrorBudgets, minute: i64, requests: u64, failures: u64| {
+        for i in 0..requests {
+            budgets.record("Etherscan", i < failures, minute);
+        }
+    };
+
+    // half of the requests failing for a few minutes, but too few of them to tell
+    record(&mut budgets, 0, 4, 2);
+    assert!(budgets.check(0, 50.0, 10).is_empty());
+
+    for minute in 1..=5 {
+        record(&mut budgets, minute, 10, 6);
+    }
+    assert_eq!(
+        budgets.check(5, 50.0, 10),
+        vec![BudgetChange::Exhausted {
+            provider: String::from("Etherscan"),
+            error_percent: 32.0 / 54.0 * 100.0,
+            requests: 54
+        }]
+    );
+    assert!(budgets.is_exhausted("Etherscan"));
+    // one alert per outage
+    record(&mut budgets, 6, 10, 10);
+    assert!(budgets.check(6, 50.0, 10).is_empty());
+
+    // still above half the budget while the failed minutes are in the window
+    for minute in 7..=12 {
+        record(&mut budgets, minute, 10, 0);
+    }
+    assert!(budgets.check(12, 50.0, 10).is_empty());
+    for minute in 13..=16 {
+        record(&mut budgets, minute, 10, 0);
+    }
+    assert_eq!(
+        budgets.check(16, 50.0, 10),
+        vec![BudgetChange::Recovered {
+            provider: String::from("Etherscan"),
+            minutes: 11
+        }]
+    );
+    assert!(!budgets.is_exhausted("Etherscan"));
+
+    assert!(is_failure("timeout"));
+    assert!(is_failure("503"));
+    assert!(is_failure("429"));
+    assert!(!is_failure("404"));
+    assert!(!is_failure("200"));
+}
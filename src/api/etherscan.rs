@@ -1,15 +1,39 @@
-use reqwest::Client;
+use super::{
+    instrumentation,
+    providers::{self, Provider},
+    usage,
+};
 use serde::{de, Deserialize, Serialize};
 use std::env;
 
+// Etherscan's v2 api serves every chain it indexes with the same key, e.g. Base
+fn multichain_url() -> String {
+    format!(
+        "{}/v2/api",
+        Provider::Etherscan.base_url().trim_end_matches("/api")
+    )
+}
+
 impl<T: de::DeserializeOwned> EtherscanAPI<T> {
     async fn send_request(url: String) -> Result<EtherscanAPI<T>, reqwest::Error> {
-        let response: EtherscanAPI<T> = Client::new()
-            .get(format!("https://api.etherscan.io/api?{}", url))
-            .send()
-            .await?
-            .json()
-            .await?;
+        EtherscanAPI::send_request_to(Provider::Etherscan.base_url(), url).await
+    }
+
+    async fn send_request_to(
+        base_url: String,
+        url: String,
+    ) -> Result<EtherscanAPI<T>, reqwest::Error> {
+        usage::record(Provider::Etherscan).await;
+        let response: EtherscanAPI<T> = providers::send(
+            Provider::Etherscan,
+            &instrumentation::etherscan_endpoint(&url),
+            providers::client()
+                .get(format!("{}?{}", base_url, url))
+                .headers(Provider::Etherscan.headers()),
+        )
+        .await?
+        .json()
+        .await?;
 
         Ok(response)
     }
@@ -26,6 +50,7 @@ impl<T: de::DeserializeOwned> EtherscanAPI<T> {
 
     pub async fn get_normal_transactions(
         address: String,
+        number_of_transactions: u16,
     ) -> Result<EtherscanAPI<Vec<EtherscanNormalTransaction>>, reqwest::Error> {
         EtherscanAPI::send_request(format!(
             "module=account\
@@ -34,10 +59,33 @@ impl<T: de::DeserializeOwned> EtherscanAPI<T> {
             &startblock=0\
             &endblock=99999999\
             &page=1\
-            &offset=25\
+            &offset={}\
             &sort=desc\
             &apikey={}",
             address,
+            number_of_transactions,
+            env::var("ETHERSCAN_API").expect("ETHERSCAN_API env var is not set")
+        ))
+        .await
+    }
+
+    // oldest first, a wallet's first funding is among them
+    pub async fn get_first_normal_transactions(
+        address: String,
+        number_of_transactions: u16,
+    ) -> Result<EtherscanAPI<Vec<EtherscanNormalTransaction>>, reqwest::Error> {
+        EtherscanAPI::send_request(format!(
+            "module=account\
+            &action=txlist\
+            &address={}\
+            &startblock=0\
+            &endblock=99999999\
+            &page=1\
+            &offset={}\
+            &sort=asc\
+            &apikey={}",
+            address,
+            number_of_transactions,
             env::var("ETHERSCAN_API").expect("ETHERSCAN_API env var is not set")
         ))
         .await
@@ -64,19 +112,127 @@ impl<T: de::DeserializeOwned> EtherscanAPI<T> {
         .await
     }
 
+    // oldest first, eth sent by contracts like mixers only shows up here
+    pub async fn get_first_internal_transactions(
+        address: String,
+        number_of_transactions: u8,
+    ) -> Result<EtherscanAPI<Vec<EtherscanInternalTransaction>>, reqwest::Error> {
+        EtherscanAPI::send_request(format!(
+            "module=account\
+            &action=txlistinternal\
+            &address={}\
+            &startblock=0\
+            &endblock=99999999\
+            &page=1\
+            &offset={}\
+            &sort=asc\
+            &apikey={}",
+            address,
+            number_of_transactions,
+            env::var("ETHERSCAN_API").expect("ETHERSCAN_API env var is not set")
+        ))
+        .await
+    }
+
+    pub async fn get_internal_transactions_on(
+        chain_id: u64,
+        address: String,
+        number_of_transactions: u8,
+    ) -> Result<EtherscanAPI<Vec<EtherscanInternalTransaction>>, reqwest::Error> {
+        EtherscanAPI::send_request_to(
+            multichain_url(),
+            format!(
+                "chainid={}\
+                &module=account\
+                &action=txlistinternal\
+                &address={}\
+                &startblock=0\
+                &endblock=99999999\
+                &page=1\
+                &offset={}\
+                &sort=desc\
+                &apikey={}",
+                chain_id,
+                address,
+                number_of_transactions,
+                env::var("ETHERSCAN_API").expect("ETHERSCAN_API env var is not set")
+            ),
+        )
+        .await
+    }
+
     pub async fn get_token_transactions(
         address: String,
+        page: u32,
+        offset: u32,
+    ) -> Result<EtherscanAPI<Vec<EtherscanTokenTransaction>>, reqwest::Error> {
+        EtherscanAPI::send_request(format!(
+            "module=account\
+            &action=tokentx\
+            &address={}\
+            &page={}\
+            &offset={}\
+            &startblock=0\
+            &endblock=99999999\
+            &sort=desc\
+            &apikey={}",
+            address,
+            page,
+            offset,
+            env::var("ETHERSCAN_API").expect("ETHERSCAN_API env var is not set")
+        ))
+        .await
+    }
+
+    // oldest first, a pair's first swaps are among them
+    pub async fn get_first_token_transactions(
+        address: String,
+        offset: u32,
     ) -> Result<EtherscanAPI<Vec<EtherscanTokenTransaction>>, reqwest::Error> {
         EtherscanAPI::send_request(format!(
             "module=account\
             &action=tokentx\
             &address={}\
             &page=1\
-            &offset=100\
+            &offset={}\
+            &startblock=0\
+            &endblock=99999999\
+            &sort=asc\
+            &apikey={}",
+            address,
+            offset,
+            env::var("ETHERSCAN_API").expect("ETHERSCAN_API env var is not set")
+        ))
+        .await
+    }
+
+    pub async fn get_contract_token_transfers(
+        contract: String,
+    ) -> Result<EtherscanAPI<Vec<EtherscanTokenTransaction>>, reqwest::Error> {
+        EtherscanAPI::send_request(format!(
+            "module=account\
+            &action=tokentx\
+            &contractaddress={}\
+            &page=1\
+            &offset=1000\
             &startblock=0\
             &endblock=99999999\
             &sort=desc\
             &apikey={}",
+            contract,
+            env::var("ETHERSCAN_API").expect("ETHERSCAN_API env var is not set")
+        ))
+        .await
+    }
+
+    pub async fn get_source_code(
+        address: String,
+    ) -> Result<EtherscanAPI<Vec<EtherscanSourceCode>>, reqwest::Error> {
+        EtherscanAPI::send_request(format!(
+            "module=contract\
+            &action=getsourcecode\
+            &address={}\
+            &apikey={}",
             address,
             env::var("ETHERSCAN_API").expect("ETHERSCAN_API env var is not set")
         ))
@@ -107,6 +263,16 @@ pub struct EtherscanAPI<T> {
     pub result: T,
 }
 
+// only the contract name is needed, it's empty for wallets and unverified contracts
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EtherscanSourceCode {
+    #[serde(rename = "ContractName")]
+    pub contract_name: String,
+    // empty for unverified contracts
+    #[serde(rename = "SourceCode", default)]
+    pub source_code: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct EtherscanEthPrices {
     pub ethbtc: String,
@@ -115,7 +281,7 @@ pub struct EtherscanEthPrices {
     pub ethusd_timestamp: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct EtherscanNormalTransaction {
     pub block_number: String,
@@ -161,7 +327,7 @@ pub struct EtherscanInternalTransaction {
     pub err_code: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct EtherscanTokenTransaction {
     pub block_number: String,
@@ -191,4 +357,7 @@ pub struct EtherscanContractCreatorAndTxHash {
     pub contract_address: String,
     pub contract_creator: String,
     pub tx_hash: String,
+    // unix seconds, missing from older responses and some gateways
+    #[serde(default)]
+    pub timestamp: String,
 }
@@ -0,0 +1,26 @@
+use super::{
+    providers::{self, Provider},
+    usage,
+};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::Value;
+
+// the relay checks the signature against the exact body, so the caller builds the body once and
+// signs it. a refused bundle is a json-rpc error, not an http one
+pub async fn send_bundle(body: String, signature: String) -> Result<Value, reqwest::Error> {
+    usage::record(Provider::Flashbots).await;
+    providers::send(
+        Provider::Flashbots,
+        "eth_sendBundle",
+        providers::client()
+            .post(Provider::Flashbots.base_url())
+            .headers(Provider::Flashbots.headers())
+            .header(CONTENT_TYPE, "application/json")
+            .header("X-Flashbots-Signature", signature)
+            .body(body),
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await
+}
@@ -0,0 +1,215 @@
+use super::{
+    cache::{CacheStats, TtlCache},
+    etherscan::{EtherscanAPI, EtherscanInternalTransaction, EtherscanNormalTransaction},
+    labels::{known_label, LabelKind},
+};
+use chrono::Duration;
+use lazy_static::lazy_static;
+use std::env;
+
+const DEFAULT_TRACE_DEPTH: usize = 3;
+// every hop is two etherscan requests
+const MAX_TRACE_DEPTH: usize = 6;
+// the oldest transfers of a wallet, its first funding is among them
+const FIRST_TRANSACTIONS: u8 = 20;
+
+pub fn trace_depth() -> usize {
+    env::var("FUNDING_TRACE_DEPTH")
+        .ok()
+        .and_then(|depth| depth.parse::<usize>().ok())
+        .filter(|depth| *depth > 0)
+        .map_or(DEFAULT_TRACE_DEPTH, |depth| depth.min(MAX_TRACE_DEPTH))
+}
+
+// the first eth a wallet received
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingHop {
+    pub wallet: String,
+    pub funder: String,
+    pub eth: f64,
+    pub time_stamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FundingOrigin {
+    // the label's name
+    Mixer(String),
+    Exchange(String),
+    Flagged(String),
+    // the last wallet never received eth, e.g. it was funded with tokens
+    Unfunded,
+    // the configured depth was traced without reaching a known address
+    DepthReached,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingPath {
+    pub wallet: String,
+    // from the wallet back to its origin
+    pub hops: Vec<FundingHop>,
+    pub origin: FundingOrigin,
+}
+
+impl FundingPath {
+    // funded from a mixer or by a flagged wallet, the usual setup of a rug
+    pub fn is_suspicious(&self) -> bool {
+        matches!(
+            self.origin,
+            FundingOrigin::Mixer(_) | FundingOrigin::Flagged(_)
+        )
+    }
+}
+
+// known addresses end the trace, exchanges fund thousands of unrelated wallets
+fn origin(address: &str) -> Option<FundingOrigin> {
+    match known_label(address)? {
+        (name, LabelKind::Mixer) => Some(FundingOrigin::Mixer(name)),
+        (name, LabelKind::Exchange) => Some(FundingOrigin::Exchange(name)),
+        (name, LabelKind::Flagged) => Some(FundingOrigin::Flagged(name)),
+        _ => None,
+    }
+}
+
+// (time stamp, from, to, wei, failed) of both kinds of transfers
+type Transfer = (i64, String, String, String, bool);
+
+fn first_funding(wallet: &str, transfers: &[Transfer]) -> Option<FundingHop> {
+    let wallet = wallet.to_lowercase();
+
+    transfers
+        .iter()
+        .filter(|(_, from, to, wei, failed)| {
+            !failed
+                && to.to_lowercase() == wallet
+                && from.to_lowercase() != wallet
+                && wei.parse::<f64>().unwrap_or(0.0) > 0.0
+        })
+        .min_by_key(|(time_stamp, ..)| *time_stamp)
+        .map(|(time_stamp, from, _, wei, _)| FundingHop {
+            wallet: wallet.clone(),
+            funder: from.to_lowercase(),
+            eth: wei.parse::<f64>().unwrap_or(0.0) / 10f64.powi(18),
+            time_stamp: *time_stamp,
+        })
+}
+
+lazy_static! {
+    // wallet -> its first funding, which never changes
+    static ref FIRST_FUNDING: TtlCache<Option<FundingHop>> =
+        TtlCache::new(Duration::try_days(1).unwrap());
+}
+
+pub async fn funding_cache_stats() -> CacheStats {
+    FIRST_FUNDING.stats().await
+}
+
+async fn get_first_funding(wallet: &str) -> Result<Option<FundingHop>, reqwest::Error> {
+    if let Some(hop) = FIRST_FUNDING.get(wallet).await {
+        return Ok(hop);
+    }
+
+    let (normal, internal) = tokio::join!(
+        EtherscanAPI::<Vec<EtherscanNormalTransaction>>::get_first_normal_transactions(
+            wallet.to_owned(),
+            u16::from(FIRST_TRANSACTIONS)
+        ),
+        EtherscanAPI::<Vec<EtherscanInternalTransaction>>::get_first_internal_transactions(
+            wallet.to_owned(),
+            FIRST_TRANSACTIONS
+        )
+    );
+    let normal = normal.map_err(|e| e.without_url())?.result;
+    let internal = internal.map_err(|e| e.without_url())?.result;
+
+    let transfers: Vec<Transfer> = normal
+        .into_iter()
+        .map(|t| (t.time_stamp, t.from, t.to, t.value, t.is_error != "0"))
+        .chain(
+            internal
+                .into_iter()
+                .map(|t| (t.time_stamp, t.from, t.to, t.value, t.is_error != "0")),
+        )
+        .filter_map(|(time_stamp, from, to, value, failed)| {
+            Some((time_stamp.parse::<i64>().ok()?, from, to, value, failed))
+        })
+        .collect();
+
+    let hop = first_funding(wallet, &transfers);
+    FIRST_FUNDING.insert(wallet, hop.clone()).await;
+    Ok(hop)
+}
+
+/*
+
+Where a token creator's eth came from: the first eth every wallet received is
+followed back, through normal and internal transfers, until it reaches a
+mixer, an exchange, a wallet flagged with /lockers or FUNDING_TRACE_DEPTH
+hops. Exchange withdrawals say little either way, they fund thousands of
+unrelated wallets.
+
+*/
+pub async fn trace_funding(wallet: String, depth: usize) -> Result<FundingPath, reqwest::Error> {
+    let wallet = wallet.to_lowercase();
+    let mut path = FundingPath {
+        wallet: wallet.clone(),
+        hops: vec![],
+        origin: FundingOrigin::DepthReached,
+    };
+    if let Some(origin) = origin(&wallet) {
+        path.origin = origin;
+        return Ok(path);
+    }
+
+    let mut current = wallet;
+    while path.hops.len() < depth {
+        let Some(hop) = get_first_funding(&current).await? else {
+            path.origin = FundingOrigin::Unfunded;
+            return Ok(path);
+        };
+        current = hop.funder.clone();
+        path.hops.push(hop);
+        if let Some(origin) = origin(&current) {
+            path.origin = origin;
+            return Ok(path);
+        }
+    }
+
+    Ok(path)
+}
+
+#[test]
+fn test_first_funding() {
+    let transfer = |time_stamp: i64, from: &str, to: &str, wei: &str, failed: bool| {
+        (
+            time_stamp,
+            from.to_owned(),
+            to.to_owned(),
+            wei.to_owned(),
+            failed,
+        )
+    };
+    let transfers = vec![
+        // the deployment itself and a failed transfer don't fund anything
+        transfer(90, "0xDeployer", "", "0", false),
+        transfer(100, "0xbad", "0xdeployer", "5000000000000000000", true),
+        transfer(200, "0xMixer", "0xDeployer", "10000000000000000000", false),
+        transfer(300, "0xfriend", "0xdeployer", "1000000000000000000", false),
+    ];
+
+    assert_eq!(
+        first_funding("0xDEPLOYER", &transfers),
+        Some(FundingHop {
+            wallet: String::from("0xdeployer"),
+            funder: String::from("0xmixer"),
+            eth: 10.0,
+            time_stamp: 200,
+        })
+    );
+    assert_eq!(first_funding("0xother", &transfers), None);
+
+    assert_eq!(
+        origin("0x910cbd523d972eb0a6f4cae4618ad62622b39dbf"),
+        Some(FundingOrigin::Mixer(String::from("Tornado Cash 10 ETH")))
+    );
+    assert_eq!(origin("0x000000000000000000000000000000000000dead"), None);
+}
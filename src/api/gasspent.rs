@@ -0,0 +1,103 @@
+use super::etherscan::EtherscanNormalTransaction;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContractGas {
+    // empty for contract creations
+    pub contract: String,
+    pub eth: f64,
+    pub transactions: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GasSpent {
+    pub eth: f64,
+    pub transactions: usize,
+    // most spent first
+    pub by_contract: Vec<ContractGas>,
+}
+
+// the fee a transaction paid, failed transactions pay for the gas they used too
+fn fee_eth(transaction: &EtherscanNormalTransaction) -> f64 {
+    let gas_used = transaction.gas_used.parse::<f64>().unwrap_or(0.0);
+    let gas_price = transaction.gas_price.parse::<f64>().unwrap_or(0.0);
+
+    gas_used * gas_price / 10.0f64.powi(18)
+}
+
+// gas the wallets paid for the transactions they sent after `since`, transactions between two of the
+// wallets show up in both histories and are counted once
+pub fn gas_spent(
+    wallets: &[String],
+    transactions: &[EtherscanNormalTransaction],
+    since: u64,
+) -> GasSpent {
+    let wallets: Vec<String> = wallets.iter().map(|w| w.to_lowercase()).collect();
+    let mut seen: Vec<String> = vec![];
+    let mut by_contract: HashMap<String, ContractGas> = HashMap::new();
+
+    for transaction in transactions {
+        let hash = transaction.hash.to_lowercase();
+        if !wallets.contains(&transaction.from.to_lowercase())
+            || transaction.time_stamp.parse::<u64>().unwrap_or(0) < since
+            || seen.contains(&hash)
+        {
+            continue;
+        }
+        seen.push(hash);
+
+        let contract = transaction.to.to_lowercase();
+        let entry = by_contract
+            .entry(contract.clone())
+            .or_insert_with(|| ContractGas {
+                contract,
+                ..ContractGas::default()
+            });
+        entry.eth += fee_eth(transaction);
+        entry.transactions += 1;
+    }
+
+    let mut by_contract: Vec<ContractGas> = by_contract.into_values().collect();
+    by_contract.sort_by(|a, b| b.eth.total_cmp(&a.eth));
+
+    GasSpent {
+        eth: by_contract.iter().map(|c| c.eth).sum(),
+        transactions: by_contract.iter().map(|c| c.transactions).sum(),
+        by_contract,
+    }
+}
+
+#[test]
+fn test_gas_spent() {
+    let transaction =
+        |hash: &str, from: &str, to: &str, time_stamp: u64| EtherscanNormalTransaction {
+            time_stamp: time_stamp.to_string(),
+            hash: hash.to_owned(),
+            from: from.to_owned(),
+            to: to.to_owned(),
+            // 100k gas at 10 gwei is 0.001 eth
+            gas_price: String::from("10000000000"),
+            gas_used: String::from("100000"),
+            ..EtherscanNormalTransaction::default()
+        };
+    let wallets = [String::from("0xa"), String::from("0xb")];
+
+    let transactions = [
+        transaction("0x1", "0xA", "0xrouter", 200),
+        transaction("0x2", "0xb", "0xrouter", 300),
+        transaction("0x3", "0xa", "0xtoken", 400),
+        // sent between the wallets, in both histories
+        transaction("0x4", "0xa", "0xb", 500),
+        transaction("0x4", "0xa", "0xb", 500),
+        // received and too old
+        transaction("0x5", "0xc", "0xa", 500),
+        transaction("0x6", "0xa", "0xrouter", 50),
+    ];
+
+    let spent = gas_spent(&wallets, &transactions, 100);
+    assert_eq!(spent.transactions, 4);
+    assert!((spent.eth - 0.004).abs() < 1e-12);
+    assert_eq!(spent.by_contract[0].contract, "0xrouter");
+    assert_eq!(spent.by_contract[0].transactions, 2);
+    assert_eq!(spent.by_contract.len(), 3);
+}
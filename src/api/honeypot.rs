@@ -1,12 +1,25 @@
-use reqwest::Client;
+use super::{
+    instrumentation,
+    providers::{self, Provider},
+    usage,
+};
+use crate::utils;
+use chrono::{DateTime, Utc};
+use core::fmt;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
-pub async fn get_token_info(contract: String) -> Result<HoneypotTokenInfo, reqwest::Error> {
+pub async fn get_token_info(
+    contract: String,
+    chain: utils::Chain,
+) -> Result<HoneypotTokenInfo, reqwest::Error> {
     let response = {
         tokio::task::spawn_blocking(move || {
             HoneypotAPI::send_request(format!(
-                "https://api.honeypot.is/v2/IsHoneypot?address={}",
-                contract
+                "{}/IsHoneypot?address={}&chainID={}",
+                Provider::Honeypot.base_url(),
+                contract,
+                chain.chain_id()
             ))
         })
         .await
@@ -21,6 +34,8 @@ pub async fn get_token_info(contract: String) -> Result<HoneypotTokenInfo, reqwe
             contract_address: HoneypotAPI::get_token_contract_address(&honeypot_api),
             decimals: HoneypotAPI::get_token_deciamls(&honeypot_api),
             pair: HoneypotAPI::get_pair_type(&honeypot_api),
+            pair_address: HoneypotAPI::get_pair_address(&honeypot_api),
+            pair_token: HoneypotAPI::get_pair_token(&honeypot_api),
             pair_symbol: HoneypotAPI::get_token_pair_symbol(&honeypot_api),
             is_honeypot: HoneypotAPI::get_is_honeypot(&honeypot_api).0,
             honeypot_reason: HoneypotAPI::get_is_honeypot(&honeypot_api).1,
@@ -30,18 +45,26 @@ pub async fn get_token_info(contract: String) -> Result<HoneypotTokenInfo, reqwe
             is_open_source: HoneypotAPI::get_contract_open_source(&honeypot_api),
             has_proxy_calls: HoneypotAPI::get_has_proxy_calls(&honeypot_api),
             flags_description: HoneypotAPI::get_flags_description(&honeypot_api),
+            total_holders: HoneypotAPI::get_total_holders(&honeypot_api),
+            pair_created_at: HoneypotAPI::get_pair_created_at(&honeypot_api),
+            chain: HoneypotAPI::get_chain(&honeypot_api).unwrap_or(chain),
+            fetched_at: Utc::now(),
+            simulated: true,
         }),
         Err(e) => Err(e.without_url()),
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HoneypotTokenInfo {
     pub symbol: String,
     pub name: String,
     pub contract_address: String,
     pub decimals: u8,
     pub pair: String,
+    pub pair_address: String,
+    // the token on the other side of the pair, usually WETH
+    pub pair_token: String,
     pub pair_symbol: String,
     pub is_honeypot: bool,
     pub honeypot_reason: Option<String>,
@@ -51,11 +74,33 @@ pub struct HoneypotTokenInfo {
     pub is_open_source: Option<bool>,
     pub has_proxy_calls: Option<bool>,
     pub flags_description: Option<Vec<String>>,
+    pub total_holders: u32,
+    pub pair_created_at: Option<i64>,
+    pub chain: utils::Chain,
+    pub fetched_at: DateTime<Utc>,
+    // false when honeypot.is doesn't know the token yet and it was read on-chain instead,
+    // the taxes and the honeypot check are unknown then
+    pub simulated: bool,
 }
 
 impl HoneypotAPI {
     async fn send_request(url: String) -> Result<HoneypotAPI, reqwest::Error> {
-        let response: HoneypotAPI = Client::new().get(url).send().await?.json().await?;
+        usage::record(Provider::Honeypot).await;
+        // brand new tokens can hang for a long time, so give up early and let the caller retry
+        let endpoint =
+            instrumentation::endpoint(url.trim_start_matches(&Provider::Honeypot.base_url()));
+        let response: HoneypotAPI = providers::send(
+            Provider::Honeypot,
+            &endpoint,
+            providers::client()
+                .get(url)
+                .timeout(Duration::from_secs(10))
+                .headers(Provider::Honeypot.headers()),
+        )
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
 
         Ok(response)
     }
@@ -108,10 +153,36 @@ impl HoneypotAPI {
         api.pair.liquidity
     }
 
+    fn get_total_holders(api: &HoneypotAPI) -> u32 {
+        api.token.total_holders
+    }
+
+    // unix timestamp, honeypot.is sends it as a string
+    fn get_pair_created_at(api: &HoneypotAPI) -> Option<i64> {
+        api.pair.created_at_timestamp.parse::<i64>().ok()
+    }
+
+    // the chain the simulation actually ran on, honeypot.is sends the id as a string
+    fn get_chain(api: &HoneypotAPI) -> Option<utils::Chain> {
+        api.chain
+            .id
+            .parse::<u64>()
+            .ok()
+            .and_then(utils::Chain::from_chain_id)
+    }
+
     fn get_pair_type(api: &HoneypotAPI) -> String {
         api.pair.pair.pair_type.to_owned()
     }
 
+    fn get_pair_address(api: &HoneypotAPI) -> String {
+        api.pair.pair.address.to_owned()
+    }
+
+    fn get_pair_token(api: &HoneypotAPI) -> String {
+        api.with_token.address.to_lowercase()
+    }
+
     fn get_contract_open_source(api: &HoneypotAPI) -> Option<bool> {
         match api.contract_code.as_ref() {
             Some(contract_code) => Some(contract_code.open_source),
@@ -0,0 +1,217 @@
+use super::{
+    alchemy::AlchemyAPI,
+    etherscan::{EtherscanAPI, EtherscanSourceCode},
+    get_contract_creator_and_tx_hash, get_proxy_implementation, known_label,
+};
+use crate::utils::address_from_word;
+use core::fmt;
+
+// EIP-1167 minimal proxies embed the implementation right after this prefix
+const MINIMAL_PROXY_PREFIX: &str = "363d3d373d3d3d363d73";
+// EIP-7702 delegations turn a wallet's code into 0xef0100 followed by the delegate
+const DELEGATION_PREFIX: &str = "ef0100";
+// forwarders are tiny, anything this small with a delegatecall only proxies calls
+const MAX_FORWARDER_CODE_BYTES: usize = 300;
+const DELEGATECALL: &str = "f4";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContractKind {
+    Erc20,
+    Erc721,
+    UniswapV2Pair,
+    UniswapV3Pool,
+    Router,
+    Safe,
+    Unknown,
+}
+
+impl fmt::Display for ContractKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContractKind::Erc20 => write!(f, "ERC-20 token"),
+            ContractKind::Erc721 => write!(f, "ERC-721 NFT collection"),
+            ContractKind::UniswapV2Pair => write!(f, "Uniswap V2 style pair"),
+            ContractKind::UniswapV3Pool => write!(f, "Uniswap V3 style pool"),
+            ContractKind::Router => write!(f, "DEX router"),
+            ContractKind::Safe => write!(f, "Safe multisig"),
+            ContractKind::Unknown => write!(f, "unknown contract"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AddressInspection {
+    pub is_contract: bool,
+    // the contract an EIP-7702 wallet delegates its code to
+    pub delegated_to: Option<String>,
+    pub kind: Option<ContractKind>,
+    pub proxy_implementation: Option<String>,
+    pub is_proxy: bool,
+    // verified source name on etherscan, of the implementation for proxies
+    pub verified_name: Option<String>,
+    pub label: Option<String>,
+    pub creator: Option<String>,
+    pub creation_tx: Option<String>,
+    pub created_at: Option<i64>,
+}
+
+// whether the runtime code pushes the function selector, how solidity dispatches calls
+fn has_selector(code: &str, selector: &str) -> bool {
+    code.contains(&format!("63{}", selector))
+}
+
+fn has_all(code: &str, selectors: &[&str]) -> bool {
+    selectors
+        .iter()
+        .all(|selector| has_selector(code, selector))
+}
+
+// lowercase runtime code without 0x, pairs are ERC-20s too so they're checked first
+pub fn classify(code: &str) -> ContractKind {
+    // getReserves, token0, token1
+    if has_all(code, &["0902f1ac", "0dfe1681", "d21220a7"]) {
+        ContractKind::UniswapV2Pair
+    // slot0, token0, token1
+    } else if has_all(code, &["3850c7bd", "0dfe1681", "d21220a7"]) {
+        ContractKind::UniswapV3Pool
+    // execTransaction, getOwners
+    } else if has_all(code, &["6a761202", "a0e67e2b"]) {
+        ContractKind::Safe
+    // WETH and factory, or the swap entry points of the V2, V3 and universal routers
+    } else if has_all(code, &["ad5c4648", "c45a0155"])
+        || ["38ed1739", "414bf389", "3593564c"]
+            .iter()
+            .any(|selector| has_selector(code, selector))
+    {
+        ContractKind::Router
+    // ownerOf, safeTransferFrom
+    } else if has_all(code, &["6352211e", "42842e0e"]) {
+        ContractKind::Erc721
+    // transfer, balanceOf, totalSupply, allowance
+    } else if has_all(code, &["a9059cbb", "70a08231", "18160ddd", "dd62ed3e"]) {
+        ContractKind::Erc20
+    } else {
+        ContractKind::Unknown
+    }
+}
+
+// the implementation of an EIP-1167 clone, true if the code looks like any other forwarder
+fn forwarder(code: &str) -> (Option<String>, bool) {
+    if let Some(rest) = code.strip_prefix(MINIMAL_PROXY_PREFIX) {
+        return (rest.get(..40).map(|address| format!("0x{}", address)), true);
+    }
+
+    let is_forwarder = code.len() / 2 <= MAX_FORWARDER_CODE_BYTES
+        && code
+            .as_bytes()
+            .chunks(2)
+            .any(|byte| byte == DELEGATECALL.as_bytes());
+    (None, is_forwarder)
+}
+
+async fn get_code(address: &str) -> Result<String, reqwest::Error> {
+    match AlchemyAPI::<String>::get_code(address.to_owned()).await {
+        Ok(response) => Ok(response.result.trim_start_matches("0x").to_lowercase()),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+async fn verified_name(address: &str) -> Option<String> {
+    match EtherscanAPI::<Vec<EtherscanSourceCode>>::get_source_code(address.to_owned()).await {
+        Ok(response) => response
+            .result
+            .first()
+            .filter(|source| !source.source_code.is_empty())
+            .map(|source| source.contract_name.clone()),
+        Err(e) => {
+            error!("get_source_code error: {}", e.without_url());
+            None
+        }
+    }
+}
+
+pub async fn inspect_address(address: String) -> Result<AddressInspection, reqwest::Error> {
+    let address = address.to_lowercase();
+    let code = get_code(&address).await?;
+    let mut inspection = AddressInspection {
+        label: known_label(&address).map(|(label, _)| label),
+        ..AddressInspection::default()
+    };
+
+    if let Some(delegate) = code.strip_prefix(DELEGATION_PREFIX) {
+        inspection.delegated_to = address_from_word(&format!("{:0>64}", delegate));
+        return Ok(inspection);
+    }
+    if code.is_empty() {
+        return Ok(inspection);
+    }
+    inspection.is_contract = true;
+
+    let (clone_of, is_forwarder) = forwarder(&code);
+    let implementation = match clone_of {
+        Some(implementation) => Some(implementation),
+        None => get_proxy_implementation(address.clone()).await?,
+    };
+    inspection.is_proxy = is_forwarder || implementation.is_some();
+
+    // a proxy's own code only forwards calls, what it does is in the implementation
+    let logic_code = match &implementation {
+        Some(implementation) => get_code(implementation).await?,
+        None => code,
+    };
+    inspection.kind = Some(classify(&logic_code));
+
+    let (verified, creation) = tokio::join!(
+        verified_name(implementation.as_deref().unwrap_or(&address)),
+        get_contract_creator_and_tx_hash(vec![address.clone()])
+    );
+    inspection.verified_name = verified;
+    inspection.proxy_implementation = implementation;
+
+    match creation {
+        Ok(creations) => {
+            if let Some(creation) = creations.first() {
+                inspection.creator = Some(creation.contract_creator.to_lowercase());
+                inspection.creation_tx = Some(creation.tx_hash.clone());
+                inspection.created_at = creation.timestamp.parse::<i64>().ok();
+            }
+        }
+        Err(e) => error!("get_contract_creator_and_tx_hash error: {}", e),
+    }
+
+    Ok(inspection)
+}
+
+#[test]
+fn test_classify() {
+    let code = |selectors: &[&str]| {
+        selectors
+            .iter()
+            .map(|selector| format!("8063{}1461", selector))
+            .collect::<String>()
+    };
+    let erc20 = ["a9059cbb", "70a08231", "18160ddd", "dd62ed3e"];
+
+    assert_eq!(classify(&code(&erc20)), ContractKind::Erc20);
+    assert_eq!(
+        classify(&code(
+            &[&erc20[..], &["0902f1ac", "0dfe1681", "d21220a7"]].concat()
+        )),
+        ContractKind::UniswapV2Pair
+    );
+    assert_eq!(
+        classify(&code(&["ad5c4648", "c45a0155"])),
+        ContractKind::Router
+    );
+    assert_eq!(classify(&code(&erc20[..2])), ContractKind::Unknown);
+
+    let implementation = "bebebebebebebebebebebebebebebebebebebebe";
+    assert_eq!(
+        forwarder(&format!(
+            "{}{}5af43d82803e903d91602b57fd5bf3",
+            MINIMAL_PROXY_PREFIX, implementation
+        )),
+        (Some(format!("0x{}", implementation)), true)
+    );
+    assert_eq!(forwarder(&code(&erc20)), (None, false));
+}
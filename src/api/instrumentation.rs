@@ -0,0 +1,254 @@
+use super::providers::Provider;
+use lazy_static::lazy_static;
+use std::{collections::BTreeMap, env, fmt::Write};
+use tokio::sync::Mutex;
+
+// upper bounds of the latency buckets in milliseconds, one more bucket takes everything slower
+const LATENCY_BUCKETS_MS: [u64; 8] = [50, 100, 250, 500, 1000, 2500, 5000, 10000];
+const DEFAULT_SLOW_REQUEST_MS: u64 = 3000;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EndpointStats {
+    buckets: [u64; LATENCY_BUCKETS_MS.len() + 1],
+    pub requests: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+    // status code, or the error kind of the requests that got no response
+    pub statuses: BTreeMap<String, u64>,
+    pub response_bytes: u64,
+}
+
+impl EndpointStats {
+    fn observe(&mut self, latency_ms: u64, status: &str, bytes: u64) {
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| latency_ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+
+        self.buckets[bucket] += 1;
+        self.requests += 1;
+        self.total_ms += latency_ms;
+        self.max_ms = self.max_ms.max(latency_ms);
+        *self.statuses.entry(status.to_owned()).or_default() += 1;
+        self.response_bytes += bytes;
+    }
+
+    // requests without a response and 4xx/5xx answers
+    pub fn errors(&self) -> u64 {
+        self.statuses
+            .iter()
+            .filter(|(status, _)| !status.starts_with('2'))
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    // the bound of the bucket the percentile falls in, never more than the slowest request
+    pub fn percentile_ms(&self, percentile: f64) -> u64 {
+        let target = ((self.requests as f64) * percentile / 100.0)
+            .ceil()
+            .max(1.0) as u64;
+        let mut seen = 0;
+
+        for (i, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return LATENCY_BUCKETS_MS
+                    .get(i)
+                    .map_or(self.max_ms, |bound| (*bound).min(self.max_ms));
+            }
+        }
+        self.max_ms
+    }
+}
+
+lazy_static! {
+    // (provider, endpoint) -> stats since the bot started
+    static ref STATS: Mutex<BTreeMap<(String, String), EndpointStats>> =
+        Mutex::new(BTreeMap::<(String, String), EndpointStats>::new());
+}
+
+/*
+
+Every provider request is recorded by endpoint with its latency, status and
+response size. The latencies go into fixed buckets, which is all /status needs
+for its percentiles and what the Prometheus histogram at the REST API's
+/metrics is made of. Requests slower than PROVIDER_SLOW_MS are logged as
+warnings with their provider, endpoint, status and size.
+
+*/
+pub fn slow_request_ms() -> u64 {
+    env::var("PROVIDER_SLOW_MS")
+        .ok()
+        .and_then(|ms| ms.parse::<u64>().ok())
+        .filter(|ms| *ms > 0)
+        .unwrap_or(DEFAULT_SLOW_REQUEST_MS)
+}
+
+// the path of a request with its addresses and ids replaced, so every token isn't its own endpoint
+pub fn endpoint(path: &str) -> String {
+    let path = path.split('?').next().unwrap_or_default();
+
+    path.trim_matches('/')
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with("0x") {
+                ":address"
+            } else if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+                ":id"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<&str>>()
+        .join("/")
+}
+
+// etherscan has a single path, its endpoints are the module and action parameters
+pub fn etherscan_endpoint(query: &str) -> String {
+    let param = |name: &str| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+            .unwrap_or("-")
+    };
+
+    format!("{}/{}", param("module"), param("action"))
+}
+
+pub async fn record(provider: Provider, endpoint: &str, latency_ms: u64, status: &str, bytes: u64) {
+    STATS
+        .lock()
+        .await
+        .entry((provider.to_string(), endpoint.to_owned()))
+        .or_default()
+        .observe(latency_ms, status, bytes);
+}
+
+pub async fn stats() -> Vec<(String, String, EndpointStats)> {
+    STATS
+        .lock()
+        .await
+        .iter()
+        .map(|((provider, endpoint), stats)| (provider.clone(), endpoint.clone(), stats.clone()))
+        .collect()
+}
+
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// the text exposition format Prometheus scrapes
+pub fn prometheus_text(stats: &[(String, String, EndpointStats)]) -> String {
+    let mut text = String::from(
+        "# HELP snipers_provider_request_duration_seconds Provider request latency by endpoint\n\
+        # TYPE snipers_provider_request_duration_seconds histogram\n",
+    );
+    for (provider, endpoint, stats) in stats {
+        let labels = format!(
+            "provider=\"{}\",endpoint=\"{}\"",
+            label(provider),
+            label(endpoint)
+        );
+        let mut cumulative = 0;
+
+        for (i, count) in stats.buckets.iter().enumerate() {
+            cumulative += count;
+            let le = LATENCY_BUCKETS_MS
+                .get(i)
+                .map_or(String::from("+Inf"), |bound| {
+                    (*bound as f64 / 1000.0).to_string()
+                });
+            let _ = writeln!(
+                text,
+                "snipers_provider_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, le, cumulative
+            );
+        }
+        let _ = writeln!(
+            text,
+            "snipers_provider_request_duration_seconds_sum{{{}}} {}\n\
+            snipers_provider_request_duration_seconds_count{{{}}} {}",
+            labels,
+            stats.total_ms as f64 / 1000.0,
+            labels,
+            stats.requests
+        );
+    }
+
+    text.push_str(
+        "# HELP snipers_provider_responses_total Provider requests by status code or error kind\n\
+        # TYPE snipers_provider_responses_total counter\n",
+    );
+    for (provider, endpoint, stats) in stats {
+        for (status, count) in &stats.statuses {
+            let _ = writeln!(
+                text,
+                "snipers_provider_responses_total{{provider=\"{}\",endpoint=\"{}\",status=\"{}\"}} {}",
+                label(provider),
+                label(endpoint),
+                label(status),
+                count
+            );
+        }
+    }
+
+    text.push_str(
+        "# HELP snipers_provider_response_bytes_total Size of the provider responses\n\
+        # TYPE snipers_provider_response_bytes_total counter\n",
+    );
+    for (provider, endpoint, stats) in stats {
+        let _ = writeln!(
+            text,
+            "snipers_provider_response_bytes_total{{provider=\"{}\",endpoint=\"{}\"}} {}",
+            label(provider),
+            label(endpoint),
+            stats.response_bytes
+        );
+    }
+
+    text
+}
+
+#[test]
+fn test_endpoint_stats() {
+    assert_eq!(
+        endpoint("erc20/0x6982508145454ce325ddbe47a25d4ec3d2311933/price?chain=eth"),
+        "erc20/:address/price"
+    );
+    assert_eq!(
+        endpoint("/safes/0xabc/multisig-transactions/"),
+        "safes/:address/multisig-transactions"
+    );
+    assert_eq!(
+        etherscan_endpoint("module=account&action=txlist&address=0xabc&apikey=key"),
+        "account/txlist"
+    );
+
+    let mut stats = EndpointStats::default();
+    for latency_ms in [30, 40, 80, 90, 120, 700, 12000] {
+        stats.observe(latency_ms, "200", 100);
+    }
+    stats.observe(10000, "timeout", 0);
+    assert_eq!(stats.requests, 8);
+    assert_eq!(stats.errors(), 1);
+    assert_eq!(stats.percentile_ms(25.0), 50);
+    assert_eq!(stats.percentile_ms(50.0), 100);
+    assert_eq!(stats.percentile_ms(100.0), 12000);
+
+    let text = prometheus_text(&[(
+        String::from("Etherscan"),
+        String::from("account/txlist"),
+        stats,
+    )]);
+    assert!(text.contains(
+        "snipers_provider_request_duration_seconds_bucket{provider=\"Etherscan\",endpoint=\"account/txlist\",le=\"0.1\"} 4"
+    ));
+    assert!(text.contains(
+        "snipers_provider_request_duration_seconds_bucket{provider=\"Etherscan\",endpoint=\"account/txlist\",le=\"+Inf\"} 8"
+    ));
+    assert!(text.contains("status=\"timeout\"} 1"));
+    assert!(text.contains("snipers_provider_response_bytes_total{provider=\"Etherscan\",endpoint=\"account/txlist\"} 700"));
+}
@@ -0,0 +1,276 @@
+use crate::persist;
+use core::fmt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr, sync::RwLock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelKind {
+    Exchange,
+    Locker,
+    Burn,
+    Mixer,
+    // wallets of known ruggers, flagged by the owner
+    Flagged,
+}
+
+impl fmt::Display for LabelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LabelKind::Exchange => write!(f, "exchange"),
+            LabelKind::Locker => write!(f, "locker"),
+            LabelKind::Burn => write!(f, "burn"),
+            LabelKind::Mixer => write!(f, "mixer"),
+            LabelKind::Flagged => write!(f, "flagged"),
+        }
+    }
+}
+
+impl FromStr for LabelKind {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exchange" => Ok(LabelKind::Exchange),
+            "locker" => Ok(LabelKind::Locker),
+            "burn" => Ok(LabelKind::Burn),
+            "mixer" => Ok(LabelKind::Mixer),
+            "flagged" => Ok(LabelKind::Flagged),
+            _ => Err(()),
+        }
+    }
+}
+
+// an address added at runtime, saved to KNOWN_ADDRESSES_FILE
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnownAddress {
+    pub address: String,
+    pub name: String,
+    pub kind: LabelKind,
+}
+
+lazy_static! {
+    // lowercase address -> name, kind. built-in addresses first, then the ones from the file
+    static ref REGISTRY: RwLock<HashMap<String, (String, LabelKind)>> =
+        RwLock::new(registry(
+            &persist::load(persist::KNOWN_ADDRESSES).unwrap_or_default()
+        ));
+}
+
+// lowercase address, name, kind
+const BUILT_IN_ADDRESSES: [(&str, &str, LabelKind); 22] = [
+    (
+        "0x28c6c06298d514db089934071355e5743bf21d60",
+        "Binance 14",
+        LabelKind::Exchange,
+    ),
+    (
+        "0x21a31ee1afc51d94c2efccaa2092ad1028285549",
+        "Binance 15",
+        LabelKind::Exchange,
+    ),
+    (
+        "0xdfd5293d8e347dfe59e90efd55b2956a1343963d",
+        "Binance 16",
+        LabelKind::Exchange,
+    ),
+    (
+        "0xf977814e90da44bfa03b6295a0616a897441acec",
+        "Binance 8",
+        LabelKind::Exchange,
+    ),
+    (
+        "0x71660c4005ba85c37ccec55d0c4493e66fe775d3",
+        "Coinbase 1",
+        LabelKind::Exchange,
+    ),
+    (
+        "0x503828976d22510aad0201ac7ec88293211d23da",
+        "Coinbase 2",
+        LabelKind::Exchange,
+    ),
+    (
+        "0xa9d1e08c7793af67e9d92fe308d5697fb81d3e43",
+        "Coinbase 10",
+        LabelKind::Exchange,
+    ),
+    (
+        "0x267be1c1d684f78cb4f6a176c4911b741e4ffdc0",
+        "Kraken 4",
+        LabelKind::Exchange,
+    ),
+    (
+        "0x6cc5f688a315f3dc28a7781717a9a798a59fda7b",
+        "OKX 7",
+        LabelKind::Exchange,
+    ),
+    (
+        "0xf89d7b9c864f589bbf53a82105107622b35eaa40",
+        "Bybit 1",
+        LabelKind::Exchange,
+    ),
+    (
+        "0xd6216fc19db775df9774a6e33526131da7d19a2c",
+        "KuCoin 6",
+        LabelKind::Exchange,
+    ),
+    (
+        "0x0d0707963952f2fba59dd06f2b425ace40b492fe",
+        "Gate.io 1",
+        LabelKind::Exchange,
+    ),
+    // TrustSwap: Team Finance Lock
+    (
+        "0xe2fe530c047f2d85298b07d9333c05737f1435fb",
+        "Team Finance Lock",
+        LabelKind::Locker,
+    ),
+    // UNCX Network Security : Token Vesting
+    (
+        "0xdba68f07d1b7ca219f78ae8582c213d975c25caf",
+        "UNCX Token Vesting",
+        LabelKind::Locker,
+    ),
+    (
+        "0x663a5c229c09b049e36dcc11a9b0d4a8eb9db214",
+        "UNCX Uniswap V2 Locker",
+        LabelKind::Locker,
+    ),
+    (
+        "0xd90e2f925da726b50c4ed8d0fb90ad053324f31b",
+        "Tornado Cash Router",
+        LabelKind::Mixer,
+    ),
+    (
+        "0x12d66f87a04a9e220743712ce6d9bb1b5616b8fc",
+        "Tornado Cash 0.1 ETH",
+        LabelKind::Mixer,
+    ),
+    (
+        "0x47ce0c6ed5b0ce3d3a51fdb1c52dc66a7c3c2936",
+        "Tornado Cash 1 ETH",
+        LabelKind::Mixer,
+    ),
+    (
+        "0x910cbd523d972eb0a6f4cae4618ad62622b39dbf",
+        "Tornado Cash 10 ETH",
+        LabelKind::Mixer,
+    ),
+    (
+        "0xa160cdab225685da1d56aa342ad8841c3b53f291",
+        "Tornado Cash 100 ETH",
+        LabelKind::Mixer,
+    ),
+    (
+        "0x000000000000000000000000000000000000dead",
+        "Burn address",
+        LabelKind::Burn,
+    ),
+    (
+        "0x0000000000000000000000000000000000000000",
+        "Null address",
+        LabelKind::Burn,
+    ),
+];
+
+// added addresses override built-in ones with the same address
+fn registry(added: &[KnownAddress]) -> HashMap<String, (String, LabelKind)> {
+    BUILT_IN_ADDRESSES
+        .iter()
+        .map(|(address, name, kind)| (address.to_string(), (name.to_string(), *kind)))
+        .chain(added.iter().map(|known| {
+            (
+                known.address.to_lowercase(),
+                (known.name.clone(), known.kind),
+            )
+        }))
+        .collect()
+}
+
+pub fn known_label(address: &str) -> Option<(String, LabelKind)> {
+    REGISTRY
+        .read()
+        .unwrap()
+        .get(&address.to_lowercase())
+        .cloned()
+}
+
+pub fn addresses_of(kind: LabelKind) -> Vec<String> {
+    let mut addresses: Vec<String> = REGISTRY
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|(_, (_, known_kind))| *known_kind == kind)
+        .map(|(address, _)| address.clone())
+        .collect();
+
+    addresses.sort();
+    addresses
+}
+
+// name and address, sorted by name
+pub fn labels_of(kind: LabelKind) -> Vec<(String, String)> {
+    let mut labels: Vec<(String, String)> = REGISTRY
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|(_, (_, known_kind))| *known_kind == kind)
+        .map(|(address, (name, _))| (name.clone(), address.clone()))
+        .collect();
+
+    labels.sort();
+    labels
+}
+
+// only addresses added at runtime are saved, the built-in ones always come from the code
+pub fn add_known_address(known: KnownAddress) {
+    let mut added = persist::load(persist::KNOWN_ADDRESSES).unwrap_or_default();
+    added.retain(|a| a.address.to_lowercase() != known.address.to_lowercase());
+    added.push(known);
+    persist::save(persist::KNOWN_ADDRESSES, &added);
+
+    *REGISTRY.write().unwrap() = registry(&added);
+}
+
+// built-in addresses can't be removed, false if the address wasn't added at runtime
+pub fn remove_known_address(address: &str) -> bool {
+    let mut added = persist::load(persist::KNOWN_ADDRESSES).unwrap_or_default();
+    let count = added.len();
+    added.retain(|a| a.address.to_lowercase() != address.to_lowercase());
+    if added.len() == count {
+        return false;
+    }
+
+    persist::save(persist::KNOWN_ADDRESSES, &added);
+    *REGISTRY.write().unwrap() = registry(&added);
+    true
+}
+
+#[test]
+fn test_known_address_registry() {
+    let added = vec![
+        KnownAddress {
+            address: String::from("0x71B5759d73262FBb223956913ecF4ecC51057641"),
+            name: String::from("PinkLock"),
+            kind: LabelKind::Locker,
+        },
+        KnownAddress {
+            address: String::from("0x000000000000000000000000000000000000dead"),
+            name: String::from("Dead"),
+            kind: LabelKind::Burn,
+        },
+    ];
+    let registry = registry(&added);
+
+    assert_eq!(
+        registry.get("0x71b5759d73262fbb223956913ecf4ecc51057641"),
+        Some(&(String::from("PinkLock"), LabelKind::Locker))
+    );
+    // added entries replace the built-in ones
+    assert_eq!(
+        registry.get("0x000000000000000000000000000000000000dead"),
+        Some(&(String::from("Dead"), LabelKind::Burn))
+    );
+    assert_eq!(registry.len(), BUILT_IN_ADDRESSES.len() + 1);
+    assert_eq!(LabelKind::from_str("locker"), Ok(LabelKind::Locker));
+}
@@ -0,0 +1,226 @@
+use super::{
+    etherscan::{EtherscanAPI, EtherscanInternalTransaction},
+    get_eth_price, honeypot,
+};
+use crate::{
+    hooks::{self, DiscoveredToken},
+    tasks,
+    telegram::{
+        alerts::{self, AlertCategory},
+        bot::{self, SETTINGS},
+    },
+    utils::{hyperlinks_from_contract, is_valid_eth_address, Chain},
+};
+use chrono::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+};
+use teloxide::{payloads::SendMessageSetters, types::ParseMode, utils::html, Bot};
+use tokio::time::sleep;
+
+// launches with higher taxes are skipped, the same limit as the Uniswap V2 checks
+const MAX_LAUNCH_TAX: f32 = 5.0;
+
+/*
+
+Launchpads deploy every token through their own factory contract, often on
+another chain than the Uniswap V2 factory the sniper watches. The factories
+are configured with LAUNCHPAD_FEEDS, e.g. `clanker:base:0x...`, and checked
+every minute through Etherscan's multichain api. Their tokens are alerted with
+the feed's name and get their own /sniperfilter criteria.
+
+The renounce and liquidity lock checks of the Uniswap V2 pipeline don't apply,
+launchpads hold the liquidity themselves. Launches are only checked with
+honeypot.is on their chain.
+
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchpadFeed {
+    pub name: String,
+    pub chain: Chain,
+    pub factory: String,
+}
+
+fn parse_chain(chain: &str) -> Option<Chain> {
+    match chain.to_lowercase().as_str() {
+        "ethereum" | "eth" => Some(Chain::Ethereum),
+        "bsc" | "bnb" => Some(Chain::Bsc),
+        "base" => Some(Chain::Base),
+        _ => None,
+    }
+}
+
+// 'name:chain:factory' entries separated by commas, invalid ones are logged and skipped
+fn parse_feeds(feeds: &str) -> Vec<LaunchpadFeed> {
+    feeds
+        .split(',')
+        .map(str::trim)
+        .filter(|feed| !feed.is_empty())
+        .filter_map(|feed| {
+            let parsed = match feed.split(':').collect::<Vec<&str>>().as_slice() {
+                [name, chain, factory] if !name.is_empty() && is_valid_eth_address(factory) => {
+                    parse_chain(chain).map(|chain| LaunchpadFeed {
+                        name: name.to_lowercase(),
+                        chain,
+                        factory: factory.to_lowercase(),
+                    })
+                }
+                _ => None,
+            };
+
+            if parsed.is_none() {
+                warn!("invalid LAUNCHPAD_FEEDS entry: {}", feed);
+            }
+            parsed
+        })
+        .collect()
+}
+
+pub fn feeds() -> Vec<LaunchpadFeed> {
+    parse_feeds(&env::var("LAUNCHPAD_FEEDS").unwrap_or_default())
+}
+
+// contracts the factory created after the timestamp with their timestamps, newest first
+fn new_deployments(
+    transactions: &[EtherscanInternalTransaction],
+    since: u64,
+) -> Vec<(String, u64)> {
+    transactions
+        .iter()
+        .map(|t| (t, t.time_stamp.parse::<u64>().unwrap_or(0)))
+        .take_while(|(_, time_stamp)| *time_stamp > since)
+        .filter(|(t, _)| t.transaction_type.starts_with("create") && t.is_error != "1")
+        .filter(|(t, _)| !t.contract_address.is_empty())
+        .map(|(t, time_stamp)| (t.contract_address.to_lowercase(), time_stamp))
+        .collect()
+}
+
+async fn alert_launch(bot: &Bot, feed: &LaunchpadFeed, info: &honeypot::HoneypotTokenInfo) {
+    let eth_price = get_eth_price().await.unwrap_or(0.0);
+    let liquidity_usd = info.liquidity as f64;
+    let liquidity_eth = if eth_price > 0.0 {
+        liquidity_usd / eth_price
+    } else {
+        0.0
+    };
+
+    for (chat_id, settings) in SETTINGS.snapshot().await {
+        if !settings.snipe_new_tokens
+            || !bot::has_premium(chat_id).await
+            || !settings
+                .launchpad_filter
+                .allows_liquidity(liquidity_eth, liquidity_usd)
+        {
+            continue;
+        }
+
+        let message = format!(
+            "🚀🚀🚀 New launchpad token 🚀🚀🚀\n\n🏷 Source: {} on {}\n💎 {} ({})\n📄 {}\n⚖️ Tax: {}% buy, {}% sell\n💵 Liquidity: ${:.0}\n{}",
+            html::escape(&feed.name),
+            feed.chain,
+            html::escape(&info.name),
+            html::escape(&info.symbol),
+            info.contract_address,
+            info.buy_tax,
+            info.sell_tax,
+            liquidity_usd,
+            hyperlinks_from_contract(
+                &info.contract_address,
+                feed.chain,
+                settings.chart_site
+            )
+        );
+
+        // trades only go through Ethereum mainnet, so there are no buy buttons
+        let _ = alerts::send_alert(bot, chat_id, AlertCategory::Sniper, None, message)
+            .await
+            .parse_mode(ParseMode::Html)
+            .disable_web_page_preview(true)
+            .await;
+    }
+}
+
+pub async fn launchpad_alerts(bot: Bot, feeds: Vec<LaunchpadFeed>) {
+    // the first cycle only remembers the newest deployment, so the factory's backlog isn't alerted
+    let mut last_timestamps: HashMap<String, u64> = HashMap::new();
+    let mut seen: HashSet<String> = HashSet::new();
+
+    loop {
+        sleep(Duration::try_minutes(1).unwrap().to_std().unwrap()).await;
+        tasks::heartbeat("launchpad_alerts").await;
+
+        for feed in &feeds {
+            let transactions = match EtherscanAPI::<Vec<EtherscanInternalTransaction>>::get_internal_transactions_on(
+                feed.chain.chain_id(),
+                feed.factory.clone(),
+                20,
+            )
+            .await
+            {
+                Ok(response) => response.result,
+                Err(e) => {
+                    error!("get_internal_transactions_on error: {}", e.without_url());
+                    continue;
+                }
+            };
+
+            let newest = transactions
+                .first()
+                .and_then(|t| t.time_stamp.parse::<u64>().ok())
+                .unwrap_or(0);
+            let last_timestamp = last_timestamps.get(&feed.name).copied();
+            last_timestamps.insert(feed.name.clone(), newest.max(last_timestamp.unwrap_or(0)));
+            let Some(last_timestamp) = last_timestamp else {
+                continue;
+            };
+
+            // a launch deploys the token next to pools and lockers, honeypot.is only knows the token
+            for (contract, time_stamp) in new_deployments(&transactions, last_timestamp) {
+                let info = match honeypot::get_token_info(contract.clone(), feed.chain).await {
+                    Ok(info) => info,
+                    Err(_) => continue,
+                };
+                if !seen.insert(info.contract_address.to_lowercase()) {
+                    continue;
+                }
+                if info.is_honeypot
+                    || info.buy_tax > MAX_LAUNCH_TAX
+                    || info.sell_tax > MAX_LAUNCH_TAX
+                {
+                    continue;
+                }
+
+                let allowed_by_hooks = hooks::token_discovered(&DiscoveredToken {
+                    source: feed.name.clone(),
+                    pair: info.pair_address.clone(),
+                    contract: info.contract_address.clone(),
+                    // deployments are sent by the factory, the wallet behind a launch isn't known
+                    creator: String::new(),
+                    creation_timestamp: time_stamp as i64,
+                });
+                if allowed_by_hooks {
+                    alert_launch(&bot, feed, &info).await;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_feeds() {
+    let factory = "0x00000000000000000000000000000000000000Fa";
+    let feeds = parse_feeds(&format!(
+        "clanker:base:{}, broken:solana:{}, nofactory:base:0x1,",
+        factory, factory
+    ));
+
+    assert_eq!(
+        feeds,
+        vec![LaunchpadFeed {
+            name: String::from("clanker"),
+            chain: Chain::Base,
+            factory: factory.to_lowercase(),
+        }]
+    );
+}
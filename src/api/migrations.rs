@@ -0,0 +1,302 @@
+use super::{
+    cache::{CacheStats, TtlCache},
+    eth_call, get_token_balance, WETH_ADDRESS,
+};
+use crate::{
+    crypto::abi::{self, AbiToken},
+    utils::address_from_word,
+};
+use chrono::Duration;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+const UNISWAP_V2_FACTORY: &str = "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f";
+const UNISWAP_V3_FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+// in hundredths of a basis point
+const V3_FEE_TIERS: [u128; 3] = [500, 3000, 10000];
+// pools with less WETH in them aren't worth alerting about
+const MIN_POOL_ETH: f64 = 0.5;
+// a pool losing this share of its WETH in one check had its liquidity removed
+const REMOVED_FRACTION: f64 = 0.5;
+// the other pool has to receive at least this share of what was removed
+const MIN_MIGRATED_FRACTION: f64 = 0.5;
+const MIGRATION_WINDOW_SECONDS: i64 = 30 * 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiquidityMove {
+    // none of the token's other pools received it (yet)
+    Removed {
+        pool: String,
+        eth: f64,
+    },
+    // into a pool that was empty before, follow_up when the removal was alerted before it was funded
+    Migrated {
+        from: String,
+        to: String,
+        eth: f64,
+        follow_up: bool,
+    },
+}
+
+lazy_static! {
+    // lowercase contract -> its WETH pools, a migration's new pool shows up once this expires
+    static ref TOKEN_POOLS: TtlCache<Vec<String>> =
+        TtlCache::new(Duration::try_minutes(10).unwrap());
+}
+
+pub async fn pool_cache_stats() -> CacheStats {
+    TOKEN_POOLS.stats().await
+}
+
+async fn pool_call(
+    factory: &str,
+    signature: &str,
+    args: &[AbiToken],
+) -> Result<Option<String>, reqwest::Error> {
+    let pool = eth_call(factory.to_owned(), abi::function_call(signature, args)).await?;
+    Ok(address_from_word(&pool))
+}
+
+// the pool honeypot.is found, plus the token's Uniswap V2 and V3 pools with WETH
+pub async fn token_pools(contract: &str, pair_address: Option<&str>) -> Vec<String> {
+    let contract = contract.to_lowercase();
+    let mut pools = match TOKEN_POOLS.get(&contract).await {
+        Some(pools) => pools,
+        None => {
+            let token = AbiToken::Address(contract.clone());
+            let weth = AbiToken::Address(WETH_ADDRESS.to_owned());
+            let mut lookups = vec![
+                pool_call(
+                    UNISWAP_V2_FACTORY,
+                    "getPair(address,address)",
+                    &[token.clone(), weth.clone()],
+                )
+                .await,
+            ];
+            for fee in V3_FEE_TIERS {
+                lookups.push(
+                    pool_call(
+                        UNISWAP_V3_FACTORY,
+                        "getPool(address,address,uint24)",
+                        &[token.clone(), weth.clone(), AbiToken::Uint(fee)],
+                    )
+                    .await,
+                );
+            }
+
+            // a failed lookup is retried next cycle instead of hiding a pool for the whole ttl
+            let complete = lookups.iter().all(|lookup| lookup.is_ok());
+            let pools: Vec<String> = lookups.into_iter().flatten().flatten().collect();
+            if complete {
+                TOKEN_POOLS.insert(&contract, pools.clone()).await;
+            }
+            pools
+        }
+    };
+
+    if let Some(pair_address) = pair_address.filter(|pair| !pair.is_empty()) {
+        let pair_address = pair_address.to_lowercase();
+        if !pools.contains(&pair_address) {
+            pools.push(pair_address);
+        }
+    }
+
+    pools
+}
+
+// pool -> WETH it holds, pools whose balance couldn't be read are left out
+pub async fn pool_weth(pools: &[String]) -> HashMap<String, f64> {
+    let mut weth = HashMap::new();
+
+    for pool in pools {
+        if let Ok(balance) = get_token_balance(WETH_ADDRESS.to_owned(), pool.clone()).await {
+            weth.insert(pool.clone(), balance as f64 / 10.0f64.powi(18));
+        }
+    }
+
+    weth
+}
+
+// (pool, eth, seen at)
+type PoolChange = (String, f64, i64);
+
+#[derive(Debug, Clone, Default)]
+pub struct PoolTracker {
+    // pool -> WETH in it at the last check
+    weth: HashMap<String, f64>,
+    // removals and additions of the window that weren't matched yet
+    removed: Vec<PoolChange>,
+    added: Vec<PoolChange>,
+}
+
+/*
+
+Projects move their liquidity to a new pool, from V2 to V3 or to a new pair,
+which looks like a rug when only the old pool is watched. The WETH of every
+pool of the token is compared between checks: liquidity leaving one pool
+while a pool that was empty until now receives most of it within half an hour
+is a migration. Pools that already had liquidity don't count, a large buy
+into one of them would otherwise pass a rug off as a migration.
+The new pool is often funded before the old one is emptied, but when it's the
+other way around the removal is alerted right away as it may well be a rug,
+and the migration follows once the new pool shows up.
+
+*/
+impl PoolTracker {
+    // pools whose WETH couldn't be read are left out of `pools` and keep their last reading
+    pub fn update(&mut self, pools: HashMap<String, f64>, now: i64) -> Vec<LiquidityMove> {
+        let first_check = self.weth.is_empty();
+        self.removed
+            .retain(|(_, _, at)| now - at < MIGRATION_WINDOW_SECONDS);
+        self.added
+            .retain(|(_, _, at)| now - at < MIGRATION_WINDOW_SECONDS);
+
+        let mut removed = vec![];
+        let mut added = vec![];
+        for (pool, after) in &pools {
+            let before = self.weth.get(pool).copied().unwrap_or(0.0);
+
+            if before >= MIN_POOL_ETH && after <= &(before * (1.0 - REMOVED_FRACTION)) {
+                removed.push((pool.clone(), before - after, now));
+            } else if before < MIN_POOL_ETH && after - before >= MIN_POOL_ETH {
+                added.push((pool.clone(), after - before, now));
+            }
+        }
+        self.weth.extend(pools);
+        if first_check {
+            return vec![];
+        }
+        // the largest moves are matched first
+        removed.sort_by(|a, b| b.1.total_cmp(&a.1));
+        added.sort_by(|a, b| b.1.total_cmp(&a.1));
+        self.added.extend(added);
+
+        let mut moves = vec![];
+        for (pool, eth, at) in removed {
+            let matched = self
+                .added
+                .iter()
+                .position(|(to, added, _)| *to != pool && *added >= eth * MIN_MIGRATED_FRACTION);
+
+            match matched {
+                Some(i) => {
+                    let (to, _, _) = self.added.remove(i);
+                    moves.push(LiquidityMove::Migrated {
+                        from: pool,
+                        to,
+                        eth,
+                        follow_up: false,
+                    });
+                }
+                None => {
+                    moves.push(LiquidityMove::Removed {
+                        pool: pool.clone(),
+                        eth,
+                    });
+                    self.removed.push((pool, eth, at));
+                }
+            }
+        }
+
+        // pools funded after an earlier removal was alerted
+        let mut unmatched = vec![];
+        for (to, added, at) in self.added.drain(..) {
+            let matched = self.removed.iter().position(|(from, eth, removed_at)| {
+                *from != to && *removed_at < now && added >= eth * MIN_MIGRATED_FRACTION
+            });
+
+            match matched {
+                Some(i) => {
+                    let (from, eth, _) = self.removed.remove(i);
+                    moves.push(LiquidityMove::Migrated {
+                        from,
+                        to,
+                        eth,
+                        follow_up: true,
+                    });
+                }
+                None => unmatched.push((to, added, at)),
+            }
+        }
+        self.added = unmatched;
+
+        moves
+    }
+}
+
+#[test]
+fn test_pool_tracker() {
+    let pools = |entries: &[(&str, f64)]| -> HashMap<String, f64> {
+        entries
+            .iter()
+            .map(|(pool, eth)| (pool.to_string(), *eth))
+            .collect()
+    };
+    let mut tracker = PoolTracker::default();
+    let now = 1_700_000_000;
+
+    assert!(tracker.update(pools(&[("v2", 40.0)]), now).is_empty());
+    // trading moves the reserves a little, that's nothing
+    assert!(tracker
+        .update(pools(&[("v2", 36.0), ("v3", 0.0)]), now + 60)
+        .is_empty());
+
+    // the v3 pool is funded first, then the v2 pool emptied
+    assert!(tracker
+        .update(pools(&[("v2", 36.0), ("v3", 30.0)]), now + 120)
+        .is_empty());
+    assert_eq!(
+        tracker.update(pools(&[("v2", 0.5), ("v3", 30.0)]), now + 180),
+        vec![LiquidityMove::Migrated {
+            from: String::from("v2"),
+            to: String::from("v3"),
+            eth: 35.5,
+            follow_up: false
+        }]
+    );
+
+    // removed with nowhere to go is alerted right away, a pool funded within the window follows up
+    let mut tracker = PoolTracker::default();
+    tracker.update(pools(&[("v2", 20.0)]), now);
+    assert_eq!(
+        tracker.update(pools(&[("v2", 0.0)]), now + 60),
+        vec![LiquidityMove::Removed {
+            pool: String::from("v2"),
+            eth: 20.0
+        }]
+    );
+    assert_eq!(
+        tracker.update(pools(&[("v2", 0.0), ("new", 19.0)]), now + 600),
+        vec![LiquidityMove::Migrated {
+            from: String::from("v2"),
+            to: String::from("new"),
+            eth: 20.0,
+            follow_up: true
+        }]
+    );
+
+    // a large buy into a pool that already had liquidity isn't where the liquidity went
+    let mut tracker = PoolTracker::default();
+    tracker.update(pools(&[("v2", 20.0), ("v3", 10.0)]), now);
+    assert_eq!(
+        tracker.update(pools(&[("v2", 0.0), ("v3", 25.0)]), now + 60),
+        vec![LiquidityMove::Removed {
+            pool: String::from("v2"),
+            eth: 20.0
+        }]
+    );
+
+    // too late, or a pool that got much less than what was removed, isn't a migration
+    let mut tracker = PoolTracker::default();
+    tracker.update(pools(&[("v2", 20.0)]), now);
+    tracker.update(pools(&[("v2", 0.0)]), now + 60);
+    assert!(tracker
+        .update(pools(&[("small", 5.0)]), now + 120)
+        .is_empty());
+    assert!(tracker
+        .update(
+            pools(&[("late", 20.0)]),
+            now + 60 + MIGRATION_WINDOW_SECONDS
+        )
+        .is_empty());
+}
@@ -1,19 +1,29 @@
-use reqwest::{header::ACCEPT, Client};
+use super::{
+    instrumentation,
+    providers::{self, Provider},
+    usage,
+};
+use reqwest::header::ACCEPT;
 use serde::{de, Deserialize};
 use std::env;
 
 async fn send_request<T: de::DeserializeOwned>(url: String) -> Result<T, reqwest::Error> {
-    let response = Client::new()
-        .get(format!("https://deep-index.moralis.io/api/v2.2/{}", url))
-        .header(ACCEPT, "applciation/json")
-        .header(
-            "X-API-Key",
-            env::var("MORALIS_API").expect("MORALIS_API env var is not set"),
-        )
-        .send()
-        .await?
-        .json()
-        .await?;
+    usage::record(Provider::Moralis).await;
+    let response = providers::send(
+        Provider::Moralis,
+        &instrumentation::endpoint(&url),
+        providers::client()
+            .get(format!("{}/{}", Provider::Moralis.base_url(), url))
+            .headers(Provider::Moralis.headers())
+            .header(ACCEPT, "applciation/json")
+            .header(
+                "X-API-Key",
+                env::var("MORALIS_API").expect("MORALIS_API env var is not set"),
+            ),
+    )
+    .await?
+    .json()
+    .await?;
 
     Ok(response)
 }
@@ -27,10 +37,11 @@ pub async fn get_token_price(contract: String) -> Result<MoralisTokenPrice, reqw
 }
 
 pub async fn get_token_balances_with_prices(
+    address: &str,
 ) -> Result<MoralisResult<MoralisTokenBalancesWithPrices>, reqwest::Error> {
     send_request::<MoralisResult<MoralisTokenBalancesWithPrices>>(format!(
         "wallets/{}/tokens?chain=eth",
-        env::var("ETH_ADDRESS").expect("ETH_ADDRESS env var is not set")
+        address
     ))
     .await
 }
@@ -0,0 +1,121 @@
+use super::usage::ProviderUsage;
+use chrono::{DateTime, Duration, Timelike, Utc};
+use std::{collections::HashMap, hash::Hash};
+
+// (seconds since the last activity, poll interval in minutes), the watch loops wake up every minute
+const ACTIVITY_TIERS: [(i64, i64); 3] = [(3600, 1), (86400, 3), (7 * 86400, 10)];
+const DORMANT_INTERVAL_MINUTES: i64 = 30;
+// even a provider far ahead of its daily pace doesn't stretch the intervals more than this
+const MAX_BUDGET_FACTOR: f64 = 4.0;
+
+/*
+
+Watched wallets and tokens are polled as often as they're active: anything
+that moved in the last hour every minute, dormant ones every half hour. New
+activity is never skipped, every poll fetches everything since the last one,
+so a dormant wallet waking up is alerted a few minutes late at worst and is
+polled every minute again from then on.
+
+When a provider with a daily limit is used faster than the pace that would
+spend the limit by the end of the UTC day, every interval is stretched by how
+far ahead it is, so the watches slow down before the quota runs out.
+
+*/
+#[derive(Debug, Clone)]
+pub struct PollSchedule<K> {
+    next_polls: HashMap<K, DateTime<Utc>>,
+}
+
+impl<K> Default for PollSchedule<K> {
+    fn default() -> Self {
+        PollSchedule {
+            next_polls: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> PollSchedule<K> {
+    // watches that were never polled are due right away
+    pub fn is_due(&self, key: &K, now: DateTime<Utc>) -> bool {
+        self.next_polls
+            .get(key)
+            .map_or(true, |next_poll| *next_poll <= now)
+    }
+
+    // last_activity is the unix timestamp of the newest transfer, 0 when there's none
+    pub fn polled(&mut self, key: K, last_activity: i64, now: DateTime<Utc>, budget_factor: f64) {
+        let minutes = interval_minutes(last_activity, now.timestamp()) as f64 * budget_factor;
+        let interval = Duration::try_seconds((minutes * 60.0) as i64).unwrap();
+
+        self.next_polls.insert(key, now + interval);
+    }
+
+    pub fn retain(&mut self, keep: impl Fn(&K) -> bool) {
+        self.next_polls.retain(|key, _| keep(key));
+    }
+}
+
+fn interval_minutes(last_activity: i64, now: i64) -> i64 {
+    let idle = now - last_activity;
+
+    ACTIVITY_TIERS
+        .iter()
+        .find(|(within, _)| last_activity > 0 && idle < *within)
+        .map_or(DORMANT_INTERVAL_MINUTES, |(_, minutes)| *minutes)
+}
+
+// how far the busiest limited provider is ahead of the pace that spends its daily limit at midnight
+pub fn budget_factor(usages: &[ProviderUsage], now: DateTime<Utc>) -> f64 {
+    // the first minutes of a day would make any request look far ahead of the pace
+    let day_elapsed = ((now.num_seconds_from_midnight() as f64) / 86400.0).max(1.0 / 24.0);
+
+    usages
+        .iter()
+        .filter_map(|usage| usage.daily_percent())
+        .map(|percent| percent / 100.0 / day_elapsed)
+        .fold(1.0, f64::max)
+        .min(MAX_BUDGET_FACTOR)
+}
+
+#[test]
+fn test_poll_schedule() {
+    use super::providers::Provider;
+
+    let now = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    let minutes_ago = |minutes: i64| (now - Duration::try_minutes(minutes).unwrap()).timestamp();
+    let mut schedule = PollSchedule::<String>::default();
+
+    let active = String::from("0xactive");
+    let dormant = String::from("0xdormant");
+    assert!(schedule.is_due(&active, now));
+
+    schedule.polled(active.clone(), minutes_ago(5), now, 1.0);
+    schedule.polled(dormant.clone(), minutes_ago(60 * 24 * 30), now, 1.0);
+    let later = |minutes: i64| now + Duration::try_minutes(minutes).unwrap();
+    assert!(schedule.is_due(&active, later(1)));
+    assert!(!schedule.is_due(&dormant, later(29)));
+    assert!(schedule.is_due(&dormant, later(30)));
+
+    // active a few hours ago, polled every 3 minutes or every 6 when a quota is spent twice as fast as its pace
+    schedule.polled(active.clone(), minutes_ago(180), now, 2.0);
+    assert!(!schedule.is_due(&active, later(5)));
+    assert!(schedule.is_due(&active, later(6)));
+    assert_eq!(
+        interval_minutes(0, now.timestamp()),
+        DORMANT_INTERVAL_MINUTES
+    );
+
+    let usage = |daily: u64| ProviderUsage {
+        provider: Provider::Etherscan,
+        daily,
+        monthly: daily,
+        daily_limit: Some(100_000),
+        monthly_limit: None,
+    };
+    // half the day is gone
+    assert_eq!(budget_factor(&[usage(25_000)], now), 1.0);
+    assert_eq!(budget_factor(&[usage(25_000), usage(75_000)], now), 1.5);
+    assert_eq!(budget_factor(&[usage(100_000)], now), 2.0);
+}
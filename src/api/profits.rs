@@ -0,0 +1,331 @@
+use super::{
+    etherscan::{
+        EtherscanInternalTransaction, EtherscanNormalTransaction, EtherscanTokenTransaction,
+    },
+    WETH_ADDRESS,
+};
+use std::{cmp::Reverse, collections::HashMap};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WalletProfit {
+    pub realized_eth: f64,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+#[derive(Debug, Default)]
+struct Holding {
+    amount: f64,
+    cost_eth: f64,
+}
+
+fn wei_to_eth(value: &str) -> f64 {
+    value.parse::<f64>().unwrap_or(0.0) / 10.0f64.powi(18)
+}
+
+fn token_amount(transaction: &EtherscanTokenTransaction) -> f64 {
+    transaction.value.parse::<f64>().unwrap_or(0.0)
+        / 10.0f64.powi(transaction.token_decimal.parse::<i32>().unwrap_or(18))
+}
+
+// swaps are paired with the eth or weth that moved in the same transaction, buys are averaged into a
+// cost basis and only sells after `since` are realized, tokens bought before the history starts are skipped
+pub fn realized_profit(
+    wallet: &str,
+    token_transactions: &[EtherscanTokenTransaction],
+    normal_transactions: &[EtherscanNormalTransaction],
+    internal_transactions: &[EtherscanInternalTransaction],
+    since: u64,
+) -> WalletProfit {
+    let wallet = wallet.to_lowercase();
+    let mut profit = WalletProfit::default();
+    let mut holdings: HashMap<String, Holding> = HashMap::new();
+
+    let mut hashes: Vec<(u64, String)> = token_transactions
+        .iter()
+        .map(|t| {
+            (
+                t.time_stamp.parse::<u64>().unwrap_or(0),
+                t.hash.to_lowercase(),
+            )
+        })
+        .collect();
+    hashes.sort();
+    hashes.dedup();
+
+    for (time_stamp, hash) in hashes {
+        let mut eth_out: f64 = normal_transactions
+            .iter()
+            .filter(|t| t.hash.to_lowercase() == hash && t.from.to_lowercase() == wallet)
+            .map(|t| wei_to_eth(&t.value))
+            .sum();
+        let mut eth_in: f64 = internal_transactions
+            .iter()
+            .filter(|t| t.hash.to_lowercase() == hash && t.to.to_lowercase() == wallet)
+            .map(|t| wei_to_eth(&t.value))
+            .sum();
+        let mut received: HashMap<String, f64> = HashMap::new();
+        let mut sent: HashMap<String, f64> = HashMap::new();
+
+        for transfer in token_transactions
+            .iter()
+            .filter(|t| t.hash.to_lowercase() == hash)
+        {
+            let contract = transfer.contract_address.to_lowercase();
+            let amount = token_amount(transfer);
+            let is_weth = contract == WETH_ADDRESS;
+
+            match (
+                transfer.to.to_lowercase() == wallet,
+                transfer.from.to_lowercase() == wallet,
+            ) {
+                (true, false) if is_weth => eth_in += amount,
+                (false, true) if is_weth => eth_out += amount,
+                (true, false) => *received.entry(contract).or_default() += amount,
+                (false, true) => *sent.entry(contract).or_default() += amount,
+                _ => (),
+            }
+        }
+
+        // multi-token swaps can't be split into per token prices
+        if received.len() == 1 && sent.is_empty() && eth_out > 0.0 {
+            let (contract, amount) = received.into_iter().next().unwrap();
+            let holding = holdings.entry(contract).or_default();
+            holding.amount += amount;
+            holding.cost_eth += eth_out;
+        } else if sent.len() == 1 && received.is_empty() && eth_in > 0.0 {
+            let (contract, amount) = sent.into_iter().next().unwrap();
+            let Some(holding) = holdings.get_mut(&contract) else {
+                continue;
+            };
+            if holding.amount <= 0.0 || amount <= 0.0 {
+                continue;
+            }
+
+            let sold = amount.min(holding.amount);
+            let cost_basis = holding.cost_eth * sold / holding.amount;
+            let proceeds = eth_in * sold / amount;
+            holding.amount -= sold;
+            holding.cost_eth -= cost_basis;
+
+            if time_stamp >= since {
+                let pnl = proceeds - cost_basis;
+                profit.realized_eth += pnl;
+                if pnl >= 0.0 {
+                    profit.wins += 1;
+                } else {
+                    profit.losses += 1;
+                }
+            }
+        }
+    }
+
+    profit
+}
+
+// a token the wallet bought in the period, etherscan has no usd price history so entries are in eth
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentBuy {
+    pub contract: String,
+    pub symbol: String,
+    pub first_bought_at: u64,
+    pub last_bought_at: u64,
+    pub buys: u32,
+    pub tokens: f64,
+    // the tokens of the buys paid in eth or weth and what they cost, buys paid with other tokens have no price
+    pub priced_tokens: f64,
+    pub cost_eth: f64,
+}
+
+impl RecentBuy {
+    pub fn entry_price_eth(&self) -> Option<f64> {
+        if self.priced_tokens > 0.0 {
+            Some(self.cost_eth / self.priced_tokens)
+        } else {
+            None
+        }
+    }
+}
+
+// tokens coming in with a transaction the wallet sent itself, so airdrops and transfers from other
+// wallets aren't buys. newest buy first
+pub fn recent_buys(
+    wallet: &str,
+    token_transactions: &[EtherscanTokenTransaction],
+    normal_transactions: &[EtherscanNormalTransaction],
+    since: u64,
+) -> Vec<RecentBuy> {
+    let wallet = wallet.to_lowercase();
+    let mut buys: HashMap<String, RecentBuy> = HashMap::new();
+
+    let mut hashes: Vec<(u64, String)> = token_transactions
+        .iter()
+        .map(|t| {
+            (
+                t.time_stamp.parse::<u64>().unwrap_or(0),
+                t.hash.to_lowercase(),
+            )
+        })
+        .filter(|(time_stamp, _)| *time_stamp >= since)
+        .collect();
+    hashes.sort();
+    hashes.dedup();
+
+    for (time_stamp, hash) in hashes {
+        let sent_by_wallet: Vec<&EtherscanNormalTransaction> = normal_transactions
+            .iter()
+            .filter(|t| t.hash.to_lowercase() == hash && t.from.to_lowercase() == wallet)
+            .collect();
+        let mut eth_out: f64 = sent_by_wallet.iter().map(|t| wei_to_eth(&t.value)).sum();
+        let mut received: HashMap<String, (String, f64)> = HashMap::new();
+        let mut sent_tokens = false;
+
+        for transfer in token_transactions
+            .iter()
+            .filter(|t| t.hash.to_lowercase() == hash)
+        {
+            let contract = transfer.contract_address.to_lowercase();
+            let amount = token_amount(transfer);
+            let is_weth = contract == WETH_ADDRESS;
+
+            match (
+                transfer.to.to_lowercase() == wallet,
+                transfer.from.to_lowercase() == wallet,
+            ) {
+                (false, true) if is_weth => eth_out += amount,
+                (true, false) if !is_weth => {
+                    let entry = received
+                        .entry(contract)
+                        .or_insert((transfer.token_symbol.clone(), 0.0));
+                    entry.1 += amount;
+                }
+                (false, true) => sent_tokens = true,
+                _ => (),
+            }
+        }
+
+        if sent_by_wallet.is_empty() && eth_out <= 0.0 {
+            continue;
+        }
+
+        // multi-token swaps can't be split into per token prices
+        let priced = received.len() == 1 && !sent_tokens && eth_out > 0.0;
+        for (contract, (symbol, amount)) in received {
+            let buy = buys.entry(contract.clone()).or_insert(RecentBuy {
+                contract,
+                symbol,
+                first_bought_at: time_stamp,
+                last_bought_at: time_stamp,
+                buys: 0,
+                tokens: 0.0,
+                priced_tokens: 0.0,
+                cost_eth: 0.0,
+            });
+            buy.last_bought_at = time_stamp;
+            buy.buys += 1;
+            buy.tokens += amount;
+            if priced {
+                buy.priced_tokens += amount;
+                buy.cost_eth += eth_out;
+            }
+        }
+    }
+
+    let mut buys: Vec<RecentBuy> = buys.into_values().collect();
+    buys.sort_by_key(|buy| Reverse(buy.last_bought_at));
+    buys
+}
+
+#[test]
+fn test_realized_profit() {
+    let wallet = "0x1111111111111111111111111111111111111111";
+    let pool = "0x2222222222222222222222222222222222222222";
+    let token = "0x3333333333333333333333333333333333333333";
+    let transfer = |hash: &str, time_stamp: &str, from: &str, to: &str, value: &str| {
+        EtherscanTokenTransaction {
+            time_stamp: time_stamp.to_owned(),
+            hash: hash.to_owned(),
+            from: from.to_owned(),
+            contract_address: token.to_owned(),
+            to: to.to_owned(),
+            value: value.to_owned(),
+            token_decimal: String::from("0"),
+            ..EtherscanTokenTransaction::default()
+        }
+    };
+    let weth_transfer = |hash: &str, time_stamp: &str, from: &str, to: &str, value: &str| {
+        EtherscanTokenTransaction {
+            contract_address: WETH_ADDRESS.to_owned(),
+            token_decimal: String::from("18"),
+            ..transfer(hash, time_stamp, from, to, value)
+        }
+    };
+
+    let token_transactions = vec![
+        // sold half of the tokens for 1.5 weth after buying them for 2 weth
+        transfer("0xsell", "300", wallet, pool, "50"),
+        weth_transfer("0xsell", "300", pool, wallet, "1500000000000000000"),
+        transfer("0xbuy", "100", pool, wallet, "100"),
+        weth_transfer("0xbuy", "100", wallet, pool, "2000000000000000000"),
+    ];
+
+    let profit = realized_profit(wallet, &token_transactions, &[], &[], 200);
+    assert!((profit.realized_eth - 0.5).abs() < 1e-9);
+    assert_eq!((profit.wins, profit.losses), (1, 0));
+
+    // sells before the period only move the cost basis
+    let profit = realized_profit(wallet, &token_transactions, &[], &[], 400);
+    assert_eq!(profit, WalletProfit::default());
+}
+
+#[test]
+fn test_recent_buys() {
+    let wallet = "0x1111111111111111111111111111111111111111";
+    let pool = "0x2222222222222222222222222222222222222222";
+    let transfer = |hash: &str, time_stamp: &str, contract: &str, from: &str, to: &str| {
+        EtherscanTokenTransaction {
+            time_stamp: time_stamp.to_owned(),
+            hash: hash.to_owned(),
+            from: from.to_owned(),
+            contract_address: contract.to_owned(),
+            to: to.to_owned(),
+            value: String::from("1000"),
+            token_symbol: contract.to_uppercase(),
+            token_decimal: String::from("0"),
+            ..EtherscanTokenTransaction::default()
+        }
+    };
+    let sent = |hash: &str, value: &str| EtherscanNormalTransaction {
+        hash: hash.to_owned(),
+        from: wallet.to_owned(),
+        to: pool.to_owned(),
+        value: value.to_owned(),
+        is_error: String::from("0"),
+        ..EtherscanNormalTransaction::default()
+    };
+
+    let token_transactions = vec![
+        // pepe bought twice for 1 and 3 eth, then for usdc which has no eth price
+        transfer("0xbuy1", "100", "pepe", pool, wallet),
+        transfer("0xbuy2", "200", "pepe", pool, wallet),
+        transfer("0xbuy3", "300", "usdc", wallet, pool),
+        transfer("0xbuy3", "300", "pepe", pool, wallet),
+        // an airdrop and a buy before the period
+        transfer("0xdrop", "400", "spam", pool, wallet),
+        transfer("0xold", "10", "old", pool, wallet),
+    ];
+    let normal_transactions = vec![
+        sent("0xbuy1", "1000000000000000000"),
+        sent("0xbuy2", "3000000000000000000"),
+        sent("0xbuy3", "0"),
+        sent("0xold", "1000000000000000000"),
+    ];
+
+    let buys = recent_buys(wallet, &token_transactions, &normal_transactions, 50);
+    assert_eq!(buys.len(), 1);
+    let pepe = &buys[0];
+    assert_eq!((pepe.symbol.as_str(), pepe.buys), ("PEPE", 3));
+    assert_eq!((pepe.first_bought_at, pepe.last_bought_at), (100, 300));
+    assert_eq!((pepe.tokens, pepe.priced_tokens), (3000.0, 2000.0));
+    assert_eq!(pepe.entry_price_eth(), Some(4.0 / 2000.0));
+}
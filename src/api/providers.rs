@@ -0,0 +1,239 @@
+use super::{errorbudget, instrumentation};
+use crate::logging;
+use chrono::Utc;
+use core::fmt;
+use lazy_static::lazy_static;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    tls, Client, Proxy, RequestBuilder, Response,
+};
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    // one pooled client for every provider, building a new one per request drops open connections
+    static ref CLIENT: Client = build_client();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Provider {
+    Etherscan,
+    Moralis,
+    Alchemy,
+    Chainbase,
+    Honeypot,
+    Safe,
+    Flashbots,
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Provider::Etherscan => write!(f, "Etherscan"),
+            Provider::Moralis => write!(f, "Moralis"),
+            Provider::Alchemy => write!(f, "Alchemy"),
+            Provider::Chainbase => write!(f, "Chainbase"),
+            Provider::Honeypot => write!(f, "honeypot.is"),
+            Provider::Safe => write!(f, "Safe Transaction Service"),
+            Provider::Flashbots => write!(f, "Flashbots relay"),
+        }
+    }
+}
+
+impl Provider {
+    pub fn all() -> [Provider; 7] {
+        [
+            Provider::Etherscan,
+            Provider::Moralis,
+            Provider::Alchemy,
+            Provider::Chainbase,
+            Provider::Honeypot,
+            Provider::Safe,
+            Provider::Flashbots,
+        ]
+    }
+
+    fn env_prefix(&self) -> &'static str {
+        match self {
+            Provider::Etherscan => "ETHERSCAN",
+            Provider::Moralis => "MORALIS",
+            Provider::Alchemy => "ALCHEMY",
+            Provider::Chainbase => "CHAINBASE",
+            Provider::Honeypot => "HONEYPOT",
+            Provider::Safe => "SAFE",
+            Provider::Flashbots => "FLASHBOTS",
+        }
+    }
+
+    fn default_base_url(&self) -> &'static str {
+        match self {
+            Provider::Etherscan => "https://api.etherscan.io/api",
+            Provider::Moralis => "https://deep-index.moralis.io/api/v2.2",
+            Provider::Alchemy => "https://eth-mainnet.g.alchemy.com/v2",
+            Provider::Chainbase => "https://api.chainbase.online/v1",
+            Provider::Honeypot => "https://api.honeypot.is/v2",
+            Provider::Safe => "https://safe-transaction-mainnet.safe.global/api/v1",
+            Provider::Flashbots => "https://relay.flashbots.net",
+        }
+    }
+
+    // e.g. ETHERSCAN_BASE_URL, for corporate proxies and self-hosted gateways
+    pub fn base_url(&self) -> String {
+        match env::var(format!("{}_BASE_URL", self.env_prefix())) {
+            Ok(url) if !url.is_empty() => url.trim_end_matches('/').to_owned(),
+            _ => self.default_base_url().to_owned(),
+        }
+    }
+
+    // free tier request limits, the others meter compute units instead of requests
+    fn default_daily_limit(&self) -> Option<u64> {
+        match self {
+            Provider::Etherscan => Some(100_000),
+            _ => None,
+        }
+    }
+
+    // e.g. MORALIS_DAILY_LIMIT=40000, 0 turns the default limit off
+    pub fn daily_limit(&self) -> Option<u64> {
+        match env::var(format!("{}_DAILY_LIMIT", self.env_prefix())) {
+            Ok(limit) => limit.parse::<u64>().ok().filter(|limit| *limit > 0),
+            Err(_) => self.default_daily_limit(),
+        }
+    }
+
+    // e.g. CHAINBASE_MONTHLY_LIMIT=300000
+    pub fn monthly_limit(&self) -> Option<u64> {
+        env::var(format!("{}_MONTHLY_LIMIT", self.env_prefix()))
+            .ok()
+            .and_then(|limit| limit.parse::<u64>().ok())
+            .filter(|limit| *limit > 0)
+    }
+
+    // e.g. ETHERSCAN_HEADERS="X-Gateway-Key: abc; X-Team: snipers"
+    pub fn headers(&self) -> HeaderMap {
+        match env::var(format!("{}_HEADERS", self.env_prefix())) {
+            Ok(headers) => parse_headers(&headers),
+            Err(_) => HeaderMap::new(),
+        }
+    }
+}
+
+fn parse_headers(headers: &str) -> HeaderMap {
+    let mut header_map = HeaderMap::new();
+
+    for header in headers.split(';').filter(|h| !h.trim().is_empty()) {
+        let Some((name, value)) = header.split_once(':') else {
+            error!("invalid custom header (expected 'Name: value'): {}", header);
+            continue;
+        };
+
+        match (
+            HeaderName::from_bytes(name.trim().as_bytes()),
+            HeaderValue::from_str(value.trim()),
+        ) {
+            (Ok(name), Ok(value)) => {
+                header_map.insert(name, value);
+            }
+            _ => error!("invalid custom header: {}", header),
+        }
+    }
+
+    header_map
+}
+
+// requests can override the default timeout with RequestBuilder::timeout
+pub fn client() -> &'static Client {
+    &CLIENT
+}
+
+// every provider request goes through here. the body is read once to measure it, the response
+// handed back is rebuilt from it so the callers still decode it with reqwest
+pub async fn send(
+    provider: Provider,
+    endpoint: &str,
+    request: RequestBuilder,
+) -> Result<Response, reqwest::Error> {
+    let started = Instant::now();
+    let result = async {
+        let response = request.send().await?;
+        let (status, headers) = (response.status(), response.headers().clone());
+        let body = response.bytes().await?;
+
+        Ok::<_, reqwest::Error>((status, headers, body))
+    }
+    .await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let (status, bytes) = match &result {
+        Ok((status, _, body)) => (status.as_u16().to_string(), body.len() as u64),
+        Err(e) => (logging::request_error_kind(e).to_owned(), 0),
+    };
+    instrumentation::record(provider, endpoint, latency_ms, &status, bytes).await;
+    errorbudget::record(provider, &status, Utc::now().timestamp() / 60).await;
+
+    if latency_ms >= instrumentation::slow_request_ms() {
+        tracing::warn!(
+            provider = %provider,
+            endpoint,
+            status = %status,
+            bytes,
+            latency_ms,
+            "slow provider request"
+        );
+    }
+
+    match result {
+        Ok((status, headers, body)) => {
+            tracing::debug!(provider = %provider, endpoint, status = %status, bytes, latency_ms, "provider request");
+
+            let mut response = http::Response::new(body);
+            *response.status_mut() = status;
+            *response.headers_mut() = headers;
+            Ok(Response::from(response))
+        }
+        Err(e) => {
+            tracing::info!(
+                provider = %provider,
+                endpoint,
+                latency_ms,
+                error_kind = %status,
+                "provider request failed"
+            );
+            Err(e)
+        }
+    }
+}
+
+// PROXY_URL applies to every provider, http://, https:// and socks5:// proxies are supported
+fn build_client() -> Client {
+    let builder = Client::builder()
+        .user_agent(concat!("snipers/", env!("CARGO_PKG_VERSION")))
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(30))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .min_tls_version(tls::Version::TLS_1_2);
+
+    let builder = match env::var("PROXY_URL") {
+        Ok(url) if !url.is_empty() => match Proxy::all(&url) {
+            Ok(proxy) => builder.proxy(proxy),
+            Err(e) => {
+                error!("invalid PROXY_URL, requests are sent directly: {}", e);
+                builder
+            }
+        },
+        _ => builder,
+    };
+
+    builder.build().expect("failed to build http client")
+}
+
+#[test]
+fn test_parse_headers() {
+    let headers = parse_headers("X-Gateway-Key: abc; X-Team:snipers;;invalid");
+
+    assert_eq!(headers.len(), 2);
+    assert_eq!(headers.get("x-gateway-key").unwrap(), "abc");
+    assert_eq!(headers.get("x-team").unwrap(), "snipers");
+}
@@ -0,0 +1,154 @@
+use super::{
+    instrumentation,
+    providers::{self, Provider},
+    usage,
+};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+async fn send_request<T: de::DeserializeOwned>(url: String) -> Result<T, reqwest::Error> {
+    usage::record(Provider::Safe).await;
+    providers::send(
+        Provider::Safe,
+        &instrumentation::endpoint(&url),
+        providers::client()
+            .get(format!("{}/{}", Provider::Safe.base_url(), url))
+            .headers(Provider::Safe.headers()),
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await
+}
+
+// older service versions return the nonce as a number, newer ones as a string
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(number) => number
+            .as_u64()
+            .ok_or_else(|| de::Error::custom("nonce is not a u64")),
+        serde_json::Value::String(string) => string.parse::<u64>().map_err(de::Error::custom),
+        _ => Err(de::Error::custom("nonce is not a number")),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SafeInfo {
+    pub address: String,
+    #[serde(deserialize_with = "number_or_string")]
+    pub nonce: u64,
+    pub threshold: u64,
+    pub owners: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SafeConfirmation {
+    pub owner: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeMultisigTransaction {
+    pub safe: String,
+    pub safe_tx_hash: String,
+    #[serde(deserialize_with = "number_or_string")]
+    pub nonce: u64,
+    pub confirmations_required: Option<u64>,
+    pub confirmations: Option<Vec<SafeConfirmation>>,
+    pub is_executed: bool,
+    pub is_successful: Option<bool>,
+    pub transaction_hash: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SafeResults<T> {
+    results: Vec<T>,
+}
+
+// body of a multisig transaction proposal, addresses have to be checksummed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeProposal {
+    pub to: String,
+    pub value: String,
+    pub data: String,
+    pub operation: u8,
+    pub safe_tx_gas: String,
+    pub base_gas: String,
+    pub gas_price: String,
+    pub gas_token: String,
+    pub refund_receiver: String,
+    pub nonce: u64,
+    pub contract_transaction_hash: String,
+    pub sender: String,
+    pub signature: String,
+    pub origin: String,
+}
+
+pub async fn get_safe_info(safe: &str) -> Result<SafeInfo, reqwest::Error> {
+    send_request::<SafeInfo>(format!("safes/{}/", safe)).await
+}
+
+// queued proposals already took the nonces after the Safe's on-chain nonce
+pub async fn get_next_safe_nonce(safe: &str) -> Result<u64, reqwest::Error> {
+    let info = get_safe_info(safe).await?;
+    let latest = send_request::<SafeResults<SafeMultisigTransaction>>(format!(
+        "safes/{}/multisig-transactions/?ordering=-nonce&limit=1",
+        safe
+    ))
+    .await?;
+
+    Ok(latest.results.first().map_or(info.nonce, |transaction| {
+        info.nonce.max(transaction.nonce + 1)
+    }))
+}
+
+pub async fn propose_safe_transaction(
+    safe: &str,
+    proposal: &SafeProposal,
+) -> Result<(), reqwest::Error> {
+    usage::record(Provider::Safe).await;
+    providers::send(
+        Provider::Safe,
+        "safes/:address/multisig-transactions",
+        providers::client()
+            .post(format!(
+                "{}/safes/{}/multisig-transactions/",
+                Provider::Safe.base_url(),
+                safe
+            ))
+            .headers(Provider::Safe.headers())
+            .json(proposal),
+    )
+    .await?
+    .error_for_status()?;
+
+    Ok(())
+}
+
+pub async fn get_safe_transaction(
+    safe_tx_hash: &str,
+) -> Result<SafeMultisigTransaction, reqwest::Error> {
+    send_request::<SafeMultisigTransaction>(format!("multisig-transactions/{}/", safe_tx_hash))
+        .await
+}
+
+#[test]
+fn test_safe_transaction_format() {
+    let transaction: SafeMultisigTransaction = serde_json::from_str(
+        r#"{
+            "safe": "0x5afe3855358e112b5647b952709e6165e1c1eeee",
+            "safeTxHash": "0x8a1c4f6b0d1f3c2e5b7a9d8c6e4f2a1b3c5d7e9f0a2b4c6d8e0f1a3b5c7d9e1f",
+            "nonce": "7",
+            "confirmationsRequired": 2,
+            "confirmations": [{ "owner": "0x11DDACb10c3891e356dcE6D7c6F22DD69c93E2Cd" }],
+            "isExecuted": false,
+            "isSuccessful": null,
+            "transactionHash": null
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(transaction.nonce, 7);
+    assert_eq!(transaction.confirmations.unwrap().len(), 1);
+    assert!(!transaction.is_executed);
+}
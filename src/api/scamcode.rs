@@ -0,0 +1,441 @@
+use core::fmt;
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::{
+    collections::hash_map::DefaultHasher,
+    env, fs,
+    hash::{Hash, Hasher},
+};
+
+// tokens per shingle, long enough that unrelated contracts rarely share one
+const SHINGLE_TOKENS: usize = 8;
+const SIGNATURE_HASHES: usize = 128;
+const DEFAULT_MIN_MATCH_PERCENT: f64 = 70.0;
+
+// kept as they are, every other identifier is a name a deployer can rename
+const KEYWORDS: [&str; 64] = [
+    "abi",
+    "abstract",
+    "address",
+    "assert",
+    "block",
+    "bool",
+    "break",
+    "bytes",
+    "bytes32",
+    "calldata",
+    "catch",
+    "constant",
+    "constructor",
+    "continue",
+    "contract",
+    "delete",
+    "else",
+    "emit",
+    "encode",
+    "encodePacked",
+    "enum",
+    "error",
+    "event",
+    "external",
+    "fallback",
+    "false",
+    "for",
+    "function",
+    "if",
+    "immutable",
+    "indexed",
+    "int",
+    "int256",
+    "interface",
+    "internal",
+    "is",
+    "library",
+    "mapping",
+    "memory",
+    "modifier",
+    "msg",
+    "new",
+    "number",
+    "origin",
+    "override",
+    "payable",
+    "private",
+    "public",
+    "pure",
+    "receive",
+    "require",
+    "return",
+    "returns",
+    "revert",
+    "sender",
+    "storage",
+    "string",
+    "struct",
+    "this",
+    "timestamp",
+    "true",
+    "tx",
+    "view",
+    "virtual",
+];
+
+/*
+
+Many rugs are the same contract redeployed with its variables renamed. The
+verified source is reduced to the tokens of its main contract with comments,
+strings, numbers and every name that isn't a Solidity keyword or type blanked
+out, then to a MinHash signature of its shingles. Two contracts with the same
+code have the same signature whatever they named things, and the share of
+equal hashes estimates how much code they have in common.
+
+The templates are the .sol files in SCAM_TEMPLATES_DIR, their family is the
+file name without a -<n> variant suffix, e.g. blacklist-2.sol is a blacklist.
+None ship with the bot, without them scans say the code wasn't compared.
+
+*/
+#[derive(Debug, Clone, PartialEq)]
+struct Signature([u64; SIGNATURE_HASHES]);
+
+impl Signature {
+    // None for code shorter than a shingle
+    fn new(tokens: &[String]) -> Option<Signature> {
+        if tokens.len() < SHINGLE_TOKENS {
+            return None;
+        }
+
+        let mut signature = [u64::MAX; SIGNATURE_HASHES];
+        for shingle in tokens.windows(SHINGLE_TOKENS) {
+            let shingle = hash(&shingle);
+            for (seed, min) in signature.iter_mut().enumerate() {
+                *min = (*min).min(hash(&(seed, shingle)));
+            }
+        }
+
+        Some(Signature(signature))
+    }
+
+    fn similarity_percent(&self, other: &Signature) -> f64 {
+        let equal = self
+            .0
+            .iter()
+            .zip(other.0.iter())
+            .filter(|(a, b)| a == b)
+            .count();
+
+        equal as f64 / SIGNATURE_HASHES as f64 * 100.0
+    }
+}
+
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateMatch {
+    pub family: String,
+    pub percent: f64,
+}
+
+impl fmt::Display for TemplateMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Code {:.0}% identical to the {} scam template",
+            self.percent, self.family
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Template {
+    family: String,
+    signature: Signature,
+}
+
+lazy_static! {
+    static ref TEMPLATES: Vec<Template> = load();
+}
+
+fn load() -> Vec<Template> {
+    let dir = env::var("SCAM_TEMPLATES_DIR").unwrap_or(String::from("scam_templates"));
+    let Ok(entries) = fs::read_dir(&dir) else {
+        warn!(
+            "no scam templates directory {}, /scan won't compare verified code",
+            dir
+        );
+        return vec![];
+    };
+
+    let mut templates = vec![];
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if path.extension().and_then(|extension| extension.to_str()) != Some("sol") {
+            continue;
+        }
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let family = match stem.rsplit_once('-') {
+            Some((family, variant)) if variant.chars().all(|c| c.is_ascii_digit()) => family,
+            _ => stem,
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(source) => match Signature::new(&normalize(&source, None)) {
+                Some(signature) => templates.push(Template {
+                    family: family.to_owned(),
+                    signature,
+                }),
+                None => warn!("scam template {} is too short", path.display()),
+            },
+            Err(e) => error!("failed to read scam template {}: {}", path.display(), e),
+        }
+    }
+
+    info!("loaded {} scam templates from {}", templates.len(), dir);
+    templates
+}
+
+pub fn has_templates() -> bool {
+    !TEMPLATES.is_empty()
+}
+
+// SCAM_TEMPLATE_MIN_MATCH, closer matches are reported
+fn min_match_percent() -> f64 {
+    env::var("SCAM_TEMPLATE_MIN_MATCH")
+        .ok()
+        .and_then(|percent| percent.parse::<f64>().ok())
+        .filter(|percent| *percent > 0.0 && *percent <= 100.0)
+        .unwrap_or(DEFAULT_MIN_MATCH_PERCENT)
+}
+
+// the closest template of a verified source, None when none is close enough
+pub fn best_match(source: &str, contract_name: &str) -> Option<TemplateMatch> {
+    let signature = Signature::new(&normalize(source, Some(contract_name)))?;
+
+    TEMPLATES
+        .iter()
+        .map(|template| TemplateMatch {
+            family: template.family.clone(),
+            percent: template.signature.similarity_percent(&signature),
+        })
+        .filter(|found| found.percent >= min_match_percent())
+        .max_by(|a, b| a.percent.total_cmp(&b.percent))
+}
+
+// etherscan returns multi-file sources as the standard json input, wrapped in a second pair of braces
+fn flatten(source: &str) -> String {
+    let trimmed = source.trim();
+    let json = if trimmed.starts_with("{{") && trimmed.ends_with("}}") {
+        &trimmed[1..trimmed.len() - 1]
+    } else {
+        trimmed
+    };
+
+    match serde_json::from_str::<Value>(json) {
+        Ok(Value::Object(input)) => {
+            let sources = input
+                .get("sources")
+                .and_then(Value::as_object)
+                .unwrap_or(&input);
+            sources
+                .values()
+                .filter_map(|file| file.get("content")?.as_str())
+                .collect::<Vec<&str>>()
+                .join("\n")
+        }
+        _ => source.to_owned(),
+    }
+}
+
+// comments are dropped and string literals emptied
+fn strip_comments(source: &str) -> String {
+    let mut code = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let next = chars.peek().copied();
+        match (c, next) {
+            ('/', Some('/')) => {
+                chars.by_ref().find(|c| *c == '\n');
+                code.push('\n');
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                code.push(' ');
+            }
+            ('"' | '\'', _) => {
+                let mut escaped = false;
+                for next in chars.by_ref() {
+                    if next == c && !escaped {
+                        break;
+                    }
+                    escaped = next == '\\' && !escaped;
+                }
+                code.push_str("\"\"");
+            }
+            _ => code.push(c),
+        }
+    }
+
+    code
+}
+
+fn tokenize(code: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = code.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut token = String::from(c);
+        if c.is_ascii_alphanumeric() || c == '_' || c == '$' {
+            while let Some(next) =
+                chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '$')
+            {
+                token.push(next);
+            }
+        }
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+// the tokens of the contract with that name, or of the last one that isn't abstract
+fn main_contract<'a>(tokens: &'a [String], name: Option<&str>) -> &'a [String] {
+    let mut found = None;
+
+    for (i, window) in tokens.windows(2).enumerate() {
+        let is_abstract = i > 0 && tokens[i - 1] == "abstract";
+        if window[0] != "contract" || is_abstract {
+            continue;
+        }
+
+        let Some(open) = tokens[i..].iter().position(|token| token == "{") else {
+            continue;
+        };
+        let mut depth = 0;
+        let mut end = tokens.len();
+        for (j, token) in tokens.iter().enumerate().skip(i + open) {
+            match token.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => continue,
+            }
+            if depth == 0 {
+                end = j + 1;
+                break;
+            }
+        }
+
+        found = Some(&tokens[i..end]);
+        if name.is_some_and(|name| window[1] == name) {
+            break;
+        }
+    }
+
+    found.unwrap_or(tokens)
+}
+
+fn normalize(source: &str, contract_name: Option<&str>) -> Vec<String> {
+    let tokens = tokenize(&strip_comments(&flatten(source)));
+
+    main_contract(&tokens, contract_name)
+        .iter()
+        .map(|token| {
+            if token.starts_with(|c: char| c.is_ascii_digit()) {
+                String::from("0")
+            } else if token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+                && !KEYWORDS.contains(&token.as_str())
+                && !token.starts_with("uint")
+            {
+                String::from("$")
+            } else {
+                token.clone()
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_template_similarity() {
+    let template = r#"
+        // SPDX-License-Identifier: MIT
+        abstract contract Context { function _msgSender() internal view virtual returns (address) { return msg.sender; } }
+        contract PepeRug is Context {
+            mapping(address => bool) private _isBot;
+            uint256 private _sellTax = 99;
+            string private constant _name = "Pepe Rug";
+            function _transfer(address from, address to, uint256 amount) private {
+                require(!_isBot[from], "bot");
+                if (to == uniswapV2Pair) { amount = amount * (100 - _sellTax) / 100; }
+                _balances[from] -= amount;
+                _balances[to] += amount;
+            }
+            /* the owner flags every buyer */
+            function setBots(address[] memory bots) public onlyOwner {
+                for (uint i = 0; i < bots.length; i++) { _isBot[bots[i]] = true; }
+            }
+        }
+    "#;
+    let renamed = r#"
+        abstract contract Context { function _msgSender() internal view virtual returns (address) { return msg.sender; } }
+        contract MoonShot is Context {
+            mapping(address => bool) private blocked;
+            uint256 private fee = 95;
+            string private constant tokenName = "Moon Shot";
+            function _move(address a, address b, uint256 value) private {
+                require(!blocked[a], "no");
+                if (b == pair) { value = value * (100 - fee) / 100; }
+                balances[a] -= value;
+                balances[b] += value;
+            }
+            function addBlocked(address[] memory list) public onlyOwner {
+                for (uint i = 0; i < list.length; i++) { blocked[list[i]] = true; }
+            }
+        }
+    "#;
+    let unrelated = r#"
+        contract Vault {
+            event Deposited(address indexed account, uint256 amount);
+            function deposit() external payable { emit Deposited(msg.sender, msg.value); }
+            function withdraw(uint256 amount) external { payable(msg.sender).transfer(amount); }
+        }
+    "#;
+
+    let signature =
+        |source: &str, name: Option<&str>| Signature::new(&normalize(source, name)).unwrap();
+    let template = signature(template, None);
+    assert_eq!(
+        template.similarity_percent(&signature(renamed, Some("MoonShot"))),
+        100.0
+    );
+    assert!(template.similarity_percent(&signature(unrelated, Some("Vault"))) < 20.0);
+
+    // the standard json input of multi-file contracts
+    let json = format!(
+        "{{{}}}",
+        serde_json::json!({ "language": "Solidity", "sources": { "MoonShot.sol": { "content": renamed } } })
+    );
+    assert_eq!(flatten(&json), renamed);
+    assert_eq!(
+        TemplateMatch {
+            family: String::from("blacklist"),
+            percent: 93.75
+        }
+        .to_string(),
+        "Code 94% identical to the blacklist scam template"
+    );
+}
@@ -0,0 +1,321 @@
+use super::{
+    alchemy::{AlchemyAPI, AlchemySimulatedCall},
+    cache::{CacheStats, TtlCache},
+    WETH_ADDRESS,
+};
+use crate::{
+    crypto::abi::{self, AbiToken},
+    utils::uint_from_word,
+};
+use chrono::{Duration, Utc};
+use lazy_static::lazy_static;
+use serde_json::{json, Value};
+
+const UNISWAP_V2_ROUTER: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+// an address without history, funded with a state override so no real wallet is involved
+const SIMULATION_WALLET: &str = "0x5e1f5e1f5e1f5e1f5e1f5e1f5e1f5e1f5e1f5e1f";
+const SIMULATION_BALANCE_WEI: u128 = 1_000 * 10u128.pow(18);
+// a buy small enough that its price impact doesn't matter, the spot price
+const SPOT_WEI: u128 = 10u128.pow(12);
+
+pub const SIMULATED_SIZES_ETH: [f64; 3] = [0.01, 0.1, 0.5];
+
+lazy_static! {
+    // "token:block" -> the simulations of every size, a block is only simulated once per token.
+    // only kept until the next few blocks make them useless
+    static ref SIZE_TAXES: TtlCache<Vec<SizeSimulation>> =
+        TtlCache::new(Duration::try_minutes(1).unwrap());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeTax {
+    pub size_eth: f64,
+    // percentages of what the pool sent or would send, what the token contract kept
+    pub buy_tax: f64,
+    pub sell_tax: f64,
+    // how much worse than the spot price the buy gets from the pool
+    pub price_impact: f64,
+    // what's lost buying and selling right away, taxes, impact and pool fees together
+    pub round_trip_loss: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeSimulation {
+    Taxed(SizeTax),
+    // usually a max transaction or max wallet limit
+    BuyReverts,
+    NoV2Pool,
+}
+
+fn percent_lost(got: f64, expected: f64) -> f64 {
+    if expected <= 0.0 {
+        return 0.0;
+    }
+
+    ((1.0 - got / expected) * 100.0).max(0.0)
+}
+
+// amounts are in wei and token base units. a sell that reverts is a 100% sell tax
+fn size_tax(
+    size_wei: u128,
+    spot_tokens: f64,
+    expected_tokens: f64,
+    received_tokens: f64,
+    expected_wei_back: f64,
+    wei_back: f64,
+) -> SizeTax {
+    let spot_tokens_for_size = spot_tokens * (size_wei as f64 / SPOT_WEI as f64);
+
+    SizeTax {
+        size_eth: size_wei as f64 / 10.0f64.powi(18),
+        buy_tax: percent_lost(received_tokens, expected_tokens),
+        sell_tax: percent_lost(wei_back, expected_wei_back),
+        price_impact: percent_lost(expected_tokens, spot_tokens_for_size),
+        round_trip_loss: percent_lost(wei_back, size_wei as f64),
+    }
+}
+
+// the word of the return data, as f64 so amounts of huge supply tokens don't saturate
+fn word_f64(call: &AlchemySimulatedCall, index: usize) -> f64 {
+    let data = call.return_data.trim_start_matches("0x");
+
+    data.get(index * 64..(index + 1) * 64)
+        .map(|word| {
+            word.chars()
+                .filter_map(|c| c.to_digit(16))
+                .fold(0.0, |value, digit| value * 16.0 + digit as f64)
+        })
+        .unwrap_or_default()
+}
+
+fn word_u128(call: &AlchemySimulatedCall, index: usize) -> u128 {
+    let data = call.return_data.trim_start_matches("0x");
+
+    data.get(index * 64..(index + 1) * 64)
+        .map(uint_from_word)
+        .unwrap_or_default()
+}
+
+fn succeeded(call: Option<&AlchemySimulatedCall>) -> bool {
+    call.is_some_and(|call| call.status == "0x1")
+}
+
+fn call(to: &str, data: String, value: u128) -> Value {
+    json!({
+        "from": SIMULATION_WALLET,
+        "to": to,
+        "data": data,
+        "value": format!("{:#x}", value),
+    })
+}
+
+fn path(from: &str, to: &str) -> AbiToken {
+    AbiToken::Array(vec![
+        AbiToken::Address(from.to_owned()),
+        AbiToken::Address(to.to_owned()),
+    ])
+}
+
+fn get_amounts_out(amount: u128, path: AbiToken) -> Value {
+    call(
+        UNISWAP_V2_ROUTER,
+        abi::function_call(
+            "getAmountsOut(uint256,address[])",
+            &[AbiToken::Uint(amount), path],
+        ),
+        0,
+    )
+}
+
+fn balance_of(token: &str) -> Value {
+    call(
+        token,
+        abi::function_call(
+            "balanceOf(address)",
+            &[AbiToken::Address(SIMULATION_WALLET.to_owned())],
+        ),
+        0,
+    )
+}
+
+fn buy(token: &str, size_wei: u128, deadline: u128) -> Value {
+    call(
+        UNISWAP_V2_ROUTER,
+        abi::function_call(
+            "swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)",
+            &[
+                AbiToken::Uint(0),
+                path(WETH_ADDRESS, token),
+                AbiToken::Address(SIMULATION_WALLET.to_owned()),
+                AbiToken::Uint(deadline),
+            ],
+        ),
+        size_wei,
+    )
+}
+
+// sells into WETH instead of ETH, so what came back is a balanceOf call away
+fn sell(token: &str, amount: u128, deadline: u128) -> Value {
+    call(
+        UNISWAP_V2_ROUTER,
+        abi::function_call(
+            "swapExactTokensForTokensSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)",
+            &[
+                AbiToken::Uint(amount),
+                AbiToken::Uint(0),
+                path(token, WETH_ADDRESS),
+                AbiToken::Address(SIMULATION_WALLET.to_owned()),
+                AbiToken::Uint(deadline),
+            ],
+        ),
+        0,
+    )
+}
+
+async fn simulate(calls: Vec<Value>) -> Result<Vec<AlchemySimulatedCall>, reqwest::Error> {
+    let state_overrides = json!({
+        SIMULATION_WALLET: { "balance": format!("{:#x}", SIMULATION_BALANCE_WEI) }
+    });
+
+    match AlchemyAPI::<Vec<Value>>::simulate_calls(state_overrides, calls).await {
+        Ok(response) => Ok(response
+            .result
+            .into_iter()
+            .flat_map(|block| block.calls)
+            .collect()),
+        Err(e) => Err(e.without_url()),
+    }
+}
+
+/*
+
+The calls can't use each other's results, so the buy is simulated twice: the
+first run finds out how many tokens it gets, the second one sells exactly
+those right after buying them.
+
+*/
+async fn simulate_size(token: &str, size_wei: u128) -> Result<SizeSimulation, reqwest::Error> {
+    let deadline = (Utc::now().timestamp() + 3600) as u128;
+
+    let bought = simulate(vec![
+        get_amounts_out(SPOT_WEI, path(WETH_ADDRESS, token)),
+        get_amounts_out(size_wei, path(WETH_ADDRESS, token)),
+        buy(token, size_wei, deadline),
+        balance_of(token),
+    ])
+    .await?;
+    if !succeeded(bought.get(1)) {
+        return Ok(SizeSimulation::NoV2Pool);
+    }
+    if !succeeded(bought.get(2)) || !succeeded(bought.get(3)) {
+        return Ok(SizeSimulation::BuyReverts);
+    }
+
+    // getAmountsOut returns (offset, length, amount in, amount out)
+    let spot_tokens = word_f64(&bought[0], 3);
+    let expected_tokens = word_f64(&bought[1], 3);
+    let received_tokens = word_f64(&bought[3], 0);
+    let received = word_u128(&bought[3], 0);
+
+    let sold = simulate(vec![
+        buy(token, size_wei, deadline),
+        call(
+            token,
+            abi::function_call(
+                "approve(address,uint256)",
+                &[
+                    AbiToken::Address(UNISWAP_V2_ROUTER.to_owned()),
+                    AbiToken::Uint(u128::MAX),
+                ],
+            ),
+            0,
+        ),
+        get_amounts_out(received, path(token, WETH_ADDRESS)),
+        sell(token, received, deadline),
+        balance_of(WETH_ADDRESS),
+    ])
+    .await?;
+
+    let expected_wei_back = if succeeded(sold.get(2)) {
+        word_f64(&sold[2], 3)
+    } else {
+        0.0
+    };
+    let wei_back = if succeeded(sold.get(3)) && succeeded(sold.get(4)) {
+        word_f64(&sold[4], 0)
+    } else {
+        0.0
+    };
+
+    Ok(SizeSimulation::Taxed(size_tax(
+        size_wei,
+        spot_tokens,
+        expected_tokens,
+        received_tokens,
+        // a sell that reverts loses everything
+        expected_wei_back.max(1.0),
+        wei_back,
+    )))
+}
+
+pub async fn size_taxes_cache_stats() -> CacheStats {
+    SIZE_TAXES.stats().await
+}
+
+// buys and sells of every simulated size through the Uniswap V2 WETH pool, against the latest
+// block. without the block number nothing is cached, failed simulations are never cached
+pub async fn get_size_taxes(token: String) -> Vec<(f64, Result<SizeSimulation, reqwest::Error>)> {
+    let key = match AlchemyAPI::<String>::get_block_number().await {
+        Ok(block) => Some(format!("{}:{}", token, block.result)),
+        Err(e) => {
+            error!("get_block_number error: {}", e.without_url());
+            None
+        }
+    };
+    if let Some(key) = &key {
+        if let Some(cached) = SIZE_TAXES.get(key).await {
+            return SIMULATED_SIZES_ETH
+                .iter()
+                .copied()
+                .zip(cached.into_iter().map(Ok))
+                .collect();
+        }
+    }
+
+    let simulations = SIMULATED_SIZES_ETH
+        .iter()
+        .map(|size| simulate_size(&token, (size * 10.0f64.powi(18)) as u128));
+    let simulations: Vec<(f64, Result<SizeSimulation, reqwest::Error>)> = SIMULATED_SIZES_ETH
+        .iter()
+        .copied()
+        .zip(futures_util::future::join_all(simulations).await)
+        .collect();
+
+    let succeeded: Option<Vec<SizeSimulation>> = simulations
+        .iter()
+        .map(|(_, simulation)| simulation.as_ref().ok().copied())
+        .collect();
+    if let (Some(key), Some(succeeded)) = (key, succeeded) {
+        SIZE_TAXES.insert(&key, succeeded).await;
+    }
+
+    simulations
+}
+
+#[test]
+fn test_size_tax() {
+    let eth = 10u128.pow(18);
+
+    // 0.1 eth buys 900 tokens at spot, the pool sends 880 and the token keeps 5%
+    let tax = size_tax(eth / 10, 0.009, 880.0, 836.0, 0.09e18, 0.0855e18);
+    assert_eq!(tax.size_eth, 0.1);
+    assert!((tax.buy_tax - 5.0).abs() < 1e-9);
+    assert!((tax.sell_tax - 5.0).abs() < 1e-9);
+    assert!((tax.price_impact - 2.222).abs() < 0.001);
+    assert!((tax.round_trip_loss - 14.5).abs() < 1e-9);
+
+    // a sell that reverts
+    let tax = size_tax(eth, 1.0, 1.0, 1.0, 1.0, 0.0);
+    assert_eq!(tax.sell_tax, 100.0);
+    assert_eq!(tax.round_trip_loss, 100.0);
+}
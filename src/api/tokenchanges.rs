@@ -0,0 +1,215 @@
+use core::fmt;
+use std::env;
+
+// taxes moving by less than this many percentage points are rounding noise between simulations
+const MIN_TAX_CHANGE_POINTS: f64 = 1.0;
+const DEFAULT_LIQUIDITY_CHANGE_PERCENT: f64 = 25.0;
+const MIN_CONCENTRATION_CHANGE_POINTS: f64 = 5.0;
+pub const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+// what the token looked like in one watch cycle, None for what couldn't be read
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenReadings {
+    // (buy, sell) in percent
+    pub taxes: Option<(f64, f64)>,
+    // lowercase, the zero address once ownership is renounced
+    pub owner: Option<String>,
+    pub liquidity_usd: Option<f64>,
+    pub top10_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenChange {
+    Taxes {
+        before: (f64, f64),
+        after: (f64, f64),
+    },
+    Ownership {
+        from: String,
+        to: String,
+    },
+    Liquidity {
+        before: f64,
+        after: f64,
+    },
+    Concentration {
+        before: f64,
+        after: f64,
+    },
+}
+
+impl fmt::Display for TokenChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenChange::Taxes { before, after } => write!(
+                f,
+                "💸 Taxes changed\nBuy {:.1}% ➡️ {:.1}%\nSell {:.1}% ➡️ {:.1}%",
+                before.0, after.0, before.1, after.1
+            ),
+            TokenChange::Ownership { from, to } if to == ZERO_ADDRESS => {
+                write!(f, "👑 Ownership renounced by {}", from)
+            }
+            TokenChange::Ownership { from, to } => {
+                write!(f, "👑 Ownership transferred\n{} ➡️ {}", from, to)
+            }
+            TokenChange::Liquidity { before, after } => write!(
+                f,
+                "💧 Liquidity {} {:.0}%\n${:.0} ➡️ ${:.0}",
+                if after > before { "up" } else { "down" },
+                percent_change(*before, *after).abs(),
+                before,
+                after
+            ),
+            TokenChange::Concentration { before, after } => write!(
+                f,
+                "🐳 Top 10 holders now hold {:.1}% of the supply, {:.1}% before",
+                after, before
+            ),
+        }
+    }
+}
+
+fn percent_change(before: f64, after: f64) -> f64 {
+    (after - before) / before * 100.0
+}
+
+// TOKEN_LIQUIDITY_CHANGE_PERCENT, smaller moves of a monitored token's liquidity aren't alerted
+pub fn liquidity_change_percent() -> f64 {
+    env::var("TOKEN_LIQUIDITY_CHANGE_PERCENT")
+        .ok()
+        .and_then(|percent| percent.parse::<f64>().ok())
+        .filter(|percent| *percent > 0.0)
+        .unwrap_or(DEFAULT_LIQUIDITY_CHANGE_PERCENT)
+}
+
+/*
+
+The readings of a monitored token are compared with the ones it had when it
+was last alerted, not with the previous cycle, so a liquidity pulled a little
+every few minutes adds up to an alert too. A reading that couldn't be taken
+keeps the last known one.
+
+*/
+impl TokenReadings {
+    pub fn update(&mut self, current: TokenReadings) -> Vec<TokenChange> {
+        let mut changes = vec![];
+
+        match (self.taxes, current.taxes) {
+            (Some(before), Some(after))
+                if (after.0 - before.0).abs() >= MIN_TAX_CHANGE_POINTS
+                    || (after.1 - before.1).abs() >= MIN_TAX_CHANGE_POINTS =>
+            {
+                changes.push(TokenChange::Taxes { before, after });
+                self.taxes = Some(after);
+            }
+            (None, Some(after)) => self.taxes = Some(after),
+            _ => {}
+        }
+
+        match (&self.owner, current.owner) {
+            (Some(from), Some(to)) if *from != to => {
+                changes.push(TokenChange::Ownership {
+                    from: from.clone(),
+                    to: to.clone(),
+                });
+                self.owner = Some(to);
+            }
+            (None, Some(to)) => self.owner = Some(to),
+            _ => {}
+        }
+
+        match (self.liquidity_usd, current.liquidity_usd) {
+            (Some(before), Some(after)) if before > 0.0 => {
+                if percent_change(before, after).abs() >= liquidity_change_percent() {
+                    changes.push(TokenChange::Liquidity { before, after });
+                    self.liquidity_usd = Some(after);
+                }
+            }
+            // no liquidity yet is no baseline either
+            (_, Some(after)) => self.liquidity_usd = Some(after),
+            _ => {}
+        }
+
+        match (self.top10_percent, current.top10_percent) {
+            (Some(before), Some(after))
+                if (after - before).abs() >= MIN_CONCENTRATION_CHANGE_POINTS =>
+            {
+                changes.push(TokenChange::Concentration { before, after });
+                self.top10_percent = Some(after);
+            }
+            (None, Some(after)) => self.top10_percent = Some(after),
+            _ => {}
+        }
+
+        changes
+    }
+}
+
+#[test]
+fn test_token_changes() {
+    let readings = |buy: f64, owner: &str, liquidity_usd: f64, top10_percent: f64| TokenReadings {
+        taxes: Some((buy, 5.0)),
+        owner: Some(owner.to_owned()),
+        liquidity_usd: Some(liquidity_usd),
+        top10_percent: Some(top10_percent),
+    };
+    let mut baseline = TokenReadings::default();
+
+    assert!(baseline
+        .update(readings(5.0, "0xdev", 100_000.0, 40.0))
+        .is_empty());
+    assert!(baseline
+        .update(readings(5.4, "0xdev", 90_000.0, 43.0))
+        .is_empty());
+
+    // 10% twice is 19%, then the third pull crosses 25% of the liquidity last alerted
+    assert!(baseline
+        .update(readings(5.0, "0xdev", 81_000.0, 40.0))
+        .is_empty());
+    assert_eq!(
+        baseline.update(readings(5.0, "0xdev", 72_900.0, 40.0)),
+        vec![TokenChange::Liquidity {
+            before: 100_000.0,
+            after: 72_900.0
+        }]
+    );
+
+    let failed = TokenReadings {
+        taxes: Some((25.0, 5.0)),
+        ..TokenReadings::default()
+    };
+    assert_eq!(
+        baseline.update(failed),
+        vec![TokenChange::Taxes {
+            before: (5.0, 5.0),
+            after: (25.0, 5.0)
+        }]
+    );
+
+    let changes = baseline.update(readings(25.0, "0xnew", 72_900.0, 52.0));
+    assert_eq!(
+        changes,
+        vec![
+            TokenChange::Ownership {
+                from: String::from("0xdev"),
+                to: String::from("0xnew")
+            },
+            TokenChange::Concentration {
+                before: 40.0,
+                after: 52.0
+            }
+        ]
+    );
+    assert_eq!(
+        changes[1].to_string(),
+        "🐳 Top 10 holders now hold 52.0% of the supply, 40.0% before"
+    );
+    assert_eq!(
+        TokenChange::Liquidity {
+            before: 100_000.0,
+            after: 72_900.0
+        }
+        .to_string(),
+        "💧 Liquidity down 27%\n$100000 ➡️ $72900"
+    );
+}
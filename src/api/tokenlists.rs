@@ -0,0 +1,74 @@
+use super::providers;
+use serde::Deserialize;
+
+// mainnet entries are the only ones the bot can trade
+const ETHEREUM_CHAIN_ID: u64 = 1;
+
+// https://tokenlists.org format, the one Uniswap and most wallets use
+#[derive(Debug, Deserialize)]
+pub struct TokenList {
+    pub name: String,
+    pub tokens: Vec<TokenListEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenListEntry {
+    pub chain_id: u64,
+    pub address: String,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    #[serde(rename = "logoURI")]
+    pub logo_uri: Option<String>,
+}
+
+impl TokenList {
+    pub fn ethereum_tokens(self) -> Vec<TokenListEntry> {
+        self.tokens
+            .into_iter()
+            .filter(|token| token.chain_id == ETHEREUM_CHAIN_ID)
+            .collect()
+    }
+}
+
+pub async fn get_token_list(url: &str) -> Result<TokenList, reqwest::Error> {
+    providers::client().get(url).send().await?.json().await
+}
+
+#[test]
+fn test_token_list_format() {
+    let list: TokenList = serde_json::from_str(
+        r#"{
+            "name": "Test List",
+            "timestamp": "2024-01-01T00:00:00.000Z",
+            "version": { "major": 1, "minor": 0, "patch": 0 },
+            "tokens": [
+                {
+                    "chainId": 1,
+                    "address": "0x6982508145454ce325ddbe47a25d4ec3d2311933",
+                    "name": "Pepe",
+                    "symbol": "PEPE",
+                    "decimals": 18,
+                    "logoURI": "https://example.com/pepe.png"
+                },
+                {
+                    "chainId": 56,
+                    "address": "0x25d887ce7a35172c62febfd67a1856f20faebb00",
+                    "name": "Pepe",
+                    "symbol": "PEPE",
+                    "decimals": 18
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(list.name, "Test List");
+    let tokens = list.ethereum_tokens();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(
+        tokens[0].logo_uri.as_deref(),
+        Some("https://example.com/pepe.png")
+    );
+}
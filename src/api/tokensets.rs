@@ -0,0 +1,162 @@
+use super::etherscan::EtherscanTokenTransaction;
+use crate::persist;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use tokio::sync::Mutex;
+
+// first buys remembered for the other chats watching the wallet, they're checked in the same cycle
+const MAX_FIRST_BUYS: usize = 1_000;
+
+lazy_static! {
+    static ref TOKEN_SETS: Mutex<TokenSets> =
+        Mutex::new(persist::load(persist::WALLET_TOKENS).unwrap_or_default());
+}
+
+/*
+
+Every watched wallet has the set of tokens it ever held, seeded from its
+transfer history the first time it receives a token and saved to
+WALLET_TOKENS_FILE. A token coming in that isn't in the set is a first buy,
+what copy traders follow, anything else adds to a bag the wallet already had.
+
+Etherscan only returns the latest transfers, so a token held long before the
+wallet was watched can still show up as a first buy once.
+
+*/
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct TokenSets {
+    // lowercase wallet -> lowercase contracts
+    wallets: HashMap<String, HashSet<String>>,
+    // (wallet, transaction hash, contract) of the latest first buys, a wallet watched by several
+    // chats is checked once per chat and every one of them gets the same answer
+    #[serde(skip)]
+    first_buys: VecDeque<(String, String, String)>,
+}
+
+impl TokenSets {
+    fn is_seeded(&self, wallet: &str) -> bool {
+        self.wallets.contains_key(&wallet.to_lowercase())
+    }
+
+    // the transfers from `before` on are the new ones, they're checked against the set instead
+    fn seed(&mut self, wallet: &str, history: &[EtherscanTokenTransaction], before: u64) {
+        let tokens = history
+            .iter()
+            .filter(|t| t.time_stamp.parse::<u64>().unwrap_or(0) < before)
+            .map(|t| t.contract_address.to_lowercase())
+            .collect();
+
+        self.wallets.insert(wallet.to_lowercase(), tokens);
+    }
+
+    // true when the token wasn't in the wallet's set yet, only seed() starts a wallet's set
+    fn add(&mut self, wallet: &str, contract: &str) -> bool {
+        self.wallets
+            .get_mut(&wallet.to_lowercase())
+            .is_some_and(|tokens| tokens.insert(contract.to_lowercase()))
+    }
+
+    // (whether the transfer brings in a token the wallet never held, whether the set changed)
+    fn saw(&mut self, wallet: &str, transaction: &EtherscanTokenTransaction) -> (bool, bool) {
+        let key = (
+            wallet.to_lowercase(),
+            transaction.hash.to_lowercase(),
+            transaction.contract_address.to_lowercase(),
+        );
+        let added = self.add(wallet, &transaction.contract_address);
+
+        if added && transaction.to.to_lowercase() == wallet.to_lowercase() {
+            if self.first_buys.len() == MAX_FIRST_BUYS {
+                self.first_buys.pop_front();
+            }
+            self.first_buys.push_back(key);
+            return (true, added);
+        }
+        (self.first_buys.contains(&key), added)
+    }
+}
+
+pub async fn is_seeded(wallet: &str) -> bool {
+    TOKEN_SETS.lock().await.is_seeded(wallet)
+}
+
+pub async fn seed(wallet: &str, history: &[EtherscanTokenTransaction], before: u64) {
+    let mut sets = TOKEN_SETS.lock().await;
+    sets.seed(wallet, history, before);
+    persist::save(persist::WALLET_TOKENS, &*sets);
+}
+
+// the set only changes with tokens the wallet never held, it's only saved then
+pub async fn saw(wallet: &str, transaction: &EtherscanTokenTransaction) -> bool {
+    let mut sets = TOKEN_SETS.lock().await;
+    let (first_buy, added) = sets.saw(wallet, transaction);
+
+    if added {
+        persist::save(persist::WALLET_TOKENS, &*sets);
+    }
+    first_buy
+}
+
+#[test]
+fn test_first_buys() {
+    let transfer = |hash: &str, contract: &str, from: &str, to: &str, time_stamp: u64| {
+        EtherscanTokenTransaction {
+            hash: hash.to_owned(),
+            contract_address: contract.to_owned(),
+            from: from.to_owned(),
+            to: to.to_owned(),
+            time_stamp: time_stamp.to_string(),
+            ..EtherscanTokenTransaction::default()
+        }
+    };
+    let wallet = "0xWALLET";
+    let mut sets = TokenSets::default();
+
+    // a wallet that was never seeded isn't tracked, seeding it later still reads its history
+    let early = transfer("0x0", "0xNEW", "0xpool", "0xwallet", 50);
+    assert_eq!(sets.saw(wallet, &early), (false, false));
+    assert!(!sets.is_seeded(wallet));
+
+    let new_token = transfer("0x2", "0xNEW", "0xpool", "0xwallet", 200);
+    sets.seed(
+        wallet,
+        &[
+            transfer("0x1", "0xPEPE", "0xpool", "0xwallet", 100),
+            new_token.clone(),
+        ],
+        200,
+    );
+    assert!(sets.is_seeded("0xwallet"));
+
+    // the transfer of the seeding cycle isn't part of the history, and another chat
+    // watching the wallet gets the same answer for it
+    assert_eq!(sets.saw(wallet, &new_token), (true, true));
+    assert_eq!(sets.saw(wallet, &new_token), (true, false));
+
+    let adds = transfer("0x3", "0xnew", "0xpool", "0xwallet", 300);
+    assert_eq!(sets.saw(wallet, &adds), (false, false));
+    let pepe = transfer("0x4", "0xpepe", "0xpool", "0xwallet", 300);
+    assert_eq!(sets.saw(wallet, &pepe), (false, false));
+
+    // a token sent out first was held before it came back
+    let sent = transfer("0x5", "0xgift", "0xwallet", "0xfriend", 400);
+    assert_eq!(sets.saw(wallet, &sent), (false, true));
+    let returned = transfer("0x6", "0xgift", "0xfriend", "0xwallet", 500);
+    assert_eq!(sets.saw(wallet, &returned), (false, false));
+
+    for i in 0..MAX_FIRST_BUYS {
+        sets.saw(
+            wallet,
+            &transfer(
+                &format!("0x{}", i),
+                &format!("0xtoken{}", i),
+                "0xpool",
+                "0xwallet",
+                600,
+            ),
+        );
+    }
+    assert_eq!(sets.first_buys.len(), MAX_FIRST_BUYS);
+    assert_eq!(sets.saw(wallet, &new_token), (false, false));
+}
@@ -0,0 +1,135 @@
+use super::providers::Provider;
+use crate::persist;
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+lazy_static! {
+    static ref USAGE: Mutex<UsageFile> =
+        Mutex::new(persist::load(persist::API_USAGE).unwrap_or_default());
+}
+
+// request counts of a provider in the current UTC day and month
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Counter {
+    day: String,
+    daily: u64,
+    month: String,
+    monthly: u64,
+}
+
+impl Counter {
+    // rolls the counts over when the day or month changed since the last request
+    fn current(&self, now: DateTime<Utc>) -> Counter {
+        let day = now.format("%Y-%m-%d").to_string();
+        let month = now.format("%Y-%m").to_string();
+
+        Counter {
+            daily: if self.day == day { self.daily } else { 0 },
+            monthly: if self.month == month { self.monthly } else { 0 },
+            day,
+            month,
+        }
+    }
+
+    fn record(&mut self, now: DateTime<Utc>) {
+        *self = self.current(now);
+        self.daily += 1;
+        self.monthly += 1;
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageFile {
+    providers: HashMap<String, Counter>,
+    #[serde(skip)]
+    saved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProviderUsage {
+    pub provider: Provider,
+    pub daily: u64,
+    pub monthly: u64,
+    pub daily_limit: Option<u64>,
+    pub monthly_limit: Option<u64>,
+}
+
+impl ProviderUsage {
+    pub fn daily_percent(&self) -> Option<f64> {
+        self.daily_limit
+            .filter(|limit| *limit > 0)
+            .map(|limit| self.daily as f64 / limit as f64 * 100.0)
+    }
+
+    pub fn monthly_percent(&self) -> Option<f64> {
+        self.monthly_limit
+            .filter(|limit| *limit > 0)
+            .map(|limit| self.monthly as f64 / limit as f64 * 100.0)
+    }
+}
+
+// writing the file on every request would be wasteful, so at most a minute of requests is lost on a crash
+pub async fn record(provider: Provider) {
+    let now = Utc::now();
+    let mut usage = USAGE.lock().await;
+
+    usage
+        .providers
+        .entry(provider.to_string())
+        .or_default()
+        .record(now);
+
+    if usage.saved_at.map_or(true, |saved_at| {
+        now - saved_at >= Duration::try_minutes(1).unwrap()
+    }) {
+        usage.saved_at = Some(now);
+        persist::save(persist::API_USAGE, &*usage);
+    }
+}
+
+pub async fn usage() -> Vec<ProviderUsage> {
+    let now = Utc::now();
+    let usage = USAGE.lock().await;
+
+    Provider::all()
+        .into_iter()
+        .map(|provider| {
+            let counter = usage
+                .providers
+                .get(&provider.to_string())
+                .map(|counter| counter.current(now))
+                .unwrap_or_default();
+
+            ProviderUsage {
+                provider,
+                daily: counter.daily,
+                monthly: counter.monthly,
+                daily_limit: provider.daily_limit(),
+                monthly_limit: provider.monthly_limit(),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_counter_rolls_over() {
+    let day = |date: &str| {
+        DateTime::parse_from_rfc3339(&format!("{}T12:00:00Z", date))
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    let mut counter = Counter::default();
+
+    counter.record(day("2024-03-30"));
+    counter.record(day("2024-03-30"));
+    assert_eq!((counter.daily, counter.monthly), (2, 2));
+
+    counter.record(day("2024-03-31"));
+    assert_eq!((counter.daily, counter.monthly), (1, 3));
+
+    let april = counter.current(day("2024-04-01"));
+    assert_eq!((april.daily, april.monthly), (0, 0));
+}
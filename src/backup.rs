@@ -0,0 +1,464 @@
+use crate::{api, crypto::abi, persist, tasks};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use chrono::{DateTime, Duration, Utc};
+use core::fmt;
+use hmac::{Hmac, Mac};
+use reqwest::{Method, Url};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+use tokio::time::sleep;
+
+const BACKUP_PREFIX: &str = "snipers-backup-";
+const BACKUP_EXTENSION: &str = ".bak";
+const DEFAULT_BACKUP_DIR: &str = "backups";
+const DEFAULT_KEEP: usize = 7;
+// UTC hour of the daily backup
+const DEFAULT_HOUR: u32 = 3;
+
+/*
+
+The state files are backed up once a day into one archive, encrypted with
+ChaCha20-Poly1305 under BACKUP_KEY, and written to BACKUP_DIR or to an S3
+compatible bucket when BACKUP_S3_BUCKET is set. Only the newest BACKUP_KEEP
+backups are kept. The archive maps every file to the env var that points at
+it, so `--restore` writes each file where the restoring run expects it, even
+if the paths changed since.
+
+Settings and watchlists live in memory and aren't part of the backups.
+
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    pub name: String,
+    pub files: Vec<String>,
+    pub destination: String,
+}
+
+// 32 bytes as 64 hex characters, e.g. from `openssl rand -hex 32`
+fn key() -> Option<[u8; 32]> {
+    let key = env::var("BACKUP_KEY").ok()?;
+    let key = key.trim().trim_start_matches("0x");
+
+    if key.len() != 64 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    abi::from_hex(key).try_into().ok()
+}
+
+pub fn enabled() -> bool {
+    key().is_some()
+}
+
+fn keep() -> usize {
+    env::var("BACKUP_KEEP")
+        .ok()
+        .and_then(|keep| keep.parse::<usize>().ok())
+        .filter(|keep| *keep > 0)
+        .unwrap_or(DEFAULT_KEEP)
+}
+
+fn hour() -> u32 {
+    env::var("BACKUP_HOUR")
+        .ok()
+        .and_then(|hour| hour.parse::<u32>().ok())
+        .filter(|hour| *hour < 24)
+        .unwrap_or(DEFAULT_HOUR)
+}
+
+// the archive of the state files that exist, keyed by their env var
+fn archive_state_files() -> BTreeMap<String, String> {
+    persist::STATE_FILES
+        .iter()
+        .filter_map(|file| {
+            fs::read_to_string(file.path())
+                .ok()
+                .map(|content| (file.env_var.to_string(), content))
+        })
+        .collect()
+}
+
+// the nonce followed by the ciphertext
+fn seal(key: &[u8; 32], archive: &BTreeMap<String, String>) -> Vec<u8> {
+    let nonce: [u8; 12] = rand::random();
+    let plaintext = serde_json::to_vec(archive).expect("a map of strings always serializes");
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .expect("chacha20poly1305 encryption doesn't fail for in-memory archives");
+
+    [nonce.to_vec(), ciphertext].concat()
+}
+
+fn open(key: &[u8; 32], sealed: &[u8]) -> Result<BTreeMap<String, String>, String> {
+    if sealed.len() < 12 {
+        return Err(String::from("the backup is truncated"));
+    }
+
+    let plaintext = ChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(Nonce::from_slice(&sealed[..12]), &sealed[12..])
+        .map_err(|_| String::from("the backup can't be decrypted, is BACKUP_KEY the same?"))?;
+    serde_json::from_slice(&plaintext).map_err(|e| e.to_string())
+}
+
+fn backup_name(now: DateTime<Utc>) -> String {
+    format!(
+        "{}{}{}",
+        BACKUP_PREFIX,
+        now.format("%Y%m%d-%H%M%S"),
+        BACKUP_EXTENSION
+    )
+}
+
+fn is_backup_name(name: &str) -> bool {
+    name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_EXTENSION)
+}
+
+// the names sort by date, everything but the newest `keep` backups
+fn expired(names: &[String], keep: usize) -> Vec<String> {
+    let mut names: Vec<String> = names.to_vec();
+    names.sort();
+
+    let expired = names.len().saturating_sub(keep);
+    names.truncate(expired);
+    names
+}
+
+// how long until the UTC clock next hits the hour, the daily digest is scheduled with it too
+pub fn until_next(now: DateTime<Utc>, hour: u32) -> Duration {
+    let today = now.date_naive().and_hms_opt(hour, 0, 0).unwrap().and_utc();
+    let next = if today > now {
+        today
+    } else {
+        today + Duration::try_days(1).unwrap()
+    };
+
+    next - now
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(message);
+
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[derive(Debug, Clone)]
+struct S3Bucket {
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Bucket {
+    fn from_env() -> Option<Result<S3Bucket, String>> {
+        let bucket = env::var("BACKUP_S3_BUCKET")
+            .ok()
+            .filter(|bucket| !bucket.is_empty())?;
+        let endpoint = env::var("BACKUP_S3_ENDPOINT")
+            .unwrap_or(String::from("https://s3.amazonaws.com"))
+            .parse::<Url>()
+            .map_err(|e| format!("invalid BACKUP_S3_ENDPOINT: {}", e));
+
+        Some(endpoint.and_then(|endpoint| {
+            Ok(S3Bucket {
+                endpoint,
+                bucket,
+                region: env::var("BACKUP_S3_REGION").unwrap_or(String::from("us-east-1")),
+                access_key: env::var("BACKUP_S3_ACCESS_KEY")
+                    .map_err(|_| String::from("BACKUP_S3_ACCESS_KEY is not set"))?,
+                secret_key: env::var("BACKUP_S3_SECRET_KEY")
+                    .map_err(|_| String::from("BACKUP_S3_SECRET_KEY is not set"))?,
+            })
+        }))
+    }
+
+    /*
+
+    Requests are signed with AWS Signature Version 4, which every S3 compatible
+    storage (R2, B2, MinIO, Spaces...) accepts. Objects are addressed path
+    style, `<endpoint>/<bucket>/<name>`, the backup names need no escaping.
+
+    */
+    async fn request(
+        &self,
+        method: Method,
+        name: &str,
+        query: &str,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, String> {
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let host = match (self.endpoint.host_str(), self.endpoint.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => return Err(String::from("BACKUP_S3_ENDPOINT has no host")),
+        };
+        let path = format!("/{}/{}", self.bucket, name);
+        let payload_hash = hex::encode(&Sha256::digest(&body));
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+            method, path, query, host, payload_hash, amz_date, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac_sha256(
+                format!("AWS4{}", self.secret_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+        let signature = hex::encode(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+        url.set_query(Some(query).filter(|query| !query.is_empty()));
+
+        // archives can be large, they get longer than the providers' timeout
+        api::client()
+            .request(method, url)
+            .timeout(std::time::Duration::from_secs(60))
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header(
+                "Authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={}",
+                    self.access_key, scope, signature
+                ),
+            )
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.without_url().to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Destination {
+    Local(PathBuf),
+    S3(S3Bucket),
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Destination::Local(dir) => write!(f, "{}", dir.display()),
+            Destination::S3(bucket) => write!(f, "s3://{}", bucket.bucket),
+        }
+    }
+}
+
+impl Destination {
+    fn from_env() -> Result<Destination, String> {
+        match S3Bucket::from_env() {
+            Some(bucket) => bucket.map(Destination::S3),
+            None => Ok(Destination::Local(PathBuf::from(
+                env::var("BACKUP_DIR").unwrap_or(String::from(DEFAULT_BACKUP_DIR)),
+            ))),
+        }
+    }
+
+    async fn put(&self, name: &str, bytes: Vec<u8>) -> Result<(), String> {
+        match self {
+            Destination::Local(dir) => fs::create_dir_all(dir)
+                .and_then(|_| fs::write(dir.join(name), bytes))
+                .map_err(|e| e.to_string()),
+            Destination::S3(bucket) => bucket
+                .request(Method::PUT, name, "", bytes)
+                .await
+                .map(|_| ()),
+        }
+    }
+
+    async fn get(&self, name: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Destination::Local(dir) => fs::read(dir.join(name)).map_err(|e| e.to_string()),
+            Destination::S3(bucket) => bucket
+                .request(Method::GET, name, "", vec![])
+                .await?
+                .bytes()
+                .await
+                .map(|bytes| bytes.to_vec())
+                .map_err(|e| e.without_url().to_string()),
+        }
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), String> {
+        match self {
+            Destination::Local(dir) => fs::remove_file(dir.join(name)).map_err(|e| e.to_string()),
+            Destination::S3(bucket) => bucket
+                .request(Method::DELETE, name, "", vec![])
+                .await
+                .map(|_| ()),
+        }
+    }
+
+    // backup names, oldest first
+    async fn list(&self) -> Result<Vec<String>, String> {
+        let mut names: Vec<String> = match self {
+            Destination::Local(dir) => match fs::read_dir(dir) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .collect(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+                Err(e) => return Err(e.to_string()),
+            },
+            // ListObjectsV2 answers with XML, only the keys are needed
+            Destination::S3(bucket) => bucket
+                .request(
+                    Method::GET,
+                    "",
+                    &format!("list-type=2&prefix={}", BACKUP_PREFIX),
+                    vec![],
+                )
+                .await?
+                .text()
+                .await
+                .map_err(|e| e.without_url().to_string())?
+                .split("<Key>")
+                .skip(1)
+                .filter_map(|part| part.split("</Key>").next())
+                .map(String::from)
+                .collect(),
+        };
+
+        names.retain(|name| is_backup_name(name));
+        names.sort();
+        Ok(names)
+    }
+}
+
+pub async fn list_backups() -> Result<(String, Vec<String>), String> {
+    let destination = Destination::from_env()?;
+    let names = destination.list().await?;
+
+    Ok((destination.to_string(), names))
+}
+
+pub async fn backup_now() -> Result<Backup, String> {
+    let key = key().ok_or("BACKUP_KEY must be set to 64 hex characters")?;
+    let destination = Destination::from_env()?;
+    let archive = archive_state_files();
+    let name = backup_name(Utc::now());
+
+    destination.put(&name, seal(&key, &archive)).await?;
+    info!("Backed up {} state files to {}", archive.len(), name);
+
+    // a failed cleanup leaves an extra backup, the new one is still there
+    match destination.list().await {
+        Ok(names) => {
+            for name in expired(&names, keep()) {
+                if let Err(e) = destination.delete(&name).await {
+                    warn!("failed to delete the expired backup {}: {}", name, e);
+                }
+            }
+        }
+        Err(e) => warn!("failed to list the backups for the retention: {}", e),
+    }
+
+    Ok(Backup {
+        name,
+        files: archive.into_keys().collect(),
+        destination: destination.to_string(),
+    })
+}
+
+// 'latest' or a backup name, returns the restored paths. run before anything loads the files
+pub async fn restore(backup: &str) -> Result<Vec<String>, String> {
+    let key = key().ok_or("BACKUP_KEY must be set to 64 hex characters")?;
+    let destination = Destination::from_env()?;
+    let name = match backup {
+        "latest" => destination
+            .list()
+            .await?
+            .pop()
+            .ok_or(format!("there are no backups in {}", destination))?,
+        name => name.to_owned(),
+    };
+
+    let archive = open(&key, &destination.get(&name).await?)?;
+    let mut restored = vec![];
+    for file in persist::STATE_FILES {
+        let Some(content) = archive.get(file.env_var) else {
+            continue;
+        };
+        let path = file.path();
+
+        // the file being replaced is kept next to it, in case the wrong backup was picked
+        if fs::metadata(&path).is_ok() {
+            fs::rename(&path, format!("{}.pre-restore", path)).map_err(|e| e.to_string())?;
+        }
+        persist::write_atomically(Path::new(&path), content.clone())?;
+        restored.push(path);
+    }
+
+    info!("Restored {} state files from {}", restored.len(), name);
+    Ok(restored)
+}
+
+pub async fn scheduled_backups() {
+    loop {
+        sleep(until_next(Utc::now(), hour()).to_std().unwrap()).await;
+        tasks::heartbeat("backups").await;
+
+        if let Err(e) = backup_now().await {
+            error!("scheduled backup failed: {}", e);
+        }
+    }
+}
+
+#[test]
+fn test_backup_archive() {
+    let key: [u8; 32] = rand::random();
+    let archive = BTreeMap::from([(
+        String::from("API_USAGE_FILE"),
+        String::from("{\"etherscan\":1}"),
+    )]);
+
+    let sealed = seal(&key, &archive);
+    assert_eq!(open(&key, &sealed), Ok(archive));
+    assert!(open(&[0; 32], &sealed).is_err());
+    assert!(open(&key, &sealed[..8]).is_err());
+
+    // RFC 4231 test case 2
+    assert_eq!(
+        hex::encode(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+
+    let now = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    let names: Vec<String> = (0..4)
+        .map(|days| backup_name(now - Duration::try_days(days).unwrap()))
+        .collect();
+    assert!(names.iter().all(|name| is_backup_name(name)));
+    assert_eq!(expired(&names, 2), vec![names[3].clone(), names[2].clone()]);
+    assert!(expired(&names, 7).is_empty());
+
+    assert_eq!(until_next(now, 3), Duration::try_hours(15).unwrap());
+    assert_eq!(
+        until_next(now - Duration::try_hours(11).unwrap(), 3),
+        Duration::try_hours(2).unwrap()
+    );
+}
@@ -0,0 +1,292 @@
+use crate::{api, crypto::trading, telegram::bot, utils};
+use clap::{Parser, Subcommand};
+use serde_json::{json, Value};
+use std::error::Error;
+
+pub(crate) type CliResult = Result<(Value, String), Box<dyn Error + Send + Sync>>;
+
+/*
+
+Without a subcommand the Telegram bot is started. The subcommands run a single
+request against the providers and exit, so scans can be scripted without
+Telegram, e.g. `snipers scan 0x6982508145454ce325ddbe47a25d4ec3d2311933 --json`.
+They need the same provider environment variables as the bot.
+
+*/
+#[derive(Debug, Parser)]
+#[command(
+    name = "snipers",
+    about = "Ethereum trading and monitoring Telegram bot"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+    #[arg(long, global = true, help = "print JSON instead of text")]
+    pub json: bool,
+    #[arg(
+        long,
+        value_name = "BACKUP",
+        help = "restore the state files from a backup, 'latest' or its name, before starting"
+    )]
+    pub restore: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    #[command(about = "scan an ERC-20 token for red flags")]
+    Scan { token: String },
+    #[command(about = "list the ERC-20 token balances of ETH_ADDRESS")]
+    Portfolio,
+    #[command(about = "show the gas price and fees of a gas amount or named action")]
+    Gas { action: Option<String> },
+    #[command(about = "list the top holders of an ERC-20 token")]
+    Holders {
+        token: String,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+}
+
+pub async fn run(command: CliCommand, as_json: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (value, text) = match command {
+        CliCommand::Scan { token } => scan(&token).await?,
+        CliCommand::Portfolio => portfolio(None).await?,
+        CliCommand::Gas { action } => gas(action.as_deref()).await?,
+        CliCommand::Holders { token, limit } => holders(&token, limit).await?,
+    };
+
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        println!("{}", text);
+    }
+
+    Ok(())
+}
+
+fn valid_token(token: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    if !utils::is_valid_eth_address(token) {
+        return Err(format!("{} is not a valid contract address", token).into());
+    }
+
+    Ok(token.to_lowercase())
+}
+
+// the REST API answers with the same JSON, see src/server.rs
+pub(crate) async fn scan(token: &str) -> CliResult {
+    let contract = valid_token(token)?;
+    let token_info = api::get_token_info(contract.clone()).await?;
+    let (scan, locker) = tokio::join!(
+        bot::scan_warnings(&token_info),
+        api::get_liquidity_locker(contract.clone())
+    );
+    let warnings = scan.warnings;
+    let locker = locker.flatten();
+
+    let value = json!({
+        "contract": token_info.contract_address,
+        "name": token_info.name,
+        "symbol": token_info.symbol,
+        "chain": token_info.chain.to_string(),
+        "buy_tax": token_info.buy_tax,
+        "sell_tax": token_info.sell_tax,
+        "liquidity_usd": token_info.liquidity,
+        "holders": token_info.total_holders,
+        "is_honeypot": token_info.is_honeypot,
+        "liquidity_locker": locker,
+        "warnings": warnings,
+    });
+
+    let mut text = format!(
+        "{} ({}) on {}\n{}\n\nTaxes: {}% buy, {}% sell\nLiquidity: ${:.0}\nHolders: {}",
+        token_info.name,
+        token_info.symbol,
+        token_info.chain,
+        token_info.contract_address,
+        token_info.buy_tax,
+        token_info.sell_tax,
+        token_info.liquidity,
+        token_info.total_holders
+    );
+    if let Some(locker) = &locker {
+        text.push_str(&format!("\nLiquidity locked with {}", locker));
+    }
+    text.push_str("\n\nWarnings:");
+    for warning in &warnings {
+        text.push_str(&format!("\n- {}", warning));
+    }
+    if warnings.is_empty() {
+        text.push_str(" none");
+    }
+
+    Ok((value, text))
+}
+
+// ETH_ADDRESS without an address
+pub(crate) async fn portfolio(address: Option<&str>) -> CliResult {
+    let tokens = match address {
+        Some(address) => api::get_wallet_token_balances(address).await?,
+        None => api::get_token_balances_with_prices().await?,
+    };
+    let total_usd: f64 = tokens.iter().map(|token| token.value_usd).sum();
+
+    let value = json!({
+        "total_usd": total_usd,
+        "tokens": tokens
+            .iter()
+            .map(|token| json!({
+                "contract": token.contract,
+                "name": token.name,
+                "symbol": token.symbol,
+                "balance": token.balance,
+                "value_usd": token.value_usd,
+                "change_24h_percent": token.usd_price_24hr_percent_change,
+            }))
+            .collect::<Vec<Value>>(),
+    });
+
+    let mut text = format!("Portfolio value: ${:.2}\n", total_usd);
+    for token in &tokens {
+        text.push_str(&format!(
+            "\n{} ({}): {:.4} (${:.2}, {:+.2}% 24h)",
+            token.name,
+            token.symbol,
+            token.balance,
+            token.value_usd,
+            token.usd_price_24hr_percent_change
+        ));
+    }
+
+    Ok((value, text))
+}
+
+// the same gas amounts and tiers as /gas
+pub(crate) async fn gas(action: Option<&str>) -> CliResult {
+    let actions = trading::gas_actions();
+    let selected = match action {
+        None => None,
+        Some(arg) => match trading::parse_gas_argument(arg, &actions) {
+            Some(trading::GasSelection::Amount(gas)) => Some((format!("{} gas", gas), gas)),
+            Some(trading::GasSelection::Action(action)) => {
+                Some((action.description.clone(), action.gas))
+            }
+            None => return Err(format!("{} is not a gas amount or a known action", arg).into()),
+        },
+    };
+
+    let (gas_price, eth_price) = tokio::try_join!(api::get_eth_gas_wei(), api::get_eth_price())?;
+    let gwei = gas_price as f64 / 10.0f64.powf(9.0);
+
+    let (value, text) = match selected {
+        Some((label, gas)) => {
+            let tiers = trading::tier_estimates(gas, gas_price, eth_price);
+            let mut text = format!("Gas price: {:.1} gwei\n\nFees for {}:", gwei, label);
+            for (tier, estimate) in &tiers {
+                text.push_str(&format!(
+                    "\n{}: {:.5} ETH (${:.2})",
+                    tier,
+                    estimate.cost_eth(),
+                    estimate.cost_usd()
+                ));
+            }
+
+            (
+                json!({
+                    "gas_price_gwei": gwei,
+                    "gas": gas as u64,
+                    "tiers": tiers
+                        .iter()
+                        .map(|(tier, estimate)| json!({
+                            "tier": tier,
+                            "cost_eth": estimate.cost_eth(),
+                            "cost_usd": estimate.cost_usd(),
+                        }))
+                        .collect::<Vec<Value>>(),
+                }),
+                text,
+            )
+        }
+        None => {
+            let mut text = format!("Gas price: {:.1} gwei\n\nTypical fees:", gwei);
+            let mut fees = vec![];
+            for action in &actions {
+                let estimate = trading::GasEstimate {
+                    gas: action.gas,
+                    gas_price,
+                    eth_price,
+                    simulated: false,
+                };
+                text.push_str(&format!(
+                    "\n{}: ${:.2}",
+                    action.description,
+                    estimate.cost_usd()
+                ));
+                fees.push(json!({
+                    "action": action.name,
+                    "gas": action.gas as u64,
+                    "cost_usd": estimate.cost_usd(),
+                }));
+            }
+
+            (json!({ "gas_price_gwei": gwei, "actions": fees }), text)
+        }
+    };
+
+    Ok((value, text))
+}
+
+async fn holders(token: &str, limit: usize) -> CliResult {
+    let contract = valid_token(token)?;
+    let (holders, concentration) = tokio::join!(
+        api::get_top_token_holders(contract.clone()),
+        api::get_holder_concentration(contract.clone())
+    );
+    let holders = holders?;
+
+    let mut text = format!("Top holders of {}:\n", contract);
+    for (i, holder) in holders.iter().take(limit).enumerate() {
+        text.push_str(&format!(
+            "\n{}. {} {} (${})",
+            i + 1,
+            holder.wallet_address,
+            holder.amount,
+            holder.usd_value
+        ));
+    }
+    if let Ok(concentration) = &concentration {
+        text.push_str(&format!(
+            "\n\nTop 5 own {:.1}%, top 10 own {:.1}% of the circulating supply",
+            concentration.top5_percent, concentration.top10_percent
+        ));
+    }
+
+    let value = json!({
+        "contract": contract,
+        "holders": holders
+            .iter()
+            .take(limit)
+            .map(|holder| json!({
+                "wallet": holder.wallet_address,
+                "amount": holder.amount,
+                "usd_value": holder.usd_value,
+            }))
+            .collect::<Vec<Value>>(),
+        "top5_percent": concentration.as_ref().ok().map(|c| c.top5_percent),
+        "top10_percent": concentration.as_ref().ok().map(|c| c.top10_percent),
+    });
+
+    Ok((value, text))
+}
+
+#[test]
+fn test_cli_parsing() {
+    let cli = Cli::parse_from(["snipers", "holders", "0xabc", "--limit", "5", "--json"]);
+    assert!(cli.json);
+    assert!(matches!(
+        cli.command,
+        Some(CliCommand::Holders { ref token, limit: 5 }) if token == "0xabc"
+    ));
+
+    // no subcommand runs the bot
+    assert!(Cli::parse_from(["snipers"]).command.is_none());
+}
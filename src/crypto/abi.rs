@@ -0,0 +1,188 @@
+use keccak_rust::{Keccak, SecurityLevel, StateBitsWidth};
+
+// minimal solidity abi encoding, only the types needed to build swap calldata
+#[derive(Debug, Clone)]
+pub enum AbiToken {
+    Address(String),
+    Uint(u128),
+    Bool(bool),
+    Bytes(Vec<u8>),
+    // bytes32 and shorter fixed size bytes, right padded
+    FixedBytes(Vec<u8>),
+    Array(Vec<AbiToken>),
+    Tuple(Vec<AbiToken>),
+}
+
+impl AbiToken {
+    fn is_dynamic(&self) -> bool {
+        match self {
+            AbiToken::Bytes(_) | AbiToken::Array(_) => true,
+            AbiToken::Tuple(tokens) => tokens.iter().any(|t| t.is_dynamic()),
+            _ => false,
+        }
+    }
+
+    fn head_size(&self) -> usize {
+        match self {
+            AbiToken::Tuple(tokens) if !self.is_dynamic() => {
+                tokens.iter().map(|t| t.head_size()).sum()
+            }
+            _ => 32,
+        }
+    }
+}
+
+pub fn keccak256(bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = bytes;
+    let mut keccak = Keccak::new(SecurityLevel::SHA256, StateBitsWidth::F1600);
+    keccak.append(&mut bytes);
+
+    keccak.hash().to_vec()
+}
+
+pub fn selector(signature: &str) -> Vec<u8> {
+    keccak256(signature.as_bytes())[..4].to_vec()
+}
+
+// returns hex encoded calldata, e.g. function_call("approve(address,uint256)", &[...])
+pub fn function_call(signature: &str, tokens: &[AbiToken]) -> String {
+    let mut calldata = selector(signature);
+    calldata.extend(encode(tokens));
+
+    to_hex(&calldata)
+}
+
+pub fn encode(tokens: &[AbiToken]) -> Vec<u8> {
+    let head_size: usize = tokens.iter().map(|t| t.head_size()).sum();
+    let mut head: Vec<u8> = vec![];
+    let mut tail: Vec<u8> = vec![];
+
+    for token in tokens {
+        if token.is_dynamic() {
+            head.extend(uint_word((head_size + tail.len()) as u128));
+            tail.extend(encode_token(token));
+        } else {
+            head.extend(encode_token(token));
+        }
+    }
+
+    head.extend(tail);
+    head
+}
+
+fn encode_token(token: &AbiToken) -> Vec<u8> {
+    match token {
+        AbiToken::Address(address) => {
+            let mut word = vec![0u8; 12];
+            word.extend(from_hex(address));
+            word
+        }
+        AbiToken::Uint(value) => uint_word(*value),
+        AbiToken::Bool(value) => uint_word(*value as u128),
+        AbiToken::Bytes(bytes) => {
+            let mut encoded = uint_word(bytes.len() as u128);
+            encoded.extend(bytes);
+            encoded.resize(32 + bytes.len().div_ceil(32) * 32, 0);
+            encoded
+        }
+        AbiToken::FixedBytes(bytes) => {
+            let mut word = bytes.clone();
+            word.resize(32, 0);
+            word
+        }
+        AbiToken::Array(tokens) => {
+            let mut encoded = uint_word(tokens.len() as u128);
+            encoded.extend(encode(tokens));
+            encoded
+        }
+        AbiToken::Tuple(tokens) => encode(tokens),
+    }
+}
+
+fn uint_word(value: u128) -> Vec<u8> {
+    let mut word = vec![0u8; 16];
+    word.extend(value.to_be_bytes());
+    word
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    format!(
+        "0x{}",
+        bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    )
+}
+
+pub fn from_hex(hex: &str) -> Vec<u8> {
+    let hex = hex.trim_start_matches("0x");
+
+    (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap_or(0))
+        .collect()
+}
+
+// a returned string, old tokens like MKR return their name and symbol as bytes32 instead
+pub fn decode_string(hex: &str) -> Option<String> {
+    let bytes = from_hex(hex);
+
+    let text = if bytes.len() == 32 {
+        bytes.into_iter().take_while(|b| *b != 0).collect()
+    } else {
+        let word = |at: usize| -> Option<usize> {
+            let word = bytes.get(at..at + 32)?;
+            // offsets and lengths fit into the last 8 bytes
+            word[..24].iter().all(|b| *b == 0).then(|| {
+                word[24..]
+                    .iter()
+                    .fold(0usize, |value, b| (value << 8) | *b as usize)
+            })
+        };
+        let offset = word(0)?;
+        let length = word(offset)?;
+        bytes.get(offset + 32..offset + 32 + length)?.to_vec()
+    };
+
+    String::from_utf8(text)
+        .ok()
+        .map(|text| text.trim().to_owned())
+        .filter(|text| !text.is_empty())
+}
+
+#[test]
+fn test_function_call() {
+    let calldata = function_call(
+        "transfer(address,uint256)",
+        &[
+            AbiToken::Address(String::from("0x11DDACb10c3891e356dcE6D7c6F22DD69c93E2Cd")),
+            AbiToken::Uint(1),
+        ],
+    );
+
+    assert_eq!(
+        calldata,
+        "0xa9059cbb\
+        00000000000000000000000011ddacb10c3891e356dce6d7c6f22dd69c93e2cd\
+        0000000000000000000000000000000000000000000000000000000000000001"
+    );
+}
+
+#[test]
+fn test_decode_string() {
+    let pepe = "0x\
+        0000000000000000000000000000000000000000000000000000000000000020\
+        0000000000000000000000000000000000000000000000000000000000000004\
+        5045504500000000000000000000000000000000000000000000000000000000";
+    assert_eq!(decode_string(pepe), Some(String::from("PEPE")));
+
+    let mkr = "0x4d4b520000000000000000000000000000000000000000000000000000000000";
+    assert_eq!(decode_string(mkr), Some(String::from("MKR")));
+
+    // contracts without the function return nothing
+    assert_eq!(decode_string("0x"), None);
+    assert_eq!(
+        decode_string("0x0000000000000000000000000000000000000000000000000000000000000000"),
+        None
+    );
+}
@@ -0,0 +1,118 @@
+use crate::{api, crypto::wallet, tasks, telegram::bot};
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use std::env;
+use teloxide::{requests::Requester, Bot};
+use tokio::{sync::Mutex, time::sleep};
+
+// about ten swaps at moderate gas
+const DEFAULT_MIN_ETH_BALANCE: f64 = 0.02;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BalanceCheck {
+    pub balance: f64,
+    pub threshold: f64,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl BalanceCheck {
+    pub fn is_low(&self) -> bool {
+        self.balance < self.threshold
+    }
+}
+
+lazy_static! {
+    static ref LAST_CHECK: Mutex<Option<BalanceCheck>> = Mutex::new(None);
+}
+
+// MIN_ETH_BALANCE, 0 turns the monitor off
+pub fn threshold() -> f64 {
+    env::var("MIN_ETH_BALANCE")
+        .ok()
+        .and_then(|threshold| threshold.parse::<f64>().ok())
+        .filter(|threshold| *threshold >= 0.0)
+        .unwrap_or(DEFAULT_MIN_ETH_BALANCE)
+}
+
+pub async fn last_check() -> Option<BalanceCheck> {
+    *LAST_CHECK.lock().await
+}
+
+// auto-buys go on until the first check, a provider outage shouldn't stop them
+pub async fn auto_buys_blocked() -> bool {
+    last_check().await.is_some_and(|check| check.is_low())
+}
+
+// Some(true) when the balance dropped below the threshold, Some(false) when it was topped up
+fn crossed(previous: Option<&BalanceCheck>, current: &BalanceCheck) -> Option<bool> {
+    let was_low = previous.is_some_and(|previous| previous.is_low());
+
+    match (was_low, current.is_low()) {
+        (false, true) => Some(true),
+        (true, false) => Some(false),
+        _ => None,
+    }
+}
+
+/*
+
+Buys, approvals and sells of ETH_ADDRESS all need ETH for gas, and an empty
+wallet makes the automatic ones (automatic DCA plans) fail
+without anyone noticing. The balance is checked every minute, the owner is
+warned once when it drops below MIN_ETH_BALANCE and automatic buys are skipped
+until it's topped up. Manual trades are still allowed.
+
+*/
+pub async fn watch_balance(bot: Bot) {
+    loop {
+        sleep(Duration::try_minutes(1).unwrap().to_std().unwrap()).await;
+        tasks::heartbeat("watch_balance").await;
+
+        let balance = match api::get_eth_balance(wallet::eth_address()).await {
+            Ok(balance) => balance,
+            Err(e) => {
+                error!("get_eth_balance error: {}", e);
+                continue;
+            }
+        };
+        let check = BalanceCheck {
+            balance,
+            threshold: threshold(),
+            checked_at: Utc::now(),
+        };
+        let previous = LAST_CHECK.lock().await.replace(check);
+
+        let message = match crossed(previous.as_ref(), &check) {
+            Some(true) => format!(
+                "🪫 The trading wallet has {:.4} ETH left, below the {} ETH minimum. Automatic buys are paused until it's topped up",
+                check.balance, check.threshold
+            ),
+            Some(false) => format!(
+                "🔋 The trading wallet was topped up to {:.4} ETH, automatic buys are back on",
+                check.balance
+            ),
+            None => continue,
+        };
+
+        if let Some(owner_chat) = bot::owner_chat() {
+            if let Err(e) = bot.send_message(owner_chat, message).await {
+                error!("failed to send the balance warning: {}", e);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_balance_crossed() {
+    let check = |balance: f64| BalanceCheck {
+        balance,
+        threshold: 0.02,
+        checked_at: Utc::now(),
+    };
+
+    assert_eq!(crossed(None, &check(0.01)), Some(true));
+    assert_eq!(crossed(None, &check(0.5)), None);
+    assert_eq!(crossed(Some(&check(0.5)), &check(0.019)), Some(true));
+    assert_eq!(crossed(Some(&check(0.01)), &check(0.005)), None);
+    assert_eq!(crossed(Some(&check(0.01)), &check(0.02)), Some(false));
+}
@@ -1 +1,13 @@
+pub mod abi;
+pub mod balance;
+pub mod exits;
+pub mod history;
+pub mod launches;
+pub mod payment;
+pub mod positions;
+pub mod rawtx;
+pub mod routes;
+pub mod safe;
 pub mod trading;
+pub mod wallet;
+pub mod walletconnect;
@@ -0,0 +1,105 @@
+use core::fmt;
+
+// covers the price moving before the exit is mined, on top of its own impact and the tax
+const EXIT_SLIPPAGE_BUFFER: f32 = 3.0;
+// what the checklist flags, a rug in progress usually shows up in one of them first
+const HIGH_SELL_TAX_PERCENT: f32 = 10.0;
+const LOW_DEPTH_USD: f64 = 10_000.0;
+const HIGH_IMPACT_PERCENT: f64 = 10.0;
+
+// the full bag's own price impact plus the sell tax and a buffer, between min and max
+pub fn exit_slippage(impact_percent: f64, sell_tax: f32, min: f32, max: f32) -> f32 {
+    (impact_percent as f32 + sell_tax + EXIT_SLIPPAGE_BUFFER)
+        .ceil()
+        .max(min)
+        .min(max)
+}
+
+/*
+
+What to know before dumping a whole position in a hurry: whether the token can
+still be sold and at what tax, how deep the pool is, how far the bag alone
+moves the price and the slippage the exit is sent with. The gas is simulated
+with the confirmation. A reading that couldn't be taken is shown as unknown
+instead of holding the exit up.
+
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExitChecklist {
+    pub symbol: String,
+    pub tokens: String,
+    pub value_usd: f64,
+    pub is_honeypot: bool,
+    pub sell_tax: f32,
+    // usd value of the WETH side of the pool
+    pub depth_usd: Option<f64>,
+    pub impact_percent: Option<f64>,
+    pub slippage: f32,
+}
+
+impl fmt::Display for ExitChecklist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let check = |flagged: bool| if flagged { "⚠️" } else { "✅" };
+
+        write!(
+            f,
+            "🚪 Exit checklist for {}\n💼 Position: {} {} ≈ ${:.2}",
+            self.symbol, self.tokens, self.symbol, self.value_usd
+        )?;
+
+        if self.is_honeypot {
+            write!(f, "\n⛔ Sells look blocked, the exit may revert")?;
+        }
+        write!(
+            f,
+            "\n{} Sell tax: {:.1}%",
+            check(self.sell_tax >= HIGH_SELL_TAX_PERCENT),
+            self.sell_tax
+        )?;
+        match self.depth_usd {
+            Some(depth_usd) => write!(
+                f,
+                "\n{} Liquidity: ${:.0}",
+                check(depth_usd < LOW_DEPTH_USD),
+                depth_usd
+            )?,
+            None => write!(f, "\n❔ Liquidity: unknown")?,
+        }
+        match self.impact_percent {
+            Some(impact) => write!(
+                f,
+                "\n{} Price impact of the full bag: ~{:.1}%",
+                check(impact >= HIGH_IMPACT_PERCENT),
+                impact
+            )?,
+            None => write!(f, "\n❔ Price impact of the full bag: unknown")?,
+        }
+
+        write!(f, "\n🛡 Protective slippage: {}%", self.slippage)
+    }
+}
+
+#[test]
+fn test_exit_checklist() {
+    // 12% impact and a 5% tax, rounded up with the buffer
+    assert_eq!(exit_slippage(12.4, 5.0, 5.0, 25.0), 21.0);
+    assert_eq!(exit_slippage(0.1, 0.0, 5.0, 25.0), 5.0);
+    assert_eq!(exit_slippage(60.0, 10.0, 5.0, 25.0), 25.0);
+
+    let checklist = ExitChecklist {
+        symbol: String::from("RUG"),
+        tokens: String::from("1500000"),
+        value_usd: 1234.5,
+        is_honeypot: false,
+        sell_tax: 12.0,
+        depth_usd: Some(8_000.0),
+        impact_percent: None,
+        slippage: 25.0,
+    };
+    assert_eq!(
+        checklist.to_string(),
+        "🚪 Exit checklist for RUG\n💼 Position: 1500000 RUG ≈ $1234.50\
+         \n⚠️ Sell tax: 12.0%\n⚠️ Liquidity: $8000\n❔ Price impact of the full bag: unknown\
+         \n🛡 Protective slippage: 25%"
+    );
+}
@@ -0,0 +1,144 @@
+use crate::{api, persist, tasks};
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Mutex, time::sleep};
+
+const SNAPSHOT_INTERVAL_MINUTES: i64 = 60;
+// /chart shows at most 30 days, older snapshots are dropped
+const KEEP_DAYS: i64 = 30;
+const SPARKLINE_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ValueSnapshot {
+    pub timestamp: i64,
+    pub value_usd: f64,
+}
+
+lazy_static! {
+    static ref SNAPSHOTS: Mutex<Vec<ValueSnapshot>> =
+        Mutex::new(persist::load(persist::PORTFOLIO_HISTORY).unwrap_or_default());
+}
+
+pub async fn record(value_usd: f64, now: DateTime<Utc>) {
+    let mut snapshots = SNAPSHOTS.lock().await;
+    let oldest = (now - Duration::try_days(KEEP_DAYS).unwrap()).timestamp();
+
+    snapshots.retain(|snapshot| snapshot.timestamp >= oldest);
+    snapshots.push(ValueSnapshot {
+        timestamp: now.timestamp(),
+        value_usd,
+    });
+    persist::save(persist::PORTFOLIO_HISTORY, &*snapshots);
+}
+
+pub async fn since(days: i64) -> Vec<ValueSnapshot> {
+    let oldest = (Utc::now() - Duration::try_days(days).unwrap()).timestamp();
+
+    SNAPSHOTS
+        .lock()
+        .await
+        .iter()
+        .filter(|snapshot| snapshot.timestamp >= oldest)
+        .copied()
+        .collect()
+}
+
+// every bar is the average of its share of the values, so long histories still fit one line
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    if values.is_empty() || width == 0 {
+        return String::new();
+    }
+
+    let buckets: Vec<f64> = values
+        .chunks(values.len().div_ceil(width))
+        .map(|chunk| chunk.iter().sum::<f64>() / chunk.len() as f64)
+        .collect();
+    let low = buckets.iter().copied().fold(f64::INFINITY, f64::min);
+    let high = buckets.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+    buckets
+        .iter()
+        .map(|value| {
+            let level = if high > low {
+                ((value - low) / (high - low) * (SPARKLINE_BARS.len() - 1) as f64).round()
+            } else {
+                0.0
+            };
+            SPARKLINE_BARS[level as usize]
+        })
+        .collect()
+}
+
+// percent change between the snapshot closest to the given age and the latest one
+pub fn change_percent(snapshots: &[ValueSnapshot], age: Duration) -> Option<f64> {
+    let latest = snapshots.last()?;
+    let target = latest.timestamp - age.num_seconds();
+    let past = snapshots
+        .iter()
+        .min_by_key(|snapshot| (snapshot.timestamp - target).abs())?;
+
+    if past.value_usd <= 0.0 || past.timestamp == latest.timestamp {
+        return None;
+    }
+
+    Some((latest.value_usd - past.value_usd) / past.value_usd * 100.0)
+}
+
+// the wallet's balances as valued by /portfolio
+pub async fn record_snapshots() {
+    loop {
+        info!("Portfolio snapshot...");
+        tasks::heartbeat("portfolio_history").await;
+
+        match api::get_token_balances_with_prices().await {
+            Ok(tokens) => {
+                let value_usd = tokens.iter().map(|token| token.value_usd).sum();
+                record(value_usd, Utc::now()).await;
+            }
+            Err(e) => error!("get_token_balances_with_prices error: {}", e),
+        }
+
+        sleep(
+            Duration::try_minutes(SNAPSHOT_INTERVAL_MINUTES)
+                .unwrap()
+                .to_std()
+                .unwrap(),
+        )
+        .await;
+    }
+}
+
+#[test]
+fn test_sparkline() {
+    assert_eq!(
+        sparkline(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0], 8),
+        "▁▂▃▄▅▆▇█"
+    );
+    // pairs are averaged into one bar
+    assert_eq!(sparkline(&[1.0, 1.0, 5.0, 5.0], 2), "▁█");
+    assert_eq!(sparkline(&[3.0, 3.0], 10), "▁▁");
+    assert_eq!(sparkline(&[], 10), "");
+
+    let hour = 3_600;
+    let snapshots: Vec<ValueSnapshot> = [100.0, 120.0, 90.0, 150.0]
+        .iter()
+        .enumerate()
+        .map(|(i, value_usd)| ValueSnapshot {
+            timestamp: i as i64 * 12 * hour,
+            value_usd: *value_usd,
+        })
+        .collect();
+    assert_eq!(
+        change_percent(&snapshots, Duration::try_hours(36).unwrap()),
+        Some(50.0)
+    );
+    assert_eq!(
+        change_percent(&snapshots, Duration::try_hours(12).unwrap()),
+        Some(((150.0 - 90.0) / 90.0) * 100.0)
+    );
+    assert_eq!(
+        change_percent(&snapshots[..1], Duration::try_days(1).unwrap()),
+        None
+    );
+}
@@ -0,0 +1,538 @@
+use crate::{
+    api::{self, AlchemyTransaction, WETH_ADDRESS},
+    crypto::{
+        abi::{self, AbiToken},
+        rawtx::{self, Eip1559Transaction, SignedTransaction},
+        trading::{self, TransactionRequest},
+        wallet,
+    },
+    persist,
+    utils::{address_from_word, hex_to_decimal, uint_from_word},
+};
+use chrono::{DateTime, Duration, Utc};
+use core::fmt;
+use k256::ecdsa::{signature::Error, SigningKey};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, env};
+use tokio::sync::Mutex;
+
+const UNISWAP_V2_ROUTER: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+
+// hard caps of the same-block snipes, LAUNCH_SNIPE_MAX_USD can only lower the amount cap
+const MAX_SNIPE_USD: f64 = 500.0;
+const DEFAULT_MAX_SNIPE_USD: f64 = 100.0;
+const MAX_REGISTERED_LAUNCHES: usize = 3;
+const MAX_LAUNCH_SLIPPAGE: f32 = 25.0;
+// the buy is skipped when it would move the fresh pool more than this
+const MAX_LAUNCH_PRICE_IMPACT_PERCENT: f64 = 5.0;
+// a launch that never happens shouldn't snipe a relaunch days later
+const REGISTRATION_HOURS: i64 = 24;
+// the buy is skipped when gas is so high the fee cap would be above this
+const MAX_BACKRUN_FEE_GWEI: u128 = 150;
+const BACKRUN_PRIORITY_FEE_GWEI: u128 = 3;
+const BACKRUN_GAS_LIMIT: u128 = 300_000;
+// a contract isn't sniped twice in this window, and buying it by hand asks for an extra confirmation
+const RECENT_SNIPE_HOURS: i64 = 24;
+
+lazy_static! {
+    pub static ref LAUNCH_SNIPES: Mutex<Vec<LaunchSnipe>> = Mutex::new(Vec::new());
+    // saved, a restart mustn't snipe a relaunch of a contract that was just sniped
+    static ref RECENT_SNIPES: Mutex<RecentSnipes> =
+        Mutex::new(persist::load(persist::RECENT_SNIPES).unwrap_or_default());
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchSnipe {
+    pub contract: String,
+    pub amount_usd: f64,
+    pub slippage: f32,
+    pub registered_at: DateTime<Utc>,
+}
+
+impl LaunchSnipe {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now - self.registered_at > Duration::try_hours(REGISTRATION_HOURS).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterError {
+    AboveCap(f64),
+    SlippageTooHigh,
+    TooManyLaunches,
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RegisterError::AboveCap(cap) => {
+                write!(f, "same-block snipes are capped at ${}", cap)
+            }
+            RegisterError::SlippageTooHigh => {
+                write!(f, "the slippage can't be above {}%", MAX_LAUNCH_SLIPPAGE)
+            }
+            RegisterError::TooManyLaunches => write!(
+                f,
+                "only {} launches can be registered at once",
+                MAX_REGISTERED_LAUNCHES
+            ),
+        }
+    }
+}
+
+pub fn max_snipe_usd() -> f64 {
+    env::var("LAUNCH_SNIPE_MAX_USD")
+        .ok()
+        .and_then(|cap| cap.parse::<f64>().ok())
+        .filter(|cap| *cap > 0.0)
+        .unwrap_or(DEFAULT_MAX_SNIPE_USD)
+        .min(MAX_SNIPE_USD)
+}
+
+// a registered contract is replaced, so the amount can be changed without removing it first
+pub fn register(snipes: &mut Vec<LaunchSnipe>, snipe: LaunchSnipe) -> Result<(), RegisterError> {
+    let cap = max_snipe_usd();
+    if snipe.amount_usd > cap {
+        return Err(RegisterError::AboveCap(cap));
+    }
+    if snipe.slippage > MAX_LAUNCH_SLIPPAGE {
+        return Err(RegisterError::SlippageTooHigh);
+    }
+
+    snipes.retain(|s| !s.is_expired(snipe.registered_at) && s.contract != snipe.contract);
+    if snipes.len() >= MAX_REGISTERED_LAUNCHES {
+        return Err(RegisterError::TooManyLaunches);
+    }
+    snipes.push(snipe);
+
+    Ok(())
+}
+
+// lowercase contract -> when its snipe was sent
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecentSnipes(HashMap<String, DateTime<Utc>>);
+
+impl RecentSnipes {
+    pub fn sniped_at(&self, contract: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.0
+            .get(&contract.to_lowercase())
+            .copied()
+            .filter(|at| now - *at < Duration::try_hours(RECENT_SNIPE_HOURS).unwrap())
+    }
+
+    // Err with when the contract was sniped if that was within RECENT_SNIPE_HOURS
+    pub fn claim(&mut self, contract: &str, now: DateTime<Utc>) -> Result<(), DateTime<Utc>> {
+        if let Some(sniped_at) = self.sniped_at(contract, now) {
+            return Err(sniped_at);
+        }
+
+        self.0
+            .retain(|_, at| now - *at < Duration::try_hours(RECENT_SNIPE_HOURS).unwrap());
+        self.0.insert(contract.to_lowercase(), now);
+
+        Ok(())
+    }
+
+    pub fn release(&mut self, contract: &str) {
+        self.0.remove(&contract.to_lowercase());
+    }
+}
+
+// when the contract was sniped, if it was in the last RECENT_SNIPE_HOURS
+pub async fn recently_sniped(contract: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    RECENT_SNIPES.lock().await.sniped_at(contract, now)
+}
+
+async fn claim_snipe(contract: &str, now: DateTime<Utc>) -> Result<(), DateTime<Utc>> {
+    let mut snipes = RECENT_SNIPES.lock().await;
+    snipes.claim(contract, now)?;
+    persist::save(persist::RECENT_SNIPES, &*snipes);
+
+    Ok(())
+}
+
+// nothing was sent after all, the contract can be sniped again
+async fn release_snipe(contract: &str) {
+    let mut snipes = RECENT_SNIPES.lock().await;
+    snipes.release(contract);
+    persist::save(persist::RECENT_SNIPES, &*snipes);
+}
+
+// a pending router call that creates the liquidity of a registered token against WETH
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiquidityAdd {
+    pub hash: String,
+    pub contract: String,
+    pub token_amount: u128,
+    pub eth_amount: u128,
+}
+
+pub fn decode_liquidity_add(
+    transaction: &AlchemyTransaction,
+    contract: &str,
+) -> Option<LiquidityAdd> {
+    if transaction.to.as_deref().map(str::to_lowercase).as_deref() != Some(UNISWAP_V2_ROUTER)
+        || transaction.input.len() < 10
+    {
+        return None;
+    }
+
+    let contract = contract.to_lowercase();
+    let selector = abi::from_hex(&transaction.input[..10]);
+    let words: Vec<&str> = transaction.input[10..]
+        .as_bytes()
+        .chunks(64)
+        .filter_map(|word| std::str::from_utf8(word).ok())
+        .collect();
+    let word = |i: usize| words.get(i).copied().unwrap_or_default();
+    let is_token = |i: usize| address_from_word(word(i)).as_deref() == Some(contract.as_str());
+    let is_weth = |i: usize| address_from_word(word(i)).as_deref() == Some(WETH_ADDRESS);
+
+    // the desired amounts are what a fresh pair gets, there are no reserves to match yet
+    let (token_amount, eth_amount) = if selector
+        == abi::selector("addLiquidityETH(address,uint256,uint256,uint256,address,uint256)")
+        && is_token(0)
+    {
+        (uint_from_word(word(1)), hex_to_decimal(&transaction.value))
+    } else if selector
+        == abi::selector(
+            "addLiquidity(address,address,uint256,uint256,uint256,uint256,address,uint256)",
+        )
+    {
+        match (is_token(0) && is_weth(1), is_weth(0) && is_token(1)) {
+            (true, _) => (uint_from_word(word(2)), uint_from_word(word(3))),
+            (_, true) => (uint_from_word(word(3)), uint_from_word(word(2))),
+            _ => return None,
+        }
+    } else {
+        return None;
+    };
+
+    Some(LiquidityAdd {
+        hash: transaction.hash.clone(),
+        contract,
+        token_amount,
+        eth_amount,
+    })
+}
+
+// Uniswap V2 getAmountOut against the reserves the liquidity add creates
+fn tokens_out(add: &LiquidityAdd, eth_in: u128) -> f64 {
+    let eth_in = eth_in as f64 * 0.997;
+
+    eth_in * add.token_amount as f64 / (add.eth_amount as f64 + eth_in)
+}
+
+// the buy placed right behind the liquidity add, None when it's above the price impact cap
+pub fn backrun_buy(
+    snipe: &LaunchSnipe,
+    add: &LiquidityAdd,
+    eth_price: f64,
+    owner: &str,
+) -> Option<TransactionRequest> {
+    if eth_price <= 0.0 || add.eth_amount == 0 {
+        return None;
+    }
+
+    let value = (snipe.amount_usd / eth_price * 10.0f64.powf(18.0)) as u128;
+    let impact = trading::price_impact_percent(add.eth_amount as f64, value as f64);
+    if impact > MAX_LAUNCH_PRICE_IMPACT_PERCENT {
+        return None;
+    }
+
+    let min_tokens_out = (tokens_out(add, value) * (1.0 - snipe.slippage as f64 / 100.0)) as u128;
+    // the bundle only lands in the target block, a short deadline is enough
+    let deadline = (Utc::now() + Duration::try_minutes(2).unwrap()).timestamp() as u128;
+
+    Some(TransactionRequest {
+        to: String::from(UNISWAP_V2_ROUTER),
+        data: abi::function_call(
+            "swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)",
+            &[
+                AbiToken::Uint(min_tokens_out),
+                AbiToken::Array(vec![
+                    AbiToken::Address(String::from(WETH_ADDRESS)),
+                    AbiToken::Address(add.contract.clone()),
+                ]),
+                AbiToken::Address(owner.to_owned()),
+                AbiToken::Uint(deadline),
+            ],
+        ),
+        value,
+        description: String::from("Swap ETH for tokens behind the liquidity add"),
+    })
+}
+
+// the signed buy, None when the gas price would take the fee above MAX_BACKRUN_FEE_GWEI.
+// the base fee can double in two blocks, the max fee covers that
+pub fn backrun_transaction(
+    buy: &TransactionRequest,
+    nonce: u64,
+    gas_price: u128,
+) -> Option<Eip1559Transaction> {
+    let gwei = 10u128.pow(9);
+    let max_fee_per_gas = gas_price * 2 + BACKRUN_PRIORITY_FEE_GWEI * gwei;
+    if max_fee_per_gas > MAX_BACKRUN_FEE_GWEI * gwei {
+        return None;
+    }
+
+    Some(Eip1559Transaction {
+        nonce,
+        max_priority_fee_per_gas: BACKRUN_PRIORITY_FEE_GWEI * gwei,
+        max_fee_per_gas,
+        gas_limit: BACKRUN_GAS_LIMIT,
+        to: buy.to.clone(),
+        value: buy.value,
+        data: buy.data.clone(),
+    })
+}
+
+// eth_sendBundle request for the relay, the liquidity add goes first so the buy can't land before it
+pub fn flashbots_bundle(signed_transactions: &[String], block_number: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_sendBundle",
+        "params": [{
+            "txs": signed_transactions,
+            "blockNumber": format!("0x{:x}", block_number),
+        }],
+    })
+}
+
+// X-Flashbots-Signature, the address and its personal_sign of the body's hex encoded hash
+fn flashbots_signature(key: &SigningKey, body: &str) -> Result<String, Error> {
+    let hash = abi::to_hex(&abi::keccak256(body.as_bytes()));
+
+    Ok(format!(
+        "{}:{}",
+        wallet::address_of(key),
+        rawtx::sign_message(key, hash.as_bytes())?
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backrun {
+    // the signed buy was bundled for these blocks, it only lands if the relay's builders include it
+    Sent {
+        buy: SignedTransaction,
+        value: u128,
+        blocks: Vec<u64>,
+    },
+    Skipped(String),
+}
+
+/*
+
+The buy is signed with LAUNCH_SNIPE_KEY, a hot key only meant to hold the ETH
+of the registered snipes. The tokens are sent to ETH_ADDRESS, so the key never
+holds anything else. The bundle is sent for the pending block and the one
+after it, Flashbots drops bundles with a reverting transaction, so a buy that
+would fail (a honeypot, a max tx) costs nothing.
+
+*/
+pub async fn backrun(
+    snipe: &LaunchSnipe,
+    add: &LiquidityAdd,
+    block_number: u64,
+    key: &SigningKey,
+) -> Backrun {
+    let skipped = |reason: &str| Backrun::Skipped(reason.to_owned());
+
+    let (eth_price, gas_price) = tokio::join!(api::get_eth_price(), api::get_eth_gas_wei());
+    let (eth_price, gas_price) = match (eth_price, gas_price) {
+        (Ok(eth_price), Ok(gas_price)) => (eth_price, gas_price),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("backrun price error: {}", e);
+            return skipped("the ETH price or the gas price is not available");
+        }
+    };
+    let Some(buy) = backrun_buy(snipe, add, eth_price, &wallet::eth_address()) else {
+        return skipped(&format!(
+            "${} would move the new pool more than {}%",
+            snipe.amount_usd, MAX_LAUNCH_PRICE_IMPACT_PERCENT
+        ));
+    };
+
+    let nonce = match api::get_transaction_count(wallet::address_of(key), true).await {
+        Ok(nonce) => nonce,
+        Err(e) => {
+            error!("get_transaction_count error: {}", e);
+            return skipped("the nonce of the snipe key is not available");
+        }
+    };
+    let Some(transaction) = backrun_transaction(&buy, nonce, gas_price) else {
+        return skipped(&format!(
+            "gas is too high, the fee would be above {} gwei",
+            MAX_BACKRUN_FEE_GWEI
+        ));
+    };
+    let signed = match transaction.sign(key) {
+        Ok(signed) => signed,
+        Err(e) => {
+            error!("sign backrun error: {}", e);
+            return skipped("the buy could not be signed");
+        }
+    };
+    let add_raw = match api::get_raw_transaction(add.hash.clone()).await {
+        Ok(Some(raw)) => raw,
+        Ok(None) => return skipped("the liquidity add left the mempool"),
+        Err(e) => {
+            error!("get_raw_transaction error: {}", e);
+            return skipped("the signed liquidity add is not available");
+        }
+    };
+
+    // claimed before the bundle goes out, two registrations of one contract can't both send
+    if let Err(sniped_at) = claim_snipe(&snipe.contract, Utc::now()).await {
+        return skipped(&format!(
+            "it was already sniped {} minutes ago",
+            (Utc::now() - sniped_at).num_minutes()
+        ));
+    }
+
+    let mut blocks = vec![];
+    for block in [block_number, block_number + 1] {
+        let body = flashbots_bundle(&[add_raw.clone(), signed.raw.clone()], block).to_string();
+        let signature = match flashbots_signature(key, &body) {
+            Ok(signature) => signature,
+            Err(e) => {
+                error!("sign flashbots bundle error: {}", e);
+                continue;
+            }
+        };
+
+        match api::send_flashbots_bundle(body, signature).await {
+            Ok(response) if response.get("error").is_none() => blocks.push(block),
+            Ok(response) => warn!("flashbots refused the bundle: {}", response["error"]),
+            Err(e) => error!("send_flashbots_bundle error: {}", e),
+        }
+    }
+
+    if blocks.is_empty() {
+        release_snipe(&snipe.contract).await;
+        return skipped("the Flashbots relay didn't take the bundle");
+    }
+
+    Backrun::Sent {
+        buy: signed,
+        value: buy.value,
+        blocks,
+    }
+}
+
+#[test]
+fn test_launch_backrun() {
+    let contract = "0x6982508145454ce325ddbe47a25d4ec3d2311933";
+    let now = Utc::now();
+    let snipe = |contract: &str, amount_usd: f64| LaunchSnipe {
+        contract: contract.to_owned(),
+        amount_usd,
+        slippage: 10.0,
+        registered_at: now,
+    };
+
+    let mut snipes = vec![];
+    assert_eq!(
+        register(&mut snipes, snipe(contract, 10_000.0)),
+        Err(RegisterError::AboveCap(max_snipe_usd()))
+    );
+    for i in 0..MAX_REGISTERED_LAUNCHES {
+        assert!(register(&mut snipes, snipe(&format!("0x{}", i), 10.0)).is_ok());
+    }
+    assert_eq!(
+        register(&mut snipes, snipe(contract, 10.0)),
+        Err(RegisterError::TooManyLaunches)
+    );
+    // expired registrations make room
+    snipes[0].registered_at = now - Duration::try_hours(REGISTRATION_HOURS + 1).unwrap();
+    assert!(register(&mut snipes, snipe(contract, 10.0)).is_ok());
+
+    let transaction = AlchemyTransaction {
+        hash: String::from("0xadd"),
+        from: String::from("0xdeployer"),
+        to: Some(String::from("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D")),
+        nonce: String::from("0x1"),
+        // 10 eth
+        value: String::from("0x8ac7230489e80000"),
+        gas: String::from("0x0"),
+        gas_price: None,
+        max_fee_per_gas: None,
+        max_priority_fee_per_gas: None,
+        input: abi::function_call(
+            "addLiquidityETH(address,uint256,uint256,uint256,address,uint256)",
+            &[
+                AbiToken::Address(contract.to_owned()),
+                AbiToken::Uint(1_000_000),
+                AbiToken::Uint(0),
+                AbiToken::Uint(0),
+                AbiToken::Address(String::from("0x1111111111111111111111111111111111111111")),
+                AbiToken::Uint(0),
+            ],
+        ),
+        transaction_index: None,
+        block_number: None,
+    };
+    let add = decode_liquidity_add(&transaction, contract).unwrap();
+    assert_eq!(
+        (add.token_amount, add.eth_amount),
+        (1_000_000, 10 * 10u128.pow(18))
+    );
+    assert_eq!(
+        decode_liquidity_add(&transaction, "0x4444444444444444444444444444444444444444"),
+        None
+    );
+
+    // $2,000 is 1 eth, ~9% of the pool
+    let owner = "0x1111111111111111111111111111111111111111";
+    assert!(backrun_buy(&snipe(contract, 2_000.0), &add, 2_000.0, owner).is_none());
+    let buy = backrun_buy(&snipe(contract, 100.0), &add, 2_000.0, owner).unwrap();
+    assert_eq!(buy.value, 5 * 10u128.pow(16));
+
+    assert_eq!(
+        flashbots_bundle(&[String::from("0xa"), String::from("0xb")], 255)["params"][0]
+            ["blockNumber"],
+        "0xff"
+    );
+
+    // 20 gwei doubles to 40 plus the tip, 80 gwei would be above the cap
+    let gwei = 10u128.pow(9);
+    let transaction = backrun_transaction(&buy, 4, 20 * gwei).unwrap();
+    assert_eq!(transaction.max_fee_per_gas, 43 * gwei);
+    assert_eq!((transaction.nonce, transaction.value), (4, buy.value));
+    assert!(backrun_transaction(&buy, 4, 80 * gwei).is_none());
+
+    let key = SigningKey::from_slice(&abi::from_hex(
+        "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+    ))
+    .unwrap();
+    let signature = flashbots_signature(&key, "{}").unwrap();
+    assert!(signature.starts_with("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266:0x"));
+}
+
+#[test]
+fn test_recent_snipes() {
+    let contract = "0x6982508145454ce325ddbe47a25d4ec3d2311933";
+    let now = Utc::now();
+    let mut snipes = RecentSnipes::default();
+    assert_eq!(snipes.claim(contract, now), Ok(()));
+
+    // a second snipe within the window is refused, whatever the case of the address
+    let later = now + Duration::try_hours(RECENT_SNIPE_HOURS - 1).unwrap();
+    assert_eq!(snipes.claim(&contract.to_uppercase(), later), Err(now));
+    assert_eq!(snipes.sniped_at(contract, later), Some(now));
+
+    let after = now + Duration::try_hours(RECENT_SNIPE_HOURS + 1).unwrap();
+    assert_eq!(snipes.sniped_at(contract, after), None);
+    assert_eq!(snipes.claim(contract, after), Ok(()));
+
+    // the window survives a restart
+    let mut saved: RecentSnipes =
+        serde_json::from_str(&serde_json::to_string(&snipes).unwrap()).unwrap();
+    assert_eq!(saved.claim(contract, after), Err(after));
+
+    snipes.release(contract);
+    assert_eq!(snipes.claim(contract, after), Ok(()));
+}
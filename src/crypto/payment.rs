@@ -0,0 +1,106 @@
+use qrcode::{Color, QrCode};
+
+const MAINNET_CHAIN_ID: u64 = 1;
+// every QR module is drawn as a square of this many pixels
+const MODULE_PIXELS: usize = 8;
+// scanners need a blank border of 4 modules around the code
+const QUIET_ZONE: usize = 4;
+
+// '0.05' -> 50000000000000000 wei, amounts are parsed exactly instead of going through f64
+pub fn parse_eth_amount(amount: &str) -> Option<u128> {
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+
+    if (whole.is_empty() && fraction.is_empty())
+        || fraction.len() > 18
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<u128>().ok()?
+    };
+    let fraction = format!("{:0<18}", fraction).parse::<u128>().ok()?;
+    let wei = whole.checked_mul(10u128.pow(18))?.checked_add(fraction)?;
+
+    (wei > 0).then_some(wei)
+}
+
+// EIP-681 eth transfer, mobile wallets prefill the recipient, chain and amount from it
+pub fn payment_uri(address: &str, wei: Option<u128>) -> String {
+    match wei {
+        Some(wei) => format!("ethereum:{}@{}?value={}", address, MAINNET_CHAIN_ID, wei),
+        None => format!("ethereum:{}@{}", address, MAINNET_CHAIN_ID),
+    }
+}
+
+// (grayscale pixels, side length) of the black on white code
+pub fn qr_code_pixels(data: &str) -> Result<(Vec<u8>, usize), String> {
+    let code = QrCode::new(data).map_err(|e| e.to_string())?;
+    let modules = code.width();
+    let size = (modules + QUIET_ZONE * 2) * MODULE_PIXELS;
+    let mut pixels = vec![255u8; size * size];
+
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color == Color::Light {
+            continue;
+        }
+
+        let x = (i % modules + QUIET_ZONE) * MODULE_PIXELS;
+        let y = (i / modules + QUIET_ZONE) * MODULE_PIXELS;
+        for row in y..y + MODULE_PIXELS {
+            pixels[row * size + x..row * size + x + MODULE_PIXELS].fill(0);
+        }
+    }
+
+    Ok((pixels, size))
+}
+
+// black on white grayscale png
+pub fn qr_code_png(data: &str) -> Result<Vec<u8>, String> {
+    let (pixels, size) = qr_code_pixels(data)?;
+
+    let mut png = vec![];
+    {
+        let mut encoder = png::Encoder::new(&mut png, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer
+            .write_image_data(&pixels)
+            .map_err(|e| e.to_string())?;
+        writer.finish().map_err(|e| e.to_string())?;
+    }
+
+    Ok(png)
+}
+
+#[test]
+fn test_payment_uri() {
+    let address = "0x11DDACb10c3891e356dcE6D7c6F22DD69c93E2Cd";
+
+    assert_eq!(parse_eth_amount("0.05"), Some(50_000_000_000_000_000));
+    assert_eq!(parse_eth_amount("2"), Some(2_000_000_000_000_000_000));
+    assert_eq!(parse_eth_amount(".5"), Some(500_000_000_000_000_000));
+    assert_eq!(parse_eth_amount("0"), None);
+    assert_eq!(parse_eth_amount("1e18"), None);
+    assert_eq!(parse_eth_amount("0.0000000000000000001"), None);
+
+    assert_eq!(
+        payment_uri(address, parse_eth_amount("0.05")),
+        "ethereum:0x11DDACb10c3891e356dcE6D7c6F22DD69c93E2Cd@1?value=50000000000000000"
+    );
+    assert_eq!(
+        payment_uri(address, None),
+        "ethereum:0x11DDACb10c3891e356dcE6D7c6F22DD69c93E2Cd@1"
+    );
+
+    let png = qr_code_png(&payment_uri(address, None)).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+}
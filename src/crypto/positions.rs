@@ -0,0 +1,408 @@
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+use tokio::sync::Mutex;
+
+// the daily digest only looks at the last day of trades
+const KEEP_FILLS_DAYS: i64 = 2;
+
+lazy_static! {
+    pub static ref POSITIONS: Mutex<Ledger> = Mutex::new(Ledger::default());
+}
+
+#[derive(Debug, Clone)]
+struct Lot {
+    tokens: f64,
+    cost_usd: f64,
+    // lowercase wallet that executed the buy
+    wallet: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Position {
+    pub symbol: String,
+    // oldest buy first, sells are matched against the front (FIFO)
+    lots: VecDeque<Lot>,
+}
+
+impl Position {
+    pub fn tokens(&self) -> f64 {
+        self.lots.iter().map(|lot| lot.tokens).sum()
+    }
+
+    pub fn cost_usd(&self) -> f64 {
+        self.lots.iter().map(|lot| lot.cost_usd).sum()
+    }
+
+    // average price paid per token
+    pub fn entry_price(&self) -> Option<f64> {
+        let tokens = self.tokens();
+        (tokens > 0.0).then(|| self.cost_usd() / tokens)
+    }
+
+    // sells are routed from the wallet holding the oldest lot
+    pub fn wallet(&self) -> Option<&str> {
+        self.lots.front().map(|lot| lot.wallet.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RealizedTrade {
+    pub contract: String,
+    pub symbol: String,
+    pub tokens: f64,
+    pub proceeds_usd: f64,
+    pub cost_basis_usd: f64,
+    // sold tokens that weren't bought through the bot, they have no cost basis
+    pub unmatched_tokens: f64,
+    pub remaining_tokens: f64,
+    pub closed_at: DateTime<Utc>,
+}
+
+impl RealizedTrade {
+    // only the part of the sell that was matched against recorded buys counts
+    pub fn pnl_usd(&self) -> f64 {
+        if self.tokens <= 0.0 {
+            return 0.0;
+        }
+
+        let matched = self.tokens - self.unmatched_tokens;
+        self.proceeds_usd * matched / self.tokens - self.cost_basis_usd
+    }
+
+    pub fn pnl_percent(&self) -> f64 {
+        if self.cost_basis_usd <= 0.0 {
+            return 0.0;
+        }
+
+        self.pnl_usd() / self.cost_basis_usd * 100.0
+    }
+}
+
+impl fmt::Display for RealizedTrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pnl = self.pnl_usd();
+
+        write!(
+            f,
+            "🧾 Receipt\n💎 Sold {:.4} {} for ${:.2}\n{} Realized PnL: {}${:.2} ({:+.2}%)\n📦 Remaining: {:.4} {}",
+            self.tokens,
+            self.symbol,
+            self.proceeds_usd,
+            if pnl >= 0.0 { "🟢" } else { "🔴" },
+            if pnl >= 0.0 { "+" } else { "-" },
+            pnl.abs(),
+            self.pnl_percent(),
+            self.remaining_tokens,
+            self.symbol
+        )?;
+
+        if self.unmatched_tokens > 0.0 {
+            write!(
+                f,
+                "\n⚠️ {:.4} {} had no recorded buy and are not included in the PnL",
+                self.unmatched_tokens, self.symbol
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TradeStats {
+    pub sells: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub realized_pnl_usd: f64,
+    pub best: Option<RealizedTrade>,
+    pub worst: Option<RealizedTrade>,
+    pub open_positions: usize,
+    pub open_cost_usd: f64,
+}
+
+// the trades of a period, best and worst are by realized PnL
+#[derive(Debug, Clone, Default)]
+pub struct TradeActivity {
+    pub buys: usize,
+    pub sells: usize,
+    pub volume_usd: f64,
+    pub realized_pnl_usd: f64,
+    pub best: Option<RealizedTrade>,
+    pub worst: Option<RealizedTrade>,
+}
+
+#[derive(Debug, Clone)]
+struct Fill {
+    is_buy: bool,
+    amount_usd: f64,
+    at: DateTime<Utc>,
+}
+
+// positions of the trading wallets, keyed by lowercase contract
+#[derive(Debug, Default)]
+pub struct Ledger {
+    positions: HashMap<String, Position>,
+    realized: Vec<RealizedTrade>,
+    // every buy and sell of the last KEEP_FILLS_DAYS
+    fills: Vec<Fill>,
+}
+
+impl Ledger {
+    fn fill(&mut self, is_buy: bool, amount_usd: f64) {
+        let now = Utc::now();
+        let oldest = now - Duration::try_days(KEEP_FILLS_DAYS).unwrap();
+
+        self.fills.retain(|fill| fill.at >= oldest);
+        self.fills.push(Fill {
+            is_buy,
+            amount_usd,
+            at: now,
+        });
+    }
+
+    pub fn buy(&mut self, contract: &str, symbol: &str, tokens: f64, cost_usd: f64, wallet: &str) {
+        self.fill(true, cost_usd);
+        let position = self.positions.entry(contract.to_lowercase()).or_default();
+
+        position.symbol = String::from(symbol);
+        position.lots.push_back(Lot {
+            tokens,
+            cost_usd,
+            wallet: wallet.to_lowercase(),
+        });
+    }
+
+    pub fn sell(
+        &mut self,
+        contract: &str,
+        symbol: &str,
+        tokens: f64,
+        proceeds_usd: f64,
+        wallet: &str,
+    ) -> RealizedTrade {
+        self.fill(false, proceeds_usd);
+        let key = contract.to_lowercase();
+        let wallet = wallet.to_lowercase();
+        let mut left = tokens;
+        let mut cost_basis_usd = 0.0;
+
+        if let Some(position) = self.positions.get_mut(&key) {
+            // only lots bought by the selling wallet are matched
+            while left > 0.0 {
+                let Some(i) = position.lots.iter().position(|lot| lot.wallet == wallet) else {
+                    break;
+                };
+                let lot = &mut position.lots[i];
+
+                if lot.tokens <= left {
+                    left -= lot.tokens;
+                    cost_basis_usd += lot.cost_usd;
+                    position.lots.remove(i);
+                } else {
+                    let cost = lot.cost_usd * left / lot.tokens;
+                    lot.tokens -= left;
+                    lot.cost_usd -= cost;
+                    cost_basis_usd += cost;
+                    left = 0.0;
+                }
+            }
+        }
+
+        let remaining_tokens = match self.positions.get(&key) {
+            Some(position) if !position.lots.is_empty() => position.tokens(),
+            _ => {
+                self.positions.remove(&key);
+                0.0
+            }
+        };
+
+        let trade = RealizedTrade {
+            contract: key,
+            symbol: String::from(symbol),
+            tokens,
+            proceeds_usd,
+            cost_basis_usd,
+            unmatched_tokens: left,
+            remaining_tokens,
+            closed_at: Utc::now(),
+        };
+
+        self.realized.push(trade.clone());
+        trade
+    }
+
+    pub fn contracts(&self) -> Vec<String> {
+        self.positions.keys().cloned().collect()
+    }
+
+    pub fn position(&self, contract: &str) -> Option<&Position> {
+        self.positions.get(&contract.to_lowercase())
+    }
+
+    pub fn stats(&self) -> TradeStats {
+        let mut stats = TradeStats {
+            open_positions: self.positions.len(),
+            open_cost_usd: self.positions.values().map(|p| p.cost_usd()).sum(),
+            ..Default::default()
+        };
+
+        for trade in &self.realized {
+            let pnl = trade.pnl_usd();
+
+            stats.sells += 1;
+            stats.realized_pnl_usd += pnl;
+            if pnl >= 0.0 {
+                stats.wins += 1;
+            } else {
+                stats.losses += 1;
+            }
+
+            if stats
+                .best
+                .as_ref()
+                .map_or(true, |best| pnl > best.pnl_usd())
+            {
+                stats.best = Some(trade.clone());
+            }
+            if stats
+                .worst
+                .as_ref()
+                .map_or(true, |worst| pnl < worst.pnl_usd())
+            {
+                stats.worst = Some(trade.clone());
+            }
+        }
+
+        stats
+    }
+
+    pub fn activity(&self, since: DateTime<Utc>) -> TradeActivity {
+        let mut activity = TradeActivity::default();
+
+        for fill in self.fills.iter().filter(|fill| fill.at >= since) {
+            if fill.is_buy {
+                activity.buys += 1;
+            } else {
+                activity.sells += 1;
+            }
+            activity.volume_usd += fill.amount_usd;
+        }
+
+        let realized: Vec<&RealizedTrade> = self
+            .realized
+            .iter()
+            .filter(|trade| trade.closed_at >= since)
+            .collect();
+        activity.realized_pnl_usd = realized.iter().map(|trade| trade.pnl_usd()).sum();
+        activity.best = realized
+            .iter()
+            .max_by(|a, b| a.pnl_usd().total_cmp(&b.pnl_usd()))
+            .map(|trade| (*trade).clone());
+        activity.worst = realized
+            .iter()
+            .min_by(|a, b| a.pnl_usd().total_cmp(&b.pnl_usd()))
+            .map(|trade| (*trade).clone());
+
+        activity
+    }
+}
+
+// None for a fresh buy, otherwise why adding to the token needs an extra confirmation
+pub fn duplicate_buy_warning(
+    position: Option<&Position>,
+    sniped_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let mut reasons = vec![];
+
+    if let Some(position) = position.filter(|position| position.tokens() > 0.0) {
+        reasons.push(format!(
+            "💼 You already hold {:.2} {} bought for ${:.2} (entry ${})",
+            position.tokens(),
+            position.symbol,
+            position.cost_usd(),
+            position.entry_price().unwrap_or_default()
+        ));
+    }
+    if let Some(sniped_at) = sniped_at {
+        reasons.push(format!(
+            "🎯 Its launch was auto-sniped {} minutes ago",
+            (now - sniped_at).num_minutes()
+        ));
+    }
+
+    (!reasons.is_empty()).then(|| {
+        format!(
+            "⚠️ This buy adds to an existing position\n\n{}",
+            reasons.join("\n")
+        )
+    })
+}
+
+#[test]
+fn test_fifo_realized_pnl() {
+    let mut ledger = Ledger::default();
+    ledger.buy("0xABC", "PEPE", 100.0, 100.0, "0xWALLET");
+    ledger.buy("0xabc", "PEPE", 100.0, 200.0, "0xwallet");
+
+    // first lot fully and half of the second one
+    let trade = ledger.sell("0xabc", "PEPE", 150.0, 450.0, "0xwallet");
+    assert_eq!(trade.cost_basis_usd, 200.0);
+    assert_eq!(trade.pnl_usd(), 250.0);
+    assert_eq!(trade.remaining_tokens, 50.0);
+
+    // only 50 tokens are left, the other 50 have no cost basis
+    let trade = ledger.sell("0xabc", "PEPE", 100.0, 100.0, "0xwallet");
+    assert_eq!(trade.unmatched_tokens, 50.0);
+    assert_eq!(trade.pnl_usd(), -50.0);
+    assert!(ledger.position("0xabc").is_none());
+
+    let stats = ledger.stats();
+    assert_eq!((stats.sells, stats.wins, stats.losses), (2, 1, 1));
+    assert_eq!(stats.realized_pnl_usd, 200.0);
+
+    let activity = ledger.activity(Utc::now() - Duration::try_days(1).unwrap());
+    assert_eq!((activity.buys, activity.sells), (2, 2));
+    assert_eq!(activity.volume_usd, 850.0);
+    assert_eq!(activity.realized_pnl_usd, 200.0);
+    assert_eq!(activity.best.map(|trade| trade.pnl_usd()), Some(250.0));
+    assert_eq!(activity.worst.map(|trade| trade.pnl_usd()), Some(-50.0));
+    let later = Utc::now() + Duration::try_seconds(1).unwrap();
+    assert_eq!(ledger.activity(later).buys, 0);
+}
+
+#[test]
+fn test_sell_matches_selling_wallet_lots() {
+    let mut ledger = Ledger::default();
+    ledger.buy("0xabc", "PEPE", 100.0, 100.0, "0xfirst");
+    ledger.buy("0xabc", "PEPE", 100.0, 300.0, "0xsecond");
+    assert_eq!(ledger.position("0xabc").unwrap().wallet(), Some("0xfirst"));
+
+    // the older lot of the other wallet is skipped
+    let trade = ledger.sell("0xabc", "PEPE", 100.0, 400.0, "0xsecond");
+    assert_eq!(trade.cost_basis_usd, 300.0);
+    assert_eq!(trade.remaining_tokens, 100.0);
+    assert_eq!(ledger.position("0xabc").unwrap().wallet(), Some("0xfirst"));
+}
+
+#[test]
+fn test_duplicate_buy_warning() {
+    let now = Utc::now();
+    let mut ledger = Ledger::default();
+    assert!(duplicate_buy_warning(ledger.position("0xabc"), None, now).is_none());
+
+    ledger.buy("0xabc", "PEPE", 200.0, 100.0, "0xwallet");
+    let position = ledger.position("0xabc").unwrap();
+    assert_eq!(position.entry_price(), Some(0.5));
+
+    let warning = duplicate_buy_warning(Some(position), None, now).unwrap();
+    assert!(warning.contains("200.00 PEPE bought for $100.00 (entry $0.5)"));
+
+    let sniped_at = now - Duration::try_minutes(12).unwrap();
+    let warning = duplicate_buy_warning(None, Some(sniped_at), now).unwrap();
+    assert!(warning.contains("auto-sniped 12 minutes ago"));
+}
@@ -0,0 +1,218 @@
+use crate::crypto::abi;
+use k256::ecdsa::{signature::Error, SigningKey};
+
+const CHAIN_ID: u128 = 1;
+// EIP-2718 type byte of EIP-1559 transactions
+const EIP1559_TYPE: u8 = 0x02;
+
+/*
+
+Almost every transaction is signed by the wallet that sends it, through
+WalletConnect or a Safe. Flashbots bundles are the exception, the relay only
+takes transactions that are already signed, so the few the bot signs itself
+are RLP encoded and signed here.
+
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rlp {
+    Bytes(Vec<u8>),
+    List(Vec<Rlp>),
+}
+
+impl Rlp {
+    // integers are big endian without leading zeros, zero is the empty string
+    pub fn uint(value: u128) -> Rlp {
+        Rlp::Bytes(
+            value
+                .to_be_bytes()
+                .into_iter()
+                .skip_while(|byte| *byte == 0)
+                .collect(),
+        )
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Rlp::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => bytes.clone(),
+            Rlp::Bytes(bytes) => {
+                let mut encoded = length_prefix(0x80, bytes.len());
+                encoded.extend(bytes);
+                encoded
+            }
+            Rlp::List(items) => {
+                let payload: Vec<u8> = items.iter().flat_map(Rlp::encode).collect();
+                let mut encoded = length_prefix(0xc0, payload.len());
+                encoded.extend(payload);
+                encoded
+            }
+        }
+    }
+}
+
+// short payloads have the length in the prefix, longer ones are followed by their length's bytes
+fn length_prefix(offset: u8, length: usize) -> Vec<u8> {
+    if length < 56 {
+        return vec![offset + length as u8];
+    }
+
+    let length_bytes: Vec<u8> = length
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|byte| *byte == 0)
+        .collect();
+    let mut prefix = vec![offset + 55 + length_bytes.len() as u8];
+    prefix.extend(length_bytes);
+    prefix
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eip1559Transaction {
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u128,
+    pub to: String,
+    pub value: u128,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedTransaction {
+    // hex encoded, what eth_sendRawTransaction and eth_sendBundle take
+    pub raw: String,
+    pub hash: String,
+}
+
+impl Eip1559Transaction {
+    // the access list is always empty
+    fn fields(&self) -> Vec<Rlp> {
+        vec![
+            Rlp::uint(CHAIN_ID),
+            Rlp::uint(self.nonce as u128),
+            Rlp::uint(self.max_priority_fee_per_gas),
+            Rlp::uint(self.max_fee_per_gas),
+            Rlp::uint(self.gas_limit),
+            Rlp::Bytes(abi::from_hex(&self.to)),
+            Rlp::uint(self.value),
+            Rlp::Bytes(abi::from_hex(&self.data)),
+            Rlp::List(vec![]),
+        ]
+    }
+
+    fn typed(fields: Vec<Rlp>) -> Vec<u8> {
+        let mut typed = vec![EIP1559_TYPE];
+        typed.extend(Rlp::List(fields).encode());
+        typed
+    }
+
+    pub fn signing_hash(&self) -> Vec<u8> {
+        abi::keccak256(&Eip1559Transaction::typed(self.fields()))
+    }
+
+    pub fn sign(&self, key: &SigningKey) -> Result<SignedTransaction, Error> {
+        let (signature, recovery_id) = key.sign_prehash_recoverable(&self.signing_hash())?;
+        let (r, s) = signature.split_bytes();
+
+        let mut fields = self.fields();
+        fields.push(Rlp::uint(recovery_id.to_byte() as u128));
+        fields.push(Rlp::Bytes(r.into_iter().skip_while(|b| *b == 0).collect()));
+        fields.push(Rlp::Bytes(s.into_iter().skip_while(|b| *b == 0).collect()));
+        let raw = Eip1559Transaction::typed(fields);
+
+        Ok(SignedTransaction {
+            hash: abi::to_hex(&abi::keccak256(&raw)),
+            raw: abi::to_hex(&raw),
+        })
+    }
+}
+
+// EIP-191 personal_sign of the message, r, s and v with v as 27 or 28
+pub fn sign_message(key: &SigningKey, message: &[u8]) -> Result<String, Error> {
+    let mut prefixed = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    prefixed.extend(message);
+    let (signature, recovery_id) = key.sign_prehash_recoverable(&abi::keccak256(&prefixed))?;
+
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(27 + recovery_id.to_byte());
+
+    Ok(abi::to_hex(&bytes))
+}
+
+#[test]
+fn test_rlp() {
+    let bytes = |text: &str| Rlp::Bytes(text.as_bytes().to_vec());
+
+    assert_eq!(bytes("dog").encode(), vec![0x83, b'd', b'o', b'g']);
+    assert_eq!(
+        Rlp::List(vec![bytes("cat"), bytes("dog")]).encode(),
+        vec![0xc8, 0x83, b'c', b'a', b't', 0x83, b'd', b'o', b'g']
+    );
+    assert_eq!(Rlp::uint(0).encode(), vec![0x80]);
+    assert_eq!(Rlp::uint(15).encode(), vec![0x0f]);
+    assert_eq!(Rlp::uint(1024).encode(), vec![0x82, 0x04, 0x00]);
+    assert_eq!(Rlp::List(vec![]).encode(), vec![0xc0]);
+
+    let long = "Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+    let encoded = bytes(long).encode();
+    assert_eq!(encoded[..2], [0xb8, 0x38]);
+    assert_eq!(encoded.len(), 2 + long.len());
+}
+
+#[test]
+fn test_sign_eip1559_transaction() {
+    // the well known first hardhat account
+    let key = SigningKey::from_slice(&abi::from_hex(
+        "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+    ))
+    .unwrap();
+    let transaction = Eip1559Transaction {
+        nonce: 7,
+        max_priority_fee_per_gas: 2_000_000_000,
+        max_fee_per_gas: 40_000_000_000,
+        gas_limit: 300_000,
+        to: String::from("0x7a250d5630b4cf539739df2c5dacb4c659f2488d"),
+        value: 10u128.pow(17),
+        data: String::from("0xb6f9de95"),
+    };
+
+    let signed = transaction.sign(&key).unwrap();
+    assert!(signed.raw.starts_with("0x02f8"));
+    assert_eq!(signed.hash.len(), 66);
+
+    // the last three fields are the signature of the unsigned fields' hash
+    let raw = abi::from_hex(&signed.raw);
+    let mut rest = &raw[1..];
+    rest = &rest[1 + (rest[0] - 0xf7) as usize..];
+    let mut fields = vec![];
+    while !rest.is_empty() {
+        let (start, length) = match rest[0] {
+            0..=0x7f => (0, 1),
+            byte @ 0x80..=0xb7 => (1, (byte - 0x80) as usize),
+            byte @ 0xc0..=0xf7 => (1, (byte - 0xc0) as usize),
+            byte => {
+                let bytes = (byte - 0xb7) as usize;
+                let length = rest[1..1 + bytes]
+                    .iter()
+                    .fold(0, |length, byte| length * 256 + *byte as usize);
+                (1 + bytes, length)
+            }
+        };
+        fields.push(rest[start..start + length].to_vec());
+        rest = &rest[start + length..];
+    }
+    assert_eq!(fields.len(), 12);
+
+    let word = |bytes: &[u8]| [vec![0; 32 - bytes.len()], bytes.to_vec()].concat();
+    let recovered = k256::ecdsa::VerifyingKey::recover_from_prehash(
+        &transaction.signing_hash(),
+        &k256::ecdsa::Signature::from_slice(&[word(&fields[10]), word(&fields[11])].concat())
+            .unwrap(),
+        k256::ecdsa::RecoveryId::from_byte(fields[9].first().copied().unwrap_or(0)).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(&recovered, key.verifying_key());
+
+    let message_signature = abi::from_hex(&sign_message(&key, b"0xabc").unwrap());
+    assert_eq!(message_signature.len(), 65);
+    assert!(matches!(message_signature[64], 27 | 28));
+}
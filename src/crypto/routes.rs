@@ -0,0 +1,216 @@
+use crate::{
+    api::{self, HoneypotTokenInfo, WETH_ADDRESS},
+    crypto::abi::{self, AbiToken},
+    utils::address_from_word,
+};
+use core::fmt;
+
+const UNISWAP_V2_FACTORY: &str = "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f";
+// every Uniswap V2 pool charges the same fee
+const V2_FEE_PERCENT: f64 = 0.3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteHop {
+    pub pool: String,
+    pub token_in: String,
+    pub symbol_in: String,
+    pub token_out: String,
+    pub symbol_out: String,
+    pub fee_percent: f64,
+}
+
+// the pools a swap goes through, in trading order
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapRoute {
+    pub hops: Vec<RouteHop>,
+}
+
+impl SwapRoute {
+    pub fn is_direct(&self) -> bool {
+        self.hops.len() == 1
+    }
+
+    // token addresses in the order the router swaps them
+    pub fn path(&self) -> Vec<String> {
+        let mut path: Vec<String> = self.hops.iter().map(|hop| hop.token_in.clone()).collect();
+        if let Some(last) = self.hops.last() {
+            path.push(last.token_out.clone());
+        }
+
+        path
+    }
+
+    // every hop takes its fee from what the previous hops left
+    pub fn total_fee_percent(&self) -> f64 {
+        let kept = self
+            .hops
+            .iter()
+            .fold(1.0, |kept, hop| kept * (1.0 - hop.fee_percent / 100.0));
+
+        (1.0 - kept) * 100.0
+    }
+
+    // the same pools the other way around, sell routes end with WETH and buy routes start with it
+    pub fn reversed(&self) -> SwapRoute {
+        SwapRoute {
+            hops: self
+                .hops
+                .iter()
+                .rev()
+                .map(|hop| RouteHop {
+                    pool: hop.pool.clone(),
+                    token_in: hop.token_out.clone(),
+                    symbol_in: hop.symbol_out.clone(),
+                    token_out: hop.token_in.clone(),
+                    symbol_out: hop.symbol_in.clone(),
+                    fee_percent: hop.fee_percent,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for SwapRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut symbols: Vec<&str> = self.hops.iter().map(|hop| hop.symbol_in.as_str()).collect();
+        if let Some(last) = self.hops.last() {
+            symbols.push(&last.symbol_out);
+        }
+
+        write!(
+            f,
+            "{} ({:.2}% fees)",
+            symbols.join(" → "),
+            self.total_fee_percent()
+        )?;
+
+        for hop in &self.hops {
+            write!(
+                f,
+                "\n   • {} → {}: {}% fee ({}...{})",
+                hop.symbol_in,
+                hop.symbol_out,
+                hop.fee_percent,
+                &hop.pool[..6],
+                &hop.pool[hop.pool.len() - 4..]
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// the Uniswap V2 pool of two tokens, None if nobody created one
+async fn v2_pool(token_a: &str, token_b: &str) -> Result<Option<String>, reqwest::Error> {
+    let pool = api::eth_call(
+        String::from(UNISWAP_V2_FACTORY),
+        abi::function_call(
+            "getPair(address,address)",
+            &[
+                AbiToken::Address(token_a.to_owned()),
+                AbiToken::Address(token_b.to_owned()),
+            ],
+        ),
+    )
+    .await?;
+
+    Ok(address_from_word(&pool))
+}
+
+// sells go through the pair honeypot.is found, tokens paired with something other than WETH
+// take a second hop into WETH. direct_only skips the intermediate token, which is how tokens
+// paired with a fake intermediate token hide their real liquidity, and uses the token's own
+// WETH pool instead. The route is swapped through the Uniswap V2 router, so every hop is the
+// Uniswap V2 pool of its tokens even when honeypot.is found e.g. a V3 pool. None if there's no
+// V2 route into WETH
+pub async fn find_sell_route(
+    token: &HoneypotTokenInfo,
+    direct_only: bool,
+) -> Result<Option<SwapRoute>, reqwest::Error> {
+    let hop = |pool: String, token_in: &str, symbol_in: &str, token_out: &str, symbol_out: &str| {
+        RouteHop {
+            pool,
+            token_in: token_in.to_lowercase(),
+            symbol_in: symbol_in.to_owned(),
+            token_out: token_out.to_lowercase(),
+            symbol_out: symbol_out.to_owned(),
+            fee_percent: V2_FEE_PERCENT,
+        }
+    };
+
+    if token.pair_token == WETH_ADDRESS || direct_only {
+        return Ok(v2_pool(&token.contract_address, WETH_ADDRESS)
+            .await?
+            .map(|pool| SwapRoute {
+                hops: vec![hop(
+                    pool,
+                    &token.contract_address,
+                    &token.symbol,
+                    WETH_ADDRESS,
+                    "WETH",
+                )],
+            }));
+    }
+
+    let (Some(token_pool), Some(weth_pool)) = (
+        v2_pool(&token.contract_address, &token.pair_token).await?,
+        v2_pool(&token.pair_token, WETH_ADDRESS).await?,
+    ) else {
+        return Ok(None);
+    };
+
+    Ok(Some(SwapRoute {
+        hops: vec![
+            hop(
+                token_pool,
+                &token.contract_address,
+                &token.symbol,
+                &token.pair_token,
+                &token.pair_symbol,
+            ),
+            hop(
+                weth_pool,
+                &token.pair_token,
+                &token.pair_symbol,
+                WETH_ADDRESS,
+                "WETH",
+            ),
+        ],
+    }))
+}
+
+#[test]
+fn test_swap_route() {
+    let route = SwapRoute {
+        hops: vec![
+            RouteHop {
+                pool: String::from("0x1111111111111111111111111111111111111111"),
+                token_in: String::from("0xaaaa"),
+                symbol_in: String::from("PEPE"),
+                token_out: String::from("0xbbbb"),
+                symbol_out: String::from("USDC"),
+                fee_percent: 1.0,
+            },
+            RouteHop {
+                pool: String::from("0x2222222222222222222222222222222222222222"),
+                token_in: String::from("0xbbbb"),
+                symbol_in: String::from("USDC"),
+                token_out: String::from("0xcccc"),
+                symbol_out: String::from("WETH"),
+                fee_percent: 0.05,
+            },
+        ],
+    };
+
+    assert!(!route.is_direct());
+    assert_eq!(route.path(), vec!["0xaaaa", "0xbbbb", "0xcccc"]);
+    assert!((route.total_fee_percent() - 1.0495).abs() < 1e-9);
+    assert_eq!(
+        route.to_string(),
+        "PEPE → USDC → WETH (1.05% fees)\n   • PEPE → USDC: 1% fee (0x1111...1111)\n   • USDC → WETH: 0.05% fee (0x2222...2222)"
+    );
+
+    let buy_route = route.reversed();
+    assert_eq!(buy_route.path(), vec!["0xcccc", "0xbbbb", "0xaaaa"]);
+    assert_eq!(buy_route.hops[0].symbol_in, "WETH");
+}
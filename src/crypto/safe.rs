@@ -0,0 +1,216 @@
+use crate::{
+    api::SafeProposal,
+    crypto::{
+        abi::{self, AbiToken},
+        trading::TransactionRequest,
+        wallet,
+    },
+    utils::to_checksum_address,
+};
+use k256::ecdsa::{signature::Error, SigningKey};
+
+// MultiSendCallOnly v1.3.0, batches calls into one Safe transaction without allowing nested delegate calls
+const MULTI_SEND_CALL_ONLY: &str = "0x40A2aCCbd92BCA938b02010E17A5b8929b49130D";
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+const CHAIN_ID: u128 = 1;
+const DOMAIN_TYPE: &str = "EIP712Domain(uint256 chainId,address verifyingContract)";
+const SAFE_TX_TYPE: &str = "SafeTx(address to,uint256 value,bytes data,uint8 operation,uint256 safeTxGas,uint256 baseGas,uint256 gasPrice,address gasToken,address refundReceiver,uint256 nonce)";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SafeOperation {
+    Call = 0,
+    DelegateCall = 1,
+}
+
+// gas is paid by the executing owner, so the refund fields are always zero
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafeTransaction {
+    pub to: String,
+    pub value: u128,
+    pub data: String,
+    pub operation: SafeOperation,
+    pub nonce: u64,
+}
+
+impl SafeTransaction {
+    // several calls are batched with MultiSendCallOnly, so e.g. approve and swap execute atomically
+    pub fn from_requests(requests: &[TransactionRequest], nonce: u64) -> SafeTransaction {
+        if let [request] = requests {
+            return SafeTransaction {
+                to: request.to.clone(),
+                value: request.value,
+                data: request.data.clone(),
+                operation: SafeOperation::Call,
+                nonce,
+            };
+        }
+
+        // every call is packed as operation (1 byte), to (20), value (32), data length (32), data
+        let transactions: Vec<u8> = requests
+            .iter()
+            .flat_map(|request| {
+                let data = abi::from_hex(&request.data);
+                let mut packed = vec![SafeOperation::Call as u8];
+                packed.extend(abi::from_hex(&request.to));
+                packed.extend(abi::encode(&[
+                    AbiToken::Uint(request.value),
+                    AbiToken::Uint(data.len() as u128),
+                ]));
+                packed.extend(data);
+                packed
+            })
+            .collect();
+
+        SafeTransaction {
+            to: String::from(MULTI_SEND_CALL_ONLY),
+            value: 0,
+            data: abi::function_call("multiSend(bytes)", &[AbiToken::Bytes(transactions)]),
+            operation: SafeOperation::DelegateCall,
+            nonce,
+        }
+    }
+
+    // the Safe app's rejection, an empty call to the Safe itself that uses up the proposal's nonce
+    pub fn rejection(safe: &str, nonce: u64) -> SafeTransaction {
+        SafeTransaction {
+            to: safe.to_owned(),
+            value: 0,
+            data: String::from("0x"),
+            operation: SafeOperation::Call,
+            nonce,
+        }
+    }
+
+    // EIP-712 hash the owners confirm, the Safe Transaction Service indexes transactions by it
+    pub fn hash(&self, safe: &str) -> String {
+        let domain_separator = abi::keccak256(&abi::encode(&[
+            AbiToken::FixedBytes(abi::keccak256(DOMAIN_TYPE.as_bytes())),
+            AbiToken::Uint(CHAIN_ID),
+            AbiToken::Address(safe.to_owned()),
+        ]));
+        let struct_hash = abi::keccak256(&abi::encode(&[
+            AbiToken::FixedBytes(abi::keccak256(SAFE_TX_TYPE.as_bytes())),
+            AbiToken::Address(self.to.clone()),
+            AbiToken::Uint(self.value),
+            AbiToken::FixedBytes(abi::keccak256(&abi::from_hex(&self.data))),
+            AbiToken::Uint(self.operation as u128),
+            AbiToken::Uint(0),
+            AbiToken::Uint(0),
+            AbiToken::Uint(0),
+            AbiToken::Address(String::from(ZERO_ADDRESS)),
+            AbiToken::Address(String::from(ZERO_ADDRESS)),
+            AbiToken::Uint(self.nonce as u128),
+        ]));
+
+        let mut message = vec![0x19, 0x01];
+        message.extend(domain_separator);
+        message.extend(struct_hash);
+
+        abi::to_hex(&abi::keccak256(&message))
+    }
+
+    // the Transaction Service only accepts proposals signed by an owner or delegate of the Safe
+    pub fn proposal(&self, safe: &str, proposer: &SigningKey) -> Result<SafeProposal, Error> {
+        let safe_tx_hash = self.hash(safe);
+
+        Ok(SafeProposal {
+            to: to_checksum_address(&self.to),
+            value: self.value.to_string(),
+            data: self.data.clone(),
+            operation: self.operation as u8,
+            safe_tx_gas: String::from("0"),
+            base_gas: String::from("0"),
+            gas_price: String::from("0"),
+            gas_token: String::from(ZERO_ADDRESS),
+            refund_receiver: String::from(ZERO_ADDRESS),
+            nonce: self.nonce,
+            signature: sign(&safe_tx_hash, proposer)?,
+            contract_transaction_hash: safe_tx_hash,
+            sender: to_checksum_address(&wallet::address_of(proposer)),
+            origin: String::from("snipers"),
+        })
+    }
+}
+
+// r, s and v of an ECDSA signature of the hash, v is 27 or 28 like Safe expects for an EOA owner
+fn sign(safe_tx_hash: &str, key: &SigningKey) -> Result<String, Error> {
+    let (signature, recovery_id) = key.sign_prehash_recoverable(&abi::from_hex(safe_tx_hash))?;
+
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(27 + recovery_id.to_byte());
+
+    Ok(abi::to_hex(&bytes))
+}
+
+// the transaction's page in the Safe web app, where the other owners confirm and execute it
+pub fn app_url(safe: &str, safe_tx_hash: &str) -> String {
+    let safe = to_checksum_address(safe);
+
+    format!(
+        "https://app.safe.global/transactions/tx?safe=eth:{}&id=multisig_{}_{}",
+        safe, safe, safe_tx_hash
+    )
+}
+
+#[test]
+fn test_safe_transaction() {
+    let request = |to: &str, data: &str| TransactionRequest {
+        to: to.to_owned(),
+        data: data.to_owned(),
+        value: 0,
+        description: String::new(),
+    };
+    let approve = request("0x6982508145454ce325ddbe47a25d4ec3d2311933", "0x095ea7b3");
+    let swap = request("0x7a250d5630b4cf539739df2c5dacb4c659f2488d", "0x791ac947");
+
+    let single = SafeTransaction::from_requests(&[approve.clone()], 3);
+    assert_eq!(single.operation, SafeOperation::Call);
+    assert_eq!(
+        (single.to.as_str(), single.data.as_str()),
+        (approve.to.as_str(), "0x095ea7b3")
+    );
+
+    // two packed calls of 1 + 20 + 32 + 32 + 4 bytes each
+    let batched = SafeTransaction::from_requests(&[approve, swap], 3);
+    assert_eq!(batched.operation, SafeOperation::DelegateCall);
+    assert_eq!(batched.to, MULTI_SEND_CALL_ONLY);
+    assert!(batched.data.starts_with("0x8d80ff0a"));
+    assert_eq!(abi::from_hex(&batched.data).len(), 4 + 32 + 32 + 2 * 96);
+
+    let rejection = SafeTransaction::rejection("0x5afe3855358e112b5647b952709e6165e1c1eeee", 3);
+    assert_eq!(
+        (rejection.value, rejection.data.as_str(), rejection.nonce),
+        (0, "0x", 3)
+    );
+
+    let hash = batched.hash("0x5afe3855358e112b5647b952709e6165e1c1eeee");
+    assert_eq!(hash.len(), 66);
+    assert_ne!(
+        hash,
+        single.hash("0x5afe3855358e112b5647b952709e6165e1c1eeee")
+    );
+
+    // the well known first hardhat account
+    let proposer = SigningKey::from_slice(&abi::from_hex(
+        "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+    ))
+    .unwrap();
+    let proposal = batched
+        .proposal("0x5afe3855358e112b5647b952709e6165e1c1eeee", &proposer)
+        .unwrap();
+    assert_eq!(
+        proposal.sender,
+        "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+    );
+    assert_eq!(proposal.contract_transaction_hash, hash);
+
+    let signature = abi::from_hex(&proposal.signature);
+    assert_eq!(signature.len(), 65);
+    let recovered = k256::ecdsa::VerifyingKey::recover_from_prehash(
+        &abi::from_hex(&hash),
+        &k256::ecdsa::Signature::from_slice(&signature[..64]).unwrap(),
+        k256::ecdsa::RecoveryId::from_byte(signature[64] - 27).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(&recovered, proposer.verifying_key());
+}
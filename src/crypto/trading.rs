@@ -1 +1,1278 @@
+use crate::{
+    api::{self, AlchemyTransaction, WETH_ADDRESS},
+    crypto::{
+        abi::{self, AbiToken},
+        routes::SwapRoute,
+    },
+    utils::{hex_to_decimal, uint_from_word},
+};
+use chrono::{Duration, Utc};
+use core::fmt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{env, str::FromStr};
 
+// used when a transaction can't be simulated yet, e.g. a swap that reverts until its approval is mined
+// swap values are based on cryptoneur.xyz/en/gas-fees-calculator
+pub const TYPICAL_V2_SWAP_GAS: u128 = 152_809;
+pub const TYPICAL_V3_SWAP_GAS: u128 = 184_523;
+const TYPICAL_APPROVE_GAS: u128 = 46_000;
+// extra gas on top of the estimate so the transaction doesn't run out of it
+const GAS_BUFFER_PERCENT: u128 = 3;
+
+// name, description and gas units of the actions /gas prices, GAS_ACTIONS can add or override them
+const GAS_ACTIONS: [(&str, &str, u128); 6] = [
+    ("transfer", "ETH transfer", TRANSFER_GAS_LIMIT),
+    ("erc20", "ERC-20 transfer", 65_000),
+    ("approve", "ERC-20 approval", TYPICAL_APPROVE_GAS),
+    ("v2swap", "Uniswap V2 swap", TYPICAL_V2_SWAP_GAS),
+    ("v3swap", "Uniswap V3 swap", TYPICAL_V3_SWAP_GAS),
+    ("nft", "NFT transfer", 85_000),
+];
+// percent of the current gas price paid by each fee tier
+pub const GAS_TIERS: [(&str, u128); 3] = [("🐢 Slow", 90), ("🚗 Standard", 100), ("🚀 Fast", 125)];
+
+// nodes only accept a replacement transaction if its fees are at least 10% higher
+const MIN_FEE_BUMP_PERCENT: u128 = 10;
+// cancelling is a plain eth transfer
+const TRANSFER_GAS_LIMIT: u128 = 21_000;
+
+const UNISWAP_V2_ROUTER: &str = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D";
+const USDC_ADDRESS: &str = "0xA0b86991c6218b36c1d19D4a2E9Eb0cE3606eB48";
+const USDC_DECIMALS: i32 = 6;
+const UNIVERSAL_ROUTER: &str = "0x3fC91A3afd70395Cd496C647d5a6CC9D4B2b7FAD";
+const PERMIT2: &str = "0x000000000022D473030F116dDEE9F6B43aC78BA3";
+// Universal Router placeholder recipient meaning the router itself
+const ROUTER_ADDRESS_THIS: &str = "0x0000000000000000000000000000000000000002";
+
+// a swap that reverted on slippage is offered again with the token's tax plus this buffer
+const RETRY_SLIPPAGE_BUFFER: f32 = 3.0;
+const DEFAULT_MAX_RETRY_SLIPPAGE: f32 = 25.0;
+
+// Universal Router command bytes
+const V2_SWAP_EXACT_IN: u8 = 0x08;
+const PERMIT2_PERMIT: u8 = 0x0a;
+const UNWRAP_WETH: u8 = 0x0c;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplacementType {
+    SpeedUp,
+    Cancel,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplacementTransaction {
+    pub replacement_type: ReplacementType,
+    pub nonce: u64,
+    pub to: String,
+    pub value: u128,
+    pub input: String,
+    pub gas_limit: u128,
+    pub old_max_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub old_max_priority_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl fmt::Display for ReplacementTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let title = match self.replacement_type {
+            ReplacementType::SpeedUp => "⏫ Speed up",
+            ReplacementType::Cancel => "🚫 Cancel",
+        };
+
+        write!(
+            f,
+            "{}\n\n🔢 Nonce: {}\n📄 To: {}\n⛽ Max fee: {:.2} gwei (was {:.2} gwei)\n💡 Priority fee: {:.2} gwei (was {:.2} gwei)",
+            title,
+            self.nonce,
+            self.to,
+            self.max_fee_per_gas as f64 / 10.0f64.powf(9.0),
+            self.old_max_fee_per_gas as f64 / 10.0f64.powf(9.0),
+            self.max_priority_fee_per_gas as f64 / 10.0f64.powf(9.0),
+            self.old_max_priority_fee_per_gas as f64 / 10.0f64.powf(9.0),
+        )
+    }
+}
+
+// resubmits the same transaction with the same nonce and higher fees
+pub fn speed_up(transaction: &AlchemyTransaction, gas_price: u128) -> ReplacementTransaction {
+    let (max_fee, priority_fee) = transaction_fees(transaction);
+
+    ReplacementTransaction {
+        replacement_type: ReplacementType::SpeedUp,
+        nonce: hex_to_decimal(&transaction.nonce) as u64,
+        to: transaction.to.clone().unwrap_or_default(),
+        value: hex_to_decimal(&transaction.value),
+        input: transaction.input.clone(),
+        gas_limit: hex_to_decimal(&transaction.gas),
+        old_max_fee_per_gas: max_fee,
+        max_fee_per_gas: bump_fee(max_fee, gas_price),
+        old_max_priority_fee_per_gas: priority_fee,
+        max_priority_fee_per_gas: bump_fee(priority_fee, 0),
+    }
+}
+
+// replaces the transaction with a zero value self-transfer using the same nonce
+pub fn cancel(transaction: &AlchemyTransaction, gas_price: u128) -> ReplacementTransaction {
+    let (max_fee, priority_fee) = transaction_fees(transaction);
+
+    ReplacementTransaction {
+        replacement_type: ReplacementType::Cancel,
+        nonce: hex_to_decimal(&transaction.nonce) as u64,
+        to: transaction.from.clone(),
+        value: 0,
+        input: String::from("0x"),
+        gas_limit: TRANSFER_GAS_LIMIT,
+        old_max_fee_per_gas: max_fee,
+        max_fee_per_gas: bump_fee(max_fee, gas_price),
+        old_max_priority_fee_per_gas: priority_fee,
+        max_priority_fee_per_gas: bump_fee(priority_fee, 0),
+    }
+}
+
+// legacy transactions only have a gas price, which acts as both fees
+fn transaction_fees(transaction: &AlchemyTransaction) -> (u128, u128) {
+    let gas_price = transaction
+        .gas_price
+        .as_ref()
+        .map(|price| hex_to_decimal(price))
+        .unwrap_or(0);
+
+    let max_fee = transaction
+        .max_fee_per_gas
+        .as_ref()
+        .map(|fee| hex_to_decimal(fee))
+        .unwrap_or(gas_price);
+    let priority_fee = transaction
+        .max_priority_fee_per_gas
+        .as_ref()
+        .map(|fee| hex_to_decimal(fee))
+        .unwrap_or(gas_price);
+
+    (max_fee, priority_fee)
+}
+
+fn bump_fee(old_fee: u128, current_fee: u128) -> u128 {
+    let min_bump = old_fee * (100 + MIN_FEE_BUMP_PERCENT) / 100 + 1;
+    min_bump.max(current_fee)
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionRequest {
+    pub to: String,
+    pub data: String,
+    pub value: u128,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SellRoute {
+    UniversalRouter,
+    Classic,
+}
+
+// what the eth of a sell ends up as, set per chat with /proceeds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SellProceeds {
+    #[default]
+    Eth,
+    Weth,
+    Usdc,
+}
+
+impl fmt::Display for SellProceeds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SellProceeds::Eth => write!(f, "ETH"),
+            SellProceeds::Weth => write!(f, "WETH"),
+            SellProceeds::Usdc => write!(f, "USDC"),
+        }
+    }
+}
+
+impl FromStr for SellProceeds {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eth" => Ok(SellProceeds::Eth),
+            "weth" => Ok(SellProceeds::Weth),
+            "usdc" => Ok(SellProceeds::Usdc),
+            _ => Err(()),
+        }
+    }
+}
+
+// EIP-712 PermitSingle, signed off-chain instead of sending an approve transaction
+#[derive(Debug, Clone)]
+pub struct PermitSingle {
+    pub token: String,
+    pub amount: u128,
+    pub expiration: u128,
+    pub nonce: u128,
+    pub spender: String,
+    pub sig_deadline: u128,
+}
+
+impl PermitSingle {
+    fn to_abi(&self) -> AbiToken {
+        AbiToken::Tuple(vec![
+            AbiToken::Tuple(vec![
+                AbiToken::Address(self.token.clone()),
+                AbiToken::Uint(self.amount),
+                AbiToken::Uint(self.expiration),
+                AbiToken::Uint(self.nonce),
+            ]),
+            AbiToken::Address(self.spender.clone()),
+            AbiToken::Uint(self.sig_deadline),
+        ])
+    }
+
+    // what the wallet is asked to sign with eth_signTypedData_v4, Permit2's domain has no version
+    pub fn typed_data(&self) -> Value {
+        json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "verifyingContract", "type": "address" },
+                ],
+                "PermitDetails": [
+                    { "name": "token", "type": "address" },
+                    { "name": "amount", "type": "uint160" },
+                    { "name": "expiration", "type": "uint48" },
+                    { "name": "nonce", "type": "uint48" },
+                ],
+                "PermitSingle": [
+                    { "name": "details", "type": "PermitDetails" },
+                    { "name": "spender", "type": "address" },
+                    { "name": "sigDeadline", "type": "uint256" },
+                ],
+            },
+            "primaryType": "PermitSingle",
+            "domain": {
+                "name": "Permit2",
+                "chainId": 1,
+                "verifyingContract": PERMIT2,
+            },
+            "message": {
+                "details": {
+                    "token": self.token,
+                    "amount": self.amount.to_string(),
+                    "expiration": self.expiration.to_string(),
+                    "nonce": self.nonce.to_string(),
+                },
+                "spender": self.spender,
+                "sigDeadline": self.sig_deadline.to_string(),
+            },
+        })
+    }
+}
+
+// what the Universal Router execute transaction needs besides the signed permit
+#[derive(Debug, Clone)]
+struct RouterSwap {
+    owner: String,
+    path: Vec<String>,
+    deadline: u128,
+}
+
+#[derive(Debug, Clone)]
+pub struct SellPlan {
+    pub route: SellRoute,
+    pub amount_in: u128,
+    pub min_eth_out: u128,
+    // the permit the wallet still has to sign, the swap is only added to the transactions with its signature
+    pub permit: Option<PermitSingle>,
+    router_swap: Option<RouterSwap>,
+    pub transactions: Vec<TransactionRequest>,
+    pub proceeds: SellProceeds,
+    // in the units of the proceeds, usdc has 6 decimals
+    pub min_proceeds_out: u128,
+}
+
+impl SellPlan {
+    // the transaction that sells the token, the conversion of its proceeds comes after it
+    pub fn swap(&self) -> Option<&TransactionRequest> {
+        self.through_swap().last()
+    }
+
+    // the approvals and the swap, the swap only goes through after the approvals
+    pub fn through_swap(&self) -> &[TransactionRequest] {
+        let follow_ups = match self.proceeds {
+            SellProceeds::Eth => 0,
+            SellProceeds::Weth | SellProceeds::Usdc => 1,
+        };
+
+        &self.transactions[..self.transactions.len().saturating_sub(follow_ups)]
+    }
+
+    // the Universal Router swap goes between the approvals and the conversion of the proceeds
+    pub fn with_permit_signature(mut self, signature: &[u8]) -> SellPlan {
+        let (Some(permit), Some(swap)) = (self.permit.take(), self.router_swap.take()) else {
+            return self;
+        };
+
+        let execute =
+            universal_router_execute(&permit, signature, &swap, self.amount_in, self.min_eth_out);
+        let position = self.through_swap().len();
+        self.transactions.insert(position, execute);
+        self
+    }
+
+    // only the minimum eth out is converted, whatever the swap returns above it stays eth
+    fn convert_proceeds(
+        mut self,
+        owner: &str,
+        proceeds: SellProceeds,
+        eth_price: f64,
+        slippage: f32,
+        deadline: u128,
+    ) -> SellPlan {
+        let follow_up = match proceeds {
+            SellProceeds::Eth => None,
+            SellProceeds::Weth => {
+                self.min_proceeds_out = self.min_eth_out;
+                Some(TransactionRequest {
+                    to: String::from(WETH_ADDRESS),
+                    data: abi::function_call("deposit()", &[]),
+                    value: self.min_eth_out,
+                    description: String::from("Wrap the proceeds into WETH"),
+                })
+            }
+            SellProceeds::Usdc => {
+                self.min_proceeds_out = (self.min_eth_out as f64 / 10.0f64.powf(18.0)
+                    * eth_price
+                    * (1.0 - slippage as f64 / 100.0)
+                    * 10.0f64.powi(USDC_DECIMALS)) as u128;
+                Some(TransactionRequest {
+                    to: String::from(UNISWAP_V2_ROUTER),
+                    data: abi::function_call(
+                        "swapExactETHForTokens(uint256,address[],address,uint256)",
+                        &[
+                            AbiToken::Uint(self.min_proceeds_out),
+                            AbiToken::Array(vec![
+                                AbiToken::Address(String::from(WETH_ADDRESS)),
+                                AbiToken::Address(String::from(USDC_ADDRESS)),
+                            ]),
+                            AbiToken::Address(owner.to_owned()),
+                            AbiToken::Uint(deadline),
+                        ],
+                    ),
+                    value: self.min_eth_out,
+                    description: String::from("Swap the proceeds for USDC"),
+                })
+            }
+        };
+
+        self.proceeds = proceeds;
+        self.transactions.extend(follow_up);
+        self
+    }
+}
+
+impl fmt::Display for SellPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let route = match self.route {
+            SellRoute::UniversalRouter => "Universal Router + Permit2",
+            SellRoute::Classic => "Classic approve + swap",
+        };
+
+        let min_eth_out = self.min_eth_out as f64 / 10.0f64.powf(18.0);
+        let min_received = match self.proceeds {
+            SellProceeds::Eth => format!("{:.6} ETH", min_eth_out),
+            SellProceeds::Weth => format!("{:.6} WETH", min_eth_out),
+            SellProceeds::Usdc => format!(
+                "{:.6} ETH ➡️ {:.2} USDC",
+                min_eth_out,
+                self.min_proceeds_out as f64 / 10.0f64.powi(USDC_DECIMALS)
+            ),
+        };
+
+        write!(
+            f,
+            "🦄 Route: {} ({} transaction(s))\n💵 Minimum received: {}",
+            route,
+            self.transactions.len() + self.permit.is_some() as usize,
+            min_received
+        )?;
+
+        let through_swap = self.through_swap();
+        for transaction in through_swap {
+            write!(f, "\n   • {}", transaction.description)?;
+        }
+        if self.permit.is_some() {
+            write!(
+                f,
+                "\n   • Sign the Permit2 permit, then permit, swap and unwrap in one transaction"
+            )?;
+        }
+        for transaction in &self.transactions[through_swap.len()..] {
+            write!(f, "\n   • {}", transaction.description)?;
+        }
+
+        Ok(())
+    }
+}
+
+// amounts are estimated from the usd amount using the current token and eth prices,
+// returns None if the token has no price. without a swap route the token is sold straight into WETH.
+// proceeds other than eth are converted by one more transaction after the swap
+// tokens is the exact amount of a sell given in tokens, amount_usd is then only its value when it was given
+pub async fn prepare_sell(
+    contract: &str,
+    owner: &str,
+    amount_usd: f64,
+    tokens: Option<TokenAmount>,
+    slippage: f32,
+    route: SellRoute,
+    swap_route: Option<&SwapRoute>,
+    proceeds: SellProceeds,
+) -> Result<Option<SellPlan>, Box<dyn std::error::Error + Send + Sync>> {
+    let token_price = api::get_token_price(contract.to_owned()).await? as f64;
+    let decimals = api::get_token_info(contract.to_owned()).await?.decimals;
+    let eth_price = api::get_eth_price().await?;
+
+    if token_price <= 0.0 || eth_price <= 0.0 {
+        return Ok(None);
+    }
+
+    let (amount_in, amount_usd) = match tokens {
+        Some(tokens) if tokens.decimals == decimals => (tokens.raw, tokens.tokens() * token_price),
+        Some(tokens) => {
+            return Err(DecimalsMismatch {
+                parsed: tokens.decimals,
+                token: decimals,
+            }
+            .into())
+        }
+        None => (
+            (amount_usd / token_price * 10.0f64.powf(decimals as f64)) as u128,
+            amount_usd,
+        ),
+    };
+    let min_eth_out =
+        (amount_usd / eth_price * (1.0 - slippage as f64 / 100.0) * 10.0f64.powf(18.0)) as u128;
+    let deadline = (Utc::now() + Duration::try_minutes(20).unwrap()).timestamp() as u128;
+    let path = swap_route
+        .map(SwapRoute::path)
+        .unwrap_or_else(|| vec![contract.to_owned(), String::from(WETH_ADDRESS)]);
+
+    let plan = match route {
+        SellRoute::Classic => {
+            classic_sell(contract, owner, &path, amount_in, min_eth_out, deadline)
+        }
+        SellRoute::UniversalRouter => {
+            let permit2_allowance = uint_from_word(
+                &api::eth_call(
+                    contract.to_owned(),
+                    abi::function_call(
+                        "allowance(address,address)",
+                        &[
+                            AbiToken::Address(owner.to_owned()),
+                            AbiToken::Address(String::from(PERMIT2)),
+                        ],
+                    ),
+                )
+                .await?,
+            );
+
+            // Permit2.allowance returns (amount, expiration, nonce)
+            let permit2_state = api::eth_call(
+                String::from(PERMIT2),
+                abi::function_call(
+                    "allowance(address,address,address)",
+                    &[
+                        AbiToken::Address(owner.to_owned()),
+                        AbiToken::Address(contract.to_owned()),
+                        AbiToken::Address(String::from(UNIVERSAL_ROUTER)),
+                    ],
+                ),
+            )
+            .await?;
+            let permit_nonce = permit2_state
+                .trim_start_matches("0x")
+                .get(128..192)
+                .map(uint_from_word)
+                .unwrap_or(0);
+
+            universal_router_sell(
+                contract,
+                owner,
+                &path,
+                amount_in,
+                min_eth_out,
+                deadline,
+                permit2_allowance >= amount_in,
+                permit_nonce,
+            )
+        }
+    };
+
+    Ok(Some(plan.convert_proceeds(
+        owner, proceeds, eth_price, slippage, deadline,
+    )))
+}
+
+// the buy side of prepare_sell, eth is swapped straight into the token through the V2 router
+pub async fn prepare_buy(
+    contract: &str,
+    owner: &str,
+    amount_usd: f64,
+    slippage: f32,
+    swap_route: Option<&SwapRoute>,
+) -> Result<Option<TransactionRequest>, Box<dyn std::error::Error + Send + Sync>> {
+    let token_price = api::get_token_price(contract.to_owned()).await? as f64;
+    let decimals = api::get_token_info(contract.to_owned()).await?.decimals;
+    let eth_price = api::get_eth_price().await?;
+
+    if token_price <= 0.0 || eth_price <= 0.0 {
+        return Ok(None);
+    }
+
+    let value = (amount_usd / eth_price * 10.0f64.powf(18.0)) as u128;
+    let min_tokens_out = (amount_usd / token_price
+        * (1.0 - slippage as f64 / 100.0)
+        * 10.0f64.powf(decimals as f64)) as u128;
+    let deadline = (Utc::now() + Duration::try_minutes(20).unwrap()).timestamp() as u128;
+    let path = swap_route
+        .map(SwapRoute::path)
+        .unwrap_or_else(|| vec![String::from(WETH_ADDRESS), contract.to_owned()]);
+
+    Ok(Some(TransactionRequest {
+        to: String::from(UNISWAP_V2_ROUTER),
+        data: abi::function_call(
+            "swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)",
+            &[
+                AbiToken::Uint(min_tokens_out),
+                AbiToken::Array(
+                    path.iter()
+                        .map(|token| AbiToken::Address(token.clone()))
+                        .collect(),
+                ),
+                AbiToken::Address(owner.to_owned()),
+                AbiToken::Uint(deadline),
+            ],
+        ),
+        value,
+        description: String::from("Swap ETH for tokens"),
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasEstimate {
+    pub gas: u128,
+    pub gas_price: u128,
+    pub eth_price: f64,
+    // false when at least one transaction fell back to a typical gas amount
+    pub simulated: bool,
+}
+
+impl GasEstimate {
+    pub fn cost_eth(&self) -> f64 {
+        (self.gas * self.gas_price) as f64 / 10.0f64.powf(18.0)
+    }
+
+    pub fn cost_usd(&self) -> f64 {
+        self.cost_eth() * self.eth_price
+    }
+}
+
+// how much the price has to move in the trade's favor for it to pay for its own gas
+pub fn breakeven_percent(cost_usd: f64, amount_usd: f64) -> f64 {
+    if amount_usd <= 0.0 {
+        return f64::INFINITY;
+    }
+
+    cost_usd / amount_usd * 100.0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GasAction {
+    pub name: String,
+    pub description: String,
+    pub gas: u128,
+}
+
+pub fn gas_actions() -> Vec<GasAction> {
+    parse_gas_actions(&env::var("GAS_ACTIONS").unwrap_or_default())
+}
+
+// 'bridge=120000,nft=90000', invalid entries are skipped
+fn parse_gas_actions(overrides: &str) -> Vec<GasAction> {
+    let mut actions: Vec<GasAction> = GAS_ACTIONS
+        .iter()
+        .map(|(name, description, gas)| GasAction {
+            name: name.to_string(),
+            description: description.to_string(),
+            gas: *gas,
+        })
+        .collect();
+
+    for (name, gas) in overrides
+        .split(',')
+        .filter_map(|entry| entry.trim().split_once('='))
+        .filter_map(|(name, gas)| {
+            Some((name.trim().to_lowercase(), gas.trim().parse::<u128>().ok()?))
+        })
+        .filter(|(name, _)| !name.is_empty())
+    {
+        match actions.iter_mut().find(|action| action.name == name) {
+            Some(action) => action.gas = gas,
+            None => actions.push(GasAction {
+                description: name.clone(),
+                name,
+                gas,
+            }),
+        }
+    }
+
+    actions
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GasSelection<'a> {
+    Amount(u128),
+    Action(&'a GasAction),
+}
+
+// the /gas argument, a gas amount like '150,000' or the name of an action
+pub fn parse_gas_argument<'a>(arg: &str, actions: &'a [GasAction]) -> Option<GasSelection<'a>> {
+    let arg = arg.to_lowercase();
+
+    match arg.replace([',', '_'], "").parse::<u128>() {
+        Ok(gas) if gas > 0 => Some(GasSelection::Amount(gas)),
+        _ => actions
+            .iter()
+            .find(|action| action.name == arg)
+            .map(GasSelection::Action),
+    }
+}
+
+// the estimate of every fee tier, the gas price is the current one in wei
+pub fn tier_estimates(
+    gas: u128,
+    gas_price: u128,
+    eth_price: f64,
+) -> Vec<(&'static str, GasEstimate)> {
+    GAS_TIERS
+        .iter()
+        .map(|(tier, percent)| {
+            (
+                *tier,
+                GasEstimate {
+                    gas,
+                    gas_price: gas_price * percent / 100,
+                    eth_price,
+                    simulated: false,
+                },
+            )
+        })
+        .collect()
+}
+
+// every transaction is simulated with eth_estimateGas from the wallet that sends it
+pub async fn estimate_transactions_gas(
+    from: &str,
+    transactions: &[TransactionRequest],
+) -> Result<GasEstimate, reqwest::Error> {
+    let gas_price = api::get_eth_gas_wei().await?;
+    let eth_price = api::get_eth_price().await?;
+    let approve_selector = abi::to_hex(&abi::selector("approve(address,uint256)"));
+
+    let mut gas = 0;
+    let mut simulated = true;
+    for transaction in transactions {
+        match api::estimate_gas(
+            from.to_owned(),
+            transaction.to.clone(),
+            transaction.data.clone(),
+            transaction.value,
+        )
+        .await
+        {
+            Ok(estimate) => gas += estimate * (100 + GAS_BUFFER_PERCENT) / 100,
+            Err(e) => {
+                warn!("estimate_gas error, using a typical amount: {}", e);
+                simulated = false;
+                gas += if transaction.data.starts_with(&approve_selector) {
+                    TYPICAL_APPROVE_GAS
+                } else {
+                    TYPICAL_V2_SWAP_GAS
+                };
+            }
+        }
+    }
+
+    Ok(GasEstimate {
+        gas,
+        gas_price,
+        eth_price,
+        simulated,
+    })
+}
+
+fn classic_sell(
+    contract: &str,
+    owner: &str,
+    path: &[String],
+    amount_in: u128,
+    min_eth_out: u128,
+    deadline: u128,
+) -> SellPlan {
+    let approve = TransactionRequest {
+        to: contract.to_owned(),
+        data: abi::function_call(
+            "approve(address,uint256)",
+            &[
+                AbiToken::Address(String::from(UNISWAP_V2_ROUTER)),
+                AbiToken::Uint(amount_in),
+            ],
+        ),
+        value: 0,
+        description: String::from("Approve Uniswap V2 router"),
+    };
+
+    let swap = TransactionRequest {
+        to: String::from(UNISWAP_V2_ROUTER),
+        data: abi::function_call(
+            "swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)",
+            &[
+                AbiToken::Uint(amount_in),
+                AbiToken::Uint(min_eth_out),
+                AbiToken::Array(
+                    path.iter()
+                        .map(|token| AbiToken::Address(token.clone()))
+                        .collect(),
+                ),
+                AbiToken::Address(owner.to_owned()),
+                AbiToken::Uint(deadline),
+            ],
+        ),
+        value: 0,
+        description: String::from("Swap tokens for ETH"),
+    };
+
+    SellPlan {
+        route: SellRoute::Classic,
+        amount_in,
+        min_eth_out,
+        permit: None,
+        router_swap: None,
+        transactions: vec![approve, swap],
+        proceeds: SellProceeds::Eth,
+        min_proceeds_out: min_eth_out,
+    }
+}
+
+fn universal_router_sell(
+    contract: &str,
+    owner: &str,
+    path: &[String],
+    amount_in: u128,
+    min_eth_out: u128,
+    deadline: u128,
+    permit2_approved: bool,
+    permit_nonce: u128,
+) -> SellPlan {
+    let mut transactions: Vec<TransactionRequest> = vec![];
+
+    // Permit2 itself needs a classic approval once per token
+    if !permit2_approved {
+        transactions.push(TransactionRequest {
+            to: contract.to_owned(),
+            data: abi::function_call(
+                "approve(address,uint256)",
+                &[
+                    AbiToken::Address(String::from(PERMIT2)),
+                    AbiToken::Uint(u128::MAX),
+                ],
+            ),
+            value: 0,
+            description: String::from("One-time Permit2 approval"),
+        });
+    }
+
+    let permit = PermitSingle {
+        token: contract.to_owned(),
+        amount: amount_in,
+        expiration: (Utc::now() + Duration::try_days(30).unwrap()).timestamp() as u128,
+        nonce: permit_nonce,
+        spender: String::from(UNIVERSAL_ROUTER),
+        sig_deadline: deadline,
+    };
+
+    SellPlan {
+        route: SellRoute::UniversalRouter,
+        amount_in,
+        min_eth_out,
+        permit: Some(permit),
+        router_swap: Some(RouterSwap {
+            owner: owner.to_owned(),
+            path: path.to_vec(),
+            deadline,
+        }),
+        transactions,
+        proceeds: SellProceeds::Eth,
+        min_proceeds_out: min_eth_out,
+    }
+}
+
+fn universal_router_execute(
+    permit: &PermitSingle,
+    signature: &[u8],
+    swap: &RouterSwap,
+    amount_in: u128,
+    min_eth_out: u128,
+) -> TransactionRequest {
+    let inputs = vec![
+        AbiToken::Bytes(abi::encode(&[
+            permit.to_abi(),
+            AbiToken::Bytes(signature.to_vec()),
+        ])),
+        AbiToken::Bytes(abi::encode(&[
+            AbiToken::Address(String::from(ROUTER_ADDRESS_THIS)),
+            AbiToken::Uint(amount_in),
+            AbiToken::Uint(min_eth_out),
+            AbiToken::Array(
+                swap.path
+                    .iter()
+                    .map(|token| AbiToken::Address(token.clone()))
+                    .collect(),
+            ),
+            AbiToken::Bool(true),
+        ])),
+        AbiToken::Bytes(abi::encode(&[
+            AbiToken::Address(swap.owner.clone()),
+            AbiToken::Uint(min_eth_out),
+        ])),
+    ];
+
+    TransactionRequest {
+        to: String::from(UNIVERSAL_ROUTER),
+        data: abi::function_call(
+            "execute(bytes,bytes[],uint256)",
+            &[
+                AbiToken::Bytes(vec![PERMIT2_PERMIT, V2_SWAP_EXACT_IN, UNWRAP_WETH]),
+                AbiToken::Array(inputs),
+                AbiToken::Uint(swap.deadline),
+            ],
+        ),
+        value: 0,
+        description: String::from("Permit, swap and unwrap in one transaction"),
+    }
+}
+
+// the decimals a sell amount was parsed with aren't the token's anymore
+#[derive(Debug)]
+pub struct DecimalsMismatch {
+    pub parsed: u8,
+    pub token: u8,
+}
+
+impl fmt::Display for DecimalsMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the amount was read with {} decimals but the token now reports {}, enter it again",
+            self.parsed, self.token
+        )
+    }
+}
+
+impl std::error::Error for DecimalsMismatch {}
+
+// a token amount in the token's base units, so what's sold is exactly what was typed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenAmount {
+    pub raw: u128,
+    pub decimals: u8,
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(unit) = 10u128.checked_pow(self.decimals as u32) else {
+            return write!(f, "{}", self.raw);
+        };
+        let fraction = format!(
+            "{:0>width$}",
+            self.raw % unit,
+            width = self.decimals as usize
+        );
+        let fraction = fraction.trim_end_matches('0');
+
+        if fraction.is_empty() {
+            write!(f, "{}", self.raw / unit)
+        } else {
+            write!(f, "{}.{}", self.raw / unit, fraction)
+        }
+    }
+}
+
+impl TokenAmount {
+    // whole tokens like '1000000' or '0.5', None for more decimals than the token has
+    pub fn parse(amount: &str, decimals: u8) -> Option<TokenAmount> {
+        let (integer, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (integer.is_empty() && fraction.is_empty())
+            || !is_digits(integer)
+            || !is_digits(fraction)
+            || fraction.len() > decimals as usize
+        {
+            return None;
+        }
+
+        let raw = format!(
+            "{}{:0<width$}",
+            integer,
+            fraction,
+            width = decimals as usize
+        )
+        .parse::<u128>()
+        .ok()?;
+        if raw == 0 {
+            return None;
+        }
+
+        Some(TokenAmount { raw, decimals })
+    }
+
+    // the amount a usd value buys at the price, for showing a usd sell in tokens
+    pub fn from_usd(amount_usd: f64, token_price: f64, decimals: u8) -> Option<TokenAmount> {
+        if token_price <= 0.0 {
+            return None;
+        }
+
+        let raw = amount_usd / token_price * 10.0f64.powi(decimals as i32);
+        (raw >= 1.0).then_some(TokenAmount {
+            raw: raw as u128,
+            decimals,
+        })
+    }
+
+    pub fn tokens(&self) -> f64 {
+        self.raw as f64 / 10.0f64.powi(self.decimals as i32)
+    }
+}
+
+// splits a buy into equal slices spread evenly over the duration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Twap {
+    pub slices: u32,
+    pub duration: Duration,
+}
+
+impl fmt::Display for Twap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} slices over {} minutes",
+            self.slices,
+            self.duration.num_minutes()
+        )
+    }
+}
+
+impl Twap {
+    pub const MAX_SLICES: u32 = 50;
+
+    pub fn new(slices: u32, minutes: i64) -> Option<Twap> {
+        if !(2..=Twap::MAX_SLICES).contains(&slices) || !(1..=24 * 60).contains(&minutes) {
+            return None;
+        }
+
+        Some(Twap {
+            slices,
+            duration: Duration::try_minutes(minutes)?,
+        })
+    }
+
+    pub fn slice_amount(&self, amount_usd: f64) -> f64 {
+        amount_usd / self.slices as f64
+    }
+
+    // time between two slices, the first slice is executed right away
+    pub fn interval(&self) -> Duration {
+        self.duration / (self.slices - 1) as i32
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TwapFill {
+    pub executed: u32,
+    pub skipped: u32,
+    pub spent_usd: f64,
+    pub tokens: f64,
+}
+
+impl TwapFill {
+    pub fn fill(&mut self, amount_usd: f64, price: f64) {
+        self.executed += 1;
+        self.spent_usd += amount_usd;
+        self.tokens += amount_usd / price;
+    }
+
+    pub fn skip(&mut self) {
+        self.skipped += 1;
+    }
+
+    // cost weighted, so cheaper slices pull the entry price down by the extra tokens they bought
+    pub fn blended_price(&self) -> Option<f64> {
+        if self.tokens > 0.0 {
+            Some(self.spent_usd / self.tokens)
+        } else {
+            None
+        }
+    }
+}
+
+// constant product pools: an order paying `amount` into a side holding `reserve` moves the
+// price by amount / (reserve + amount), the 0.3% fee aside
+pub fn price_impact_percent(reserve: f64, amount: f64) -> f64 {
+    if reserve + amount <= 0.0 {
+        return 0.0;
+    }
+
+    amount / (reserve + amount) * 100.0
+}
+
+// the V2 router's require message, the V3 router's and the Universal Router's custom errors
+pub fn is_slippage_revert(reason: &str) -> bool {
+    ["INSUFFICIENT_OUTPUT_AMOUNT", "Too little received"]
+        .iter()
+        .any(|message| reason.contains(message))
+        || ["V2TooLittleReceived()", "V3TooLittleReceived()"]
+            .iter()
+            .any(|error| reason.contains(&abi::to_hex(&abi::selector(error))))
+}
+
+// SLIPPAGE_RETRY_MAX, retries never go above it
+pub fn max_retry_slippage() -> f32 {
+    env::var("SLIPPAGE_RETRY_MAX")
+        .ok()
+        .and_then(|max| max.parse::<f32>().ok())
+        .filter(|max| *max > 0.0 && *max < 100.0)
+        .unwrap_or(DEFAULT_MAX_RETRY_SLIPPAGE)
+}
+
+// the tax plus a buffer, or the buffer on top of the failed slippage when that already covered
+// the tax and the price moved instead. None when the retry couldn't go any higher
+pub fn retry_slippage(slippage: f32, tax_percent: f32, max: f32) -> Option<f32> {
+    let retry = if tax_percent + RETRY_SLIPPAGE_BUFFER > slippage {
+        tax_percent + RETRY_SLIPPAGE_BUFFER
+    } else {
+        slippage + RETRY_SLIPPAGE_BUFFER
+    };
+
+    Some(retry.ceil().min(max)).filter(|retry| *retry > slippage)
+}
+
+// the largest order that keeps the price impact at or below the limit
+pub fn max_order_for_impact(reserve: f64, max_impact_percent: f64) -> f64 {
+    let max_impact = max_impact_percent / 100.0;
+    if max_impact >= 1.0 {
+        return f64::INFINITY;
+    }
+
+    reserve * max_impact / (1.0 - max_impact)
+}
+
+#[test]
+fn test_gas_estimate() {
+    let estimate = GasEstimate {
+        gas: 150_000,
+        gas_price: 20 * 10u128.pow(9),
+        eth_price: 2_000.0,
+        simulated: true,
+    };
+
+    assert!((estimate.cost_eth() - 0.003).abs() < 1e-12);
+    assert!((estimate.cost_usd() - 6.0).abs() < 1e-9);
+    assert_eq!(breakeven_percent(estimate.cost_usd(), 300.0), 2.0);
+    assert_eq!(breakeven_percent(6.0, 0.0), f64::INFINITY);
+}
+
+#[test]
+fn test_bump_fee() {
+    assert_eq!(bump_fee(100, 0), 111);
+    assert_eq!(bump_fee(100, 500), 500);
+}
+
+#[test]
+fn test_twap_blended_price() {
+    let twap = Twap::new(4, 30).unwrap();
+    assert_eq!(twap.slice_amount(200.0), 50.0);
+    assert_eq!(twap.interval(), Duration::try_minutes(10).unwrap());
+    assert!(Twap::new(1, 30).is_none());
+
+    let mut fill = TwapFill::default();
+    assert_eq!(fill.blended_price(), None);
+
+    fill.fill(100.0, 1.0);
+    fill.skip();
+    fill.fill(100.0, 2.0);
+
+    assert_eq!(fill.executed, 2);
+    assert_eq!(fill.skipped, 1);
+    assert_eq!(fill.tokens, 150.0);
+    assert!((fill.blended_price().unwrap() - 200.0 / 150.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_price_impact() {
+    // $1M on each side of the pool
+    assert_eq!(price_impact_percent(1_000_000.0, 0.0), 0.0);
+    assert!((price_impact_percent(1_000_000.0, 10_000.0) - 0.990099).abs() < 1e-6);
+
+    let max_order = max_order_for_impact(1_000_000.0, 2.0);
+    assert!((max_order - 20_408.163265).abs() < 1e-6);
+    assert!((price_impact_percent(1_000_000.0, max_order) - 2.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_retry_slippage() {
+    assert!(is_slippage_revert(
+        "execution reverted: UniswapV2Router: INSUFFICIENT_OUTPUT_AMOUNT"
+    ));
+    assert!(is_slippage_revert("execution reverted 0x39d35496"));
+    assert!(!is_slippage_revert(
+        "execution reverted: TransferHelper: TRANSFER_FROM_FAILED"
+    ));
+
+    // a 10% tax token bought with 5% slippage
+    assert_eq!(retry_slippage(5.0, 10.0, 25.0), Some(13.0));
+    assert_eq!(retry_slippage(5.0, 0.5, 25.0), Some(8.0));
+    assert_eq!(retry_slippage(20.0, 30.0, 25.0), Some(25.0));
+    assert_eq!(retry_slippage(25.0, 30.0, 25.0), None);
+}
+
+#[test]
+fn test_gas_actions() {
+    let actions = parse_gas_actions("nft=90000, bridge = 120000, broken=abc, =5");
+
+    assert_eq!(actions.len(), GAS_ACTIONS.len() + 1);
+    assert_eq!(
+        actions
+            .iter()
+            .find(|action| action.name == "nft")
+            .unwrap()
+            .gas,
+        90_000
+    );
+    assert_eq!(
+        actions.last(),
+        Some(&GasAction {
+            name: String::from("bridge"),
+            description: String::from("bridge"),
+            gas: 120_000,
+        })
+    );
+
+    assert_eq!(
+        parse_gas_argument("150,000", &actions),
+        Some(GasSelection::Amount(150_000))
+    );
+    assert!(matches!(
+        parse_gas_argument("NFT", &actions),
+        Some(GasSelection::Action(action)) if action.gas == 90_000
+    ));
+    assert_eq!(parse_gas_argument("0", &actions), None);
+    assert_eq!(parse_gas_argument("unknown", &actions), None);
+
+    // 21,000 gas at 20 gwei is 0.00042 eth
+    let tiers = tier_estimates(21_000, 20_000_000_000, 2_000.0);
+    assert_eq!(tiers.len(), GAS_TIERS.len());
+    assert!((tiers[1].1.cost_usd() - 0.84).abs() < 1e-9);
+    assert!(tiers[0].1.cost_usd() < tiers[2].1.cost_usd());
+}
+
+#[test]
+fn test_sell_proceeds() {
+    let owner = "0x00000000000000000000000000000000000000aa";
+    let path = [
+        String::from("0x00000000000000000000000000000000000000bb"),
+        String::from(WETH_ADDRESS),
+    ];
+    let eth = 10u128.pow(18);
+    let sell = || classic_sell(&path[0], owner, &path, 1_000, eth, 0);
+
+    let plan = sell().convert_proceeds(owner, SellProceeds::Eth, 2_000.0, 5.0, 0);
+    assert_eq!(plan.transactions.len(), 2);
+    assert_eq!(plan.swap().unwrap().description, "Swap tokens for ETH");
+    assert!(plan.to_string().contains("Minimum received: 1.000000 ETH"));
+
+    // 1 eth at $2,000 with 5% slippage on the second swap too
+    let plan = sell().convert_proceeds(owner, SellProceeds::Usdc, 2_000.0, 5.0, 0);
+    assert_eq!(plan.transactions.len(), 3);
+    assert_eq!(plan.swap().unwrap().description, "Swap tokens for ETH");
+    assert_eq!(plan.through_swap().len(), 2);
+    assert_eq!(plan.transactions[2].value, eth);
+    assert_eq!(plan.min_proceeds_out, 1_900_000_000);
+    assert!(plan
+        .to_string()
+        .contains("Minimum received: 1.000000 ETH ➡️ 1900.00 USDC"));
+
+    let plan = sell().convert_proceeds(owner, SellProceeds::Weth, 2_000.0, 5.0, 0);
+    assert_eq!(plan.transactions[2].to, WETH_ADDRESS);
+    assert_eq!(plan.min_proceeds_out, eth);
+    assert_eq!("usdc".parse::<SellProceeds>(), Ok(SellProceeds::Usdc));
+}
+
+#[test]
+fn test_permit_signature() {
+    let owner = "0x00000000000000000000000000000000000000aa";
+    let path = [
+        String::from("0x00000000000000000000000000000000000000bb"),
+        String::from(WETH_ADDRESS),
+    ];
+    let eth = 10u128.pow(18);
+    let plan = universal_router_sell(&path[0], owner, &path, 1_000, eth, 0, false, 3)
+        .convert_proceeds(owner, SellProceeds::Weth, 2_000.0, 5.0, 0);
+
+    // nothing calls the router before the wallet signed the permit
+    assert_eq!(plan.transactions.len(), 2);
+    assert!(plan.transactions.iter().all(|t| t.to != UNIVERSAL_ROUTER));
+    assert!(plan.to_string().contains("(3 transaction(s))"));
+    let typed_data = plan.permit.as_ref().unwrap().typed_data();
+    assert_eq!(typed_data["domain"]["verifyingContract"], PERMIT2);
+    assert_eq!(typed_data["message"]["details"]["nonce"], "3");
+
+    let signature = [0xab; 65];
+    let plan = plan.with_permit_signature(&signature);
+    assert!(plan.permit.is_none());
+    assert_eq!(plan.transactions.len(), 3);
+    assert_eq!(plan.swap().unwrap().to, UNIVERSAL_ROUTER);
+    assert!(plan.swap().unwrap().data.contains(&"ab".repeat(65)));
+    assert_eq!(plan.transactions[2].to, WETH_ADDRESS);
+}
+
+#[test]
+fn test_token_amount() {
+    // the same number is a million times more tokens with 6 decimals than with 18
+    let usdc = TokenAmount::parse("1000000", 6).unwrap();
+    assert_eq!(usdc.raw, 1_000_000_000_000);
+    let pepe = TokenAmount::parse("1000000", 18).unwrap();
+    assert_eq!(pepe.raw, 1_000_000 * 10u128.pow(18));
+
+    let half = TokenAmount::parse("0.5", 6).unwrap();
+    assert_eq!(half.raw, 500_000);
+    assert_eq!(half.to_string(), "0.5");
+    assert_eq!(TokenAmount::parse(".25", 2).unwrap().raw, 25);
+    assert_eq!(
+        TokenAmount::parse("1234.000001", 6).unwrap().to_string(),
+        "1234.000001"
+    );
+    assert_eq!(TokenAmount::parse("12", 0).unwrap().to_string(), "12");
+
+    // more precision than the token has, zero and anything that isn't a plain number
+    assert_eq!(TokenAmount::parse("0.0000001", 6), None);
+    assert_eq!(TokenAmount::parse("0", 18), None);
+    assert_eq!(TokenAmount::parse("1e6", 18), None);
+    assert_eq!(TokenAmount::parse("-5", 18), None);
+    assert_eq!(TokenAmount::parse(".", 18), None);
+
+    let estimate = TokenAmount::from_usd(50.0, 0.0005, 9).unwrap();
+    assert_eq!(estimate.to_string(), "100000");
+    assert_eq!(TokenAmount::from_usd(50.0, 0.0, 9), None);
+}
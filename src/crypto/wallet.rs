@@ -0,0 +1,138 @@
+use crate::{api, crypto::abi, utils};
+use k256::ecdsa::SigningKey;
+use lazy_static::lazy_static;
+use std::{collections::HashMap, env};
+use tokio::sync::Mutex;
+
+lazy_static! {
+    static ref NONCES: Mutex<NonceTracker> = Mutex::new(NonceTracker::default());
+}
+
+/*
+
+Trades, TWAP slices, auto-snipes and transfers from the same wallet can be sent
+at the same moment, each transaction reserves its nonce here so no two of them
+share one. The chain's pending count is read on every reservation, a transaction
+sent from the wallet app directly moves the next nonce past it. When a send
+fails the address resyncs to the pending count, the nonce it reserved is handed
+out again.
+
+*/
+#[derive(Debug, Default)]
+struct NonceTracker {
+    // lowercase address to the nonce it hands out next
+    next: HashMap<String, u64>,
+}
+
+impl NonceTracker {
+    fn reserve(&mut self, address: &str, pending: u64) -> u64 {
+        let next = self.next.entry(address.to_lowercase()).or_insert(pending);
+        let nonce = (*next).max(pending);
+        *next = nonce + 1;
+
+        nonce
+    }
+
+    fn resync(&mut self, address: &str, pending: u64) {
+        self.next.insert(address.to_lowercase(), pending);
+    }
+}
+
+// the lock is held while the pending count is read, reservations of the same moment are serialized
+pub async fn reserve_nonce(address: &str) -> Result<u64, reqwest::Error> {
+    let mut nonces = NONCES.lock().await;
+    let pending = api::get_transaction_count(address.to_owned(), true).await?;
+
+    Ok(nonces.reserve(address, pending))
+}
+
+// after a rejected, failed or dropped transaction the chain is the truth again
+pub async fn resync_nonce(address: &str) {
+    let mut nonces = NONCES.lock().await;
+
+    match api::get_transaction_count(address.to_owned(), true).await {
+        Ok(pending) => nonces.resync(address, pending),
+        Err(e) => {
+            // without a count the next reservation starts from the chain's
+            error!("get_transaction_count error: {}", e);
+            nonces.next.remove(&address.to_lowercase());
+        }
+    }
+}
+
+pub fn eth_address() -> String {
+    env::var("ETH_ADDRESS").expect("ETH_ADDRESS env var is not set")
+}
+
+// lowercase, None when SAFE_ADDRESS isn't set or isn't a valid address
+pub fn safe_address() -> Option<String> {
+    env::var("SAFE_ADDRESS")
+        .ok()
+        .filter(|safe| utils::is_valid_eth_address(safe.trim()))
+        .map(|safe| safe.trim().to_lowercase())
+}
+
+pub fn is_safe(wallet: &str) -> bool {
+    safe_address().is_some_and(|safe| safe == wallet.to_lowercase())
+}
+
+// key of the Safe owner or delegate that signs the Safe's proposals, it can't move funds on its own
+pub fn safe_proposer_key() -> Option<SigningKey> {
+    env::var("SAFE_PROPOSER_KEY")
+        .ok()
+        .and_then(|key| SigningKey::from_slice(&abi::from_hex(key.trim())).ok())
+}
+
+// the hot key that signs same-block launch snipes, only fund it with what the snipes may spend
+pub fn launch_snipe_key() -> Option<SigningKey> {
+    env::var("LAUNCH_SNIPE_KEY")
+        .ok()
+        .and_then(|key| SigningKey::from_slice(&abi::from_hex(key.trim())).ok())
+}
+
+// lowercase address of the key, the last 20 bytes of the hashed public key
+pub fn address_of(key: &SigningKey) -> String {
+    let public_key = key.verifying_key().to_encoded_point(false);
+
+    abi::to_hex(&abi::keccak256(&public_key.as_bytes()[1..])[12..])
+}
+
+// ETH_ADDRESS first, then the comma separated TRADING_WALLETS and SAFE_ADDRESS, all lowercase
+pub fn trading_wallets() -> Vec<String> {
+    let mut wallets = vec![eth_address().to_lowercase()];
+
+    for wallet in env::var("TRADING_WALLETS").unwrap_or_default().split(',') {
+        if !utils::is_valid_eth_address(wallet.trim()) {
+            continue;
+        }
+
+        let wallet = wallet.trim().to_lowercase();
+        if !wallets.contains(&wallet) {
+            wallets.push(wallet);
+        }
+    }
+
+    if let Some(safe) = safe_address().filter(|safe| !wallets.contains(safe)) {
+        wallets.push(safe);
+    }
+
+    wallets
+}
+
+#[test]
+fn test_nonce_tracker() {
+    let mut nonces = NonceTracker::default();
+
+    // two sends of the same moment see the same pending count
+    assert_eq!(nonces.reserve("0xAA", 5), 5);
+    assert_eq!(nonces.reserve("0xaa", 5), 6);
+    assert_eq!(nonces.reserve("0xbb", 1), 1);
+
+    // a transaction sent from the wallet app took nonce 7
+    assert_eq!(nonces.reserve("0xaa", 8), 8);
+
+    // nonce 9 was rejected and never broadcast
+    assert_eq!(nonces.reserve("0xaa", 9), 9);
+    nonces.resync("0xaa", 9);
+    assert_eq!(nonces.reserve("0xaa", 9), 9);
+}
@@ -0,0 +1,624 @@
+use crate::{
+    crypto::{
+        abi,
+        trading::{ReplacementTransaction, TransactionRequest},
+    },
+    persist,
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use chrono::Utc;
+use ed25519_dalek::{Signer, SigningKey};
+use futures_util::{SinkExt, StreamExt};
+use hkdf::Hkdf;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{collections::VecDeque, env, time::Duration};
+use tokio::{net::TcpStream, sync::Mutex, time::timeout};
+use tokio_tungstenite::{connect_async, tungstenite, MaybeTlsStream, WebSocketStream};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const RELAY_URL: &str = "wss://relay.walletconnect.com";
+const MAINNET: &str = "eip155:1";
+// sign protocol message tags, wallets drop messages with the wrong one
+const TAG_SESSION_PROPOSE: u32 = 1100;
+const TAG_SESSION_SETTLE_RESPONSE: u32 = 1103;
+const TAG_SESSION_REQUEST: u32 = 1108;
+const TAG_SESSION_DELETE: u32 = 1112;
+// the phone has this long to approve a pairing or a transaction,
+// also the ttl of the messages, they're useless once nobody waits for the answer
+const APPROVAL_TIMEOUT_SECONDS: u64 = 300;
+
+/*
+
+Trades can be signed on a mobile wallet through WalletConnect v2 instead of
+with a key on the host. The bot pairs with the wallet once (/walletconnect),
+keeps the session's symmetric key and sends every transaction of a trade as an
+eth_sendTransaction request. The wallet signs and broadcasts it and answers
+with the hash, which the bot tracks like any other transaction. The Permit2
+permit of a Universal Router sell is signed with eth_signTypedData_v4 before
+its swap is built, sessions paired before that have to pair again.
+
+Only the session key is saved, it can't sign anything and the wallet can end
+the session at any time. The relay is only connected while a pairing or a
+request is waiting for the phone.
+
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub topic: String,
+    // hex encoded, encrypts the messages of the session topic
+    sym_key: String,
+    // lowercase address the wallet approved
+    pub account: String,
+    // None when the wallet didn't set one, the session then lasts until either side ends it
+    #[serde(default)]
+    pub expiry: Option<i64>,
+}
+
+lazy_static! {
+    static ref SESSION: Mutex<Option<Session>> =
+        Mutex::new(persist::load(persist::WALLETCONNECT_SESSION));
+}
+
+// WalletConnect needs a project id from cloud.walletconnect.com
+pub fn project_id() -> Option<String> {
+    env::var("WALLETCONNECT_PROJECT_ID")
+        .ok()
+        .filter(|id| !id.is_empty())
+}
+
+pub async fn session() -> Option<Session> {
+    SESSION.lock().await.clone().filter(|session| {
+        session
+            .expiry
+            .map_or(true, |expiry| expiry > Utc::now().timestamp())
+    })
+}
+
+// the session that signs for the wallet, trades from other wallets are signed as before
+pub async fn session_for(wallet: &str) -> Option<Session> {
+    session()
+        .await
+        .filter(|session| session.account.eq_ignore_ascii_case(wallet))
+}
+
+fn topic_of(sym_key: &[u8; 32]) -> String {
+    hex::encode(&Sha256::digest(sym_key))
+}
+
+// millisecond timestamp with a random suffix, like the official clients
+fn rpc_id() -> u64 {
+    Utc::now().timestamp_millis() as u64 * 1000 + rand::random::<u64>() % 1000
+}
+
+// type 0 envelope: 0x00 | iv | ciphertext and tag, base64 encoded
+fn encrypt(sym_key: &[u8; 32], message: &str) -> String {
+    let iv: [u8; 12] = rand::random();
+    let sealed = ChaCha20Poly1305::new(Key::from_slice(sym_key))
+        .encrypt(Nonce::from_slice(&iv), message.as_bytes())
+        .expect("chacha20poly1305 encryption doesn't fail for in-memory messages");
+
+    let mut envelope = vec![0];
+    envelope.extend(iv);
+    envelope.extend(sealed);
+
+    STANDARD.encode(envelope)
+}
+
+fn decrypt(sym_key: &[u8; 32], envelope: &str) -> Option<Value> {
+    let envelope = STANDARD.decode(envelope).ok()?;
+    if envelope.len() < 13 || envelope[0] != 0 {
+        return None;
+    }
+
+    let message = ChaCha20Poly1305::new(Key::from_slice(sym_key))
+        .decrypt(Nonce::from_slice(&envelope[1..13]), &envelope[13..])
+        .ok()?;
+
+    serde_json::from_slice(&message).ok()
+}
+
+// the session key both sides derive from the proposer's and the responder's x25519 keys
+fn session_key(secret: &StaticSecret, responder_public_key: &str) -> Option<[u8; 32]> {
+    let public: [u8; 32] = abi::from_hex(responder_public_key).try_into().ok()?;
+    let shared = secret.diffie_hellman(&PublicKey::from(public));
+
+    let mut key = [0; 32];
+    Hkdf::<Sha256>::new(None, shared.as_bytes())
+        .expand(&[], &mut key)
+        .ok()?;
+
+    Some(key)
+}
+
+fn sym_key_of(session: &Session) -> Option<[u8; 32]> {
+    abi::from_hex(&session.sym_key).try_into().ok()
+}
+
+// relay auth, a JWT signed by a throwaway ed25519 client key
+fn auth_jwt(key: &SigningKey) -> String {
+    let mut multicodec = vec![0xed, 0x01];
+    multicodec.extend(key.verifying_key().as_bytes());
+    let issuer = format!("did:key:z{}", bs58::encode(multicodec).into_string());
+    let issued_at = Utc::now().timestamp();
+
+    let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "EdDSA", "typ": "JWT" }).to_string());
+    let claims = URL_SAFE_NO_PAD.encode(
+        json!({
+            "iss": issuer,
+            "sub": hex::encode(&rand::random::<[u8; 32]>()),
+            "aud": RELAY_URL,
+            "iat": issued_at,
+            "exp": issued_at + 24 * 60 * 60,
+        })
+        .to_string(),
+    );
+    let data = format!("{}.{}", header, claims);
+    let signature = URL_SAFE_NO_PAD.encode(key.sign(data.as_bytes()).to_bytes());
+
+    format!("{}.{}", data, signature)
+}
+
+struct Relay {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    // (topic, envelope) published to us while waiting for a relay response
+    received: VecDeque<(String, String)>,
+}
+
+impl Relay {
+    async fn connect() -> Result<Relay, String> {
+        let project_id = project_id().ok_or("WALLETCONNECT_PROJECT_ID is not set")?;
+        let key = SigningKey::from_bytes(&rand::random());
+        let url = format!(
+            "{}/?auth={}&projectId={}",
+            RELAY_URL,
+            auth_jwt(&key),
+            project_id
+        );
+
+        let (socket, _) = connect_async(url).await.map_err(|e| e.to_string())?;
+
+        Ok(Relay {
+            socket,
+            received: VecDeque::new(),
+        })
+    }
+
+    async fn next_json(&mut self) -> Result<Value, String> {
+        loop {
+            match self.socket.next().await {
+                Some(Ok(tungstenite::Message::Text(text))) => {
+                    return serde_json::from_str(&text).map_err(|e| e.to_string())
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.to_string()),
+                None => return Err(String::from("the relay closed the connection")),
+            }
+        }
+    }
+
+    // published messages are acknowledged, otherwise the relay delivers them again
+    async fn handle_subscription(&mut self, message: &Value) -> Result<(), String> {
+        if let (Some(topic), Some(envelope)) = (
+            message["params"]["data"]["topic"].as_str(),
+            message["params"]["data"]["message"].as_str(),
+        ) {
+            self.received
+                .push_back((topic.to_owned(), envelope.to_owned()));
+        }
+
+        self.send(json!({ "id": message["id"], "jsonrpc": "2.0", "result": true }))
+            .await
+    }
+
+    async fn send(&mut self, message: Value) -> Result<(), String> {
+        self.socket
+            .send(tungstenite::Message::Text(message.to_string()))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        let id = rpc_id();
+        self.send(json!({ "id": id, "jsonrpc": "2.0", "method": method, "params": params }))
+            .await?;
+
+        loop {
+            let message = self.next_json().await?;
+
+            if message["method"] == "irn_subscription" {
+                self.handle_subscription(&message).await?;
+            } else if message["id"] == id {
+                return match message.get("error") {
+                    Some(error) => Err(format!("{} failed: {}", method, error)),
+                    None => Ok(message["result"].clone()),
+                };
+            }
+        }
+    }
+
+    async fn subscribe(&mut self, topic: &str) -> Result<(), String> {
+        self.request("irn_subscribe", json!({ "topic": topic }))
+            .await
+            .map(|_| ())
+    }
+
+    async fn publish(
+        &mut self,
+        topic: &str,
+        sym_key: &[u8; 32],
+        message: &Value,
+        tag: u32,
+    ) -> Result<(), String> {
+        self.request(
+            "irn_publish",
+            json!({
+                "topic": topic,
+                "message": encrypt(sym_key, &message.to_string()),
+                "ttl": APPROVAL_TIMEOUT_SECONDS,
+                "tag": tag,
+                "prompt": true,
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    // the next message published to the topic the filter accepts, decrypted
+    async fn receive(
+        &mut self,
+        topic: &str,
+        sym_key: &[u8; 32],
+        filter: impl Fn(&Value) -> bool,
+    ) -> Result<Value, String> {
+        loop {
+            if let Some(i) = self.received.iter().position(|(t, _)| t == topic) {
+                let (_, envelope) = self.received.remove(i).unwrap();
+                match decrypt(sym_key, &envelope) {
+                    Some(message) if filter(&message) => return Ok(message),
+                    _ => continue,
+                }
+            }
+
+            let message = self.next_json().await?;
+            if message["method"] == "irn_subscription" {
+                self.handle_subscription(&message).await?;
+            }
+        }
+    }
+}
+
+// a proposal waiting for the wallet to scan the uri and approve
+pub struct Pairing {
+    pub uri: String,
+    topic: String,
+    sym_key: [u8; 32],
+    secret: StaticSecret,
+    proposal_id: u64,
+    relay: Relay,
+}
+
+pub async fn pair() -> Result<Pairing, String> {
+    let sym_key: [u8; 32] = rand::random();
+    let topic = topic_of(&sym_key);
+    let secret = StaticSecret::from(rand::random::<[u8; 32]>());
+    let proposal_id = rpc_id();
+
+    let mut relay = Relay::connect().await?;
+    relay.subscribe(&topic).await?;
+
+    // the relay keeps the proposal until the wallet subscribes to the pairing topic
+    let proposal = json!({
+        "id": proposal_id,
+        "jsonrpc": "2.0",
+        "method": "wc_sessionPropose",
+        "params": {
+            "relays": [{ "protocol": "irn" }],
+            "proposer": {
+                "publicKey": hex::encode(PublicKey::from(&secret).as_bytes()),
+                "metadata": {
+                    "name": "snipers",
+                    "description": "Ethereum trading and monitoring Telegram bot",
+                    "url": "https://github.com/marce1l/snipers",
+                    "icons": [],
+                },
+            },
+            "requiredNamespaces": {
+                "eip155": {
+                    "chains": [MAINNET],
+                    "methods": ["eth_sendTransaction", "eth_signTypedData_v4"],
+                    "events": ["chainChanged", "accountsChanged"],
+                },
+            },
+        },
+    });
+    relay
+        .publish(&topic, &sym_key, &proposal, TAG_SESSION_PROPOSE)
+        .await?;
+
+    let uri = format!(
+        "wc:{}@2?relay-protocol=irn&symKey={}&expiryTimestamp={}",
+        topic,
+        hex::encode(&sym_key),
+        Utc::now().timestamp() + APPROVAL_TIMEOUT_SECONDS as i64
+    );
+
+    Ok(Pairing {
+        uri,
+        topic,
+        sym_key,
+        secret,
+        proposal_id,
+        relay,
+    })
+}
+
+impl Pairing {
+    async fn settle(mut self) -> Result<Session, String> {
+        let proposal_id = self.proposal_id;
+        let response = self
+            .relay
+            .receive(&self.topic, &self.sym_key, |m| m["id"] == proposal_id)
+            .await?;
+        if let Some(error) = response.get("error") {
+            return Err(format!(
+                "the wallet rejected the pairing: {}",
+                error["message"]
+            ));
+        }
+
+        let sym_key = response["result"]["responderPublicKey"]
+            .as_str()
+            .and_then(|public_key| session_key(&self.secret, public_key))
+            .ok_or("the wallet's response has no valid public key")?;
+        let topic = topic_of(&sym_key);
+        self.relay.subscribe(&topic).await?;
+
+        let settle = self
+            .relay
+            .receive(&topic, &sym_key, |m| m["method"] == "wc_sessionSettle")
+            .await?;
+        let settled = json!({ "id": settle["id"], "jsonrpc": "2.0", "result": true });
+        self.relay
+            .publish(&topic, &sym_key, &settled, TAG_SESSION_SETTLE_RESPONSE)
+            .await?;
+
+        // accounts are 'eip155:1:0x...'
+        let account = settle["params"]["namespaces"]["eip155"]["accounts"]
+            .as_array()
+            .and_then(|accounts| {
+                accounts
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .find_map(|account| {
+                        account
+                            .strip_prefix(&format!("{}:", MAINNET))
+                            .map(str::to_lowercase)
+                    })
+            })
+            .ok_or("the wallet didn't approve a mainnet account")?;
+
+        Ok(Session {
+            topic,
+            sym_key: hex::encode(&sym_key),
+            account,
+            expiry: settle["params"]["expiry"].as_i64(),
+        })
+    }
+
+    // waits for the wallet, the new session replaces the previous one
+    pub async fn approve(self) -> Result<Session, String> {
+        let session = timeout(Duration::from_secs(APPROVAL_TIMEOUT_SECONDS), self.settle())
+            .await
+            .map_err(|_| String::from("the pairing wasn't approved in time"))??;
+
+        persist::save(persist::WALLETCONNECT_SESSION, &session);
+        *SESSION.lock().await = Some(session.clone());
+
+        Ok(session)
+    }
+}
+
+fn session_request(id: u64, method: &str, params: Value) -> Value {
+    json!({
+        "id": id,
+        "jsonrpc": "2.0",
+        "method": "wc_sessionRequest",
+        "params": {
+            "request": {
+                "method": method,
+                "params": params,
+            },
+            "chainId": MAINNET,
+        },
+    })
+}
+
+// without a nonce the wallet picks the next one itself
+fn transaction_params(
+    account: &str,
+    transaction: &TransactionRequest,
+    nonce: Option<u64>,
+) -> Value {
+    let mut params = json!({
+        "from": account,
+        "to": transaction.to,
+        "data": transaction.data,
+        "value": format!("{:#x}", transaction.value),
+    });
+    if let Some(nonce) = nonce {
+        params["nonce"] = json!(format!("{:#x}", nonce));
+    }
+
+    json!([params])
+}
+
+// sends a request to the wallet and waits for its answer, a string result or the reason it was rejected
+async fn request(session: &Session, method: &str, params: Value) -> Result<String, String> {
+    let sym_key = sym_key_of(session).ok_or("invalid session key")?;
+    let id = rpc_id();
+
+    let request = async {
+        let mut relay = Relay::connect().await?;
+        relay.subscribe(&session.topic).await?;
+        let request = session_request(id, method, params);
+        relay
+            .publish(&session.topic, &sym_key, &request, TAG_SESSION_REQUEST)
+            .await?;
+
+        relay
+            .receive(&session.topic, &sym_key, |m| {
+                m["id"] == id || m["method"] == "wc_sessionDelete"
+            })
+            .await
+    };
+    let message = timeout(Duration::from_secs(APPROVAL_TIMEOUT_SECONDS), request)
+        .await
+        .map_err(|_| String::from("the request wasn't approved in time"))??;
+
+    if message["method"] == "wc_sessionDelete" {
+        *SESSION.lock().await = None;
+        persist::remove(persist::WALLETCONNECT_SESSION);
+        return Err(String::from("the wallet ended the session"));
+    }
+
+    match (message["result"].as_str(), message.get("error")) {
+        (Some(result), _) => Ok(result.to_owned()),
+        (None, Some(error)) => Err(format!(
+            "the wallet rejected the request: {}",
+            error["message"]
+        )),
+        (None, None) => Err(String::from("the wallet didn't answer the request")),
+    }
+}
+
+// asks the wallet to sign and broadcast the transaction, returns its hash
+pub async fn send_transaction(
+    session: &Session,
+    transaction: &TransactionRequest,
+    nonce: Option<u64>,
+) -> Result<String, String> {
+    request(
+        session,
+        "eth_sendTransaction",
+        transaction_params(&session.account, transaction, nonce),
+    )
+    .await
+}
+
+// the replacement keeps the stuck transaction's nonce, the mempool only takes it with higher fees
+pub async fn send_replacement(
+    session: &Session,
+    replacement: &ReplacementTransaction,
+) -> Result<String, String> {
+    let params = json!([{
+        "from": session.account,
+        "to": replacement.to,
+        "data": replacement.input,
+        "value": format!("{:#x}", replacement.value),
+        "nonce": format!("{:#x}", replacement.nonce),
+        "gas": format!("{:#x}", replacement.gas_limit),
+        "maxFeePerGas": format!("{:#x}", replacement.max_fee_per_gas),
+        "maxPriorityFeePerGas": format!("{:#x}", replacement.max_priority_fee_per_gas),
+    }]);
+
+    request(session, "eth_sendTransaction", params).await
+}
+
+// asks the wallet to sign EIP-712 typed data, returns the 65 byte signature
+pub async fn sign_typed_data(session: &Session, typed_data: &Value) -> Result<Vec<u8>, String> {
+    let signature = request(
+        session,
+        "eth_signTypedData_v4",
+        json!([session.account, typed_data.to_string()]),
+    )
+    .await?;
+
+    hex::decode(signature.trim_start_matches("0x"))
+        .ok()
+        .filter(|signature| signature.len() == 65)
+        .ok_or_else(|| String::from("the wallet returned an invalid signature"))
+}
+
+// forgets the session and tells the wallet, one that's offline for longer than the message ttl
+// keeps showing the session until it's removed there
+pub async fn disconnect() -> Option<Session> {
+    let session = SESSION.lock().await.take()?;
+    persist::remove(persist::WALLETCONNECT_SESSION);
+
+    if let Some(sym_key) = sym_key_of(&session) {
+        let delete = json!({
+            "id": rpc_id(),
+            "jsonrpc": "2.0",
+            "method": "wc_sessionDelete",
+            "params": { "code": 6000, "message": "User disconnected." },
+        });
+
+        let result = match Relay::connect().await {
+            Ok(mut relay) => {
+                relay
+                    .publish(&session.topic, &sym_key, &delete, TAG_SESSION_DELETE)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("walletconnect session delete error: {}", e);
+        }
+    }
+
+    Some(session)
+}
+
+#[test]
+fn test_walletconnect_envelope() {
+    let sym_key: [u8; 32] = rand::random();
+    let message = json!({ "id": 1, "jsonrpc": "2.0", "result": true });
+
+    let envelope = encrypt(&sym_key, &message.to_string());
+    assert_eq!(decrypt(&sym_key, &envelope), Some(message));
+    assert_eq!(decrypt(&[0; 32], &envelope), None);
+
+    // both sides of the key exchange end up with the same session key and topic
+    let proposer = StaticSecret::from(rand::random::<[u8; 32]>());
+    let responder = StaticSecret::from(rand::random::<[u8; 32]>());
+    let proposer_key = session_key(
+        &proposer,
+        &hex::encode(PublicKey::from(&responder).as_bytes()),
+    )
+    .unwrap();
+    let responder_key = session_key(
+        &responder,
+        &hex::encode(PublicKey::from(&proposer).as_bytes()),
+    )
+    .unwrap();
+    assert_eq!(proposer_key, responder_key);
+    assert_eq!(topic_of(&proposer_key).len(), 64);
+
+    let request = session_request(
+        7,
+        "eth_sendTransaction",
+        transaction_params(
+            "0x1111111111111111111111111111111111111111",
+            &TransactionRequest {
+                to: String::from("0x2222222222222222222222222222222222222222"),
+                data: String::from("0x"),
+                value: 255,
+                description: String::new(),
+            },
+            Some(16),
+        ),
+    );
+    assert_eq!(request["params"]["request"]["params"][0]["value"], "0xff");
+    assert_eq!(request["params"]["request"]["params"][0]["nonce"], "0x10");
+    assert_eq!(request["params"]["chainId"], "eip155:1");
+}
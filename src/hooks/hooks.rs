@@ -0,0 +1,150 @@
+use crate::telegram::alerts::AlertCategory;
+use lazy_static::lazy_static;
+use teloxide::types::ChatId;
+
+#[cfg(feature = "webhook-hook")]
+mod webhook;
+
+/*
+
+Hooks let forks add their own logic (webhooks, databases, extra filters)
+without patching the core modules. Every hook is compiled in behind its own
+cargo feature and registered in `registered`, so a default build runs none.
+
+Hooks are called inline from the bot's tasks and handlers, anything slow
+(e.g. a request) should be spawned instead of awaited.
+
+*/
+pub trait Hook: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // false keeps the token out of the sniper alerts
+    fn on_token_discovered(&self, _token: &DiscoveredToken) -> bool {
+        true
+    }
+
+    fn on_trade_executed(&self, _trade: &ExecutedTrade) {}
+
+    fn on_alert_sent(&self, _alert: &SentAlert) {}
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredToken {
+    // 'uniswap_v2' or the name of the launchpad feed, see src/api/launchpads.rs
+    pub source: String,
+    // the pool honeypot.is found for launchpad tokens, can be empty
+    pub pair: String,
+    pub contract: String,
+    pub creator: String,
+    pub creation_timestamp: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecutedTrade {
+    pub contract: String,
+    pub symbol: String,
+    pub is_buy: bool,
+    pub amount_usd: f64,
+    pub tokens: f64,
+    pub wallet: String,
+    // sells only
+    pub realized_pnl_usd: Option<f64>,
+    // the mined transaction, trades are only reported once they're on-chain
+    pub hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentAlert {
+    pub chat_id: ChatId,
+    pub category: AlertCategory,
+    pub text: String,
+}
+
+lazy_static! {
+    static ref HOOKS: Vec<Box<dyn Hook>> = registered();
+}
+
+fn registered() -> Vec<Box<dyn Hook>> {
+    #[allow(unused_mut)]
+    let mut hooks: Vec<Box<dyn Hook>> = Vec::new();
+
+    #[cfg(feature = "webhook-hook")]
+    hooks.extend(webhook::WebhookHook::from_env().map(|hook| Box::new(hook) as Box<dyn Hook>));
+
+    for hook in &hooks {
+        info!("Registered hook {}", hook.name());
+    }
+
+    hooks
+}
+
+// every hook sees the token, even after another one rejected it
+fn allows(hooks: &[Box<dyn Hook>], token: &DiscoveredToken) -> bool {
+    hooks.iter().fold(true, |allowed, hook| {
+        hook.on_token_discovered(token) && allowed
+    })
+}
+
+pub fn token_discovered(token: &DiscoveredToken) -> bool {
+    allows(&HOOKS, token)
+}
+
+pub fn trade_executed(trade: &ExecutedTrade) {
+    for hook in HOOKS.iter() {
+        hook.on_trade_executed(trade);
+    }
+}
+
+pub fn alert_sent(alert: &SentAlert) {
+    for hook in HOOKS.iter() {
+        hook.on_alert_sent(alert);
+    }
+}
+
+#[test]
+fn test_token_hooks() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct NoFreshCreators;
+    impl Hook for NoFreshCreators {
+        fn name(&self) -> &'static str {
+            "no_fresh_creators"
+        }
+
+        fn on_token_discovered(&self, token: &DiscoveredToken) -> bool {
+            token.creator != "0xfresh"
+        }
+    }
+
+    struct Counter(Arc<AtomicUsize>);
+    impl Hook for Counter {
+        fn name(&self) -> &'static str {
+            "counter"
+        }
+
+        fn on_token_discovered(&self, _token: &DiscoveredToken) -> bool {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+    }
+
+    let token = |creator: &str| DiscoveredToken {
+        source: String::from("uniswap_v2"),
+        pair: String::from("0xpair"),
+        contract: String::from("0xtoken"),
+        creator: creator.to_owned(),
+        creation_timestamp: 0,
+    };
+    let seen = Arc::new(AtomicUsize::new(0));
+    let hooks: Vec<Box<dyn Hook>> =
+        vec![Box::new(NoFreshCreators), Box::new(Counter(seen.clone()))];
+
+    assert!(allows(&hooks, &token("0xold")));
+    // the counter after the rejecting hook still sees the token
+    assert!(!allows(&hooks, &token("0xfresh")));
+    assert_eq!(seen.load(Ordering::Relaxed), 2);
+    assert!(allows(&[], &token("0xfresh")));
+}
@@ -0,0 +1,91 @@
+use super::{DiscoveredToken, ExecutedTrade, Hook, SentAlert};
+use lazy_static::lazy_static;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::{env, time::Duration};
+
+lazy_static! {
+    static ref CLIENT: Client = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap();
+}
+
+// posts every event as JSON to HOOK_WEBHOOK_URL, e.g. to feed a dashboard or a database
+pub struct WebhookHook {
+    url: String,
+}
+
+impl WebhookHook {
+    pub fn from_env() -> Option<WebhookHook> {
+        env::var("HOOK_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| WebhookHook { url })
+    }
+
+    fn post(&self, event: &str, data: Value) {
+        let url = self.url.clone();
+        let body = json!({ "event": event, "data": data });
+
+        tokio::spawn(async move {
+            let result = CLIENT
+                .post(&url)
+                .json(&body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+
+            if let Err(e) = result {
+                warn!("webhook hook error: {}", e.without_url());
+            }
+        });
+    }
+}
+
+impl Hook for WebhookHook {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn on_token_discovered(&self, token: &DiscoveredToken) -> bool {
+        self.post(
+            "token_discovered",
+            json!({
+                "source": token.source,
+                "pair": token.pair,
+                "contract": token.contract,
+                "creator": token.creator,
+                "creation_timestamp": token.creation_timestamp,
+            }),
+        );
+        true
+    }
+
+    fn on_trade_executed(&self, trade: &ExecutedTrade) {
+        self.post(
+            "trade_executed",
+            json!({
+                "contract": trade.contract,
+                "symbol": trade.symbol,
+                "side": if trade.is_buy { "buy" } else { "sell" },
+                "amount_usd": trade.amount_usd,
+                "tokens": trade.tokens,
+                "wallet": trade.wallet,
+                "realized_pnl_usd": trade.realized_pnl_usd,
+                "hash": trade.hash,
+            }),
+        );
+    }
+
+    fn on_alert_sent(&self, alert: &SentAlert) {
+        self.post(
+            "alert_sent",
+            json!({
+                "chat_id": alert.chat_id.0,
+                "category": alert.category.to_string(),
+                "text": alert.text,
+            }),
+        );
+    }
+}
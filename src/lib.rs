@@ -0,0 +1,18 @@
+#[path = "api/api.rs"]
+pub mod api;
+pub mod backup;
+pub mod cli;
+#[path = "crypto/crypto.rs"]
+pub mod crypto;
+#[path = "hooks/hooks.rs"]
+pub mod hooks;
+pub mod logging;
+pub mod persist;
+pub mod server;
+pub mod tasks;
+#[path = "telegram/telegram.rs"]
+pub mod telegram;
+pub mod utils;
+
+#[macro_use]
+extern crate log;
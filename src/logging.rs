@@ -0,0 +1,368 @@
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use pretty_env_logger::env_logger::{self, filter::Filter};
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex as StdMutex, RwLock,
+    },
+    time::Instant,
+};
+use teloxide::{prelude::*, types::ParseMode, utils::html, RequestError};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::{sleep, Duration},
+};
+use tracing_log::AsLog;
+use tracing_subscriber::{filter::filter_fn, layer::SubscriberExt, Layer};
+
+pub const DEFAULT_FILTERS: &str = "snipers=info";
+// warnings are batched so a failing provider doesn't send a message per request
+const STREAM_BATCH_SECONDS: u64 = 30;
+const MAX_STREAMED_LINES: usize = 20;
+
+// short names accepted by /loglevel, anything else is used as a full module path
+const MODULES: [(&str, &str); 7] = [
+    ("api", "snipers::api"),
+    ("bot", "snipers::telegram::bot"),
+    ("telegram", "snipers::telegram"),
+    ("crypto", "snipers::crypto"),
+    ("tasks", "snipers::tasks"),
+    ("utils", "snipers::utils"),
+    ("all", "snipers"),
+];
+
+lazy_static! {
+    // module -> level, the current filters are rebuilt from these on every change
+    static ref DIRECTIVES: RwLock<BTreeMap<String, LevelFilter>> =
+        RwLock::new(parse_filters(DEFAULT_FILTERS).unwrap());
+    static ref FILTER: RwLock<Filter> = RwLock::new(build_filter(&DIRECTIVES.read().unwrap()));
+    static ref STREAM: StdMutex<Option<UnboundedSender<String>>> = StdMutex::new(None);
+    static ref STREAM_RECEIVER: StdMutex<Option<UnboundedReceiver<String>>> = StdMutex::new(None);
+}
+
+// off by default, the owner opts in with /loglevel stream on
+static STREAMING: AtomicBool = AtomicBool::new(false);
+// provider requests are made, and their failures logged, in the api module
+const PROVIDER_TARGET: &str = "snipers::api";
+// set while a provider is in an outage, the provider lines are only counted then
+static HOLDING_BACK: AtomicBool = AtomicBool::new(false);
+static HELD_BACK_LINES: AtomicU64 = AtomicU64::new(0);
+
+/*
+
+LOG_FORMAT=json writes every line as a JSON object for log shippers like Loki.
+The log records are forwarded to a tracing-subscriber JSON layer, next to the
+structured events of provider requests (provider, latency_ms, error_kind) and
+commands (chat_id, command, latency_ms). Without it the structured events are
+plain log lines with their fields appended. /loglevel filters both.
+
+*/
+fn json_logs() -> bool {
+    env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"))
+}
+
+// the filtering is done here so it can be changed at runtime, the wrapped
+// pretty_env_logger logger or the JSON layer only formats
+struct ReloadableLogger {
+    inner: env_logger::Logger,
+    json: bool,
+}
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        FILTER.read().unwrap().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !FILTER.read().unwrap().matches(record) {
+            return;
+        }
+
+        if self.json {
+            let _ = tracing_log::format_trace(record);
+        } else {
+            self.inner.log(record);
+        }
+
+        // only the bot's own records, failed telegram requests would otherwise stream themselves
+        if record.level() <= Level::Warn
+            && record.target().starts_with("snipers")
+            && STREAMING.load(Ordering::Relaxed)
+        {
+            if HOLDING_BACK.load(Ordering::Relaxed) && record.target().starts_with(PROVIDER_TARGET)
+            {
+                HELD_BACK_LINES.fetch_add(1, Ordering::Relaxed);
+            } else if let Some(sender) = STREAM.lock().unwrap().as_ref() {
+                let _ = sender.send(format!(
+                    "{} {}: {}",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+fn module_path(name: &str) -> String {
+    match MODULES.iter().find(|(short, _)| *short == name) {
+        Some((_, path)) => path.to_string(),
+        None => name.to_owned(),
+    }
+}
+
+// 'api=debug bot=info' or 'api=debug,bot=info', a bare level applies to the whole bot
+pub fn parse_filters(filters: &str) -> Result<BTreeMap<String, LevelFilter>, String> {
+    let mut directives = BTreeMap::new();
+
+    for directive in filters
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|directive| !directive.is_empty())
+    {
+        let (module, level) = match directive.split_once('=') {
+            Some((module, level)) => (module_path(module), level),
+            None => (String::from("snipers"), directive),
+        };
+        let level = LevelFilter::from_str(level).map_err(|_| level.to_owned())?;
+
+        directives.insert(module, level);
+    }
+
+    Ok(directives)
+}
+
+fn build_filter(directives: &BTreeMap<String, LevelFilter>) -> Filter {
+    let mut builder = env_logger::filter::Builder::new();
+
+    for (module, level) in directives {
+        builder.filter_module(module, *level);
+    }
+
+    builder.build()
+}
+
+fn format_filters(directives: &BTreeMap<String, LevelFilter>) -> String {
+    directives
+        .iter()
+        .map(|(module, level)| format!("{}={}", module, level.to_string().to_lowercase()))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+// the /loglevel filters for tracing events, log records were already filtered before they were forwarded
+fn tracing_enabled(metadata: &tracing::Metadata) -> bool {
+    metadata.target() == "log"
+        || FILTER.read().unwrap().enabled(
+            &Metadata::builder()
+                .level(metadata.level().as_log())
+                .target(metadata.target())
+                .build(),
+        )
+}
+
+pub fn init() {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    *STREAM.lock().unwrap() = Some(sender);
+    *STREAM_RECEIVER.lock().unwrap() = Some(receiver);
+
+    let json = json_logs();
+    if json {
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_current_span(false)
+                .with_span_list(false)
+                .with_filter(filter_fn(tracing_enabled)),
+        );
+        tracing::subscriber::set_global_default(subscriber).expect("subscriber is already set");
+    }
+
+    let logger = ReloadableLogger {
+        inner: pretty_env_logger::formatted_builder()
+            .filter_level(LevelFilter::Trace)
+            .build(),
+        json,
+    };
+
+    log::set_boxed_logger(Box::new(logger)).expect("logger is already set");
+    log::set_max_level(FILTER.read().unwrap().filter());
+}
+
+// merges the directives into the current ones, returns the resulting filters
+pub fn set_filters(directives: BTreeMap<String, LevelFilter>) -> String {
+    let mut current = DIRECTIVES.write().unwrap();
+    current.extend(directives);
+
+    apply(&current)
+}
+
+pub fn reset_filters() -> String {
+    let mut current = DIRECTIVES.write().unwrap();
+    *current = parse_filters(DEFAULT_FILTERS).unwrap();
+
+    apply(&current)
+}
+
+fn apply(directives: &BTreeMap<String, LevelFilter>) -> String {
+    let filter = build_filter(directives);
+    log::set_max_level(filter.filter());
+    *FILTER.write().unwrap() = filter;
+
+    format_filters(directives)
+}
+
+pub fn current_filters() -> String {
+    format_filters(&DIRECTIVES.read().unwrap())
+}
+
+pub fn set_streaming(enabled: bool) {
+    STREAMING.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_streaming() -> bool {
+    STREAMING.load(Ordering::Relaxed)
+}
+
+// returns the lines held back since it started once it stops
+pub fn hold_back_stream(hold_back: bool) -> u64 {
+    HOLDING_BACK.store(hold_back, Ordering::Relaxed);
+
+    if hold_back {
+        0
+    } else {
+        HELD_BACK_LINES.swap(0, Ordering::Relaxed)
+    }
+}
+
+// coarse classes of failures, so the JSON logs can be grouped without parsing messages
+pub fn error_kind(e: &(dyn Error + 'static)) -> &'static str {
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        return request_error_kind(e);
+    }
+
+    match e.downcast_ref::<RequestError>() {
+        Some(RequestError::Api(_)) => "telegram_api",
+        Some(RequestError::RetryAfter(_)) => "rate_limited",
+        Some(RequestError::Network(e)) => request_error_kind(e),
+        Some(_) => "telegram",
+        None => "other",
+    }
+}
+
+pub fn request_error_kind(e: &reqwest::Error) -> &'static str {
+    if e.is_timeout() {
+        "timeout"
+    } else if e.is_connect() {
+        "connect"
+    } else if e.status().is_some_and(|status| status.as_u16() == 429) {
+        "rate_limited"
+    } else if e.is_status() {
+        "http_status"
+    } else if e.is_decode() {
+        "decode"
+    } else {
+        "request"
+    }
+}
+
+// '/Buy@snipers_bot PEPE 50' -> '/buy'
+fn command_name(text: &str) -> String {
+    let command = text.split_whitespace().next().unwrap_or_default();
+
+    command.split('@').next().unwrap_or_default().to_lowercase()
+}
+
+// written when the update carrying the command is done with, whichever endpoint handled it
+pub struct CommandLog {
+    chat_id: i64,
+    command: String,
+    started: Instant,
+}
+
+impl CommandLog {
+    pub fn start(chat_id: ChatId, text: &str) -> CommandLog {
+        CommandLog {
+            chat_id: chat_id.0,
+            command: command_name(text),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for CommandLog {
+    fn drop(&mut self) {
+        tracing::info!(
+            target: "snipers::telegram::bot",
+            chat_id = self.chat_id,
+            command = %self.command,
+            latency_ms = self.started.elapsed().as_millis() as u64,
+            "command handled"
+        );
+    }
+}
+
+// sends the streamed warnings and errors to the chat in batches
+pub async fn stream_to_chat(bot: Bot, chat_id: ChatId) {
+    let receiver = STREAM_RECEIVER.lock().unwrap().take();
+    let Some(mut receiver) = receiver else {
+        // the logger wasn't installed, there is nothing to stream
+        return std::future::pending().await;
+    };
+
+    while let Some(line) = receiver.recv().await {
+        sleep(Duration::from_secs(STREAM_BATCH_SECONDS)).await;
+
+        let mut lines = vec![line];
+        while let Ok(line) = receiver.try_recv() {
+            lines.push(line);
+        }
+
+        let skipped = lines.len().saturating_sub(MAX_STREAMED_LINES);
+        let mut text = lines
+            .iter()
+            .take(MAX_STREAMED_LINES)
+            .map(|line| html::escape(line))
+            .collect::<Vec<String>>()
+            .join("\n");
+        if skipped > 0 {
+            text.push_str(&format!("\n... and {} more", skipped));
+        }
+
+        let _ = bot
+            .send_message(chat_id, format!("⚠️ Log warnings\n\n<code>{}</code>", text))
+            .parse_mode(ParseMode::Html)
+            .await;
+    }
+}
+
+#[test]
+fn test_parse_filters() {
+    let directives = parse_filters("api=debug, bot=warn teloxide=info").unwrap();
+
+    assert_eq!(
+        format_filters(&directives),
+        "snipers::api=debug,snipers::telegram::bot=warn,teloxide=info"
+    );
+    assert_eq!(
+        parse_filters("trace").unwrap().get("snipers"),
+        Some(&LevelFilter::Trace)
+    );
+    assert_eq!(parse_filters("api=loud"), Err(String::from("loud")));
+}
+
+#[test]
+fn test_command_name() {
+    assert_eq!(command_name("/Buy@snipers_bot PEPE 50 5"), "/buy");
+    assert_eq!(command_name("/status"), "/status");
+    assert_eq!(command_name(""), "");
+}
@@ -1,33 +1,51 @@
-#[path = "api/api.rs"]
-mod api;
-#[path = "crypto/crypto.rs"]
-mod crypto;
-#[path = "telegram/telegram.rs"]
-mod telegram;
-mod utils;
-
 #[macro_use]
 extern crate log;
+use clap::Parser;
+use snipers::{
+    backup,
+    cli::{self, Cli},
+    logging, telegram,
+};
 use std::env;
 
 #[tokio::main]
 async fn main() {
-    // for teloxide logging
-    // env::set_var("RUST_LOG", "trace");
-    // for snipers logging
-    env::set_var("RUST_LOG", "snipers=info");
-
-    pretty_env_logger::init();
+    // snipers=info by default, teloxide and per-module levels can be changed at runtime with /loglevel
+    logging::init();
 
     env::set_var("PORT", "");
     env::set_var("WEBHOOK_URL", "");
 
     env::set_var("ALCHEMY_API", "");
     env::set_var("TELOXIDE_TOKEN", "");
+    env::set_var("OWNER_ID", "");
     env::set_var("ETH_ADDRESS", "");
     env::set_var("ETHERSCAN_API", "");
     env::set_var("MORALIS_API", "");
     env::set_var("CHAINBASE_API", "");
 
-    telegram::bot::run().await;
+    let args = Cli::parse();
+    // restored before the bot or a subcommand loads the state files
+    if let Some(name) = &args.restore {
+        match backup::restore(name).await {
+            Ok(paths) => info!("Restored {}", paths.join(", ")),
+            Err(e) => {
+                eprintln!("restore failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(command) = args.command {
+        if let Err(e) = cli::run(command, args.json).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Err(e) = telegram::bot::run().await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,150 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{env, fs, io::ErrorKind, path::Path};
+
+/*
+
+What the bot keeps across restarts is saved as JSON files, each one moved
+with its own env var. Files are written next to themselves and renamed over
+the old one, so a crash while saving never leaves half a file behind. Every
+file is listed in STATE_FILES, which is what the backups archive.
+
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StateFile {
+    pub env_var: &'static str,
+    pub default: &'static str,
+    // what the file holds, for the logs
+    pub name: &'static str,
+}
+
+impl StateFile {
+    pub fn path(&self) -> String {
+        env::var(self.env_var).unwrap_or(String::from(self.default))
+    }
+}
+
+pub const API_USAGE: StateFile = StateFile {
+    env_var: "API_USAGE_FILE",
+    default: "api_usage.json",
+    name: "api usage",
+};
+pub const COMMAND_HISTORY: StateFile = StateFile {
+    env_var: "COMMAND_HISTORY_FILE",
+    default: "command_history.json",
+    name: "command history",
+};
+pub const KNOWN_ADDRESSES: StateFile = StateFile {
+    env_var: "KNOWN_ADDRESSES_FILE",
+    default: "known_addresses.json",
+    name: "known addresses",
+};
+pub const PENDING_PROMPTS: StateFile = StateFile {
+    env_var: "PENDING_PROMPTS_FILE",
+    default: "pending_prompts.json",
+    name: "pending prompts",
+};
+pub const PORTFOLIO_HISTORY: StateFile = StateFile {
+    env_var: "PORTFOLIO_HISTORY_FILE",
+    default: "portfolio_history.json",
+    name: "portfolio history",
+};
+pub const RECENT_SNIPES: StateFile = StateFile {
+    env_var: "RECENT_SNIPES_FILE",
+    default: "recent_snipes.json",
+    name: "recent snipes",
+};
+pub const TRADE_LOCKS: StateFile = StateFile {
+    env_var: "TRADE_LOCKS_FILE",
+    default: "trade_locks.json",
+    name: "trade locks",
+};
+pub const WALLETCONNECT_SESSION: StateFile = StateFile {
+    env_var: "WALLETCONNECT_SESSION_FILE",
+    default: "walletconnect_session.json",
+    name: "walletconnect session",
+};
+pub const WALLET_TOKENS: StateFile = StateFile {
+    env_var: "WALLET_TOKENS_FILE",
+    default: "wallet_tokens.json",
+    name: "wallet tokens",
+};
+pub const WATCH_ACTIVITY: StateFile = StateFile {
+    env_var: "WATCH_ACTIVITY_FILE",
+    default: "watch_activity.json",
+    name: "watch activity",
+};
+
+pub const STATE_FILES: [StateFile; 10] = [
+    API_USAGE,
+    COMMAND_HISTORY,
+    KNOWN_ADDRESSES,
+    PENDING_PROMPTS,
+    PORTFOLIO_HISTORY,
+    RECENT_SNIPES,
+    TRADE_LOCKS,
+    WALLETCONNECT_SESSION,
+    WALLET_TOKENS,
+    WATCH_ACTIVITY,
+];
+
+// None when the file doesn't exist yet or can't be read, an invalid file is logged and ignored
+pub fn load<T: DeserializeOwned>(file: StateFile) -> Option<T> {
+    let content = fs::read_to_string(file.path()).ok()?;
+
+    serde_json::from_str(&content)
+        .map_err(|e| error!("invalid {} file, ignoring it: {}", file.name, e))
+        .ok()
+}
+
+pub fn save<T: Serialize + ?Sized>(file: StateFile, value: &T) {
+    let path = file.path();
+    let result = serde_json::to_string(value)
+        .map_err(|e| e.to_string())
+        .and_then(|json| write_atomically(Path::new(&path), json));
+
+    if let Err(e) = result {
+        error!("failed to save {}: {}", file.name, e);
+    }
+}
+
+// a file that was never written is already removed
+pub fn remove(file: StateFile) {
+    if let Err(e) = fs::remove_file(file.path()) {
+        if e.kind() != ErrorKind::NotFound {
+            error!("failed to remove {}: {}", file.name, e);
+        }
+    }
+}
+
+// written next to the file and renamed over it, so nothing ever reads half of it
+pub fn write_atomically(path: &Path, content: String) -> Result<(), String> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    fs::write(&temporary, content)
+        .and_then(|_| fs::rename(&temporary, path))
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_save_and_load() {
+    let file = StateFile {
+        env_var: "PERSIST_TEST_FILE",
+        default: "persist_test.json",
+        name: "persist test",
+    };
+    let path = env::temp_dir().join(format!("snipers-persist-{}.json", std::process::id()));
+    env::set_var(file.env_var, &path);
+
+    assert_eq!(load::<Vec<u64>>(file), None);
+    save(file, &vec![1u64, 2, 3]);
+    assert_eq!(load::<Vec<u64>>(file), Some(vec![1, 2, 3]));
+    assert!(fs::metadata(format!("{}.tmp", path.display())).is_err());
+
+    fs::write(&path, "not json").unwrap();
+    assert_eq!(load::<Vec<u64>>(file), None);
+
+    remove(file);
+    remove(file);
+    assert!(fs::metadata(&path).is_err());
+}
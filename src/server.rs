@@ -0,0 +1,172 @@
+use crate::{
+    api, cli, tasks,
+    telegram::bot::{WATCHED_TOKENS, WATCHED_WALLETS},
+    utils,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::HashMap, env, net::SocketAddr, sync::Arc};
+use teloxide::types::ChatId;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+// a failed bind or server is retried after 5 seconds, doubling up to 10 minutes
+const MIN_RETRY_SECS: u64 = 5;
+const MAX_RETRY_SECS: u64 = 600;
+
+/*
+
+The REST API serves the same data as the CLI subcommands, so dashboards can
+use it without going through Telegram. It's only started when API_SERVER_TOKEN
+is set and every request needs an `Authorization: Bearer <token>` header. It's
+read-only, nothing can be bought, sold or changed through it.
+
+*/
+pub fn token() -> Option<String> {
+    env::var("API_SERVER_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+}
+
+fn addr() -> SocketAddr {
+    env::var("API_SERVER_ADDR")
+        .ok()
+        .and_then(|addr| addr.parse().ok())
+        .unwrap_or_else(|| DEFAULT_ADDR.parse().unwrap())
+}
+
+// compares every byte, so the response time doesn't tell how much of the token was right
+fn is_authorized(header: Option<&str>, token: &str) -> bool {
+    let Some(given) = header.and_then(|header| header.strip_prefix("Bearer ")) else {
+        return false;
+    };
+
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn auth<B>(State(token): State<Arc<String>>, request: Request<B>, next: Next<B>) -> Response {
+    let header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    if !is_authorized(header, &token) {
+        return error(StatusCode::UNAUTHORIZED, "invalid or missing token");
+    }
+    tasks::heartbeat("api_server").await;
+
+    next.run(request).await
+}
+
+fn error(status: StatusCode, message: impl ToString) -> Response {
+    (status, Json(json!({ "error": message.to_string() }))).into_response()
+}
+
+fn respond(result: cli::CliResult) -> Response {
+    match result {
+        Ok((value, _)) => Json(value).into_response(),
+        Err(e) => error(StatusCode::BAD_GATEWAY, e),
+    }
+}
+
+async fn scan(Path(contract): Path<String>) -> Response {
+    if !utils::is_valid_eth_address(&contract) {
+        return error(StatusCode::BAD_REQUEST, "invalid contract address");
+    }
+
+    respond(cli::scan(&contract).await)
+}
+
+async fn portfolio(Path(address): Path<String>) -> Response {
+    if !utils::is_valid_eth_address(&address) {
+        return error(StatusCode::BAD_REQUEST, "invalid wallet address");
+    }
+
+    respond(cli::portfolio(Some(&address)).await)
+}
+
+#[derive(Debug, Deserialize)]
+struct GasQuery {
+    action: Option<String>,
+}
+
+async fn gas(Query(query): Query<GasQuery>) -> Response {
+    respond(cli::gas(query.action.as_deref()).await)
+}
+
+fn by_chat(watchlists: &HashMap<ChatId, Vec<String>>) -> HashMap<String, Vec<String>> {
+    watchlists
+        .iter()
+        .map(|(chat_id, entries)| (chat_id.0.to_string(), entries.clone()))
+        .collect()
+}
+
+async fn watchlist() -> Response {
+    let wallets = by_chat(&WATCHED_WALLETS.lock().await);
+    let tokens = by_chat(&WATCHED_TOKENS.lock().await);
+
+    Json(json!({ "wallets": wallets, "tokens": tokens })).into_response()
+}
+
+// provider request metrics in the Prometheus text format
+async fn metrics() -> Response {
+    let text = api::prometheus_text(&api::get_endpoint_stats().await);
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], text).into_response()
+}
+
+pub fn router(token: String) -> Router {
+    Router::new()
+        .route("/scan/:contract", get(scan))
+        .route("/portfolio/:address", get(portfolio))
+        .route("/gas", get(gas))
+        .route("/watchlist", get(watchlist))
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(Arc::new(token), auth))
+}
+
+// supervised like the other background tasks, so a taken port or a server error is retried
+// with a growing delay instead of returning and taking the bot down
+pub async fn serve(token: String) {
+    let addr = addr();
+    let mut retry_secs = MIN_RETRY_SECS;
+
+    loop {
+        match axum::Server::try_bind(&addr) {
+            Ok(server) => {
+                info!("REST API listening on {}...", addr);
+                if let Err(e) = server
+                    .serve(router(token.clone()).into_make_service())
+                    .await
+                {
+                    error!("REST API server error: {}", e);
+                }
+            }
+            Err(e) => error!("failed to bind the REST API to {}: {}", addr, e),
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(retry_secs)).await;
+        retry_secs = (retry_secs * 2).min(MAX_RETRY_SECS);
+    }
+}
+
+#[test]
+fn test_is_authorized() {
+    assert!(is_authorized(Some("Bearer secret"), "secret"));
+    assert!(!is_authorized(Some("Bearer secreT"), "secret"));
+    assert!(!is_authorized(Some("Bearer secret2"), "secret"));
+    assert!(!is_authorized(Some("secret"), "secret"));
+    assert!(!is_authorized(None, "secret"));
+}
@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use core::fmt;
+use lazy_static::lazy_static;
+use std::{collections::BTreeMap, future::Future};
+use tokio::{sync::Mutex, task::JoinSet};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone)]
+pub enum TaskState {
+    Running,
+    Stopped,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskStatus {
+    pub state: TaskState,
+    pub started_at: DateTime<Utc>,
+    pub last_cycle: Option<DateTime<Utc>>,
+    pub cycles: u64,
+}
+
+#[derive(Debug)]
+pub struct TaskError {
+    pub name: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "background task '{}' failed: {}", self.name, self.reason)
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+lazy_static! {
+    static ref TASKS: Mutex<BTreeMap<&'static str, TaskStatus>> =
+        Mutex::new(BTreeMap::<&'static str, TaskStatus>::new());
+    // cancelled on shutdown, the background tasks and the jobs started by commands share it
+    static ref SHUTDOWN: CancellationToken = CancellationToken::new();
+}
+
+pub fn shutdown_token() -> CancellationToken {
+    SHUTDOWN.clone()
+}
+
+/*
+
+Background loops are never expected to return, so a task that exits or panics
+is reported as a fatal error through the JoinSet, while cancelling the token
+stops every task cleanly on shutdown.
+
+*/
+pub fn spawn<F>(
+    tasks: &mut JoinSet<Result<(), TaskError>>,
+    name: &'static str,
+    shutdown: CancellationToken,
+    future: F,
+) where
+    F: Future<Output = ()> + Send + 'static,
+{
+    info!("Spawning {}...", name);
+
+    tasks.spawn(async move {
+        set_state(name, TaskState::Running).await;
+
+        // the inner task turns panics into JoinErrors instead of tearing down the JoinSet task
+        let handle = tokio::spawn(future);
+        let abort_handle = handle.abort_handle();
+
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                abort_handle.abort();
+                set_state(name, TaskState::Stopped).await;
+                Ok(())
+            }
+            result = handle => {
+                let reason = match result {
+                    Ok(()) => String::from("task exited unexpectedly"),
+                    Err(e) if e.is_panic() => String::from("task panicked"),
+                    Err(e) => e.to_string(),
+                };
+
+                set_state(name, TaskState::Failed(reason.clone())).await;
+                Err(TaskError { name, reason })
+            }
+        }
+    });
+}
+
+/*
+
+Jobs started by commands, like a TWAP or tracking a transaction, finish on
+their own. They stop on shutdown like the background tasks, but finishing or
+failing only ends the job, it doesn't take the bot down.
+
+*/
+pub fn spawn_job<F>(name: &'static str, future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let shutdown = shutdown_token();
+
+    tokio::spawn(async move {
+        set_state(name, TaskState::Running).await;
+
+        let handle = tokio::spawn(future);
+        let abort_handle = handle.abort_handle();
+
+        let state = tokio::select! {
+            _ = shutdown.cancelled() => {
+                abort_handle.abort();
+                TaskState::Stopped
+            }
+            result = handle => match result {
+                Ok(()) => TaskState::Stopped,
+                Err(e) if e.is_panic() => TaskState::Failed(String::from("job panicked")),
+                Err(e) => TaskState::Failed(e.to_string()),
+            },
+        };
+        if let TaskState::Failed(reason) = &state {
+            error!("job '{}' failed: {}", name, reason);
+        }
+        set_state(name, state).await;
+    });
+}
+
+// called by the background loops on every cycle
+pub async fn heartbeat(name: &'static str) {
+    if let Some(status) = TASKS.lock().await.get_mut(name) {
+        status.last_cycle = Some(Utc::now());
+        status.cycles += 1;
+    }
+}
+
+pub async fn statuses() -> Vec<(&'static str, TaskStatus)> {
+    TASKS
+        .lock()
+        .await
+        .iter()
+        .map(|(name, status)| (*name, status.clone()))
+        .collect()
+}
+
+async fn set_state(name: &'static str, state: TaskState) {
+    TASKS
+        .lock()
+        .await
+        .entry(name)
+        .and_modify(|status| status.state = state.clone())
+        .or_insert(TaskStatus {
+            state,
+            started_at: Utc::now(),
+            last_cycle: None,
+            cycles: 0,
+        });
+}
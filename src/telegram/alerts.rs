@@ -0,0 +1,442 @@
+use crate::{
+    hooks::{self, SentAlert},
+    telegram::{bot::SETTINGS, shards},
+};
+use chrono::Utc;
+use core::fmt;
+use futures_util::future::BoxFuture;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, future::IntoFuture, str::FromStr};
+use teloxide::{
+    payloads::{SendMessage, SendMessageSetters},
+    prelude::*,
+    requests::JsonRequest,
+    types::{ParseMode, ReplyMarkup},
+    RequestError,
+};
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AlertCategory {
+    Watch,
+    Sniper,
+    Gas,
+    Digest,
+}
+
+impl fmt::Display for AlertCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AlertCategory::Watch => write!(f, "watch"),
+            AlertCategory::Sniper => write!(f, "sniper"),
+            AlertCategory::Gas => write!(f, "gas"),
+            AlertCategory::Digest => write!(f, "digest"),
+        }
+    }
+}
+
+impl FromStr for AlertCategory {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "watch" => Ok(AlertCategory::Watch),
+            "sniper" => Ok(AlertCategory::Sniper),
+            "gas" => Ok(AlertCategory::Gas),
+            "digest" => Ok(AlertCategory::Digest),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Priority {
+    // always sent with a notification
+    High,
+    // silent during quiet hours
+    #[default]
+    Normal,
+    // always sent silently
+    Low,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Priority::High => write!(f, "high"),
+            Priority::Normal => write!(f, "normal"),
+            Priority::Low => write!(f, "low"),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "high" => Ok(Priority::High),
+            "normal" => Ok(Priority::Normal),
+            "low" => Ok(Priority::Low),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NotificationPreferences {
+    pub priorities: HashMap<AlertCategory, Priority>,
+    // (start, end) hours in the chat's time zone, the end hour is exclusive and may wrap around midnight
+    pub quiet_hours: Option<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertRoutes {
+    pub categories: HashMap<AlertCategory, ChatId>,
+    // lowercase wallet address -> destination, takes precedence over the watch category
+    pub wallets: HashMap<String, ChatId>,
+}
+
+// what a watched wallet did, decoded from its token transfer and the transaction that caused it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WalletActivity {
+    Swap,
+    // a swap receiving a token the wallet never held before
+    NewTokenBuy,
+    Transfer,
+    Deployment,
+    // tokens sent to an exchange's deposit address or hot wallet
+    CexDeposit,
+    // tokens sent from an exchange's hot wallet
+    CexWithdrawal,
+}
+
+impl WalletActivity {
+    pub fn is_exchange_transfer(&self) -> bool {
+        matches!(
+            self,
+            WalletActivity::CexDeposit | WalletActivity::CexWithdrawal
+        )
+    }
+
+    // plain transfers and exchange deposits and withdrawals, none of them went through a DEX
+    pub fn is_transfer(&self) -> bool {
+        *self == WalletActivity::Transfer || self.is_exchange_transfer()
+    }
+}
+
+impl fmt::Display for WalletActivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WalletActivity::Swap => write!(f, "swap"),
+            WalletActivity::NewTokenBuy => write!(f, "new token buy"),
+            WalletActivity::Transfer => write!(f, "transfer"),
+            WalletActivity::Deployment => write!(f, "contract deployment"),
+            WalletActivity::CexDeposit => write!(f, "possible sell via CEX"),
+            WalletActivity::CexWithdrawal => write!(f, "withdrawal from CEX"),
+        }
+    }
+}
+
+// without any flag set every activity of the wallet is alerted
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WalletFilter {
+    pub swaps: bool,
+    pub new_tokens: bool,
+    pub deployments: bool,
+    pub min_transfer_usd: Option<f64>,
+    // lowercase contracts, with them or held_tokens only transfers of those tokens are alerted
+    pub tokens: Vec<String>,
+    // the tokens the bot's wallet holds right now
+    pub held_tokens: bool,
+}
+
+impl WalletFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == WalletFilter::default()
+    }
+
+    pub fn filters_activity(&self) -> bool {
+        self.swaps || self.new_tokens || self.deployments || self.min_transfer_usd.is_some()
+    }
+
+    pub fn filters_tokens(&self) -> bool {
+        !self.tokens.is_empty() || self.held_tokens
+    }
+
+    // value_usd is only needed for transfers
+    pub fn allows(&self, activity: WalletActivity, value_usd: Option<f64>) -> bool {
+        if !self.filters_activity() {
+            return true;
+        }
+
+        match activity {
+            WalletActivity::Swap => self.swaps,
+            WalletActivity::NewTokenBuy => self.swaps || self.new_tokens,
+            WalletActivity::Deployment => self.deployments,
+            // a deposit is a sell that didn't go through a DEX, so it's a swap too
+            WalletActivity::CexDeposit => self.swaps || self.allows_transfer(value_usd),
+            WalletActivity::Transfer | WalletActivity::CexWithdrawal => {
+                self.allows_transfer(value_usd)
+            }
+        }
+    }
+
+    fn allows_transfer(&self, value_usd: Option<f64>) -> bool {
+        self.min_transfer_usd
+            .is_some_and(|min| value_usd.unwrap_or(0.0) >= min)
+    }
+
+    // held is None when the bot's holdings couldn't be read, a missed sell is worse than a few
+    // extra alerts so every token is let through then
+    pub fn allows_token(&self, contract: &str, held: Option<&[String]>) -> bool {
+        if !self.filters_tokens() {
+            return true;
+        }
+
+        let contract = contract.to_lowercase();
+        if self.tokens.contains(&contract) {
+            return true;
+        }
+
+        match held {
+            Some(held) => self.held_tokens && held.contains(&contract),
+            None => self.held_tokens,
+        }
+    }
+}
+
+impl fmt::Display for WalletFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut flags = vec![];
+        if self.swaps {
+            flags.push(String::from("swaps"));
+        }
+        if self.new_tokens {
+            flags.push(String::from("new token buys"));
+        }
+        if self.deployments {
+            flags.push(String::from("deployments"));
+        }
+        if let Some(min) = self.min_transfer_usd {
+            flags.push(format!("transfers above ${}", min));
+        }
+        let activity = if flags.is_empty() {
+            String::from("everything")
+        } else {
+            flags.join(", ")
+        };
+
+        let mut tokens = self.tokens.clone();
+        if self.held_tokens {
+            tokens.push(String::from("the tokens the bot holds"));
+        }
+        if tokens.is_empty() {
+            write!(f, "{}", activity)
+        } else {
+            write!(f, "{} involving {}", activity, tokens.join(", "))
+        }
+    }
+}
+
+lazy_static! {
+    // chat -> lowercase wallet -> filter, wallets without an entry alert everything
+    pub static ref WALLET_FILTERS: Mutex<HashMap<ChatId, HashMap<String, WalletFilter>>> =
+        Mutex::new(HashMap::<ChatId, HashMap<String, WalletFilter>>::new());
+    pub static ref ALERT_ROUTES: Mutex<HashMap<ChatId, AlertRoutes>> =
+        Mutex::new(HashMap::<ChatId, AlertRoutes>::new());
+    pub static ref NOTIFICATION_PREFERENCES: Mutex<HashMap<ChatId, NotificationPreferences>> =
+        Mutex::new(HashMap::<ChatId, NotificationPreferences>::new());
+}
+
+// the message of an alert, the alert hooks only run once telegram accepted it
+pub struct AlertRequest {
+    request: JsonRequest<SendMessage>,
+    alert: SentAlert,
+}
+
+impl AlertRequest {
+    pub fn parse_mode(self, parse_mode: ParseMode) -> Self {
+        AlertRequest {
+            request: self.request.parse_mode(parse_mode),
+            ..self
+        }
+    }
+
+    pub fn disable_web_page_preview(self, disable: bool) -> Self {
+        AlertRequest {
+            request: self.request.disable_web_page_preview(disable),
+            ..self
+        }
+    }
+
+    pub fn reply_markup<T: Into<ReplyMarkup>>(self, reply_markup: T) -> Self {
+        AlertRequest {
+            request: self.request.reply_markup(reply_markup),
+            ..self
+        }
+    }
+}
+
+impl IntoFuture for AlertRequest {
+    type Output = Result<Message, RequestError>;
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            shards::pace().await;
+            let message = self.request.await?;
+            hooks::alert_sent(&self.alert);
+            Ok(message)
+        })
+    }
+}
+
+// every alert goes through here, so routing and notification preferences apply to all of them
+pub async fn send_alert(
+    bot: &Bot,
+    chat_id: ChatId,
+    category: AlertCategory,
+    wallet: Option<&str>,
+    text: String,
+) -> AlertRequest {
+    let destination = destination(chat_id, category, wallet).await;
+    let hour = SETTINGS.get(chat_id).await.locale.hour(Utc::now());
+    let silent = is_silent(chat_id, category, hour).await;
+
+    AlertRequest {
+        request: bot
+            .send_message(destination, text.clone())
+            .disable_notification(silent),
+        alert: SentAlert {
+            chat_id,
+            category,
+            text,
+        },
+    }
+}
+
+async fn is_silent(chat_id: ChatId, category: AlertCategory, hour: u32) -> bool {
+    let preferences = NOTIFICATION_PREFERENCES.lock().await;
+
+    let Some(chat_preferences) = preferences.get(&chat_id) else {
+        return false;
+    };
+
+    match chat_preferences
+        .priorities
+        .get(&category)
+        .copied()
+        .unwrap_or_default()
+    {
+        Priority::High => false,
+        Priority::Low => true,
+        Priority::Normal => chat_preferences
+            .quiet_hours
+            .is_some_and(|(start, end)| is_quiet_hour(start, end, hour)),
+    }
+}
+
+fn is_quiet_hour(start: u32, end: u32, hour: u32) -> bool {
+    if start <= end {
+        hour >= start && hour < end
+    } else {
+        hour >= start || hour < end
+    }
+}
+
+pub async fn wallet_filter(chat_id: ChatId, wallet: &str) -> WalletFilter {
+    WALLET_FILTERS
+        .lock()
+        .await
+        .get(&chat_id)
+        .and_then(|filters| filters.get(&wallet.to_lowercase()))
+        .cloned()
+        .unwrap_or_default()
+}
+
+// alerts are sent to the chat that set them up, unless it routed them somewhere else
+pub async fn destination(chat_id: ChatId, category: AlertCategory, wallet: Option<&str>) -> ChatId {
+    let routes = ALERT_ROUTES.lock().await;
+
+    let Some(chat_routes) = routes.get(&chat_id) else {
+        return chat_id;
+    };
+
+    if let Some(wallet_route) = wallet.and_then(|w| chat_routes.wallets.get(&w.to_lowercase())) {
+        return *wallet_route;
+    }
+
+    *chat_routes.categories.get(&category).unwrap_or(&chat_id)
+}
+
+#[test]
+fn test_is_quiet_hour() {
+    assert_eq!(is_quiet_hour(1, 7, 3), true);
+    assert_eq!(is_quiet_hour(1, 7, 7), false);
+    assert_eq!(is_quiet_hour(23, 7, 23), true);
+    assert_eq!(is_quiet_hour(23, 7, 2), true);
+    assert_eq!(is_quiet_hour(23, 7, 12), false);
+}
+
+#[test]
+fn test_wallet_filter_allows() {
+    let everything = WalletFilter::default();
+    assert_eq!(everything.allows(WalletActivity::Transfer, None), true);
+
+    let filter = WalletFilter {
+        swaps: true,
+        min_transfer_usd: Some(1000.0),
+        ..Default::default()
+    };
+    assert_eq!(filter.allows(WalletActivity::Swap, None), true);
+    assert_eq!(filter.allows(WalletActivity::NewTokenBuy, None), true);
+    assert_eq!(filter.allows(WalletActivity::Deployment, None), false);
+    assert_eq!(filter.allows(WalletActivity::Transfer, Some(500.0)), false);
+    assert_eq!(filter.allows(WalletActivity::Transfer, Some(1500.0)), true);
+    assert_eq!(filter.allows(WalletActivity::CexDeposit, None), true);
+    assert_eq!(
+        filter.allows(WalletActivity::CexWithdrawal, Some(500.0)),
+        false
+    );
+
+    let transfers = WalletFilter {
+        min_transfer_usd: Some(1000.0),
+        ..Default::default()
+    };
+    assert_eq!(
+        transfers.allows(WalletActivity::CexDeposit, Some(500.0)),
+        false
+    );
+    assert_eq!(
+        transfers.allows(WalletActivity::CexDeposit, Some(1500.0)),
+        true
+    );
+}
+
+#[test]
+fn test_wallet_filter_tokens() {
+    let held = vec![String::from("0xheld")];
+    let filter = WalletFilter {
+        tokens: vec![String::from("0xabc")],
+        ..Default::default()
+    };
+    // a token filter alone doesn't filter the kind of activity
+    assert!(filter.allows(WalletActivity::Transfer, None));
+    assert!(filter.allows_token("0xABC", Some(&held)));
+    assert!(!filter.allows_token("0xheld", Some(&held)));
+
+    let filter = WalletFilter {
+        swaps: true,
+        held_tokens: true,
+        ..Default::default()
+    };
+    assert!(filter.allows_token("0xheld", Some(&held)));
+    assert!(!filter.allows_token("0xother", Some(&held)));
+    assert!(filter.allows_token("0xother", None));
+    assert_eq!(
+        filter.to_string(),
+        "swaps involving the tokens the bot holds"
+    );
+
+    assert!(WalletFilter::default().allows_token("0xother", Some(&held)));
+}
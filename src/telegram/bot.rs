@@ -1,28 +1,89 @@
-use crate::{api, utils};
+use crate::{
+    api, backup,
+    crypto::{
+        balance, exits, history, launches, payment, positions, routes, safe, trading, wallet,
+        walletconnect,
+    },
+    hooks::{self, ExecutedTrade},
+    logging, server,
+    tasks::{self, TaskState},
+    telegram::{
+        alerts::{self, AlertCategory, Priority, WalletActivity, WalletFilter},
+        commandhistory, dca,
+        debug::{self, DebugSection},
+        deeplinks::{self, DeepLink},
+        digest,
+        expiry::{self, WatchKind},
+        groups::{self, Permission},
+        input, lanes,
+        locale::Locale,
+        metadata::{self, TokenMetadata},
+        profiles, prompts, scans,
+        settings::{SettingsStore, SettingsToggle},
+        shards,
+        symbols::{self, Resolution},
+        templates::{self, TemplateKind},
+        tradelock, walletimport,
+    },
+    utils,
+};
 use chrono::{DateTime, Duration, Utc};
 use core::fmt;
 use lazy_static::lazy_static;
-use std::{collections::HashMap, env, str::FromStr};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ops::ControlFlow,
+    str::FromStr,
+    sync::Arc,
+};
 use teloxide::{
     dispatching::{
         dialogue::{self, GetChatId, InMemStorage},
         UpdateFilterExt, UpdateHandler,
     },
+    net::Download,
     prelude::*,
-    types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode},
+    types::{
+        Document, InlineKeyboardButton, InlineKeyboardMarkup, InlineQueryResult,
+        InlineQueryResultArticle, InputFile, InputMessageContent, InputMessageContentText, Me,
+        MediaKind, MediaText, MessageEntityKind, MessageId, MessageKind, ParseMode, UpdateKind,
+        User, UserId,
+    },
     update_listeners::webhooks,
     utils::{
         command::{parse_command, BotCommands},
         html,
     },
 };
-use thousands::Separable;
-use tokio::sync::Mutex;
-use utils::hyperlinks_from_contract;
+use tokio::{sync::Mutex, task::JoinSet};
+use utils::{hyperlinks_from_contract, Chain, ChartSite};
 
 type MyDialogue = Dialogue<State, InMemStorage<State>>;
 type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
+// telegram caps inline keyboards at 100 buttons, a portfolio row has up to 4
+const MAX_PORTFOLIO_ACTION_ROWS: usize = 25;
+const MAX_STATUS_ENDPOINTS: usize = 10;
+// a watchlist export is a few kilobytes, anything this big is the wrong file
+const MAX_IMPORT_FILE_BYTES: u32 = 1024 * 1024;
+// new tokens are only monitored for a couple of hours
+const MAX_LAUNCH_DELAY_BLOCKS: u64 = 300;
+// a wallet's latest transfers rarely reach further back than a month
+const MAX_RECENT_BUYS_DAYS: i64 = 30;
+const MAX_LISTED_RECENT_BUYS: usize = 20;
+
+// portfolio quick sells don't ask for a slippage
+const DEFAULT_SLIPPAGE: f32 = 5.0;
+// trades moving the price more than this are flagged unless the chat set its own limit
+const DEFAULT_MAX_PRICE_IMPACT: f64 = 3.0;
+// percentage points the tax of a larger simulated trade may exceed the smallest one's by
+const SIZE_TAX_INCREASE_WARNING: f64 = 2.0;
+// the bot holds no private keys, only a wallet paired with /walletconnect signs and sends trades
+const NOT_SIGNED: &str =
+    "the bot can't sign transactions, pair the wallet with /walletconnect to trade from it";
+
 #[derive(Clone, Debug)]
 enum OrderType {
     Buy,
@@ -53,13 +114,25 @@ impl FromStr for OrderType {
 struct TradeToken {
     contract: Option<String>,
     amount: Option<f64>,
+    // sells given in tokens, amount is then their usd value when they were validated
+    tokens: Option<trading::TokenAmount>,
     slippage: Option<f32>,
     order_type: OrderType,
+    // lowercase executing wallet, None until a buy's wallet is picked
+    wallet: Option<String>,
+    // buys only, executed in slices instead of all at once
+    twap: Option<trading::Twap>,
+    // the pools the swap goes through, None sells straight into WETH
+    route: Option<routes::SwapRoute>,
 }
 
 impl fmt::Display for TradeToken {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // TradeToken will only be displayed if parameters are correct
+        if let Some(wallet) = &self.wallet {
+            writeln!(f, "👛 Wallet: {}", wallet)?;
+        }
+
         match self.order_type {
             OrderType::Buy => write!(
                 f,
@@ -69,30 +142,261 @@ impl fmt::Display for TradeToken {
                 self.slippage.as_ref().unwrap(),
                 self.order_type
             ),
-            OrderType::Sell => write!(
-                f,
-                "📄 Contract: {}\n💰Amount: {}\n🏷 Slippage: {}\n🔴 Order type: {}",
-                self.contract.as_ref().unwrap(),
-                self.amount.as_ref().unwrap(),
-                self.slippage.as_ref().unwrap(),
-                self.order_type
-            ),
+            OrderType::Sell => match &self.tokens {
+                Some(tokens) => write!(
+                    f,
+                    "📄 Contract: {}\n💰Amount: {} tokens ({} base units, {} decimals) ≈ ${:.2}\n🏷 Slippage: {}\n🔴 Order type: {}",
+                    self.contract.as_ref().unwrap(),
+                    tokens,
+                    tokens.raw,
+                    tokens.decimals,
+                    self.amount.as_ref().unwrap(),
+                    self.slippage.as_ref().unwrap(),
+                    self.order_type
+                ),
+                None => write!(
+                    f,
+                    "📄 Contract: {}\n💰Amount: {}\n🏷 Slippage: {}\n🔴 Order type: {}",
+                    self.contract.as_ref().unwrap(),
+                    self.amount.as_ref().unwrap(),
+                    self.slippage.as_ref().unwrap(),
+                    self.order_type
+                ),
+            },
+        }?;
+
+        if let Some(twap) = &self.twap {
+            write!(f, "\n⏱ TWAP: {}", twap)?;
+        }
+        if let Some(route) = self.route.as_ref().filter(|route| !route.is_direct()) {
+            write!(f, "\n🔀 Route: {}", route)?;
+        }
+        Ok(())
+    }
+}
+
+// inline buttons under /portfolio and new token alerts, the callback data is '<action>:<contract>'
+#[derive(Clone, Debug, PartialEq)]
+enum PortfolioAction {
+    Buy { contract: String, usd: u32 },
+    Sell { contract: String, percent: u8 },
+    Scan(String),
+}
+
+// the buy buttons under new token alerts
+const NEW_TOKEN_BUY_AMOUNTS_USD: [u32; 2] = [50, 100];
+// starts the verdict a re-check appends to a new token alert, the next re-check replaces it
+const RECHECK_HEADER: &str = "🔄 Re-checked at";
+
+impl PortfolioAction {
+    fn callback_data(&self) -> String {
+        match self {
+            PortfolioAction::Buy { contract, usd } => format!("buy{}:{}", usd, contract),
+            PortfolioAction::Sell { contract, percent } => format!("sell{}:{}", percent, contract),
+            PortfolioAction::Scan(contract) => format!("scan:{}", contract),
+        }
+    }
+
+    fn parse(data: &str) -> Option<PortfolioAction> {
+        let (action, contract) = data.split_once(':')?;
+        if !utils::is_valid_eth_address(contract) {
+            return None;
+        }
+
+        if action == "scan" {
+            return Some(PortfolioAction::Scan(contract.to_owned()));
+        }
+
+        if let Some(usd) = action.strip_prefix("buy") {
+            return match usd.parse::<u32>() {
+                Ok(usd) if usd > 0 => Some(PortfolioAction::Buy {
+                    contract: contract.to_owned(),
+                    usd,
+                }),
+                _ => None,
+            };
+        }
+
+        match action.strip_prefix("sell")?.parse::<u8>() {
+            Ok(percent) if (1..=100).contains(&percent) => Some(PortfolioAction::Sell {
+                contract: contract.to_owned(),
+                percent,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// launch criteria new tokens have to meet before they're alerted, empty lets every launch through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SniperFilter {
+    pub min_liquidity_eth: Option<f64>,
+    pub min_liquidity_usd: Option<f64>,
+    pub max_fdv_usd: Option<f64>,
+    // unique buyers other than the deployer and the wallets it funded, and what they spent
+    pub min_buyers: Option<usize>,
+    pub min_buy_volume_eth: Option<f64>,
+    pub min_buy_volume_usd: Option<f64>,
+}
+
+impl SniperFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == SniperFilter::default()
+    }
+
+    pub fn needs_metrics(&self) -> bool {
+        self.min_liquidity_eth.is_some()
+            || self.min_liquidity_usd.is_some()
+            || self.max_fdv_usd.is_some()
+    }
+
+    pub fn needs_buyers(&self) -> bool {
+        self.min_buyers.is_some()
+            || self.min_buy_volume_eth.is_some()
+            || self.min_buy_volume_usd.is_some()
+    }
+
+    pub fn allows_buyers(&self, buyers: &api::EarlyBuyers) -> bool {
+        self.min_buyers.map_or(true, |min| buyers.buyers >= min)
+            && self
+                .min_buy_volume_eth
+                .map_or(true, |min| buyers.volume_eth >= min)
+            && self
+                .min_buy_volume_usd
+                .map_or(true, |min| buyers.volume_usd >= min)
+    }
+
+    pub fn allows(&self, metrics: &api::LaunchMetrics) -> bool {
+        self.allows_liquidity(metrics.liquidity_eth, metrics.liquidity_usd)
+            && self.max_fdv_usd.map_or(true, |max| metrics.fdv_usd <= max)
+    }
+
+    pub fn allows_liquidity(&self, liquidity_eth: f64, liquidity_usd: f64) -> bool {
+        self.min_liquidity_eth
+            .map_or(true, |min| liquidity_eth >= min)
+            && self
+                .min_liquidity_usd
+                .map_or(true, |min| liquidity_usd >= min)
+    }
+}
+
+impl fmt::Display for SniperFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "every launch");
+        }
+
+        let mut criteria = vec![];
+        if let Some(min) = self.min_liquidity_eth {
+            criteria.push(format!("liquidity above {} ETH", min));
+        }
+        if let Some(min) = self.min_liquidity_usd {
+            criteria.push(format!("liquidity above ${}", min));
+        }
+        if let Some(max) = self.max_fdv_usd {
+            criteria.push(format!("FDV below ${}", max));
+        }
+        if let Some(min) = self.min_buyers {
+            criteria.push(format!("at least {} buyers", min));
+        }
+        if let Some(min) = self.min_buy_volume_eth {
+            criteria.push(format!("buys above {} ETH", min));
+        }
+        if let Some(min) = self.min_buy_volume_usd {
+            criteria.push(format!("buys above ${}", min));
+        }
+
+        write!(f, "{}", criteria.join(", "))
+    }
+}
+
+// what happens to trades that would move the price more than the percentage
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PriceImpactLimit {
+    Off,
+    Warn(f64),
+    Cap(f64),
+}
+
+impl Default for PriceImpactLimit {
+    fn default() -> Self {
+        PriceImpactLimit::Warn(DEFAULT_MAX_PRICE_IMPACT)
+    }
+}
+
+impl fmt::Display for PriceImpactLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceImpactLimit::Off => write!(f, "not checked"),
+            PriceImpactLimit::Warn(percent) => write!(f, "warned above {}%", percent),
+            PriceImpactLimit::Cap(percent) => write!(f, "capped at {}%", percent),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct Settings {
     pub hide_zero_token_balances: bool,
     pub snipe_new_tokens: bool,
+    // sell with approve + swap instead of Permit2 for tokens that don't support it
+    pub classic_approvals: bool,
+    // skip intermediate tokens, fake intermediate pools can drain the swap
+    pub direct_routes_only: bool,
+    // the owner's opt-in to backrun registered launches with Flashbots bundles
+    pub same_block_snipes: bool,
+    pub sniper_filter: SniperFilter,
+    // launchpad tokens have no Uniswap V2 pair, only their liquidity can be filtered
+    pub launchpad_filter: SniperFilter,
+    pub chart_site: ChartSite,
+    // new token alerts wait this many blocks after the liquidity was added
+    pub launch_delay_blocks: u64,
+    pub price_impact_limit: PriceImpactLimit,
+    pub sell_proceeds: trading::SellProceeds,
+    pub locale: Locale,
+}
+
+// destructive actions wait in the dialogue state until they're confirmed
+#[derive(Clone, Debug)]
+enum ConfirmableAction {
+    WatchWallets(Vec<String>),
+    WatchTokens(Vec<String>),
+}
+
+impl ConfirmableAction {
+    // returns the reply for the chat
+    async fn execute(self, chat_id: ChatId) -> String {
+        match self {
+            ConfirmableAction::WatchWallets(wallets) => set_watched_wallets(chat_id, wallets).await,
+            ConfirmableAction::WatchTokens(contracts) => {
+                set_watched_tokens(chat_id, contracts).await
+            }
+        }
+    }
+}
+
+// a confirmed trade waiting for the chat's PIN or authenticator code, see tradelock.rs
+#[derive(Clone, Debug)]
+enum GuardedTrade {
+    // with the funding wallet picked for a buy
+    Trade(TradeToken),
+    // the id of the DCA plan whose proposed buy was confirmed
+    Dca(u32),
 }
 
 #[derive(Clone, Default)]
 enum State {
     #[default]
     Start,
-    Confirm,
+    // the trade waiting for the yes, every chat stages its own
+    Confirm(TradeToken),
+    ConfirmAction(ConfirmableAction),
+    VerifyTrade(GuardedTrade),
     Settings,
+    PickToken {
+        command: String,
+        args: Vec<String>,
+        candidates: Vec<String>,
+    },
 }
 
 #[derive(BotCommands, Clone, Debug)]
@@ -101,51 +405,420 @@ enum State {
     rename_rule = "lowercase"
 )]
 enum Command {
+    // deep links open the chat with '/start <payload>'
+    #[command(description = "off")]
+    Start(String),
     #[command(description = "list availabe commands")]
     Help,
-    #[command(description = "buy ERC-20 token")]
+    #[command(
+        description = "buy ERC-20 token <token> <usd> <slippage%> [twap <slices> <minutes>]"
+    )]
     Buy(String),
-    #[command(description = "sell ERC-20 token")]
+    #[command(description = "sell ERC-20 token <token> <usd> <slippage%>")]
     Sell(String),
+    #[command(
+        description = "sell a whole position after a checklist of its tax, liquidity, price impact and gas <token>"
+    )]
+    Exit(String),
+    #[command(
+        description = "buy a token on a schedule <token> <usd> <30m|4h|1d|1w> [confirm] | list | pause|resume|stop <id>"
+    )]
+    Dca(String),
     #[command(description = "get wallet ERC-20 token balances")]
     Portfolio,
-    #[command(description = "get current eth gas")]
-    Gas,
-    #[command(description = "start monitoring etherum wallets")]
+    #[command(description = "get a payment link and QR code to top up the wallet [eth]")]
+    Receive(String),
+    #[command(
+        description = "send ETH from the wallet paired through WalletConnect <address> <eth>"
+    )]
+    Send(String),
+    #[command(
+        description = "get current eth gas and fees, optionally for a gas amount or action [gas|transfer|erc20|approve|v2swap|v3swap|nft]"
+    )]
+    Gas(String),
+    #[command(
+        description = "show the gas your wallets spent in the last day, week and month by contract [day|week|month]"
+    )]
+    Gasspent(String),
+    #[command(description = "start monitoring etherum wallets <wallet> [wallet...] | off")]
     Watch(String),
-    #[command(description = "scan an ERC-20 token")]
+    #[command(
+        description = "only alert specific activity of a watched wallet <wallet> <swaps|newtokens|deploys|min=usd|token=contractOrSymbol|held|off>"
+    )]
+    Watchfilter(String),
+    #[command(description = "start monitoring ERC-20 token activity <token> [token...] | off")]
+    Watchtoken(String),
+    #[command(
+        description = "add the wallets of an Etherscan or Nansen CSV export to the watchlist, send the file with this command as its caption"
+    )]
+    Importwallets,
+    #[command(
+        description = "inspect an address: wallet or contract, its type, verification and creator <address>"
+    )]
+    Inspect(String),
+    #[command(
+        description = "trace where a token creator's ETH came from, flagging mixers and flagged wallets <token>"
+    )]
+    Creator(String),
+    #[command(
+        description = "list the tokens a wallet bought recently with entry and current prices <address> [days]"
+    )]
+    Recentbuys(String),
+    #[command(description = "scan an ERC-20 token <token>")]
     Scan(String),
     #[command(description = "change bot settings")]
     Settings,
+    #[command(
+        description = "only alert new tokens meeting launch criteria [launchpad] <minliq=eth|minliq=usd$|maxfdv=usd|minbuyers=count|minvol=eth|minvol=usd$|off>"
+    )]
+    Sniperfilter(String),
+    #[command(
+        description = "fix a token's metadata <token> [symbol=] [name=] [decimals=] [logo=] | <token> off | list"
+    )]
+    Tokenmeta(String),
+    #[command(description = "import a token list in the Uniswap format <url> | off")]
+    Tokenlist(String),
+    #[command(
+        description = "customize alert and portfolio messages <wallet|sniper|portfolio> [text|reset]"
+    )]
+    Template(String),
+    #[command(
+        description = "chart the portfolio value [7|30] or set the chart site of token links [dexscreener|dextools|defined]"
+    )]
+    Chart(String),
+    #[command(description = "wait before alerting new tokens to skip anti-snipe taxes <blocks>")]
+    Launchdelay(String),
+    #[command(description = "warn about or cap trades above a price impact <percent> [cap] | off")]
+    Maximpact(String),
+    #[command(
+        description = "keep sell proceeds as ETH or WETH or swap them to USDC [eth|weth|usdc]"
+    )]
+    Proceeds(String),
+    #[command(
+        description = "set the time zone and number style of the chat [timezone] [en|eu|fr|ch]"
+    )]
+    Locale(String),
     #[command(description = "cancel current command")]
     Cancel,
+    #[command(description = "run your previous report or scan again")]
+    Last,
+    #[command(description = "list your last 10 reports and scans with buttons to run them again")]
+    Historycmd,
+    #[command(description = "scan recent large transfers of an ERC-20 token <token> [minUsd]")]
+    Whales(String),
+    #[command(description = "compare tokens side by side <token> <token> [token...]")]
+    Compare(String),
+    #[command(description = "check if a swap transaction got sandwiched <txHash>")]
+    Sandwich(String),
+    #[command(description = "speed up a stuck transaction <txHash>")]
+    Speedup(String),
+    #[command(description = "cancel a stuck transaction or Safe proposal <txHash>")]
+    Canceltx(String),
+    #[command(description = "route alerts to another chat <category|wallet> <chatId|off>")]
+    Route(String),
+    #[command(description = "show the status of the bot")]
+    Status,
+    #[command(description = "show realized PnL and trade stats")]
+    Sniperstats,
+    #[command(description = "rank watched wallets by realized profits [7|30 days]")]
+    Leaderboard(String),
+    #[command(
+        description = "set the notification priority of an alert category <category> <high|normal|low>"
+    )]
+    Priority(String),
+    #[command(description = "set quiet hours for normal priority alerts <startHour-endHour|off>")]
+    Quiet(String),
+    #[command(
+        description = "share watchlists and settings with another chat, no code issues one (owner only) [code|off]"
+    )]
+    Link(String),
+    #[command(description = "grant or revoke premium for a chat (owner only) [chatId]")]
+    Grant(String),
+    #[command(
+        description = "set the permission level of a group member (owner only) [userId] <viewer|trader|admin>"
+    )]
+    Permission(String),
+    #[command(
+        description = "change log levels at runtime, e.g. api=debug bot=info (owner only) [filters|reset|stream on|off]"
+    )]
+    Loglevel(String),
+    #[command(
+        description = "list or update the known locker, burn, mixer and flagged addresses (owner only) [add|burn|mixer|flag <address> <name>|remove <address>]"
+    )]
+    Lockers(String),
+    #[command(
+        description = "register a launch for a same-block Flashbots snipe (owner only) [<token> <usd> [slippage%]|remove <token>]"
+    )]
+    Launchsnipe(String),
+    #[command(
+        description = "review watches without alerts for a while and prune them [days] | prune [days]"
+    )]
+    Cleanup(String),
+    #[command(
+        description = "sign trades on a mobile wallet through WalletConnect (owner only) [off]"
+    )]
+    Walletconnect(String),
+    #[command(description = "back up the state files now or list the backups (owner only) [now]")]
+    Backup(String),
+    #[command(
+        description = "show the monitored tokens, the last transaction seen of watched wallets, pending prompts and cache hit rates (owner only) [tokens|wallets|prompts|caches|json]"
+    )]
+    Debug(String),
+    #[command(
+        description = "require a PIN or authenticator code to execute trades [pin <pin>|totp|off <pin or code>]"
+    )]
+    Tradelock(String),
+}
+
+impl Command {
+    // heavy features that are only available to premium chats
+    fn is_premium(&self) -> bool {
+        matches!(
+            self,
+            Command::Watch(_)
+                | Command::Watchfilter(_)
+                | Command::Watchtoken(_)
+                | Command::Importwallets
+                | Command::Leaderboard(_)
+        )
+    }
+
+    // lowest permission level a group member needs to run the command
+    fn required_permission(&self) -> Permission {
+        match self {
+            Command::Buy(_)
+            | Command::Sell(_)
+            | Command::Exit(_)
+            | Command::Dca(_)
+            | Command::Speedup(_)
+            | Command::Canceltx(_)
+            | Command::Send(_) => Permission::Trader,
+            Command::Settings
+            | Command::Sniperfilter(_)
+            | Command::Launchdelay(_)
+            | Command::Maximpact(_)
+            | Command::Proceeds(_)
+            | Command::Locale(_)
+            | Command::Watch(_)
+            | Command::Watchfilter(_)
+            | Command::Watchtoken(_)
+            | Command::Importwallets
+            | Command::Route(_)
+            | Command::Tokenmeta(_)
+            | Command::Tokenlist(_)
+            | Command::Template(_)
+            | Command::Priority(_)
+            | Command::Quiet(_)
+            | Command::Link(_)
+            | Command::Cleanup(_)
+            | Command::Tradelock(_)
+            | Command::Start(_) => Permission::Admin,
+            // the portfolio chart is for everyone, changing the chart site isn't
+            Command::Chart(args) if args.trim().to_lowercase().parse::<ChartSite>().is_ok() => {
+                Permission::Admin
+            }
+            _ => Permission::Viewer,
+        }
+    }
 }
 
 lazy_static! {
-    pub static ref SETTINGS: Mutex<HashMap<ChatId, Settings>> =
-        Mutex::new(HashMap::<ChatId, Settings>::new());
-    static ref TRADE_TOKEN: Mutex<TradeToken> = Mutex::new(TradeToken {
-        contract: None,
-        amount: None,
-        slippage: None,
-        order_type: OrderType::Buy
-    });
+    pub static ref SETTINGS: SettingsStore = SettingsStore::default();
     pub static ref WATCHED_WALLETS: Mutex<HashMap<ChatId, Vec<String>>> =
         Mutex::new(HashMap::<ChatId, Vec<String>>::new());
+    pub static ref WATCHED_TOKENS: Mutex<HashMap<ChatId, Vec<String>>> =
+        Mutex::new(HashMap::<ChatId, Vec<String>>::new());
+    static ref PREMIUM_CHATS: Mutex<HashSet<ChatId>> = Mutex::new(HashSet::<ChatId>::new());
+}
+
+fn owner_id() -> Option<u64> {
+    env::var("OWNER_ID")
+        .ok()
+        .and_then(|id| id.parse::<u64>().ok())
+}
+
+// the owner's private chat id is the same as the owner's user id
+pub fn owner_chat() -> Option<ChatId> {
+    owner_id().map(|owner| ChatId(owner as i64))
+}
+
+fn is_owner(msg: &Message) -> bool {
+    match (owner_id(), msg.from()) {
+        (Some(owner), Some(user)) => user.id.0 == owner,
+        _ => false,
+    }
+}
+
+// the owner has every permission in every group
+async fn member_permission(msg: &Message) -> Permission {
+    if is_owner(msg) {
+        return Permission::Admin;
+    }
+
+    groups::permission(&msg.chat, msg.from().map(|user| user.id)).await
+}
+
+pub async fn has_premium(chat_id: ChatId) -> bool {
+    if owner_chat() == Some(chat_id) {
+        return true;
+    }
+
+    PREMIUM_CHATS.lock().await.contains(&chat_id)
 }
 
-pub async fn run() {
+pub async fn run() -> Result<(), tasks::TaskError> {
     info!("Starting telegram bot...");
 
     let bot = Bot::from_env();
-    let cloned_bot = bot.clone();
-    let cloned_bot2 = bot.clone();
+    let shard = shards::shard();
+    if shard.is_sharded() {
+        shards::load().await;
+    }
+
+    if shard.is_primary() {
+        prompts::expire_all(&bot).await;
+
+        // the "/" menu is built from the Command enum on every start, so it never drifts from the handlers
+        if let Err(e) = bot.set_my_commands(Command::bot_commands()).await {
+            error!("failed to register the command menu: {}", e);
+        }
+    }
+
+    let shutdown = tasks::shutdown_token();
+    let mut background_tasks = JoinSet::new();
 
-    info!("Spawning watch_wallets...");
-    tokio::spawn(async move { api::watch_wallets(cloned_bot).await });
+    // every shard watches the wallets and tokens of its own chats
+    tasks::spawn(
+        &mut background_tasks,
+        "watch_wallets",
+        shutdown.clone(),
+        api::watch_wallets(bot.clone()),
+    );
+    tasks::spawn(
+        &mut background_tasks,
+        "watch_approvals",
+        shutdown.clone(),
+        api::watch_approvals(bot.clone()),
+    );
+    tasks::spawn(
+        &mut background_tasks,
+        "watch_launches",
+        shutdown.clone(),
+        api::watch_launches(bot.clone()),
+    );
+    tasks::spawn(
+        &mut background_tasks,
+        "watch_tokens",
+        shutdown.clone(),
+        api::watch_tokens(bot.clone()),
+    );
+    tasks::spawn(
+        &mut background_tasks,
+        "watch_proxies",
+        shutdown.clone(),
+        api::watch_proxies(bot.clone()),
+    );
+    tasks::spawn(
+        &mut background_tasks,
+        "watch_supply",
+        shutdown.clone(),
+        api::watch_supply(bot.clone()),
+    );
+    if shard.is_sharded() {
+        tasks::spawn(
+            &mut background_tasks,
+            "shard_sync",
+            shutdown.clone(),
+            shards::sync(),
+        );
+    }
 
-    info!("Spawning new_token_alerts...");
-    tokio::spawn(async move { api::new_token_alerts(cloned_bot2).await });
+    // the rest is about updates or all chats at once, which only the primary polls
+    if shard.is_primary() {
+        tasks::spawn(
+            &mut background_tasks,
+            "new_token_alerts",
+            shutdown.clone(),
+            api::new_token_alerts(bot.clone()),
+        );
+        let launchpad_feeds = api::launchpad_feeds();
+        if !launchpad_feeds.is_empty() {
+            tasks::spawn(
+                &mut background_tasks,
+                "launchpad_alerts",
+                shutdown.clone(),
+                api::launchpad_alerts(bot.clone(), launchpad_feeds),
+            );
+        }
+        tasks::spawn(
+            &mut background_tasks,
+            "portfolio_history",
+            shutdown.clone(),
+            history::record_snapshots(),
+        );
+        tasks::spawn(
+            &mut background_tasks,
+            "watch_expiry",
+            shutdown.clone(),
+            expiry::expire_watches(bot.clone()),
+        );
+        tasks::spawn(
+            &mut background_tasks,
+            "watch_concentration",
+            shutdown.clone(),
+            api::watch_concentration(bot.clone()),
+        );
+        tasks::spawn(
+            &mut background_tasks,
+            "watch_error_budgets",
+            shutdown.clone(),
+            api::watch_error_budgets(bot.clone()),
+        );
+        if let Some(owner_chat) = owner_chat() {
+            tasks::spawn(
+                &mut background_tasks,
+                "log_stream",
+                shutdown.clone(),
+                logging::stream_to_chat(bot.clone(), owner_chat),
+            );
+            tasks::spawn(
+                &mut background_tasks,
+                "daily_digest",
+                shutdown.clone(),
+                digest::send_daily_digests(bot.clone(), owner_chat),
+            );
+        }
+        if let Some(token) = server::token() {
+            tasks::spawn(
+                &mut background_tasks,
+                "api_server",
+                shutdown.clone(),
+                server::serve(token),
+            );
+        }
+        tasks::spawn(
+            &mut background_tasks,
+            "dca",
+            shutdown.clone(),
+            dca::run_dca_plans(bot.clone()),
+        );
+        if balance::threshold() > 0.0 {
+            tasks::spawn(
+                &mut background_tasks,
+                "watch_balance",
+                shutdown.clone(),
+                balance::watch_balance(bot.clone()),
+            );
+        }
+        if backup::enabled() {
+            tasks::spawn(
+                &mut background_tasks,
+                "backups",
+                shutdown.clone(),
+                backup::scheduled_backups(),
+            );
+        }
+    }
 
     // let port: u16 = env::var("PORT")
     //     .expect("PORT env variable is not set")
@@ -172,42 +845,313 @@ pub async fn run() {
     //     )
     //     .await;
 
-    Dispatcher::builder(bot, schema())
+    let mut dispatcher = Dispatcher::builder(bot, schema())
         .dependencies(dptree::deps![InMemStorage::<State>::new()])
+        .error_handler(Arc::new(
+            |e: Box<dyn std::error::Error + Send + Sync>| async move {
+                tracing::error!(
+                    error_kind = logging::error_kind(e.as_ref()),
+                    "handler error: {}",
+                    e
+                );
+            },
+        ))
+        .distribution_function(lanes::distribute)
         .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
+        .build();
+
+    let updates = async {
+        if shard.is_primary() {
+            dispatcher.dispatch().await;
+        } else if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("failed to listen for ctrl-c: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    let result = tokio::select! {
+        _ = updates => Ok(()),
+        // background tasks only finish on their own if something went wrong
+        Some(task_result) = background_tasks.join_next() => match task_result {
+            Ok(result) => result,
+            Err(e) => Err(tasks::TaskError { name: "unknown", reason: e.to_string() }),
+        },
+    };
+
+    info!("Shutting down background tasks...");
+    shutdown.cancel();
+    while background_tasks.join_next().await.is_some() {}
+
+    result
+}
+
+// runs updates through the dispatcher's handlers without polling telegram, the dialogue state
+// is kept between updates so multi-step flows can be driven one update at a time
+pub struct BotHandlers {
+    storage: Arc<InMemStorage<State>>,
+}
+
+impl Default for BotHandlers {
+    fn default() -> Self {
+        BotHandlers {
+            storage: InMemStorage::new(),
+        }
+    }
+}
+
+impl BotHandlers {
+    pub async fn handle(&self, bot: Bot, update: Update) -> HandlerResult {
+        let me = bot.get_me().await?;
+
+        match schema()
+            .dispatch(dptree::deps![bot, me, update, self.storage.clone()])
+            .await
+        {
+            ControlFlow::Break(result) => result,
+            // the dispatcher drops updates no handler matched as well
+            ControlFlow::Continue(_) => Ok(()),
+        }
+    }
 }
 
 fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
     use dptree::case;
 
+    // permissions middleware, premium commands from non-premium chats never reach their endpoint
+    let premium_handler = dptree::filter(|cmd: Command| cmd.is_premium())
+        .filter_async(|msg: Message| async move { !has_premium(msg.chat.id).await })
+        .endpoint(premium_required);
+
+    // in groups, members can only run the commands their permission level allows
+    let permission_handler = dptree::filter_async(|cmd: Command, msg: Message| async move {
+        member_permission(&msg).await < cmd.required_permission()
+    })
+    .endpoint(permission_required);
+
+    // logged with the chat, command and latency once the update is handled
     let command_handler = teloxide::filter_command::<Command, _>()
+        .map(|msg: Message| logging::CommandLog::start(msg.chat.id, msg.text().unwrap_or_default()))
+        .inspect_async(|msg: Message| async move {
+            if let Some(user) = msg.from() {
+                let text = msg.text().unwrap_or_default();
+                commandhistory::record(msg.chat.id, user.id, text).await;
+            }
+        })
+        .branch(permission_handler)
+        .branch(premium_handler)
         .branch(
             case![State::Start]
                 .branch(case![Command::Buy(tt)].endpoint(trade_token))
-                .branch(case![Command::Sell(tt)].endpoint(trade_token)),
+                .branch(case![Command::Sell(tt)].endpoint(trade_token))
+                .branch(case![Command::Exit(t)].endpoint(exit_position)),
+        )
+        .branch(case![Command::Dca(d)].endpoint(dca_command))
+        .branch(
+            case![Command::Portfolio]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, get_portfolio)),
+        )
+        .branch(case![Command::Receive(a)].endpoint(receive_payment))
+        .branch(case![Command::Send(a)].endpoint(send_eth))
+        .branch(
+            case![Command::Gas(g)].endpoint(|bot: Bot, msg: Message| report(bot, msg, get_eth_gas)),
+        )
+        .branch(
+            case![Command::Gasspent(g)]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, gas_spent)),
+        )
+        .branch(
+            case![Command::Inspect(a)]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, inspect_address)),
+        )
+        .branch(
+            case![Command::Creator(t)]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, creator_funding_report)),
+        )
+        .branch(
+            case![Command::Recentbuys(r)]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, recent_buys)),
         )
-        .branch(case![Command::Portfolio].endpoint(get_portfolio))
-        .branch(case![Command::Gas].endpoint(get_eth_gas))
         .branch(case![Command::Scan(t)].endpoint(scan_token))
         .branch(case![Command::Settings].endpoint(change_settings))
+        .branch(case![Command::Sniperfilter(f)].endpoint(set_sniper_filter))
+        .branch(case![Command::Launchdelay(d)].endpoint(set_launch_delay))
+        .branch(case![Command::Maximpact(m)].endpoint(set_max_price_impact))
+        .branch(case![Command::Proceeds(p)].endpoint(set_sell_proceeds))
+        .branch(case![Command::Locale(l)].endpoint(set_locale))
         .branch(case![Command::Watch(w)].endpoint(watch_wallets))
+        .branch(case![Command::Watchfilter(f)].endpoint(set_wallet_filter))
+        .branch(case![Command::Watchtoken(t)].endpoint(watch_tokens))
+        .branch(case![Command::Importwallets].endpoint(import_wallets_help))
+        .branch(case![Command::Tokenmeta(t)].endpoint(set_token_metadata))
+        .branch(case![Command::Tokenlist(l)].endpoint(import_token_list))
+        .branch(case![Command::Template(t)].endpoint(set_template))
+        .branch(case![Command::Chart(c)].endpoint(set_chart_site))
+        .branch(
+            case![Command::Whales(w)]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, whale_transfers)),
+        )
+        .branch(
+            case![Command::Compare(c)]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, compare_tokens)),
+        )
+        .branch(
+            case![Command::Sandwich(h)]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, check_sandwich)),
+        )
+        .branch(case![Command::Speedup(h)].endpoint(rescue_transaction))
+        .branch(case![Command::Canceltx(h)].endpoint(rescue_transaction))
+        .branch(case![Command::Route(r)].endpoint(route_alerts))
+        .branch(case![Command::Priority(p)].endpoint(set_alert_priority))
+        .branch(case![Command::Quiet(q)].endpoint(set_quiet_hours))
+        .branch(case![Command::Status].endpoint(|bot: Bot, msg: Message| report(bot, msg, status)))
+        .branch(
+            case![Command::Sniperstats]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, sniper_stats)),
+        )
+        .branch(
+            case![Command::Leaderboard(d)]
+                .endpoint(|bot: Bot, msg: Message| report(bot, msg, wallet_leaderboard)),
+        )
+        .branch(case![Command::Link(l)].endpoint(link_chats))
+        .branch(case![Command::Start(s)].endpoint(start))
         .branch(case![Command::Help].endpoint(help))
-        .branch(case![Command::Cancel].endpoint(cancel));
+        .branch(case![Command::Cancel].endpoint(cancel))
+        .branch(case![Command::Last].endpoint(last_command))
+        .branch(case![Command::Historycmd].endpoint(command_history))
+        .branch(case![Command::Grant(c)].endpoint(grant_premium))
+        .branch(case![Command::Permission(p)].endpoint(set_member_permission))
+        .branch(case![Command::Loglevel(l)].endpoint(set_log_level))
+        .branch(case![Command::Lockers(l)].endpoint(known_lockers))
+        .branch(case![Command::Launchsnipe(l)].endpoint(launch_snipes))
+        .branch(case![Command::Cleanup(c)].endpoint(cleanup_watches))
+        .branch(case![Command::Walletconnect(w)].endpoint(walletconnect_pairing))
+        .branch(case![Command::Backup(b)].endpoint(backup_state))
+        .branch(case![Command::Debug(d)].endpoint(debug_state))
+        .branch(case![Command::Tradelock(t)].endpoint(set_trade_lock));
+
+    // QR code photos are read for an address, the largest size decodes best
+    let photo_handler = dptree::filter_map(|msg: Message| {
+        msg.photo()
+            .and_then(|sizes| sizes.last())
+            .map(|size| size.file.id.clone())
+    })
+    .endpoint(read_qr_photo);
+
+    // a document has a caption instead of a text, so the command doesn't reach the command handler
+    let import_handler = dptree::filter_map(|msg: Message| {
+        let command = msg.caption()?.split_whitespace().next()?;
+        if command.split('@').next() != Some("/importwallets") {
+            return None;
+        }
+        msg.document().cloned()
+    })
+    .endpoint(import_wallets);
 
     let message_handler = Update::filter_message()
         .branch(command_handler)
+        .branch(case![State::VerifyTrade(trade)].endpoint(verify_trade))
+        .branch(import_handler)
+        .branch(photo_handler)
         .branch(dptree::endpoint(invalid_state));
 
+    // in groups, only the member who opened a prompt can answer it
+    let prompt_owner_handler = dptree::filter_async(|q: CallbackQuery| async move {
+        match q.chat_id() {
+            Some(chat_id) => !groups::is_prompt_owner(chat_id, q.from.id).await,
+            None => false,
+        }
+    })
+    .endpoint(not_prompt_owner);
+
+    // portfolio buttons aren't prompts, so they work in any state and for any member allowed to use them
+    let portfolio_action_handler =
+        dptree::filter_map(|q: CallbackQuery| q.data.as_deref().and_then(PortfolioAction::parse))
+            .endpoint(portfolio_action);
+
+    // proposed DCA buys can be confirmed whenever, they don't belong to a dialogue
+    let dca_confirm_handler = dptree::filter(|q: CallbackQuery| {
+        q.data
+            .as_deref()
+            .is_some_and(|data| data.starts_with("dca:"))
+    })
+    .endpoint(dca_confirm);
+
+    // expired watches can be renewed from the notice at any time
+    let renew_watch_handler = dptree::filter_map(|q: CallbackQuery| {
+        q.data.as_deref().and_then(WatchKind::parse_renew_callback)
+    })
+    .endpoint(renew_watch);
+
     let callback_query_handler = Update::filter_callback_query()
-        .branch(case![State::Confirm].endpoint(confirm_transaction))
-        .branch(case![State::Settings].endpoint(confirm_settings));
+        .branch(portfolio_action_handler)
+        .branch(dca_confirm_handler)
+        .branch(renew_watch_handler)
+        .branch(prompt_owner_handler)
+        .branch(case![State::Confirm(tt)].endpoint(confirm_transaction))
+        .branch(case![State::Settings].endpoint(confirm_settings))
+        .branch(case![State::ConfirmAction(action)].endpoint(confirm_action))
+        .branch(
+            case![State::PickToken {
+                command,
+                args,
+                candidates
+            }]
+            .endpoint(pick_token),
+        )
+        .branch(dptree::endpoint(expired_prompt));
 
-    dialogue::enter::<Update, InMemStorage<State>, State, _>()
-        .branch(message_handler)
-        .branch(callback_query_handler)
+    // shared scan cards are inline messages without a chat, so they're handled outside the dialogue
+    let scan_share_handler = Update::filter_inline_query().endpoint(share_scan);
+    let scan_refresh_handler = Update::filter_callback_query()
+        .filter_map(|q: CallbackQuery| {
+            q.data
+                .as_deref()
+                .and_then(|data| data.strip_prefix("scanrefresh:"))
+                .filter(|contract| utils::is_valid_eth_address(contract))
+                .map(String::from)
+        })
+        .endpoint(refresh_scan);
+    // alerts can be routed to chats without a dialogue, the re-check doesn't need one either
+    let recheck_handler = Update::filter_callback_query()
+        .filter_map(|q: CallbackQuery| {
+            q.data
+                .as_deref()
+                .and_then(|data| data.strip_prefix("recheck:"))
+                .filter(|contract| utils::is_valid_eth_address(contract))
+                .map(String::from)
+        })
+        .endpoint(recheck_new_token);
+
+    let monitor_token_handler = Update::filter_callback_query()
+        .filter_map(|q: CallbackQuery| {
+            q.data
+                .as_deref()
+                .and_then(|data| data.strip_prefix("monitor:"))
+                .filter(|contract| utils::is_valid_eth_address(contract))
+                .map(String::from)
+        })
+        .endpoint(monitor_token);
+    // re-runs go through the whole schema again, dialogue included
+    let rerun_handler = Update::filter_callback_query()
+        .filter_map(|q: CallbackQuery| {
+            q.data
+                .as_deref()
+                .and_then(|data| data.strip_prefix("rerun:"))
+                .and_then(|id| id.parse::<u64>().ok())
+        })
+        .endpoint(rerun_from_history);
+
+    dptree::entry()
+        .branch(scan_share_handler)
+        .branch(scan_refresh_handler)
+        .branch(recheck_handler)
+        .branch(monitor_token_handler)
+        .branch(rerun_handler)
+        .branch(
+            dialogue::enter::<Update, InMemStorage<State>, State, _>()
+                .branch(message_handler)
+                .branch(callback_query_handler),
+        )
 }
 
 fn make_yes_no_keyboard() -> InlineKeyboardMarkup {
@@ -219,6 +1163,30 @@ fn make_yes_no_keyboard() -> InlineKeyboardMarkup {
     InlineKeyboardMarkup::new(buttons)
 }
 
+async fn make_wallets_keyboard(wallets: &[String]) -> InlineKeyboardMarkup {
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = vec![];
+
+    for (i, wallet) in wallets.iter().enumerate() {
+        let balance = match api::get_eth_balance(wallet.clone()).await {
+            Ok(balance) => format!("{:.4} ETH", balance),
+            Err(_) => String::from("balance unavailable"),
+        };
+
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!(
+                "{}...{} ({})",
+                &wallet[..6],
+                &wallet[wallet.len() - 4..],
+                balance
+            ),
+            format!("wallet:{}", i),
+        )]);
+    }
+    buttons.push(vec![InlineKeyboardButton::callback("No", "no")]);
+
+    InlineKeyboardMarkup::new(buttons)
+}
+
 fn make_settings_keyboard() -> InlineKeyboardMarkup {
     let buttons: Vec<Vec<InlineKeyboardButton>> = vec![
         vec![InlineKeyboardButton::callback(
@@ -229,6 +1197,18 @@ fn make_settings_keyboard() -> InlineKeyboardMarkup {
             "Hide zero token balances",
             "hide_zero_balance",
         )],
+        vec![InlineKeyboardButton::callback(
+            "Classic approve + swap sells",
+            "classic_approvals",
+        )],
+        vec![InlineKeyboardButton::callback(
+            "Direct WETH pool routes only",
+            "direct_routes_only",
+        )],
+        vec![InlineKeyboardButton::callback(
+            "Same-block launch snipes (owner only)",
+            "same_block_snipes",
+        )],
     ];
 
     InlineKeyboardMarkup::new(buttons)
@@ -238,12 +1218,27 @@ async fn validate_tradetoken_args(args: &Vec<&str>, order_type: OrderType) -> Op
     let mut trade_token: TradeToken = TradeToken {
         contract: None,
         amount: None,
+        tokens: None,
         slippage: None,
         order_type: order_type,
+        wallet: None,
+        twap: None,
+        route: None,
     };
 
-    if args.len() != 3 {
-        return None;
+    // buys can take an optional 'twap <slices> <minutes>' suffix
+    match (args.len(), &trade_token.order_type) {
+        (3, _) => (),
+        (6, OrderType::Buy) if args[3] == "twap" => {
+            trade_token.twap = match (args[4].parse(), args[5].parse()) {
+                (Ok(slices), Ok(minutes)) => trading::Twap::new(slices, minutes),
+                _ => None,
+            };
+            if trade_token.twap.is_none() {
+                return None;
+            }
+        }
+        _ => return None,
     }
 
     if utils::is_valid_eth_address(args[0]) {
@@ -256,19 +1251,43 @@ async fn validate_tradetoken_args(args: &Vec<&str>, order_type: OrderType) -> Op
         Ok(v) => Some(v),
         Err(_) => None,
     };
+    if let (None, OrderType::Sell, Some(contract)) = (
+        trade_token.amount,
+        &trade_token.order_type,
+        &trade_token.contract,
+    ) {
+        if let Some((tokens, amount_usd)) = sell_token_amount(contract, args[1]).await {
+            trade_token.tokens = Some(tokens);
+            trade_token.amount = Some(amount_usd);
+        }
+    }
 
     trade_token.slippage = match args[2].parse() {
         Ok(v) => Some(v),
         Err(_) => None,
     };
 
-    let mut tt = TRADE_TOKEN.lock().await;
-    *tt = trade_token.clone();
-
     Some(trade_token)
 }
 
-async fn validate_watchwallets_args(chat_id: ChatId, args: &Vec<&str>) -> Option<Vec<String>> {
+// a sell amount like '1000000tokens' or '1000000pepe' in whole tokens, with its usd value
+async fn sell_token_amount(contract: &str, amount: &str) -> Option<(trading::TokenAmount, f64)> {
+    let token_info = api::get_token_info(contract.to_owned()).await.ok()?;
+    let lowercase = amount.to_lowercase();
+    let number = lowercase
+        .strip_suffix("tokens")
+        .or_else(|| lowercase.strip_suffix(&token_info.symbol.to_lowercase()))?;
+
+    let tokens = trading::TokenAmount::parse(number, token_info.decimals)?;
+    let price = api::get_token_price(contract.to_owned()).await.ok()? as f64;
+    if price <= 0.0 {
+        return None;
+    }
+
+    Some((tokens, tokens.tokens() * price))
+}
+
+fn validate_watchwallets_args(args: &Vec<&str>) -> Option<Vec<String>> {
     let mut watched_wallets: Vec<String> = vec![];
 
     for wallet in args {
@@ -277,9 +1296,6 @@ async fn validate_watchwallets_args(chat_id: ChatId, args: &Vec<&str>) -> Option
         }
     }
 
-    let mut ww = WATCHED_WALLETS.lock().await;
-    *ww = HashMap::from([(chat_id, watched_wallets.clone())]);
-
     if watched_wallets.is_empty() {
         None
     } else {
@@ -287,14 +1303,70 @@ async fn validate_watchwallets_args(chat_id: ChatId, args: &Vec<&str>) -> Option
     }
 }
 
-async fn loading_message(bot: &Bot, msg: &Message) -> MessageId {
-    let loading_message = bot.send_message(msg.chat.id, "...").await;
+// reports run in their own lane, one stuck on a provider gives up instead of leaving the chat waiting
+async fn report<F>(bot: Bot, msg: Message, handler: fn(Bot, Message) -> F) -> HandlerResult
+where
+    F: std::future::Future<Output = HandlerResult>,
+{
+    match tokio::time::timeout(lanes::REPORT_TIMEOUT, handler(bot.clone(), msg.clone())).await {
+        Ok(result) => result,
+        Err(_) => {
+            groups::reply(
+                &bot,
+                &msg,
+                format!(
+                    "⏳ This took longer than {} seconds, the providers may be slow. Try again in a bit",
+                    lanes::REPORT_TIMEOUT.as_secs()
+                ),
+            )
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+async fn loading_message(bot: &Bot, chat_id: ChatId) -> MessageId {
+    let loading_message = bot.send_message(chat_id, "...").await;
     loading_message.unwrap().id
 }
 
 async fn trade_token(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
     let (command, args) =
         parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+
+    // the token pick and trade confirmation prompts can only be answered by this member
+    groups::set_prompt_owner(msg.chat.id, msg.from().map(|user| user.id)).await;
+
+    if let Some(token) = args.first() {
+        match symbols::resolve(msg.chat.id, token).await {
+            Resolution::Address(address) => args[0] = address,
+            Resolution::Ambiguous(candidates) => {
+                return pick_token_prompt(
+                    &bot,
+                    &dialogue,
+                    msg.chat.id,
+                    command.to_lowercase(),
+                    args,
+                    candidates,
+                )
+                .await;
+            }
+            Resolution::Unknown => {}
+        }
+    }
+
+    start_trade(&bot, &dialogue, msg.chat.id, &command, &args).await
+}
+
+async fn start_trade(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    chat_id: ChatId,
+    command: &str,
+    args: &Vec<String>,
+) -> HandlerResult {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
     let trade_token: Option<TradeToken> = validate_tradetoken_args(
         &args,
         OrderType::from_str(command.to_lowercase().as_str()).unwrap(),
@@ -309,7 +1381,7 @@ async fn trade_token(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerRes
                 None => {
                     incorrect_params = true;
                     bot.send_message(
-                        msg.chat.id,
+                        chat_id,
                         format!("Trade cancelled: submitted contract is incorrect!"),
                     )
                     .await?;
@@ -321,7 +1393,7 @@ async fn trade_token(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerRes
                 None => {
                     incorrect_params = true;
                     bot.send_message(
-                        msg.chat.id,
+                        chat_id,
                         format!("Trade cancelled: submitted amount is incorrect!"),
                     )
                     .await?;
@@ -333,7 +1405,7 @@ async fn trade_token(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerRes
                 None => {
                     incorrect_params = true;
                     bot.send_message(
-                        msg.chat.id,
+                        chat_id,
                         format!("Trade cancelled: submitted slippage is incorrect!"),
                     )
                     .await?;
@@ -341,17 +1413,35 @@ async fn trade_token(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerRes
             }
 
             if !incorrect_params {
-                bot.send_message(msg.chat.id, format!("{}", tt)).await?;
-                bot.send_message(msg.chat.id, "Do you want to execute the transaction?")
-                    .reply_markup(make_yes_no_keyboard())
-                    .await?;
+                let Some(tt) = route_trade(bot, chat_id, tt).await? else {
+                    dialogue.exit().await?;
+                    return Ok(());
+                };
+                let tt = check_price_impact(bot, chat_id, tt).await?;
 
-                dialogue.update(State::Confirm).await?;
+                let duplicate_warning = match tt.order_type {
+                    OrderType::Buy => duplicate_buy_warning(tt.contract.as_ref().unwrap()).await,
+                    OrderType::Sell => None,
+                };
+                match duplicate_warning {
+                    Some(warning) => {
+                        let prompt = bot
+                            .send_message(chat_id, warning)
+                            .reply_markup(InlineKeyboardMarkup::new(vec![vec![
+                                InlineKeyboardButton::callback("No", "no"),
+                                InlineKeyboardButton::callback("Add to position", "addtoposition"),
+                            ]]))
+                            .await?;
+                        prompts::opened(chat_id, prompt.id).await;
+                        dialogue.update(State::Confirm(tt)).await?;
+                    }
+                    None => trade_prompt(bot, dialogue, chat_id, tt).await?,
+                }
             }
         }
         None => {
             bot.send_message(
-                msg.chat.id,
+                chat_id,
                 format!("Trade cancelled: submitted trade parameters are incorrect!"),
             )
             .await?;
@@ -362,167 +1452,6557 @@ async fn trade_token(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerRes
     Ok(())
 }
 
-async fn confirm_transaction(bot: Bot, dialogue: MyDialogue, q: CallbackQuery) -> HandlerResult {
-    let chat_id = q.chat_id().unwrap();
-
-    match q.clone().data {
-        Some(callback) => {
-            bot.answer_callback_query(q.id).await?;
+// finds the pools the swap goes through, None cancels the trade when the chat only allows
+// direct routes and the token has no WETH pool of its own
+async fn route_trade(
+    bot: &Bot,
+    chat_id: ChatId,
+    tt: TradeToken,
+) -> Result<Option<TradeToken>, teloxide::RequestError> {
+    let direct_only = SETTINGS.get(chat_id).await.direct_routes_only;
 
-            bot.delete_message(chat_id, q.message.unwrap().id).await?;
+    let token_info = match api::get_token_info(tt.contract.clone().unwrap()).await {
+        Ok(token_info) => token_info,
+        Err(e) => {
+            error!("get_token_info error: {}", e);
+            return Ok(Some(tt));
+        }
+    };
 
-            if callback == "yes" {
-                bot.send_message(chat_id, format!("Transaction executed!"))
-                    .await?;
-                // TODO: handle transaction
-            } else if callback == "no" {
-                bot.send_message(chat_id, format!("Transaction was not executed!"))
-                    .await?;
-            }
+    match routes::find_sell_route(&token_info, direct_only).await {
+        Ok(Some(route)) => {
+            let route = match tt.order_type {
+                OrderType::Buy => route.reversed(),
+                OrderType::Sell => route,
+            };
+            Ok(Some(TradeToken {
+                route: Some(route),
+                ..tt
+            }))
         }
-        None => {
+        Ok(None) if direct_only => {
             bot.send_message(
                 chat_id,
-                format!("Something went wrong with the button handling"),
+                format!(
+                    "Trade cancelled: {} has no direct WETH pool, turn off direct routes in /settings to trade through its {} pair",
+                    token_info.symbol, token_info.pair_symbol
+                ),
             )
             .await?;
+            Ok(None)
+        }
+        Ok(None) => Ok(Some(tt)),
+        Err(e) => {
+            error!("find_sell_route error: {}", e);
+            Ok(Some(tt))
         }
     }
+}
+
+// compares the order with the largest one the pool takes under the chat's price impact limit,
+// twap orders are checked per slice as the pool has time to recover in between
+async fn check_price_impact(
+    bot: &Bot,
+    chat_id: ChatId,
+    tt: TradeToken,
+) -> Result<TradeToken, teloxide::RequestError> {
+    let limit = SETTINGS.get(chat_id).await.price_impact_limit;
+    let (max_impact, cap) = match limit {
+        PriceImpactLimit::Off => return Ok(tt),
+        PriceImpactLimit::Warn(percent) => (percent, false),
+        PriceImpactLimit::Cap(percent) => (percent, true),
+    };
+
+    let pair = match api::get_token_info(tt.contract.clone().unwrap()).await {
+        Ok(token_info) => token_info.pair_address,
+        Err(e) => {
+            error!("get_token_info error: {}", e);
+            return Ok(tt);
+        }
+    };
+    let depth_usd = match api::get_pool_depth_usd(pair).await {
+        Ok(Some(depth_usd)) => depth_usd,
+        Ok(None) => return Ok(tt),
+        Err(e) => {
+            error!("get_pool_depth_usd error: {}", e);
+            return Ok(tt);
+        }
+    };
+
+    let slices = tt.twap.as_ref().map_or(1, |twap| twap.slices) as f64;
+    let order_usd = tt.amount.unwrap() / slices;
+    let impact = trading::price_impact_percent(depth_usd, order_usd);
+    if impact <= max_impact {
+        return Ok(tt);
+    }
+
+    let max_amount = trading::max_order_for_impact(depth_usd, max_impact) * slices;
+    if cap {
+        bot.send_message(
+            chat_id,
+            format!(
+                "✂️ Order capped from ${:.2} to ${:.2} to keep the price impact under {}%",
+                tt.amount.unwrap(),
+                max_amount,
+                max_impact
+            ),
+        )
+        .await?;
+
+        return Ok(TradeToken {
+            amount: Some(max_amount),
+            tokens: None,
+            ..tt
+        });
+    }
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "⚠️ Price impact: ~{:.1}% is above the {}% limit, orders up to ${:.2} stay under it",
+            impact, max_impact, max_amount
+        ),
+    )
+    .await?;
+
+    Ok(tt)
+}
+
+// with more than one wallet, picking the wallet confirms the buy
+async fn trade_prompt(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    chat_id: ChatId,
+    tt: TradeToken,
+) -> HandlerResult {
+    let wallets = wallet::trading_wallets();
+
+    match tt.order_type {
+        OrderType::Buy if wallets.len() > 1 => {
+            bot.send_message(chat_id, format!("{}", tt)).await?;
+            let prompt = bot
+                .send_message(chat_id, "Which wallet should fund the transaction?")
+                .reply_markup(make_wallets_keyboard(&wallets).await)
+                .await?;
+            prompts::opened(chat_id, prompt.id).await;
+            dialogue.update(State::Confirm(tt)).await?;
+        }
+        _ => confirm_trade_prompt(bot, dialogue, chat_id, tt).await?,
+    }
+
+    Ok(())
+}
+
+// buying a token that's already held or was just sniped takes an extra confirmation
+async fn duplicate_buy_warning(contract: &str) -> Option<String> {
+    let now = Utc::now();
+    let sniped_at = launches::recently_sniped(contract, now).await;
+
+    positions::duplicate_buy_warning(
+        positions::POSITIONS.lock().await.position(contract),
+        sniped_at,
+        now,
+    )
+}
+
+// '/exit <token>', for when a rug starts: the whole balance is sold with a single confirmation
+async fn exit_position(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
+    let token = parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username())
+        .unwrap()
+        .1
+        .join("");
+    groups::set_prompt_owner(msg.chat.id, msg.from().map(|user| user.id)).await;
+
+    match symbols::resolve(msg.chat.id, token.trim()).await {
+        Resolution::Address(address) => {
+            exit_checklist(&bot, &dialogue, msg.chat.id, &address).await
+        }
+        Resolution::Ambiguous(candidates) => {
+            pick_token_prompt(
+                &bot,
+                &dialogue,
+                msg.chat.id,
+                String::from("exit"),
+                vec![token.trim().to_owned()],
+                candidates,
+            )
+            .await
+        }
+        Resolution::Unknown => exit_checklist(&bot, &dialogue, msg.chat.id, token.trim()).await,
+    }
+}
+
+// the exit skips the chat's price impact cap, selling less than the whole bag isn't an exit
+async fn exit_checklist(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    chat_id: ChatId,
+    contract: &str,
+) -> HandlerResult {
+    if !utils::is_valid_eth_address(contract) {
+        bot.send_message(chat_id, "Exit cancelled: the submitted token is not valid!")
+            .await?;
+        return Ok(());
+    }
+
+    let loading_message_id = loading_message(bot, chat_id).await;
+    let tt = TradeToken {
+        contract: Some(contract.to_owned()),
+        amount: None,
+        tokens: None,
+        slippage: None,
+        order_type: OrderType::Sell,
+        wallet: None,
+        twap: None,
+        route: None,
+    };
+    let wallet = trade_wallet(&tt).await;
+
+    let (balance, token_info, price) = tokio::join!(
+        api::get_token_balance(contract.to_owned(), wallet.clone()),
+        api::get_token_info(contract.to_owned()),
+        api::get_token_price(contract.to_owned())
+    );
+    let (balance, token_info, price) = match (balance, token_info, price) {
+        (Ok(balance), Ok(token_info), Ok(price)) if price > 0.0 => {
+            (balance, token_info, price as f64)
+        }
+        (balance, token_info, price) => {
+            error!(
+                "exit checklist error: balance {:?}, token info {:?}, price {:?}",
+                balance.err().map(|e| e.to_string()),
+                token_info.err().map(|e| e.to_string()),
+                price.map_err(|e| e.to_string())
+            );
+            bot.delete_message(chat_id, loading_message_id).await?;
+            bot.send_message(chat_id, "Something went wrong, please try again later")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if balance == 0 {
+        bot.delete_message(chat_id, loading_message_id).await?;
+        bot.send_message(
+            chat_id,
+            format!(
+                "Exit cancelled: {} is not held by {}",
+                token_info.symbol, wallet
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let tokens = trading::TokenAmount {
+        raw: balance,
+        decimals: token_info.decimals,
+    };
+    let value_usd = tokens.tokens() * price;
+    let depth_usd = match api::get_pool_depth_usd(token_info.pair_address.clone()).await {
+        Ok(depth_usd) => depth_usd,
+        Err(e) => {
+            error!("get_pool_depth_usd error: {}", e);
+            None
+        }
+    };
+    let impact_percent =
+        depth_usd.map(|depth_usd| trading::price_impact_percent(depth_usd, value_usd));
+    let slippage = exits::exit_slippage(
+        impact_percent.unwrap_or(0.0),
+        token_info.sell_tax,
+        DEFAULT_SLIPPAGE,
+        trading::max_retry_slippage(),
+    );
+
+    let checklist = exits::ExitChecklist {
+        symbol: token_info.symbol.clone(),
+        tokens: tokens.to_string(),
+        value_usd,
+        is_honeypot: token_info.is_honeypot,
+        sell_tax: token_info.sell_tax,
+        depth_usd,
+        impact_percent,
+        slippage,
+    };
+    bot.delete_message(chat_id, loading_message_id).await?;
+    bot.send_message(chat_id, checklist.to_string()).await?;
+
+    let tt = TradeToken {
+        amount: Some(value_usd),
+        tokens: Some(tokens),
+        slippage: Some(slippage),
+        wallet: Some(wallet),
+        ..tt
+    };
+    let Some(tt) = route_trade(bot, chat_id, tt).await? else {
+        return Ok(());
+    };
+    confirm_trade_prompt(bot, dialogue, chat_id, tt).await?;
+
+    Ok(())
+}
+
+// the executing wallet is fixed before asking, so the confirmation shows where the trade runs from
+async fn confirm_trade_prompt(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    chat_id: ChatId,
+    tt: TradeToken,
+) -> HandlerResult {
+    let tt = TradeToken {
+        wallet: Some(trade_wallet(&tt).await),
+        ..tt
+    };
+
+    let symbol = token_symbol(chat_id, tt.contract.as_ref().unwrap()).await;
+    let mut card = format!("🪙 Token: {}\n{}", symbol, tt);
+    if let Some(estimate) = sell_tokens_estimate(&tt).await {
+        card = format!("{}\n{}", card, estimate);
+    }
+    if let Some(gas) = trade_gas(chat_id, &tt).await {
+        card = format!("{}\n{}", card, gas);
+    }
+    bot.send_message(chat_id, card).await?;
+    let prompt = bot
+        .send_message(chat_id, "Do you want to execute the transaction?")
+        .reply_markup(make_yes_no_keyboard())
+        .await?;
+    prompts::opened(chat_id, prompt.id).await;
+    dialogue.update(State::Confirm(tt)).await?;
+
+    Ok(())
+}
+
+// the tokens a sell given in usd comes to at the current price
+async fn sell_tokens_estimate(tt: &TradeToken) -> Option<String> {
+    if !matches!(tt.order_type, OrderType::Sell) || tt.tokens.is_some() {
+        return None;
+    }
+    let contract = tt.contract.clone()?;
+    let decimals = api::get_token_info(contract.clone()).await.ok()?.decimals;
+    let price = api::get_token_price(contract).await.ok()? as f64;
+    let tokens = trading::TokenAmount::from_usd(tt.amount?, price, decimals)?;
+
+    Some(format!(
+        "🔢 ≈ {} tokens ({} base units, {} decimals)",
+        tokens, tokens.raw, tokens.decimals
+    ))
+}
+
+// a Safe can't sign a Permit2 permit, its approve and swap are batched instead.
+// a WalletConnect session signs the permit before the swap is built
+async fn sell_route(chat_id: ChatId, wallet: &str) -> trading::SellRoute {
+    let classic_approvals = SETTINGS.get(chat_id).await.classic_approvals;
+
+    if classic_approvals || wallet::is_safe(wallet) {
+        trading::SellRoute::Classic
+    } else {
+        trading::SellRoute::UniversalRouter
+    }
+}
+
+// simulates the transactions the trade would send, None if they can't be built
+async fn trade_gas(chat_id: ChatId, tt: &TradeToken) -> Option<String> {
+    let contract = tt.contract.as_ref()?;
+    let wallet = tt.wallet.as_ref()?;
+    let amount = tt.amount?;
+    let slippage = tt.slippage?;
+
+    let transactions = match tt.order_type {
+        OrderType::Buy => {
+            trading::prepare_buy(contract, wallet, amount, slippage, tt.route.as_ref())
+                .await
+                .map(|buy| buy.map(|buy| (vec![buy], false)))
+        }
+        OrderType::Sell => trading::prepare_sell(
+            contract,
+            wallet,
+            amount,
+            tt.tokens,
+            slippage,
+            sell_route(chat_id, wallet).await,
+            tt.route.as_ref(),
+            SETTINGS.get(chat_id).await.sell_proceeds,
+        )
+        .await
+        .map(|plan| plan.map(|plan| (plan.transactions, plan.permit.is_some()))),
+    };
+    let (transactions, unsigned_swap) = match transactions {
+        Ok(transactions) => transactions?,
+        Err(e) => {
+            error!("prepare trade error: {}", e);
+            return None;
+        }
+    };
+
+    // a Safe's transactions are executed by one of its owners, who pays the gas
+    let from = if wallet::is_safe(wallet) {
+        wallet::safe_proposer_key()
+            .map(|key| wallet::address_of(&key))
+            .unwrap_or_else(|| wallet::eth_address().to_lowercase())
+    } else {
+        wallet.clone()
+    };
+    let mut estimate = match trading::estimate_transactions_gas(&from, &transactions).await {
+        Ok(estimate) => estimate,
+        Err(e) => {
+            error!("estimate_transactions_gas error: {}", e);
+            return None;
+        }
+    };
+    // the Universal Router swap can't be simulated before the wallet signed its permit
+    if unsigned_swap {
+        estimate.gas += trading::TYPICAL_V2_SWAP_GAS;
+        estimate.simulated = false;
+    }
+    let breakeven = trading::breakeven_percent(estimate.cost_usd(), amount);
+
+    Some(format!(
+        "⛽ Gas: {:.5} ETH (${:.2}), {}\n📈 Breakeven: {}",
+        estimate.cost_eth(),
+        estimate.cost_usd(),
+        if estimate.simulated {
+            "simulated"
+        } else {
+            "partly estimated, a transaction couldn't be simulated yet"
+        },
+        match tt.order_type {
+            OrderType::Buy => format!("the price has to rise {:.2}% to cover the gas", breakeven),
+            OrderType::Sell => format!("the gas costs {:.2}% of the sell", breakeven),
+        }
+    ))
+}
+
+async fn pick_token_prompt(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    chat_id: ChatId,
+    command: String,
+    args: Vec<String>,
+    candidates: Vec<symbols::TokenCandidate>,
+) -> HandlerResult {
+    let buttons: Vec<Vec<InlineKeyboardButton>> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| {
+            vec![InlineKeyboardButton::callback(
+                format!(
+                    "{} - {} ({}...{})",
+                    candidate.symbol,
+                    candidate.name,
+                    &candidate.address[..6],
+                    &candidate.address[candidate.address.len() - 4..]
+                ),
+                i.to_string(),
+            )]
+        })
+        .collect();
+
+    let prompt = bot
+        .send_message(
+            chat_id,
+            format!(
+                "There are multiple tokens named {}, which one did you mean?",
+                args[0]
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::new(buttons))
+        .await?;
+    prompts::opened(chat_id, prompt.id).await;
+
+    dialogue
+        .update(State::PickToken {
+            command,
+            args,
+            candidates: candidates.into_iter().map(|c| c.address).collect(),
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn pick_token(
+    bot: Bot,
+    dialogue: MyDialogue,
+    q: CallbackQuery,
+    (command, mut args, candidates): (String, Vec<String>, Vec<String>),
+) -> HandlerResult {
+    let chat_id = q.chat_id().unwrap();
 
+    bot.answer_callback_query(q.id).await?;
+    let message_id = q.message.unwrap().id;
+    prompts::resolved(chat_id, message_id).await;
+    bot.delete_message(chat_id, message_id).await?;
     dialogue.exit().await?;
+
+    match q
+        .data
+        .and_then(|data| data.parse::<usize>().ok())
+        .and_then(|i| candidates.get(i))
+    {
+        Some(address) => {
+            args[0] = address.clone();
+
+            if command == "scan" {
+                scan_contract(&bot, chat_id, address).await?;
+            } else if command == "exit" {
+                exit_checklist(&bot, &dialogue, chat_id, address).await?;
+            } else {
+                start_trade(&bot, &dialogue, chat_id, &command, &args).await?;
+            }
+        }
+        None => {
+            bot.send_message(
+                chat_id,
+                format!("Something went wrong with the button handling"),
+            )
+            .await?;
+        }
+    }
+
     Ok(())
 }
 
-async fn watch_wallets(bot: Bot, msg: Message) -> HandlerResult {
-    let (_, args) =
-        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
-    let wallets = validate_watchwallets_args(msg.chat.id, &args).await;
+async fn confirm_transaction(
+    bot: Bot,
+    dialogue: MyDialogue,
+    tt: TradeToken,
+    q: CallbackQuery,
+) -> HandlerResult {
+    let chat_id = q.chat_id().unwrap();
+
+    match q.clone().data {
+        Some(callback) => {
+            bot.answer_callback_query(q.id).await?;
 
-    match wallets {
-        Some(value) => {
-            let mut message: String = String::from("Currently watched wallets:\n");
-            let mut counter: u8 = 0;
+            let message_id = q.message.unwrap().id;
+            prompts::resolved(chat_id, message_id).await;
+            bot.delete_message(chat_id, message_id).await?;
 
-            for wallet in value {
-                counter = counter + 1;
-                message.push_str(&format!("\n{}. {}", counter, &wallet));
+            // the buy itself is confirmed next, the dialogue stays in State::Confirm
+            if callback == "addtoposition" {
+                return trade_prompt(&bot, &dialogue, chat_id, tt).await;
             }
 
-            bot.send_message(msg.chat.id, message).await?;
+            // picking a funding wallet confirms the buy
+            let picked_wallet = callback
+                .strip_prefix("wallet:")
+                .and_then(|i| i.parse::<usize>().ok())
+                .and_then(|i| wallet::trading_wallets().get(i).cloned());
+
+            if callback == "yes" || picked_wallet.is_some() {
+                let tt = TradeToken {
+                    wallet: picked_wallet.or(tt.wallet),
+                    ..tt
+                };
+                return guard_trade(&bot, &dialogue, chat_id, GuardedTrade::Trade(tt)).await;
+            } else if callback == "no" {
+                bot.send_message(chat_id, format!("Transaction was not executed!"))
+                    .await?;
+            }
         }
         None => {
             bot.send_message(
-                msg.chat.id,
-                format!("Watch wallets cancelled: submitted wallets are incorrect"),
+                chat_id,
+                format!("Something went wrong with the button handling"),
+            )
+            .await?;
+        }
+    }
+
+    dialogue.exit().await?;
+    Ok(())
+}
+
+// chats with a trade lock send their PIN or authenticator code before the trade runs
+async fn guard_trade(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    chat_id: ChatId,
+    trade: GuardedTrade,
+) -> HandlerResult {
+    let ask = match tradelock::challenge(chat_id).await {
+        tradelock::Challenge::Open => {
+            return run_guarded_trade(bot, dialogue, chat_id, trade).await
+        }
+        tradelock::Challenge::LockedOut(until) => {
+            bot.send_message(
+                chat_id,
+                format!(
+                    "{}, nothing was executed",
+                    trade_lockout_message(chat_id, until).await
+                ),
+            )
+            .await?;
+            dialogue.exit().await?;
+            return Ok(());
+        }
+        tradelock::Challenge::Pin => "🔐 Reply with the PIN to execute the trade",
+        tradelock::Challenge::Totp => {
+            "🔐 Reply with the code from your authenticator app to execute the trade"
+        }
+    };
+
+    bot.send_message(chat_id, format!("{}, or /cancel", ask))
+        .await?;
+    dialogue.update(State::VerifyTrade(trade)).await?;
+
+    Ok(())
+}
+
+async fn run_guarded_trade(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    chat_id: ChatId,
+    trade: GuardedTrade,
+) -> HandlerResult {
+    match trade {
+        GuardedTrade::Trade(tt) => execute_trade(bot, dialogue, chat_id, tt).await,
+        GuardedTrade::Dca(id) => {
+            dialogue.exit().await?;
+            confirmed_dca_buy(bot, chat_id, id).await
+        }
+    }
+}
+
+async fn trade_lockout_message(chat_id: ChatId, until: DateTime<Utc>) -> String {
+    let locale = SETTINGS.get(chat_id).await.locale;
+
+    format!(
+        "🔒 Too many wrong codes, trades are locked until {} ({})",
+        locale.time(until, "%H:%M"),
+        locale.zone(until)
+    )
+}
+
+// the message with the code is deleted right away, it stays out of the chat history
+async fn verify_trade(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    trade: GuardedTrade,
+) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    if let Some(user) = &msg.from {
+        if !groups::is_prompt_owner(chat_id, user.id).await {
+            return Ok(());
+        }
+    }
+
+    let Some(code) = msg.text().map(String::from) else {
+        return Ok(());
+    };
+    if let Err(e) = bot.delete_message(chat_id, msg.id).await {
+        warn!("failed to delete the trade code message: {}", e);
+    }
+
+    match tradelock::verify(chat_id, &code).await {
+        tradelock::Verdict::Accepted => run_guarded_trade(&bot, &dialogue, chat_id, trade).await,
+        tradelock::Verdict::Rejected { attempts_left } => {
+            bot.send_message(
+                chat_id,
+                format!(
+                    "❌ Wrong code, {} more attempts before trades are locked",
+                    attempts_left
+                ),
             )
             .await?;
+            Ok(())
+        }
+        tradelock::Verdict::LockedOut(until) => {
+            bot.send_message(
+                chat_id,
+                format!(
+                    "{}, nothing was executed",
+                    trade_lockout_message(chat_id, until).await
+                ),
+            )
+            .await?;
+            dialogue.exit().await?;
+            Ok(())
+        }
+    }
+}
+
+// runs the trade confirmed in the dialogue
+async fn execute_trade(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    chat_id: ChatId,
+    trade_token: TradeToken,
+) -> HandlerResult {
+    let wallet = trade_wallet(&trade_token).await;
+
+    match trade_token.order_type {
+        OrderType::Sell => {
+            let route = sell_route(chat_id, &wallet).await;
+
+            match trading::prepare_sell(
+                trade_token.contract.as_ref().unwrap(),
+                &wallet,
+                trade_token.amount.unwrap(),
+                trade_token.tokens,
+                trade_token.slippage.unwrap(),
+                route,
+                trade_token.route.as_ref(),
+                SETTINGS.get(chat_id).await.sell_proceeds,
+            )
+            .await
+            {
+                Ok(Some(plan)) if wallet::is_safe(&wallet) => {
+                    propose_to_safe(bot, chat_id, &wallet, plan, trade_token.clone()).await?;
+                }
+                Ok(Some(plan)) => {
+                    // a swap waiting for its permit is checked once it's signed
+                    if plan.permit.is_none() {
+                        if let Some(retry) =
+                            slippage_retry(&trade_token, &wallet, plan.through_swap()).await
+                        {
+                            return offer_slippage_retry(
+                                bot,
+                                dialogue,
+                                chat_id,
+                                trade_token,
+                                retry,
+                            )
+                            .await;
+                        }
+                    }
+
+                    if let Some(session) = walletconnect::session_for(&wallet).await {
+                        bot.send_message(chat_id, plan.to_string()).await?;
+                        if plan.permit.is_some() {
+                            tasks::spawn_job(
+                                "sell_with_permit",
+                                sell_with_permit(
+                                    bot.clone(),
+                                    dialogue.clone(),
+                                    chat_id,
+                                    session,
+                                    plan,
+                                    trade_token,
+                                ),
+                            );
+                        } else {
+                            tasks::spawn_job(
+                                "execute_with_walletconnect",
+                                execute_with_walletconnect(
+                                    bot.clone(),
+                                    chat_id,
+                                    session,
+                                    plan.transactions,
+                                    trade_token,
+                                ),
+                            );
+                        }
+                        dialogue.exit().await?;
+                        return Ok(());
+                    }
+
+                    bot.send_message(
+                        chat_id,
+                        format!("{}\n\nTransaction was not executed: {}", plan, NOT_SIGNED),
+                    )
+                    .await?;
+                }
+                Ok(None) => {
+                    bot.send_message(chat_id, "Trade cancelled: token price is not available!")
+                        .await?;
+                }
+                Err(e) => {
+                    error!("prepare_sell error: {}", e);
+                    let message = match e.downcast_ref::<trading::DecimalsMismatch>() {
+                        Some(mismatch) => format!("Trade cancelled: {}", mismatch),
+                        None => format!("Something went wrong, please try again later"),
+                    };
+                    bot.send_message(chat_id, message).await?;
+                }
+            }
+        }
+        OrderType::Buy => match trade_token.twap {
+            Some(twap) => {
+                if walletconnect::session_for(&wallet).await.is_none() {
+                    bot.send_message(chat_id, format!("TWAP was not started: {}", NOT_SIGNED))
+                        .await?;
+                } else {
+                    bot.send_message(chat_id, format!("⏱ TWAP started from {}: {}", wallet, twap))
+                        .await?;
+                    tasks::spawn_job(
+                        "twap",
+                        execute_twap(bot.clone(), chat_id, wallet, trade_token, twap),
+                    );
+                }
+            }
+            None => match trading::prepare_buy(
+                trade_token.contract.as_ref().unwrap(),
+                &wallet,
+                trade_token.amount.unwrap(),
+                trade_token.slippage.unwrap(),
+                trade_token.route.as_ref(),
+            )
+            .await
+            {
+                Ok(Some(buy)) => {
+                    let buys = std::slice::from_ref(&buy);
+                    if let Some(retry) = slippage_retry(&trade_token, &wallet, buys).await {
+                        return offer_slippage_retry(bot, dialogue, chat_id, trade_token, retry)
+                            .await;
+                    }
+
+                    if let Some(session) = walletconnect::session_for(&wallet).await {
+                        tasks::spawn_job(
+                            "execute_with_walletconnect",
+                            execute_with_walletconnect(
+                                bot.clone(),
+                                chat_id,
+                                session,
+                                vec![buy],
+                                trade_token,
+                            ),
+                        );
+                        dialogue.exit().await?;
+                        return Ok(());
+                    }
+
+                    bot.send_message(
+                        chat_id,
+                        format!("Transaction was not executed: {}", NOT_SIGNED),
+                    )
+                    .await?;
+                }
+                Ok(None) => {
+                    bot.send_message(chat_id, "Trade cancelled: token price is not available!")
+                        .await?;
+                }
+                Err(e) => {
+                    error!("prepare_buy error: {}", e);
+                    bot.send_message(
+                        chat_id,
+                        format!("Something went wrong, please try again later"),
+                    )
+                    .await?;
+                }
+            },
+        },
+    }
+
+    dialogue.exit().await?;
+    Ok(())
+}
+
+// Some when the swap would revert because it gets less than the slippage allows, with the
+// slippage a retry can use, None when the swap goes through or fails for another reason.
+// the swap comes last, the approvals before it are simulated first so a sell has its allowance
+async fn slippage_retry(
+    tt: &TradeToken,
+    from: &str,
+    transactions: &[trading::TransactionRequest],
+) -> Option<Option<f32>> {
+    let simulation = match transactions {
+        [swap] => {
+            api::simulate_revert(
+                from.to_owned(),
+                swap.to.clone(),
+                swap.data.clone(),
+                swap.value,
+            )
+            .await
+        }
+        _ => {
+            api::simulate_revert_in_order(
+                from.to_owned(),
+                transactions
+                    .iter()
+                    .map(|t| (t.to.clone(), t.data.clone(), t.value))
+                    .collect(),
+            )
+            .await
+        }
+    };
+    let reason = match simulation {
+        Ok(reason) => reason?,
+        Err(e) => {
+            error!("simulate_revert error: {}", e);
+            return None;
+        }
+    };
+    if !trading::is_slippage_revert(&reason) {
+        return None;
+    }
+
+    // the measured tax is usually why the minimum output wasn't met
+    let tax = match api::get_token_info(tt.contract.clone()?).await {
+        Ok(info) => match tt.order_type {
+            OrderType::Buy => info.buy_tax,
+            OrderType::Sell => info.sell_tax,
+        },
+        Err(e) => {
+            error!("get_token_info error: {}", e);
+            0.0
+        }
+    };
+
+    Some(trading::retry_slippage(
+        tt.slippage?,
+        tax,
+        trading::max_retry_slippage(),
+    ))
+}
+
+// the retry is a new confirmation of the same trade, so one tap sends it with the higher slippage
+async fn offer_slippage_retry(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    chat_id: ChatId,
+    tt: TradeToken,
+    retry: Option<f32>,
+) -> HandlerResult {
+    let slippage = tt.slippage.unwrap_or_default();
+
+    let Some(retry) = retry else {
+        bot.send_message(
+            chat_id,
+            format!(
+                "Trade cancelled: the swap would revert, it gets less than {}% slippage allows and retries are capped at {}%",
+                slippage,
+                trading::max_retry_slippage()
+            ),
+        )
+        .await?;
+        dialogue.exit().await?;
+        return Ok(());
+    };
+
+    let tt = TradeToken {
+        slippage: Some(retry),
+        ..tt
+    };
+
+    let prompt = bot
+        .send_message(
+            chat_id,
+            format!(
+                "⚠️ The swap would revert, it gets less than {}% slippage allows. Retry with {}% slippage?",
+                slippage, retry
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::new(vec![vec![
+            InlineKeyboardButton::callback("No", "no"),
+            InlineKeyboardButton::callback(format!("Retry with {}%", retry), "yes"),
+        ]]))
+        .await?;
+    prompts::opened(chat_id, prompt.id).await;
+    dialogue.update(State::Confirm(tt)).await?;
+
+    Ok(())
+}
+
+// the trade's transactions are signed and broadcast by the paired wallet one at a time, each
+// one has to be mined before the next is requested, e.g. the approve before the swap. the mined
+// swap's block is checked for a sandwich right away
+async fn execute_with_walletconnect(
+    bot: Bot,
+    chat_id: ChatId,
+    session: walletconnect::Session,
+    transactions: Vec<trading::TransactionRequest>,
+    trade_token: TradeToken,
+) {
+    let Some(hash) = send_with_walletconnect(&bot, chat_id, &session, &transactions).await else {
+        return;
+    };
+
+    let mut message = format!("✅ Transaction executed from {}!", session.account);
+    if let Some(receipt) = record_trade(&trade_token, &hash).await {
+        message = format!("{}\n\n{}", message, receipt);
+    }
+    let _ = bot.send_message(chat_id, message).await;
+
+    if let Err(e) = sandwich_report(&bot, chat_id, &hash).await {
+        error!("sandwich_report error: {}", e);
+    }
+}
+
+// the wallet signs the Permit2 permit, then the sell is checked for slippage and sent like any other
+async fn sell_with_permit(
+    bot: Bot,
+    dialogue: MyDialogue,
+    chat_id: ChatId,
+    session: walletconnect::Session,
+    plan: trading::SellPlan,
+    trade_token: TradeToken,
+) {
+    let Some(permit) = plan.permit.as_ref() else {
+        return;
+    };
+
+    let _ = bot
+        .send_message(chat_id, "📱 Sign the Permit2 permit in your wallet")
+        .await;
+    let signature = match walletconnect::sign_typed_data(&session, &permit.typed_data()).await {
+        Ok(signature) => signature,
+        Err(e) => {
+            warn!("walletconnect sign_typed_data error: {}", e);
+            let _ = bot
+                .send_message(chat_id, format!("Trade cancelled: {}", e))
+                .await;
+            return;
+        }
+    };
+    let plan = plan.with_permit_signature(&signature);
+
+    if let Some(retry) = slippage_retry(&trade_token, &session.account, plan.through_swap()).await {
+        if let Err(e) = offer_slippage_retry(&bot, &dialogue, chat_id, trade_token, retry).await {
+            error!("offer_slippage_retry error: {}", e);
+        }
+        return;
+    }
+
+    execute_with_walletconnect(bot, chat_id, session, plan.transactions, trade_token).await;
+}
+
+// every transaction is sent for approval once the one before it was mined, returns the hash of
+// the last one, None when one was rejected or failed
+async fn send_with_walletconnect(
+    bot: &Bot,
+    chat_id: ChatId,
+    session: &walletconnect::Session,
+    transactions: &[trading::TransactionRequest],
+) -> Option<String> {
+    let mut last_hash = None;
+
+    for transaction in transactions {
+        let _ = bot
+            .send_message(
+                chat_id,
+                format!("📱 Approve in your wallet: {}", transaction.description),
+            )
+            .await;
+
+        // another job can send from the same wallet while this one waits for the phone
+        let nonce = match wallet::reserve_nonce(&session.account).await {
+            Ok(nonce) => Some(nonce),
+            Err(e) => {
+                error!("reserve_nonce error: {}", e);
+                None
+            }
+        };
+        let hash = match walletconnect::send_transaction(session, transaction, nonce).await {
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!("walletconnect send_transaction error: {}", e);
+                wallet::resync_nonce(&session.account).await;
+                let _ = bot
+                    .send_message(chat_id, format!("Trade cancelled: {}", e))
+                    .await;
+                return None;
+            }
+        };
+        let _ = bot
+            .send_message(
+                chat_id,
+                format!(
+                    "⏳ Sent, waiting to be mined: {}",
+                    utils::tx_link(Chain::Ethereum, &hash, &hash)
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .disable_web_page_preview(true)
+            .await;
+
+        if !is_mined_successfully(&hash).await {
+            wallet::resync_nonce(&session.account).await;
+            let _ = bot
+                .send_message(
+                    chat_id,
+                    format!(
+                        "❌ {} failed or wasn't mined in time, the trade was stopped: {}",
+                        transaction.description,
+                        utils::tx_link(Chain::Ethereum, &hash, &hash)
+                    ),
+                )
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true)
+                .await;
+            return None;
+        }
+        last_hash = Some(hash);
+    }
+
+    last_hash
+}
+
+// polls the receipt for up to 30 minutes, a low gas price can keep it pending for a while
+async fn is_mined_successfully(hash: &str) -> bool {
+    for _ in 0..30 * 4 {
+        tokio::time::sleep(Duration::try_seconds(15).unwrap().to_std().unwrap()).await;
+
+        match api::get_transaction_receipt(hash.to_owned()).await {
+            Ok(Some(receipt)) => return receipt.status == "0x1",
+            Ok(None) => continue,
+            Err(e) => error!("get_transaction_receipt error: {}", e),
+        }
+    }
+
+    false
+}
+
+// sells from the Safe are proposed to the Safe Transaction Service instead of being signed with
+// a raw key, the trade is recorded once the owners executed it
+async fn propose_to_safe(
+    bot: &Bot,
+    chat_id: ChatId,
+    safe: &str,
+    plan: trading::SellPlan,
+    trade_token: TradeToken,
+) -> HandlerResult {
+    let Some(proposer) = wallet::safe_proposer_key() else {
+        bot.send_message(
+            chat_id,
+            "Trade cancelled: SAFE_PROPOSER_KEY is not set, the Safe transaction can't be signed!",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let nonce = match api::get_next_safe_nonce(safe).await {
+        Ok(nonce) => nonce,
+        Err(e) => {
+            error!("get_next_safe_nonce error: {}", e);
+            bot.send_message(
+                chat_id,
+                "Trade cancelled: the Safe Transaction Service is not available!",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let transaction = safe::SafeTransaction::from_requests(&plan.transactions, nonce);
+    let safe_tx_hash = transaction.hash(safe);
+
+    let proposal = match transaction.proposal(safe, &proposer) {
+        Ok(proposal) => proposal,
+        Err(e) => {
+            error!("sign Safe proposal error: {}", e);
+            bot.send_message(
+                chat_id,
+                "Trade cancelled: the Safe transaction could not be signed!",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    if let Err(e) = api::propose_safe_transaction(safe, &proposal).await {
+        error!("propose_safe_transaction error: {}", e);
+        bot.send_message(
+            chat_id,
+            "Trade cancelled: the Safe transaction could not be proposed!",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let proposed = SafeProposed {
+        safe: safe.to_owned(),
+        safe_tx_hash,
+        nonce,
+        plan,
+    };
+    let message = bot
+        .send_message(
+            chat_id,
+            proposed.status("⏳ Waiting for the owners' confirmations"),
+        )
+        .disable_web_page_preview(true)
+        .await?;
+
+    tasks::spawn_job(
+        "track_safe_transaction",
+        track_safe_transaction(bot.clone(), chat_id, message.id, proposed, trade_token),
+    );
+
+    Ok(())
+}
+
+struct SafeProposed {
+    safe: String,
+    safe_tx_hash: String,
+    nonce: u64,
+    plan: trading::SellPlan,
+}
+
+impl SafeProposed {
+    fn status(&self, status: &str) -> String {
+        format!(
+            "🔐 Proposed to Safe {}\n\n{}\n\n🔢 Safe nonce: {}\n#️⃣ {}\n\n{}\n\n{}",
+            self.safe,
+            self.plan,
+            self.nonce,
+            self.safe_tx_hash,
+            status,
+            safe::app_url(&self.safe, &self.safe_tx_hash)
+        )
+    }
+}
+
+// edits the proposal message as owners confirm, until it's executed or another transaction took its nonce
+async fn track_safe_transaction(
+    bot: Bot,
+    chat_id: ChatId,
+    message_id: MessageId,
+    proposed: SafeProposed,
+    trade_token: TradeToken,
+) {
+    let mut last_status = String::new();
+
+    // give up after a day, the owners might never confirm
+    for _ in 0..24 * 60 {
+        tokio::time::sleep(Duration::try_minutes(1).unwrap().to_std().unwrap()).await;
+        tasks::heartbeat("track_safe_transaction").await;
+
+        let transaction = match api::get_safe_transaction(&proposed.safe_tx_hash).await {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                error!("get_safe_transaction error: {}", e);
+                continue;
+            }
+        };
+        let tx_link = transaction
+            .transaction_hash
+            .as_deref()
+            .map(|hash| utils::explorer_tx_url(Chain::Ethereum, hash))
+            .unwrap_or_default();
+
+        let (status, finished) = if transaction.is_executed {
+            if transaction.is_successful == Some(false) {
+                (format!("❌ Execution failed\n{}", tx_link), true)
+            } else {
+                let mut status = format!("✅ Executed\n{}", tx_link);
+                if let Some(hash) = &transaction.transaction_hash {
+                    if let Some(receipt) = record_trade(&trade_token, hash).await {
+                        status = format!("{}\n\n{}", status, receipt);
+                    }
+                }
+                (status, true)
+            }
+        } else if api::get_safe_info(&proposed.safe)
+            .await
+            .is_ok_and(|info| info.nonce > proposed.nonce)
+        {
+            (
+                String::from("🚫 Another Safe transaction was executed with this nonce"),
+                true,
+            )
+        } else {
+            (
+                format!(
+                    "⏳ {}/{} confirmations",
+                    transaction.confirmations.map_or(0, |c| c.len()),
+                    transaction
+                        .confirmations_required
+                        .map_or(String::from("?"), |required| required.to_string())
+                ),
+                false,
+            )
+        };
+
+        if status != last_status {
+            let _ = bot
+                .edit_message_text(chat_id, message_id, proposed.status(&status))
+                .disable_web_page_preview(true)
+                .await;
+            last_status = status;
+        }
+
+        if finished {
+            return;
+        }
+    }
+
+    let _ = bot
+        .edit_message_text(
+            chat_id,
+            message_id,
+            proposed.status("❌ Still not executed after a day, tracking stopped"),
+        )
+        .disable_web_page_preview(true)
+        .await;
+}
+
+// buys one slice per interval through the paired wallet, slices are skipped while the token
+// fails the honeypot and tax checks, only mined slices count towards the fill
+async fn execute_twap(
+    bot: Bot,
+    chat_id: ChatId,
+    wallet: String,
+    trade_token: TradeToken,
+    twap: trading::Twap,
+) {
+    let contract = trade_token.contract.clone().unwrap();
+    let slice_amount = twap.slice_amount(trade_token.amount.unwrap());
+    let slice = TradeToken {
+        amount: Some(slice_amount),
+        ..trade_token
+    };
+    let mut fill = trading::TwapFill::default();
+
+    for i in 1..=twap.slices {
+        if i > 1 {
+            tokio::time::sleep(twap.interval().to_std().unwrap()).await;
+        }
+        tasks::heartbeat("twap").await;
+
+        // why the slice was skipped, with the provider whose failed request caused it
+        let skip: Option<(&str, Option<api::Provider>)> =
+            match api::is_token_honeypot(contract.clone()).await {
+                Some(false) => match api::get_token_price(contract.clone()).await {
+                    Ok(price) if price > 0.0 => {
+                        match buy_twap_slice(&bot, chat_id, &wallet, &slice).await {
+                            Ok(()) => {
+                                fill.fill(slice_amount, price as f64);
+                                None
+                            }
+                            Err(reason) => Some((reason, None)),
+                        }
+                    }
+                    Ok(_) => Some(("token price is not available", None)),
+                    Err(e) => {
+                        error!("get_token_price error: {}", e);
+                        Some(("token price is not available", Some(api::Provider::Moralis)))
+                    }
+                },
+                Some(true) => Some(("token looks like a honeypot or has high taxes", None)),
+                None => Some((
+                    "safety checks are not available",
+                    Some(api::Provider::Honeypot),
+                )),
+            };
+
+        if let Some((reason, provider)) = skip {
+            fill.skip();
+            // the owner was alerted about the outage once, the finished message has the count
+            if let Some(provider) = provider {
+                if api::is_provider_down(provider).await {
+                    continue;
+                }
+            }
+            let _ = bot
+                .send_message(
+                    chat_id,
+                    format!("⏭ TWAP slice {}/{} skipped: {}", i, twap.slices, reason),
+                )
+                .await;
+        }
+    }
+
+    let message = match fill.blended_price() {
+        Some(price) => format!(
+            "✅ TWAP finished for {}\n\n🧩 Slices: {}/{} executed\n💰 Spent: ${:.2}\n🏷 Blended entry price: ${}",
+            short_address(&contract).await,
+            fill.executed,
+            twap.slices,
+            fill.spent_usd,
+            price
+        ),
+        None => format!(
+            "❌ TWAP finished for {} without executing any slice",
+            short_address(&contract).await
+        ),
+    };
+    let _ = bot
+        .send_message(chat_id, message)
+        .parse_mode(ParseMode::Html)
+        .await;
+}
+
+// Ok once the slice was mined, otherwise why it wasn't bought
+async fn buy_twap_slice(
+    bot: &Bot,
+    chat_id: ChatId,
+    wallet: &str,
+    slice: &TradeToken,
+) -> Result<(), &'static str> {
+    let Some(session) = walletconnect::session_for(wallet).await else {
+        return Err("the wallet is no longer paired with /walletconnect");
+    };
+    let buy = match trading::prepare_buy(
+        slice.contract.as_ref().unwrap(),
+        wallet,
+        slice.amount.unwrap(),
+        slice.slippage.unwrap(),
+        slice.route.as_ref(),
+    )
+    .await
+    {
+        Ok(Some(buy)) => buy,
+        Ok(None) => return Err("token price is not available"),
+        Err(e) => {
+            error!("prepare_buy error: {}", e);
+            return Err("the buy couldn't be prepared");
+        }
+    };
+
+    // the chat was already told why the slice wasn't sent or mined
+    let hash = send_with_walletconnect(bot, chat_id, &session, &[buy])
+        .await
+        .ok_or("the transaction wasn't sent or failed")?;
+    record_trade(slice, &hash).await;
+
+    Ok(())
+}
+
+// '/dca <token> <usd> <interval> [confirm]', '/dca list', '/dca pause|resume|stop <id>'
+async fn dca_command(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let chat_id = msg.chat.id;
+
+    match args.as_slice() {
+        [] | ["list"] => {
+            let plans = dca::list(chat_id).await;
+            let locale = SETTINGS.get(chat_id).await.locale;
+            let message = if plans.is_empty() {
+                String::from("There are no DCA plans")
+            } else {
+                plans
+                    .iter()
+                    .fold(String::from("🔁 DCA plans:\n"), |message, plan| {
+                        format!("{}\n{}", message, plan.describe(&locale))
+                    })
+            };
+            groups::reply(&bot, &msg, message).await?;
+        }
+        [action @ ("pause" | "resume" | "stop"), id] => {
+            let Ok(id) = id.trim_start_matches('#').parse::<u32>() else {
+                groups::reply(&bot, &msg, "DCA cancelled: submitted plan id is incorrect!").await?;
+                return Ok(());
+            };
+
+            let plan = match *action {
+                "pause" => dca::set_paused(chat_id, id, true).await,
+                "resume" => dca::set_paused(chat_id, id, false).await,
+                _ => dca::remove(chat_id, id).await,
+            };
+            let message = match plan {
+                Some(plan) if *action == "stop" => format!("DCA plan #{} stopped", plan.id),
+                Some(plan) => plan.describe(&SETTINGS.get(chat_id).await.locale),
+                None => format!("There is no DCA plan #{}", id),
+            };
+            groups::reply(&bot, &msg, message).await?;
+        }
+        [token, amount, interval] | [token, amount, interval, "confirm"] => {
+            let contract = match symbols::resolve(chat_id, token).await {
+                Resolution::Address(address) => address,
+                Resolution::Ambiguous(_) => {
+                    groups::reply(
+                        &bot,
+                        &msg,
+                        format!(
+                            "There are multiple tokens named {}, use the contract address",
+                            token
+                        ),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                Resolution::Unknown => {
+                    groups::reply(
+                        &bot,
+                        &msg,
+                        "DCA cancelled: submitted contract is incorrect!",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            };
+            let Some(amount_usd) = amount.parse::<f64>().ok().filter(|amount| *amount > 0.0) else {
+                groups::reply(&bot, &msg, "DCA cancelled: submitted amount is incorrect!").await?;
+                return Ok(());
+            };
+            let Some(interval) = dca::parse_interval(interval) else {
+                groups::reply(
+                    &bot,
+                    &msg,
+                    "DCA cancelled: the interval should look like 30m, 4h, 1d or 1w, and be at least 10 minutes",
+                )
+                .await?;
+                return Ok(());
+            };
+
+            let symbol = token_symbol(chat_id, &contract).await;
+            let plan = dca::add(
+                chat_id,
+                contract,
+                symbol,
+                amount_usd,
+                interval,
+                args.len() == 3,
+            )
+            .await;
+
+            groups::reply(
+                &bot,
+                &msg,
+                format!(
+                    "🔁 DCA plan created, the first buy runs within a minute\n\n{}",
+                    plan
+                ),
+            )
+            .await?;
+        }
+        _ => {
+            groups::reply(
+                &bot,
+                &msg,
+                "Usage: /dca <token> <usd> <interval> [confirm] | list | pause <id> | resume <id> | stop <id>",
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+// safety checks run before every buy, a failing token is skipped until the next run
+pub async fn dca_buy(bot: &Bot, plan: &dca::DcaPlan) {
+    let blocked = plan.automatic && balance::auto_buys_blocked().await;
+    let message = match api::is_token_honeypot(plan.contract.clone()).await {
+        Some(false) if blocked => format!(
+            "⏭ DCA #{} skipped: the trading wallet's ETH balance is below {} ETH, automatic buys resume once it's topped up",
+            plan.id,
+            balance::threshold()
+        ),
+        Some(false) if plan.automatic => execute_dca_buy(plan).await,
+        Some(false) => {
+            let _ = bot
+                .send_message(
+                    plan.chat_id,
+                    format!(
+                        "🔁 DCA #{}: buy ${} of {}?",
+                        plan.id, plan.amount_usd, plan.symbol
+                    ),
+                )
+                .reply_markup(InlineKeyboardMarkup::new(vec![vec![
+                    InlineKeyboardButton::callback("Buy now", format!("dca:{}", plan.id)),
+                ]]))
+                .await;
+            return;
+        }
+        Some(true) => format!(
+            "⏭ DCA #{} skipped: {} looks like a honeypot or has high taxes",
+            plan.id, plan.symbol
+        ),
+        // the owner was alerted about the honeypot.is outage once, the chat isn't told on every run
+        None if api::is_provider_down(api::Provider::Honeypot).await => return,
+        None => format!(
+            "⏭ DCA #{} skipped: safety checks are not available",
+            plan.id
+        ),
+    };
+
+    let _ = bot.send_message(plan.chat_id, message).await;
+}
+
+// nothing is broadcast from the trading wallet, so the buy is reported and not counted
+async fn execute_dca_buy(plan: &dca::DcaPlan) -> String {
+    format!(
+        "⏭ DCA #{}: the ${} buy of {} from {} was not executed, {}",
+        plan.id,
+        plan.amount_usd,
+        plan.symbol,
+        wallet::eth_address().to_lowercase(),
+        NOT_SIGNED
+    )
+}
+
+async fn dca_confirm(bot: Bot, dialogue: MyDialogue, q: CallbackQuery) -> HandlerResult {
+    let chat_id = q.chat_id().unwrap();
+
+    if callback_permission(&q).await < Permission::Trader {
+        bot.answer_callback_query(q.id)
+            .text("You need the trader permission to buy")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    bot.answer_callback_query(q.id).await?;
+    if let Some(message) = q.message {
+        bot.delete_message(chat_id, message.id).await?;
+    }
+
+    match q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("dca:"))
+        .and_then(|id| id.parse::<u32>().ok())
+    {
+        Some(id) => {
+            // the code is taken from the member who confirmed the buy
+            groups::set_prompt_owner(chat_id, Some(q.from.id)).await;
+            guard_trade(&bot, &dialogue, chat_id, GuardedTrade::Dca(id)).await
+        }
+        None => Ok(()),
+    }
+}
+
+async fn confirmed_dca_buy(bot: &Bot, chat_id: ChatId, id: u32) -> HandlerResult {
+    // the plan could have been paused or stopped since the buy was proposed
+    let message = match dca::get(chat_id, id).await {
+        Some(plan) if !plan.paused => execute_dca_buy(&plan).await,
+        _ => String::from("This DCA plan was paused or stopped, nothing was bought"),
+    };
+    bot.send_message(chat_id, message).await?;
+
+    Ok(())
+}
+
+// sells are routed from the wallet that bought the position, everything else defaults to ETH_ADDRESS
+async fn trade_wallet(trade_token: &TradeToken) -> String {
+    if let Some(wallet) = &trade_token.wallet {
+        return wallet.clone();
+    }
+
+    let held_by = match (&trade_token.order_type, &trade_token.contract) {
+        (OrderType::Sell, Some(contract)) => positions::POSITIONS
+            .lock()
+            .await
+            .position(contract)
+            .and_then(|position| position.wallet().map(String::from)),
+        _ => None,
+    };
+
+    held_by.unwrap_or_else(|| wallet::eth_address().to_lowercase())
+}
+
+// updates the positions ledger once the trade's transaction was mined, token amounts are
+// estimated from the usd amount the same way the trade itself is, returns the receipt for sells
+async fn record_trade(trade_token: &TradeToken, hash: &str) -> Option<positions::RealizedTrade> {
+    let contract = trade_token.contract.clone().unwrap();
+    let amount_usd = trade_token.amount.unwrap();
+
+    let (price, token_info) = tokio::join!(
+        api::get_token_price(contract.clone()),
+        api::get_token_info(contract.clone())
+    );
+    let price = match price {
+        Ok(price) if price > 0.0 => price as f64,
+        Ok(_) => return None,
+        Err(e) => {
+            error!("get_token_price error: {}", e);
+            return None;
+        }
+    };
+    let symbol = match token_info {
+        Ok(token_info) => token_info.symbol,
+        Err(_) => short_address(&contract).await,
+    };
+
+    let wallet = trade_wallet(trade_token).await;
+    // sells given in tokens sell exactly those
+    let tokens = trade_token
+        .tokens
+        .map_or(amount_usd / price, |tokens| tokens.tokens());
+
+    let mut ledger = positions::POSITIONS.lock().await;
+    let realized = match trade_token.order_type {
+        OrderType::Buy => {
+            ledger.buy(&contract, &symbol, tokens, amount_usd, &wallet);
+            None
+        }
+        OrderType::Sell => Some(ledger.sell(&contract, &symbol, tokens, amount_usd, &wallet)),
+    };
+    drop(ledger);
+
+    hooks::trade_executed(&ExecutedTrade {
+        contract,
+        symbol,
+        is_buy: matches!(trade_token.order_type, OrderType::Buy),
+        amount_usd,
+        tokens,
+        wallet,
+        realized_pnl_usd: realized.as_ref().map(|trade| trade.pnl_usd()),
+        hash: hash.to_owned(),
+    });
+
+    realized
+}
+
+async fn sniper_stats(bot: Bot, msg: Message) -> HandlerResult {
+    let stats = positions::POSITIONS.lock().await.stats();
+
+    if stats.sells == 0 && stats.open_positions == 0 {
+        groups::reply(&bot, &msg, "No trades were recorded yet").await?;
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "📊 Trading stats\n\n{} Realized PnL: {}${:.2}\n🧾 Sells: {} (🟢 {} | 🔴 {})\n📦 Open positions: {} (${:.2} cost basis)",
+        if stats.realized_pnl_usd >= 0.0 { "🟢" } else { "🔴" },
+        if stats.realized_pnl_usd >= 0.0 { "+" } else { "-" },
+        stats.realized_pnl_usd.abs(),
+        stats.sells,
+        stats.wins,
+        stats.losses,
+        stats.open_positions,
+        stats.open_cost_usd
+    );
+
+    if let Some(best) = stats.best {
+        message.push_str(&format!(
+            "\n\n🏆 Best: {} {:+.2}$ ({:+.2}%)",
+            best.symbol,
+            best.pnl_usd(),
+            best.pnl_percent()
+        ));
+    }
+
+    if let Some(worst) = stats.worst {
+        message.push_str(&format!(
+            "\n💀 Worst: {} {:+.2}$ ({:+.2}%)",
+            worst.symbol,
+            worst.pnl_usd(),
+            worst.pnl_percent()
+        ));
+    }
+
+    groups::reply(&bot, &msg, message).await?;
+
+    Ok(())
+}
+
+// '/leaderboard' or '/leaderboard 30', profits are in eth and valued at the current price
+async fn wallet_leaderboard(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let days: i64 = match args.first().map(|days| days.parse::<i64>()) {
+        None => 7,
+        Some(Ok(days)) if days == 7 || days == 30 => days,
+        _ => {
+            groups::reply(&bot, &msg, "The period can be 7 or 30 days").await?;
+            return Ok(());
+        }
+    };
+
+    let wallets = WATCHED_WALLETS
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .cloned()
+        .unwrap_or_default();
+    if wallets.is_empty() {
+        groups::reply(
+            &bot,
+            &msg,
+            "There are no watched wallets, add some with /watch",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+    let eth_price = api::get_eth_price().await.unwrap_or(0.0);
+
+    let mut ranking: Vec<(String, api::WalletProfit)> = vec![];
+    let mut failed: Vec<String> = vec![];
+    for wallet in wallets {
+        match api::get_wallet_profit(wallet.clone(), days).await {
+            Ok(profit) => ranking.push((wallet, profit)),
+            Err(e) => {
+                error!("get_wallet_profit error: {}", e);
+                failed.push(wallet);
+            }
+        }
+    }
+    ranking.sort_by(|a, b| b.1.realized_eth.total_cmp(&a.1.realized_eth));
+
+    let mut message = format!(
+        "🏆 Watched wallets by realized profits in the last {} days:\n",
+        days
+    );
+    for (i, (wallet, profit)) in ranking.iter().enumerate() {
+        message.push_str(&format!(
+            "\n{}. {} {}: {:+.3} ETH ({}${:.2}) | {} wins, {} losses",
+            i + 1,
+            if profit.realized_eth >= 0.0 {
+                "🟢"
+            } else {
+                "🔴"
+            },
+            short_address(wallet).await,
+            profit.realized_eth,
+            if profit.realized_eth >= 0.0 { "+" } else { "-" },
+            (profit.realized_eth * eth_price).abs(),
+            profit.wins,
+            profit.losses
+        ));
+    }
+    for wallet in failed {
+        message.push_str(&format!(
+            "\n❔ {}: history is not available",
+            short_address(&wallet).await
+        ));
+    }
+    message.push_str("\n\nOnly the latest 100 token transfers of a wallet are checked");
+
+    bot.delete_message(msg.chat.id, loading_message_id).await?;
+    groups::reply(&bot, &msg, message)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
+
+    Ok(())
+}
+
+// '/recentbuys <address> [days]', to size a wallet up before watching it
+async fn recent_buys(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let Some(wallet) = args
+        .first()
+        .filter(|wallet| utils::is_valid_eth_address(wallet))
+    else {
+        groups::reply(&bot, &msg, "The submitted wallet is not valid!").await?;
+        return Ok(());
+    };
+    let days: i64 = match args.get(1).map(|days| days.parse::<i64>()) {
+        None => 7,
+        Some(Ok(days)) if (1..=MAX_RECENT_BUYS_DAYS).contains(&days) => days,
+        _ => {
+            groups::reply(
+                &bot,
+                &msg,
+                format!("The period can be 1 to {} days", MAX_RECENT_BUYS_DAYS),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+    let buys = match api::get_recent_buys(wallet.to_string(), days).await {
+        Ok(buys) => buys,
+        Err(e) => {
+            error!("get_recent_buys error: {}", e);
+            bot.delete_message(msg.chat.id, loading_message_id).await?;
+            groups::reply(&bot, &msg, "Something went wrong, please try again later").await?;
+            return Ok(());
+        }
+    };
+    let eth_price = api::get_eth_price().await.unwrap_or(0.0);
+    let locale = SETTINGS.get(msg.chat.id).await.locale;
+
+    let mut message = format!(
+        "🛒 Tokens {} bought in the last {} days: {}\n",
+        short_address(wallet).await,
+        days,
+        buys.len()
+    );
+    for (i, buy) in buys.iter().take(MAX_LISTED_RECENT_BUYS).enumerate() {
+        let last_bought_at =
+            DateTime::<Utc>::from_timestamp(buy.last_bought_at as i64, 0).unwrap_or_default();
+        message.push_str(&format!(
+            "\n{}. {} {}: {} buy(s), last {}",
+            i + 1,
+            html::escape(&buy.symbol),
+            short_address(&buy.contract).await,
+            buy.buys,
+            locale.time(last_bought_at, "%Y-%m-%d")
+        ));
+
+        let price = match api::get_recent_buy_price(buy.contract.clone()).await {
+            Ok(price) if price > 0.0 => price as f64,
+            Ok(_) => {
+                message.push_str("\n   💵 Current price is not available");
+                continue;
+            }
+            Err(e) => {
+                error!("get_recent_buy_price error: {}", e);
+                message.push_str("\n   💵 Current price is not available");
+                continue;
+            }
+        };
+        // etherscan has no price history, entries are compared with the current price in eth
+        match buy.entry_price_eth() {
+            Some(entry_eth) if eth_price > 0.0 => {
+                let change = (price / eth_price / entry_eth - 1.0) * 100.0;
+                message.push_str(&format!(
+                    "\n   💵 Entry {} ETH ➡️ now {} ETH (${}) {} {}{}%",
+                    locale.price(entry_eth),
+                    locale.price(price / eth_price),
+                    locale.price(price),
+                    if change >= 0.0 { "🟢" } else { "🔴" },
+                    if change >= 0.0 { "+" } else { "" },
+                    locale.number(change, 1)
+                ));
+            }
+            Some(entry_eth) => message.push_str(&format!(
+                "\n   💵 Entry {} ETH ➡️ now ${}, the ETH price is not available",
+                locale.price(entry_eth),
+                locale.price(price)
+            )),
+            None => message.push_str(&format!(
+                "\n   💵 Paid with another token ➡️ now ${}",
+                locale.price(price)
+            )),
+        }
+    }
+    if buys.len() > MAX_LISTED_RECENT_BUYS {
+        message.push_str(&format!(
+            "\n\n... and {} more",
+            buys.len() - MAX_LISTED_RECENT_BUYS
+        ));
+    }
+    message.push_str("\n\nOnly the latest 100 token transfers of a wallet are checked");
+
+    bot.delete_message(msg.chat.id, loading_message_id).await?;
+    groups::reply(&bot, &msg, message)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
+
+    Ok(())
+}
+
+// '/watch <wallets>' replaces the watchlist, '/watch off' clears it
+async fn watch_wallets(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let wallets = if args.len() == 1 && args[0] == "off" {
+        vec![]
+    } else {
+        match validate_watchwallets_args(&args) {
+            Some(wallets) => wallets,
+            None => {
+                groups::reply(
+                    &bot,
+                    &msg,
+                    format!("Watch wallets cancelled: submitted wallets are incorrect"),
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    };
+
+    let watched = WATCHED_WALLETS
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .cloned()
+        .unwrap_or_default();
+    let removed = removed_entries(&watched, &wallets);
+
+    if removed.is_empty() {
+        groups::reply(&bot, &msg, set_watched_wallets(msg.chat.id, wallets).await).await?;
+    } else {
+        ask_confirmation(
+            &bot,
+            &dialogue,
+            &msg,
+            format!(
+                "⚠️ These wallets will no longer be watched:\n{}",
+                removed.join("\n")
+            ),
+            ConfirmableAction::WatchWallets(wallets),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+// case insensitive, so checksummed and lowercase addresses match
+fn removed_entries(current: &[String], new: &[String]) -> Vec<String> {
+    current
+        .iter()
+        .filter(|entry| !new.iter().any(|n| n.eq_ignore_ascii_case(entry)))
+        .cloned()
+        .collect()
+}
+
+async fn set_watched_wallets(chat_id: ChatId, wallets: Vec<String>) -> String {
+    let linked_chats = profiles::linked_chats(chat_id).await;
+
+    if wallets.is_empty() {
+        let mut watched_wallets = WATCHED_WALLETS.lock().await;
+        for linked_chat in linked_chats {
+            watched_wallets.remove(&linked_chat);
+        }
+        return String::from("Wallets are no longer watched");
+    }
+
+    let mut watched_wallets = WATCHED_WALLETS.lock().await;
+    for linked_chat in &linked_chats {
+        watched_wallets.insert(*linked_chat, wallets.clone());
+    }
+    drop(watched_wallets);
+    for linked_chat in linked_chats {
+        for wallet in &wallets {
+            expiry::touch(linked_chat, WatchKind::Wallet, wallet).await;
+        }
+    }
+
+    let mut message: String = String::from("Currently watched wallets:\n");
+    for (i, wallet) in wallets.iter().enumerate() {
+        match watched_wallet_label(chat_id, wallet).await {
+            Some(label) => message.push_str(&format!("\n{}. {} ({})", i + 1, wallet, label)),
+            None => message.push_str(&format!("\n{}. {}", i + 1, wallet)),
+        }
+    }
+
+    message
+}
+
+async fn import_wallets_help(bot: Bot, msg: Message) -> HandlerResult {
+    groups::reply(
+        &bot,
+        &msg,
+        format!(
+            "Send the CSV file with /importwallets as its caption. Etherscan and Nansen exports are read by their Address and Label or Name Tag columns, a file without a header as address,label lines. Up to {} rows are imported, the wallets are added to the watchlist",
+            walletimport::MAX_IMPORT_ROWS
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+// the command handler checks commands sent as text, a caption has to be checked here
+async fn import_wallets(bot: Bot, msg: Message, document: Document) -> HandlerResult {
+    if member_permission(&msg).await < Command::Importwallets.required_permission() {
+        return permission_required(bot, msg, Command::Importwallets).await;
+    }
+    if !has_premium(msg.chat.id).await {
+        return premium_required(bot, msg).await;
+    }
+    if document.file.size > MAX_IMPORT_FILE_BYTES {
+        groups::reply(&bot, &msg, "Wallet import cancelled: the file is too big").await?;
+        return Ok(());
+    }
+
+    let mut content = vec![];
+    let file = bot.get_file(document.file.id).await?;
+    bot.download_file(&file.path, &mut content).await?;
+
+    let watched = WATCHED_WALLETS
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .cloned()
+        .unwrap_or_default();
+    let summary = match walletimport::parse(&String::from_utf8_lossy(&content), &watched) {
+        Ok(summary) => summary,
+        Err(e) => {
+            groups::reply(&bot, &msg, format!("Wallet import cancelled: {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let linked_chats = profiles::linked_chats(msg.chat.id).await;
+    let mut watched_wallets = WATCHED_WALLETS.lock().await;
+    for linked_chat in &linked_chats {
+        let wallets = watched_wallets.entry(*linked_chat).or_default();
+        for (address, _) in &summary.added {
+            if !wallets.iter().any(|w| w.eq_ignore_ascii_case(address)) {
+                wallets.push(address.clone());
+            }
+        }
+    }
+    drop(watched_wallets);
+    walletimport::set_labels(&linked_chats, &summary.added).await;
+    for linked_chat in linked_chats {
+        for (address, _) in &summary.added {
+            expiry::touch(linked_chat, WatchKind::Wallet, address).await;
+        }
+    }
+
+    groups::reply(&bot, &msg, summary.to_string()).await?;
+    Ok(())
+}
+
+// generic yes/no dialog for destructive actions, the summary should say everything that changes
+async fn ask_confirmation(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    msg: &Message,
+    summary: String,
+    action: ConfirmableAction,
+) -> HandlerResult {
+    groups::set_prompt_owner(msg.chat.id, msg.from().map(|user| user.id)).await;
+
+    let prompt = groups::reply(bot, msg, format!("{}\n\nDo you want to continue?", summary))
+        .reply_markup(make_yes_no_keyboard())
+        .await?;
+    prompts::opened(msg.chat.id, prompt.id).await;
+    dialogue.update(State::ConfirmAction(action)).await?;
+
+    Ok(())
+}
+
+async fn confirm_action(
+    bot: Bot,
+    dialogue: MyDialogue,
+    q: CallbackQuery,
+    action: ConfirmableAction,
+) -> HandlerResult {
+    let chat_id = q.chat_id().unwrap();
+
+    bot.answer_callback_query(q.id).await?;
+    let message_id = q.message.unwrap().id;
+    prompts::resolved(chat_id, message_id).await;
+    bot.delete_message(chat_id, message_id).await?;
+    dialogue.exit().await?;
+
+    match q.data.as_deref() {
+        Some("yes") => {
+            bot.send_message(chat_id, action.execute(chat_id).await)
+                .await?;
+        }
+        _ => {
+            bot.send_message(chat_id, "Nothing was changed").await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_portfolio(bot: Bot, msg: Message) -> HandlerResult {
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+
+    match api::get_token_balances_with_prices().await {
+        Ok(owned_tokens) => {
+            let mut message: String = String::from("Portfolio:\n");
+            let template = templates::get(msg.chat.id, TemplateKind::Portfolio)
+                .await
+                .map(|template| html::escape(&template));
+            let mut found = false;
+            let mut buttons: Vec<Vec<InlineKeyboardButton>> = vec![];
+
+            for mut token in owned_tokens {
+                metadata::get(msg.chat.id, &token.contract)
+                    .await
+                    .apply(&mut token);
+                symbols::remember(msg.chat.id, &token.symbol, &token.name, &token.contract).await;
+
+                if SETTINGS.get(msg.chat.id).await.hide_zero_token_balances
+                    && token.value_usd == 0.0
+                {
+                    continue;
+                }
+
+                let percent_change = {
+                    if token.usd_price_24hr_percent_change > 0.0 {
+                        format!("📈 +{:.2}%", token.usd_price_24hr_percent_change)
+                    } else {
+                        format!("📉 {:.2}%", token.usd_price_24hr_percent_change)
+                    }
+                };
+
+                let locale = SETTINGS.get(msg.chat.id).await.locale;
+                let balance = locale.number(token.balance, 2);
+                let usd_value = locale.number(token.value_usd, 2);
+
+                // TODO: add thumbnail to message if available
+                match &template {
+                    Some(template) => {
+                        let entry = templates::render(
+                            template,
+                            &[
+                                ("token_name", html::escape(&token.name)),
+                                ("token_symbol", html::escape(&token.symbol)),
+                                ("contract", token.contract.clone()),
+                                ("balance", balance),
+                                ("usd_value", usd_value),
+                                ("percent_change", percent_change),
+                                (
+                                    "portfolio_percent",
+                                    format!("{:.2}", token.portfolio_percentage),
+                                ),
+                                ("links", contract_links(msg.chat.id, &token.contract).await),
+                            ],
+                        );
+                        message.push_str(&format!("\n{}\n", entry));
+                    }
+                    None => message.push_str(&format!(
+                        "\n💎 {} ({})\n💰 {} (${})\n{}\n📊 {:.2}%\n{}\n",
+                        token.name,
+                        token.symbol,
+                        balance,
+                        usd_value,
+                        percent_change,
+                        token.portfolio_percentage,
+                        contract_links(msg.chat.id, &token.contract).await
+                    )),
+                }
+
+                if buttons.len() < MAX_PORTFOLIO_ACTION_ROWS {
+                    buttons.push(make_portfolio_actions(
+                        &token,
+                        chart_site(msg.chat.id).await,
+                    ));
+                }
+
+                found = true;
+            }
+
+            bot.delete_message(msg.chat.id, loading_message_id).await?;
+            if found {
+                groups::reply(&bot, &msg, format!("{}", message))
+                    .parse_mode(ParseMode::Html)
+                    .disable_web_page_preview(true)
+                    .reply_markup(InlineKeyboardMarkup::new(buttons))
+                    .await?;
+            } else {
+                groups::reply(&bot, &msg, format!("No token balances were found!")).await?;
+            }
+        }
+        Err(e) => {
+            error!("get_token_balances_with_prices error: {}", e);
+            bot.delete_message(msg.chat.id, loading_message_id).await?;
+            groups::reply(
+                &bot,
+                &msg,
+                format!("Something went wrong, please try again later"),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn make_portfolio_actions(
+    token: &api::OwnedToken,
+    chart_site: ChartSite,
+) -> Vec<InlineKeyboardButton> {
+    let mut row = vec![];
+
+    if token.value_usd > 0.0 {
+        for percent in [25, 100] {
+            let action = PortfolioAction::Sell {
+                contract: token.contract.clone(),
+                percent,
+            };
+            let text = match percent {
+                25 => format!("{}: Sell 25%", token.symbol),
+                _ => format!("Sell {}%", percent),
+            };
+            row.push(InlineKeyboardButton::callback(text, action.callback_data()));
+        }
+    }
+
+    row.push(InlineKeyboardButton::callback(
+        if row.is_empty() {
+            format!("{}: Scan", token.symbol)
+        } else {
+            String::from("Scan")
+        },
+        PortfolioAction::Scan(token.contract.clone()).callback_data(),
+    ));
+
+    if let Ok(url) = reqwest::Url::parse(&utils::chart_url(
+        chart_site,
+        Chain::Ethereum,
+        &token.contract,
+    )) {
+        row.push(InlineKeyboardButton::url("Chart", url));
+    }
+
+    row
+}
+
+// buy and scan buttons under new token alerts
+pub fn new_token_keyboard(contract: &str) -> InlineKeyboardMarkup {
+    let mut row: Vec<InlineKeyboardButton> = NEW_TOKEN_BUY_AMOUNTS_USD
+        .iter()
+        .map(|usd| {
+            InlineKeyboardButton::callback(
+                format!("Buy ${}", usd),
+                PortfolioAction::Buy {
+                    contract: contract.to_owned(),
+                    usd: *usd,
+                }
+                .callback_data(),
+            )
+        })
+        .collect();
+    row.push(InlineKeyboardButton::callback(
+        "Scan",
+        PortfolioAction::Scan(contract.to_owned()).callback_data(),
+    ));
+
+    InlineKeyboardMarkup::new(vec![
+        row,
+        vec![InlineKeyboardButton::callback(
+            "🔄 Re-check now",
+            format!("recheck:{}", contract),
+        )],
+    ])
+}
+
+// the owner has every permission in every group
+async fn callback_permission(q: &CallbackQuery) -> Permission {
+    if owner_id() == Some(q.from.id.0) {
+        return Permission::Admin;
+    }
+
+    match &q.message {
+        Some(message) => groups::permission(&message.chat, Some(q.from.id)).await,
+        None => Permission::Viewer,
+    }
+}
+
+async fn portfolio_action(
+    bot: Bot,
+    dialogue: MyDialogue,
+    q: CallbackQuery,
+    action: PortfolioAction,
+) -> HandlerResult {
+    let chat_id = q.chat_id().unwrap();
+
+    match action {
+        PortfolioAction::Scan(contract) => {
+            bot.answer_callback_query(q.id).await?;
+            scan_contract(&bot, chat_id, &contract).await?;
+        }
+        PortfolioAction::Buy { contract, usd } => {
+            if callback_permission(&q).await < Permission::Trader {
+                bot.answer_callback_query(q.id)
+                    .text("You need the trader permission to buy")
+                    .show_alert(true)
+                    .await?;
+                return Ok(());
+            }
+
+            if !matches!(dialogue.get().await?, Some(State::Start) | None) {
+                bot.answer_callback_query(q.id)
+                    .text("Finish or /cancel the current command first")
+                    .show_alert(true)
+                    .await?;
+                return Ok(());
+            }
+
+            bot.answer_callback_query(q.id).await?;
+
+            // the same checks, wallet pick and confirmation as '/buy <contract> <usd>'
+            groups::set_prompt_owner(chat_id, Some(q.from.id)).await;
+            start_trade(
+                &bot,
+                &dialogue,
+                chat_id,
+                "buy",
+                &vec![contract, usd.to_string(), DEFAULT_SLIPPAGE.to_string()],
+            )
+            .await?;
+        }
+        PortfolioAction::Sell { contract, percent } => {
+            if callback_permission(&q).await < Permission::Trader {
+                bot.answer_callback_query(q.id)
+                    .text("You need the trader permission to sell")
+                    .show_alert(true)
+                    .await?;
+                return Ok(());
+            }
+
+            if !matches!(dialogue.get().await?, Some(State::Start) | None) {
+                bot.answer_callback_query(q.id)
+                    .text("Finish or /cancel the current command first")
+                    .show_alert(true)
+                    .await?;
+                return Ok(());
+            }
+
+            bot.answer_callback_query(q.id).await?;
+
+            // the balance may have changed since the portfolio was sent
+            let value_usd = match api::get_token_balances_with_prices().await {
+                Ok(owned_tokens) => owned_tokens
+                    .into_iter()
+                    .find(|token| token.contract.eq_ignore_ascii_case(&contract))
+                    .map(|token| token.value_usd)
+                    .unwrap_or(0.0),
+                Err(e) => {
+                    error!("get_token_balances_with_prices error: {}", e);
+                    bot.send_message(chat_id, "Something went wrong, please try again later")
+                        .await?;
+                    return Ok(());
+                }
+            };
+
+            if value_usd <= 0.0 {
+                bot.send_message(chat_id, "Trade cancelled: the token is no longer held!")
+                    .await?;
+                return Ok(());
+            }
+
+            groups::set_prompt_owner(chat_id, Some(q.from.id)).await;
+            let Some(tt) = route_trade(
+                &bot,
+                chat_id,
+                TradeToken {
+                    contract: Some(contract),
+                    amount: Some(value_usd * percent as f64 / 100.0),
+                    tokens: None,
+                    slippage: Some(DEFAULT_SLIPPAGE),
+                    order_type: OrderType::Sell,
+                    wallet: None,
+                    twap: None,
+                    route: None,
+                },
+            )
+            .await?
+            else {
+                return Ok(());
+            };
+            let tt = check_price_impact(&bot, chat_id, tt).await?;
+            confirm_trade_prompt(&bot, &dialogue, chat_id, tt).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// '/receive [eth]', the QR code encodes an EIP-681 uri for ETH_ADDRESS
+async fn receive_payment(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let wei = match args.first() {
+        Some(amount) => match payment::parse_eth_amount(amount) {
+            Some(wei) => Some(wei),
+            None => {
+                groups::reply(&bot, &msg, "The submitted eth amount is not valid!").await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    let address = wallet::eth_address();
+    let uri = payment::payment_uri(&address, wei);
+
+    match payment::qr_code_png(&uri) {
+        Ok(png) => {
+            let amount = match args.first() {
+                Some(amount) => format!("{} ETH", amount),
+                None => String::from("ETH"),
+            };
+            groups::reply_photo(&bot, &msg, InputFile::memory(png))
+                .caption(format!(
+                    "Send {} on Ethereum mainnet to:\n<code>{}</code>\n\n{}",
+                    amount,
+                    address,
+                    html::escape(&uri)
+                ))
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+        Err(e) => {
+            error!("qr_code_png error: {}", e);
+            groups::reply(&bot, &msg, format!("Payment link: {}", uri)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// '/send 0x... 0.05', the address can also be an EIP-681 link. the paired wallet signs the transfer
+async fn send_eth(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let Some(to) = args.first().and_then(|arg| input::parse_address(arg)) else {
+        groups::reply(&bot, &msg, "The submitted address is not valid!").await?;
+        return Ok(());
+    };
+    let Some(wei) = args
+        .get(1)
+        .and_then(|amount| payment::parse_eth_amount(amount))
+    else {
+        groups::reply(&bot, &msg, "The submitted eth amount is not valid!").await?;
+        return Ok(());
+    };
+    let Some(session) = walletconnect::session().await else {
+        groups::reply(&bot, &msg, format!("ETH was not sent: {}", NOT_SIGNED)).await?;
+        return Ok(());
+    };
+
+    let amount = args[1].to_string();
+    let transfer = trading::TransactionRequest {
+        to: to.clone(),
+        data: String::from("0x"),
+        value: wei,
+        description: format!("send {} ETH to {}", amount, to),
+    };
+    let chat_id = msg.chat.id;
+    tasks::spawn_job("send_eth", async move {
+        if let Some(hash) = send_with_walletconnect(&bot, chat_id, &session, &[transfer]).await {
+            let _ = bot
+                .send_message(
+                    chat_id,
+                    format!(
+                        "✅ Sent {} ETH from {} to {}: {}",
+                        amount,
+                        session.account,
+                        to,
+                        utils::tx_link(Chain::Ethereum, &hash, &hash)
+                    ),
+                )
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true)
+                .await;
+        }
+    });
+
+    Ok(())
+}
+
+// '/gasspent' or '/gasspent month', the contracts of the chosen period (a week by default) are listed
+async fn gas_spent(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let periods = [("day", 1), ("week", 7), ("month", 30)];
+    let selected = match args.first().map(|arg| arg.to_lowercase()) {
+        None => 1,
+        Some(arg) => match periods.iter().position(|(name, _)| *name == arg) {
+            Some(i) => i,
+            None => {
+                groups::reply(&bot, &msg, "The period can be day, week or month").await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+    let days: Vec<i64> = periods.iter().map(|(_, days)| *days).collect();
+    let (spent, eth_price) = tokio::join!(
+        api::get_gas_spent(wallet::trading_wallets(), &days),
+        api::get_eth_price()
+    );
+    bot.delete_message(msg.chat.id, loading_message_id).await?;
+
+    let spent = match spent {
+        Ok(spent) => spent,
+        Err(e) => {
+            error!("get_gas_spent error: {}", e);
+            groups::reply(&bot, &msg, "Something went wrong, please try again later").await?;
+            return Ok(());
+        }
+    };
+    let eth_price = eth_price.unwrap_or(0.0);
+
+    let mut message = String::from("⛽ Gas spent by your wallets\n");
+    for ((name, _), spent) in periods.iter().zip(&spent) {
+        message.push_str(&format!(
+            "\nLast {}: {:.4} ETH (${:.2}) in {} transactions",
+            name,
+            spent.eth,
+            spent.eth * eth_price,
+            spent.transactions
+        ));
+    }
+
+    let (name, _) = periods[selected];
+    let by_contract = &spent[selected].by_contract;
+    if !by_contract.is_empty() {
+        message.push_str(&format!("\n\nBy contract in the last {}:", name));
+    }
+    for contract in by_contract.iter().take(10) {
+        let target = if contract.contract.is_empty() {
+            String::from("Contract creations")
+        } else {
+            short_address(&contract.contract).await
+        };
+        message.push_str(&format!(
+            "\n{}: {:.4} ETH (${:.2}), {} tx",
+            target,
+            contract.eth,
+            contract.eth * eth_price,
+            contract.transactions
+        ));
+    }
+    if by_contract.len() > 10 {
+        message.push_str(&format!("\n...and {} more", by_contract.len() - 10));
+    }
+
+    groups::reply(&bot, &msg, message)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
+
+    Ok(())
+}
+
+async fn get_eth_gas(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let locale = SETTINGS.get(msg.chat.id).await.locale;
+    let actions = trading::gas_actions();
+    // a gas amount or a named action, every action at the standard tier otherwise
+    let selected = match args.first() {
+        None => None,
+        Some(arg) => match trading::parse_gas_argument(arg, &actions) {
+            Some(trading::GasSelection::Amount(gas)) => {
+                Some((format!("{} gas", locale.integer(gas)), gas))
+            }
+            Some(trading::GasSelection::Action(action)) => Some((
+                format!(
+                    "{} ({} gas)",
+                    action.description,
+                    locale.integer(action.gas)
+                ),
+                action.gas,
+            )),
+            None => {
+                groups::reply(
+                    &bot,
+                    &msg,
+                    format!(
+                        "Usage: /gas [gasAmount|{}]",
+                        actions
+                            .iter()
+                            .map(|action| action.name.as_str())
+                            .collect::<Vec<&str>>()
+                            .join("|")
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+
+    let (gas_price, eth_price) = match tokio::join!(api::get_eth_gas_wei(), api::get_eth_price()) {
+        (Ok(gas_price), Ok(eth_price)) => (gas_price, eth_price),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("get_eth_gas error: {}", e);
+            bot.delete_message(msg.chat.id, loading_message_id).await?;
+            groups::reply(
+                &bot,
+                &msg,
+                format!("Something went wrong, please try again later"),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut response = format!(
+        "Current eth gas is: {:.0} gwei\n\n",
+        gas_price as f64 / 10.0f64.powf(9.0)
+    );
+    match selected {
+        Some((label, gas)) => {
+            response.push_str(&format!("Fees for {}:", label));
+            for (tier, estimate) in trading::tier_estimates(gas, gas_price, eth_price) {
+                response.push_str(&format!(
+                    "\n{}: {:.5} ETH (${:.2})",
+                    tier,
+                    estimate.cost_eth(),
+                    estimate.cost_usd()
+                ));
+            }
+        }
+        None => {
+            // typical amounts, the trade confirmations simulate the actual transactions
+            response.push_str("Typical fees:");
+            for action in &actions {
+                let estimate = trading::GasEstimate {
+                    gas: action.gas,
+                    gas_price,
+                    eth_price,
+                    simulated: false,
+                };
+                response.push_str(&format!(
+                    "\n{}: ${:.2}",
+                    action.description,
+                    estimate.cost_usd()
+                ));
+            }
+        }
+    }
+
+    bot.delete_message(msg.chat.id, loading_message_id).await?;
+    groups::reply(&bot, &msg, response).await?;
+
+    Ok(())
+}
+
+async fn cancel(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
+    groups::reply(&bot, &msg, "Current command is cancelled").await?;
+    dialogue.exit().await?;
+    Ok(())
+}
+
+// handles a command from the history as if the member had just sent it, in place of msg
+async fn rerun_command(
+    bot: Bot,
+    me: Me,
+    storage: Arc<InMemStorage<State>>,
+    mut msg: Message,
+    from: User,
+    text: String,
+) -> HandlerResult {
+    if let MessageKind::Common(common) = &mut msg.kind {
+        common.from = Some(from);
+        common.media_kind = MediaKind::Text(MediaText {
+            text,
+            entities: vec![],
+        });
+    }
+    let update = Update {
+        id: 0,
+        kind: UpdateKind::Message(msg),
+    };
+
+    match schema()
+        .dispatch(dptree::deps![bot, me, update, storage])
+        .await
+    {
+        ControlFlow::Break(result) => result,
+        ControlFlow::Continue(_) => Ok(()),
+    }
+}
+
+async fn last_command(
+    bot: Bot,
+    me: Me,
+    storage: Arc<InMemStorage<State>>,
+    msg: Message,
+) -> HandlerResult {
+    let Some(from) = msg.from().cloned() else {
+        return Ok(());
+    };
+
+    match commandhistory::commands(msg.chat.id, from.id).await.first() {
+        Some(entry) => {
+            let text = entry.text.clone();
+            rerun_command(bot, me, storage, msg, from, text).await
+        }
+        None => {
+            groups::reply(&bot, &msg, "You haven't run any report or scan here yet").await?;
+            Ok(())
+        }
+    }
+}
+
+async fn command_history(bot: Bot, msg: Message) -> HandlerResult {
+    let Some(from) = msg.from() else {
+        return Ok(());
+    };
+    let commands = commandhistory::commands(msg.chat.id, from.id).await;
+
+    if commands.is_empty() {
+        groups::reply(&bot, &msg, "You haven't run any report or scan here yet").await?;
+        return Ok(());
+    }
+
+    let buttons: Vec<Vec<InlineKeyboardButton>> = commands
+        .iter()
+        .map(|entry| {
+            vec![InlineKeyboardButton::callback(
+                entry.text.chars().take(60).collect::<String>(),
+                format!("rerun:{}", entry.id),
+            )]
+        })
+        .collect();
+
+    groups::reply(
+        &bot,
+        &msg,
+        format!(
+            "🕘 Your last {} commands, tap one to run it again",
+            commands.len()
+        ),
+    )
+    .reply_markup(InlineKeyboardMarkup::new(buttons))
+    .await?;
+
+    Ok(())
+}
+
+async fn rerun_from_history(
+    bot: Bot,
+    me: Me,
+    storage: Arc<InMemStorage<State>>,
+    q: CallbackQuery,
+    id: u64,
+) -> HandlerResult {
+    let Some(msg) = q.message.clone() else {
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    };
+
+    let commands = commandhistory::commands(msg.chat.id, q.from.id).await;
+    let Some(entry) = commands.iter().find(|entry| entry.id == id) else {
+        bot.answer_callback_query(q.id)
+            .text("This command isn't in your history anymore, /historycmd lists it again")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    };
+    bot.answer_callback_query(q.id).await?;
+
+    let text = entry.text.clone();
+    rerun_command(bot, me, storage, msg, q.from, text).await
+}
+
+async fn help(bot: Bot, msg: Message) -> HandlerResult {
+    groups::reply(&bot, &msg, Command::descriptions().to_string()).await?;
+    Ok(())
+}
+
+async fn status(bot: Bot, msg: Message) -> HandlerResult {
+    let locale = SETTINGS.get(msg.chat.id).await.locale;
+    let mut message = String::from("Bot status:\n\nBackground tasks:");
+
+    for (name, status) in tasks::statuses().await {
+        let state = match status.state {
+            TaskState::Running => String::from("🟢 running"),
+            TaskState::Stopped => String::from("⚪️ stopped"),
+            TaskState::Failed(reason) => format!("🔴 failed ({})", reason),
+        };
+        let last_cycle = match status.last_cycle {
+            Some(last_cycle) => locale.time(last_cycle, "%H:%M:%S"),
+            None => String::from("-"),
+        };
+
+        message.push_str(&format!(
+            "\n{} {}\n   ⏰ ({}) started: {}, last cycle: {}, cycles: {}",
+            name,
+            state,
+            locale.zone(status.started_at),
+            locale.time(status.started_at, "%Y-%m-%d %H:%M:%S"),
+            last_cycle,
+            status.cycles
+        ));
+    }
+
+    if let Some(check) = balance::last_check().await {
+        message.push_str(&format!(
+            "\n\nTrading wallet: {:.4} ETH (minimum {} ETH, checked {} {}){}",
+            check.balance,
+            check.threshold,
+            locale.time(check.checked_at, "%H:%M:%S"),
+            locale.zone(check.checked_at),
+            if check.is_low() {
+                "\n🪫 Automatic buys are paused until it's topped up"
+            } else {
+                ""
+            }
+        ));
+    }
+
+    message.push_str("\n\nAPI usage (UTC day | month):");
+    for usage in api::get_api_usage().await {
+        let percent = |percent: Option<f64>| match percent {
+            Some(percent) if percent >= 80.0 => format!(" ⚠️ {:.1}%", percent),
+            Some(percent) => format!(" {:.1}%", percent),
+            None => String::new(),
+        };
+
+        message.push_str(&format!(
+            "\n{}: {}{} | {}{}",
+            usage.provider,
+            locale.integer(usage.daily.into()),
+            percent(usage.daily_percent()),
+            locale.integer(usage.monthly.into()),
+            percent(usage.monthly_percent())
+        ));
+    }
+
+    let outages = api::get_provider_outages().await;
+    if !outages.is_empty() {
+        message.push_str(&format!("\n🔴 Error budget spent: {}", outages.join(", ")));
+    }
+
+    // the busiest endpoints, all of them are in the REST API's /metrics
+    let mut endpoints = api::get_endpoint_stats().await;
+    endpoints.sort_by(|a, b| b.2.requests.cmp(&a.2.requests));
+    if !endpoints.is_empty() {
+        message.push_str("\n\nProvider latency (p50 | p95 since start):");
+    }
+    for (provider, endpoint, stats) in endpoints.iter().take(MAX_STATUS_ENDPOINTS) {
+        message.push_str(&format!(
+            "\n{} {}: {}ms | {}ms, {} requests{}",
+            provider,
+            endpoint,
+            stats.percentile_ms(50.0),
+            stats.percentile_ms(95.0),
+            locale.integer(stats.requests.into()),
+            match stats.errors() {
+                0 => String::new(),
+                errors => format!(", ⚠️ {} failed", locale.integer(errors.into())),
+            }
+        ));
+    }
+
+    groups::reply(&bot, &msg, message).await?;
+    Ok(())
+}
+
+async fn premium_required(bot: Bot, msg: Message) -> HandlerResult {
+    groups::reply(
+        &bot,
+        &msg,
+        "This command is only available for premium chats, ask the bot owner for access.",
+    )
+    .await?;
+    Ok(())
+}
+
+async fn permission_required(bot: Bot, msg: Message, cmd: Command) -> HandlerResult {
+    groups::reply(
+        &bot,
+        &msg,
+        format!(
+            "You need {} permission for this command, ask the bot owner for access.",
+            cmd.required_permission()
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn not_prompt_owner(bot: Bot, q: CallbackQuery) -> HandlerResult {
+    bot.answer_callback_query(q.id)
+        .text("Only the member who started this command can answer it")
+        .show_alert(true)
+        .await?;
+    Ok(())
+}
+
+// buttons that don't match the dialogue state belong to prompts that can no longer be answered
+async fn expired_prompt(bot: Bot, q: CallbackQuery) -> HandlerResult {
+    bot.answer_callback_query(q.id).await?;
+
+    if let Some(message) = q.message {
+        prompts::resolved(message.chat.id, message.id).await;
+        prompts::expire(&bot, message.chat.id, message.id).await?;
+    }
+    Ok(())
+}
+
+// '/permission <userId> <level>', or reply to a member's message with '/permission <level>'
+async fn set_member_permission(bot: Bot, msg: Message) -> HandlerResult {
+    if !is_owner(&msg) {
+        groups::reply(&bot, &msg, "Only the bot owner can set permissions!").await?;
+        return Ok(());
+    }
+
+    if !groups::is_group(&msg.chat) {
+        groups::reply(&bot, &msg, "Permissions can only be set in groups!").await?;
+        return Ok(());
+    }
+
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let replied_user = msg
+        .reply_to_message()
+        .and_then(|replied| replied.from())
+        .map(|user| (user.id, user.full_name()));
+
+    let (member, level) = match (replied_user, args.as_slice()) {
+        (Some(member), [level]) => (Some(member), level.parse::<Permission>()),
+        (_, [user_id, level]) => (
+            user_id
+                .parse::<u64>()
+                .ok()
+                .map(|id| (UserId(id), id.to_string())),
+            level.parse::<Permission>(),
+        ),
+        _ => (None, Err(())),
+    };
+
+    match (member, level) {
+        (Some((user_id, name)), Ok(level)) => {
+            groups::set_permission(msg.chat.id, user_id, level).await;
+            groups::reply(&bot, &msg, format!("{} is now a {}", name, level)).await?;
+        }
+        (None, _) => {
+            groups::reply(
+                &bot,
+                &msg,
+                "Reply to a member's message or submit a user id!",
+            )
+            .await?;
+        }
+        (_, Err(_)) => {
+            groups::reply(
+                &bot,
+                &msg,
+                "The submitted permission is not valid! (viewer, trader, admin)",
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+// settings changes apply to every chat sharing the profile
+async fn update_settings(chat_id: ChatId, update: impl Fn(&mut Settings)) {
+    let linked_chats = profiles::linked_chats(chat_id).await;
+    SETTINGS.update(&linked_chats, update).await;
+}
+
+// a newly linked chat takes over the watchlists and settings of the chat that issued the code
+async fn copy_profile(from: ChatId, to: ChatId) {
+    fn copy<T: Clone>(map: &mut HashMap<ChatId, T>, from: ChatId, to: ChatId) {
+        match map.get(&from).cloned() {
+            Some(value) => map.insert(to, value),
+            None => map.remove(&to),
+        };
+    }
+
+    SETTINGS.copy(from, to).await;
+    copy(&mut *WATCHED_WALLETS.lock().await, from, to);
+    copy(&mut *WATCHED_TOKENS.lock().await, from, to);
+}
+
+async fn redeem_link_code(bot: &Bot, msg: &Message, code: &str) -> HandlerResult {
+    let redeemed = profiles::PROFILES
+        .lock()
+        .await
+        .redeem(code, msg.chat.id, Utc::now());
+
+    match redeemed {
+        Ok(issuer) => {
+            copy_profile(issuer, msg.chat.id).await;
+            let linked_chats = profiles::linked_chats(msg.chat.id).await;
+            groups::reply(
+                bot,
+                msg,
+                format!(
+                    "🔗 Linked with {} other chat(s), watchlists and settings are now shared",
+                    linked_chats.len() - 1
+                ),
+            )
+            .await?;
+        }
+        Err(e) => {
+            groups::reply(bot, msg, format!("Link cancelled: {}!", e)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// '/link' issues a code, '/link <code>' in another chat joins the issuing chat's profile
+async fn link_chats(bot: Bot, msg: Message) -> HandlerResult {
+    let me = bot.get_me().await?;
+    let (_, args) = parse_command(msg.text().unwrap(), me.username()).unwrap();
+
+    match args.first() {
+        Some(&"off") => {
+            let message = if profiles::PROFILES.lock().await.unlink(msg.chat.id) {
+                "This chat is no longer linked, it keeps its current watchlists and settings"
+            } else {
+                "This chat isn't linked to another chat"
+            };
+            groups::reply(&bot, &msg, message).await?;
+        }
+        Some(code) => redeem_link_code(&bot, &msg, code).await?,
+        None => {
+            if !is_owner(&msg) {
+                groups::reply(&bot, &msg, "Only the bot owner can issue link codes!").await?;
+                return Ok(());
+            }
+
+            let code = profiles::PROFILES
+                .lock()
+                .await
+                .issue_code(msg.chat.id, Utc::now());
+            let linked_chats = profiles::linked_chats(msg.chat.id).await;
+
+            groups::reply(
+                &bot,
+                &msg,
+                format!(
+                    "🔗 Link code: {} (valid for {} minutes)\n\nSend /link {} in the other chat, or open\nPrivate chat: https://t.me/{}?start=link-{}\nGroup: https://t.me/{}?startgroup=link-{}\n\nLinked chats: {}",
+                    code,
+                    profiles::LINK_CODE_MINUTES,
+                    code,
+                    me.username(),
+                    code,
+                    me.username(),
+                    code,
+                    linked_chats
+                        .iter()
+                        .map(|chat| chat.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            )
+            .disable_web_page_preview(true)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+// deep links carry a link code or a contract to scan, a plain /start shows the commands
+async fn start(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    match args.first().and_then(|payload| deeplinks::parse(payload)) {
+        Some(DeepLink::Link(code)) => redeem_link_code(&bot, &msg, &code).await,
+        Some(DeepLink::Scan(contract)) => deep_link_scan(&bot, &msg, &contract).await,
+        None => help(bot, msg).await,
+    }
+}
+
+// anyone can click a scan link, chats without premium are rate limited
+async fn deep_link_scan(bot: &Bot, msg: &Message, contract: &str) -> HandlerResult {
+    if !is_owner(msg) && !has_premium(msg.chat.id).await {
+        if let Err(seconds) = deeplinks::allow_scan(msg.chat.id.0, Utc::now().timestamp()).await {
+            groups::reply(
+                bot,
+                msg,
+                format!(
+                    "Too many scans from links, try again in {} minute(s)",
+                    (seconds + 59) / 60
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    scan_contract(bot, msg.chat.id, contract).await
+}
+
+async fn grant_premium(bot: Bot, msg: Message) -> HandlerResult {
+    if !is_owner(&msg) {
+        groups::reply(&bot, &msg, "Only the bot owner can grant premium!").await?;
+        return Ok(());
+    }
+
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    // without arguments the current chat is granted
+    let chat_id = match args.first() {
+        Some(id) => match id.parse::<i64>() {
+            Ok(id) => ChatId(id),
+            Err(_) => {
+                groups::reply(&bot, &msg, "The submitted chat id is not valid!").await?;
+                return Ok(());
+            }
+        },
+        None => msg.chat.id,
+    };
+
+    let mut premium_chats = PREMIUM_CHATS.lock().await;
+
+    if premium_chats.remove(&chat_id) {
+        groups::reply(&bot, &msg, format!("Premium revoked for chat {}", chat_id)).await?;
+    } else {
+        premium_chats.insert(chat_id);
+        groups::reply(&bot, &msg, format!("Premium granted for chat {}", chat_id)).await?;
+    }
+
+    Ok(())
+}
+
+async fn set_log_level(bot: Bot, msg: Message) -> HandlerResult {
+    if !is_owner(&msg) {
+        groups::reply(&bot, &msg, "Only the bot owner can change log levels!").await?;
+        return Ok(());
+    }
+
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let response = match args.as_slice() {
+        [] => format!(
+            "Log levels: {}\nStreaming warnings to the owner chat: {}",
+            logging::current_filters(),
+            if logging::is_streaming() { "on" } else { "off" }
+        ),
+        ["reset"] => format!("Log levels reset to {}", logging::reset_filters()),
+        ["stream", toggle @ ("on" | "off")] => {
+            logging::set_streaming(*toggle == "on");
+            format!(
+                "Streaming warnings and errors to the owner chat turned {}",
+                toggle
+            )
+        }
+        ["stream", ..] => String::from("Usage: /loglevel stream on|off"),
+        filters => match logging::parse_filters(&filters.join(" ")) {
+            Ok(directives) => format!("Log levels set to {}", logging::set_filters(directives)),
+            Err(level) => format!(
+                "'{}' is not a log level, use off, error, warn, info, debug or trace",
+                level
+            ),
+        },
+    };
+
+    groups::reply(&bot, &msg, response).await?;
+
+    Ok(())
+}
+
+// '/debug' shows every section, '/debug <section>...' only those, '/debug json' sends all of it as a file
+async fn debug_state(bot: Bot, msg: Message) -> HandlerResult {
+    if !is_owner(&msg) {
+        groups::reply(
+            &bot,
+            &msg,
+            "Only the bot owner can inspect the bot's state!",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let snapshot = debug::snapshot().await;
+
+    if args.as_slice() == ["json"] {
+        let json = serde_json::to_vec_pretty(&snapshot)?;
+        groups::reply_document(
+            &bot,
+            &msg,
+            InputFile::memory(json).file_name(format!("debug-{}.json", snapshot.taken_at)),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let sections = if args.is_empty() {
+        vec![
+            DebugSection::Tokens,
+            DebugSection::Wallets,
+            DebugSection::Prompts,
+            DebugSection::Caches,
+        ]
+    } else {
+        match args
+            .iter()
+            .map(|arg| arg.to_lowercase().parse::<DebugSection>())
+            .collect::<Result<Vec<DebugSection>, ()>>()
+        {
+            Ok(sections) => sections,
+            Err(_) => {
+                groups::reply(
+                    &bot,
+                    &msg,
+                    "Usage: /debug [tokens|wallets|prompts|caches|json]",
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    };
+
+    let locale = SETTINGS.get(msg.chat.id).await.locale;
+    groups::reply(&bot, &msg, snapshot.render(&sections, &locale)).await?;
+    Ok(())
+}
+
+async fn backup_state(bot: Bot, msg: Message) -> HandlerResult {
+    if !is_owner(&msg) {
+        groups::reply(&bot, &msg, "Only the bot owner can manage backups!").await?;
+        return Ok(());
+    }
+
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let response = match args.as_slice() {
+        [] => match backup::list_backups().await {
+            Ok((destination, names)) if names.is_empty() => {
+                format!("There are no backups in {} yet", destination)
+            }
+            Ok((destination, names)) => format!(
+                "Backups in {}, newest last:\n{}",
+                destination,
+                names.join("\n")
+            ),
+            Err(e) => format!("Failed to list the backups: {}", e),
+        },
+        ["now"] => match backup::backup_now().await {
+            Ok(backup) => format!(
+                "Backed up {} state files to {} in {}",
+                backup.files.len(),
+                backup.name,
+                backup.destination
+            ),
+            Err(e) => format!("Backup failed: {}", e),
+        },
+        _ => String::from("Usage: /backup [now]"),
+    };
+
+    groups::reply(&bot, &msg, response).await?;
+
+    Ok(())
+}
+
+async fn known_lockers(bot: Bot, msg: Message) -> HandlerResult {
+    if !is_owner(&msg) {
+        groups::reply(&bot, &msg, "Only the bot owner can change known lockers!").await?;
+        return Ok(());
+    }
+
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let response = match args.as_slice() {
+        [] => {
+            let mut response = String::new();
+            for (title, kind) in [
+                ("Lockers", api::LabelKind::Locker),
+                ("Burn addresses", api::LabelKind::Burn),
+                ("Mixers", api::LabelKind::Mixer),
+                ("Flagged wallets", api::LabelKind::Flagged),
+            ] {
+                response.push_str(&format!("{}:\n", title));
+                for (name, address) in api::labels_of(kind) {
+                    response.push_str(&format!("{} {}\n", name, address));
+                }
+                response.push('\n');
+            }
+            response
+        }
+        [action @ ("add" | "burn" | "mixer" | "flag"), address, name @ ..]
+            if !name.is_empty() && utils::is_valid_eth_address(address) =>
+        {
+            let kind = match *action {
+                "add" => api::LabelKind::Locker,
+                "burn" => api::LabelKind::Burn,
+                "mixer" => api::LabelKind::Mixer,
+                _ => api::LabelKind::Flagged,
+            };
+            api::add_known_address(api::KnownAddress {
+                address: address.to_lowercase(),
+                name: name.join(" "),
+                kind,
+            });
+            format!("{} is now a known {} address", name.join(" "), kind)
+        }
+        ["remove", address] => {
+            if api::remove_known_address(address) {
+                format!("{} was removed", address)
+            } else {
+                format!(
+                    "{} wasn't added with /lockers, built-in addresses can't be removed",
+                    address
+                )
+            }
+        }
+        _ => String::from(
+            "Usage: /lockers [add <address> <name> | burn <address> <name> | mixer <address> <name> \
+             | flag <address> <name> | remove <address>]",
+        ),
+    };
+
+    groups::reply(&bot, &msg, response).await?;
+
+    Ok(())
+}
+
+// '/tradelock pin <pin>', '/tradelock totp', '/tradelock off <pin or code>'. The messages with a
+// PIN or code are deleted, so the answers aren't replies to them
+async fn set_trade_lock(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let chat_id = msg.chat.id;
+
+    if matches!(args.as_slice(), ["pin", ..] | ["off", ..]) {
+        if let Err(e) = bot.delete_message(chat_id, msg.id).await {
+            warn!("failed to delete the trade lock message: {}", e);
+        }
+    }
+
+    let already_locked =
+        "Trades are already locked, remove the lock first with /tradelock off <pin or code>";
+    let response = match args.as_slice() {
+        [] => match tradelock::challenge(chat_id).await {
+            tradelock::Challenge::Open => String::from(
+                "Trades are executed once they're confirmed with the button. /tradelock pin <pin> or /tradelock totp makes them need a code too",
+            ),
+            tradelock::Challenge::Pin => String::from("🔐 Trades need the chat's PIN"),
+            tradelock::Challenge::Totp => {
+                String::from("🔐 Trades need a code from the authenticator app")
+            }
+            tradelock::Challenge::LockedOut(until) => trade_lockout_message(chat_id, until).await,
+        },
+        ["pin", pin] if tradelock::is_valid_pin(pin) => {
+            if tradelock::set_pin(chat_id, pin).await {
+                String::from("🔐 Trades now need the PIN, it's asked for after every confirmation")
+            } else {
+                String::from(already_locked)
+            }
+        }
+        ["pin", _] => String::from("The PIN has to be 4 to 12 digits"),
+        ["totp"] => match tradelock::set_totp(chat_id).await {
+            Some(secret) => {
+                let uri = tradelock::totp_uri(&secret, &chat_id.to_string());
+                let caption = format!(
+                    "Scan the code with your authenticator app or enter the secret:\n<code>{}</code>\n\n🔐 Trades now need a code from the app. Delete this message once the app shows the codes, anyone who reads it can make them",
+                    secret
+                );
+
+                match payment::qr_code_png(&uri) {
+                    Ok(png) => {
+                        groups::reply_photo(&bot, &msg, InputFile::memory(png))
+                            .caption(caption)
+                            .parse_mode(ParseMode::Html)
+                            .await?;
+                    }
+                    Err(e) => {
+                        error!("qr_code_png error: {}", e);
+                        groups::reply(&bot, &msg, caption)
+                            .parse_mode(ParseMode::Html)
+                            .await?;
+                    }
+                }
+                return Ok(());
+            }
+            None => String::from(already_locked),
+        },
+        ["off", code] => match tradelock::challenge(chat_id).await {
+            tradelock::Challenge::Open => String::from("Trades aren't locked"),
+            _ => match tradelock::remove(chat_id, code).await {
+                tradelock::Verdict::Accepted => String::from(
+                    "🔓 Trades are executed once they're confirmed with the button again",
+                ),
+                tradelock::Verdict::Rejected { attempts_left } => format!(
+                    "❌ Wrong code, {} more attempts before trades are locked",
+                    attempts_left
+                ),
+                tradelock::Verdict::LockedOut(until) => {
+                    trade_lockout_message(chat_id, until).await
+                }
+            },
+        },
+        _ => String::from("Usage: /tradelock [pin <pin> | totp | off <pin or code>]"),
+    };
+    bot.send_message(chat_id, response).await?;
+
+    Ok(())
+}
+
+async fn walletconnect_pairing(bot: Bot, msg: Message) -> HandlerResult {
+    if !is_owner(&msg) {
+        groups::reply(&bot, &msg, "Only the bot owner can pair a wallet!").await?;
+        return Ok(());
+    }
+
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    match args.as_slice() {
+        [] => {}
+        ["off"] => {
+            let response = match walletconnect::disconnect().await {
+                Some(session) => format!("{} is disconnected", session.account),
+                None => String::from("No wallet is paired"),
+            };
+            groups::reply(&bot, &msg, response).await?;
+            return Ok(());
+        }
+        _ => {
+            groups::reply(&bot, &msg, "Usage: /walletconnect [off]").await?;
+            return Ok(());
+        }
+    }
+
+    if walletconnect::project_id().is_none() {
+        groups::reply(
+            &bot,
+            &msg,
+            "Set WALLETCONNECT_PROJECT_ID to pair a wallet with WalletConnect!",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let pairing = match walletconnect::pair().await {
+        Ok(pairing) => pairing,
+        Err(e) => {
+            error!("walletconnect pair error: {}", e);
+            groups::reply(&bot, &msg, "The WalletConnect relay is not available!").await?;
+            return Ok(());
+        }
+    };
+
+    let caption = format!(
+        "Scan the code with your wallet or paste the link into it:\n<code>{}</code>\n\nTrades from the approved account are sent to the wallet for signing.",
+        html::escape(&pairing.uri)
+    );
+    match payment::qr_code_png(&pairing.uri) {
+        Ok(png) => {
+            groups::reply_photo(&bot, &msg, InputFile::memory(png))
+                .caption(caption)
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+        Err(e) => {
+            error!("qr_code_png error: {}", e);
+            groups::reply(&bot, &msg, caption)
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+    }
+
+    let chat_id = msg.chat.id;
+    tasks::spawn_job("walletconnect_pairing", async move {
+        let response = match pairing.approve().await {
+            Ok(session) => format!(
+                "✅ {} is paired, its trades are signed in your wallet",
+                session.account
+            ),
+            Err(e) => format!("Pairing failed: {}", e),
+        };
+        let _ = bot.send_message(chat_id, response).await;
+    });
+
+    Ok(())
+}
+
+// '/cleanup [days]' lists watches without alerts for that many days, '/cleanup prune [days]' removes them
+async fn cleanup_watches(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let (prune, days) = match args.as_slice() {
+        ["prune", days @ ..] => (true, days.first()),
+        days => (false, days.first()),
+    };
+    let Some(min_inactive) = days
+        .map_or(Ok(7), |days| days.parse::<i64>())
+        .ok()
+        .filter(|days| *days > 0)
+        .and_then(Duration::try_days)
+    else {
+        groups::reply(&bot, &msg, "Usage: /cleanup [days] | prune [days]").await?;
+        return Ok(());
+    };
+
+    let mut inactive = vec![];
+    for kind in [WatchKind::Wallet, WatchKind::Token] {
+        inactive.extend(
+            expiry::inactive_for(kind, min_inactive)
+                .await
+                .into_iter()
+                .filter(|watch| watch.chat_id == msg.chat.id),
+        );
+    }
+
+    let mut message = if inactive.is_empty() {
+        format!(
+            "🧹 Every watch had an alert in the last {} days",
+            min_inactive.num_days()
+        )
+    } else if prune {
+        format!(
+            "🧹 Removed watches without alerts for {}+ days:\n",
+            min_inactive.num_days()
+        )
+    } else {
+        format!(
+            "🧹 Watches without alerts for {}+ days:\n",
+            min_inactive.num_days()
+        )
+    };
+    for watch in &inactive {
+        if prune {
+            expiry::remove(watch.chat_id, watch.kind, &watch.entry).await;
+        }
+        message.push_str(&format!(
+            "\n{} {}: {} days",
+            if watch.kind == WatchKind::Wallet {
+                "👛"
+            } else {
+                "🪙"
+            },
+            watch.entry,
+            watch.inactive.num_days()
+        ));
+    }
+
+    // filters, labels and routes left behind by wallets that were removed with /watch
+    let orphans = expiry::prune_orphans().await;
+    if orphans > 0 {
+        message.push_str(&format!(
+            "\n\nRemoved {} wallet filters, labels and routes of unwatched wallets",
+            orphans
+        ));
+    }
+    if !prune && !inactive.is_empty() {
+        message.push_str(&format!(
+            "\n\nSend /cleanup prune {} to remove them",
+            min_inactive.num_days()
+        ));
+    }
+    for kind in [WatchKind::Wallet, WatchKind::Token] {
+        if let Some(ttl) = kind.ttl() {
+            message.push_str(&format!(
+                "\n{} watches expire after {} days without alerts",
+                kind,
+                ttl.num_days()
+            ));
+        }
+    }
+
+    groups::reply(&bot, &msg, message).await?;
+
+    Ok(())
+}
+
+async fn renew_watch(bot: Bot, q: CallbackQuery, renewal: (WatchKind, String)) -> HandlerResult {
+    let chat_id = q.chat_id().unwrap();
+    let (kind, entry) = renewal;
+
+    if callback_permission(&q).await < Permission::Admin {
+        bot.answer_callback_query(q.id)
+            .text("You need the admin permission to change watches")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    bot.answer_callback_query(q.id).await?;
+    if let Some(message) = q.message {
+        bot.edit_message_reply_markup(chat_id, message.id).await?;
+    }
+
+    let response = if expiry::renew(chat_id, kind, &entry).await {
+        format!("🔁 {} is watched again", entry)
+    } else {
+        format!("🔁 {} is still watched, its expiry was reset", entry)
+    };
+    bot.send_message(chat_id, response).await?;
+
+    Ok(())
+}
+
+// groups share plenty of photos, there only the ones with an address are answered
+async fn read_qr_photo(bot: Bot, msg: Message, file_id: String) -> HandlerResult {
+    let mut jpeg = vec![];
+    let file = bot.get_file(file_id).await?;
+    bot.download_file(&file.path, &mut jpeg).await?;
+
+    // decoding a photo takes a while, it's kept off the async workers
+    let decoded = tokio::task::spawn_blocking(move || input::address_from_photo(&jpeg)).await;
+    let address = match decoded {
+        Ok(Ok(address)) => address,
+        Ok(Err(e)) => {
+            error!("address_from_photo error: {}", e);
+            None
+        }
+        Err(e) => {
+            error!("address_from_photo panicked: {}", e);
+            None
+        }
+    };
+
+    match address {
+        Some(address) => {
+            let mut row = vec![InlineKeyboardButton::callback(
+                "Scan",
+                PortfolioAction::Scan(address.clone()).callback_data(),
+            )];
+            // adds the wallet to the watchlist like a renewed watch, watching is premium
+            if has_premium(msg.chat.id).await {
+                row.push(InlineKeyboardButton::callback(
+                    "Watch",
+                    WatchKind::Wallet.renew_callback_data(&address),
+                ));
+            }
+
+            let mut text = format!("📷 Address in the QR code:\n<code>{}</code>", address);
+            if walletconnect::session().await.is_some() {
+                text.push_str(&format!(
+                    "\n\nSend ETH to it with /send {} &lt;eth&gt;",
+                    address
+                ));
+            }
+
+            groups::reply(&bot, &msg, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(InlineKeyboardMarkup::new(vec![row]))
+                .await?;
+        }
+        None if msg.chat.is_private() => {
+            groups::reply(
+                &bot,
+                &msg,
+                "No Ethereum address QR code was found in the photo",
+            )
+            .await?;
+        }
+        None => (),
+    }
+
+    Ok(())
+}
+
+// the label the chat imported the wallet with comes before the address' own
+async fn watched_wallet_label(chat_id: ChatId, wallet: &str) -> Option<String> {
+    match walletimport::label(chat_id, wallet).await {
+        Some(label) => Some(label),
+        None => api::get_address_label(wallet).await,
+    }
+}
+
+async fn launch_snipes(bot: Bot, msg: Message) -> HandlerResult {
+    if !is_owner(&msg) {
+        groups::reply(&bot, &msg, "Only the bot owner can register launches!").await?;
+        return Ok(());
+    }
+
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let opted_in = SETTINGS.get(msg.chat.id).await.same_block_snipes;
+    let mut snipes = launches::LAUNCH_SNIPES.lock().await;
+
+    let response = match args.as_slice() {
+        [] => {
+            snipes.retain(|snipe| !snipe.is_expired(Utc::now()));
+            let mut response = String::from("Registered launches:\n");
+            for snipe in snipes.iter() {
+                response.push_str(&format!(
+                    "{} ${} ({}% slippage)\n",
+                    snipe.contract, snipe.amount_usd, snipe.slippage
+                ));
+            }
+            response
+        }
+        ["remove", contract] => {
+            let registered = snipes.len();
+            snipes.retain(|snipe| snipe.contract != contract.to_lowercase());
+            if snipes.len() < registered {
+                format!("{} was removed", contract)
+            } else {
+                format!("{} isn't registered", contract)
+            }
+        }
+        [contract, usd, slippage @ ..]
+            if utils::is_valid_eth_address(contract) && slippage.len() <= 1 =>
+        {
+            let amount_usd = usd.parse::<f64>().ok().filter(|usd| *usd > 0.0);
+            let slippage = match slippage.first() {
+                Some(slippage) => slippage.trim_end_matches('%').parse::<f32>().ok(),
+                None => Some(DEFAULT_SLIPPAGE),
+            };
+
+            match (amount_usd, slippage) {
+                (Some(amount_usd), Some(slippage)) => {
+                    let snipe = launches::LaunchSnipe {
+                        contract: contract.to_lowercase(),
+                        amount_usd,
+                        slippage,
+                        registered_at: Utc::now(),
+                    };
+
+                    match launches::register(&mut snipes, snipe) {
+                        Ok(()) if wallet::launch_snipe_key().is_none() => format!(
+                            "{} is registered, set LAUNCH_SNIPE_KEY to the key that signs the buy",
+                            contract
+                        ),
+                        Ok(()) if opted_in => format!(
+                            "{} is registered, its liquidity add is backrun with a ${} buy",
+                            contract, amount_usd
+                        ),
+                        Ok(()) => format!(
+                            "{} is registered, turn on same-block launch snipes in /settings to buy it",
+                            contract
+                        ),
+                        Err(e) => format!("Launch not registered: {}", e),
+                    }
+                }
+                _ => String::from("Usage: /launchsnipe <token> <usd> [slippage%]"),
+            }
+        }
+        _ => String::from("Usage: /launchsnipe [<token> <usd> [slippage%] | remove <token>]"),
+    };
+    drop(snipes);
+
+    groups::reply(&bot, &msg, response).await?;
+
+    Ok(())
+}
+
+async fn invalid_state(bot: Bot, msg: Message) -> HandlerResult {
+    groups::reply(&bot, &msg, "Type /help to see availabe commands.").await?;
+    Ok(())
+}
+
+pub async fn watched_wallet_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    wallet: &String,
+    transaction: &api::EtherscanTokenTransaction,
+    activity: Option<WalletActivity>,
+) -> HandlerResult {
+    let epoch_time = DateTime::UNIX_EPOCH
+        + Duration::try_seconds(transaction.time_stamp.parse::<i64>().unwrap()).unwrap();
+    let datetime = DateTime::<Utc>::from(epoch_time);
+    let locale = SETTINGS.get(chat_id).await.locale;
+    let timestamp = locale.time(datetime, "%Y-%m-%d %H:%M:%S");
+
+    symbols::remember(
+        chat_id,
+        &transaction.token_symbol,
+        &transaction.token_name,
+        &transaction.contract_address,
+    )
+    .await;
+
+    let wallet_label = match watched_wallet_label(chat_id, wallet).await {
+        Some(label) => format!("{} ({})", html::escape(&label), wallet),
+        None => wallet.to_owned(),
+    };
+    let tx_link = utils::tx_link(Chain::Ethereum, &transaction.hash, "Tx");
+
+    let message = match templates::get(chat_id, TemplateKind::Wallet).await {
+        Some(template) => {
+            // the usd value needs a price request, so it's only fetched when the template shows it
+            let usd_value = if templates::uses(&template, "usd_value") {
+                locale.number(api::transfer_value_usd(transaction).await, 0)
+            } else {
+                String::new()
+            };
+
+            templates::render(
+                &html::escape(&template),
+                &[
+                    ("wallet", wallet_label),
+                    (
+                        "activity",
+                        activity.map(|a| a.to_string()).unwrap_or_default(),
+                    ),
+                    ("token_name", html::escape(&transaction.token_name)),
+                    ("token_symbol", html::escape(&transaction.token_symbol)),
+                    ("contract", transaction.contract_address.clone()),
+                    ("usd_value", usd_value),
+                    ("time", timestamp),
+                    ("tx_link", tx_link),
+                    (
+                        "links",
+                        contract_links(chat_id, &transaction.contract_address).await,
+                    ),
+                ],
+            )
+        }
+        // first buys are what copy traders follow, so they stand out from the wallet's other trades
+        None if activity == Some(WalletActivity::NewTokenBuy) => format!(
+            "🆕🆕🆕 NEW BUY 🆕🆕🆕\n\n🔎 {}\n\n💎 {} ({})\n🏷 First buy, the wallet never held this token\n⏰ ({}) {}\n{} | {}",
+            wallet_label,
+            transaction.token_name,
+            transaction.token_symbol,
+            locale.zone(datetime),
+            timestamp,
+            tx_link,
+            contract_links(chat_id, &transaction.contract_address).await
+        ),
+        None => format!(
+            "🚨🚨🚨 New transaction 🚨🚨🚨\n\n🔎 {}\n\n{}💎 {} ({})\n⏰ ({}) {}\n{} | {}",
+            wallet_label,
+            match activity {
+                Some(activity) => format!("🏷 {}\n", activity),
+                None => String::new(),
+            },
+            transaction.token_name,
+            transaction.token_symbol,
+            locale.zone(datetime),
+            timestamp,
+            tx_link,
+            contract_links(chat_id, &transaction.contract_address).await
+        ),
+    };
+
+    alerts::send_alert(bot, chat_id, AlertCategory::Watch, Some(wallet), message)
+        .await
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
+
+    Ok(())
+}
+
+// routed by category, the buys come from several wallets that might each have their own route
+pub async fn convergent_buy_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    buy: &api::ConvergentBuy,
+) -> HandlerResult {
+    symbols::remember(chat_id, &buy.token_symbol, &buy.token_name, &buy.contract).await;
+
+    let mut wallets = vec![];
+    for wallet_buy in &buy.buys {
+        let wallet_label = match watched_wallet_label(chat_id, &wallet_buy.wallet).await {
+            Some(label) => format!("{} ({})", html::escape(&label), wallet_buy.wallet),
+            None => wallet_buy.wallet.clone(),
+        };
+        wallets.push(format!(
+            "🔎 {} | {}",
+            wallet_label,
+            utils::tx_link(Chain::Ethereum, &wallet_buy.transaction.hash, "Tx")
+        ));
+    }
+
+    alerts::send_alert(
+        bot,
+        chat_id,
+        AlertCategory::Watch,
+        None,
+        format!(
+            "🔥🔥🔥 {} wallets bought {} 🔥🔥🔥\n\n💎 {} ({})\n📄 {}\n\n{}\n\n{}",
+            buy.buys.len(),
+            html::escape(&buy.token_symbol),
+            html::escape(&buy.token_name),
+            html::escape(&buy.token_symbol),
+            buy.contract,
+            wallets.join("\n"),
+            contract_links(chat_id, &buy.contract).await
+        ),
+    )
+    .await
+    .parse_mode(ParseMode::Html)
+    .disable_web_page_preview(true)
+    .await?;
+
+    Ok(())
+}
+
+// sent before the alerts of a wallet that had more new transactions than could be fetched
+pub async fn truncated_transactions_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    wallet: &str,
+    shown: usize,
+) -> HandlerResult {
+    let wallet_label = match watched_wallet_label(chat_id, wallet).await {
+        Some(label) => format!("{} ({})", html::escape(&label), wallet),
+        None => wallet.to_owned(),
+    };
+
+    alerts::send_alert(
+        bot,
+        chat_id,
+        AlertCategory::Watch,
+        Some(wallet),
+        format!(
+            "⚠️ Too many transactions since the last check for {}, showing the latest {}. Older ones were skipped, check the wallet on {}",
+            wallet_label,
+            shown,
+            html::link(&utils::explorer_address_url(Chain::Ethereum, wallet), "Etherscan")
+        ),
+    )
+    .await
+    .parse_mode(ParseMode::Html)
+    .disable_web_page_preview(true)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn watched_wallet_deployment_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    wallet: &String,
+    transaction: &api::EtherscanNormalTransaction,
+) -> HandlerResult {
+    let epoch_time = DateTime::UNIX_EPOCH
+        + Duration::try_seconds(transaction.time_stamp.parse::<i64>().unwrap_or(0)).unwrap();
+    let datetime = DateTime::<Utc>::from(epoch_time);
+    let locale = SETTINGS.get(chat_id).await.locale;
+
+    alerts::send_alert(
+        bot,
+        chat_id,
+        AlertCategory::Watch,
+        Some(wallet),
+        format!(
+            "🚨🚨🚨 New contract deployed 🚨🚨🚨\n\n🔎 {}\n\n📄 {}\n⏰ ({}) {}\n{} | {}",
+            match watched_wallet_label(chat_id, wallet).await {
+                Some(label) => format!("{} ({})", html::escape(&label), wallet),
+                None => wallet.to_owned(),
+            },
+            transaction.contract_address,
+            locale.zone(datetime),
+            locale.time(datetime, "%Y-%m-%d %H:%M:%S"),
+            utils::tx_link(Chain::Ethereum, &transaction.hash, "Tx"),
+            contract_links(chat_id, &transaction.contract_address).await
+        ),
+    )
+    .await
+    .parse_mode(ParseMode::Html)
+    .disable_web_page_preview(true)
+    .await?;
+
+    Ok(())
+}
+
+// sent with a notification regardless of the chat's priorities or quiet hours, a drainer doesn't wait
+pub async fn approval_warning_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    wallet: &str,
+    approval: &api::Approval,
+    own: bool,
+) -> HandlerResult {
+    let wallet_label = match watched_wallet_label(chat_id, wallet).await {
+        Some(label) => format!("{} ({})", html::escape(&label), wallet),
+        None => wallet.to_owned(),
+    };
+    let token = match api::get_address_label(&approval.token).await {
+        Some(label) => format!("{} ({})", html::escape(&label), approval.token),
+        None => approval.token.clone(),
+    };
+    let amount = match approval.amount {
+        Some(amount) => SETTINGS.get(chat_id).await.locale.integer(amount),
+        None => String::from("unlimited"),
+    };
+
+    alerts::send_alert(
+        bot,
+        chat_id,
+        AlertCategory::Watch,
+        Some(wallet),
+        format!(
+            "⚠️⚠️⚠️ Suspicious approval ⚠️⚠️⚠️
+
+🔎 {}{}
+
+✍️ {} of {} (raw amount)
+📄 {}
+🎯 Spender: {}
+
+The spender is not a known router and not a verified contract, this is how wallet drainers get access to tokens.
+{} | <a href=\"https://revoke.cash/address/{}?chainId=1\">Revoke</a>",
+            wallet_label,
+            if own { " (your wallet)" } else { "" },
+            approval.kind,
+            amount,
+            token,
+            approval.spender,
+            utils::tx_link(Chain::Ethereum, &approval.hash, "Tx"),
+            wallet
+        ),
+    )
+    .await
+    .parse_mode(ParseMode::Html)
+    .disable_web_page_preview(true)
+    .disable_notification(false)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn watch_expired_notification(bot: &Bot, watch: &expiry::InactiveWatch) -> HandlerResult {
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        "🔁 Renew",
+        watch.kind.renew_callback_data(&watch.entry),
+    )]]);
+
+    alerts::send_alert(
+        bot,
+        watch.chat_id,
+        AlertCategory::Watch,
+        (watch.kind == WatchKind::Wallet).then_some(watch.entry.as_str()),
+        format!(
+            "⌛ The {} {} is no longer watched, there were no alerts for {} days",
+            watch.kind,
+            watch.entry,
+            watch.inactive.num_days()
+        ),
+    )
+    .await
+    .reply_markup(keyboard)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn launch_backrun_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    snipe: &launches::LaunchSnipe,
+    add: &launches::LiquidityAdd,
+    backrun: &launches::Backrun,
+) -> HandlerResult {
+    let outcome = match backrun {
+        launches::Backrun::Sent { buy, value, blocks } => format!(
+            "🎯 Backrun bundle sent for block {}: ${} ({:.4} ETH) with {}% slippage\n⏳ {}",
+            blocks
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<String>>()
+                .join(" and "),
+            snipe.amount_usd,
+            *value as f64 / 10.0f64.powf(18.0),
+            snipe.slippage,
+            utils::tx_link(Chain::Ethereum, &buy.hash, "Buy")
+        ),
+        launches::Backrun::Skipped(reason) => format!("⏭ Nothing was sent: {}", reason),
+    };
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "🚀 Liquidity add spotted in the mempool
+
+📄 {}
+💧 {:.2} ETH | {}
+
+{}",
+            add.contract,
+            add.eth_amount as f64 / 10.0f64.powf(18.0),
+            utils::tx_link(Chain::Ethereum, &add.hash, "Tx"),
+            outcome
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .disable_web_page_preview(true)
+    .await?;
+
+    if let launches::Backrun::Sent { buy, .. } = backrun {
+        tasks::spawn_job(
+            "track_launch_snipe",
+            track_launch_snipe(bot.clone(), chat_id, snipe.clone(), buy.hash.clone()),
+        );
+    }
+
+    Ok(())
+}
+
+// a bundle the builders didn't include never lands, the buy is only recorded once it's mined
+async fn track_launch_snipe(bot: Bot, chat_id: ChatId, snipe: launches::LaunchSnipe, hash: String) {
+    let response = if is_mined_successfully(&hash).await {
+        let trade_token = TradeToken {
+            contract: Some(snipe.contract.clone()),
+            amount: Some(snipe.amount_usd),
+            tokens: None,
+            slippage: Some(snipe.slippage),
+            order_type: OrderType::Buy,
+            wallet: Some(wallet::eth_address().to_lowercase()),
+            twap: None,
+            route: None,
+        };
+        record_trade(&trade_token, &hash).await;
+
+        format!(
+            "✅ The launch snipe of {} landed, {}",
+            snipe.contract,
+            utils::tx_link(Chain::Ethereum, &hash, "Buy")
+        )
+    } else {
+        format!(
+            "❌ The launch snipe of {} didn't land, the bundle wasn't included or the buy reverted",
+            snipe.contract
+        )
+    };
+
+    if let Err(e) = bot
+        .send_message(chat_id, response)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await
+    {
+        error!("failed to send the launch snipe result: {}", e);
+    }
+}
+
+// '/watchfilter <wallet> <swaps|newtokens|deploys|min=usd|token=contractOrSymbol|held ...|off>'
+async fn set_wallet_filter(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let Some(wallet) = args
+        .first()
+        .filter(|wallet| utils::is_valid_eth_address(wallet))
+        .map(|wallet| wallet.to_lowercase())
+    else {
+        groups::reply(
+            &bot,
+            &msg,
+            "Usage: /watchfilter <walletAddress> \
+             <swaps|newtokens|deploys|min=usd|token=contractOrSymbol|held ...|off>",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let mut filter = WalletFilter::default();
+    for flag in &args[1..] {
+        match flag.to_lowercase().as_str() {
+            "off" => filter = WalletFilter::default(),
+            "swaps" => filter.swaps = true,
+            "newtokens" => filter.new_tokens = true,
+            "deploys" => filter.deployments = true,
+            "held" => filter.held_tokens = true,
+            flag if flag.starts_with("token=") => {
+                let token = flag.strip_prefix("token=").unwrap_or_default();
+                match symbols::resolve(msg.chat.id, token).await {
+                    Resolution::Address(address) => {
+                        let address = address.to_lowercase();
+                        if !filter.tokens.contains(&address) {
+                            filter.tokens.push(address);
+                        }
+                    }
+                    _ => {
+                        groups::reply(
+                            &bot,
+                            &msg,
+                            format!(
+                                "Watch filter cancelled: {} is not a valid contract or known symbol!",
+                                token
+                            ),
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                }
+            }
+            flag => match flag.strip_prefix("min=").map(|min| min.parse::<f64>()) {
+                Some(Ok(min)) if min > 0.0 => filter.min_transfer_usd = Some(min),
+                _ => {
+                    groups::reply(
+                        &bot,
+                        &msg,
+                        format!("Watch filter cancelled: {} is not a valid filter!", flag),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            },
+        }
+    }
+
+    let mut filters = alerts::WALLET_FILTERS.lock().await;
+    let chat_filters = filters.entry(msg.chat.id).or_default();
+    if filter.is_empty() {
+        chat_filters.remove(&wallet);
+    } else {
+        chat_filters.insert(wallet.clone(), filter.clone());
+    }
+    drop(filters);
+
+    groups::reply(&bot, &msg, format!("Alerts for {}: {}", wallet, filter)).await?;
+
+    Ok(())
+}
+
+fn set_criterion(criterion: &mut Option<f64>, value: &str) -> bool {
+    match value.parse::<f64>() {
+        Ok(value) if value > 0.0 => {
+            *criterion = Some(value);
+            true
+        }
+        _ => false,
+    }
+}
+
+// '/sniperfilter minliq=5 minliq=10000$ maxfdv=1000000 minbuyers=20 minvol=2', liquidity and volume without a '$' are in eth
+async fn set_sniper_filter(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    // '/sniperfilter launchpad ...' sets the criteria of the launchpad feeds
+    let (launchpad, args) = match args.split_first() {
+        Some((first, rest)) if first.eq_ignore_ascii_case("launchpad") => (true, rest.to_vec()),
+        _ => (false, args),
+    };
+
+    if args.is_empty() {
+        groups::reply(
+            &bot,
+            &msg,
+            "Usage: /sniperfilter [launchpad] <minliq=eth|minliq=usd$|maxfdv=usd|minbuyers=count|minvol=eth|minvol=usd$ ...|off>",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut filter = SniperFilter::default();
+    for criterion in &args {
+        let criterion = criterion.to_lowercase();
+        let valid = match criterion.split_once('=') {
+            Some(("minliq", value)) if value.ends_with('$') => {
+                set_criterion(&mut filter.min_liquidity_usd, value.trim_end_matches('$'))
+            }
+            Some(("minliq", value)) => set_criterion(&mut filter.min_liquidity_eth, value),
+            Some(("maxfdv", value)) => {
+                set_criterion(&mut filter.max_fdv_usd, value.trim_end_matches('$'))
+            }
+            Some(("minbuyers", value)) => match value.parse::<usize>() {
+                Ok(min) if min > 0 => {
+                    filter.min_buyers = Some(min);
+                    true
+                }
+                _ => false,
+            },
+            Some(("minvol", value)) if value.ends_with('$') => {
+                set_criterion(&mut filter.min_buy_volume_usd, value.trim_end_matches('$'))
+            }
+            Some(("minvol", value)) => set_criterion(&mut filter.min_buy_volume_eth, value),
+            None if criterion == "off" => {
+                filter = SniperFilter::default();
+                true
+            }
+            _ => false,
+        };
+
+        if !valid {
+            groups::reply(
+                &bot,
+                &msg,
+                format!(
+                    "Sniper filter cancelled: {} is not a valid criterion!",
+                    criterion
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    if launchpad && (filter.needs_buyers() || filter.max_fdv_usd.is_some()) {
+        groups::reply(
+            &bot,
+            &msg,
+            "Sniper filter cancelled: launchpad tokens can only be filtered by liquidity (minliq)!",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if launchpad {
+        update_settings(msg.chat.id, |settings| settings.launchpad_filter = filter).await;
+        groups::reply(&bot, &msg, format!("Launchpad token alerts: {}", filter)).await?;
+    } else {
+        update_settings(msg.chat.id, |settings| settings.sniper_filter = filter).await;
+        groups::reply(&bot, &msg, format!("New token alerts: {}", filter)).await?;
+    }
+
+    Ok(())
+}
+
+// '/tokenmeta <token> symbol=X decimals=9 ...' merges into the override, '/tokenmeta <token> off' drops it
+async fn set_token_metadata(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let chat_id = msg.chat.id;
+
+    if args.is_empty() || args == ["list"] {
+        let overrides = metadata::overrides(chat_id).await;
+        let message = if overrides.is_empty() {
+            String::from("There are no token metadata overrides")
+        } else {
+            overrides.iter().fold(
+                String::from("Token metadata overrides:\n"),
+                |message, (contract, metadata)| format!("{}\n{}: {}", message, contract, metadata),
+            )
+        };
+        groups::reply(&bot, &msg, message).await?;
+        return Ok(());
+    }
+
+    let contract = match symbols::resolve(chat_id, args[0]).await {
+        Resolution::Address(address) => address,
+        _ => {
+            groups::reply(
+                &bot,
+                &msg,
+                "Token metadata cancelled: submitted contract is incorrect!",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if args[1..] == ["off"] {
+        let message = if metadata::remove_override(chat_id, &contract).await {
+            format!("Metadata override of {} removed", contract)
+        } else {
+            format!("There is no metadata override for {}", contract)
+        };
+        groups::reply(&bot, &msg, message).await?;
+        return Ok(());
+    }
+
+    let mut token_metadata = TokenMetadata::default();
+    for field in &args[1..] {
+        let valid = match field.split_once('=') {
+            Some(("symbol", value)) if !value.is_empty() => {
+                token_metadata.symbol = Some(value.trim_start_matches('$').to_string());
+                true
+            }
+            Some(("name", value)) if !value.is_empty() => {
+                token_metadata.name = Some(value.replace('_', " "));
+                true
+            }
+            Some(("decimals", value)) => {
+                token_metadata.decimals = value.parse::<u8>().ok();
+                token_metadata.decimals.is_some()
+            }
+            Some(("logo", value)) if reqwest::Url::parse(value).is_ok() => {
+                token_metadata.logo = Some(value.to_string());
+                true
+            }
+            _ => false,
+        };
+
+        if !valid {
+            groups::reply(
+                &bot,
+                &msg,
+                format!("Token metadata cancelled: {} is not a valid field!", field),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    if token_metadata.is_empty() {
+        groups::reply(
+            &bot,
+            &msg,
+            "Usage: /tokenmeta <token> [symbol=X] [name=Long_Name] [decimals=18] [logo=url] | <token> off | list",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let token_metadata = metadata::set_override(chat_id, &contract, token_metadata).await;
+    groups::reply(
+        &bot,
+        &msg,
+        format!("Metadata of {} set to: {}", contract, token_metadata),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// '/tokenlist <url>' replaces the chat's imported list, '/tokenlist off' drops it
+async fn import_token_list(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let chat_id = msg.chat.id;
+
+    let message = match args.as_slice() {
+        [] => match metadata::imported_list(chat_id).await {
+            Some(list) => format!(
+                "Imported token list: {} ({} tokens)\n{}",
+                list.name,
+                list.token_count(),
+                list.url
+            ),
+            None => String::from("There is no imported token list"),
+        },
+        ["off"] => match metadata::remove_list(chat_id).await {
+            Some(list) => format!("Token list {} removed", list.name),
+            None => String::from("There is no imported token list"),
+        },
+        [url] if reqwest::Url::parse(url).is_ok() => {
+            let loading_message_id = loading_message(&bot, chat_id).await;
+            let token_list = api::get_token_list(url).await;
+            bot.delete_message(chat_id, loading_message_id).await?;
+
+            match token_list {
+                Ok(token_list) => {
+                    let name = token_list.name.clone();
+                    let list = metadata::import_list(
+                        chat_id,
+                        name,
+                        url.to_string(),
+                        token_list.ethereum_tokens(),
+                    )
+                    .await;
+                    format!(
+                        "Imported {} Ethereum tokens from {}, they take precedence over the data providers",
+                        list.token_count(),
+                        list.name
+                    )
+                }
+                Err(e) => {
+                    warn!("get_token_list error: {}", e);
+                    String::from("The token list could not be downloaded, or it's not in the Uniswap token list format")
+                }
+            }
+        }
+        _ => String::from("Usage: /tokenlist <url> | off"),
+    };
+
+    groups::reply(&bot, &msg, message).await?;
+
+    Ok(())
+}
+
+// '/template <kind> <text>', the text keeps its line breaks, '/template <kind> reset' goes back to the default
+async fn set_template(bot: Bot, msg: Message) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let mut parts = msg.text().unwrap().splitn(3, char::is_whitespace);
+    let kind = parts.nth(1).map(|kind| kind.to_lowercase());
+    let text = parts.next().unwrap_or_default().trim();
+
+    let Some(kind) = kind.and_then(|kind| kind.parse::<TemplateKind>().ok()) else {
+        let kinds: Vec<String> = TemplateKind::all().iter().map(|k| k.to_string()).collect();
+        groups::reply(
+            &bot,
+            &msg,
+            format!(
+                "Usage: /template <{}> [text|reset]\nPlaceholders are written as {{token_symbol}}",
+                kinds.join("|")
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let placeholders = kind
+        .placeholders()
+        .iter()
+        .map(|p| format!("{{{}}}", p))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    let message = match text {
+        "" => match templates::get(chat_id, kind).await {
+            Some(template) => format!(
+                "The {} template is:\n\n{}\n\nPlaceholders: {}",
+                kind, template, placeholders
+            ),
+            None => format!(
+                "The {} template is the default one\n\nPlaceholders: {}",
+                kind, placeholders
+            ),
+        },
+        "reset" => {
+            if templates::reset(chat_id, kind).await {
+                format!("The {} template was reset to the default", kind)
+            } else {
+                format!("The {} template is already the default", kind)
+            }
+        }
+        _ if text.chars().count() > templates::MAX_TEMPLATE_LENGTH => format!(
+            "Template cancelled: templates can be at most {} characters long!",
+            templates::MAX_TEMPLATE_LENGTH
+        ),
+        _ => {
+            let unknown = templates::unknown_placeholders(kind, text);
+            if unknown.is_empty() {
+                templates::set(chat_id, kind, text.to_owned()).await;
+                format!("The {} template was updated", kind)
+            } else {
+                format!(
+                    "Template cancelled: unknown placeholders {}\n\nPlaceholders: {}",
+                    unknown.join(", "),
+                    placeholders
+                )
+            }
+        }
+    };
+
+    // sent without a parse mode, templates are shown exactly as they were written
+    groups::reply(&bot, &msg, message).await?;
+
+    Ok(())
+}
+
+async fn set_chart_site(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    if let Some(days) = args.first().and_then(|days| days.parse::<i64>().ok()) {
+        return portfolio_chart(&bot, &msg, days).await;
+    }
+
+    let message = match args.first() {
+        Some(site) => match site.to_lowercase().parse::<ChartSite>() {
+            Ok(chart_site) => {
+                update_settings(msg.chat.id, |settings| settings.chart_site = chart_site).await;
+                format!("Chart links now open {}", chart_site)
+            }
+            Err(_) => String::from("Usage: /chart <7|30> | <dexscreener|dextools|defined>"),
+        },
+        None => format!("Chart links open {}", chart_site(msg.chat.id).await),
+    };
+
+    groups::reply(&bot, &msg, message).await?;
+
+    Ok(())
+}
+
+// '/chart 7' or '/chart 30', the snapshots are taken every hour by the portfolio_history task
+async fn portfolio_chart(bot: &Bot, msg: &Message, days: i64) -> HandlerResult {
+    if days != 7 && days != 30 {
+        groups::reply(bot, msg, "The chart can show 7 or 30 days").await?;
+        return Ok(());
+    }
+
+    let snapshots = history::since(days).await;
+    let (Some(first), Some(last)) = (snapshots.first(), snapshots.last()) else {
+        groups::reply(
+            bot,
+            msg,
+            "There are no portfolio snapshots yet, the first one is taken when the bot starts",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let locale = SETTINGS.get(msg.chat.id).await.locale;
+    let values: Vec<f64> = snapshots
+        .iter()
+        .map(|snapshot| snapshot.value_usd)
+        .collect();
+    let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let low = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let change = |age: Duration| match history::change_percent(&snapshots, age) {
+        Some(change) => format!("{:+.2}%", change),
+        None => String::from("n/a"),
+    };
+
+    let message = format!(
+        "📈 Portfolio value, last {} days
+
+{}
+
+💰 ${} ➡️ ${}
+📊 24h: {} | {}d: {}
+🔝 High: ${} | 🔻 Low: ${}",
+        days,
+        history::sparkline(&values, 24),
+        locale.number(first.value_usd, 2),
+        locale.number(last.value_usd, 2),
+        change(Duration::try_days(1).unwrap()),
+        days,
+        change(Duration::try_days(days).unwrap()),
+        locale.number(high, 2),
+        locale.number(low, 2),
+    );
+
+    groups::reply(bot, msg, message).await?;
+
+    Ok(())
+}
+
+// contracts with longer dead blocks than the delay are waited out anyway
+async fn set_launch_delay(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let Some(blocks) = args.first().and_then(|blocks| blocks.parse::<u64>().ok()) else {
+        groups::reply(&bot, &msg, "Usage: /launchdelay <blocks>, 0 turns it off").await?;
+        return Ok(());
+    };
+
+    if blocks > MAX_LAUNCH_DELAY_BLOCKS {
+        groups::reply(
+            &bot,
+            &msg,
+            format!(
+                "Launch delay cancelled: the delay can be at most {} blocks!",
+                MAX_LAUNCH_DELAY_BLOCKS
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    update_settings(msg.chat.id, |settings| {
+        settings.launch_delay_blocks = blocks
+    })
+    .await;
+
+    groups::reply(
+        &bot,
+        &msg,
+        format!(
+            "New token alerts wait {} blocks (~{}s) after liquidity is added",
+            blocks,
+            blocks * api::SECONDS_PER_BLOCK
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+fn parse_impact_percent(percent: &str) -> Option<f64> {
+    percent
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .ok()
+        .filter(|percent| *percent > 0.0 && *percent < 100.0)
+}
+
+// '/maximpact 2' warns about bigger trades, '/maximpact 2 cap' shrinks them to the limit
+async fn set_max_price_impact(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let limit = match args.as_slice() {
+        [] => {
+            let limit = SETTINGS.get(msg.chat.id).await.price_impact_limit;
+            groups::reply(&bot, &msg, format!("Price impact: {}", limit)).await?;
+            return Ok(());
+        }
+        ["off"] => Some(PriceImpactLimit::Off),
+        [percent] => parse_impact_percent(percent).map(PriceImpactLimit::Warn),
+        [percent, "cap"] => parse_impact_percent(percent).map(PriceImpactLimit::Cap),
+        _ => None,
+    };
+    let Some(limit) = limit else {
+        groups::reply(&bot, &msg, "Usage: /maximpact <percent> [cap] | off").await?;
+        return Ok(());
+    };
+
+    update_settings(msg.chat.id, |settings| settings.price_impact_limit = limit).await;
+
+    groups::reply(&bot, &msg, format!("Price impact: {}", limit)).await?;
+
+    Ok(())
+}
+
+// '/proceeds usdc' swaps the eth of every sell to USDC right after it
+async fn set_sell_proceeds(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let message = match args.first() {
+        Some(proceeds) => match proceeds.to_lowercase().parse::<trading::SellProceeds>() {
+            Ok(proceeds) => {
+                update_settings(msg.chat.id, |settings| settings.sell_proceeds = proceeds).await;
+                format!("Sell proceeds: {}", proceeds)
+            }
+            Err(_) => String::from("Usage: /proceeds <eth|weth|usdc>"),
+        },
+        None => format!(
+            "Sell proceeds: {}",
+            SETTINGS.get(msg.chat.id).await.sell_proceeds
+        ),
+    };
+
+    groups::reply(&bot, &msg, message).await?;
+
+    Ok(())
+}
+
+// '/locale Europe/Berlin eu', either part can be set alone
+async fn set_locale(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let mut locale = SETTINGS.get(msg.chat.id).await.locale;
+    if let Some(invalid) = args.iter().find(|arg| !locale.set(arg)) {
+        groups::reply(
+            &bot,
+            &msg,
+            format!(
+                "Locale cancelled: {} is neither an IANA time zone like Europe/Berlin nor en, eu, fr or ch!",
+                invalid
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if !args.is_empty() {
+        update_settings(msg.chat.id, |settings| settings.locale = locale).await;
+    }
+    groups::reply(&bot, &msg, format!("Locale: {}", locale)).await?;
+
+    Ok(())
+}
+
+// '/watchtoken <tokens>' replaces the watchlist, '/watchtoken off' clears it
+async fn watch_tokens(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let mut contracts: Vec<String> = vec![];
+    if !(args.len() == 1 && args[0] == "off") {
+        for token in &args {
+            if let Resolution::Address(address) = symbols::resolve(msg.chat.id, token).await {
+                contracts.push(address);
+            }
+        }
+
+        if contracts.is_empty() {
+            groups::reply(
+                &bot,
+                &msg,
+                format!("Watch tokens cancelled: submitted tokens are incorrect"),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    let watched = WATCHED_TOKENS
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .cloned()
+        .unwrap_or_default();
+    let removed = removed_entries(&watched, &contracts);
+
+    if removed.is_empty() {
+        groups::reply(&bot, &msg, set_watched_tokens(msg.chat.id, contracts).await).await?;
+    } else {
+        ask_confirmation(
+            &bot,
+            &dialogue,
+            &msg,
+            format!(
+                "⚠️ These tokens will no longer be watched:\n{}",
+                removed.join("\n")
+            ),
+            ConfirmableAction::WatchTokens(contracts),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn set_watched_tokens(chat_id: ChatId, contracts: Vec<String>) -> String {
+    let linked_chats = profiles::linked_chats(chat_id).await;
+
+    if contracts.is_empty() {
+        let mut watched_tokens = WATCHED_TOKENS.lock().await;
+        for linked_chat in linked_chats {
+            watched_tokens.remove(&linked_chat);
+        }
+        return String::from("Tokens are no longer watched");
+    }
+
+    let mut watched_tokens = WATCHED_TOKENS.lock().await;
+    for linked_chat in &linked_chats {
+        watched_tokens.insert(*linked_chat, contracts.clone());
+    }
+    drop(watched_tokens);
+    for linked_chat in linked_chats {
+        for contract in &contracts {
+            expiry::touch(linked_chat, WatchKind::Token, contract).await;
+        }
+    }
+
+    let mut message = String::from("Currently watched tokens:\n");
+    for (i, contract) in contracts.iter().enumerate() {
+        match api::get_token_info(contract.clone()).await {
+            Ok(token_info) => message.push_str(&format!(
+                "\n{}. {} ({})",
+                i + 1,
+                contract,
+                token_info.symbol
+            )),
+            Err(_) => message.push_str(&format!("\n{}. {}", i + 1, contract)),
+        }
+    }
+    message.push_str(
+        "\n\nYou will be notified about new large holders, big sells by top holders, creator wallet movements, tax changes, ownership transfers, big liquidity moves, top holder concentration changes, proxy implementation upgrades and mints or burns changing the total supply",
+    );
+
+    message
+}
+
+// adds the token to the watchlist of the chat and its linked chats, false if it was already watched
+async fn add_watched_token(chat_id: ChatId, contract: &str) -> bool {
+    let linked_chats = profiles::linked_chats(chat_id).await;
+
+    let mut watched_tokens = WATCHED_TOKENS.lock().await;
+    let mut contracts = watched_tokens.get(&chat_id).cloned().unwrap_or_default();
+    if contracts
+        .iter()
+        .any(|token| token.eq_ignore_ascii_case(contract))
+    {
+        return false;
+    }
+    contracts.push(contract.to_owned());
+    for linked_chat in &linked_chats {
+        watched_tokens.insert(*linked_chat, contracts.clone());
+    }
+    drop(watched_tokens);
+
+    for linked_chat in linked_chats {
+        expiry::touch(linked_chat, WatchKind::Token, contract).await;
+    }
+    true
+}
+
+// the Monitor button of scan cards, the same watch /watchtoken sets up
+async fn monitor_token(bot: Bot, q: CallbackQuery, contract: String) -> HandlerResult {
+    let Some(chat_id) = q.message.as_ref().map(|message| message.chat.id) else {
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    };
+
+    let refusal = if callback_permission(&q).await < Permission::Admin {
+        Some("You need the admin permission to monitor tokens")
+    } else if !has_premium(chat_id).await {
+        Some("Monitoring tokens is a premium feature")
+    } else {
+        None
+    };
+    if let Some(refusal) = refusal {
+        bot.answer_callback_query(q.id)
+            .text(refusal)
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    if !add_watched_token(chat_id, &contract).await {
+        bot.answer_callback_query(q.id)
+            .text("This token is already monitored")
+            .await?;
+        return Ok(());
+    }
+    bot.answer_callback_query(q.id).await?;
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "👁 Monitoring {}\n\nYou will be notified when its taxes change, its ownership is transferred, its liquidity moves more than {}% or its top 10 holders gain or lose 5% of the supply, along with the other /watchtoken alerts",
+            token_symbol(chat_id, &contract).await,
+            api::liquidity_change_percent()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn watched_token_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    contract: &str,
+    activity: &api::TokenActivity,
+) -> HandlerResult {
+    let symbol = match api::get_token_info(contract.to_owned()).await {
+        Ok(token_info) => html::escape(&token_info.symbol),
+        Err(_) => short_address(contract).await,
+    };
+    let locale = SETTINGS.get(chat_id).await.locale;
+
+    let details = match activity {
+        api::TokenActivity::NewLargeHolder { holder, value_usd } => format!(
+            "🐳 New top holder\n👤 {}\n💰 ${}",
+            short_address(holder).await,
+            locale.number(*value_usd, 0)
+        ),
+        api::TokenActivity::TopHolderSell(transfer) => format!(
+            "🔴 Top holder moved tokens\n{} ➡️ {}\n💰 ${} ({} tokens)\n{}",
+            short_address(&transfer.from).await,
+            short_address(&transfer.to).await,
+            locale.number(transfer.value_usd, 0),
+            locale.number(transfer.amount, 0),
+            utils::tx_link(Chain::Ethereum, &transfer.hash, "Tx")
+        ),
+        api::TokenActivity::CreatorMovement(transfer) => format!(
+            "👨‍💻 Creator wallet activity\n{} ➡️ {}\n💰 ${} ({} tokens)\n{}",
+            short_address(&transfer.from).await,
+            short_address(&transfer.to).await,
+            locale.number(transfer.value_usd, 0),
+            locale.number(transfer.amount, 0),
+            utils::tx_link(Chain::Ethereum, &transfer.hash, "Tx")
+        ),
+        api::TokenActivity::ClusterBuy(cluster) => {
+            let mut wallets = String::new();
+            for wallet in &cluster.wallets {
+                wallets.push_str(&format!("\n👤 {}", short_address(wallet).await));
+            }
+
+            format!(
+                "🕸 Cluster buy\n{} fresh wallets funded by {} bought within the hour{}",
+                cluster.wallets.len(),
+                short_address(&cluster.funder).await,
+                wallets
+            )
+        }
+        api::TokenActivity::Change(change) => html::escape(&change.to_string()),
+        api::TokenActivity::Liquidity(api::LiquidityMove::Removed { pool, eth }) => format!(
+            "💧 Liquidity removed (possible rug)\n{:.2} ETH left pool {}, none of the token's other pools received it",
+            eth,
+            short_address(pool).await
+        ),
+        api::TokenActivity::Liquidity(api::LiquidityMove::Migrated {
+            from,
+            to,
+            eth,
+            follow_up,
+        }) => format!(
+            "🔀 Possible liquidity migration{}\n{:.2} ETH left pool {} while a new pool received about as much, check who owns the new pool's liquidity\n🏊 <code>{}</code>",
+            if *follow_up {
+                ", the liquidity removed earlier may have reappeared"
+            } else {
+                ""
+            },
+            eth,
+            short_address(from).await,
+            to
+        ),
+    };
+
+    alerts::send_alert(
+        bot,
+        chat_id,
+        AlertCategory::Watch,
+        Some(contract),
+        format!(
+            "🚨🚨🚨 Token activity 🚨🚨🚨\n\n💎 {}\n📄 {}\n\n{}\n{}",
+            symbol,
+            contract,
+            details,
+            contract_links(chat_id, contract).await
+        ),
+    )
+    .await
+    .parse_mode(ParseMode::Html)
+    .disable_web_page_preview(true)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn proxy_upgrade_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    contract: &str,
+    previous: &str,
+    implementation: Option<&str>,
+) -> HandlerResult {
+    let symbol = match api::get_token_info(contract.to_owned()).await {
+        Ok(token_info) => html::escape(&token_info.symbol),
+        Err(_) => short_address(contract).await,
+    };
+
+    alerts::send_alert(
+        bot,
+        chat_id,
+        AlertCategory::Watch,
+        Some(contract),
+        format!(
+            "🚨🚨🚨 Proxy upgraded 🚨🚨🚨\n\n💎 {}\n📄 {}\n\n⚠️ The token's implementation changed, its logic (taxes, transfers, blacklists) might be completely different now\n\n🔙 Old: {}\n🆕 New: {}\n{}",
+            symbol,
+            contract,
+            html::link(
+                    &format!("{}#code", utils::explorer_address_url(Chain::Ethereum, previous)),
+                    previous
+                ),
+            match implementation {
+                Some(implementation) => html::link(
+                    &format!("{}#code", utils::explorer_address_url(Chain::Ethereum, implementation)),
+                    implementation
+                ),
+                None => String::from("none (implementation removed)"),
+            },
+            contract_links(chat_id, contract).await
+        ),
+    )
+    .await
+    .parse_mode(ParseMode::Html)
+    .disable_web_page_preview(true)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn supply_change_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    contract: &str,
+    change: api::SupplyChange,
+    total_supply: u128,
+) -> HandlerResult {
+    let (symbol, decimals) = match api::get_token_info(contract.to_owned()).await {
+        Ok(token_info) => (html::escape(&token_info.symbol), token_info.decimals),
+        Err(_) => (short_address(contract).await, 18),
+    };
+    let locale = SETTINGS.get(chat_id).await.locale;
+    let amount = |raw: u128| locale.number(raw as f64 / 10f64.powi(decimals as i32), 0);
+
+    let text = match change {
+        api::SupplyChange::Mint { amount: minted, percent } => format!(
+            "🚨🚨🚨 Tokens minted 🚨🚨🚨\n\n💎 {}\n📄 {}\n\n⚠️ {} new tokens were minted (+{:.2}% of the supply), every holder got diluted and the minter can dump them\n\n🪙 Total supply: {}\n{}",
+            symbol,
+            contract,
+            amount(minted),
+            percent,
+            amount(total_supply),
+            contract_links(chat_id, contract).await
+        ),
+        api::SupplyChange::Burn { amount: burned, percent } => format!(
+            "🔥🔥🔥 Tokens burned 🔥🔥🔥\n\n💎 {}\n📄 {}\n\n{} tokens were burned (-{:.2}% of the supply)\n\n🪙 Total supply: {}\n{}",
+            symbol,
+            contract,
+            amount(burned),
+            percent,
+            amount(total_supply),
+            contract_links(chat_id, contract).await
+        ),
+    };
+
+    alerts::send_alert(bot, chat_id, AlertCategory::Watch, Some(contract), text)
+        .await
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn accumulation_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    contract: &str,
+    oldest: &api::ConcentrationSnapshot,
+    latest: &api::ConcentrationSnapshot,
+) -> HandlerResult {
+    let symbol = match api::get_token_info(contract.to_owned()).await {
+        Ok(token_info) => html::escape(&token_info.symbol),
+        Err(_) => short_address(contract).await,
+    };
+    let percent = |percent: Option<f64>| match percent {
+        Some(percent) => format!("{:.2}%", percent),
+        None => String::from("unknown"),
+    };
+
+    alerts::send_alert(
+        bot,
+        chat_id,
+        AlertCategory::Watch,
+        Some(contract),
+        format!(
+            "🚨🚨🚨 Stealth accumulation 🚨🚨🚨\n\n💎 {}\n📄 {}\n\nChanges in the last {}:\n👨‍💻 Deployer: {} ➡️ {}\n👥 Top 10: {:.1}% ➡️ {:.1}%\n{}",
+            symbol,
+            contract,
+            format_age((latest.taken_at - oldest.taken_at).num_seconds()),
+            percent(oldest.deployer_percent),
+            percent(latest.deployer_percent),
+            oldest.concentration.top10_percent,
+            latest.concentration.top10_percent,
+            contract_links(chat_id, contract).await
+        ),
+    )
+    .await
+    .parse_mode(ParseMode::Html)
+    .disable_web_page_preview(true)
+    .await?;
+
+    Ok(())
+}
+
+async fn inspect_address(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let address = args.first().map(|a| a.to_lowercase()).unwrap_or_default();
+
+    if !utils::is_valid_eth_address(&address) {
+        groups::reply(&bot, &msg, "Please provide a valid address").await?;
+        return Ok(());
+    }
+
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+    let inspection = api::inspect_address(address.clone()).await;
+    let inspection = match inspection {
+        Ok(inspection) => inspection,
+        Err(e) => {
+            bot.delete_message(msg.chat.id, loading_message_id).await?;
+            error!("inspect_address error: {}", e);
+            groups::reply(&bot, &msg, "Something went wrong, please try again later").await?;
+            return Ok(());
+        }
+    };
+
+    let mut message = format!(
+        "🔍 {}\n🏷 {}\n",
+        html::link(
+            &utils::explorer_address_url(Chain::Ethereum, &address),
+            &address
+        ),
+        short_address(&address).await
+    );
+
+    if !inspection.is_contract {
+        let (balance, nonce) = tokio::join!(
+            api::get_eth_balance(address.clone()),
+            api::get_transaction_count(address.clone(), false)
+        );
+        message.push_str("\n👤 Wallet (EOA)");
+        if let Some(delegate) = &inspection.delegated_to {
+            message.push_str(&format!(
+                "\n🔗 Delegates its code to {} (EIP-7702)",
+                short_address(delegate).await
+            ));
+        }
+        if let Ok(balance) = balance {
+            message.push_str(&format!("\n💰 {:.4} ETH", balance));
+        }
+        if let Ok(nonce) = nonce {
+            message.push_str(&format!("\n📤 {} transactions sent", nonce));
+        }
+        message.push_str(&format!("\n\n👉 Follow its trades with /watch {}", address));
+    } else {
+        let kind = inspection.kind.unwrap_or(api::ContractKind::Unknown);
+        message.push_str(&format!("\n📄 Contract: {}", kind));
+
+        if let Some(implementation) = &inspection.proxy_implementation {
+            message.push_str(&format!(
+                "\n🪞 Proxy of {}",
+                html::link(
+                    &format!(
+                        "{}#code",
+                        utils::explorer_address_url(Chain::Ethereum, implementation)
+                    ),
+                    &short_address(implementation).await
+                )
+            ));
+        } else if inspection.is_proxy {
+            message.push_str("\n🪞 Proxy, the implementation couldn't be found");
+        }
+
+        match &inspection.verified_name {
+            Some(name) => message.push_str(&format!("\n✅ Verified as {}", html::escape(name))),
+            None => message.push_str("\n⚠️ Unverified source code"),
+        }
+
+        if let Some(created_at) = inspection.created_at {
+            let created_at = DateTime::UNIX_EPOCH + Duration::try_seconds(created_at).unwrap();
+            let locale = SETTINGS.get(msg.chat.id).await.locale;
+            message.push_str(&format!(
+                "\n📅 Created {} {}",
+                locale.time(created_at, "%Y-%m-%d %H:%M:%S"),
+                locale.zone(created_at)
+            ));
+        }
+        if let Some(creator) = &inspection.creator {
+            let creation = match &inspection.creation_tx {
+                Some(tx_hash) => format!(" in {}", utils::tx_link(Chain::Ethereum, tx_hash, "tx")),
+                None => String::new(),
+            };
+            message.push_str(&format!(
+                "\n👷 Creator: {}{}",
+                short_address(creator).await,
+                creation
+            ));
+        }
+
+        // honeypot.is resolves a pair to its token, so both can be scanned
+        if matches!(
+            kind,
+            api::ContractKind::Erc20 | api::ContractKind::UniswapV2Pair
+        ) {
+            message.push_str(&format!("\n\n👉 Check the token with /scan {}", address));
+        }
+    }
+
+    bot.delete_message(msg.chat.id, loading_message_id).await?;
+    groups::reply(&bot, &msg, message)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
+
+    Ok(())
+}
+
+// the token's creator and where its eth came from, None for tokens without a known creator
+async fn creator_funding(contract: &str) -> Result<Option<api::FundingPath>, reqwest::Error> {
+    let creations = api::get_contract_creator_and_tx_hash(vec![contract.to_owned()]).await?;
+    let Some(creation) = creations.first() else {
+        return Ok(None);
+    };
+
+    api::trace_funding(
+        creation.contract_creator.clone(),
+        api::funding_trace_depth(),
+    )
+    .await
+    .map(Some)
+}
+
+fn funding_origin(path: &api::FundingPath) -> String {
+    match &path.origin {
+        api::FundingOrigin::Flagged(name) if path.hops.is_empty() => {
+            format!("❌ Created by a flagged wallet ({})", html::escape(name))
+        }
+        api::FundingOrigin::Flagged(name) => {
+            format!("❌ Funded by a flagged wallet ({})", html::escape(name))
+        }
+        api::FundingOrigin::Mixer(name) => {
+            format!("❌ Funded through a mixer ({})", html::escape(name))
+        }
+        api::FundingOrigin::Exchange(name) => format!("🏦 Withdrawn from {}", html::escape(name)),
+        api::FundingOrigin::Unfunded if path.hops.is_empty() => {
+            String::from("❔ The creator never received ETH")
+        }
+        api::FundingOrigin::Unfunded => String::from("❔ The first funder never received ETH"),
+        api::FundingOrigin::DepthReached => format!(
+            "❔ No mixer, exchange or flagged wallet within {} hops",
+            path.hops.len()
+        ),
+    }
+}
+
+async fn funding_summary(contract: &str) -> Option<String> {
+    let path = match creator_funding(contract).await {
+        Ok(path) => path?,
+        Err(e) => {
+            error!("creator_funding error: {}", e);
+            return None;
+        }
+    };
+
+    let mut chain = vec![short_address(&path.wallet).await];
+    for hop in &path.hops {
+        chain.push(format!(
+            "{} ({:.2} ETH)",
+            short_address(&hop.funder).await,
+            hop.eth
+        ));
+    }
+
+    Some(format!(
+        "🕵️ Creator funding: {}\n{}",
+        chain.join(" ⬅️ "),
+        funding_origin(&path)
+    ))
+}
+
+// '/creator <contractOrSymbol>', every hop of the creator's funding with its amount and date
+async fn creator_funding_report(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let contract = match args.first() {
+        Some(token) => match symbols::resolve(msg.chat.id, token).await {
+            Resolution::Address(address) => address,
+            _ => {
+                groups::reply(
+                    &bot,
+                    &msg,
+                    format!("{} is not a valid contract or known symbol!", token),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+        None => {
+            groups::reply(&bot, &msg, "Usage: /creator <contractOrSymbol>").await?;
+            return Ok(());
+        }
+    };
+
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+    let path = creator_funding(&contract).await;
+    bot.delete_message(msg.chat.id, loading_message_id).await?;
+
+    let path = match path {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            groups::reply(&bot, &msg, "The creator of this contract couldn't be found").await?;
+            return Ok(());
+        }
+        Err(e) => {
+            error!("creator_funding error: {}", e);
+            groups::reply(&bot, &msg, "Something went wrong, please try again later").await?;
+            return Ok(());
+        }
+    };
+
+    let locale = SETTINGS.get(msg.chat.id).await.locale;
+    let mut message = format!(
+        "👷 Creator of {}: {}\n\n💸 Funding path:",
+        short_address(&contract).await,
+        html::link(
+            &utils::explorer_address_url(Chain::Ethereum, &path.wallet),
+            &short_address(&path.wallet).await
+        )
+    );
+    for hop in &path.hops {
+        let funded_at = DateTime::UNIX_EPOCH + Duration::try_seconds(hop.time_stamp).unwrap();
+        message.push_str(&format!(
+            "\n{} ⬅️ {:.4} ETH from {} on {}",
+            short_address(&hop.wallet).await,
+            hop.eth,
+            html::link(
+                &utils::explorer_address_url(Chain::Ethereum, &hop.funder),
+                &short_address(&hop.funder).await
+            ),
+            locale.time(funded_at, "%Y-%m-%d")
+        ));
+    }
+    message.push_str(&format!("\n\n{}", funding_origin(&path)));
+
+    groups::reply(&bot, &msg, message)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
+
+    Ok(())
+}
+
+async fn scan_token(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
+    let contract = parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username())
+        .unwrap()
+        .1
+        .join("");
+
+    match symbols::resolve(msg.chat.id, contract.trim()).await {
+        Resolution::Address(address) => scan_contract(&bot, msg.chat.id, &address).await,
+        Resolution::Ambiguous(candidates) => {
+            groups::set_prompt_owner(msg.chat.id, msg.from().map(|user| user.id)).await;
+
+            pick_token_prompt(
+                &bot,
+                &dialogue,
+                msg.chat.id,
+                String::from("scan"),
+                vec![contract.trim().to_owned()],
+                candidates,
+            )
+            .await
+        }
+        Resolution::Unknown => scan_contract(&bot, msg.chat.id, contract.trim()).await,
+    }
+}
+
+async fn scan_contract(bot: &Bot, chat_id: ChatId, contract: &str) -> HandlerResult {
+    let loading_message_id = loading_message(bot, chat_id).await;
+
+    if !utils::is_valid_eth_address(contract) {
+        bot.delete_message(chat_id, loading_message_id).await?;
+        bot.send_message(chat_id, format!("The submitted contract is not valid!"))
+            .await?;
+        return Ok(());
+    }
+
+    let locale = SETTINGS.get(chat_id).await.locale;
+    let card = refresh_scan_card(contract, locale).await;
+    bot.delete_message(chat_id, loading_message_id).await?;
+
+    match card {
+        Ok(card) => {
+            let token_info = &card.token_info;
+            symbols::remember(
+                chat_id,
+                &token_info.symbol,
+                &token_info.name,
+                &token_info.contract_address,
+            )
+            .await;
+
+            bot.send_message(
+                chat_id,
+                render_scan_card(Some(chat_id), locale, &card).await,
+            )
+            .parse_mode(ParseMode::Html)
+            .disable_web_page_preview(true)
+            .reply_markup(scan_card_keyboard(&token_info.contract_address, true))
+            .await?;
+        }
+        Err(e) => {
+            error!("get_token_info error: {}", e);
+            bot.send_message(
+                chat_id,
+                format!("Something went wrong, please try again later"),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+// the cached scan with its stale fields fetched again, or a new scan with every field
+async fn refresh_scan_card(
+    contract: &str,
+    locale: Locale,
+) -> Result<scans::ScanCard, api::TokenInfoError> {
+    let now = Utc::now();
+    let mut card = match scans::get(contract).await {
+        Some(mut card) => {
+            if card
+                .stale_fields(now, locale)
+                .contains(&scans::ScanField::Warnings)
+            {
+                card.token_info = api::get_scan_token_info(contract.to_owned()).await?;
+            }
+            card
+        }
+        None => scans::ScanCard::new(api::get_scan_token_info(contract.to_owned()).await?),
+    };
+
+    for field in card.stale_fields(now, locale) {
+        let text = scan_field(field, &card.token_info, &locale).await;
+        card.set(field, text, now);
+    }
+    card.locale = locale;
+    scans::store(card.clone()).await;
+
+    Ok(card)
+}
+
+async fn scan_field(
+    field: scans::ScanField,
+    token_info: &api::HoneypotTokenInfo,
+    locale: &Locale,
+) -> Option<String> {
+    let contract = &token_info.contract_address;
+
+    match field {
+        scans::ScanField::Warnings => {
+            let warnings = scan_warnings(token_info).await.warnings;
+            // without the simulation no warnings doesn't mean much, the banner says so
+            if warnings.is_empty() {
+                return token_info
+                    .simulated
+                    .then(|| String::from("✅ There were no warnings found"));
+            }
+
+            Some(
+                warnings
+                    .iter()
+                    .map(|warning| format!("❌ {}", warning))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            )
+        }
+        scans::ScanField::Supply => supply_summary(token_info, locale).await,
+        scans::ScanField::Holdings => holdings_summary(token_info).await,
+        scans::ScanField::Concentration => concentration_summary(contract).await,
+        scans::ScanField::SizeTaxes => size_tax_summary(contract).await,
+        scans::ScanField::AntiBot => anti_bot_summary(token_info, locale).await,
+        scans::ScanField::Locker => match api::get_liquidity_locker(contract.to_owned()).await {
+            Some(Some(locker)) => Some(format!(
+                "🔒 Liquidity locked with {}",
+                html::escape(&locker)
+            )),
+            _ => None,
+        },
+        scans::ScanField::Funding => funding_summary(contract).await,
+        scans::ScanField::Template if !api::has_scam_templates() => Some(String::from(
+            "🧬 Not compared to scam templates, none are loaded from SCAM_TEMPLATES_DIR",
+        )),
+        scans::ScanField::Template => match api::get_template_match(contract.to_owned()).await {
+            Ok(found) => found.map(|found| format!("🧬 {}", html::escape(&found.to_string()))),
+            Err(e) => {
+                error!("get_template_match error: {}", e);
+                None
+            }
+        },
+    }
+}
+
+// shared cards (no chat) leave out what the wallets hold and link the default chart site, their
+// time is in the locale of the user who shared or refreshed them
+async fn render_scan_card(
+    chat_id: Option<ChatId>,
+    locale: Locale,
+    card: &scans::ScanCard,
+) -> String {
+    let token_info = &card.token_info;
+    let links = match chat_id {
+        Some(chat_id) => {
+            contract_links_on(chat_id, &token_info.contract_address, token_info.chain).await
+        }
+        None => hyperlinks_from_contract(
+            &token_info.contract_address,
+            token_info.chain,
+            ChartSite::default(),
+        ),
+    };
+
+    let taxes = if token_info.simulated {
+        format!("({}%, {}%)", token_info.buy_tax, token_info.sell_tax)
+    } else {
+        String::from("(taxes unknown)")
+    };
+    let mut info = format!(
+        "Scan result for: \n📄 {}\n\n💎 {} ({})\n⛓ {}\n⚖️ {}\n💵 ${}\n{}\n\n🚨 Warnings:",
+        token_info.contract_address,
+        token_info.name,
+        token_info.symbol,
+        token_info.chain,
+        taxes,
+        locale.number(token_info.liquidity.floor(), 0),
+        links
+    );
+
+    if !token_info.simulated {
+        info = format!(
+            "{}\n⚠️ Simulation unavailable, honeypot.is doesn't know this token yet. Only on-chain data \
+             is shown, the taxes and whether it can be sold weren't checked",
+            info
+        );
+    } else if api::is_token_info_stale(token_info) {
+        info = format!(
+            "{}\n🕒 honeypot.is is unavailable, showing data from {} ago",
+            info,
+            format_age((Utc::now() - token_info.fetched_at).num_seconds())
+        );
+    }
+
+    let fields = [
+        scans::ScanField::Supply,
+        scans::ScanField::Holdings,
+        scans::ScanField::Concentration,
+        scans::ScanField::SizeTaxes,
+        scans::ScanField::AntiBot,
+        scans::ScanField::Locker,
+        scans::ScanField::Warnings,
+        scans::ScanField::Funding,
+        scans::ScanField::Template,
+    ];
+    for field in fields {
+        if field == scans::ScanField::Holdings && chat_id.is_none() {
+            continue;
+        }
+        if let Some(text) = card.field(field) {
+            info = format!("{}\n{}", info, text);
+        }
+    }
+
+    format!(
+        "{}\n\n🕒 As of {} {}",
+        info,
+        locale.time(card.fetched_at(), "%Y-%m-%d %H:%M"),
+        locale.zone(card.fetched_at())
+    )
+}
+
+// shared cards don't belong to a chat, they have nothing to monitor the token for
+fn scan_card_keyboard(contract: &str, monitor: bool) -> InlineKeyboardMarkup {
+    let mut buttons = vec![vec![
+        InlineKeyboardButton::callback("🔄 Refresh", format!("scanrefresh:{}", contract)),
+        InlineKeyboardButton::switch_inline_query("📤 Share", format!("scan {}", contract)),
+    ]];
+    if monitor {
+        buttons.push(vec![InlineKeyboardButton::callback(
+            "👁 Monitor this token",
+            format!("monitor:{}", contract),
+        )]);
+    }
+
+    InlineKeyboardMarkup::new(buttons)
+}
+
+// cards shared with the Share button are inline messages, refreshing them leaves out the holdings too
+async fn refresh_scan(bot: Bot, q: CallbackQuery, contract: String) -> HandlerResult {
+    let chat_id = match &q.message {
+        Some(message) => message.chat.id,
+        None => q.from.id.into(),
+    };
+    let locale = SETTINGS.get(chat_id).await.locale;
+    let card = match refresh_scan_card(&contract, locale).await {
+        Ok(card) => card,
+        Err(e) => {
+            error!("get_token_info error: {}", e);
+            bot.answer_callback_query(q.id)
+                .text("Something went wrong, please try again later")
+                .await?;
+            return Ok(());
+        }
+    };
+    bot.answer_callback_query(q.id).await?;
+
+    // editing fails when nothing changed since the last refresh, which is fine
+    if let Some(message) = &q.message {
+        let _ = bot
+            .edit_message_text(
+                message.chat.id,
+                message.id,
+                render_scan_card(Some(message.chat.id), locale, &card).await,
+            )
+            .parse_mode(ParseMode::Html)
+            .disable_web_page_preview(true)
+            .reply_markup(scan_card_keyboard(&contract, true))
+            .await;
+    } else if let Some(inline_message_id) = &q.inline_message_id {
+        let _ = bot
+            .edit_message_text_inline(
+                inline_message_id,
+                render_scan_card(None, locale, &card).await,
+            )
+            .parse_mode(ParseMode::Html)
+            .disable_web_page_preview(true)
+            .reply_markup(scan_card_keyboard(&contract, false))
+            .await;
+    }
+
+    Ok(())
+}
+
+// the html a message was sent with, rebuilt from its entities. nested ones keep the outer formatting
+fn message_html(message: &Message) -> String {
+    let Some(text) = message.text() else {
+        return String::new();
+    };
+
+    let mut rendered = String::new();
+    let mut position = 0;
+    for entity in message.parse_entities().unwrap_or_default() {
+        let range = entity.range();
+        if range.start < position {
+            continue;
+        }
+
+        rendered.push_str(&html::escape(&text[position..range.start]));
+        rendered.push_str(&match entity.kind() {
+            MessageEntityKind::TextLink { url } => html::link(url.as_str(), entity.text()),
+            MessageEntityKind::Bold => html::bold(&html::escape(entity.text())),
+            MessageEntityKind::Italic => html::italic(&html::escape(entity.text())),
+            MessageEntityKind::Code => html::code_inline(entity.text()),
+            _ => html::escape(entity.text()),
+        });
+        position = range.end;
+    }
+    rendered.push_str(&html::escape(&text[position..]));
+
+    rendered
+}
+
+// the alert is edited with the verdict of the moment, the time it was checked and the same buttons
+async fn recheck_new_token(bot: Bot, q: CallbackQuery, contract: String) -> HandlerResult {
+    let Some(message) = q.message else {
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    };
+    bot.answer_callback_query(q.id)
+        .text("Re-checking...")
+        .await?;
+
+    let verdict = api::recheck_launch(contract.clone()).await;
+    let locale = SETTINGS.get(message.chat.id).await.locale;
+    let now = Utc::now();
+
+    let rendered = message_html(&message);
+    let alert = rendered
+        .split(&format!("\n\n{}", RECHECK_HEADER))
+        .next()
+        .unwrap_or_default();
+    let text = format!(
+        "{}\n\n{} {} {}:\n{}{}",
+        alert,
+        RECHECK_HEADER,
+        locale.time(now, "%Y-%m-%d %H:%M:%S"),
+        locale.zone(now),
+        verdict,
+        if verdict.passed() {
+            ""
+        } else {
+            "\n⚠️ Not every check passed this time"
+        }
+    );
+
+    // editing fails when the verdict and the time didn't change, which is fine
+    let _ = bot
+        .edit_message_text(message.chat.id, message.id, text)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .reply_markup(new_token_keyboard(&contract))
+        .await;
+
+    Ok(())
+}
+
+// '@bot scan <contract>' typed by the Share button, only answered from the cache so it costs no requests
+async fn share_scan(bot: Bot, q: InlineQuery) -> HandlerResult {
+    let contract = q
+        .query
+        .trim()
+        .trim_start_matches("scan")
+        .trim()
+        .to_lowercase();
+
+    let mut results = vec![];
+    if let Some(card) = scans::get(&contract).await {
+        let token_info = &card.token_info;
+        let locale = SETTINGS.get(q.from.id.into()).await.locale;
+        results.push(InlineQueryResult::Article(
+            InlineQueryResultArticle::new(
+                contract.clone(),
+                format!("Scan of {} ({})", token_info.name, token_info.symbol),
+                InputMessageContent::Text(
+                    InputMessageContentText::new(render_scan_card(None, locale, &card).await)
+                        .parse_mode(ParseMode::Html)
+                        .disable_web_page_preview(true),
+                ),
+            )
+            .description(format!(
+                "As of {} {}",
+                locale.time(card.fetched_at(), "%Y-%m-%d %H:%M"),
+                locale.zone(card.fetched_at())
+            ))
+            .reply_markup(scan_card_keyboard(&contract, false)),
+        ));
+    }
+
+    bot.answer_inline_query(q.id, results).cache_time(0).await?;
+    Ok(())
+}
+
+// the scan of a new token alert, fetched once and formatted in every chat's locale
+#[derive(Debug, Clone)]
+pub struct NewTokenScan {
+    token_info: api::HoneypotTokenInfo,
+    score: String,
+}
+
+// the launch filtering already fetched most of it
+pub async fn new_token_scan(contract: &str) -> Option<NewTokenScan> {
+    let token_info = match api::get_token_info(contract.to_owned()).await {
+        Ok(token_info) => token_info,
+        Err(e) => {
+            error!("get_token_info error: {}", e);
+            return None;
+        }
+    };
+    let score = scan_warnings(&token_info).await.score();
+
+    Some(NewTokenScan { token_info, score })
+}
+
+pub fn new_token_summary(scan: &NewTokenScan, locale: &Locale) -> String {
+    let token_info = &scan.token_info;
+
+    format!(
+        "💎 {} ({})\n📄 {}\n⚖️ Tax: {}% buy, {}% sell\n💵 Liquidity: ${}\n👥 Holders: {}\n🛡 Safety score: {}",
+        html::escape(&token_info.name),
+        html::escape(&token_info.symbol),
+        token_info.contract_address,
+        token_info.buy_tax,
+        token_info.sell_tax,
+        locale.number(token_info.liquidity.floor(), 0),
+        locale.integer(token_info.total_holders.into()),
+        scan.score
+    )
+}
+
+// top 10 holders (excluding the pair, lockers and burn) holding more than this can dump the price at will
+const CONCENTRATION_WARNING_PERCENT: f64 = 50.0;
+
+// market cap counts the circulating supply, FDV the total one
+async fn supply_summary(token_info: &api::HoneypotTokenInfo, locale: &Locale) -> Option<String> {
+    let contract = token_info.contract_address.clone();
+    let (supply, price) = tokio::join!(
+        api::get_supply_info(contract.clone(), token_info.decimals),
+        api::get_token_price(contract.clone())
+    );
+    let supply = match supply {
+        Ok(supply) => supply,
+        Err(e) => {
+            error!("get_supply_info error: {}", e);
+            return None;
+        }
+    };
+
+    let mut summary = format!(
+        "🪙 Supply: {} circulating / {} total",
+        locale.number(supply.circulating_tokens(), 0),
+        locale.number(supply.total_tokens(), 0)
+    );
+
+    match price {
+        Ok(price) if price > 0.0 => {
+            summary = format!(
+                "{}\n🏦 Market cap: ${} | FDV: ${}",
+                summary,
+                locale.number(supply.market_cap(price as f64), 0),
+                locale.number(supply.fdv(price as f64), 0)
+            );
+        }
+        Ok(_) => (),
+        Err(e) => error!("get_token_price error: {}", e),
+    }
+
+    Some(summary)
+}
+
+// kept apart from the supply, shared scan cards don't show what the wallets hold
+async fn holdings_summary(token_info: &api::HoneypotTokenInfo) -> Option<String> {
+    let contract = token_info.contract_address.clone();
+    let supply = match api::get_supply_info(contract.clone(), token_info.decimals).await {
+        Ok(supply) => supply,
+        Err(e) => {
+            error!("get_supply_info error: {}", e);
+            return None;
+        }
+    };
+
+    let mut held: u128 = 0;
+    for wallet in wallet::trading_wallets() {
+        match api::get_token_balance(contract.clone(), wallet).await {
+            Ok(balance) => held += balance,
+            Err(e) => {
+                error!("get_token_balance error: {}", e);
+                return None;
+            }
+        }
+    }
+
+    Some(format!(
+        "👛 Your wallets hold {:.4}% of the supply",
+        supply.percent_of_supply(held)
+    ))
+}
+
+// held tokens also show how the concentration changed since the oldest snapshot
+async fn concentration_summary(contract: &str) -> Option<String> {
+    let concentration = match api::get_holder_concentration(contract.to_owned()).await {
+        Ok(concentration) => concentration,
+        Err(e) => {
+            error!("get_holder_concentration error: {}", e);
+            return None;
+        }
+    };
+
+    let mut summary = format!(
+        "👥 Top 5 hold {:.1}%, top 10 hold {:.1}%",
+        concentration.top5_percent, concentration.top10_percent
+    );
+
+    if let Some(oldest) = api::concentration_history(contract).await.first() {
+        summary = format!(
+            "{} ({:+.1}% in {})",
+            summary,
+            concentration.top10_percent - oldest.concentration.top10_percent,
+            format_age((Utc::now() - oldest.taken_at).num_seconds())
+        );
+    }
+
+    Some(summary)
+}
+
+// effective taxes of simulated buys and sells at a few sizes, some tokens only punish larger trades
+async fn size_tax_summary(contract: &str) -> Option<String> {
+    let simulations = api::get_size_taxes(contract.to_owned()).await;
+    let mut summary =
+        String::from("🧪 Simulated trades (buy tax, sell tax, price impact, round trip loss):");
+    let mut taxes = vec![];
+
+    for (size, simulation) in simulations {
+        let line = match simulation {
+            Ok(api::SizeSimulation::Taxed(tax)) => {
+                taxes.push(tax.buy_tax.max(tax.sell_tax));
+                format!(
+                    "{} ETH: {:.1}%, {:.1}%, {:.1}%, {:.1}%",
+                    size, tax.buy_tax, tax.sell_tax, tax.price_impact, tax.round_trip_loss
+                )
+            }
+            Ok(api::SizeSimulation::BuyReverts) => format!("{} ETH: the buy reverts", size),
+            // trades at other sizes can't find a pool either
+            Ok(api::SizeSimulation::NoV2Pool) => return None,
+            Err(e) => {
+                error!("get_size_taxes error: {}", e);
+                return None;
+            }
+        };
+        summary = format!("{}\n{}", summary, line);
+    }
+
+    if let (Some(first), Some(highest)) = (taxes.first(), taxes.iter().copied().reduce(f64::max)) {
+        if highest - first >= SIZE_TAX_INCREASE_WARNING {
+            summary = format!(
+                "{}\n⚠️ Taxes grow with the trade size, from {:.1}% to {:.1}%",
+                summary, first, highest
+            );
+        }
+    }
+
+    Some(summary)
+}
+
+// the limits a snipe has to pass, with what the max tx is worth to size the buy under it
+async fn anti_bot_summary(token_info: &api::HoneypotTokenInfo, locale: &Locale) -> Option<String> {
+    let contract = token_info.contract_address.clone();
+    let (anti_bot, total_supply) = tokio::join!(
+        api::get_anti_bot(contract.clone()),
+        api::get_total_supply(contract.clone())
+    );
+    let (anti_bot, total_supply) = match (anti_bot, total_supply) {
+        (Ok(anti_bot), Ok(total_supply)) => (anti_bot, total_supply),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("get_anti_bot error: {}", e);
+            return None;
+        }
+    };
+
+    let mechanics = anti_bot.describe(total_supply, token_info.decimals, locale);
+    if mechanics.is_empty() {
+        return None;
+    }
+    let mut summary = format!("🤖 Anti-bot mechanics:\n{}", mechanics.join("\n"));
+
+    // price impact only makes the same ETH buy fewer tokens, so this stays under the limit
+    if let Some(max_tx) = anti_bot.max_tx_tokens(total_supply) {
+        if let (Ok(price), Ok(eth_price)) =
+            tokio::join!(api::get_token_price(contract.clone()), api::get_eth_price())
+        {
+            if price > 0.0 && eth_price > 0.0 {
+                let tokens = max_tx as f64 / 10f64.powi(token_info.decimals as i32);
+                summary = format!(
+                    "{}\n💡 A buy of up to {:.4} ETH passes the max tx at the current price",
+                    summary,
+                    tokens * price as f64 / eth_price
+                );
+            }
+        }
+    }
+
+    Some(summary)
+}
+
+// the checks of a scan, the safety score is how many of them passed
+#[derive(Debug, Clone, Default)]
+pub struct ScanWarnings {
+    pub checks: usize,
+    pub failed: usize,
+    pub warnings: Vec<String>,
+}
+
+impl ScanWarnings {
+    // a check fails with one or more warnings
+    fn check(&mut self, warnings: impl IntoIterator<Item = String>) {
+        let before = self.warnings.len();
+        self.warnings.extend(warnings);
+
+        self.checks += 1;
+        if self.warnings.len() > before {
+            self.failed += 1;
+        }
+    }
+
+    pub fn score(&self) -> String {
+        format!("{}/{}", self.checks - self.failed, self.checks)
+    }
+}
+
+pub async fn scan_warnings(token_info: &api::HoneypotTokenInfo) -> ScanWarnings {
+    let mut scan = ScanWarnings::default();
+
+    // honeypot.is' simulation and the flags it raised
+    let mut honeypot = vec![];
+    if token_info.is_honeypot {
+        honeypot.push(
+            token_info
+                .honeypot_reason
+                .clone()
+                .unwrap_or(String::from("TOKEN IS A HONEYPOT")),
+        );
+    }
+    if let Some(flags_description) = &token_info.flags_description {
+        honeypot.extend(flags_description.clone());
+    }
+    scan.check(honeypot);
+
+    scan.check(
+        token_info
+            .has_proxy_calls
+            .unwrap_or(false)
+            .then(|| String::from("Contract has proxy calls!")),
+    );
+    scan.check(
+        (token_info.is_open_source == Some(false))
+            .then(|| String::from("Contract is not open source!")),
+    );
+    scan.check((token_info.liquidity < 5000.0).then(|| String::from("Liquidity is very small!")));
+
+    let (renounced, locked, anti_snipe, concentration, funding) = tokio::join!(
+        api::is_contract_renounced(token_info.contract_address.clone()),
+        api::get_liquidity_locker(token_info.contract_address.clone()),
+        api::get_anti_snipe(token_info.contract_address.clone()),
+        api::get_holder_concentration(token_info.contract_address.clone()),
+        creator_funding(&token_info.contract_address)
+    );
+
+    scan.check((renounced == Some(false)).then(|| String::from("Contract is not renounced!")));
+    scan.check((locked == Some(None)).then(|| String::from("Liquidity might not be locked!")));
+    scan.check(
+        anti_snipe
+            .ok()
+            .flatten()
+            .filter(|anti_snipe| anti_snipe.is_detected())
+            .map(|anti_snipe| format!("Anti-snipe mechanics: {}!", anti_snipe)),
+    );
+    scan.check(
+        concentration
+            .ok()
+            .filter(|concentration| concentration.top10_percent > CONCENTRATION_WARNING_PERCENT)
+            .map(|concentration| {
+                format!(
+                    "Top 10 holders own {:.1}% of the supply!",
+                    concentration.top10_percent
+                )
+            }),
+    );
+    scan.check(
+        funding
+            .ok()
+            .flatten()
+            .filter(|path| path.is_suspicious())
+            .map(|_| String::from("Creator was funded through a mixer or a flagged wallet!")),
+    );
+
+    scan
+}
+
+async fn compare_tokens(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    if args.len() < 2 || args.len() > 5 {
+        groups::reply(
+            &bot,
+            &msg,
+            "Usage: /compare <contractOrSymbol> <contractOrSymbol> [...] (2-5 tokens)",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut contracts = vec![];
+    for token in &args {
+        match symbols::resolve(msg.chat.id, token).await {
+            Resolution::Address(address) => contracts.push(address),
+            _ if utils::is_valid_eth_address(token) => contracts.push(token.to_string()),
+            _ => {
+                groups::reply(
+                    &bot,
+                    &msg,
+                    format!("{} is not a valid contract or known symbol!", token),
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+
+    let mut set = JoinSet::new();
+    for (i, contract) in contracts.into_iter().enumerate() {
+        set.spawn(async move {
+            match api::get_token_info(contract).await {
+                Ok(token_info) => {
+                    let warnings = scan_warnings(&token_info).await;
+                    Ok((i, token_info, warnings))
+                }
+                Err(e) => Err(e),
+            }
+        });
+    }
+
+    let mut results = vec![];
+    while let Some(result) = set.join_next().await {
+        match result {
+            Ok(Ok(result)) => results.push(result),
+            Ok(Err(e)) => error!("get_token_info error: {}", e),
+            Err(e) => error!("compare_tokens error: {}", e),
+        }
+    }
+    results.sort_by_key(|(i, _, _)| *i);
+
+    bot.delete_message(msg.chat.id, loading_message_id).await?;
+
+    if results.len() < 2 {
+        groups::reply(
+            &bot,
+            &msg,
+            format!("Something went wrong, please try again later"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    for (_, token_info, _) in &results {
+        symbols::remember(
+            msg.chat.id,
+            &token_info.symbol,
+            &token_info.name,
+            &token_info.contract_address,
+        )
+        .await;
+    }
+
+    let locale = SETTINGS.get(msg.chat.id).await.locale;
+    let now = Utc::now().timestamp();
+    let mut rows = vec![
+        vec![String::from("")],
+        vec![String::from("Tax")],
+        vec![String::from("Liq $")],
+        vec![String::from("Holders")],
+        vec![String::from("Age")],
+        vec![String::from("Score")],
+    ];
+
+    for (_, token_info, warnings) in &results {
+        let columns = [
+            // stale honeypot.is data is marked with an asterisk
+            format!(
+                "{}{}",
+                token_info.symbol.chars().take(10).collect::<String>(),
+                if api::is_token_info_stale(&token_info) {
+                    "*"
+                } else {
+                    ""
+                }
+            ),
+            format!("{}/{}%", token_info.buy_tax, token_info.sell_tax),
+            locale.number(token_info.liquidity.floor(), 0),
+            locale.integer(token_info.total_holders.into()),
+            match token_info.pair_created_at {
+                Some(created_at) => format_age(now - created_at),
+                None => String::from("?"),
+            },
+            warnings.score(),
+        ];
+
+        for (row, column) in rows.iter_mut().zip(columns) {
+            row.push(column);
+        }
+    }
+
+    let mut info = format!(
+        "⚖️ Token comparison\n\n<pre>{}</pre>\n",
+        html::escape(&utils::format_table(&rows))
+    );
+    if results
+        .iter()
+        .any(|(_, token_info, _)| api::is_token_info_stale(token_info))
+    {
+        info.push_str("* honeypot.is is unavailable, showing older data\n");
+    }
+
+    for (_, token_info, warnings) in &results {
+        info = format!(
+            "{}\n💎 {} {}: {}\n{}",
+            info,
+            html::escape(&token_info.symbol),
+            html::code_inline(&token_info.contract_address),
+            if warnings.warnings.is_empty() {
+                String::from("✅ no warnings")
+            } else {
+                format!("❌ {} warning(s)", warnings.warnings.len())
+            },
+            contract_links_on(msg.chat.id, &token_info.contract_address, token_info.chain).await
+        );
+    }
+
+    groups::reply(&bot, &msg, info)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
+
+    Ok(())
+}
+
+fn format_age(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+
+    if seconds < 3600 {
+        format!("{}m", seconds / 60)
+    } else if seconds < 86400 {
+        format!("{}h", seconds / 3600)
+    } else {
+        format!("{}d", seconds / 86400)
+    }
+}
+
+pub async fn chart_site(chat_id: ChatId) -> ChartSite {
+    SETTINGS.get(chat_id).await.chart_site
+}
+
+// chart and swap links of an ethereum token, on the chat's preferred chart site
+async fn contract_links(chat_id: ChatId, contract: &str) -> String {
+    contract_links_on(chat_id, contract, Chain::Ethereum).await
+}
+
+async fn contract_links_on(chat_id: ChatId, contract: &str, chain: Chain) -> String {
+    hyperlinks_from_contract(contract, chain, chart_site(chat_id).await)
+}
+
+// the chat's metadata overrides win over honeypot.is
+async fn token_symbol(chat_id: ChatId, contract: &str) -> String {
+    if let Some(symbol) = metadata::get(chat_id, contract).await.symbol {
+        return symbol;
+    }
+
+    match api::get_token_info(contract.to_owned()).await {
+        Ok(token_info) => token_info.symbol,
+        Err(_) => short_address(contract).await,
+    }
+}
+
+async fn short_address(address: &str) -> String {
+    match api::get_address_label(address).await {
+        Some(label) => html::escape(&label),
+        None if address.len() == 42 => {
+            format!("{}...{}", &address[..6], &address[address.len() - 4..])
+        }
+        None => String::from(address),
+    }
+}
+
+async fn whale_transfers(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let contract = match args.first() {
+        Some(token) => match symbols::resolve(msg.chat.id, token).await {
+            Resolution::Address(address) => address,
+            _ => token.to_string(),
+        },
+        None => String::new(),
+    };
+
+    if !utils::is_valid_eth_address(&contract) {
+        groups::reply(&bot, &msg, format!("The submitted contract is not valid!")).await?;
+        return Ok(());
+    }
+
+    // default threshold is $10,000
+    let min_value_usd = match args.get(1) {
+        Some(value) => match value.parse::<f64>() {
+            Ok(value) => value,
+            Err(_) => {
+                groups::reply(&bot, &msg, "The submitted minimum usd value is not valid!").await?;
+                return Ok(());
+            }
+        },
+        None => 10_000.0,
+    };
+
+    let locale = SETTINGS.get(msg.chat.id).await.locale;
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+
+    match api::get_whale_transfers(contract.clone(), min_value_usd).await {
+        Ok(transfers) => {
+            let flow = api::exchange_flow(&transfers);
+            let mut message = format!(
+                "🐋 Whale transfers over ${} for:\n📄 {}\n",
+                locale.number(min_value_usd.floor(), 0),
+                contract
+            );
+
+            if transfers.is_empty() {
+                message.push_str("\nNo large transfers were found recently");
+            }
+
+            for transfer in transfers.iter().take(10) {
+                message.push_str(&format!(
+                    "\n💰 ${} ({} tokens)\n   {} ➡️ {} | {}",
+                    locale.number(transfer.value_usd, 0),
+                    locale.number(transfer.amount, 0),
+                    short_address(&transfer.from).await,
+                    short_address(&transfer.to).await,
+                    utils::tx_link(Chain::Ethereum, &transfer.hash, "Tx")
+                ));
+            }
+
+            let net_flow = flow.inflow_usd - flow.outflow_usd;
+            message.push_str(&format!(
+                "\n\n🏦 Exchange inflow: ${}\n🏦 Exchange outflow: ${}\n{}",
+                locale.number(flow.inflow_usd, 0),
+                locale.number(flow.outflow_usd, 0),
+                if net_flow > 0.0 {
+                    "⚠️ More tokens are moving into exchanges, whales might be distributing"
+                } else if net_flow < 0.0 {
+                    "✅ More tokens are leaving exchanges, whales might be accumulating"
+                } else {
+                    "➖ No net exchange flow"
+                }
+            ));
+
+            bot.delete_message(msg.chat.id, loading_message_id).await?;
+            groups::reply(&bot, &msg, message)
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true)
+                .await?;
+        }
+        Err(e) => {
+            error!("get_whale_transfers error: {}", e);
+            bot.delete_message(msg.chat.id, loading_message_id).await?;
+            groups::reply(
+                &bot,
+                &msg,
+                format!("Something went wrong, please try again later"),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn check_sandwich(bot: Bot, msg: Message) -> HandlerResult {
+    let tx_hash = parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username())
+        .unwrap()
+        .1
+        .join("");
+
+    if !utils::is_valid_tx_hash(tx_hash.trim()) {
+        groups::reply(&bot, &msg, "The submitted transaction hash is not valid!").await?;
+        return Ok(());
+    }
+
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
+    let result = sandwich_report(&bot, msg.chat.id, tx_hash.trim()).await;
+    bot.delete_message(msg.chat.id, loading_message_id).await?;
+
+    result
+}
+
+pub async fn sandwich_report(bot: &Bot, chat_id: ChatId, tx_hash: &str) -> HandlerResult {
+    let message = match api::detect_sandwich(tx_hash.to_owned()).await {
+        Ok(api::SandwichCheck::NotFound) => {
+            String::from("Transaction was not found or is not confirmed yet!")
+        }
+        Ok(api::SandwichCheck::NotASwap) => {
+            String::from("Transaction is not a Uniswap V2 swap, it can't be sandwiched!")
+        }
+        Ok(api::SandwichCheck::Clean) => String::from("✅ Transaction was not sandwiched"),
+        Ok(api::SandwichCheck::Sandwiched(sandwich)) => {
+            let loss = match sandwich.estimated_loss_eth {
+                Some(loss_eth) => match api::get_eth_price().await {
+                    Ok(eth_price) => format!("{:.4} ETH (${:.2})", loss_eth, loss_eth * eth_price),
+                    Err(_) => format!("{:.4} ETH", loss_eth),
+                },
+                None => String::from("unknown"),
+            };
+
+            format!(
+                "🥪 Transaction was sandwiched! 🥪\n\n📄 Pair: {}\n🤖 Attacker: {}\n{} | {}\n💸 Estimated loss: {}",
+                sandwich.pair,
+                sandwich.attacker,
+                utils::tx_link(Chain::Ethereum, &sandwich.frontrun_hash, "Frontrun"),
+                utils::tx_link(Chain::Ethereum, &sandwich.backrun_hash, "Backrun"),
+                loss
+            )
+        }
+        Err(e) => {
+            error!("detect_sandwich error: {}", e);
+            String::from("Something went wrong, please try again later")
         }
-    }
+    };
+
+    bot.send_message(chat_id, message)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
 
     Ok(())
 }
 
-async fn get_portfolio(bot: Bot, msg: Message) -> HandlerResult {
-    let loading_message_id = loading_message(&bot, &msg).await;
-
-    match api::get_token_balances_with_prices().await {
-        Ok(owned_tokens) => {
-            let mut message: String = String::from("Portfolio:\n");
-            let mut found = false;
-
-            for token in owned_tokens {
-                if SETTINGS
-                    .lock()
-                    .await
-                    .get(&msg.chat.id)
-                    .unwrap_or(&Settings {
-                        ..Default::default()
-                    })
-                    .hide_zero_token_balances
-                    && token.value_usd == 0.0
-                {
-                    continue;
-                }
-
-                let percent_change = {
-                    if token.usd_price_24hr_percent_change > 0.0 {
-                        format!("📈 +{:.2}%", token.usd_price_24hr_percent_change)
-                    } else {
-                        format!("📉 {:.2}%", token.usd_price_24hr_percent_change)
-                    }
-                };
+async fn rescue_transaction(bot: Bot, msg: Message) -> HandlerResult {
+    let (command, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let tx_hash = args.join("");
 
-                // TODO: add thumbnail to message if available
-                message.push_str(&format!(
-                    "\n💎 {} ({})\n💰 {} (${})\n{}\n📊 {:.2}%\n{}\n",
-                    token.name,
-                    token.symbol,
-                    format!("{:.2}", token.balance).separate_with_commas(),
-                    format!("{:.2}", token.value_usd).separate_with_commas(),
-                    percent_change,
-                    token.portfolio_percentage,
-                    hyperlinks_from_contract(&token.contract)
-                ));
+    if !utils::is_valid_tx_hash(tx_hash.trim()) {
+        groups::reply(&bot, &msg, "The submitted transaction hash is not valid!").await?;
+        return Ok(());
+    }
 
-                found = true;
-            }
+    let loading_message_id = loading_message(&bot, msg.chat.id).await;
 
+    let cancel = command.to_lowercase() == "canceltx";
+    let transaction = match api::get_transaction(tx_hash.trim().to_owned()).await {
+        Ok(Some(transaction)) => transaction,
+        // a Safe proposal isn't on chain until an owner executes it
+        Ok(None) => {
+            let text = match wallet::safe_address() {
+                Some(safe) => replace_safe_proposal(&safe, tx_hash.trim(), cancel).await,
+                None => String::from("Transaction was not found!"),
+            };
             bot.delete_message(msg.chat.id, loading_message_id).await?;
-            if found {
-                bot.send_message(msg.chat.id, format!("{}", message))
-                    .parse_mode(ParseMode::Html)
-                    .disable_web_page_preview(true)
-                    .await?;
-            } else {
-                bot.send_message(msg.chat.id, format!("No token balances were found!"))
-                    .await?;
-            }
+            groups::reply(&bot, &msg, text).await?;
+            return Ok(());
         }
         Err(e) => {
-            error!("get_token_balances_with_prices error: {}", e);
+            error!("get_transaction error: {}", e);
             bot.delete_message(msg.chat.id, loading_message_id).await?;
-            bot.send_message(
-                msg.chat.id,
+            groups::reply(
+                &bot,
+                &msg,
                 format!("Something went wrong, please try again later"),
             )
             .await?;
+            return Ok(());
         }
-    }
+    };
 
-    Ok(())
-}
+    if transaction.block_number.is_some() {
+        bot.delete_message(msg.chat.id, loading_message_id).await?;
+        groups::reply(
+            &bot,
+            &msg,
+            "Transaction is already confirmed, it can't be replaced!",
+        )
+        .await?;
+        return Ok(());
+    }
 
-async fn get_eth_gas(bot: Bot, msg: Message) -> HandlerResult {
-    let loading_message_id = loading_message(&bot, &msg).await;
+    if !wallet::trading_wallets().contains(&transaction.from.to_lowercase()) {
+        bot.delete_message(msg.chat.id, loading_message_id).await?;
+        groups::reply(
+            &bot,
+            &msg,
+            "Only transactions sent from your wallets can be replaced!",
+        )
+        .await?;
+        return Ok(());
+    }
 
-    match api::get_eth_gas().await {
-        Ok(gwei_fee) => {
-            match api::get_eth_price().await {
-                Ok(eth_price) => {
-                    // gas estimations calculated based on cryptoneur.xyz/en/gas-fees-calculator + fees
-                    let uniswap_v2: f64 = gwei_fee * 0.000000001 * eth_price * 152809.0 * 1.03;
-                    let uniswap_v3: f64 = gwei_fee * 0.000000001 * eth_price * 184523.0 * 1.03;
+    match api::get_eth_gas_wei().await {
+        Ok(gas_price) => {
+            let replacement = if cancel {
+                trading::cancel(&transaction, gas_price)
+            } else {
+                trading::speed_up(&transaction, gas_price)
+            };
+            bot.delete_message(msg.chat.id, loading_message_id).await?;
 
-                    let response = format!("Current eth gas is: {:.0} gwei\n\nEstimated fees:\n🦄 Uniswap V2 swap: ${:.2}\n🦄 Uniswap V3 swap: ${:.2}", gwei_fee, uniswap_v2, uniswap_v3);
-                    bot.delete_message(msg.chat.id, loading_message_id).await?;
-                    bot.send_message(msg.chat.id, response).await?;
-                }
-                Err(e) => {
-                    error!("get_eth_price error: {}", e);
-                    bot.delete_message(msg.chat.id, loading_message_id).await?;
-                    bot.send_message(
-                        msg.chat.id,
-                        format!("Something went wrong, please try again later"),
+            // the replacement is signed where the stuck transaction was, in the paired wallet
+            match walletconnect::session_for(&transaction.from).await {
+                Some(session) => {
+                    groups::reply(
+                        &bot,
+                        &msg,
+                        format!(
+                            "{}\n\n📱 Approve the replacement in your wallet",
+                            replacement
+                        ),
                     )
                     .await?;
+                    tasks::spawn_job(
+                        "replace_with_walletconnect",
+                        replace_with_walletconnect(bot.clone(), msg.chat.id, session, replacement),
+                    );
+                }
+                None => {
+                    let text = format!(
+                        "{}\n\nNothing was sent: {}. To replace it from your wallet app use nonce {} and at least these fees",
+                        replacement, NOT_SIGNED, replacement.nonce
+                    );
+                    groups::reply(&bot, &msg, text).await?;
                 }
             }
         }
         Err(e) => {
-            error!("get_eth_gas error: {}", e);
+            error!("get_eth_gas_wei error: {}", e);
             bot.delete_message(msg.chat.id, loading_message_id).await?;
-            bot.send_message(
-                msg.chat.id,
+            groups::reply(
+                &bot,
+                &msg,
                 format!("Something went wrong, please try again later"),
             )
             .await?;
@@ -532,165 +8012,300 @@ async fn get_eth_gas(bot: Bot, msg: Message) -> HandlerResult {
     Ok(())
 }
 
-async fn cancel(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
-    bot.send_message(msg.chat.id, "Current command is cancelled")
-        .await?;
-    dialogue.exit().await?;
-    Ok(())
-}
+// the replacement and the stuck transaction share a nonce, whichever is mined first wins
+async fn replace_with_walletconnect(
+    bot: Bot,
+    chat_id: ChatId,
+    session: walletconnect::Session,
+    replacement: trading::ReplacementTransaction,
+) {
+    let hash = match walletconnect::send_replacement(&session, &replacement).await {
+        Ok(hash) => hash,
+        Err(e) => {
+            warn!("walletconnect send_replacement error: {}", e);
+            let _ = bot
+                .send_message(chat_id, format!("The replacement was not sent: {}", e))
+                .await;
+            return;
+        }
+    };
+    let link = utils::tx_link(Chain::Ethereum, &hash, &hash);
+    let _ = bot
+        .send_message(
+            chat_id,
+            format!("⏳ Replacement sent, waiting to be mined: {}", link),
+        )
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await;
 
-async fn help(bot: Bot, msg: Message) -> HandlerResult {
-    bot.send_message(msg.chat.id, Command::descriptions().to_string())
-        .await?;
-    Ok(())
+    let message = if is_mined_successfully(&hash).await {
+        format!("✅ The replacement was mined: {}", link)
+    } else {
+        format!(
+            "❌ The replacement failed or wasn't mined in time, the stuck transaction may have been mined first: {}",
+            link
+        )
+    };
+    let _ = bot
+        .send_message(chat_id, message)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await;
 }
 
-async fn invalid_state(bot: Bot, msg: Message) -> HandlerResult {
-    bot.send_message(msg.chat.id, "Type /help to see availabe commands.")
-        .await?;
-    Ok(())
-}
+// a queued Safe proposal has no fee, it's cancelled by proposing a rejection with its nonce
+async fn replace_safe_proposal(safe: &str, safe_tx_hash: &str, cancel: bool) -> String {
+    let proposal = match api::get_safe_transaction(safe_tx_hash).await {
+        Ok(proposal) if proposal.safe.eq_ignore_ascii_case(safe) => proposal,
+        Ok(_) => return String::from("Transaction was not found!"),
+        Err(e) => {
+            warn!("get_safe_transaction error: {}", e);
+            return String::from("Transaction was not found!");
+        }
+    };
 
-pub async fn watched_wallet_notification(
-    bot: &Bot,
-    chat_id: ChatId,
-    wallet: &String,
-    transaction: &api::EtherscanTokenTransaction,
-) -> HandlerResult {
-    let epoch_time = DateTime::UNIX_EPOCH
-        + Duration::try_seconds(transaction.time_stamp.parse::<i64>().unwrap()).unwrap();
-    let datetime = DateTime::<Utc>::from(epoch_time);
-    let timestamp = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
+    if proposal.is_executed {
+        return String::from("Transaction is already confirmed, it can't be replaced!");
+    }
+    if !cancel {
+        return format!(
+            "Nothing was sent: a Safe proposal has no fee to raise. Confirm and execute it in the Safe app: {}",
+            safe::app_url(safe, safe_tx_hash)
+        );
+    }
+    let Some(proposer) = wallet::safe_proposer_key() else {
+        return String::from(
+            "Nothing was sent: SAFE_PROPOSER_KEY is not set, the rejection can't be signed!",
+        );
+    };
 
-    bot.send_message(
-        chat_id,
-        format!(
-            "🚨🚨🚨 New transaction 🚨🚨🚨\n\n🔎 {}\n\n💎 {} ({})\n⏰ (UTC) {}\n{} | {}",
-            wallet,
-            transaction.token_name,
-            transaction.token_symbol,
-            timestamp,
-            html::link(
-                &format!("https://etherscan.io/tx/{}", transaction.hash),
-                "Tx"
-            ),
-            hyperlinks_from_contract(&transaction.contract_address)
-        ),
-    )
-    .parse_mode(ParseMode::Html)
-    .disable_web_page_preview(true)
-    .await?;
+    let rejection = safe::SafeTransaction::rejection(safe, proposal.nonce);
+    let rejection_hash = rejection.hash(safe);
+    let proposed = match rejection.proposal(safe, &proposer) {
+        Ok(rejection) => api::propose_safe_transaction(safe, &rejection).await,
+        Err(e) => {
+            error!("sign Safe rejection error: {}", e);
+            return String::from("Nothing was sent: the rejection could not be signed!");
+        }
+    };
+    if let Err(e) = proposed {
+        error!("propose_safe_transaction error: {}", e);
+        return String::from("Nothing was sent: the rejection could not be proposed!");
+    }
 
-    Ok(())
+    format!(
+        "🚫 Rejection proposed for Safe nonce {}, once the owners execute it the proposal can't be executed anymore: {}",
+        proposal.nonce,
+        safe::app_url(safe, &rejection_hash)
+    )
 }
 
-async fn scan_token(bot: Bot, msg: Message) -> HandlerResult {
-    let loading_message_id = loading_message(&bot, &msg).await;
-    let contract = parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username())
-        .unwrap()
-        .1
-        .join("");
+async fn route_alerts(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
 
-    if utils::is_valid_eth_address(contract.trim()) {
-        match api::get_token_info(contract.trim().to_owned()).await {
-            Ok(token_info) => {
-                let mut warning = false;
-                let mut info = format!(
-                    "Scan result for: \n📄 {}\n\n💎 {} ({})\n⚖️ ({}%, {}%)\n💵 ${}\n{}\n\n🚨 Warnings:",
-                    token_info.contract_address,
-                    token_info.name,
-                    token_info.symbol,
-                    token_info.buy_tax,
-                    token_info.sell_tax,
-                    token_info.liquidity.floor().separate_with_commas(),
-                    hyperlinks_from_contract(&token_info.contract_address)
-                );
+    if args.len() != 2 {
+        let routes = alerts::ALERT_ROUTES.lock().await;
+        let mut message = String::from(
+            "Usage: /route <watch|sniper|gas|digest|walletAddress> <chatId|off>\n\nCurrent routes:",
+        );
 
-                if token_info.is_honeypot {
-                    info = format!(
-                        "{}\n❌ {}",
-                        info,
-                        token_info
-                            .honeypot_reason
-                            .unwrap_or(String::from("TOKEN IS A HONEYPOT"))
-                    );
-                    warning = true;
+        match routes.get(&msg.chat.id) {
+            Some(chat_routes)
+                if !chat_routes.categories.is_empty() || !chat_routes.wallets.is_empty() =>
+            {
+                for (category, destination) in &chat_routes.categories {
+                    message.push_str(&format!("\n{} ➡️ {}", category, destination));
                 }
-
-                if token_info.flags_description.is_some() {
-                    for desc in token_info.flags_description.clone().unwrap() {
-                        info = format!("{}\n❌ {}", info, desc);
-                    }
-                    warning = true;
+                for (wallet, destination) in &chat_routes.wallets {
+                    message.push_str(&format!("\n{} ➡️ {}", wallet, destination));
                 }
+            }
+            _ => message.push_str("\nAll alerts are sent to this chat"),
+        }
 
-                if token_info.has_proxy_calls.unwrap_or(false) {
-                    info = info + "\n❌ Contract has proxy calls!";
-                    warning = true;
-                }
+        groups::reply(&bot, &msg, message).await?;
+        return Ok(());
+    }
 
-                if !token_info.is_open_source.unwrap_or(true) {
-                    info = info + "\n❌ Contract is not open source!";
-                    warning = true;
-                }
+    let category = AlertCategory::from_str(&args[0].to_lowercase()).ok();
+    let wallet = utils::is_valid_eth_address(args[0]).then(|| args[0].to_lowercase());
 
-                if token_info.liquidity < 5000.0 {
-                    info = info + "\n❌ Liquidity is very small!";
-                    warning = true;
-                }
+    if category.is_none() && wallet.is_none() {
+        groups::reply(
+            &bot,
+            &msg,
+            "Route cancelled: submitted alert category or wallet is incorrect!",
+        )
+        .await?;
+        return Ok(());
+    }
 
-                match api::is_contract_renounced(token_info.contract_address.clone()).await {
-                    Some(response) => {
-                        if !response {
-                            info = info + "\n❌ Contract is not renounced!";
-                            warning = true;
-                        }
-                    }
-                    None => {}
-                }
+    let destination = if args[1] == "off" {
+        None
+    } else {
+        match args[1].parse::<i64>() {
+            Ok(id) => Some(ChatId(id)),
+            Err(_) => {
+                groups::reply(
+                    &bot,
+                    &msg,
+                    "Route cancelled: submitted chat id is incorrect!",
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    };
 
-                match api::is_liquidity_locked(token_info.contract_address.clone()).await {
-                    Some(response) => {
-                        if !response {
-                            info = info + "\n❌ Liquidity might not be locked!";
-                            warning = true;
-                        }
-                    }
-                    None => {}
-                }
+    // make sure the bot is a member of the destination and allowed to post there
+    if let Some(destination) = destination {
+        if let Err(e) = bot
+            .send_message(
+                destination,
+                format!(
+                    "✅ {} alerts from chat {} will be posted here",
+                    args[0], msg.chat.id
+                ),
+            )
+            .await
+        {
+            warn!("route validation error: {}", e);
+            groups::reply(
+                &bot,
+                &msg,
+                "Route cancelled: the bot can't post to the submitted chat!",
+            )
+            .await?;
+            return Ok(());
+        }
+    }
 
-                if !warning {
-                    info = info + "\n✅ There were no warnings found";
-                }
+    let mut routes = alerts::ALERT_ROUTES.lock().await;
+    let chat_routes = routes.entry(msg.chat.id).or_default();
 
-                bot.delete_message(msg.chat.id, loading_message_id).await?;
-                bot.send_message(msg.chat.id, info)
-                    .parse_mode(ParseMode::Html)
-                    .disable_web_page_preview(true)
-                    .await?;
-            }
-            Err(e) => {
-                error!("get_token_info error: {}", e);
-                bot.send_message(
-                    msg.chat.id,
-                    format!("Something went wrong, please try again later"),
+    match (category, wallet, destination) {
+        (Some(category), _, Some(destination)) => {
+            chat_routes.categories.insert(category, destination);
+        }
+        (Some(category), _, None) => {
+            chat_routes.categories.remove(&category);
+        }
+        (None, Some(wallet), Some(destination)) => {
+            chat_routes.wallets.insert(wallet, destination);
+        }
+        (None, Some(wallet), None) => {
+            chat_routes.wallets.remove(&wallet);
+        }
+        (None, None, _) => {}
+    }
+
+    groups::reply(&bot, &msg, "Alert route updated!").await?;
+
+    Ok(())
+}
+
+async fn set_alert_priority(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+
+    let category = args
+        .first()
+        .and_then(|c| AlertCategory::from_str(&c.to_lowercase()).ok());
+    let priority = args
+        .get(1)
+        .and_then(|p| Priority::from_str(&p.to_lowercase()).ok());
+
+    match (category, priority) {
+        (Some(category), Some(priority)) => {
+            alerts::NOTIFICATION_PREFERENCES
+                .lock()
+                .await
+                .entry(msg.chat.id)
+                .or_default()
+                .priorities
+                .insert(category, priority);
+
+            groups::reply(
+                &bot,
+                &msg,
+                format!("{} alerts are now {} priority!", category, priority),
+            )
+            .await?;
+        }
+        _ => {
+            groups::reply(
+                &bot,
+                &msg,
+                "Usage: /priority <watch|sniper|gas|digest> <high|normal|low>\n\n\
+                high: always notifies\nnormal: silent during quiet hours\nlow: always silent",
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn set_quiet_hours(bot: Bot, msg: Message) -> HandlerResult {
+    let (_, args) =
+        parse_command(msg.text().unwrap(), bot.get_me().await.unwrap().username()).unwrap();
+    let arg = args.join("");
+
+    let quiet_hours = if arg == "off" {
+        None
+    } else {
+        let hours: Vec<Option<u32>> = arg
+            .split('-')
+            .map(|hour| hour.parse::<u32>().ok().filter(|hour| *hour < 24))
+            .collect();
+
+        match hours[..] {
+            [Some(start), Some(end)] if start != end => Some((start, end)),
+            _ => {
+                groups::reply(
+                    &bot,
+                    &msg,
+                    "Usage: /quiet <startHour-endHour|off> in the chat's /locale time zone, e.g. /quiet 23-7",
                 )
                 .await?;
+                return Ok(());
             }
         }
-    } else {
-        bot.delete_message(msg.chat.id, loading_message_id).await?;
-        bot.send_message(msg.chat.id, format!("The submitted contract is not valid!"))
-            .await?;
-    }
+    };
+
+    alerts::NOTIFICATION_PREFERENCES
+        .lock()
+        .await
+        .entry(msg.chat.id)
+        .or_default()
+        .quiet_hours = quiet_hours;
+
+    match quiet_hours {
+        Some((start, end)) => {
+            groups::reply(
+                &bot,
+                &msg,
+                format!(
+                "Quiet hours set from {}:00 to {}:00 ({}), only high priority alerts will notify",
+                start, end, SETTINGS.get(msg.chat.id).await.locale.timezone.name()
+            ),
+            )
+            .await?
+        }
+        None => groups::reply(&bot, &msg, "Quiet hours are off").await?,
+    };
 
     Ok(())
 }
 
 async fn change_settings(bot: Bot, msg: Message, dialogue: MyDialogue) -> HandlerResult {
-    bot.send_message(msg.chat.id, "Settings:")
+    groups::set_prompt_owner(msg.chat.id, msg.from().map(|user| user.id)).await;
+    let prompt = groups::reply(&bot, &msg, "Settings:")
         .reply_markup(make_settings_keyboard())
         .await?;
+    prompts::opened(msg.chat.id, prompt.id).await;
     dialogue.update(State::Settings).await?;
 
     Ok(())
@@ -698,67 +8313,102 @@ async fn change_settings(bot: Bot, msg: Message, dialogue: MyDialogue) -> Handle
 
 async fn confirm_settings(bot: Bot, dialogue: MyDialogue, q: CallbackQuery) -> HandlerResult {
     let chat_id = q.chat_id().unwrap();
-    let mut settings = SETTINGS.lock().await;
-    let mut change_settings: HashMap<ChatId, Settings> = settings.to_owned();
+    bot.answer_callback_query(q.id).await?;
 
-    match q.data {
-        Some(callback) => {
-            bot.answer_callback_query(q.id).await?;
+    // TODO: figure out how to accept multiple callbackQuerys without being stuck in the settings state
+    let message_id = q.message.unwrap().id;
+    prompts::resolved(chat_id, message_id).await;
+    bot.delete_message(chat_id, message_id).await?;
 
-            // TODO: figure out how to accept multiple callbackQuerys without being stuck in the settings state
-            bot.delete_message(chat_id, q.message.unwrap().id).await?;
+    let Some(toggle) = q.data.as_deref().and_then(SettingsToggle::from_callback) else {
+        bot.send_message(
+            chat_id,
+            format!("Something went wrong with the button handling"),
+        )
+        .await?;
+        dialogue.exit().await?;
+        return Ok(());
+    };
 
-            if callback == "hide_zero_balance" {
-                change_settings
-                    .entry(chat_id.clone())
-                    .and_modify(|value| {
-                        value.hide_zero_token_balances = !value.hide_zero_token_balances
-                    })
-                    .or_insert(Settings {
-                        hide_zero_token_balances: true,
-                        ..Default::default()
-                    });
-
-                if !change_settings
-                    .get(&chat_id)
-                    .unwrap()
-                    .hide_zero_token_balances
-                {
-                    bot.send_message(chat_id, format!("Zero token balances are NOT hidden!"))
-                        .await?;
-                } else {
-                    bot.send_message(chat_id, format!("Zero token balances are hidden!"))
-                        .await?;
-                }
-            } else if callback == "snipe_new_tokens" {
-                change_settings
-                    .entry(chat_id.clone())
-                    .and_modify(|value| value.snipe_new_tokens = !value.snipe_new_tokens)
-                    .or_insert(Settings {
-                        snipe_new_tokens: true,
-                        ..Default::default()
-                    });
-
-                if !change_settings.get(&chat_id).unwrap().snipe_new_tokens {
-                    bot.send_message(chat_id, format!("New tokens are NOT sniped!"))
-                        .await?;
-                } else {
-                    bot.send_message(chat_id, format!("New tokens are sniped!"))
-                        .await?;
-                }
-            }
-        }
-        None => {
-            bot.send_message(
-                chat_id,
-                format!("Something went wrong with the button handling"),
-            )
-            .await?;
-        }
+    if toggle == SettingsToggle::SnipeNewTokens && !has_premium(chat_id).await {
+        bot.send_message(
+            chat_id,
+            "Sniping new tokens is only available for premium chats!",
+        )
+        .await?;
+        dialogue.exit().await?;
+        return Ok(());
     }
 
-    *settings = change_settings;
+    if toggle == SettingsToggle::SameBlockSnipes && owner_id() != Some(q.from.id.0) {
+        bot.send_message(
+            chat_id,
+            "Only the bot owner can turn on same-block launch snipes!",
+        )
+        .await?;
+        dialogue.exit().await?;
+        return Ok(());
+    }
+
+    // linked chats share the toggles
+    let linked_chats = profiles::linked_chats(chat_id).await;
+    let enabled = SETTINGS.toggle(chat_id, &linked_chats, toggle).await;
+
+    let response = match (toggle, enabled) {
+        (SettingsToggle::HideZeroBalances, false) => "Zero token balances are NOT hidden!",
+        (SettingsToggle::HideZeroBalances, true) => "Zero token balances are hidden!",
+        (SettingsToggle::ClassicApprovals, false) => "Sells use Permit2 and the Universal Router!",
+        (SettingsToggle::ClassicApprovals, true) => "Sells use classic approve + swap!",
+        (SettingsToggle::DirectRoutesOnly, false) => {
+            "Trades can route through intermediate tokens!"
+        }
+        (SettingsToggle::DirectRoutesOnly, true) => "Trades only use direct WETH pools!",
+        (SettingsToggle::SnipeNewTokens, false) => "New tokens are NOT sniped!",
+        (SettingsToggle::SnipeNewTokens, true) => "New tokens are sniped!",
+        (SettingsToggle::SameBlockSnipes, false) => "Same-block launch snipes are turned off!",
+        (SettingsToggle::SameBlockSnipes, true) => {
+            "Same-block launch snipes are turned on! Launches registered with /launchsnipe are bought in the block their liquidity is added, only register tokens you trust."
+        }
+    };
+    bot.send_message(chat_id, response).await?;
     dialogue.exit().await?;
 
     Ok(())
 }
+
+#[test]
+fn test_portfolio_action_callback_data() {
+    let contract = "0x6982508145454ce325ddbe47a25d4ec3d2311933";
+    let sell = PortfolioAction::Sell {
+        contract: contract.to_owned(),
+        percent: 25,
+    };
+
+    assert_eq!(PortfolioAction::parse(&sell.callback_data()), Some(sell));
+    assert_eq!(
+        PortfolioAction::parse(&format!("scan:{}", contract)),
+        Some(PortfolioAction::Scan(contract.to_owned()))
+    );
+    assert_eq!(PortfolioAction::parse(&format!("sell0:{}", contract)), None);
+    let buy = PortfolioAction::Buy {
+        contract: contract.to_owned(),
+        usd: 100,
+    };
+    assert_eq!(PortfolioAction::parse(&buy.callback_data()), Some(buy));
+    assert_eq!(PortfolioAction::parse(&format!("buy0:{}", contract)), None);
+    assert_eq!(PortfolioAction::parse("wallet:1"), None);
+    assert_eq!(PortfolioAction::parse("yes"), None);
+}
+
+#[test]
+fn test_command_menu_limits() {
+    // telegram rejects the whole menu if a single entry is out of bounds
+    for command in Command::bot_commands() {
+        assert!(command.command.len() <= 32, "{}", command.command);
+        assert!(
+            (3..=256).contains(&command.description.chars().count()),
+            "{}",
+            command.command
+        );
+    }
+}
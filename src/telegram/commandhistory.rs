@@ -0,0 +1,158 @@
+use crate::{persist, telegram::lanes::REPORT_COMMANDS};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use teloxide::types::{ChatId, UserId};
+use tokio::sync::Mutex;
+
+// commands kept of every member of a chat, newest first
+pub const MAX_COMMANDS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    // the command as it was sent, without the bot's @username
+    pub text: String,
+    // numbered per member, sending it again keeps it so the re-run buttons of an older
+    // /historycmd still work
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct MemberHistory {
+    // newest first
+    commands: Vec<HistoryEntry>,
+    next_id: u64,
+}
+
+/*
+
+/last re-runs the member's previous command and /historycmd lists their last
+MAX_COMMANDS with a button to re-run each one, handy for scanning the same
+token over and over during a launch. A command sent again moves to the top
+instead of being listed twice. The history is kept per chat and member, so in
+groups everyone gets their own, and it's saved to COMMAND_HISTORY_FILE.
+
+Only the read-only reports and /scan are kept. A re-run goes through the same
+handlers without asking again, so a trade, a setting or anything carrying a
+PIN replayed with one tap could do real damage.
+
+*/
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandHistory {
+    // chat -> member -> their commands
+    chats: HashMap<i64, HashMap<u64, MemberHistory>>,
+}
+
+// the command's name in lowercase, without the slash and the bot's @username
+fn command_name(text: &str) -> Option<String> {
+    let command = text.split_whitespace().next()?.strip_prefix('/')?;
+    Some(command.split('@').next()?.to_lowercase())
+}
+
+fn is_replayable(name: &str) -> bool {
+    name == "scan" || REPORT_COMMANDS.contains(&name)
+}
+
+impl CommandHistory {
+    pub fn record(&mut self, chat_id: i64, user_id: u64, text: &str) -> bool {
+        let Some(name) = command_name(text).filter(|name| is_replayable(name)) else {
+            return false;
+        };
+
+        // '/scan@bot X' and '/scan X' are the same command, the arguments are kept as they were
+        let args = text
+            .trim()
+            .split_once(char::is_whitespace)
+            .map(|(_, args)| args.trim())
+            .unwrap_or_default();
+        let text = if args.is_empty() {
+            format!("/{}", name)
+        } else {
+            format!("/{} {}", name, args)
+        };
+
+        let member = self
+            .chats
+            .entry(chat_id)
+            .or_default()
+            .entry(user_id)
+            .or_default();
+        let id = match member.commands.iter().position(|entry| entry.text == text) {
+            Some(i) => member.commands.remove(i).id,
+            None => {
+                member.next_id += 1;
+                member.next_id
+            }
+        };
+        member.commands.insert(0, HistoryEntry { text, id });
+        member.commands.truncate(MAX_COMMANDS);
+
+        true
+    }
+
+    pub fn commands(&self, chat_id: i64, user_id: u64) -> Vec<HistoryEntry> {
+        self.chats
+            .get(&chat_id)
+            .and_then(|members| members.get(&user_id))
+            .map(|member| member.commands.clone())
+            .unwrap_or_default()
+    }
+}
+
+lazy_static! {
+    static ref COMMAND_HISTORY: Mutex<CommandHistory> =
+        Mutex::new(persist::load(persist::COMMAND_HISTORY).unwrap_or_default());
+}
+
+pub async fn record(chat_id: ChatId, user_id: UserId, text: &str) {
+    let mut history = COMMAND_HISTORY.lock().await;
+
+    if history.record(chat_id.0, user_id.0, text) {
+        persist::save(persist::COMMAND_HISTORY, &*history);
+    }
+}
+
+pub async fn commands(chat_id: ChatId, user_id: UserId) -> Vec<HistoryEntry> {
+    COMMAND_HISTORY.lock().await.commands(chat_id.0, user_id.0)
+}
+
+#[test]
+fn test_command_history() {
+    let mut history = CommandHistory::default();
+
+    assert!(history.record(1, 7, "/scan@snipers_bot  PEPE"));
+    assert!(history.record(1, 7, "/portfolio"));
+    assert!(!history.record(1, 7, "/last"));
+    assert!(!history.record(1, 7, "/tradelock pin 1234"));
+    assert!(!history.record(1, 7, "gm"));
+    // trades and settings would be replayed without asking again
+    assert!(!history.record(1, 7, "/buy PEPE 0.1"));
+    assert!(!history.record(1, 7, "/unwatch 0xabc"));
+    // sent again, it moves to the top
+    assert!(history.record(1, 7, "/scan PEPE"));
+
+    assert_eq!(
+        history.commands(1, 7),
+        vec![
+            HistoryEntry {
+                text: String::from("/scan PEPE"),
+                id: 1
+            },
+            HistoryEntry {
+                text: String::from("/portfolio"),
+                id: 2
+            },
+        ]
+    );
+    // other members and chats have their own
+    assert!(history.commands(1, 8).is_empty());
+    assert!(history.commands(2, 7).is_empty());
+
+    for i in 0..20 {
+        history.record(1, 7, &format!("/gas {}", i));
+    }
+    let commands = history.commands(1, 7);
+    assert_eq!(commands.len(), MAX_COMMANDS);
+    assert_eq!(commands[0].text, "/gas 19");
+    assert_eq!(commands[0].id, 22);
+}
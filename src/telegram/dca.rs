@@ -0,0 +1,204 @@
+use crate::{
+    tasks,
+    telegram::{bot, locale::Locale},
+};
+use chrono::{DateTime, Duration, Utc};
+use core::fmt;
+use lazy_static::lazy_static;
+use teloxide::prelude::*;
+use tokio::{sync::Mutex, time::sleep};
+
+// plans run at most this often, so a typo can't turn into a buy every minute
+const MIN_INTERVAL_MINUTES: i64 = 10;
+
+#[derive(Debug, Clone)]
+pub struct DcaPlan {
+    pub id: u32,
+    pub chat_id: ChatId,
+    pub contract: String,
+    pub symbol: String,
+    pub amount_usd: f64,
+    pub interval: Duration,
+    // false proposes every buy with a one-tap button instead of executing it
+    pub automatic: bool,
+    pub paused: bool,
+    pub next_run: DateTime<Utc>,
+    pub executions: u32,
+}
+
+impl fmt::Display for DcaPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.describe(&Locale::default()))
+    }
+}
+
+impl DcaPlan {
+    // the next run in the chat's time zone
+    pub fn describe(&self, locale: &Locale) -> String {
+        format!(
+            "#{} {} ${} every {}{}, {} buys{}",
+            self.id,
+            self.symbol,
+            self.amount_usd,
+            format_interval(self.interval),
+            if self.automatic { "" } else { " (confirm)" },
+            self.executions,
+            if self.paused {
+                String::from(", ⏸ paused")
+            } else {
+                format!(
+                    ", next at {} {}",
+                    locale.time(self.next_run, "%m-%d %H:%M"),
+                    locale.zone(self.next_run)
+                )
+            }
+        )
+    }
+}
+
+lazy_static! {
+    static ref DCA_PLANS: Mutex<Vec<DcaPlan>> = Mutex::new(vec![]);
+    static ref NEXT_ID: Mutex<u32> = Mutex::new(1);
+}
+
+// '30m', '4h', '1d' or '1w'
+pub fn parse_interval(interval: &str) -> Option<Duration> {
+    let unit = interval.chars().last()?;
+    let amount = interval[..interval.len() - unit.len_utf8()]
+        .parse::<i64>()
+        .ok()?;
+
+    let interval = match unit {
+        'm' => Duration::try_minutes(amount)?,
+        'h' => Duration::try_hours(amount)?,
+        'd' => Duration::try_days(amount)?,
+        'w' => Duration::try_weeks(amount)?,
+        _ => return None,
+    };
+
+    if interval < Duration::try_minutes(MIN_INTERVAL_MINUTES)? {
+        return None;
+    }
+
+    Some(interval)
+}
+
+fn format_interval(interval: Duration) -> String {
+    let minutes = interval.num_minutes();
+
+    if minutes % (60 * 24 * 7) == 0 {
+        format!("{}w", minutes / (60 * 24 * 7))
+    } else if minutes % (60 * 24) == 0 {
+        format!("{}d", minutes / (60 * 24))
+    } else if minutes % 60 == 0 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+// the first buy runs right away
+pub async fn add(
+    chat_id: ChatId,
+    contract: String,
+    symbol: String,
+    amount_usd: f64,
+    interval: Duration,
+    automatic: bool,
+) -> DcaPlan {
+    let mut next_id = NEXT_ID.lock().await;
+    let plan = DcaPlan {
+        id: *next_id,
+        chat_id,
+        contract,
+        symbol,
+        amount_usd,
+        interval,
+        automatic,
+        paused: false,
+        next_run: Utc::now(),
+        executions: 0,
+    };
+    *next_id += 1;
+
+    DCA_PLANS.lock().await.push(plan.clone());
+    plan
+}
+
+pub async fn list(chat_id: ChatId) -> Vec<DcaPlan> {
+    DCA_PLANS
+        .lock()
+        .await
+        .iter()
+        .filter(|plan| plan.chat_id == chat_id)
+        .cloned()
+        .collect()
+}
+
+pub async fn get(chat_id: ChatId, id: u32) -> Option<DcaPlan> {
+    list(chat_id).await.into_iter().find(|plan| plan.id == id)
+}
+
+// resuming doesn't catch up on missed buys, the next one runs a full interval later
+pub async fn set_paused(chat_id: ChatId, id: u32, paused: bool) -> Option<DcaPlan> {
+    let mut plans = DCA_PLANS.lock().await;
+    let plan = plans
+        .iter_mut()
+        .find(|plan| plan.chat_id == chat_id && plan.id == id)?;
+
+    if plan.paused && !paused {
+        plan.next_run = Utc::now() + plan.interval;
+    }
+    plan.paused = paused;
+
+    Some(plan.clone())
+}
+
+pub async fn remove(chat_id: ChatId, id: u32) -> Option<DcaPlan> {
+    let mut plans = DCA_PLANS.lock().await;
+    let index = plans
+        .iter()
+        .position(|plan| plan.chat_id == chat_id && plan.id == id)?;
+
+    Some(plans.remove(index))
+}
+
+// moves every due plan to its next run and returns them
+async fn take_due_plans(now: DateTime<Utc>) -> Vec<DcaPlan> {
+    let mut plans = DCA_PLANS.lock().await;
+    let mut due = vec![];
+
+    for plan in plans.iter_mut() {
+        if !plan.paused && plan.next_run <= now {
+            due.push(plan.clone());
+            plan.next_run = now + plan.interval;
+        }
+    }
+
+    due
+}
+
+pub async fn run_dca_plans(bot: Bot) {
+    loop {
+        sleep(Duration::try_minutes(1).unwrap().to_std().unwrap()).await;
+        info!("DCA cycle...");
+        tasks::heartbeat("dca").await;
+
+        for plan in take_due_plans(Utc::now()).await {
+            bot::dca_buy(&bot, &plan).await;
+        }
+    }
+}
+
+#[test]
+fn test_parse_interval() {
+    assert_eq!(parse_interval("30m"), Duration::try_minutes(30));
+    assert_eq!(parse_interval("4h"), Duration::try_hours(4));
+    assert_eq!(parse_interval("1w"), Duration::try_weeks(1));
+    assert_eq!(parse_interval("5m"), None);
+    assert_eq!(parse_interval("h"), None);
+    assert_eq!(parse_interval("2x"), None);
+
+    assert_eq!(format_interval(Duration::try_days(2).unwrap()), "2d");
+    assert_eq!(format_interval(Duration::try_minutes(90).unwrap()), "90m");
+}
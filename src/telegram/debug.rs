@@ -0,0 +1,213 @@
+use crate::{
+    api::{self, CacheStats},
+    telegram::{bot::WATCHED_WALLETS, locale::Locale, prompts},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{collections::BTreeMap, str::FromStr};
+
+// shared by the sections shown, so the message stays within telegram's limit, the json file has everything
+const MAX_LINES: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugSection {
+    Tokens,
+    Wallets,
+    Prompts,
+    Caches,
+}
+
+impl FromStr for DebugSection {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tokens" => Ok(DebugSection::Tokens),
+            "wallets" => Ok(DebugSection::Wallets),
+            "prompts" => Ok(DebugSection::Prompts),
+            "caches" => Ok(DebugSection::Caches),
+            _ => Err(()),
+        }
+    }
+}
+
+/*
+
+What the background loops are working from when an expected alert didn't
+fire: the tokens the token watches poll and the chats they alert, the newest
+transaction the wallet watch has seen of every watched wallet (a wallet with
+none wasn't polled yet, or its history couldn't be fetched), the prompts still
+waiting for a button and how often the response caches answered. It's the
+state of this instance only, other shards have their own.
+
+*/
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DebugSnapshot {
+    pub taken_at: i64,
+    // lowercase contract -> the chats it's monitored for
+    pub monitored_tokens: BTreeMap<String, Vec<i64>>,
+    // chat -> watched wallet -> timestamp of the newest transaction seen
+    pub watched_wallets: BTreeMap<i64, BTreeMap<String, Option<u64>>>,
+    // (chat, message)
+    pub pending_prompts: Vec<(i64, i32)>,
+    pub caches: BTreeMap<&'static str, CacheStats>,
+}
+
+pub async fn snapshot() -> DebugSnapshot {
+    let mut monitored_tokens = BTreeMap::new();
+    for (contract, chat_ids) in api::monitored_proxy_tokens().await {
+        let mut chat_ids: Vec<i64> = chat_ids.iter().map(|chat_id| chat_id.0).collect();
+        chat_ids.sort();
+        monitored_tokens.insert(contract, chat_ids);
+    }
+
+    let last_seen = api::get_last_seen_transactions().await;
+    let mut watched_wallets = BTreeMap::new();
+    for (chat_id, wallets) in WATCHED_WALLETS.lock().await.iter() {
+        let seen = last_seen.get(chat_id);
+        let wallets: BTreeMap<String, Option<u64>> = wallets
+            .iter()
+            .map(|wallet| (wallet.clone(), seen.and_then(|s| s.get(wallet)).copied()))
+            .collect();
+        watched_wallets.insert(chat_id.0, wallets);
+    }
+
+    DebugSnapshot {
+        taken_at: Utc::now().timestamp(),
+        monitored_tokens,
+        watched_wallets,
+        pending_prompts: prompts::pending()
+            .await
+            .iter()
+            .map(|(chat_id, message_id)| (chat_id.0, message_id.0))
+            .collect(),
+        caches: api::get_cache_stats().await.into_iter().collect(),
+    }
+}
+
+impl DebugSnapshot {
+    pub fn render(&self, sections: &[DebugSection], locale: &Locale) -> String {
+        let taken_at = DateTime::<Utc>::from_timestamp(self.taken_at, 0).unwrap_or_default();
+        let mut message = format!(
+            "🛠 State at {} ({})",
+            locale.time(taken_at, "%Y-%m-%d %H:%M:%S"),
+            locale.zone(taken_at)
+        );
+
+        let max_lines = MAX_LINES / sections.len().max(1);
+        for section in sections {
+            let (title, lines) = match section {
+                DebugSection::Tokens => (
+                    format!("Monitored tokens ({})", self.monitored_tokens.len()),
+                    self.monitored_tokens
+                        .iter()
+                        .map(|(contract, chat_ids)| format!("{} → chats {:?}", contract, chat_ids))
+                        .collect::<Vec<String>>(),
+                ),
+                DebugSection::Wallets => {
+                    let mut lines = vec![];
+                    for (chat_id, wallets) in &self.watched_wallets {
+                        for (wallet, seen) in wallets {
+                            let seen = match seen
+                                .and_then(|seen| DateTime::<Utc>::from_timestamp(seen as i64, 0))
+                            {
+                                Some(seen) => format!(
+                                    "last seen {} ({})",
+                                    locale.time(seen, "%Y-%m-%d %H:%M:%S"),
+                                    locale.zone(seen)
+                                ),
+                                None => String::from("not polled yet"),
+                            };
+                            lines.push(format!("{} {}: {}", chat_id, wallet, seen));
+                        }
+                    }
+                    (format!("Watched wallets ({})", lines.len()), lines)
+                }
+                DebugSection::Prompts => (
+                    format!("Pending prompts ({})", self.pending_prompts.len()),
+                    self.pending_prompts
+                        .iter()
+                        .map(|(chat_id, message_id)| {
+                            format!("chat {} message {}", chat_id, message_id)
+                        })
+                        .collect(),
+                ),
+                DebugSection::Caches => (
+                    String::from("Caches (hits | misses | entries)"),
+                    self.caches
+                        .iter()
+                        .map(|(name, stats)| {
+                            format!(
+                                "{}: {} | {} | {}{}",
+                                name,
+                                stats.hits,
+                                stats.misses,
+                                stats.entries,
+                                stats
+                                    .hit_percent()
+                                    .map(|percent| format!(", {:.0}% hits", percent))
+                                    .unwrap_or_default()
+                            )
+                        })
+                        .collect(),
+                ),
+            };
+
+            message.push_str(&format!("\n\n{}:", title));
+            for line in lines.iter().take(max_lines) {
+                message.push_str(&format!("\n{}", line));
+            }
+            if lines.len() > max_lines {
+                message.push_str(&format!(
+                    "\n... and {} more, /debug json has all of them",
+                    lines.len() - max_lines
+                ));
+            }
+        }
+
+        message
+    }
+}
+
+#[test]
+fn test_debug_render() {
+    let snapshot = DebugSnapshot {
+        taken_at: 1_700_000_000,
+        monitored_tokens: BTreeMap::from([(String::from("0xtoken"), vec![-100, 42])]),
+        watched_wallets: BTreeMap::from([(
+            42,
+            BTreeMap::from([
+                (String::from("0xfresh"), None),
+                (String::from("0xwhale"), Some(1_699_999_940)),
+            ]),
+        )]),
+        pending_prompts: vec![(42, 7)],
+        caches: BTreeMap::from([
+            (
+                "address_labels",
+                CacheStats {
+                    hits: 3,
+                    misses: 1,
+                    entries: 1,
+                },
+            ),
+            ("funders", CacheStats::default()),
+        ]),
+    };
+
+    let all = [
+        DebugSection::Tokens,
+        DebugSection::Wallets,
+        DebugSection::Prompts,
+        DebugSection::Caches,
+    ];
+    assert_eq!(
+        snapshot.render(&all, &Locale::default()),
+        "🛠 State at 2023-11-14 22:13:20 (UTC)\
+         \n\nMonitored tokens (1):\n0xtoken → chats [-100, 42]\
+         \n\nWatched wallets (2):\n42 0xfresh: not polled yet\n42 0xwhale: last seen 2023-11-14 22:12:20 (UTC)\
+         \n\nPending prompts (1):\nchat 42 message 7\
+         \n\nCaches (hits | misses | entries):\naddress_labels: 3 | 1 | 1, 75% hits\nfunders: 0 | 0 | 0"
+    );
+    assert_eq!("wallets".parse::<DebugSection>(), Ok(DebugSection::Wallets));
+}
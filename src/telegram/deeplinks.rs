@@ -0,0 +1,114 @@
+use lazy_static::lazy_static;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::Mutex;
+
+// the scans a chat without premium can run from links in the window
+const USER_SCANS: usize = 3;
+const USER_WINDOW_SECONDS: i64 = 10 * 60;
+// for all of them together, so a link shared widely doesn't spend the providers' quota
+const GLOBAL_SCANS: usize = 20;
+const GLOBAL_WINDOW_SECONDS: i64 = 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeepLink {
+    // t.me/<bot>?start=link-<code>
+    Link(String),
+    // t.me/<bot>?start=scan_<contract>
+    Scan(String),
+}
+
+// the '/start' payload, None for a plain /start or one that isn't a deep link of the bot
+pub fn parse(payload: &str) -> Option<DeepLink> {
+    if let Some(code) = payload.strip_prefix("link-") {
+        return Some(DeepLink::Link(code.to_owned()));
+    }
+
+    payload
+        .strip_prefix("scan_")
+        .filter(|contract| !contract.is_empty())
+        .map(|contract| DeepLink::Scan(contract.to_owned()))
+}
+
+#[derive(Debug, Default)]
+pub struct ScanLimits {
+    // chat -> timestamps of its scans from links in the window, oldest first
+    by_chat: HashMap<i64, VecDeque<i64>>,
+    all: VecDeque<i64>,
+}
+
+lazy_static! {
+    static ref SCAN_LIMITS: Mutex<ScanLimits> = Mutex::new(ScanLimits::default());
+}
+
+fn expire(scans: &mut VecDeque<i64>, now: i64, window: i64) {
+    while scans.front().is_some_and(|at| now - at >= window) {
+        scans.pop_front();
+    }
+}
+
+/*
+
+Links to the bot on a website open it for people who never used it, and every
+scan costs provider requests. Chats without premium can run a few scans from
+links every ten minutes and all of them together twenty a minute, a scan
+that's over the limit says when the next one can run. /scan itself isn't
+limited, it's only the links that can be clicked by anyone.
+
+*/
+impl ScanLimits {
+    // Err with the seconds until the next scan can run
+    pub fn allow(&mut self, chat_id: i64, now: i64) -> Result<(), i64> {
+        expire(&mut self.all, now, GLOBAL_WINDOW_SECONDS);
+        let chat = self.by_chat.entry(chat_id).or_default();
+        expire(chat, now, USER_WINDOW_SECONDS);
+
+        if chat.len() >= USER_SCANS {
+            return Err(chat[0] + USER_WINDOW_SECONDS - now);
+        }
+        if self.all.len() >= GLOBAL_SCANS {
+            return Err(self.all[0] + GLOBAL_WINDOW_SECONDS - now);
+        }
+
+        chat.push_back(now);
+        self.all.push_back(now);
+        self.by_chat.retain(|_, scans| !scans.is_empty());
+
+        Ok(())
+    }
+}
+
+pub async fn allow_scan(chat_id: i64, now: i64) -> Result<(), i64> {
+    SCAN_LIMITS.lock().await.allow(chat_id, now)
+}
+
+#[test]
+fn test_deep_links() {
+    assert_eq!(
+        parse("scan_0xdAC17F958D2ee523a2206206994597C13D831ec7"),
+        Some(DeepLink::Scan(String::from(
+            "0xdAC17F958D2ee523a2206206994597C13D831ec7"
+        )))
+    );
+    assert_eq!(
+        parse("link-AB12CD"),
+        Some(DeepLink::Link(String::from("AB12CD")))
+    );
+    assert_eq!(parse("scan_"), None);
+    assert_eq!(parse("promo"), None);
+
+    let mut limits = ScanLimits::default();
+    let now = 1_700_000_000;
+    for i in 0..USER_SCANS as i64 {
+        assert_eq!(limits.allow(1, now + i), Ok(()));
+    }
+    assert_eq!(limits.allow(1, now + 60), Err(USER_WINDOW_SECONDS - 60));
+    assert_eq!(limits.allow(1, now + USER_WINDOW_SECONDS), Ok(()));
+
+    // the other chats share what's left of the global limit
+    let mut limits = ScanLimits::default();
+    for chat_id in 0..GLOBAL_SCANS as i64 {
+        assert_eq!(limits.allow(chat_id, now), Ok(()));
+    }
+    assert_eq!(limits.allow(100, now + 20), Err(GLOBAL_WINDOW_SECONDS - 20));
+    assert_eq!(limits.allow(100, now + GLOBAL_WINDOW_SECONDS), Ok(()));
+}
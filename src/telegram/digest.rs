@@ -0,0 +1,233 @@
+use crate::{
+    api, backup,
+    crypto::{
+        history,
+        positions::{self, RealizedTrade, TradeActivity},
+        wallet,
+    },
+    tasks,
+    telegram::{
+        alerts::{self, AlertCategory},
+        bot::SETTINGS,
+        locale::Locale,
+    },
+};
+use chrono::{Duration, Utc};
+use core::fmt;
+use std::env;
+use teloxide::{types::ChatId, Bot};
+use tokio::time::sleep;
+
+// UTC hour of the daily digest
+const DEFAULT_HOUR: u32 = 20;
+
+fn hour() -> u32 {
+    env::var("DIGEST_HOUR")
+        .ok()
+        .and_then(|hour| hour.parse::<u32>().ok())
+        .filter(|hour| *hour < 24)
+        .unwrap_or(DEFAULT_HOUR)
+}
+
+/*
+
+The owner's summary of the last 24 hours: the portfolio value from the hourly
+snapshots /chart uses, and the trading activity from the positions ledger with
+the gas the trading wallets paid. The ledger is kept in memory, so trades from
+before a restart aren't in it. A reading that couldn't be taken is left out.
+
+*/
+#[derive(Debug, Clone, Default)]
+pub struct DailyDigest {
+    pub value_usd: Option<f64>,
+    pub change_percent: Option<f64>,
+    pub activity: TradeActivity,
+    // (eth, usd)
+    pub gas: Option<(f64, f64)>,
+    // the owner chat's
+    pub locale: Locale,
+}
+
+fn sign(value: f64) -> &'static str {
+    if value >= 0.0 {
+        "+"
+    } else {
+        "-"
+    }
+}
+
+fn signed_usd(locale: &Locale, usd: f64) -> String {
+    format!("{}${}", sign(usd), locale.number(usd.abs(), 2))
+}
+
+fn trade_line(locale: &Locale, trade: &RealizedTrade) -> String {
+    format!(
+        "{} {} ({}{}%)",
+        trade.symbol,
+        signed_usd(locale, trade.pnl_usd()),
+        sign(trade.pnl_percent()),
+        locale.number(trade.pnl_percent().abs(), 1)
+    )
+}
+
+impl fmt::Display for DailyDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let locale = &self.locale;
+        write!(f, "📊 Daily summary")?;
+        match (self.value_usd, self.change_percent) {
+            (Some(value_usd), Some(change)) => write!(
+                f,
+                "\n💼 Portfolio: ${} (24h {}{}%)",
+                locale.number(value_usd, 2),
+                sign(change),
+                locale.number(change.abs(), 2)
+            )?,
+            (Some(value_usd), None) => {
+                write!(f, "\n💼 Portfolio: ${}", locale.number(value_usd, 2))?
+            }
+            _ => {}
+        }
+
+        let activity = &self.activity;
+        write!(f, "\n\n🔁 Trading activity (24h)")?;
+        if activity.buys + activity.sells == 0 {
+            write!(f, "\nNo trades")?;
+        } else {
+            write!(
+                f,
+                "\n🧮 Trades: {} ({} buys, {} sells)\n💵 Volume: ${}",
+                activity.buys + activity.sells,
+                activity.buys,
+                activity.sells,
+                locale.number(activity.volume_usd, 2)
+            )?;
+        }
+        if let Some((eth, usd)) = self.gas {
+            write!(
+                f,
+                "\n⛽ Gas spent: {} ETH (${})",
+                locale.number(eth, 4),
+                locale.number(usd, 2)
+            )?;
+        }
+        if activity.sells > 0 {
+            write!(
+                f,
+                "\n{} Realized PnL: {}",
+                if activity.realized_pnl_usd >= 0.0 {
+                    "🟢"
+                } else {
+                    "🔴"
+                },
+                signed_usd(locale, activity.realized_pnl_usd)
+            )?;
+        }
+        if let Some(best) = &activity.best {
+            write!(f, "\n🏆 Best: {}", trade_line(locale, best))?;
+        }
+        // one sell is both the best and the worst
+        if let Some(worst) = activity.worst.as_ref().filter(|_| activity.sells > 1) {
+            write!(f, "\n💀 Worst: {}", trade_line(locale, worst))?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn daily_digest(locale: Locale) -> DailyDigest {
+    let snapshots = history::since(1).await;
+    let activity = positions::POSITIONS
+        .lock()
+        .await
+        .activity(Utc::now() - Duration::try_days(1).unwrap());
+
+    let (gas, eth_price) = tokio::join!(
+        api::get_gas_spent(wallet::trading_wallets(), &[1]),
+        api::get_eth_price()
+    );
+    let gas = match (gas, eth_price) {
+        (Ok(gas), Ok(eth_price)) => gas.first().map(|gas| (gas.eth, gas.eth * eth_price)),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("daily digest gas error: {}", e);
+            None
+        }
+    };
+
+    DailyDigest {
+        value_usd: snapshots.last().map(|snapshot| snapshot.value_usd),
+        change_percent: history::change_percent(&snapshots, Duration::try_days(1).unwrap()),
+        activity,
+        gas,
+        locale,
+    }
+}
+
+pub async fn send_daily_digests(bot: Bot, owner_chat: ChatId) {
+    loop {
+        sleep(backup::until_next(Utc::now(), hour()).to_std().unwrap()).await;
+        tasks::heartbeat("daily_digest").await;
+
+        let digest = daily_digest(SETTINGS.get(owner_chat).await.locale).await;
+
+        if let Err(e) = alerts::send_alert(
+            &bot,
+            owner_chat,
+            AlertCategory::Digest,
+            None,
+            digest.to_string(),
+        )
+        .await
+        .await
+        {
+            error!("failed to send the daily digest: {}", e);
+        }
+    }
+}
+
+#[test]
+fn test_daily_digest() {
+    let trade = |symbol: &str, proceeds_usd: f64, cost_basis_usd: f64| RealizedTrade {
+        contract: String::from("0xabc"),
+        symbol: String::from(symbol),
+        tokens: 100.0,
+        proceeds_usd,
+        cost_basis_usd,
+        unmatched_tokens: 0.0,
+        remaining_tokens: 0.0,
+        closed_at: Utc::now(),
+    };
+    let digest = DailyDigest {
+        value_usd: Some(12_345.678),
+        change_percent: Some(3.2),
+        activity: TradeActivity {
+            buys: 3,
+            sells: 2,
+            volume_usd: 1_234.0,
+            realized_pnl_usd: 123.45,
+            best: Some(trade("PEPE", 750.0, 600.0)),
+            worst: Some(trade("RUG", 40.0, 66.55)),
+        },
+        gas: Some((0.01234, 45.67)),
+        locale: Locale::default(),
+    };
+
+    assert_eq!(
+        digest.to_string(),
+        "📊 Daily summary\n💼 Portfolio: $12,345.68 (24h +3.20%)\
+         \n\n🔁 Trading activity (24h)\n🧮 Trades: 5 (3 buys, 2 sells)\n💵 Volume: $1,234.00\
+         \n⛽ Gas spent: 0.0123 ETH ($45.67)\n🟢 Realized PnL: +$123.45\
+         \n🏆 Best: PEPE +$150.00 (+25.0%)\n💀 Worst: RUG -$26.55 (-39.9%)"
+    );
+
+    let mut eu = digest.clone();
+    assert!(eu.locale.set("eu"));
+    assert!(eu
+        .to_string()
+        .starts_with("📊 Daily summary\n💼 Portfolio: $12.345,68 (24h +3,20%)"));
+
+    let quiet = DailyDigest::default();
+    assert_eq!(
+        quiet.to_string(),
+        "📊 Daily summary\n\n🔁 Trading activity (24h)\nNo trades"
+    );
+}
@@ -0,0 +1,323 @@
+use crate::{
+    persist, tasks,
+    telegram::{
+        alerts::{ALERT_ROUTES, WALLET_FILTERS},
+        bot::{self, WATCHED_TOKENS, WATCHED_WALLETS},
+        profiles, shards,
+        walletimport::WALLET_LABELS,
+    },
+};
+use chrono::{DateTime, Duration, Utc};
+use core::fmt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env};
+use teloxide::prelude::*;
+use tokio::{sync::Mutex, time::sleep};
+
+const DEFAULT_TTL_DAYS: i64 = 30;
+// the expiry counts days, an alert only saves the file again when the last one is older than this
+const SAVE_INTERVAL_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WatchKind {
+    Wallet,
+    Token,
+}
+
+impl fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WatchKind::Wallet => write!(f, "wallet"),
+            WatchKind::Token => write!(f, "token"),
+        }
+    }
+}
+
+impl WatchKind {
+    // WATCH_WALLET_TTL_DAYS and WATCH_TOKEN_TTL_DAYS, 0 keeps the watches until they're removed
+    pub fn ttl(&self) -> Option<Duration> {
+        let var = match self {
+            WatchKind::Wallet => "WATCH_WALLET_TTL_DAYS",
+            WatchKind::Token => "WATCH_TOKEN_TTL_DAYS",
+        };
+        let days = env::var(var)
+            .ok()
+            .and_then(|days| days.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_TTL_DAYS);
+
+        Duration::try_days(days).filter(|_| days > 0)
+    }
+
+    fn watchlist(&self) -> &'static Mutex<HashMap<ChatId, Vec<String>>> {
+        match self {
+            WatchKind::Wallet => &*WATCHED_WALLETS,
+            WatchKind::Token => &*WATCHED_TOKENS,
+        }
+    }
+
+    pub fn renew_callback_data(&self, entry: &str) -> String {
+        format!("renew:{}:{}", self, entry)
+    }
+
+    pub fn parse_renew_callback(data: &str) -> Option<(WatchKind, String)> {
+        let (kind, entry) = data.strip_prefix("renew:")?.split_once(':')?;
+        let kind = match kind {
+            "wallet" => WatchKind::Wallet,
+            "token" => WatchKind::Token,
+            _ => return None,
+        };
+
+        Some((kind, entry.to_owned()))
+    }
+}
+
+type LastActive = HashMap<(ChatId, WatchKind, String), DateTime<Utc>>;
+
+lazy_static! {
+    // (chat, kind, lowercase entry) -> the last alert, or when the entry was added or renewed.
+    // saved to WATCH_ACTIVITY_FILE, so a restart doesn't reset the inactivity clock
+    static ref LAST_ACTIVE: Mutex<LastActive> = Mutex::new(load());
+}
+
+// json objects can't have tuple keys, the file is a list of entries
+fn load() -> LastActive {
+    persist::load::<Vec<(ChatId, WatchKind, String, DateTime<Utc>)>>(persist::WATCH_ACTIVITY)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(chat_id, kind, entry, last)| ((chat_id, kind, entry), last))
+        .collect()
+}
+
+fn save(last_active: &LastActive) {
+    let entries: Vec<_> = last_active
+        .iter()
+        .map(|((chat_id, kind, entry), last)| (chat_id, kind, entry, last))
+        .collect();
+
+    persist::save(persist::WATCH_ACTIVITY, &entries);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InactiveWatch {
+    pub chat_id: ChatId,
+    pub kind: WatchKind,
+    pub entry: String,
+    pub inactive: Duration,
+}
+
+pub async fn touch(chat_id: ChatId, kind: WatchKind, entry: &str) {
+    let now = Utc::now();
+    let mut last_active = LAST_ACTIVE.lock().await;
+    let previous = last_active.insert((chat_id, kind, entry.to_lowercase()), now);
+
+    if previous.map_or(true, |previous| {
+        now - previous >= Duration::try_minutes(SAVE_INTERVAL_MINUTES).unwrap()
+    }) {
+        save(&last_active);
+    }
+}
+
+// entries seen for the first time start counting now, so a restart doesn't expire everything at once
+fn inactive_watches(
+    last_active: &mut LastActive,
+    kind: WatchKind,
+    watched: &HashMap<ChatId, Vec<String>>,
+    now: DateTime<Utc>,
+    min_inactive: Duration,
+) -> Vec<InactiveWatch> {
+    last_active.retain(|(chat_id, k, entry), _| {
+        *k != kind
+            || watched
+                .get(chat_id)
+                .is_some_and(|entries| entries.iter().any(|e| e.eq_ignore_ascii_case(entry)))
+    });
+
+    let mut inactive = vec![];
+    for (chat_id, entries) in watched {
+        for entry in entries {
+            let last = *last_active
+                .entry((*chat_id, kind, entry.to_lowercase()))
+                .or_insert(now);
+
+            if now - last >= min_inactive {
+                inactive.push(InactiveWatch {
+                    chat_id: *chat_id,
+                    kind,
+                    entry: entry.clone(),
+                    inactive: now - last,
+                });
+            }
+        }
+    }
+    inactive.sort_by(|a, b| b.inactive.cmp(&a.inactive));
+
+    inactive
+}
+
+pub async fn inactive_for(kind: WatchKind, min_inactive: Duration) -> Vec<InactiveWatch> {
+    let watched = kind.watchlist().lock().await.clone();
+    let mut last_active = LAST_ACTIVE.lock().await;
+
+    let inactive = inactive_watches(&mut last_active, kind, &watched, Utc::now(), min_inactive);
+    save(&last_active);
+
+    inactive
+}
+
+// linked chats share their watchlists, the entry is removed from all of them. wallet filters and
+// routes are dropped with the wallet, a new watch starts without them
+pub async fn remove(chat_id: ChatId, kind: WatchKind, entry: &str) {
+    let linked_chats = profiles::linked_chats(chat_id).await;
+
+    let mut watchlists = kind.watchlist().lock().await;
+    for linked_chat in &linked_chats {
+        if let Some(entries) = watchlists.get_mut(linked_chat) {
+            entries.retain(|e| !e.eq_ignore_ascii_case(entry));
+            if entries.is_empty() {
+                watchlists.remove(linked_chat);
+            }
+        }
+    }
+    drop(watchlists);
+
+    let mut last_active = LAST_ACTIVE.lock().await;
+    for linked_chat in linked_chats {
+        last_active.remove(&(linked_chat, kind, entry.to_lowercase()));
+    }
+    save(&last_active);
+    drop(last_active);
+
+    prune_orphans().await;
+}
+
+// in every linked chat, returns false if the entry was still watched and only its timer was reset
+pub async fn renew(chat_id: ChatId, kind: WatchKind, entry: &str) -> bool {
+    let linked_chats = profiles::linked_chats(chat_id).await;
+
+    let mut watchlists = kind.watchlist().lock().await;
+    let mut added = false;
+    for linked_chat in &linked_chats {
+        let entries = watchlists.entry(*linked_chat).or_default();
+        if !entries.iter().any(|e| e.eq_ignore_ascii_case(entry)) {
+            entries.push(entry.to_owned());
+            added |= *linked_chat == chat_id;
+        }
+    }
+    drop(watchlists);
+
+    for linked_chat in linked_chats {
+        touch(linked_chat, kind, entry).await;
+    }
+
+    added
+}
+
+// filters, labels and routes of wallets the chat no longer watches, returns how many were removed
+pub async fn prune_orphans() -> usize {
+    let watched = WATCHED_WALLETS.lock().await.clone();
+    let is_watched = |chat_id: &ChatId, wallet: &str| {
+        watched
+            .get(chat_id)
+            .is_some_and(|wallets| wallets.iter().any(|w| w.eq_ignore_ascii_case(wallet)))
+    };
+    let mut removed = 0;
+
+    for (chat_id, filters) in WALLET_FILTERS.lock().await.iter_mut() {
+        let before = filters.len();
+        filters.retain(|wallet, _| is_watched(chat_id, wallet));
+        removed += before - filters.len();
+    }
+    for (chat_id, labels) in WALLET_LABELS.lock().await.iter_mut() {
+        let before = labels.len();
+        labels.retain(|wallet, _| is_watched(chat_id, wallet));
+        removed += before - labels.len();
+    }
+    for (chat_id, routes) in ALERT_ROUTES.lock().await.iter_mut() {
+        let before = routes.wallets.len();
+        routes
+            .wallets
+            .retain(|wallet, _| is_watched(chat_id, wallet));
+        removed += before - routes.wallets.len();
+    }
+
+    removed
+}
+
+pub async fn expire_watches(bot: Bot) {
+    loop {
+        sleep(Duration::try_hours(1).unwrap().to_std().unwrap()).await;
+        info!("Watch expiry cycle...");
+        tasks::heartbeat("watch_expiry").await;
+
+        for kind in [WatchKind::Wallet, WatchKind::Token] {
+            let Some(ttl) = kind.ttl() else {
+                continue;
+            };
+
+            for watch in inactive_for(kind, ttl).await {
+                // the alerts that keep a watch active are only seen by the shard of its chat
+                if !shards::owns(watch.chat_id).await {
+                    continue;
+                }
+                remove(watch.chat_id, watch.kind, &watch.entry).await;
+                let _ = bot::watch_expired_notification(&bot, &watch).await;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_inactive_watches() {
+    let now = Utc::now();
+    let days = |days: i64| Duration::try_days(days).unwrap();
+    let (chat, other) = (ChatId(1), ChatId(2));
+
+    let mut last_active = HashMap::new();
+    last_active.insert(
+        (chat, WatchKind::Wallet, String::from("0xa")),
+        now - days(40),
+    );
+    last_active.insert(
+        (chat, WatchKind::Wallet, String::from("0xb")),
+        now - days(3),
+    );
+    // no longer watched
+    last_active.insert(
+        (other, WatchKind::Wallet, String::from("0xa")),
+        now - days(90),
+    );
+    last_active.insert(
+        (other, WatchKind::Token, String::from("0xt")),
+        now - days(90),
+    );
+
+    let watched = HashMap::from([(
+        chat,
+        vec![
+            String::from("0xA"),
+            String::from("0xb"),
+            String::from("0xc"),
+        ],
+    )]);
+
+    let inactive = inactive_watches(&mut last_active, WatchKind::Wallet, &watched, now, days(30));
+    assert_eq!(inactive.len(), 1);
+    assert_eq!(
+        (inactive[0].entry.as_str(), inactive[0].inactive),
+        ("0xA", days(40))
+    );
+
+    // the new wallet starts counting now, the unwatched one is forgotten, other kinds are kept
+    assert_eq!(
+        last_active[&(chat, WatchKind::Wallet, String::from("0xc"))],
+        now
+    );
+    assert!(!last_active.contains_key(&(other, WatchKind::Wallet, String::from("0xa"))));
+    assert!(last_active.contains_key(&(other, WatchKind::Token, String::from("0xt"))));
+
+    assert_eq!(
+        WatchKind::parse_renew_callback(&WatchKind::Token.renew_callback_data("0xt")),
+        Some((WatchKind::Token, String::from("0xt")))
+    );
+}
@@ -0,0 +1,147 @@
+use core::fmt;
+use lazy_static::lazy_static;
+use std::{collections::HashMap, str::FromStr};
+use teloxide::{
+    payloads::{SendDocumentSetters, SendMessageSetters, SendPhotoSetters},
+    prelude::*,
+    requests::{JsonRequest, MultipartRequest},
+    types::{Chat, InputFile, UserId},
+};
+use tokio::sync::Mutex;
+
+// ordered from least to most privileged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Permission {
+    // read-only commands (scans, gas, portfolio)
+    #[default]
+    Viewer,
+    // can also buy, sell and rescue transactions
+    Trader,
+    // can also change settings, watchlists and alert routing
+    Admin,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Permission::Viewer => write!(f, "viewer"),
+            Permission::Trader => write!(f, "trader"),
+            Permission::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Permission::Viewer),
+            "trader" => Ok(Permission::Trader),
+            "admin" => Ok(Permission::Admin),
+            _ => Err(()),
+        }
+    }
+}
+
+lazy_static! {
+    // group members without an entry are viewers
+    static ref MEMBER_PERMISSIONS: Mutex<HashMap<ChatId, HashMap<UserId, Permission>>> =
+        Mutex::new(HashMap::<ChatId, HashMap<UserId, Permission>>::new());
+    // the member who opened the chat's current inline keyboard prompt (trade confirm, settings, token pick)
+    static ref PROMPT_OWNERS: Mutex<HashMap<ChatId, UserId>> =
+        Mutex::new(HashMap::<ChatId, UserId>::new());
+}
+
+pub fn is_group(chat: &Chat) -> bool {
+    chat.is_group() || chat.is_supergroup()
+}
+
+// private chats belong to a single user, so permissions only apply to groups
+pub async fn permission(chat: &Chat, user_id: Option<UserId>) -> Permission {
+    if !is_group(chat) {
+        return Permission::Admin;
+    }
+
+    match user_id {
+        Some(user_id) => MEMBER_PERMISSIONS
+            .lock()
+            .await
+            .get(&chat.id)
+            .and_then(|members| members.get(&user_id))
+            .copied()
+            .unwrap_or_default(),
+        None => Permission::Viewer,
+    }
+}
+
+pub async fn set_permission(chat_id: ChatId, user_id: UserId, permission: Permission) {
+    let mut permissions = MEMBER_PERMISSIONS.lock().await;
+    let members = permissions.entry(chat_id).or_default();
+
+    if permission == Permission::default() {
+        members.remove(&user_id);
+    } else {
+        members.insert(user_id, permission);
+    }
+}
+
+pub async fn set_prompt_owner(chat_id: ChatId, user_id: Option<UserId>) {
+    let mut prompt_owners = PROMPT_OWNERS.lock().await;
+
+    match user_id {
+        Some(user_id) => prompt_owners.insert(chat_id, user_id),
+        None => prompt_owners.remove(&chat_id),
+    };
+}
+
+// prompts without a recorded owner can be answered by anyone
+pub async fn is_prompt_owner(chat_id: ChatId, user_id: UserId) -> bool {
+    PROMPT_OWNERS
+        .lock()
+        .await
+        .get(&chat_id)
+        .map_or(true, |owner| *owner == user_id)
+}
+
+// in groups the reply is threaded to the command, so parallel users can tell whose answer it is
+pub fn reply<T: Into<String>>(
+    bot: &Bot,
+    msg: &Message,
+    text: T,
+) -> JsonRequest<teloxide::payloads::SendMessage> {
+    let request = bot.send_message(msg.chat.id, text);
+
+    if is_group(&msg.chat) {
+        request.reply_to_message_id(msg.id)
+    } else {
+        request
+    }
+}
+
+pub fn reply_photo(
+    bot: &Bot,
+    msg: &Message,
+    photo: InputFile,
+) -> MultipartRequest<teloxide::payloads::SendPhoto> {
+    let request = bot.send_photo(msg.chat.id, photo);
+
+    if is_group(&msg.chat) {
+        request.reply_to_message_id(msg.id)
+    } else {
+        request
+    }
+}
+
+pub fn reply_document(
+    bot: &Bot,
+    msg: &Message,
+    document: InputFile,
+) -> MultipartRequest<teloxide::payloads::SendDocument> {
+    let request = bot.send_document(msg.chat.id, document);
+
+    if is_group(&msg.chat) {
+        request.reply_to_message_id(msg.id)
+    } else {
+        request
+    }
+}
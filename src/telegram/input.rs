@@ -0,0 +1,96 @@
+use crate::utils;
+
+const EIP681_SCHEME: &str = "ethereum:";
+
+// a bare address or the target of an EIP-681 uri, e.g. 'ethereum:pay-0x...@1/transfer?address=0x...'.
+// token transfer uris point at the token contract, that's what /scan needs
+pub fn parse_address(text: &str) -> Option<String> {
+    let text = text.trim();
+    let target = match text.get(..EIP681_SCHEME.len()) {
+        Some(scheme) if scheme.eq_ignore_ascii_case(EIP681_SCHEME) => {
+            let uri = &text[EIP681_SCHEME.len()..];
+            uri.strip_prefix("pay-")
+                .unwrap_or(uri)
+                .split(['@', '/', '?'])
+                .next()?
+        }
+        _ => text,
+    };
+
+    utils::is_valid_eth_address(target).then(|| target.to_owned())
+}
+
+// contents of every QR code found in the grayscale pixels
+fn decode_qr_codes(width: usize, height: usize, luma: &[u8]) -> Vec<String> {
+    let mut image =
+        rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| luma[y * width + x]);
+
+    image
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| grid.decode().ok().map(|(_, content)| content))
+        .collect()
+}
+
+fn to_luma(pixels: &[u8], format: jpeg_decoder::PixelFormat) -> Vec<u8> {
+    match format {
+        jpeg_decoder::PixelFormat::L8 => pixels.to_vec(),
+        // big endian, the high byte is enough
+        jpeg_decoder::PixelFormat::L16 => pixels.chunks(2).map(|p| p[0]).collect(),
+        jpeg_decoder::PixelFormat::RGB24 => pixels
+            .chunks(3)
+            .map(|p| ((p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000) as u8)
+            .collect(),
+        // cmyk photos are rare, the key channel is enough for black codes
+        jpeg_decoder::PixelFormat::CMYK32 => pixels.chunks(4).map(|p| 255 - p[3]).collect(),
+    }
+}
+
+// the first address in the QR codes of a Telegram photo, photos are always sent as jpeg
+pub fn address_from_photo(jpeg: &[u8]) -> Result<Option<String>, String> {
+    let mut decoder = jpeg_decoder::Decoder::new(jpeg);
+    let pixels = decoder.decode().map_err(|e| e.to_string())?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| String::from("missing image info"))?;
+    let luma = to_luma(&pixels, info.pixel_format);
+
+    Ok(
+        decode_qr_codes(info.width as usize, info.height as usize, &luma)
+            .iter()
+            .find_map(|content| parse_address(content)),
+    )
+}
+
+#[test]
+fn test_parse_address() {
+    let address = "0x11DDACb10c3891e356dcE6D7c6F22DD69c93E2Cd";
+    let token = "0x6982508145454ce325ddbe47a25d4ec3d2311933";
+
+    assert_eq!(parse_address(address), Some(address.to_owned()));
+    assert_eq!(
+        parse_address(&format!("ethereum:{}@1?value=50000000000000000", address)),
+        Some(address.to_owned())
+    );
+    assert_eq!(
+        parse_address(&format!(
+            "ethereum:pay-{}@1/transfer?address={}&uint256=1",
+            token, address
+        )),
+        Some(token.to_owned())
+    );
+    assert_eq!(
+        parse_address("bitcoin:bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh"),
+        None
+    );
+
+    // the codes /receive sends are read back
+    let (pixels, size) =
+        crate::crypto::payment::qr_code_pixels(&format!("ethereum:{}@1", address)).unwrap();
+    assert_eq!(
+        decode_qr_codes(size, size, &pixels)
+            .iter()
+            .find_map(|content| parse_address(content)),
+        Some(address.to_owned())
+    );
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+use teloxide::types::{ChatId, Update, UpdateKind};
+
+// reports that don't read or change the chat's dialogue, so they can run next to everything else
+pub const REPORT_COMMANDS: [&str; 12] = [
+    "portfolio",
+    "gas",
+    "gasspent",
+    "inspect",
+    "creator",
+    "recentbuys",
+    "whales",
+    "compare",
+    "sandwich",
+    "status",
+    "sniperstats",
+    "leaderboard",
+];
+// a report still waiting on its providers after this is given up on
+pub const REPORT_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lane {
+    // commands and the answers to their prompts, in the order they were sent
+    Commands,
+    Reports,
+    // button presses, confirmations can't wait for a command that's still running
+    Buttons,
+}
+
+/*
+
+The dispatcher handles the updates of a chat one at a time, so a /portfolio
+waiting 20 seconds on its providers used to hold up the /sell confirmation
+pressed right after it. Every chat has three lanes instead: commands and the
+text answers to their prompts stay in order in one of them, button presses get
+their own, and the read-only reports run in a third one and give up after
+REPORT_TIMEOUT. Updates within a lane are still handled one at a time.
+
+*/
+pub fn message_lane(text: &str) -> Lane {
+    let command = text
+        .split_whitespace()
+        .next()
+        .and_then(|command| command.strip_prefix('/'))
+        .and_then(|command| command.split('@').next())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    if REPORT_COMMANDS.contains(&command.as_str()) {
+        Lane::Reports
+    } else {
+        Lane::Commands
+    }
+}
+
+// the dispatcher's distribution function
+pub fn distribute(update: &Update) -> Option<(ChatId, Lane)> {
+    let lane = match &update.kind {
+        UpdateKind::CallbackQuery(_) => Lane::Buttons,
+        UpdateKind::Message(msg) => message_lane(msg.text().unwrap_or_default()),
+        _ => Lane::Commands,
+    };
+
+    update.chat().map(|chat| (chat.id, lane))
+}
+
+#[test]
+fn test_message_lane() {
+    assert_eq!(message_lane("/portfolio"), Lane::Reports);
+    assert_eq!(message_lane("/gas@snipers_bot 120000"), Lane::Reports);
+    assert_eq!(message_lane("/sell PEPE 100"), Lane::Commands);
+    // answers to prompts stay behind the command that asked for them
+    assert_eq!(message_lane("123456"), Lane::Commands);
+    assert_eq!(message_lane(""), Lane::Commands);
+}
@@ -0,0 +1,203 @@
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+// how a chat writes 1234.56
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum NumberStyle {
+    #[default]
+    En,
+    Eu,
+    Fr,
+    Ch,
+}
+
+impl NumberStyle {
+    // (thousands separator, decimal separator)
+    fn separators(&self) -> (&'static str, &'static str) {
+        match self {
+            NumberStyle::En => (",", "."),
+            NumberStyle::Eu => (".", ","),
+            // narrow no-break space, so numbers don't wrap in the middle
+            NumberStyle::Fr => ("\u{202f}", ","),
+            NumberStyle::Ch => ("'", "."),
+        }
+    }
+}
+
+impl fmt::Display for NumberStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberStyle::En => write!(f, "en"),
+            NumberStyle::Eu => write!(f, "eu"),
+            NumberStyle::Fr => write!(f, "fr"),
+            NumberStyle::Ch => write!(f, "ch"),
+        }
+    }
+}
+
+impl FromStr for NumberStyle {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(NumberStyle::En),
+            "eu" => Ok(NumberStyle::Eu),
+            "fr" => Ok(NumberStyle::Fr),
+            "ch" => Ok(NumberStyle::Ch),
+            _ => Err(()),
+        }
+    }
+}
+
+/*
+
+Every chat has a time zone and a number style, set with /locale. Times shown
+in alerts, scans and schedules are converted to the chat's zone and labeled
+with its abbreviation, and quiet hours are read in it too, so a chat in
+Europe/Berlin quiets 23-7 of its own night. Scheduled tasks keep running on
+UTC, only what the chat sees of them changes.
+
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Locale {
+    pub timezone: Tz,
+    pub numbers: NumberStyle,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale {
+            timezone: Tz::UTC,
+            numbers: NumberStyle::default(),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, numbers as {}",
+            self.timezone.name(),
+            self.number(1234567.89, 2)
+        )
+    }
+}
+
+impl Locale {
+    // a zone from its IANA name, any case, or a number style
+    pub fn set(&mut self, arg: &str) -> bool {
+        if let Ok(numbers) = arg.to_lowercase().parse::<NumberStyle>() {
+            self.numbers = numbers;
+            return true;
+        }
+
+        match Tz::from_str_insensitive(arg) {
+            Ok(timezone) => {
+                self.timezone = timezone;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    // e.g. '%Y-%m-%d %H:%M', the abbreviation of the zone is left to the caller with %Z
+    pub fn time(&self, time: DateTime<Utc>, format: &str) -> String {
+        time.with_timezone(&self.timezone)
+            .format(format)
+            .to_string()
+    }
+
+    // the zone's abbreviation at that time, CET or CEST in Europe/Berlin
+    pub fn zone(&self, time: DateTime<Utc>) -> String {
+        self.time(time, "%Z")
+    }
+
+    pub fn hour(&self, time: DateTime<Utc>) -> u32 {
+        time.with_timezone(&self.timezone).hour()
+    }
+
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let (_, decimal) = self.numbers.separators();
+        let formatted = format!("{:.*}", decimals, value.abs());
+        let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+        let grouped = self.group(integer);
+
+        // -0.001 with two decimals is 0.00, not -0.00
+        let rounds_to_zero = formatted.chars().all(|c| c == '0' || c == '.');
+        let sign = if value < 0.0 && !rounds_to_zero {
+            "-"
+        } else {
+            ""
+        };
+        if fraction.is_empty() {
+            format!("{}{}", sign, grouped)
+        } else {
+            format!("{}{}{}{}", sign, grouped, decimal, fraction)
+        }
+    }
+
+    // token prices can be tiny, they keep four significant digits and at least two decimals
+    pub fn price(&self, value: f64) -> String {
+        let decimals = if value.abs() >= 1.0 || value == 0.0 {
+            2
+        } else {
+            (3 - value.abs().log10().floor() as i32).max(2) as usize
+        };
+
+        self.number(value, decimals)
+    }
+
+    // counts and raw token amounts, an f64 doesn't keep every digit of those
+    pub fn integer(&self, value: u128) -> String {
+        self.group(&value.to_string())
+    }
+
+    fn group(&self, digits: &str) -> String {
+        let (thousands, _) = self.numbers.separators();
+        let mut grouped = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push_str(thousands);
+            }
+            grouped.push(digit);
+        }
+
+        grouped
+    }
+}
+
+#[test]
+fn test_locale() {
+    let mut locale = Locale::default();
+    assert_eq!(locale.number(1234567.891, 2), "1,234,567.89");
+    assert_eq!(locale.number(-999.0, 0), "-999");
+    assert_eq!(locale.number(-0.001, 2), "0.00");
+    assert_eq!(locale.price(1234.5), "1,234.50");
+    assert_eq!(locale.price(0.000012344), "0.00001234");
+
+    assert!(locale.set("EU"));
+    assert_eq!(locale.number(1234.5, 2), "1.234,50");
+    assert!(locale.set("ch"));
+    assert_eq!(locale.number(1000.0, 0), "1'000");
+    assert_eq!(
+        locale.integer(u128::MAX),
+        "340'282'366'920'938'463'463'374'607'431'768'211'455"
+    );
+    assert!(!locale.set("Mars/Olympus"));
+
+    let summer = DateTime::parse_from_rfc3339("2024-07-01T22:30:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    assert_eq!(locale.time(summer, "%Y-%m-%d %H:%M"), "2024-07-01 22:30");
+    assert_eq!(locale.zone(summer), "UTC");
+
+    assert!(locale.set("europe/berlin"));
+    assert_eq!(locale.time(summer, "%Y-%m-%d %H:%M"), "2024-07-02 00:30");
+    assert_eq!(locale.zone(summer), "CEST");
+    assert_eq!(locale.hour(summer), 0);
+    assert_eq!(locale.to_string(), "Europe/Berlin, numbers as 1'234'567.89");
+}
@@ -0,0 +1,218 @@
+use crate::api::{OwnedToken, TokenListEntry};
+use core::fmt;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use teloxide::types::ChatId;
+use tokio::sync::Mutex;
+
+// fixes for tokens the data providers get wrong, unset fields keep the provider's value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenMetadata {
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+    pub logo: Option<String>,
+}
+
+impl TokenMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == TokenMetadata::default()
+    }
+
+    // fields set on self win over the ones on fallback
+    fn or(self, fallback: TokenMetadata) -> TokenMetadata {
+        TokenMetadata {
+            name: self.name.or(fallback.name),
+            symbol: self.symbol.or(fallback.symbol),
+            decimals: self.decimals.or(fallback.decimals),
+            logo: self.logo.or(fallback.logo),
+        }
+    }
+
+    // the usd value is kept, a wrong decimals value skews the provider's price by the same factor
+    pub fn apply(&self, token: &mut OwnedToken) {
+        if let Some(name) = &self.name {
+            token.name = name.clone();
+        }
+        if let Some(symbol) = &self.symbol {
+            token.symbol = symbol.clone();
+        }
+        if let Some(logo) = &self.logo {
+            token.thumbnail = Some(logo.clone());
+        }
+        if let Some(decimals) = self.decimals {
+            token.balance *= 10.0f64.powi(token.decimals as i32 - decimals as i32);
+            token.decimals = decimals;
+        }
+    }
+}
+
+impl From<TokenListEntry> for TokenMetadata {
+    fn from(entry: TokenListEntry) -> Self {
+        TokenMetadata {
+            name: Some(entry.name),
+            symbol: Some(entry.symbol),
+            decimals: Some(entry.decimals),
+            logo: entry.logo_uri,
+        }
+    }
+}
+
+impl fmt::Display for TokenMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = vec![];
+
+        if let Some(symbol) = &self.symbol {
+            fields.push(format!("symbol={}", symbol));
+        }
+        if let Some(name) = &self.name {
+            fields.push(format!("name={}", name));
+        }
+        if let Some(decimals) = self.decimals {
+            fields.push(format!("decimals={}", decimals));
+        }
+        if let Some(logo) = &self.logo {
+            fields.push(format!("logo={}", logo));
+        }
+
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+// an imported token list, only one list per chat is kept
+#[derive(Debug, Clone, Default)]
+pub struct ImportedList {
+    pub name: String,
+    pub url: String,
+    tokens: HashMap<String, TokenMetadata>,
+}
+
+impl ImportedList {
+    pub fn token_count(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+lazy_static! {
+    // lowercase contract -> metadata set with /tokenmeta
+    static ref OVERRIDES: Mutex<HashMap<ChatId, HashMap<String, TokenMetadata>>> =
+        Mutex::new(HashMap::<ChatId, HashMap<String, TokenMetadata>>::new());
+    static ref TOKEN_LISTS: Mutex<HashMap<ChatId, ImportedList>> =
+        Mutex::new(HashMap::<ChatId, ImportedList>::new());
+}
+
+// merges the new fields into an existing override
+pub async fn set_override(
+    chat_id: ChatId,
+    contract: &str,
+    metadata: TokenMetadata,
+) -> TokenMetadata {
+    let mut overrides = OVERRIDES.lock().await;
+    let entry = overrides
+        .entry(chat_id)
+        .or_default()
+        .entry(contract.to_lowercase())
+        .or_default();
+
+    *entry = metadata.or(entry.clone());
+    entry.clone()
+}
+
+pub async fn remove_override(chat_id: ChatId, contract: &str) -> bool {
+    OVERRIDES
+        .lock()
+        .await
+        .get_mut(&chat_id)
+        .is_some_and(|overrides| overrides.remove(&contract.to_lowercase()).is_some())
+}
+
+pub async fn overrides(chat_id: ChatId) -> Vec<(String, TokenMetadata)> {
+    let mut overrides: Vec<(String, TokenMetadata)> = OVERRIDES
+        .lock()
+        .await
+        .get(&chat_id)
+        .map(|overrides| overrides.clone().into_iter().collect())
+        .unwrap_or_default();
+
+    overrides.sort_by(|a, b| a.0.cmp(&b.0));
+    overrides
+}
+
+pub async fn import_list(
+    chat_id: ChatId,
+    name: String,
+    url: String,
+    entries: Vec<TokenListEntry>,
+) -> ImportedList {
+    let list = ImportedList {
+        name,
+        url,
+        tokens: entries
+            .into_iter()
+            .map(|entry| (entry.address.to_lowercase(), TokenMetadata::from(entry)))
+            .collect(),
+    };
+
+    TOKEN_LISTS.lock().await.insert(chat_id, list.clone());
+    list
+}
+
+pub async fn remove_list(chat_id: ChatId) -> Option<ImportedList> {
+    TOKEN_LISTS.lock().await.remove(&chat_id)
+}
+
+pub async fn imported_list(chat_id: ChatId) -> Option<ImportedList> {
+    TOKEN_LISTS.lock().await.get(&chat_id).cloned()
+}
+
+// manual overrides take precedence over the imported list
+pub async fn get(chat_id: ChatId, contract: &str) -> TokenMetadata {
+    let contract = contract.to_lowercase();
+    let overridden = OVERRIDES
+        .lock()
+        .await
+        .get(&chat_id)
+        .and_then(|overrides| overrides.get(&contract).cloned())
+        .unwrap_or_default();
+    let listed = TOKEN_LISTS
+        .lock()
+        .await
+        .get(&chat_id)
+        .and_then(|list| list.tokens.get(&contract).cloned())
+        .unwrap_or_default();
+
+    overridden.or(listed)
+}
+
+#[test]
+fn test_apply_metadata() {
+    let mut token = OwnedToken {
+        name: String::from("Unknown"),
+        contract: String::from("0x6982508145454ce325ddbe47a25d4ec3d2311933"),
+        thumbnail: None,
+        symbol: String::new(),
+        balance: 1.5,
+        decimals: 18,
+        value_usd: 100.0,
+        usd_price_24hr_percent_change: 0.0,
+        portfolio_percentage: 0.0,
+    };
+    let overridden = TokenMetadata {
+        symbol: Some(String::from("PEPE")),
+        decimals: Some(9),
+        ..Default::default()
+    };
+    let listed = TokenMetadata {
+        name: Some(String::from("Pepe")),
+        symbol: Some(String::from("LISTED")),
+        ..Default::default()
+    };
+
+    overridden.or(listed).apply(&mut token);
+    assert_eq!(
+        (token.name.as_str(), token.symbol.as_str()),
+        ("Pepe", "PEPE")
+    );
+    assert!((token.balance - 1.5e9).abs() < 1e-3);
+    assert_eq!((token.decimals, token.value_usd), (9, 100.0));
+}
@@ -0,0 +1,175 @@
+use chrono::{DateTime, Duration, Utc};
+use core::fmt;
+use lazy_static::lazy_static;
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+};
+use teloxide::types::ChatId;
+use tokio::sync::Mutex;
+
+pub const LINK_CODE_MINUTES: i64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkError {
+    UnknownCode,
+    SameChat,
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LinkError::UnknownCode => write!(f, "the link code is invalid or expired"),
+            LinkError::SameChat => write!(f, "the link code was issued in this chat"),
+        }
+    }
+}
+
+// chats sharing one profile keep the same watchlists and settings
+#[derive(Debug, Default)]
+pub struct Profiles {
+    // chat -> profile, the profile is named after the chat that issued the first code
+    links: HashMap<ChatId, ChatId>,
+    // single use codes -> (issuing chat, expiry)
+    codes: HashMap<String, (ChatId, DateTime<Utc>)>,
+}
+
+impl Profiles {
+    pub fn issue_code(&mut self, chat_id: ChatId, now: DateTime<Utc>) -> String {
+        self.codes.retain(|_, (_, expires_at)| *expires_at > now);
+
+        let code = new_code();
+        self.codes.insert(
+            code.clone(),
+            (
+                chat_id,
+                now + Duration::try_minutes(LINK_CODE_MINUTES).unwrap(),
+            ),
+        );
+
+        code
+    }
+
+    // joins the issuing chat's profile, returns the issuing chat so its data can be copied over
+    pub fn redeem(
+        &mut self,
+        code: &str,
+        chat_id: ChatId,
+        now: DateTime<Utc>,
+    ) -> Result<ChatId, LinkError> {
+        let code = code.to_uppercase();
+        let Some((issuer, expires_at)) = self.codes.get(&code).copied() else {
+            return Err(LinkError::UnknownCode);
+        };
+        if expires_at <= now {
+            self.codes.remove(&code);
+            return Err(LinkError::UnknownCode);
+        }
+        if issuer == chat_id {
+            return Err(LinkError::SameChat);
+        }
+
+        self.codes.remove(&code);
+        self.unlink(chat_id);
+        let profile = self.links.get(&issuer).copied().unwrap_or(issuer);
+        self.links.insert(issuer, profile);
+        self.links.insert(chat_id, profile);
+
+        Ok(issuer)
+    }
+
+    // every chat sharing the profile, including the chat itself
+    pub fn linked_chats(&self, chat_id: ChatId) -> Vec<ChatId> {
+        let Some(profile) = self.links.get(&chat_id) else {
+            return vec![chat_id];
+        };
+
+        let mut chats: Vec<ChatId> = self
+            .links
+            .iter()
+            .filter(|(_, p)| *p == profile)
+            .map(|(chat, _)| *chat)
+            .collect();
+        chats.sort_by_key(|chat| chat.0);
+
+        chats
+    }
+
+    // the chat keeps its copy of the watchlists and settings
+    pub fn unlink(&mut self, chat_id: ChatId) -> bool {
+        let Some(profile) = self.links.remove(&chat_id) else {
+            return false;
+        };
+
+        // a profile of one chat isn't shared anymore
+        let remaining: Vec<ChatId> = self
+            .links
+            .iter()
+            .filter(|(_, p)| **p == profile)
+            .map(|(chat, _)| *chat)
+            .collect();
+        if remaining.len() == 1 {
+            self.links.remove(&remaining[0]);
+        }
+
+        true
+    }
+}
+
+lazy_static! {
+    pub static ref PROFILES: Mutex<Profiles> = Mutex::new(Profiles::default());
+}
+
+// RandomState is randomly seeded, random enough for short lived codes without a rand dependency
+fn new_code() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_i64(Utc::now().timestamp_nanos_opt().unwrap_or_default());
+
+    format!("{:08X}", hasher.finish() as u32)
+}
+
+pub async fn linked_chats(chat_id: ChatId) -> Vec<ChatId> {
+    PROFILES.lock().await.linked_chats(chat_id)
+}
+
+#[test]
+fn test_link_chats() {
+    let now = Utc::now();
+    let (private, group, other_group) = (ChatId(42), ChatId(-100), ChatId(-200));
+    let mut profiles = Profiles::default();
+
+    let code = profiles.issue_code(private, now);
+    assert_eq!(
+        profiles.redeem(&code, private, now),
+        Err(LinkError::SameChat)
+    );
+    assert_eq!(
+        profiles.redeem(&code, group, now + Duration::try_minutes(11).unwrap()),
+        Err(LinkError::UnknownCode)
+    );
+
+    let code = profiles.issue_code(private, now);
+    assert_eq!(
+        profiles.redeem(&code.to_lowercase(), group, now),
+        Ok(private)
+    );
+    // codes are single use
+    assert_eq!(
+        profiles.redeem(&code, other_group, now),
+        Err(LinkError::UnknownCode)
+    );
+
+    // a code issued by a linked chat joins the same profile
+    let code = profiles.issue_code(group, now);
+    assert_eq!(profiles.redeem(&code, other_group, now), Ok(group));
+    assert_eq!(
+        profiles.linked_chats(private),
+        vec![other_group, group, private]
+    );
+
+    assert!(profiles.unlink(group));
+    assert!(profiles.unlink(other_group));
+    assert!(!profiles.unlink(private));
+    assert_eq!(profiles.linked_chats(private), vec![private]);
+    assert_eq!(profiles.linked_chats(ChatId(7)), vec![ChatId(7)]);
+}
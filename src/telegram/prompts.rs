@@ -0,0 +1,78 @@
+use crate::persist;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use teloxide::{prelude::*, types::MessageId, RequestError};
+use tokio::sync::Mutex;
+
+const EXPIRED_TEXT: &str = "⌛ This prompt expired, please resubmit the command";
+
+// an inline keyboard message that is still waiting for a button press
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct PendingPrompt {
+    chat_id: i64,
+    message_id: i32,
+}
+
+lazy_static! {
+    static ref PENDING_PROMPTS: Mutex<Vec<PendingPrompt>> =
+        Mutex::new(persist::load(persist::PENDING_PROMPTS).unwrap_or_default());
+}
+
+pub async fn opened(chat_id: ChatId, message_id: MessageId) {
+    let mut prompts = PENDING_PROMPTS.lock().await;
+
+    prompts.push(PendingPrompt {
+        chat_id: chat_id.0,
+        message_id: message_id.0,
+    });
+    persist::save(persist::PENDING_PROMPTS, &*prompts);
+}
+
+pub async fn resolved(chat_id: ChatId, message_id: MessageId) {
+    let mut prompts = PENDING_PROMPTS.lock().await;
+    let count = prompts.len();
+
+    prompts.retain(|p| !(p.chat_id == chat_id.0 && p.message_id == message_id.0));
+    if prompts.len() != count {
+        persist::save(persist::PENDING_PROMPTS, &*prompts);
+    }
+}
+
+pub async fn pending() -> Vec<(ChatId, MessageId)> {
+    PENDING_PROMPTS
+        .lock()
+        .await
+        .iter()
+        .map(|prompt| (ChatId(prompt.chat_id), MessageId(prompt.message_id)))
+        .collect()
+}
+
+// editing the text also drops the keyboard, so the stale buttons can't be pressed
+pub async fn expire(bot: &Bot, chat_id: ChatId, message_id: MessageId) -> Result<(), RequestError> {
+    bot.edit_message_text(chat_id, message_id, EXPIRED_TEXT)
+        .await?;
+
+    Ok(())
+}
+
+// dialogue states and trade parameters only live in memory, so prompts from before a restart can't be answered
+pub async fn expire_all(bot: &Bot) {
+    let mut prompts = PENDING_PROMPTS.lock().await;
+
+    if !prompts.is_empty() {
+        info!(
+            "Expiring {} prompts left over from the last run",
+            prompts.len()
+        );
+    }
+
+    for prompt in prompts.drain(..) {
+        if let Err(e) = expire(bot, ChatId(prompt.chat_id), MessageId(prompt.message_id)).await {
+            warn!(
+                "failed to expire prompt {} in chat {}: {}",
+                prompt.message_id, prompt.chat_id, e
+            );
+        }
+    }
+    persist::save(persist::PENDING_PROMPTS, &*prompts);
+}
@@ -0,0 +1,202 @@
+use crate::{api::HoneypotTokenInfo, telegram::locale::Locale};
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::Mutex;
+
+// the least recently scanned tokens are dropped past this
+const MAX_CACHED_SCANS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ScanField {
+    // honeypot.is data and everything derived from it
+    Warnings,
+    Supply,
+    Holdings,
+    Concentration,
+    SizeTaxes,
+    // max tx, max wallet and the other limits on buyers, lifted soon after launch
+    AntiBot,
+    Locker,
+    // where the creator's eth came from
+    Funding,
+    Template,
+}
+
+impl ScanField {
+    pub const ALL: [ScanField; 9] = [
+        ScanField::Warnings,
+        ScanField::Supply,
+        ScanField::Holdings,
+        ScanField::Concentration,
+        ScanField::SizeTaxes,
+        ScanField::AntiBot,
+        ScanField::Locker,
+        ScanField::Funding,
+        ScanField::Template,
+    ];
+
+    // how long a field is shown before a refresh fetches it again
+    fn max_age(&self) -> Duration {
+        match self {
+            ScanField::Warnings | ScanField::SizeTaxes | ScanField::AntiBot => {
+                Duration::try_minutes(5).unwrap()
+            }
+            ScanField::Supply | ScanField::Holdings => Duration::try_minutes(10).unwrap(),
+            ScanField::Concentration => Duration::try_minutes(15).unwrap(),
+            // lockers are rarely added after the launch, a creator's funding never changes but
+            // its funders can be flagged later
+            ScanField::Locker | ScanField::Funding => Duration::try_hours(1).unwrap(),
+            // verified source doesn't change
+            ScanField::Template => Duration::try_days(1).unwrap(),
+        }
+    }
+}
+
+/*
+
+Scans are kept as cards, the rendered lines of every field with the time they
+were fetched. Refreshing a card only fetches the fields older than their
+max_age, and shared cards are answered from here without any request. The
+numbers in the fields are written in the locale of the chat that last
+refreshed the card, a chat with another locale writes every field again.
+
+*/
+#[derive(Debug, Clone)]
+pub struct ScanCard {
+    pub token_info: HoneypotTokenInfo,
+    // None when the provider failed, the field is left out of the card
+    fields: BTreeMap<ScanField, (Option<String>, DateTime<Utc>)>,
+    pub locale: Locale,
+    used_at: DateTime<Utc>,
+}
+
+impl ScanCard {
+    pub fn new(token_info: HoneypotTokenInfo) -> ScanCard {
+        ScanCard {
+            token_info,
+            fields: BTreeMap::new(),
+            locale: Locale::default(),
+            used_at: Utc::now(),
+        }
+    }
+
+    // fields that were never fetched are stale too, and all of them for another locale
+    pub fn stale_fields(&self, now: DateTime<Utc>, locale: Locale) -> Vec<ScanField> {
+        ScanField::ALL
+            .into_iter()
+            .filter(|field| {
+                self.locale != locale
+                    || self
+                        .fields
+                        .get(field)
+                        .map_or(true, |(_, fetched_at)| now - *fetched_at >= field.max_age())
+            })
+            .collect()
+    }
+
+    pub fn field(&self, field: ScanField) -> Option<&str> {
+        self.fields
+            .get(&field)
+            .and_then(|(text, _)| text.as_deref())
+    }
+
+    pub fn set(&mut self, field: ScanField, text: Option<String>, now: DateTime<Utc>) {
+        self.fields.insert(field, (text, now));
+    }
+
+    // the card is as fresh as its oldest field
+    pub fn fetched_at(&self) -> DateTime<Utc> {
+        self.fields
+            .values()
+            .map(|(_, fetched_at)| *fetched_at)
+            .min()
+            .unwrap_or(self.token_info.fetched_at)
+    }
+}
+
+lazy_static! {
+    // lowercase contract -> its last scan
+    static ref SCAN_CARDS: Mutex<HashMap<String, ScanCard>> =
+        Mutex::new(HashMap::<String, ScanCard>::new());
+}
+
+pub async fn get(contract: &str) -> Option<ScanCard> {
+    SCAN_CARDS
+        .lock()
+        .await
+        .get(&contract.to_lowercase())
+        .cloned()
+}
+
+pub async fn store(mut card: ScanCard) {
+    let mut cards = SCAN_CARDS.lock().await;
+    card.used_at = Utc::now();
+    cards.insert(card.token_info.contract_address.to_lowercase(), card);
+
+    if cards.len() > MAX_CACHED_SCANS {
+        if let Some(oldest) = cards
+            .iter()
+            .min_by_key(|(_, card)| card.used_at)
+            .map(|(contract, _)| contract.clone())
+        {
+            cards.remove(&oldest);
+        }
+    }
+}
+
+#[test]
+fn test_scan_card_stale_fields() {
+    use crate::utils::Chain;
+
+    let now = Utc::now();
+    let minutes_ago = |minutes: i64| now - Duration::try_minutes(minutes).unwrap();
+    let mut card = ScanCard::new(HoneypotTokenInfo {
+        symbol: String::from("TKN"),
+        name: String::from("Token"),
+        contract_address: String::from("0x00000000000000000000000000000000000000aa"),
+        decimals: 18,
+        pair: String::from("UniswapV2"),
+        pair_address: String::new(),
+        pair_token: String::new(),
+        pair_symbol: String::from("WETH"),
+        is_honeypot: false,
+        honeypot_reason: None,
+        buy_tax: 0.0,
+        sell_tax: 0.0,
+        liquidity: 10_000.0,
+        is_open_source: Some(true),
+        has_proxy_calls: Some(false),
+        flags_description: None,
+        total_holders: 100,
+        pair_created_at: None,
+        chain: Chain::Ethereum,
+        fetched_at: minutes_ago(20),
+        simulated: true,
+    });
+    assert_eq!(
+        card.stale_fields(now, Locale::default()),
+        ScanField::ALL.to_vec()
+    );
+    assert_eq!(card.fetched_at(), minutes_ago(20));
+
+    for field in ScanField::ALL {
+        card.set(field, Some(format!("{:?}", field)), minutes_ago(6));
+    }
+    card.set(ScanField::Locker, None, minutes_ago(30));
+
+    assert_eq!(
+        card.stale_fields(now, Locale::default()),
+        vec![
+            ScanField::Warnings,
+            ScanField::SizeTaxes,
+            ScanField::AntiBot
+        ]
+    );
+    assert_eq!(card.field(ScanField::Supply), Some("Supply"));
+    let mut locale = Locale::default();
+    assert!(locale.set("eu"));
+    assert_eq!(card.stale_fields(now, locale), ScanField::ALL.to_vec());
+    assert_eq!(card.field(ScanField::Locker), None);
+    assert_eq!(card.fetched_at(), minutes_ago(30));
+}
@@ -0,0 +1,135 @@
+use super::bot::Settings;
+use std::collections::HashMap;
+use teloxide::types::ChatId;
+use tokio::sync::RwLock;
+
+// the on/off switches of the /settings keyboard, named by their callback data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingsToggle {
+    SnipeNewTokens,
+    HideZeroBalances,
+    ClassicApprovals,
+    DirectRoutesOnly,
+    SameBlockSnipes,
+}
+
+impl SettingsToggle {
+    pub fn from_callback(data: &str) -> Option<SettingsToggle> {
+        match data {
+            "snipe_new_tokens" => Some(SettingsToggle::SnipeNewTokens),
+            "hide_zero_balance" => Some(SettingsToggle::HideZeroBalances),
+            "classic_approvals" => Some(SettingsToggle::ClassicApprovals),
+            "direct_routes_only" => Some(SettingsToggle::DirectRoutesOnly),
+            "same_block_snipes" => Some(SettingsToggle::SameBlockSnipes),
+            _ => None,
+        }
+    }
+
+    fn switch(self, settings: &mut Settings) -> &mut bool {
+        match self {
+            SettingsToggle::SnipeNewTokens => &mut settings.snipe_new_tokens,
+            SettingsToggle::HideZeroBalances => &mut settings.hide_zero_token_balances,
+            SettingsToggle::ClassicApprovals => &mut settings.classic_approvals,
+            SettingsToggle::DirectRoutesOnly => &mut settings.direct_routes_only,
+            SettingsToggle::SameBlockSnipes => &mut settings.same_block_snipes,
+        }
+    }
+}
+
+/*
+
+Settings are small Copy values, so readers copy their chat's entry out instead
+of holding on to the map, and updates change the entries they touch in place
+under one short write lock. Nothing awaits a request while the lock is held.
+
+*/
+#[derive(Debug, Default)]
+pub struct SettingsStore {
+    chats: RwLock<HashMap<ChatId, Settings>>,
+}
+
+impl SettingsStore {
+    // chats that never changed a setting get the defaults
+    pub async fn get(&self, chat_id: ChatId) -> Settings {
+        self.chats
+            .read()
+            .await
+            .get(&chat_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // for the background tasks that go through every chat
+    pub async fn snapshot(&self) -> HashMap<ChatId, Settings> {
+        self.chats.read().await.clone()
+    }
+
+    pub async fn update(&self, chat_ids: &[ChatId], update: impl Fn(&mut Settings)) {
+        let mut chats = self.chats.write().await;
+
+        for chat_id in chat_ids {
+            update(chats.entry(*chat_id).or_default());
+        }
+    }
+
+    // flips the chat's switch and sets the other chats to the same value, returns the new value
+    pub async fn toggle(
+        &self,
+        chat_id: ChatId,
+        linked_chats: &[ChatId],
+        toggle: SettingsToggle,
+    ) -> bool {
+        let mut chats = self.chats.write().await;
+
+        let enabled = !*toggle.switch(chats.entry(chat_id).or_default());
+        for chat_id in linked_chats.iter().chain([&chat_id]) {
+            *toggle.switch(chats.entry(*chat_id).or_default()) = enabled;
+        }
+
+        enabled
+    }
+
+    pub async fn copy(&self, from: ChatId, to: ChatId) {
+        let mut chats = self.chats.write().await;
+
+        match chats.get(&from).copied() {
+            Some(settings) => chats.insert(to, settings),
+            None => chats.remove(&to),
+        };
+    }
+
+    // the shards take over the primary's settings as they are, see shards.rs
+    pub async fn replace(&self, settings: HashMap<ChatId, Settings>) {
+        *self.chats.write().await = settings;
+    }
+}
+
+#[tokio::test]
+async fn test_settings_toggle() {
+    let store = SettingsStore::default();
+    let (chat, linked) = (ChatId(1), ChatId(2));
+
+    // linked chats that diverged follow the toggled chat
+    store
+        .update(&[linked], |settings| settings.classic_approvals = true)
+        .await;
+
+    assert!(
+        store
+            .toggle(chat, &[linked], SettingsToggle::ClassicApprovals)
+            .await
+    );
+    assert!(store.get(linked).await.classic_approvals);
+    assert!(
+        !store
+            .toggle(chat, &[linked], SettingsToggle::ClassicApprovals)
+            .await
+    );
+    assert!(!store.get(linked).await.classic_approvals);
+
+    assert_eq!(
+        SettingsToggle::from_callback("hide_zero_balance"),
+        Some(SettingsToggle::HideZeroBalances)
+    );
+    assert!(!store.get(ChatId(3)).await.snipe_new_tokens);
+}
@@ -0,0 +1,402 @@
+use crate::{
+    persist, tasks,
+    telegram::{
+        alerts::{
+            AlertRoutes, NotificationPreferences, WalletFilter, ALERT_ROUTES,
+            NOTIFICATION_PREFERENCES, WALLET_FILTERS,
+        },
+        bot::{Settings, SETTINGS, WATCHED_TOKENS, WATCHED_WALLETS},
+    },
+};
+use chrono::{Duration, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::PathBuf,
+    time::{Instant, SystemTime},
+};
+use teloxide::types::ChatId;
+use tokio::{sync::Mutex, time::sleep};
+
+// a shard that didn't renew its lease for this long is down
+const LEASE_TIMEOUT_SECONDS: i64 = 5 * 60;
+const SYNC_SECONDS: i64 = 30;
+// Telegram's limit for one bot token, every shard sends with the same one
+const MESSAGES_PER_SECOND: f64 = 30.0;
+
+/*
+
+One bot token polls its updates from one place, so with SHARD_COUNT instances
+the one with SHARD_INDEX 0 is the primary: it handles every command and runs
+the tasks that aren't about a chat's watch list. Every instance, the primary
+included, runs the wallet, approval and token watches for the chats whose id
+hashes to its index, so each of them only polls its share of the chats.
+
+The instances share SHARD_DIR. The primary writes the watch lists and the
+chats' settings, wallet filters, alert routes and notification preferences
+there as they change and the other shards pick them up, so their alerts look
+like the primary's. Every shard renews a lease file there. When a shard's
+lease runs out the primary watches its chats until it's back, so a crashed
+instance doesn't silence a share of the chats.
+
+The shards still send with one bot token, so each of them only sends its
+share of Telegram's per-token rate limit.
+
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shard {
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Shard {
+    fn from_env() -> Shard {
+        let var = |name: &str, default: u64| match env::var(name) {
+            Ok(value) => value
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("{} env variable value is not an integer", name)),
+            Err(_) => default,
+        };
+        let (index, count) = (var("SHARD_INDEX", 0), var("SHARD_COUNT", 1));
+        assert!(
+            count > 0 && index < count,
+            "SHARD_INDEX must be below SHARD_COUNT"
+        );
+
+        Shard { index, count }
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.index == 0
+    }
+
+    pub fn is_sharded(&self) -> bool {
+        self.count > 1
+    }
+
+    // the shard a chat belongs to, the primary also takes the chats of the shards that are down
+    fn owns(&self, chat_id: ChatId, down: &HashSet<u64>) -> bool {
+        let shard = shard_of(chat_id, self.count);
+        shard == self.index || (self.is_primary() && down.contains(&shard))
+    }
+}
+
+// FNV-1a, unlike DefaultHasher it's guaranteed to be the same in every build
+fn shard_of(chat_id: ChatId, count: u64) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in chat_id.0.to_le_bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash % count
+}
+
+// what the shards need of the primary's chats to alert them the same way
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct SharedChats {
+    wallets: HashMap<i64, Vec<String>>,
+    tokens: HashMap<i64, Vec<String>>,
+    settings: HashMap<i64, Settings>,
+    wallet_filters: HashMap<i64, HashMap<String, WalletFilter>>,
+    alert_routes: HashMap<i64, AlertRoutes>,
+    notification_preferences: HashMap<i64, NotificationPreferences>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Lease {
+    index: u64,
+    count: u64,
+    renewed_at: i64,
+}
+
+lazy_static! {
+    static ref SHARD: Shard = Shard::from_env();
+    // shards whose lease ran out, only tracked by the primary
+    static ref DOWN_SHARDS: Mutex<HashSet<u64>> = Mutex::new(HashSet::new());
+    // when the shard's next message can go out
+    static ref NEXT_SEND: Mutex<Instant> = Mutex::new(Instant::now());
+}
+
+pub fn shard() -> Shard {
+    *SHARD
+}
+
+fn dir() -> PathBuf {
+    PathBuf::from(env::var("SHARD_DIR").unwrap_or(String::from("shards")))
+}
+
+fn chats_path() -> PathBuf {
+    dir().join("chats.json")
+}
+
+fn lease_path(index: u64) -> PathBuf {
+    dir().join(format!("lease-{}.json", index))
+}
+
+pub async fn owns(chat_id: ChatId) -> bool {
+    SHARD.owns(chat_id, &*DOWN_SHARDS.lock().await)
+}
+
+// drops the chats another shard watches
+pub async fn retain_owned<T>(by_chat: &mut HashMap<ChatId, T>) {
+    let down = DOWN_SHARDS.lock().await;
+    by_chat.retain(|chat_id, _| SHARD.owns(*chat_id, &down));
+}
+
+fn by_id<T: Clone>(by_chat: &HashMap<ChatId, T>) -> HashMap<i64, T> {
+    by_chat
+        .iter()
+        .map(|(chat_id, value)| (chat_id.0, value.clone()))
+        .collect()
+}
+
+fn by_chat<T>(by_id: HashMap<i64, T>) -> HashMap<ChatId, T> {
+    by_id
+        .into_iter()
+        .map(|(id, value)| (ChatId(id), value))
+        .collect()
+}
+
+async fn current_chats() -> SharedChats {
+    SharedChats {
+        wallets: by_id(&*WATCHED_WALLETS.lock().await),
+        tokens: by_id(&*WATCHED_TOKENS.lock().await),
+        settings: by_id(&SETTINGS.snapshot().await),
+        wallet_filters: by_id(&*WALLET_FILTERS.lock().await),
+        alert_routes: by_id(&*ALERT_ROUTES.lock().await),
+        notification_preferences: by_id(&*NOTIFICATION_PREFERENCES.lock().await),
+    }
+}
+
+fn read_chats() -> Option<SharedChats> {
+    let content = fs::read_to_string(chats_path()).ok()?;
+    serde_json::from_str(&content)
+        .map_err(|e| error!("invalid shared chats file: {}", e))
+        .ok()
+}
+
+fn write_chats(chats: &SharedChats) {
+    let result = serde_json::to_string(chats)
+        .map_err(|e| e.to_string())
+        .and_then(|json| persist::write_atomically(&chats_path(), json));
+
+    if let Err(e) = result {
+        error!("failed to write the shared chats: {}", e);
+    }
+}
+
+async fn apply_chats(chats: SharedChats) {
+    *WATCHED_WALLETS.lock().await = by_chat(chats.wallets);
+    *WATCHED_TOKENS.lock().await = by_chat(chats.tokens);
+    SETTINGS.replace(by_chat(chats.settings)).await;
+    *WALLET_FILTERS.lock().await = by_chat(chats.wallet_filters);
+    *ALERT_ROUTES.lock().await = by_chat(chats.alert_routes);
+    *NOTIFICATION_PREFERENCES.lock().await = by_chat(chats.notification_preferences);
+}
+
+fn modified() -> Option<SystemTime> {
+    fs::metadata(chats_path()).ok()?.modified().ok()
+}
+
+fn renew_lease() {
+    let lease = Lease {
+        index: SHARD.index,
+        count: SHARD.count,
+        renewed_at: Utc::now().timestamp(),
+    };
+    let result = serde_json::to_string(&lease)
+        .map_err(|e| e.to_string())
+        .and_then(|json| persist::write_atomically(&lease_path(SHARD.index), json));
+
+    if let Err(e) = result {
+        error!("failed to renew the shard lease: {}", e);
+    }
+}
+
+// the shards other than the primary whose lease ran out or was never written
+fn expired_leases(leases: &[Lease], count: u64, now: i64) -> HashSet<u64> {
+    (1..count)
+        .filter(|index| {
+            !leases.iter().any(|lease| {
+                lease.index == *index
+                    && lease.count == count
+                    && now - lease.renewed_at < LEASE_TIMEOUT_SECONDS
+            })
+        })
+        .collect()
+}
+
+fn read_leases() -> Vec<Lease> {
+    (1..SHARD.count)
+        .filter_map(|index| fs::read_to_string(lease_path(index)).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect()
+}
+
+// waits for the shard's turn to send, so all shards together stay within the token's rate limit
+pub async fn pace() {
+    if !SHARD.is_sharded() {
+        return;
+    }
+
+    let interval = std::time::Duration::from_secs_f64(SHARD.count as f64 / MESSAGES_PER_SECOND);
+    let wait = {
+        let mut next_send = NEXT_SEND.lock().await;
+        let now = Instant::now();
+        let send_at = (*next_send).max(now);
+        *next_send = send_at + interval;
+        send_at - now
+    };
+
+    sleep(wait).await;
+}
+
+// the chats the shards start from, before the first sync
+pub async fn load() {
+    if let Err(e) = fs::create_dir_all(dir()) {
+        error!("failed to create the shard directory: {}", e);
+    }
+
+    if let Some(chats) = read_chats() {
+        apply_chats(chats).await;
+    }
+    renew_lease();
+
+    info!(
+        "running shard {} of {}{}",
+        SHARD.index,
+        SHARD.count,
+        if SHARD.is_primary() {
+            ", the primary"
+        } else {
+            ""
+        }
+    );
+}
+
+pub async fn sync() {
+    let started_at = Utc::now();
+    let mut published = current_chats().await;
+    let mut last_modified = modified();
+
+    loop {
+        sleep(
+            Duration::try_seconds(SYNC_SECONDS)
+                .unwrap()
+                .to_std()
+                .unwrap(),
+        )
+        .await;
+        tasks::heartbeat("shard_sync").await;
+        renew_lease();
+
+        if !SHARD.is_primary() {
+            let modified = modified();
+            if modified != last_modified {
+                if let Some(chats) = read_chats() {
+                    apply_chats(chats).await;
+                }
+                last_modified = modified;
+            }
+            continue;
+        }
+
+        let chats = current_chats().await;
+        if chats != published {
+            write_chats(&chats);
+            published = chats;
+        }
+
+        // a primary that just started gives the other shards a lease timeout before taking over
+        if Utc::now() - started_at < Duration::try_seconds(LEASE_TIMEOUT_SECONDS).unwrap() {
+            continue;
+        }
+        let down = expired_leases(&read_leases(), SHARD.count, Utc::now().timestamp());
+        let mut down_shards = DOWN_SHARDS.lock().await;
+        for index in down.difference(&down_shards) {
+            warn!(
+                "shard {} is down, its chats are watched by the primary",
+                index
+            );
+        }
+        for index in down_shards.difference(&down) {
+            info!("shard {} is back, it watches its chats again", index);
+        }
+        *down_shards = down;
+    }
+}
+
+#[test]
+fn test_shards() {
+    let chats: Vec<ChatId> = (-500..500).map(|id| ChatId(id * 7919)).collect();
+    let shards: Vec<Shard> = (0..3).map(|index| Shard { index, count: 3 }).collect();
+    let none_down = HashSet::new();
+
+    // every chat has exactly one shard, and the shards get a similar share of them
+    for chat_id in &chats {
+        assert_eq!(
+            shards
+                .iter()
+                .filter(|shard| shard.owns(*chat_id, &none_down))
+                .count(),
+            1
+        );
+    }
+    for shard in &shards {
+        let owned = chats
+            .iter()
+            .filter(|chat_id| shard.owns(**chat_id, &none_down))
+            .count();
+        assert!(owned > 250 && owned < 420, "{}", owned);
+    }
+    assert_eq!(shard_of(ChatId(-1001234567890), 1), 0);
+
+    // the primary takes the chats of shard 2 while its lease is expired
+    let now = 1_700_000_000;
+    let leases = [
+        Lease {
+            index: 1,
+            count: 3,
+            renewed_at: now - 30,
+        },
+        Lease {
+            index: 2,
+            count: 3,
+            renewed_at: now - LEASE_TIMEOUT_SECONDS,
+        },
+    ];
+    let down = expired_leases(&leases, 3, now);
+    assert_eq!(down, HashSet::from([2]));
+    let orphan = *chats
+        .iter()
+        .find(|chat_id| shard_of(**chat_id, 3) == 2)
+        .unwrap();
+    assert!(shards[0].owns(orphan, &down));
+    assert!(!shards[1].owns(orphan, &down));
+
+    // a lease written for another shard count doesn't count
+    let resized = [Lease {
+        index: 1,
+        count: 2,
+        renewed_at: now,
+    }];
+    assert_eq!(expired_leases(&resized, 3, now), HashSet::from([1, 2]));
+
+    // the chats' state goes through the shared file as it is
+    let mut chats = SharedChats::default();
+    let mut preferences = NotificationPreferences::default();
+    preferences.priorities.insert(
+        crate::telegram::alerts::AlertCategory::Watch,
+        crate::telegram::alerts::Priority::Low,
+    );
+    preferences.quiet_hours = Some((23, 7));
+    chats.notification_preferences.insert(-100, preferences);
+    let mut settings = Settings::default();
+    settings.locale.set("Europe/Berlin");
+    chats.settings.insert(-100, settings);
+    let json = serde_json::to_string(&chats).unwrap();
+    assert_eq!(serde_json::from_str::<SharedChats>(&json).unwrap(), chats);
+}
@@ -0,0 +1,66 @@
+use crate::utils;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use teloxide::types::ChatId;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct TokenCandidate {
+    pub symbol: String,
+    pub name: String,
+    pub address: String,
+}
+
+pub enum Resolution {
+    Address(String),
+    Ambiguous(Vec<TokenCandidate>),
+    Unknown,
+}
+
+lazy_static! {
+    // uppercase symbol -> tokens seen with that symbol in the chat's portfolio, scans and alerts
+    static ref SYMBOLS: Mutex<HashMap<ChatId, HashMap<String, Vec<TokenCandidate>>>> =
+        Mutex::new(HashMap::<ChatId, HashMap<String, Vec<TokenCandidate>>>::new());
+}
+
+pub async fn remember(chat_id: ChatId, symbol: &str, name: &str, address: &str) {
+    if symbol.is_empty() || !utils::is_valid_eth_address(address) {
+        return;
+    }
+
+    let mut symbols = SYMBOLS.lock().await;
+    let candidates = symbols
+        .entry(chat_id)
+        .or_default()
+        .entry(symbol.to_uppercase())
+        .or_default();
+
+    if !candidates
+        .iter()
+        .any(|c| c.address.to_lowercase() == address.to_lowercase())
+    {
+        candidates.push(TokenCandidate {
+            symbol: String::from(symbol),
+            name: String::from(name),
+            address: String::from(address),
+        });
+    }
+}
+
+// accepts addresses as is, and tickers with or without a leading '$'
+pub async fn resolve(chat_id: ChatId, input: &str) -> Resolution {
+    if utils::is_valid_eth_address(input) {
+        return Resolution::Address(String::from(input));
+    }
+
+    let symbol = input.trim_start_matches('$').to_uppercase();
+    let symbols = SYMBOLS.lock().await;
+
+    match symbols.get(&chat_id).and_then(|s| s.get(&symbol)) {
+        Some(candidates) if candidates.len() == 1 => {
+            Resolution::Address(candidates[0].address.clone())
+        }
+        Some(candidates) if candidates.len() > 1 => Resolution::Ambiguous(candidates.clone()),
+        _ => Resolution::Unknown,
+    }
+}
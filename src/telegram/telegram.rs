@@ -1 +1,22 @@
+pub mod alerts;
 pub mod bot;
+pub mod commandhistory;
+pub mod dca;
+pub mod debug;
+pub mod deeplinks;
+pub mod digest;
+pub mod expiry;
+pub mod groups;
+pub mod input;
+pub mod lanes;
+pub mod locale;
+pub mod metadata;
+pub mod profiles;
+pub mod prompts;
+pub mod scans;
+pub mod settings;
+pub mod shards;
+pub mod symbols;
+pub mod templates;
+pub mod tradelock;
+pub mod walletimport;
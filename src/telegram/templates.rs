@@ -0,0 +1,198 @@
+use core::fmt;
+use lazy_static::lazy_static;
+use std::{collections::HashMap, str::FromStr};
+use teloxide::types::ChatId;
+use tokio::sync::Mutex;
+
+// long enough for a detailed alert, short enough to stay under telegram's message limit once rendered
+pub const MAX_TEMPLATE_LENGTH: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TemplateKind {
+    Wallet,
+    Sniper,
+    Portfolio,
+}
+
+impl fmt::Display for TemplateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TemplateKind::Wallet => write!(f, "wallet"),
+            TemplateKind::Sniper => write!(f, "sniper"),
+            TemplateKind::Portfolio => write!(f, "portfolio"),
+        }
+    }
+}
+
+impl FromStr for TemplateKind {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wallet" => Ok(TemplateKind::Wallet),
+            "sniper" => Ok(TemplateKind::Sniper),
+            "portfolio" => Ok(TemplateKind::Portfolio),
+            _ => Err(()),
+        }
+    }
+}
+
+impl TemplateKind {
+    pub fn all() -> [TemplateKind; 3] {
+        [
+            TemplateKind::Wallet,
+            TemplateKind::Sniper,
+            TemplateKind::Portfolio,
+        ]
+    }
+
+    pub fn placeholders(&self) -> &'static [&'static str] {
+        match self {
+            TemplateKind::Wallet => &[
+                "wallet",
+                "activity",
+                "token_name",
+                "token_symbol",
+                "contract",
+                "usd_value",
+                "time",
+                "tx_link",
+                "links",
+            ],
+            TemplateKind::Sniper => &[
+                "pair",
+                "contract",
+                "liquidity_eth",
+                "liquidity_usd",
+                "fdv_usd",
+                "anti_snipe",
+                "links",
+            ],
+            TemplateKind::Portfolio => &[
+                "token_name",
+                "token_symbol",
+                "contract",
+                "balance",
+                "usd_value",
+                "percent_change",
+                "portfolio_percent",
+                "links",
+            ],
+        }
+    }
+}
+
+lazy_static! {
+    // chats without a template use the built-in messages
+    static ref TEMPLATES: Mutex<HashMap<ChatId, HashMap<TemplateKind, String>>> =
+        Mutex::new(HashMap::<ChatId, HashMap<TemplateKind, String>>::new());
+}
+
+// names between braces, '{{' and '}}' aren't supported so every brace pair is a placeholder
+fn placeholder_names(template: &str) -> Vec<&str> {
+    let mut names = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        names.push(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+
+    names
+}
+
+pub fn unknown_placeholders(kind: TemplateKind, template: &str) -> Vec<String> {
+    placeholder_names(template)
+        .into_iter()
+        .filter(|name| !kind.placeholders().contains(name))
+        .map(String::from)
+        .collect()
+}
+
+pub fn uses(template: &str, placeholder: &str) -> bool {
+    placeholder_names(template).contains(&placeholder)
+}
+
+// one pass over the template, so braces in a value (a token named {symbol}) are never replaced
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 1..start + end];
+        rendered.push_str(&rest[..start]);
+        match values.iter().find(|(placeholder, _)| *placeholder == name) {
+            Some((_, value)) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+pub async fn get(chat_id: ChatId, kind: TemplateKind) -> Option<String> {
+    TEMPLATES
+        .lock()
+        .await
+        .get(&chat_id)
+        .and_then(|templates| templates.get(&kind).cloned())
+}
+
+pub async fn set(chat_id: ChatId, kind: TemplateKind, template: String) {
+    TEMPLATES
+        .lock()
+        .await
+        .entry(chat_id)
+        .or_default()
+        .insert(kind, template);
+}
+
+pub async fn reset(chat_id: ChatId, kind: TemplateKind) -> bool {
+    TEMPLATES
+        .lock()
+        .await
+        .get_mut(&chat_id)
+        .is_some_and(|templates| templates.remove(&kind).is_some())
+}
+
+#[test]
+fn test_render_template() {
+    let template = "{token_symbol} worth ${usd_value}\n{tx_link} {unknown";
+
+    assert_eq!(
+        unknown_placeholders(TemplateKind::Wallet, "{token_symbol} {price}"),
+        vec![String::from("price")]
+    );
+    assert!(unknown_placeholders(TemplateKind::Wallet, template).is_empty());
+    assert!(uses(template, "usd_value"));
+    assert!(!uses(template, "wallet"));
+
+    assert_eq!(
+        render(
+            template,
+            &[
+                ("token_symbol", String::from("PEPE")),
+                ("usd_value", String::from("1,200")),
+                ("tx_link", String::from("Tx")),
+            ]
+        ),
+        "PEPE worth $1,200\nTx {unknown"
+    );
+    assert_eq!(
+        render(
+            "{token_symbol} {token_name} {wallet}",
+            &[
+                ("token_symbol", String::from("{token_name}")),
+                ("token_name", String::from("Token")),
+            ]
+        ),
+        "{token_name} Token {wallet}"
+    );
+}
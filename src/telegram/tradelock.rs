@@ -0,0 +1,365 @@
+use crate::persist;
+use chrono::{DateTime, Duration, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::Sha256;
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+use teloxide::types::ChatId;
+use tokio::sync::Mutex;
+
+// wrong codes in a row before the chat is locked out
+const MAX_FAILURES: u32 = 5;
+const LOCKOUT_MINUTES: i64 = 15;
+const TOTP_STEP_SECONDS: i64 = 30;
+const TOTP_DIGITS: u32 = 6;
+// PBKDF2-HMAC-SHA256 rounds of new PINs, a 4 digit PIN can't be hashed cheaply 10,000 times over
+const PIN_HASH_ROUNDS: u32 = 600_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Guard {
+    // hex PBKDF2-HMAC-SHA256 of the PIN, the rounds are kept so they can be raised for new PINs
+    Pin {
+        salt: String,
+        rounds: u32,
+        hash: String,
+    },
+    // base32, the way authenticator apps take it
+    Totp {
+        secret: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Challenge {
+    // trades are confirmed with the button only
+    Open,
+    Pin,
+    Totp,
+    LockedOut(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Accepted,
+    Rejected { attempts_left: u32 },
+    LockedOut(DateTime<Utc>),
+}
+
+/*
+
+A chat can require a PIN or an authenticator (TOTP) code before a trade it
+confirmed is executed, so a stolen Telegram session can't fire trades with a
+tap. The code is sent as a reply, which the bot deletes once it's read.
+MAX_FAILURES wrong codes in a row lock the chat's trades for LOCKOUT_MINUTES,
+and a TOTP code can only be used once.
+
+PINs are only kept as salted PBKDF2 hashes. The TOTP secret has to be kept as it is,
+like every other state file TRADE_LOCKS_FILE is as safe as the server it's on.
+
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TradeLock {
+    guard: Guard,
+    failures: u32,
+    // unix timestamp the lockout ends at
+    locked_until: Option<i64>,
+    // the TOTP step of the last code accepted, it can't be replayed
+    last_step: Option<i64>,
+}
+
+impl TradeLock {
+    fn new(guard: Guard) -> TradeLock {
+        TradeLock {
+            guard,
+            failures: 0,
+            locked_until: None,
+            last_step: None,
+        }
+    }
+
+    fn challenge(&self, now: DateTime<Utc>) -> Challenge {
+        match (self.locked_until, &self.guard) {
+            (Some(until), _) if until > now.timestamp() => {
+                Challenge::LockedOut(DateTime::from_timestamp(until, 0).unwrap())
+            }
+            (_, Guard::Pin { .. }) => Challenge::Pin,
+            (_, Guard::Totp { .. }) => Challenge::Totp,
+        }
+    }
+
+    // entered_hash is the PIN's hash with the lock's salt and rounds, see entered_pin_hash
+    fn verify(&mut self, code: &str, entered_hash: Option<&str>, now: DateTime<Utc>) -> Verdict {
+        if let Challenge::LockedOut(until) = self.challenge(now) {
+            return Verdict::LockedOut(until);
+        }
+
+        let code = code.trim();
+        let accepted = match &self.guard {
+            Guard::Pin { hash, .. } => entered_hash
+                .is_some_and(|entered| bool::from(entered.as_bytes().ct_eq(hash.as_bytes()))),
+            Guard::Totp { secret } => match totp_step(secret, code, now) {
+                Some(step) if self.last_step.map_or(true, |last| step > last) => {
+                    self.last_step = Some(step);
+                    true
+                }
+                _ => false,
+            },
+        };
+
+        if accepted {
+            self.failures = 0;
+            self.locked_until = None;
+            return Verdict::Accepted;
+        }
+
+        self.failures += 1;
+        if self.failures >= MAX_FAILURES {
+            let until = now + Duration::try_minutes(LOCKOUT_MINUTES).unwrap();
+            self.failures = 0;
+            self.locked_until = Some(until.timestamp());
+            return Verdict::LockedOut(until);
+        }
+        Verdict::Rejected {
+            attempts_left: MAX_FAILURES - self.failures,
+        }
+    }
+}
+
+fn pin_hash(salt: &str, rounds: u32, pin: &str) -> String {
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(pin.as_bytes(), salt.as_bytes(), rounds, &mut hash);
+
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// 4 to 12 digits
+pub fn is_valid_pin(pin: &str) -> bool {
+    (4..=12).contains(&pin.len()) && pin.chars().all(|c| c.is_ascii_digit())
+}
+
+// RFC 4226, the code of one counter value
+fn hotp(key: &[u8], counter: u64, digits: u32) -> u32 {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).unwrap();
+    mac.update(&counter.to_be_bytes());
+    let mac = mac.finalize().into_bytes();
+    let offset = (mac[19] & 0x0f) as usize;
+    let code = u32::from_be_bytes([
+        mac[offset],
+        mac[offset + 1],
+        mac[offset + 2],
+        mac[offset + 3],
+    ]) & 0x7fff_ffff;
+
+    code % 10u32.pow(digits)
+}
+
+// the step the code belongs to, the previous and next steps are accepted for clock drift
+fn totp_step(secret: &str, code: &str, now: DateTime<Utc>) -> Option<i64> {
+    let key = base32_decode(secret)?;
+    let code = code
+        .parse::<u32>()
+        .ok()
+        .filter(|_| code.len() == TOTP_DIGITS as usize)?;
+    let current = now.timestamp() / TOTP_STEP_SECONDS;
+
+    (current - 1..=current + 1).find(|step| hotp(&key, *step as u64, TOTP_DIGITS) == code)
+}
+
+// authenticator apps take the secret in any case, with spaces and sometimes padded
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded: String = encoded
+        .chars()
+        .filter(|c| *c != '=' && !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    BASE32_NOPAD.decode(encoded.as_bytes()).ok()
+}
+
+// what authenticator apps scan to add the account
+pub fn totp_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/snipers:{}?secret={}&issuer=snipers&digits={}&period={}",
+        account, secret, TOTP_DIGITS, TOTP_STEP_SECONDS
+    )
+}
+
+lazy_static! {
+    static ref TRADE_LOCKS: Mutex<HashMap<i64, TradeLock>> =
+        Mutex::new(persist::load(persist::TRADE_LOCKS).unwrap_or_default());
+}
+
+pub async fn challenge(chat_id: ChatId) -> Challenge {
+    TRADE_LOCKS
+        .lock()
+        .await
+        .get(&chat_id.0)
+        .map_or(Challenge::Open, |lock| lock.challenge(Utc::now()))
+}
+
+// PBKDF2 takes a while, the PIN is hashed on the blocking pool without holding TRADE_LOCKS.
+// None for locks without a PIN and chats that are locked out
+async fn entered_pin_hash(chat_id: ChatId, code: &str) -> Option<String> {
+    let (salt, rounds) = match TRADE_LOCKS.lock().await.get(&chat_id.0) {
+        Some(lock) if matches!(lock.challenge(Utc::now()), Challenge::LockedOut(_)) => return None,
+        Some(TradeLock {
+            guard: Guard::Pin { salt, rounds, .. },
+            ..
+        }) => (salt.clone(), *rounds),
+        _ => return None,
+    };
+    let pin = code.trim().to_owned();
+
+    tokio::task::spawn_blocking(move || pin_hash(&salt, rounds, &pin))
+        .await
+        .ok()
+}
+
+// chats without a lock have nothing to verify
+pub async fn verify(chat_id: ChatId, code: &str) -> Verdict {
+    let entered_hash = entered_pin_hash(chat_id, code).await;
+    let mut locks = TRADE_LOCKS.lock().await;
+    let Some(lock) = locks.get_mut(&chat_id.0) else {
+        return Verdict::Accepted;
+    };
+
+    let verdict = lock.verify(code, entered_hash.as_deref(), Utc::now());
+    persist::save(persist::TRADE_LOCKS, &*locks);
+    verdict
+}
+
+// false if the chat already has a lock, it has to be removed with its code first
+pub async fn set_pin(chat_id: ChatId, pin: &str) -> bool {
+    if TRADE_LOCKS.lock().await.contains_key(&chat_id.0) {
+        return false;
+    }
+
+    let salt = BASE32_NOPAD.encode(&rand::random::<[u8; 10]>());
+    let (hash_salt, pin) = (salt.clone(), pin.to_owned());
+    let Ok(hash) =
+        tokio::task::spawn_blocking(move || pin_hash(&hash_salt, PIN_HASH_ROUNDS, &pin)).await
+    else {
+        return false;
+    };
+
+    // a lock set while the PIN was hashed stays
+    let mut locks = TRADE_LOCKS.lock().await;
+    if locks.contains_key(&chat_id.0) {
+        return false;
+    }
+    locks.insert(
+        chat_id.0,
+        TradeLock::new(Guard::Pin {
+            salt,
+            rounds: PIN_HASH_ROUNDS,
+            hash,
+        }),
+    );
+    persist::save(persist::TRADE_LOCKS, &*locks);
+    true
+}
+
+// the new secret, None if the chat already has a lock
+pub async fn set_totp(chat_id: ChatId) -> Option<String> {
+    let mut locks = TRADE_LOCKS.lock().await;
+    if locks.contains_key(&chat_id.0) {
+        return None;
+    }
+
+    let secret = BASE32_NOPAD.encode(&rand::random::<[u8; 20]>());
+    locks.insert(
+        chat_id.0,
+        TradeLock::new(Guard::Totp {
+            secret: secret.clone(),
+        }),
+    );
+    persist::save(persist::TRADE_LOCKS, &*locks);
+    Some(secret)
+}
+
+// the lock is only removed with its own code, the same failures count towards the lockout
+pub async fn remove(chat_id: ChatId, code: &str) -> Verdict {
+    let entered_hash = entered_pin_hash(chat_id, code).await;
+    let mut locks = TRADE_LOCKS.lock().await;
+    let Some(lock) = locks.get_mut(&chat_id.0) else {
+        return Verdict::Accepted;
+    };
+
+    let verdict = lock.verify(code, entered_hash.as_deref(), Utc::now());
+    if verdict == Verdict::Accepted {
+        locks.remove(&chat_id.0);
+    }
+    persist::save(persist::TRADE_LOCKS, &*locks);
+    verdict
+}
+
+#[test]
+fn test_trade_lock() {
+    // RFC 6238 appendix B, the SHA-1 secret at 59 seconds
+    let key = b"12345678901234567890";
+    assert_eq!(hotp(key, 1, 8), 94287082);
+    let secret = BASE32_NOPAD.encode(key);
+    assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    assert_eq!(base32_decode(&secret).as_deref(), Some(&key[..]));
+    assert_eq!(
+        base32_decode("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").as_deref(),
+        Some(&key[..])
+    );
+
+    let at = |seconds: i64| DateTime::from_timestamp(seconds, 0).unwrap();
+    assert_eq!(totp_step(&secret, "287082", at(59)), Some(1));
+    assert_eq!(totp_step(&secret, "287082", at(80)), Some(1));
+    assert_eq!(totp_step(&secret, "287082", at(120)), None);
+
+    // a code can't be used twice
+    let mut lock = TradeLock::new(Guard::Totp { secret });
+    assert_eq!(lock.verify("287082", None, at(59)), Verdict::Accepted);
+    assert_eq!(
+        lock.verify("287082", None, at(60)),
+        Verdict::Rejected { attempts_left: 4 }
+    );
+
+    // few rounds, the test only checks the lockout
+    let entered = |pin: &str| pin_hash("salt", 1_000, pin);
+    let mut lock = TradeLock::new(Guard::Pin {
+        hash: entered("4821"),
+        salt: String::from("salt"),
+        rounds: 1_000,
+    });
+    assert_eq!(lock.challenge(at(0)), Challenge::Pin);
+    for attempts_left in (1..MAX_FAILURES).rev() {
+        assert_eq!(
+            lock.verify("0000", Some(&entered("0000")), at(0)),
+            Verdict::Rejected { attempts_left }
+        );
+    }
+    let until = at(LOCKOUT_MINUTES * 60);
+    assert_eq!(
+        lock.verify("0000", Some(&entered("0000")), at(0)),
+        Verdict::LockedOut(until)
+    );
+    // the right PIN doesn't help until the lockout is over
+    assert_eq!(
+        lock.verify("4821", Some(&entered("4821")), at(60)),
+        Verdict::LockedOut(until)
+    );
+    assert_eq!(lock.challenge(at(60)), Challenge::LockedOut(until));
+    assert_eq!(
+        lock.verify(" 4821 ", Some(&entered("4821")), until),
+        Verdict::Accepted
+    );
+    assert_eq!(
+        lock.verify("4821", None, until),
+        Verdict::Rejected { attempts_left: 4 }
+    );
+
+    assert!(is_valid_pin("4821"));
+    assert!(!is_valid_pin("48a1"));
+    assert!(!is_valid_pin("123"));
+}
@@ -0,0 +1,273 @@
+use crate::utils;
+use core::fmt;
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use teloxide::types::ChatId;
+use tokio::sync::Mutex;
+
+// a bigger file is more likely a transaction export than a list of wallets
+pub const MAX_IMPORT_ROWS: usize = 500;
+const MAX_LABEL_CHARS: usize = 40;
+// the rows listed in the summary, it has to fit in one message
+const MAX_LISTED_ROWS: usize = 30;
+// header names of the label column, the first one found is used
+const LABEL_COLUMNS: [&str; 7] = [
+    "label",
+    "private name tag",
+    "name tag",
+    "nametag",
+    "name",
+    "entity",
+    "note",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportError {
+    Empty,
+    NoAddressColumn,
+    TooManyRows,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ImportError::Empty => write!(f, "the file has no rows"),
+            ImportError::NoAddressColumn => write!(f, "the file has no address column"),
+            ImportError::TooManyRows => {
+                write!(f, "the file has more than {} rows", MAX_IMPORT_ROWS)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportSummary {
+    // (address, label)
+    pub added: Vec<(String, Option<String>)>,
+    // already watched or repeated in the file
+    pub duplicates: Vec<String>,
+    // (line, reason)
+    pub invalid: Vec<(usize, String)>,
+}
+
+impl fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "📥 Wallet import\n✅ {} added\n🔁 {} duplicate\n❌ {} invalid",
+            self.added.len(),
+            self.duplicates.len(),
+            self.invalid.len()
+        )?;
+
+        if !self.added.is_empty() {
+            write!(f, "\n\nAdded:")?;
+        }
+        for (address, label) in self.added.iter().take(MAX_LISTED_ROWS) {
+            match label {
+                Some(label) => write!(f, "\n{} ({})", address, label)?,
+                None => write!(f, "\n{}", address)?,
+            }
+        }
+        if self.added.len() > MAX_LISTED_ROWS {
+            write!(f, "\n... and {} more", self.added.len() - MAX_LISTED_ROWS)?;
+        }
+        if !self.invalid.is_empty() {
+            write!(f, "\n\nInvalid rows:")?;
+        }
+        for (line, reason) in self.invalid.iter().take(MAX_LISTED_ROWS) {
+            write!(f, "\nline {}: {}", line, reason)?;
+        }
+        if self.invalid.len() > MAX_LISTED_ROWS {
+            write!(f, "\n... and {} more", self.invalid.len() - MAX_LISTED_ROWS)?;
+        }
+
+        Ok(())
+    }
+}
+
+lazy_static! {
+    // chat -> lowercase wallet -> the label it was imported with
+    pub static ref WALLET_LABELS: Mutex<HashMap<ChatId, HashMap<String, String>>> =
+        Mutex::new(HashMap::<ChatId, HashMap<String, String>>::new());
+}
+
+pub async fn label(chat_id: ChatId, wallet: &str) -> Option<String> {
+    WALLET_LABELS
+        .lock()
+        .await
+        .get(&chat_id)
+        .and_then(|labels| labels.get(&wallet.to_lowercase()))
+        .cloned()
+}
+
+pub async fn set_labels(chat_ids: &[ChatId], added: &[(String, Option<String>)]) {
+    let mut labels = WALLET_LABELS.lock().await;
+
+    for chat_id in chat_ids {
+        let chat_labels = labels.entry(*chat_id).or_default();
+        for (address, label) in added {
+            if let Some(label) = label {
+                chat_labels.insert(address.to_lowercase(), label.clone());
+            }
+        }
+    }
+}
+
+// one line of the file, quoted cells can hold commas and "" for a quote
+fn split_row(line: &str) -> Vec<String> {
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(std::mem::take(&mut cell)),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell);
+
+    cells
+        .into_iter()
+        .map(|cell| cell.trim().to_owned())
+        .collect()
+}
+
+// (address column, label column) of a header row, None when it isn't one
+fn header_columns(cells: &[String]) -> Option<(usize, Option<usize>)> {
+    let names: Vec<String> = cells.iter().map(|cell| cell.to_lowercase()).collect();
+    let address = names.iter().position(|name| name.contains("address"))?;
+    let label = LABEL_COLUMNS
+        .iter()
+        .find_map(|column| names.iter().position(|name| name == column));
+
+    Some((address, label))
+}
+
+/*
+
+Etherscan exports a watch list or private name tags as "Address","Private
+Name Tag",... and Nansen as Address,Label,... with other columns in between,
+so the columns are found by their header. A file without a header is read as
+address and label in the first two columns. Every wallet of the file that
+isn't already watched is added, rows that can't be added are reported with
+their line so they can be fixed and imported again.
+
+*/
+pub fn parse(csv: &str, watched: &[String]) -> Result<ImportSummary, ImportError> {
+    let mut rows = csv
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+
+    let (_, first) = rows.peek().copied().ok_or(ImportError::Empty)?;
+    let first = split_row(first);
+    let (address_column, label_column) =
+        if first.iter().any(|cell| utils::is_valid_eth_address(cell)) {
+            (0, Some(1))
+        } else {
+            rows.next();
+            header_columns(&first).ok_or(ImportError::NoAddressColumn)?
+        };
+
+    let rows: Vec<(usize, &str)> = rows.collect();
+    if rows.is_empty() {
+        return Err(ImportError::Empty);
+    }
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(ImportError::TooManyRows);
+    }
+
+    let mut seen: HashSet<String> = watched.iter().map(|w| w.to_lowercase()).collect();
+    let mut summary = ImportSummary::default();
+
+    for (line, row) in rows {
+        let cells = split_row(row);
+        let address = match cells.get(address_column) {
+            Some(address) if utils::is_valid_eth_address(address) => address.clone(),
+            Some(cell) if !cell.is_empty() => {
+                let cell: String = cell.chars().take(MAX_LABEL_CHARS).collect();
+                summary
+                    .invalid
+                    .push((line, format!("{} is not an address", cell)));
+                continue;
+            }
+            _ => {
+                summary.invalid.push((line, String::from("no address")));
+                continue;
+            }
+        };
+
+        if !seen.insert(address.to_lowercase()) {
+            summary.duplicates.push(address);
+            continue;
+        }
+
+        let label = label_column
+            .and_then(|column| cells.get(column))
+            .map(|label| label.chars().take(MAX_LABEL_CHARS).collect::<String>())
+            .filter(|label| !label.is_empty());
+        summary.added.push((address, label));
+    }
+
+    Ok(summary)
+}
+
+#[test]
+fn test_wallet_import() {
+    let alice = "0x28c6c06298d514db089934071355e5743bf21d60";
+    let bob = "0x21a31ee1afc51d94c2efccaa2092ad1028285549";
+    let carol = "0xdfd5293d8e347dfe59e90efd55b2956a1343963d";
+
+    let etherscan = format!(
+        "\u{feff}\"Address\",\"Private Name Tag\",\"Note\"\n\
+         \"{}\",\"Smart money, early\",\"\"\n\
+         \"{}\",\"\",\"\"\n\
+         \"0x123\",\"broken\",\"\"\n\
+         \n\
+         \"{}\",\"again\",\"\"\n\
+         \"{}\",\"watched\",\"\"",
+        alice, bob, "0x28C6c06298d514Db089934071355E5743bf21d60", carol
+    );
+    let summary = parse(&etherscan, &[carol.to_uppercase().replace("0X", "0x")]).unwrap();
+    assert_eq!(
+        summary.added,
+        vec![
+            (alice.to_owned(), Some(String::from("Smart money, early"))),
+            (bob.to_owned(), None)
+        ]
+    );
+    assert_eq!(summary.duplicates.len(), 2);
+    assert_eq!(
+        summary.invalid,
+        vec![(4, String::from("0x123 is not an address"))]
+    );
+    assert!(summary
+        .to_string()
+        .starts_with("📥 Wallet import\n✅ 2 added\n🔁 2 duplicate\n❌ 1 invalid"));
+
+    // nansen puts the label after other columns, files without a header are address, label
+    let nansen = format!("Chain,Label,Wallet Address\nethereum,Fund A,{}\n", bob);
+    assert_eq!(
+        parse(&nansen, &[]).unwrap().added,
+        vec![(bob.to_owned(), Some(String::from("Fund A")))]
+    );
+    let plain = format!("{},whale\n{}", alice, bob);
+    assert_eq!(parse(&plain, &[]).unwrap().added.len(), 2);
+
+    assert_eq!(
+        parse("Name,Chain\nwhale,ethereum", &[]),
+        Err(ImportError::NoAddressColumn)
+    );
+    assert_eq!(parse("Address,Label\n", &[]), Err(ImportError::Empty));
+}
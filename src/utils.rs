@@ -1,4 +1,7 @@
+use core::fmt;
 use keccak_rust::{Keccak, SecurityLevel, StateBitsWidth};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use teloxide::utils::html;
 
 pub fn hex_to_decimal(hex: &str) -> u128 {
@@ -6,6 +9,28 @@ pub fn hex_to_decimal(hex: &str) -> u128 {
     u128::from_str_radix(rm_prefix, 16).unwrap()
 }
 
+// decodes a 32 byte abi word, values that don't fit into u128 are saturated
+pub fn uint_from_word(word: &str) -> u128 {
+    let trimmed = word.trim_start_matches("0x").trim_start_matches('0');
+
+    if trimmed.is_empty() {
+        return 0;
+    }
+
+    u128::from_str_radix(trimmed, 16).unwrap_or(u128::MAX)
+}
+
+// addresses are stored right-aligned in a 32 byte word, an all zero word means no address
+pub fn address_from_word(word: &str) -> Option<String> {
+    let word = word.trim_start_matches("0x");
+
+    if word.len() != 64 || word.chars().all(|c| c == '0') {
+        return None;
+    }
+
+    Some(format!("0x{}", word[24..].to_lowercase()))
+}
+
 pub fn to_eth(hex: &str) -> f64 {
     let wei = hex_to_decimal(hex);
     let eth: f64 = wei as f64 / 10.0f64.powf(18.0);
@@ -36,54 +61,217 @@ pub fn is_valid_eth_address(address: &str) -> bool {
 }
 
 fn eth_address_checksum(address: &str) -> bool {
-    let lowercase_address = address.to_lowercase();
+    address.chars().all(|char| char.is_ascii_hexdigit())
+        && to_checksum_address(address).trim_start_matches("0x") == address
+}
+
+// EIP-55 mixed case address, some APIs reject lowercase ones
+pub fn to_checksum_address(address: &str) -> String {
+    let lowercase_address = address.trim_start_matches("0x").to_lowercase();
 
     let mut bytes = lowercase_address.as_bytes();
     let mut keccak = Keccak::new(SecurityLevel::SHA256, StateBitsWidth::F1600);
     keccak.append(&mut bytes);
     let hash_bytes = keccak.hash();
 
-    let hash = hash_bytes
-        .iter()
-        .map(|b| format!("{:#04x}", b).trim_start_matches("0x").to_owned())
-        .collect::<Vec<_>>()
-        .join("");
-
-    let mut checksum = String::from("");
-    for (i, char) in lowercase_address.chars().enumerate() {
-        if "0123456789".contains(char) {
-            checksum.push(char);
-        } else if "abcdef".contains(char) {
-            if hash.chars().nth(i).unwrap().to_digit(16).unwrap() > 7 {
-                checksum.push_str(&char.to_uppercase().to_string());
+    let checksum: String = lowercase_address
+        .chars()
+        .enumerate()
+        .map(|(i, char)| {
+            let nibble = (hash_bytes[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+            if char.is_ascii_alphabetic() && nibble > 7 {
+                char.to_ascii_uppercase()
             } else {
-                checksum.push(char);
+                char
             }
-        } else {
-            return false;
+        })
+        .collect();
+
+    format!("0x{}", checksum)
+}
+
+pub fn is_valid_tx_hash(hash: &str) -> bool {
+    hash.starts_with("0x")
+        && hash.len() == 66
+        && hash
+            .trim_start_matches("0x")
+            .chars()
+            .all(|c| c.is_ascii_hexdigit())
+}
+
+// every link the bot sends is built from these templates, {chain} is the site's name for the chain
+const EXPLORER_TX_URL: &str = "https://{explorer}/tx/{hash}";
+const EXPLORER_ADDRESS_URL: &str = "https://{explorer}/address/{address}";
+const DEXSCREENER_URL: &str = "https://dexscreener.com/{chain}/{address}";
+const DEXTOOLS_URL: &str = "https://www.dextools.io/app/en/{chain}/pair-explorer/{address}";
+const DEFINED_URL: &str = "https://www.defined.fi/{chain}/{address}";
+const UNISWAP_URL: &str = "https://app.uniswap.org/swap?outputCurrency={address}&chain={chain}";
+
+// chains the links are built for, honeypot.is can simulate trades on all of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Chain {
+    #[default]
+    Ethereum,
+    Bsc,
+    Base,
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Chain::Ethereum => write!(f, "Ethereum"),
+            Chain::Bsc => write!(f, "BSC"),
+            Chain::Base => write!(f, "Base"),
+        }
+    }
+}
+
+impl Chain {
+    pub fn chain_id(&self) -> u64 {
+        match *self {
+            Chain::Ethereum => 1,
+            Chain::Bsc => 56,
+            Chain::Base => 8453,
+        }
+    }
+
+    pub fn from_chain_id(chain_id: u64) -> Option<Chain> {
+        match chain_id {
+            1 => Some(Chain::Ethereum),
+            56 => Some(Chain::Bsc),
+            8453 => Some(Chain::Base),
+            _ => None,
+        }
+    }
+
+    fn explorer(&self) -> &'static str {
+        match self {
+            Chain::Ethereum => "etherscan.io",
+            Chain::Bsc => "bscscan.com",
+            Chain::Base => "basescan.org",
+        }
+    }
+
+    // the sites don't agree on chain names
+    fn slug(&self, site: ChartSite) -> &'static str {
+        match (self, site) {
+            (Chain::Ethereum, ChartSite::DexScreener) => "ethereum",
+            (Chain::Ethereum, ChartSite::DexTools) => "ether",
+            (Chain::Ethereum, ChartSite::Defined) => "eth",
+            (Chain::Bsc, ChartSite::DexTools) => "bnb",
+            (Chain::Bsc, _) => "bsc",
+            (Chain::Base, _) => "base",
+        }
+    }
+
+    fn uniswap_slug(&self) -> &'static str {
+        match self {
+            Chain::Ethereum => "ethereum",
+            Chain::Bsc => "bnb",
+            Chain::Base => "base",
         }
     }
+}
 
-    return address == checksum;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ChartSite {
+    #[default]
+    DexScreener,
+    DexTools,
+    Defined,
 }
 
-pub fn hyperlinks_from_contract(address: &str) -> String {
+impl fmt::Display for ChartSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ChartSite::DexScreener => write!(f, "DexScreener"),
+            ChartSite::DexTools => write!(f, "DEXTools"),
+            ChartSite::Defined => write!(f, "Defined"),
+        }
+    }
+}
+
+impl FromStr for ChartSite {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dexscreener" => Ok(ChartSite::DexScreener),
+            "dextools" => Ok(ChartSite::DexTools),
+            "defined" => Ok(ChartSite::Defined),
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn explorer_tx_url(chain: Chain, hash: &str) -> String {
+    EXPLORER_TX_URL
+        .replace("{explorer}", chain.explorer())
+        .replace("{hash}", hash)
+}
+
+pub fn explorer_address_url(chain: Chain, address: &str) -> String {
+    EXPLORER_ADDRESS_URL
+        .replace("{explorer}", chain.explorer())
+        .replace("{address}", address)
+}
+
+pub fn chart_url(site: ChartSite, chain: Chain, address: &str) -> String {
+    let template = match site {
+        ChartSite::DexScreener => DEXSCREENER_URL,
+        ChartSite::DexTools => DEXTOOLS_URL,
+        ChartSite::Defined => DEFINED_URL,
+    };
+
+    template
+        .replace("{chain}", chain.slug(site))
+        .replace("{address}", address)
+}
+
+pub fn swap_url(chain: Chain, address: &str) -> String {
+    UNISWAP_URL
+        .replace("{chain}", chain.uniswap_slug())
+        .replace("{address}", address)
+}
+
+pub fn tx_link(chain: Chain, hash: &str, text: &str) -> String {
+    html::link(&explorer_tx_url(chain, hash), text)
+}
+
+pub fn hyperlinks_from_contract(address: &str, chain: Chain, chart_site: ChartSite) -> String {
     format!(
         "{} | {}",
-        html::link(
-            &format!("https://dexscreener.com/ethereum/{}", address),
-            "Chart"
-        ),
-        html::link(
-            &format!(
-                "https://app.uniswap.org/swap?outputCurrency={}&chain=ethereum",
-                address
-            ),
-            "Swap"
-        )
+        html::link(&chart_url(chart_site, chain, address), "Chart"),
+        html::link(&swap_url(chain, address), "Swap")
     )
 }
 
+// pads every column to its widest cell, meant to be sent inside a <pre> block
+pub fn format_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .map(|(i, cell)| format!("{:<width$}", cell, width = widths[i]))
+                .collect::<Vec<_>>()
+                .join(" | ")
+                .trim_end()
+                .to_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[test]
 fn test_is_valid_eth_address() {
     let valid_address = "0x11DDACb10c3891e356dcE6D7c6F22DD69c93E2Cd";
@@ -91,4 +279,75 @@ fn test_is_valid_eth_address() {
 
     assert_eq!(is_valid_eth_address(valid_address), true);
     assert_eq!(is_valid_eth_address(invalid_address), false);
+    assert_eq!(
+        to_checksum_address(&valid_address.to_lowercase()),
+        valid_address
+    );
+}
+
+#[test]
+fn test_format_table() {
+    let rows = vec![
+        vec![
+            String::from(""),
+            String::from("PEPE"),
+            String::from("WOJAK"),
+        ],
+        vec![
+            String::from("Tax"),
+            String::from("0/0%"),
+            String::from("5/5%"),
+        ],
+    ];
+
+    assert_eq!(format_table(&rows), "    | PEPE | WOJAK\nTax | 0/0% | 5/5%");
+}
+
+#[test]
+fn test_links() {
+    let address = "0x6982508145454ce325ddbe47a25d4ec3d2311933";
+
+    assert_eq!(
+        chart_url(ChartSite::DexScreener, Chain::Ethereum, address),
+        format!("https://dexscreener.com/ethereum/{}", address)
+    );
+    assert_eq!(
+        chart_url(ChartSite::DexTools, Chain::Bsc, address),
+        format!(
+            "https://www.dextools.io/app/en/bnb/pair-explorer/{}",
+            address
+        )
+    );
+    assert_eq!(
+        swap_url(Chain::Ethereum, address),
+        format!(
+            "https://app.uniswap.org/swap?outputCurrency={}&chain=ethereum",
+            address
+        )
+    );
+    assert_eq!(
+        explorer_address_url(Chain::Base, address),
+        format!("https://basescan.org/address/{}", address)
+    );
+}
+
+#[test]
+fn test_address_from_word() {
+    assert_eq!(
+        address_from_word("0x000000000000000000000000C02AAA39B223FE8D0A0E5C4F27EAD9083C756CC2"),
+        Some(String::from("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"))
+    );
+    assert_eq!(
+        address_from_word("0x0000000000000000000000000000000000000000000000000000000000000000"),
+        None
+    );
+    assert_eq!(address_from_word("0x"), None);
+}
+
+#[test]
+fn test_chain_ids() {
+    for chain in [Chain::Ethereum, Chain::Bsc, Chain::Base] {
+        assert_eq!(Chain::from_chain_id(chain.chain_id()), Some(chain));
+    }
+    assert_eq!(Chain::from_chain_id(137), None);
 }
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{TestBot, WALLET};
+use serde_json::json;
+use snipers::{api::LaunchMetrics, telegram::bot::SETTINGS};
+use teloxide::types::ChatId;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+#[tokio::test]
+async fn test_scan_invalid_contract() {
+    let bot = TestBot::start(1001).await;
+
+    bot.send_text("/scan 0x1234").await;
+
+    assert_eq!(
+        bot.sent_messages().await,
+        vec!["...", "The submitted contract is not valid!"]
+    );
+    assert!(bot.api_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_scan_provider_unavailable() {
+    let bot = TestBot::start(1002).await;
+    let contract = "0x2222222222222222222222222222222222222222";
+
+    bot.send_text(&format!("/scan {}", contract)).await;
+
+    // honeypot.is is retried before giving up
+    let honeypot_requests = bot
+        .api_requests()
+        .await
+        .into_iter()
+        .filter(|request| request.starts_with("/IsHoneypot") && request.contains(contract))
+        .count();
+    assert_eq!(honeypot_requests, 3);
+    assert_eq!(
+        bot.sent_messages().await.last().unwrap(),
+        "Something went wrong, please try again later"
+    );
+}
+
+#[tokio::test]
+async fn test_portfolio() {
+    let bot = TestBot::start(1003).await;
+    Mock::given(method("GET"))
+        .and(path(format!("/wallets/{}/tokens", WALLET)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "cursor": null,
+            "page": 0,
+            "page_size": 100,
+            "result": [{
+                "token_address": "0x6982508145454ce325ddbe47a25d4ec3d2311933",
+                "symbol": "PEPE",
+                "name": "Pepe",
+                "logo": null,
+                "thumbnail": null,
+                "decimals": 18,
+                "balance": "1500000000000000000000",
+                "possible_spam": false,
+                "verified_contract": true,
+                "balance_formatted": "1500",
+                "usd_price": 0.01,
+                "usd_price_24hr_percent_change": 4.5,
+                "usd_price_24hr_usd_change": 0.0,
+                "usd_value": 15.0,
+                "usd_value_24hr_usd_change": 0.0,
+                "total_supply": null,
+                "total_supply_formatted": null,
+                "percentage_relative_to_total_supply": null,
+                "native_token": false,
+                "portfolio_percentage": 100.0
+            }]
+        })))
+        .mount(&bot.api)
+        .await;
+
+    bot.send_text("/portfolio").await;
+
+    let portfolio = bot.sent_messages().await.last().unwrap().clone();
+    assert!(portfolio.starts_with("Portfolio:"));
+    assert!(portfolio.contains("💎 Pepe (PEPE)"));
+    assert!(portfolio.contains("💰 1,500.00 ($15.00)"));
+    assert!(portfolio.contains("📈 +4.50%"));
+}
+
+#[tokio::test]
+async fn test_declined_trade() {
+    let bot = TestBot::start(1004).await;
+    let contract = "0x3333333333333333333333333333333333333333";
+
+    bot.send_text(&format!("/sell {} 100 5", contract)).await;
+    bot.press_button("no").await;
+
+    let messages = bot.sent_messages().await;
+    assert!(messages[0].starts_with("🪙 Token: 0x3333...3333"));
+    assert!(messages[0].contains(&format!("📄 Contract: {}", contract)));
+    assert_eq!(messages[1], "Do you want to execute the transaction?");
+    assert_eq!(messages[2], "Transaction was not executed!");
+}
+
+#[tokio::test]
+async fn test_sniper_filter() {
+    let bot = TestBot::start(1005).await;
+
+    bot.send_text("/sniperfilter minliq=5 maxfdv=1000000").await;
+    bot.send_text("/sniperfilter minliq=lots").await;
+
+    assert_eq!(
+        bot.sent_messages().await,
+        vec![
+            "New token alerts: liquidity above 5 ETH, FDV below $1000000",
+            "Sniper filter cancelled: minliq=lots is not a valid criterion!",
+        ]
+    );
+
+    // the rejected filter didn't replace the first one
+    let filter = SETTINGS.get(ChatId(1005)).await.sniper_filter;
+    let launch = |liquidity_eth: f64, fdv_usd: f64| LaunchMetrics {
+        liquidity_eth,
+        liquidity_usd: liquidity_eth * 3000.0,
+        fdv_usd,
+    };
+    assert!(filter.allows(&launch(10.0, 500_000.0)));
+    assert!(!filter.allows(&launch(2.0, 500_000.0)));
+    assert!(!filter.allows(&launch(10.0, 5_000_000.0)));
+}
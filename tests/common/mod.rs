@@ -0,0 +1,184 @@
+use lazy_static::lazy_static;
+use serde_json::{json, Value};
+use snipers::{persist, telegram::bot::BotHandlers};
+use std::{
+    env,
+    sync::atomic::{AtomicI32, Ordering},
+};
+use teloxide::{types::Update, Bot};
+use tokio::sync::{Mutex, MutexGuard};
+use wiremock::{
+    matchers::{method, path_regex},
+    Mock, MockServer, ResponseTemplate,
+};
+
+// the owner, so every command is allowed without setting up permissions
+pub const USER_ID: i64 = 42;
+pub const WALLET: &str = "0x1111111111111111111111111111111111111111";
+const PROVIDERS: [&str; 5] = ["ETHERSCAN", "MORALIS", "ALCHEMY", "CHAINBASE", "HONEYPOT"];
+
+lazy_static! {
+    // provider urls and keys are read from the environment, so the tests take turns
+    static ref ENVIRONMENT: Mutex<()> = Mutex::new(());
+}
+
+// a bot talking to a mocked telegram, with every provider pointed at one mock server
+// requests no mock matches get a 404, the same as an unavailable provider
+pub struct TestBot {
+    pub api: MockServer,
+    telegram: MockServer,
+    bot: Bot,
+    handlers: BotHandlers,
+    chat_id: i64,
+    update_id: AtomicI32,
+    _environment: MutexGuard<'static, ()>,
+}
+
+impl TestBot {
+    // chats keep their settings between tests, so every test uses its own chat
+    pub async fn start(chat_id: i64) -> TestBot {
+        let environment = ENVIRONMENT.lock().await;
+        let api = MockServer::start().await;
+        let telegram = MockServer::start().await;
+
+        for provider in PROVIDERS {
+            env::set_var(format!("{}_BASE_URL", provider), api.uri());
+            env::set_var(format!("{}_API", provider), "test");
+        }
+        env::set_var("OWNER_ID", USER_ID.to_string());
+        env::set_var("ETH_ADDRESS", WALLET);
+        // every state file goes to the temp dir, running the tests leaves the repo untouched
+        for file in persist::STATE_FILES {
+            env::set_var(
+                file.env_var,
+                env::temp_dir().join(format!("snipers_test_{}", file.default)),
+            );
+        }
+
+        mount_telegram(&telegram).await;
+        let bot = Bot::new("1234:TEST").set_api_url(reqwest::Url::parse(&telegram.uri()).unwrap());
+
+        TestBot {
+            api,
+            telegram,
+            bot,
+            handlers: BotHandlers::default(),
+            chat_id,
+            update_id: AtomicI32::new(1),
+            _environment: environment,
+        }
+    }
+
+    pub async fn send_text(&self, text: &str) {
+        let update_id = self.update_id.fetch_add(1, Ordering::SeqCst);
+        self.handle(json!({
+            "update_id": update_id,
+            "message": self.message(update_id, text),
+        }))
+        .await;
+    }
+
+    pub async fn press_button(&self, data: &str) {
+        let update_id = self.update_id.fetch_add(1, Ordering::SeqCst);
+        self.handle(json!({
+            "update_id": update_id,
+            "callback_query": {
+                "id": update_id.to_string(),
+                "from": user(),
+                "message": self.message(update_id, "prompt"),
+                "chat_instance": "1",
+                "data": data,
+            },
+        }))
+        .await;
+    }
+
+    // texts of the messages the bot sent, oldest first
+    pub async fn sent_messages(&self) -> Vec<String> {
+        self.telegram
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|request| request.url.path().to_lowercase().ends_with("/sendmessage"))
+            .filter_map(|request| request.body_json::<Value>().ok())
+            .filter_map(|body| body["text"].as_str().map(String::from))
+            .collect()
+    }
+
+    // requests the bot made to the providers, as paths with their query
+    pub async fn api_requests(&self) -> Vec<String> {
+        self.api
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|request| match request.url.query() {
+                Some(query) => format!("{}?{}", request.url.path(), query),
+                None => request.url.path().to_owned(),
+            })
+            .collect()
+    }
+
+    fn message(&self, message_id: i32, text: &str) -> Value {
+        json!({
+            "message_id": message_id,
+            "date": 1700000000,
+            "chat": { "id": self.chat_id, "type": "private", "first_name": "Tester" },
+            "from": user(),
+            "text": text,
+        })
+    }
+
+    async fn handle(&self, update: Value) {
+        let update: Update = serde_json::from_value(update).unwrap();
+        self.handlers
+            .handle(self.bot.clone(), update)
+            .await
+            .unwrap();
+    }
+}
+
+fn user() -> Value {
+    json!({ "id": USER_ID, "is_bot": false, "first_name": "Tester" })
+}
+
+fn telegram_ok(result: Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "ok": true, "result": result }))
+}
+
+async fn mount_telegram(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path_regex("(?i)/getme$"))
+        .respond_with(telegram_ok(json!({
+            "id": 1,
+            "is_bot": true,
+            "first_name": "Snipers",
+            "username": "snipers_bot",
+            "can_join_groups": true,
+            "can_read_all_group_messages": false,
+            "supports_inline_queries": false,
+        })))
+        .mount(server)
+        .await;
+
+    // the handlers only use the id of the messages they send
+    Mock::given(method("POST"))
+        .and(path_regex("(?i)/(sendmessage|sendphoto|editmessagetext)$"))
+        .respond_with(telegram_ok(json!({
+            "message_id": 1000,
+            "date": 1700000000,
+            "chat": { "id": USER_ID, "type": "private", "first_name": "Tester" },
+            "text": "sent",
+        })))
+        .mount(server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(
+            "(?i)/(deletemessage|answercallbackquery|editmessagereplymarkup|setmycommands)$",
+        ))
+        .respond_with(telegram_ok(json!(true)))
+        .mount(server)
+        .await;
+}